@@ -0,0 +1,274 @@
+#include "ascii.h"
+#include <algorithm>
+#include <cmath>
+#include <fstream>
+#include <unordered_map>
+
+namespace Ascii {
+
+// Settings that dither the character grid to palette, with the full image's
+// spatial and color mode options turned off
+static Dithering::Parameters gridParameters(const Dithering::Parameters& params,
+                                            const std::vector<cv::Vec3b>& palette) {
+    Dithering::Parameters grid = params;
+    grid.paletteMode = Dithering::PaletteMode::CUSTOM;
+    grid.customPalette = palette;
+    grid.protectedColors.clear();
+    grid.colorMode = Dithering::ColorMode::RGB;
+    for (auto& channel : grid.channelOverrides) channel.enabled = false;
+    grid.blurRadius = 0.0f;
+    grid.sharpenAmount = 0.0f;
+    grid.pixelate = 1;
+    grid.blend = 0.0f;
+    return grid;
+}
+
+// Braille dot bits by position in the 2x4 cell: dots 1-3 and 7 down the left
+// column, 4-6 and 8 down the right
+static const int BRAILLE_BITS[4][2] = {{0x01, 0x08}, {0x02, 0x10}, {0x04, 0x20}, {0x40, 0x80}};
+
+// UTF-8 for U+2800 + bits
+static std::string brailleCharacter(int bits) {
+    std::string character(3, '\0');
+    character[0] = static_cast<char>(0xE2);
+    character[1] = static_cast<char>(0xA0 | (bits >> 6));
+    character[2] = static_cast<char>(0x80 | (bits & 0x3F));
+    return character;
+}
+
+std::vector<std::string> convertToText(const cv::Mat& input, const Dithering::Parameters& params,
+                                       const Options& options) {
+    std::vector<std::string> lines;
+    if (input.empty() || (!options.braille && options.ramp.size() < 2)) return lines;
+
+    int columns = std::max(1, options.columns);
+    int rows = std::max(1, static_cast<int>(std::lround(
+        static_cast<double>(input.rows) * columns / input.cols / std::max(0.1f, options.charAspect))));
+
+    if (options.braille) {
+        cv::Mat dots;
+        cv::resize(input, dots, cv::Size(columns * 2, rows * 4), 0.0, 0.0, cv::INTER_AREA);
+        std::vector<cv::Vec3b> palette = {cv::Vec3b(0, 0, 0), cv::Vec3b(255, 255, 255)};
+        cv::Mat dithered = Dithering::ditherImage(dots, gridParameters(params, palette));
+        for (int row = 0; row < rows; ++row) {
+            std::string line;
+            for (int column = 0; column < columns; ++column) {
+                int bits = 0;
+                for (int y = 0; y < 4; ++y) {
+                    const cv::Vec3b* pixels = dithered.ptr<cv::Vec3b>(row * 4 + y);
+                    for (int x = 0; x < 2; ++x) {
+                        bool ink = pixels[column * 2 + x][0] < 128;
+                        if (ink != options.invert) bits |= BRAILLE_BITS[y][x];
+                    }
+                }
+                line += brailleCharacter(bits);
+            }
+            lines.push_back(line);
+        }
+        return lines;
+    }
+
+    cv::Mat blocks;
+    cv::resize(input, blocks, cv::Size(columns, rows), 0.0, 0.0, cv::INTER_AREA);
+
+    // One gray per character; ramp position 0 is paper (white, or black inverted)
+    const int count = static_cast<int>(options.ramp.size());
+    std::vector<cv::Vec3b> palette;
+    for (int i = 0; i < count; ++i) {
+        int level = 255 * i / (count - 1);
+        uchar gray = static_cast<uchar>(options.invert ? level : 255 - level);
+        palette.push_back(cv::Vec3b(gray, gray, gray));
+    }
+
+    cv::Mat dithered = Dithering::ditherImage(blocks, gridParameters(params, palette));
+    for (int y = 0; y < dithered.rows; ++y) {
+        std::string line;
+        const cv::Vec3b* row = dithered.ptr<cv::Vec3b>(y);
+        for (int x = 0; x < dithered.cols; ++x) {
+            int level = options.invert ? row[x][0] : 255 - row[x][0];
+            line += options.ramp[std::clamp((level * (count - 1) + 127) / 255, 0, count - 1)];
+        }
+        // Trailing spaces only make the file bigger
+        line.erase(line.find_last_not_of(' ') + 1);
+        lines.push_back(line);
+    }
+    return lines;
+}
+
+cv::Mat renderText(const std::vector<std::string>& lines, const Options& options,
+                   const cv::Vec3b& ink, const cv::Vec3b& paper) {
+    // Braille characters are three bytes of UTF-8
+    const int bytesPerCharacter = options.braille ? 3 : 1;
+    int columns = 0;
+    for (const auto& line : lines) {
+        columns = std::max(columns, static_cast<int>(line.size()) / bytesPerCharacter);
+    }
+    if (columns == 0 || lines.empty()) return cv::Mat();
+
+    const int cellWidth = std::max(2, options.cellWidth);
+    const int cellHeight = std::max(2, static_cast<int>(std::lround(cellWidth * options.charAspect)));
+    const cv::Vec3b& background = options.invert ? ink : paper;
+    const cv::Vec3b& foreground = options.invert ? paper : ink;
+    cv::Mat image(static_cast<int>(lines.size()) * cellHeight, columns * cellWidth, CV_8UC3,
+                  cv::Scalar(background[0], background[1], background[2]));
+
+    cv::Scalar color(foreground[0], foreground[1], foreground[2]);
+    if (options.braille) {
+        int radius = std::max(1, static_cast<int>(std::min(cellWidth / 4.0, cellHeight / 8.0) * 0.8));
+        for (size_t row = 0; row < lines.size(); ++row) {
+            const std::string& line = lines[row];
+            for (size_t column = 0; column < line.size() / 3; ++column) {
+                int bits = ((line[column * 3 + 1] & 0x03) << 6) | (line[column * 3 + 2] & 0x3F);
+                for (int y = 0; y < 4; ++y) {
+                    for (int x = 0; x < 2; ++x) {
+                        if (!(bits & BRAILLE_BITS[y][x])) continue;
+                        cv::Point center(static_cast<int>(column) * cellWidth + cellWidth * (1 + 2 * x) / 4,
+                                         static_cast<int>(row) * cellHeight + cellHeight * (1 + 2 * y) / 8);
+                        cv::circle(image, center, radius, color, cv::FILLED, cv::LINE_AA);
+                    }
+                }
+            }
+        }
+        return image;
+    }
+
+    // Scale the font so capitals fill about 70% of the cell height, or the width
+    const int font = cv::FONT_HERSHEY_PLAIN;
+    int baseline = 0;
+    cv::Size unit = cv::getTextSize("M", font, 1.0, 1, &baseline);
+    double fontScale = std::min(cellHeight * 0.7 / unit.height, cellWidth * 0.9 / unit.width);
+    int thickness = std::max(1, static_cast<int>(std::lround(fontScale * 0.8)));
+
+    // Each character centered in its own cell, so the grid stays monospaced
+    for (size_t row = 0; row < lines.size(); ++row) {
+        for (size_t column = 0; column < lines[row].size(); ++column) {
+            std::string character(1, lines[row][column]);
+            if (character == " ") continue;
+            cv::Size size = cv::getTextSize(character, font, fontScale, thickness, &baseline);
+            cv::Point origin(static_cast<int>(column) * cellWidth + (cellWidth - size.width) / 2,
+                             static_cast<int>(row) * cellHeight + (cellHeight + size.height) / 2);
+            cv::putText(image, character, origin, font, fontScale, color, thickness, cv::LINE_AA);
+        }
+    }
+    return image;
+}
+
+const std::vector<std::string>& getAnsiColorIds() {
+    static const std::vector<std::string> ids = {"16", "256", "truecolor"};
+    return ids;
+}
+
+bool parseAnsiColors(const std::string& id, AnsiColors& colors) {
+    const auto& ids = getAnsiColorIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    colors = static_cast<AnsiColors>(match - ids.begin());
+    return true;
+}
+
+std::string getAnsiColorName(AnsiColors colors) {
+    switch (colors) {
+        case AnsiColors::ANSI16: return "16 Colors";
+        case AnsiColors::XTERM256: return "256 Colors";
+        case AnsiColors::TRUECOLOR: return "Truecolor";
+        default: return "Unknown";
+    }
+}
+
+// Colors to match against (BGR). 16 colors are xterm's defaults, in SGR index
+// order. 256 colors uses only the 6x6x6 cube and 24 grays (indexes 16-255):
+// terminal themes change the first 16, but not these
+static std::vector<cv::Vec3b> terminalPalette(AnsiColors colors) {
+    std::vector<cv::Vec3b> palette;
+    if (colors == AnsiColors::ANSI16) {
+        static const unsigned int standard[16] = {
+            0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+            0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff};
+        for (unsigned int rgb : standard) {
+            palette.push_back(cv::Vec3b(rgb & 0xff, (rgb >> 8) & 0xff, (rgb >> 16) & 0xff));
+        }
+        return palette;
+    }
+
+    const uchar levels[6] = {0, 95, 135, 175, 215, 255};
+    for (int r = 0; r < 6; ++r) {
+        for (int g = 0; g < 6; ++g) {
+            for (int b = 0; b < 6; ++b) palette.push_back(cv::Vec3b(levels[b], levels[g], levels[r]));
+        }
+    }
+    for (int i = 0; i < 24; ++i) {
+        uchar gray = static_cast<uchar>(8 + 10 * i);
+        palette.push_back(cv::Vec3b(gray, gray, gray));
+    }
+    return palette;
+}
+
+// SGR parameters selecting a color as foreground or background
+static std::string colorCode(const cv::Vec3b& color, bool background, AnsiColors colors,
+                             const std::vector<cv::Vec3b>& palette,
+                             std::unordered_map<unsigned int, int>& matches) {
+    if (colors == AnsiColors::TRUECOLOR) {
+        return (background ? "48;2;" : "38;2;") + std::to_string(color[2]) + ";" + std::to_string(color[1]) +
+               ";" + std::to_string(color[0]);
+    }
+
+    // Dithered results have few colors, so each is matched once
+    unsigned int key = (color[2] << 16) | (color[1] << 8) | color[0];
+    auto found = matches.find(key);
+    if (found == matches.end()) {
+        cv::Vec3b closest = Dithering::findClosestColor(color, palette);
+        int index = static_cast<int>(std::find(palette.begin(), palette.end(), closest) - palette.begin());
+        found = matches.emplace(key, index).first;
+    }
+    int index = found->second;
+    if (colors == AnsiColors::XTERM256) return (background ? "48;5;" : "38;5;") + std::to_string(16 + index);
+    int base = index < 8 ? (background ? 40 : 30) : (background ? 100 : 90);
+    return std::to_string(base + index % 8);
+}
+
+std::vector<std::string> convertToAnsi(const cv::Mat& image, int columns, AnsiColors colors) {
+    std::vector<std::string> lines;
+    if (image.empty()) return lines;
+
+    cv::Mat pixels = image;
+    if (columns > 0 && columns < image.cols) {
+        int rows = std::max(1, static_cast<int>(std::lround(static_cast<double>(image.rows) * columns / image.cols)));
+        cv::resize(image, pixels, cv::Size(columns, rows), 0.0, 0.0, cv::INTER_NEAREST);
+    }
+
+    std::vector<cv::Vec3b> palette = terminalPalette(colors);
+    std::unordered_map<unsigned int, int> matches;
+    const std::string upperHalf = "\xE2\x96\x80";
+    for (int y = 0; y < pixels.rows; y += 2) {
+        std::string line, foreground, background;
+        const cv::Vec3b* top = pixels.ptr<cv::Vec3b>(y);
+        const cv::Vec3b* bottom = y + 1 < pixels.rows ? pixels.ptr<cv::Vec3b>(y + 1) : nullptr;
+        for (int x = 0; x < pixels.cols; ++x) {
+            // Only changed colors are sent again
+            std::string codes;
+            std::string code = colorCode(top[x], false, colors, palette, matches);
+            if (code != foreground) codes += (codes.empty() ? "" : ";") + (foreground = code);
+            // An odd last row leaves the terminal's own background under the half blocks
+            code = bottom ? colorCode(bottom[x], true, colors, palette, matches) : "49";
+            if (code != background) codes += (codes.empty() ? "" : ";") + (background = code);
+            if (!codes.empty()) line += "\x1b[" + codes + "m";
+            line += upperHalf;
+        }
+        lines.push_back(line + "\x1b[0m");
+    }
+    return lines;
+}
+
+std::string joinLines(const std::vector<std::string>& lines) {
+    std::string text;
+    for (const auto& line : lines) text += line + "\n";
+    return text;
+}
+
+bool exportText(const std::vector<std::string>& lines, const std::string& path) {
+    std::ofstream file(path, std::ios::binary);
+    file << joinLines(lines);
+    return static_cast<bool>(file);
+}
+
+} // namespace Ascii
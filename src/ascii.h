@@ -0,0 +1,63 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Ascii {
+
+// Characters from lightest to densest
+const std::string DEFAULT_RAMP = " .:-=+*#%@";
+
+// ASCII art options
+struct Options {
+    int columns = 80;
+    std::string ramp = DEFAULT_RAMP;
+    bool invert = false;        // Dense characters for light areas, for light text on a dark background
+    float charAspect = 2.0f;    // Character cell height / width
+    int cellWidth = 8;          // Pixels per character in the rendered image
+    bool braille = false;       // Pack 2x4 dithered dots into each U+2800 braille character instead of the ramp
+};
+
+// One character per block of the input. The blocks are dithered to one gray
+// level per ramp character with the chosen algorithm, so shading between
+// characters is diffused or patterned like the image itself. In braille mode
+// the image is dithered to 1-bit at 2x4 pixels per character and each ink
+// pixel raises a dot; lines are UTF-8, and blank cells stay U+2800 so chat
+// apps don't collapse them. Spatial adjustments (blur, sharpen) are skipped;
+// they are sized for the full image
+std::vector<std::string> convertToText(const cv::Mat& input, const Dithering::Parameters& params,
+                                       const Options& options);
+
+// Text drawn on a grid of character cells, ink on paper (swapped when inverted).
+// Braille is drawn as round dots, since the built-in fonts have no braille
+cv::Mat renderText(const std::vector<std::string>& lines, const Options& options,
+                   const cv::Vec3b& ink, const cv::Vec3b& paper);
+
+// Terminal color support for ANSI export
+enum class AnsiColors {
+    ANSI16,         // The 16 standard colors (SGR 30-37, 90-97)
+    XTERM256,       // 6x6x6 color cube and gray ramp
+    TRUECOLOR       // 24-bit RGB
+};
+
+// ANSI color lookup by command line id (16, 256, truecolor)
+const std::vector<std::string>& getAnsiColorIds();
+bool parseAnsiColors(const std::string& id, AnsiColors& colors);
+std::string getAnsiColorName(AnsiColors colors);
+
+// A result as ANSI escape-coded text of upper half blocks (U+2580): each
+// character shows two pixels, the top in the foreground color and the bottom
+// in the background. Images wider than columns are scaled down with nearest
+// neighbor so dither patterns stay crisp (0 = one character per pixel).
+// Colors the terminal can't show are matched to its nearest palette entry
+std::vector<std::string> convertToAnsi(const cv::Mat& image, int columns, AnsiColors colors);
+
+// Lines joined with newlines, as written to a .txt file
+std::string joinLines(const std::vector<std::string>& lines);
+
+bool exportText(const std::vector<std::string>& lines, const std::string& path);
+
+} // namespace Ascii
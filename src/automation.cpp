@@ -0,0 +1,605 @@
+#include "automation.h"
+#include "batch.h"
+#include "compare.h"
+#include "errors.h"
+#include "imageio.h"
+#include "json.h"
+#include "platform.h"
+#include "plugins.h"
+#include "script.h"
+#include "settings.h"
+#include "stats.h"
+#include "video.h"
+#include <cctype>
+#include <chrono>
+#include <cstdio>
+#include <filesystem>
+#include <map>
+#include <random>
+#include <sstream>
+#include <vector>
+
+#ifdef _WIN32
+#include <winsock2.h>
+#include <ws2tcpip.h>
+using Socket = SOCKET;
+#else
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <sys/select.h>
+#include <sys/socket.h>
+#include <unistd.h>
+using Socket = int;
+#endif
+
+// A client that hangs up early mustn't end the program with SIGPIPE
+#ifdef MSG_NOSIGNAL
+static const int SEND_FLAGS = MSG_NOSIGNAL;
+#else
+static const int SEND_FLAGS = 0;
+#endif
+
+namespace Automation {
+
+// Requests larger than this are refused; settings documents and scripts are small
+static const size_t MAX_REQUEST_BYTES = 1 << 20;
+
+// A whole request must arrive within this, however slowly its bytes trickle in
+static const int REQUEST_TIMEOUT_SECONDS = 30;
+
+struct Request {
+    std::string method;
+    std::string path;
+    std::vector<std::pair<std::string, std::string>> query;    // In order, names may repeat
+    std::map<std::string, std::string> headers;                // Names in lowercase
+    std::string body;
+
+    std::string get(const std::string& name) const {
+        for (const auto& [key, value] : query) {
+            if (key == name) return value;
+        }
+        return "";
+    }
+};
+
+struct Response {
+    int status = 200;
+    std::string body;               // JSON
+};
+
+static void closeSocket(Socket socket) {
+#ifdef _WIN32
+    closesocket(socket);
+#else
+    close(socket);
+#endif
+}
+
+static Json jsonArray(const std::vector<std::string>& items) {
+    Json array = Json::array();
+    for (const auto& item : items) array.push(Json::of(item));
+    return array;
+}
+
+static Response errorResponse(int status, const Errors::Error& error) {
+    return {status, writeJson(Json::object()
+                                  .set("error", Json::of(Errors::getErrorCodeId(error.code)))
+                                  .set("message", Json::of(error.message)))};
+}
+
+static Response errorResponse(const Errors::Error& error) {
+    using Errors::ErrorCode;
+    switch (error.code) {
+        case ErrorCode::INVALID_ARGUMENT: return errorResponse(400, error);
+        case ErrorCode::NOT_FOUND: return errorResponse(404, error);
+        case ErrorCode::UNSUPPORTED_FORMAT: return errorResponse(415, error);
+        default: return errorResponse(500, error);
+    }
+}
+
+static const char* getStatusText(int status) {
+    switch (status) {
+        case 200: return "OK";
+        case 400: return "Bad Request";
+        case 401: return "Unauthorized";
+        case 404: return "Not Found";
+        case 405: return "Method Not Allowed";
+        case 408: return "Request Timeout";
+        case 409: return "Conflict";
+        case 411: return "Length Required";
+        case 413: return "Payload Too Large";
+        case 415: return "Unsupported Media Type";
+        case 501: return "Not Implemented";
+        default: return "Internal Server Error";
+    }
+}
+
+// %XX escapes and + for space, as in query strings
+static std::string urlDecode(const std::string& text) {
+    std::string decoded;
+    for (size_t i = 0; i < text.size(); ++i) {
+        if (text[i] == '+') {
+            decoded += ' ';
+        } else if (text[i] == '%' && i + 2 < text.size() && std::isxdigit(static_cast<unsigned char>(text[i + 1])) &&
+                   std::isxdigit(static_cast<unsigned char>(text[i + 2]))) {
+            decoded += static_cast<char>(std::stoi(text.substr(i + 1, 2), nullptr, 16));
+            i += 2;
+        } else {
+            decoded += text[i];
+        }
+    }
+    return decoded;
+}
+
+// Read one request: the head up to the blank line, then Content-Length bytes
+// of body. False if the connection closed early or the request is malformed;
+// status is set when it's worth answering
+static bool readRequest(Socket client, Request& request, int& status) {
+    std::string data;
+    size_t headEnd = std::string::npos;
+    size_t bodySize = 0;
+    char buffer[8192];
+    status = 400;
+    auto deadline = std::chrono::steady_clock::now() + std::chrono::seconds(REQUEST_TIMEOUT_SECONDS);
+
+    while (headEnd == std::string::npos || data.size() < headEnd + 4 + bodySize) {
+        if (std::chrono::steady_clock::now() > deadline) {
+            status = 408;
+            return false;
+        }
+        int received = recv(client, buffer, sizeof(buffer), 0);
+        if (received <= 0) {
+            status = data.empty() ? 0 : 408;    // Hung up, or stopped sending until the socket timed out
+            return false;
+        }
+        data.append(buffer, received);
+        if (data.size() > MAX_REQUEST_BYTES) {
+            status = 413;
+            return false;
+        }
+        if (headEnd != std::string::npos) continue;
+
+        headEnd = data.find("\r\n\r\n");
+        if (headEnd == std::string::npos) continue;
+        std::istringstream head(data.substr(0, headEnd));
+        std::string line, target;
+        std::getline(head, line);
+        std::istringstream requestLine(line);
+        if (!(requestLine >> request.method >> target)) return false;
+
+        while (std::getline(head, line)) {
+            size_t colon = line.find(':');
+            if (colon == std::string::npos) continue;
+            std::string name = line.substr(0, colon);
+            for (char& c : name) c = static_cast<char>(std::tolower(static_cast<unsigned char>(c)));
+            std::string value = line.substr(colon + 1);
+            value.erase(0, value.find_first_not_of(" \t"));
+            value.erase(value.find_last_not_of(" \t\r") + 1);
+            request.headers[name] = value;
+        }
+
+        // Bodies must come with a Content-Length; chunked uploads aren't read
+        auto encoding = request.headers.find("transfer-encoding");
+        if (encoding != request.headers.end()) {
+            std::string value = encoding->second;
+            for (char& c : value) c = static_cast<char>(std::tolower(static_cast<unsigned char>(c)));
+            status = value.find("chunked") != std::string::npos ? 411 : 501;
+            return false;
+        }
+        auto length = request.headers.find("content-length");
+        try {
+            bodySize = length == request.headers.end() ? 0 : std::stoul(length->second);
+        } catch (const std::exception&) {
+            return false;
+        }
+        if (bodySize > MAX_REQUEST_BYTES) {
+            status = 413;
+            return false;
+        }
+
+        // Clients such as curl wait for this before sending a larger body
+        auto expect = request.headers.find("expect");
+        if (expect != request.headers.end() && data.size() < headEnd + 4 + bodySize) {
+            std::string value = expect->second;
+            for (char& c : value) c = static_cast<char>(std::tolower(static_cast<unsigned char>(c)));
+            if (value == "100-continue") {
+                static const char continueLine[] = "HTTP/1.1 100 Continue\r\n\r\n";
+                send(client, continueLine, static_cast<int>(sizeof(continueLine) - 1), SEND_FLAGS);
+            }
+        }
+
+        size_t question = target.find('?');
+        request.path = target.substr(0, question);
+        if (question == std::string::npos) continue;
+        std::istringstream query(target.substr(question + 1));
+        std::string pair;
+        while (std::getline(query, pair, '&')) {
+            size_t equals = pair.find('=');
+            std::string name = urlDecode(pair.substr(0, equals));
+            std::string value = equals == std::string::npos ? "" : urlDecode(pair.substr(equals + 1));
+            if (!name.empty()) request.query.emplace_back(name, value);
+        }
+    }
+
+    request.body = data.substr(headEnd + 4, bodySize);
+    return true;
+}
+
+static void sendResponse(Socket client, const Response& response) {
+    std::string data = "HTTP/1.1 " + std::to_string(response.status) + " " + getStatusText(response.status) +
+                       "\r\nContent-Type: application/json\r\nContent-Length: " +
+                       std::to_string(response.body.size()) + "\r\nConnection: close\r\n\r\n" + response.body;
+    size_t sent = 0;
+    while (sent < data.size()) {
+        int count = send(client, data.data() + sent, static_cast<int>(data.size() - sent), SEND_FLAGS);
+        if (count <= 0) return;
+        sent += count;
+    }
+}
+
+// Compared in full whatever the input, so the time taken gives nothing away
+static bool tokenMatches(const std::string& given, const std::string& token) {
+    unsigned char difference = given.size() == token.size() ? 0 : 1;
+    for (size_t i = 0; i < given.size(); ++i) {
+        difference |= static_cast<unsigned char>(given[i] ^ token[i % token.size()]);
+    }
+    return difference == 0;
+}
+
+static std::string makeToken() {
+    std::random_device random;
+    std::string token;
+    const char* digits = "0123456789abcdef";
+    for (int i = 0; i < 32; ++i) token += digits[random() % 16];
+    return token;
+}
+
+// Settings for a request: its body, else the preset it names, else the defaults
+static bool readSettings(const Request& request, const Options& options, Dithering::Parameters& params,
+                         Response& response) {
+    params = options.defaults;
+    std::string error;
+    std::string preset = request.get("preset");
+    if (!request.body.empty()) {
+        if (!Settings::fromJson(request.body, params, &error)) {
+            response = errorResponse({Errors::ErrorCode::INVALID_ARGUMENT, error});
+            return false;
+        }
+    } else if (!preset.empty() && !Settings::loadPreset(preset, params, &error)) {
+        response = errorResponse({Errors::ErrorCode::NOT_FOUND, error});
+        return false;
+    }
+    return true;
+}
+
+static Errors::Error describePathCheck(const Platform::PathCheck& check) {
+    using Platform::PathErrorCode;
+    switch (check.code) {
+        case PathErrorCode::NOT_FOUND: return {Errors::ErrorCode::NOT_FOUND, check.message};
+        case PathErrorCode::BAD_EXTENSION: return {Errors::ErrorCode::UNSUPPORTED_FORMAT, check.message};
+        default: return {Errors::ErrorCode::INVALID_ARGUMENT, check.message};
+    }
+}
+
+// A file to read, checked as the CLI checks its input
+static bool readInputPath(const Request& request, const std::string& name, const std::vector<std::string>& extensions,
+                          std::string& path, Response& response) {
+    if (request.get(name).empty()) {
+        response = errorResponse({Errors::ErrorCode::INVALID_ARGUMENT, name + " is required"});
+        return false;
+    }
+    Platform::PathCheck check = Platform::validateInputFile(request.get(name), extensions);
+    if (!check.ok()) {
+        response = errorResponse(describePathCheck(check));
+        return false;
+    }
+    path = check.path;
+    return true;
+}
+
+// Input and output files of a request. An existing output is only replaced
+// with overwrite=true, so a stray request can't clobber a file
+static bool readPaths(const Request& request, const std::vector<std::string>& inputExtensions,
+                      const std::vector<std::string>& outputExtensions, std::string& input, std::string& output,
+                      Response& response) {
+    if (!readInputPath(request, "input", inputExtensions, input, response)) return false;
+    if (request.get("output").empty()) {
+        response = errorResponse({Errors::ErrorCode::INVALID_ARGUMENT, "output is required"});
+        return false;
+    }
+    Platform::PathCheck check = Platform::validateOutputFile(request.get("output"), outputExtensions);
+    if (!check.ok()) {
+        response = errorResponse(describePathCheck(check));
+        return false;
+    }
+    output = check.path;
+    std::error_code ec;
+    if (std::filesystem::exists(output, ec) && request.get("overwrite") != "true") {
+        response = errorResponse(409, {Errors::ErrorCode::INVALID_ARGUMENT,
+                                       output + " already exists; pass overwrite=true to replace it"});
+        return false;
+    }
+    return true;
+}
+
+static Response handleStatus() {
+    std::vector<std::string> algorithms, plugins;
+    for (const auto& info : Dithering::getAlgorithmLibrary()) {
+        algorithms.push_back(Settings::getAlgorithmId(info.algorithm));
+    }
+    for (const auto& info : Plugins::getAlgorithms()) plugins.push_back(info.id);
+    return {200, writeJson(Json::object()
+                               .set("app", Json::of("Dither's Boyfriend"))
+                               .set("settingsVersion", Json::of(static_cast<double>(Settings::SCHEMA_VERSION)))
+                               .set("algorithms", jsonArray(algorithms))
+                               .set("plugins", jsonArray(plugins))
+                               .set("presets", jsonArray(Settings::listPresets())))};
+}
+
+static Response handleDither(const Request& request, const Options& options) {
+    Response response;
+    std::string input, output;
+    Dithering::Parameters params;
+    if (!readPaths(request, ImageIO::getImageExtensions(), ImageIO::getSaveExtensions(), input, output, response) ||
+        !readSettings(request, options, params, response)) {
+        return response;
+    }
+
+    Batch::FileResult result = Batch::processFile(input, output, params, Batch::Options());
+    if (!result.ok()) return errorResponse(result.error);
+    UsageStats::recordJob(Dithering::getAlgorithmName(params.algorithm), result.size.width, result.size.height,
+                          result.seconds, false);
+    return {200, writeJson(Json::object()
+                               .set("output", Json::of(output))
+                               .set("width", Json::of(static_cast<double>(result.size.width)))
+                               .set("height", Json::of(static_cast<double>(result.size.height)))
+                               .set("seconds", Json::of(result.seconds)))};
+}
+
+// Colors of an image file (usually a result) against the palette of the settings
+static Response handleColors(const Request& request, const Options& options) {
+    Response response;
+    Dithering::Parameters params;
+    if (!readSettings(request, options, params, response)) return response;
+    std::string path;
+    if (!readInputPath(request, "image", ImageIO::getImageExtensions(), path, response)) return response;
+
+    cv::Mat image, alpha;
+    Compare::ColorReport report;
+    if (!ImageIO::loadImage(path, image, alpha)) {
+        Errors::Error error = ImageIO::describeLoadFailure(path);
+        error.message += ": " + path;
+        return errorResponse(error);
+    }
+    if (!Compare::countOutputColors(image, params, report)) {
+        return errorResponse({Errors::ErrorCode::UNSUPPORTED_FORMAT, "Not an 8-bit color image: " + path});
+    }
+
+    Json colors = Json::array();
+    for (const auto& count : report.colors) {
+        char hex[8];
+        std::snprintf(hex, sizeof(hex), "#%02x%02x%02x", count.color[2], count.color[1], count.color[0]);
+        colors.push(Json::object()
+                        .set("color", Json::of(hex))
+                        .set("pixels", Json::of(static_cast<double>(count.pixels)))
+                        .set("inPalette", Json::of(count.inPalette)));
+    }
+    return {200, writeJson(Json::object()
+                               .set("count", Json::of(static_cast<double>(report.colors.size())))
+                               .set("limit", Json::of(static_cast<double>(report.limit)))
+                               .set("outsidePalette", Json::of(static_cast<double>(report.outsidePalette)))
+                               .set("exceedsPalette", Json::of(report.exceedsPalette()))
+                               .set("colors", colors))};
+}
+
+// Cancelled when the server stops, so closing the app doesn't wait for the video
+static Response handleVideo(const Request& request, const Options& options, const std::atomic<bool>& stopping) {
+    Response response;
+    std::string input, output;
+    Dithering::Parameters params;
+    // Videos and animated images in, either out
+    std::vector<std::string> extensions = Video::getVideoExtensions();
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    extensions.insert(extensions.end(), animationExtensions.begin(), animationExtensions.end());
+    if (!readPaths(request, extensions, extensions, input, output, response) ||
+        !readSettings(request, options, params, response)) {
+        return response;
+    }
+
+    Errors::Error error;
+    auto start = std::chrono::steady_clock::now();
+    auto progress = [&stopping](const Video::Progress&) { return !stopping; };
+    if (!Video::processVideo(input, output, params, Video::Options(), progress, &error)) return errorResponse(error);
+    double seconds = std::chrono::duration<double>(std::chrono::steady_clock::now() - start).count();
+    return {200, writeJson(Json::object().set("output", Json::of(output)).set("seconds", Json::of(seconds)))};
+}
+
+static Response handleScript(const Request& request, const Options& options) {
+    Response response;
+    Dithering::Parameters params = options.defaults;
+    std::string preset = request.get("preset");
+    std::string presetError;
+    if (!preset.empty() && !Settings::loadPreset(preset, params, &presetError)) {
+        return errorResponse({Errors::ErrorCode::NOT_FOUND, presetError});
+    }
+
+    std::vector<std::string> args, lines;
+    for (const auto& [name, value] : request.query) {
+        if (name == "arg") args.push_back(value);
+    }
+    Errors::Error error;
+    auto log = [&lines](const std::string& message) { lines.push_back(message); };
+    bool overwrite = request.get("overwrite") == "true";
+    if (!Script::runScriptText(request.body, "request", args, params, log, &error, overwrite)) {
+        return errorResponse(error);
+    }
+    return {200, writeJson(Json::object().set("log", jsonArray(lines)))};
+}
+
+static Response handleRequest(const Request& request, const Options& options, const std::atomic<bool>& stopping) {
+    auto authorization = request.headers.find("authorization");
+    if (authorization == request.headers.end() ||
+        !tokenMatches(authorization->second, "Bearer " + options.token)) {
+        return errorResponse(401, {Errors::ErrorCode::INVALID_ARGUMENT, "Missing or wrong token"});
+    }
+
+    bool get = request.method == "GET";
+    bool post = request.method == "POST";
+    try {
+        if (request.path == "/api/status" && get) return handleStatus();
+        if (request.path == "/api/schema" && get) return {200, Settings::getSettingsSchema()};
+        if (request.path == "/api/dither" && post) return handleDither(request, options);
+        if (request.path == "/api/colors" && post) return handleColors(request, options);
+        if (request.path == "/api/video" && post) return handleVideo(request, options, stopping);
+        if (request.path == "/api/script" && post) return handleScript(request, options);
+    } catch (const std::exception& e) {
+        return errorResponse(500, Errors::fromException(e));
+    }
+
+    for (const char* path : {"/api/status", "/api/schema", "/api/dither", "/api/colors", "/api/video", "/api/script"}) {
+        if (request.path == path) {
+            return errorResponse(405, {Errors::ErrorCode::INVALID_ARGUMENT, request.method + " is not allowed here"});
+        }
+    }
+    return errorResponse(404, {Errors::ErrorCode::NOT_FOUND, "No such endpoint: " + request.path});
+}
+
+Server::~Server() {
+    stop();
+}
+
+bool Server::start(const Options& serverOptions, LogCallback serverLog, std::string* error) {
+    stop();
+    options = serverOptions;
+    if (options.token.empty()) options.token = makeToken();
+    log = serverLog;
+
+#ifdef _WIN32
+    WSADATA data;
+    if (WSAStartup(MAKEWORD(2, 2), &data) != 0) {
+        if (error) *error = "Could not start Winsock";
+        return false;
+    }
+#endif
+
+    Socket socket = ::socket(AF_INET, SOCK_STREAM, 0);
+#ifdef _WIN32
+    if (socket == INVALID_SOCKET) {
+#else
+    if (socket < 0) {
+#endif
+        if (error) *error = "Could not create a socket";
+        return false;
+    }
+    sockaddr_in address = {};
+    address.sin_family = AF_INET;
+    address.sin_port = htons(static_cast<uint16_t>(options.port));
+    address.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    int reuse = 1;
+    setsockopt(socket, SOL_SOCKET, SO_REUSEADDR, reinterpret_cast<const char*>(&reuse), sizeof(reuse));
+
+    if (bind(socket, reinterpret_cast<sockaddr*>(&address), sizeof(address)) != 0 || listen(socket, 8) != 0) {
+        if (error) *error = "Could not listen on 127.0.0.1:" + std::to_string(options.port) + " (port in use?)";
+        closeSocket(socket);
+#ifdef _WIN32
+        WSACleanup();
+#endif
+        return false;
+    }
+
+    listener = static_cast<std::intptr_t>(socket);
+    stopping = false;
+    running = true;
+    thread = std::thread(&Server::run, this);
+    worker = std::thread(&Server::serve, this);
+    return true;
+}
+
+void Server::stop() {
+    if (!running) return;
+    {
+        std::lock_guard<std::mutex> lock(clientsMutex);
+        stopping = true;
+    }
+    clientsReady.notify_all();
+    thread.join();
+    worker.join();
+    for (std::intptr_t client : clients) closeSocket(static_cast<Socket>(client));
+    clients.clear();
+    closeSocket(static_cast<Socket>(listener));
+    listener = -1;
+    running = false;
+#ifdef _WIN32
+    WSACleanup();
+#endif
+}
+
+// Accepts connections and hands them to the worker, waking up a few times a
+// second to notice stop()
+void Server::run() {
+    Socket socket = static_cast<Socket>(listener);
+    while (!stopping) {
+        fd_set readable;
+        FD_ZERO(&readable);
+        FD_SET(socket, &readable);
+        timeval timeout = {0, 250000};
+        if (select(static_cast<int>(socket) + 1, &readable, nullptr, nullptr, &timeout) <= 0) continue;
+
+        Socket client = accept(socket, nullptr, nullptr);
+#ifdef _WIN32
+        if (client == INVALID_SOCKET) continue;
+        DWORD wait = 10000;
+#else
+        if (client < 0) continue;
+        timeval wait = {10, 0};
+#endif
+        // A client that stops sending mustn't hold up the server
+        setsockopt(client, SOL_SOCKET, SO_RCVTIMEO, reinterpret_cast<const char*>(&wait), sizeof(wait));
+        {
+            std::lock_guard<std::mutex> lock(clientsMutex);
+            clients.push_back(static_cast<std::intptr_t>(client));
+        }
+        clientsReady.notify_one();
+    }
+}
+
+// Answers the accepted connections one at a time, in order
+void Server::serve() {
+    while (true) {
+        Socket client;
+        {
+            std::unique_lock<std::mutex> lock(clientsMutex);
+            clientsReady.wait(lock, [this]() { return stopping || !clients.empty(); });
+            if (stopping) return;
+            client = static_cast<Socket>(clients.front());
+            clients.pop_front();
+        }
+
+        Request request;
+        int status = 400;
+        auto start = std::chrono::steady_clock::now();
+        if (readRequest(client, request, status)) {
+            Response response = handleRequest(request, options, stopping);
+            sendResponse(client, response);
+            if (log) {
+                double seconds = std::chrono::duration<double>(std::chrono::steady_clock::now() - start).count();
+                char took[32];
+                std::snprintf(took, sizeof(took), " (%.2f s)", seconds);
+                log(request.method + " " + request.path + " " + std::to_string(response.status) + took);
+            }
+        } else if (status == 413) {
+            sendResponse(client, errorResponse(413, {Errors::ErrorCode::INVALID_ARGUMENT, "Request too large"}));
+        } else if (status == 408) {
+            sendResponse(client, errorResponse(408, {Errors::ErrorCode::INVALID_ARGUMENT, "Request took too long"}));
+        } else if (status == 411) {
+            sendResponse(client, errorResponse(411, {Errors::ErrorCode::INVALID_ARGUMENT,
+                                                     "Chunked bodies aren't supported; send a Content-Length"}));
+        } else if (status == 501) {
+            sendResponse(client, errorResponse(501, {Errors::ErrorCode::INVALID_ARGUMENT,
+                                                     "Unsupported Transfer-Encoding"}));
+        }
+        closeSocket(client);
+    }
+}
+
+} // namespace Automation
@@ -0,0 +1,81 @@
+#pragma once
+
+#include <atomic>
+#include <condition_variable>
+#include <cstdint>
+#include <deque>
+#include <functional>
+#include <mutex>
+#include <string>
+#include <thread>
+
+#include "dithering.h"
+
+namespace Automation {
+
+// Local HTTP API for scripts and other programs (Photoshop scripts, OBS, build
+// systems). It only listens on 127.0.0.1 and every request must carry the
+// token as "Authorization: Bearer <token>". Requests and responses are JSON,
+// one request at a time:
+//
+//   GET  /api/status                       App, algorithm, plugin and preset ids
+//   GET  /api/schema                       Settings JSON Schema
+//   POST /api/dither?input=&output=        Dither an image file into another
+//   POST /api/colors?image=                Colors an image uses, checked against the palette
+//   POST /api/video?input=&output=         Dither a video or animation
+//   POST /api/script?arg=&arg=             Run the body as a pipeline script
+//
+// The body of dither, colors and video is a settings document (as in settings files);
+// without one the server's default settings are used, or a preset with
+// preset=<name>. Paths are checked as the CLI checks them, and an existing
+// output is only replaced with overwrite=true. Failures answer with
+// {"error": <error code id>, "message": ...}
+//
+// There is no WebSocket endpoint: every operation is a single request and
+// answer, so plain HTTP covers them. Progress streaming would need one
+const int DEFAULT_PORT = 8642;
+
+struct Options {
+    int port = DEFAULT_PORT;
+    std::string token;                      // Empty = a random one, see Server::getToken
+    Dithering::Parameters defaults;         // Settings for requests that don't send any
+};
+
+// One line per request, e.g. "POST /api/dither 200 (1.25 s)". Called from the
+// server's thread
+using LogCallback = std::function<void(const std::string&)>;
+
+struct Server {
+    Server() = default;
+    Server(const Server&) = delete;
+    Server& operator=(const Server&) = delete;
+    ~Server();
+
+    // Start listening. False if the port can't be used; error says why
+    bool start(const Options& options, LogCallback log = nullptr, std::string* error = nullptr);
+
+    // Stop listening. A video being dithered is cancelled; connections not yet
+    // answered are closed
+    void stop();
+
+    bool isRunning() const { return running; }
+    int getPort() const { return options.port; }
+    const std::string& getToken() const { return options.token; }
+
+private:
+    void run();
+    void serve();
+
+    Options options;
+    LogCallback log;
+    std::intptr_t listener = -1;
+    std::thread thread;                     // Accepts connections
+    std::thread worker;                     // Answers them, one at a time
+    std::deque<std::intptr_t> clients;      // Accepted, waiting for the worker
+    std::mutex clientsMutex;
+    std::condition_variable clientsReady;
+    std::atomic<bool> running{false};
+    std::atomic<bool> stopping{false};
+};
+
+} // namespace Automation
@@ -0,0 +1,254 @@
+#include "batch.h"
+#include <algorithm>
+#include <atomic>
+#include <chrono>
+#include <filesystem>
+#include <iostream>
+#include <map>
+#include <mutex>
+#include <set>
+#include <thread>
+
+#include "settings.h"
+
+namespace Batch {
+
+std::vector<std::string> collectImages(const std::string& folder) {
+    std::vector<std::string> files;
+    std::error_code ec;
+    for (const auto& entry : std::filesystem::directory_iterator(folder, ec)) {
+        if (!entry.is_regular_file()) continue;
+        try {
+            std::string path = entry.path().string();
+            if (ImageIO::isImageFile(path)) files.push_back(path);
+        } catch (const std::exception&) {
+            // Name can't be represented as a narrow string on this system
+            std::cerr << "Warning: Skipping file with unsupported name in " << folder << std::endl;
+        }
+    }
+    std::sort(files.begin(), files.end());
+    return files;
+}
+
+std::string outputPath(const std::string& input, const std::string& outputDir,
+                       const Dithering::Parameters& params, const Options& options) {
+    std::filesystem::path inputPath(input);
+    std::string ext = options.outputFormat;
+    if (ext.empty()) {
+        ext = inputPath.extension().string();
+        if (!ext.empty()) ext.erase(0, 1);
+
+        // Read-only inputs like HEIC and camera RAW are saved as PNG
+        std::string lower = ImageIO::getExtension(input);
+        const auto& writable = ImageIO::getSaveExtensions();
+        if (std::find(writable.begin(), writable.end(), lower) == writable.end()) ext = "png";
+    }
+    size_t colors = params.paletteMode == Dithering::PaletteMode::ADAPTIVE
+                        ? static_cast<size_t>(params.paletteSize)
+                        : Dithering::getPalette(params).size();
+
+    const std::vector<std::pair<std::string, std::string>> fields = {
+        {"{name}", inputPath.stem().string()},
+        {"{ext}", ext},
+        {"{algorithm}", Settings::getAlgorithmId(params.algorithm)},
+        {"{palette}", Settings::getPaletteModeId(params.paletteMode)},
+        {"{colors}", std::to_string(colors)},
+        {"{seed}", std::to_string(params.seed)}
+    };
+    std::string name = options.nameTemplate.empty() ? "{name}.{ext}" : options.nameTemplate;
+    for (const auto& field : fields) {
+        for (size_t at = name.find(field.first); at != std::string::npos;
+             at = name.find(field.first, at + field.second.size())) {
+            name.replace(at, field.first.size(), field.second);
+        }
+    }
+    return (std::filesystem::path(outputDir) / name).lexically_normal().string();
+}
+
+// Number a path that's already taken: photo.png -> photo_2.png, photo_3.png, ...
+static std::string uniquePath(const std::string& path, const std::set<std::string>& taken) {
+    if (!taken.count(path)) return path;
+    std::filesystem::path base(path);
+    for (int n = 2;; ++n) {
+        std::filesystem::path candidate = base;
+        candidate.replace_filename(base.stem().string() + "_" + std::to_string(n) + base.extension().string());
+        if (!taken.count(candidate.string())) return candidate.string();
+    }
+}
+
+FileResult processFile(const std::string& input, const std::string& output,
+                       const Dithering::Parameters& params, const Options& options, size_t memoryLimit) {
+    FileResult result;
+    result.input = input;
+    result.output = output;
+
+    using Errors::ErrorCode;
+    std::error_code ec;
+    if (std::filesystem::equivalent(input, output, ec)) {
+        result.error = {ErrorCode::INVALID_ARGUMENT, "Output would overwrite the input"};
+        return result;
+    }
+
+    std::filesystem::path folder = std::filesystem::path(output).parent_path();
+    if (!folder.empty() && !std::filesystem::create_directories(folder, ec) && ec) {
+        result.error = {ErrorCode::IO, "Could not create " + folder.string()};
+        return result;
+    }
+
+    try {
+        cv::Mat color, alpha, precise;
+        if (!ImageIO::loadImage(input, color, alpha, nullptr, &precise)) {
+            result.error = ImageIO::describeLoadFailure(input);
+            return result;
+        }
+
+        if (options.transform) {
+            color = options.transform(color);
+            if (!alpha.empty()) alpha = options.transform(alpha);
+            if (!precise.empty()) precise = options.transform(precise);
+            if (color.empty()) {
+                result.error = {ErrorCode::INVALID_ARGUMENT, "Could not transform image"};
+                return result;
+            }
+        }
+
+        auto start = std::chrono::high_resolution_clock::now();
+        ImageIO::applyMatte(color, alpha, options.exportOptions);
+        if (!precise.empty()) {
+            ImageIO::applyMatte(precise, alpha, options.exportOptions);
+            color = precise;
+        }
+        cv::Mat dithered = Dithering::ditherWithinMemory(color, params, memoryLimit);
+        if (options.ditherAlpha && !alpha.empty()) {
+            alpha = Dithering::ditherAlpha(alpha, params);
+        }
+        auto end = std::chrono::high_resolution_clock::now();
+        result.seconds = std::chrono::duration<float>(end - start).count();
+        result.size = dithered.size();
+
+        ImageIO::ExportOptions exportOptions = options.exportOptions;
+        if (exportOptions.encode.keepMetadata) ImageIO::readMetadata(input, exportOptions.metadata);
+        if (!ImageIO::saveImage(output, dithered, alpha, exportOptions)) {
+            result.error = ImageIO::describeSaveFailure(output);
+        }
+    } catch (const std::exception& e) {
+        // One oversized or odd file shouldn't take the whole batch down
+        result.error = Errors::fromException(e);
+    }
+    return result;
+}
+
+std::vector<FileResult> processBatch(const std::vector<std::string>& inputs, const std::string& outputDir,
+                                     const Dithering::Parameters& params, const Options& options,
+                                     ProgressCallback progress) {
+    std::vector<FileResult> results(inputs.size());
+    std::error_code ec;
+    std::filesystem::create_directories(outputDir, ec);
+
+    // Output names are settled up front so numbering doesn't depend on timing
+    std::vector<std::string> outputs;
+    std::set<std::string> taken;
+    for (const auto& input : inputs) {
+        outputs.push_back(uniquePath(outputPath(input, outputDir, params, options), taken));
+        taken.insert(outputs.back());
+    }
+
+    int jobs = options.jobs;
+    if (jobs <= 0) {
+        jobs = std::clamp(static_cast<int>(std::thread::hardware_concurrency()), 1, 4);
+    }
+    jobs = std::max(1, std::min(jobs, static_cast<int>(inputs.size())));
+    size_t memoryLimit = options.memoryLimit / jobs;
+
+    // Workers take the next file until none are left
+    std::atomic<size_t> next{0};
+    std::mutex progressMutex;
+    int finished = 0;
+    auto work = [&]() {
+        for (size_t i = next++; i < inputs.size(); i = next++) {
+            if (options.keepRunning && !options.keepRunning()) {
+                results[i].input = inputs[i];
+                results[i].output = outputs[i];
+                results[i].error = {Errors::ErrorCode::CANCELLED, "Cancelled"};
+            } else {
+                results[i] = processFile(inputs[i], outputs[i], params, options, memoryLimit);
+            }
+            std::lock_guard<std::mutex> lock(progressMutex);
+            ++finished;
+            if (progress) progress(finished, static_cast<int>(inputs.size()), results[i]);
+        }
+    };
+
+    std::vector<std::thread> workers;
+    for (int i = 1; i < jobs; ++i) workers.emplace_back(work);
+    work();
+    for (auto& worker : workers) worker.join();
+    return results;
+}
+
+// Size and modification time, to tell when a file has stopped changing
+struct FileState {
+    std::uintmax_t size = 0;
+    std::filesystem::file_time_type modified;
+
+    bool operator==(const FileState& other) const {
+        return size == other.size && modified == other.modified;
+    }
+};
+
+void watchFolder(const std::string& folder, const std::string& outputDir,
+                 const Dithering::Parameters& params, const Options& options,
+                 std::function<bool()> keepRunning, ProgressCallback progress) {
+    std::map<std::string, FileState> changing;     // Seen in the last scan, not yet settled
+    std::map<std::string, FileState> failed;       // Not retried until the file changes
+    auto pause = std::chrono::milliseconds(static_cast<int>(std::max(0.1, options.pollInterval) * 1000.0));
+
+    while (keepRunning()) {
+        std::vector<std::string> ready;
+        for (const auto& input : collectImages(folder)) {
+            std::error_code ec;
+            FileState state;
+            state.size = std::filesystem::file_size(input, ec);
+            state.modified = std::filesystem::last_write_time(input, ec);
+            if (ec) continue;
+
+            auto failure = failed.find(input);
+            if (failure != failed.end()) {
+                if (failure->second == state) continue;
+                failed.erase(failure);
+            }
+
+            std::string output = outputPath(input, outputDir, params, options);
+            auto outputModified = std::filesystem::last_write_time(output, ec);
+            if (!ec && outputModified >= state.modified) {
+                changing.erase(input);
+                continue;
+            }
+
+            auto seen = changing.find(input);
+            if (seen != changing.end() && seen->second == state) {
+                ready.push_back(input);
+                changing.erase(seen);
+            } else {
+                changing[input] = state;
+            }
+        }
+
+        if (!ready.empty()) {
+            for (const auto& result : processBatch(ready, outputDir, params, options, progress)) {
+                if (result.ok()) continue;
+                std::error_code ec;
+                failed[result.input] = {std::filesystem::file_size(result.input, ec),
+                                        std::filesystem::last_write_time(result.input, ec)};
+            }
+        }
+
+        // Sleep in short steps so stopping doesn't wait for a whole interval
+        auto wake = std::chrono::steady_clock::now() + pause;
+        while (keepRunning() && std::chrono::steady_clock::now() < wake) {
+            std::this_thread::sleep_for(std::chrono::milliseconds(100));
+        }
+    }
+}
+
+} // namespace Batch
@@ -0,0 +1,80 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <cstddef>
+#include <functional>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+#include "imageio.h"
+
+namespace Batch {
+
+// Batch processing options
+struct Options {
+    int jobs = 0;                   // Images dithered at the same time (0 = up to 4, one per core)
+    std::string outputFormat;       // Output extension without the dot (empty = same as the input, PNG for HEIC/RAW)
+    std::string nameTemplate = "{name}.{ext}";  // Output file name, see outputPath
+    size_t memoryLimit = 0;         // Working memory shared by all jobs, as for ditherWithinMemory (0 = off)
+    bool ditherAlpha = false;       // Dither transparency to 1-bit
+    double pollInterval = 1.0;      // Seconds between folder scans in watch mode
+    ImageIO::ExportOptions exportOptions;
+
+    // Applied to color, alpha and full precision color of every image before
+    // dithering (crop, resize, ...). Returning an empty image fails that file
+    std::function<cv::Mat(const cv::Mat&)> transform;
+
+    // Asked before each file; once it returns false the files not yet started
+    // fail as cancelled (empty = run them all)
+    std::function<bool()> keepRunning;
+};
+
+// Outcome for one input file
+struct FileResult {
+    std::string input;
+    std::string output;
+    Errors::Error error;            // Why it failed
+    cv::Size size;                  // Dithered size
+    float seconds = 0.0f;
+
+    bool ok() const { return error.ok(); }
+};
+
+// Progress callback: (files finished, total files, the file just finished).
+// Called from worker threads, one call at a time
+using ProgressCallback = std::function<void(int, int, const FileResult&)>;
+
+// The images in a folder, in file name order
+std::vector<std::string> collectImages(const std::string& folder);
+
+// Where an input is written: the output folder plus the name template with
+// {name} (input file name without extension), {ext} (output extension),
+// {algorithm}, {palette}, {colors} (palette size) and {seed} filled in. The
+// template may contain folders, e.g. "{algorithm}/{name}.{ext}"
+std::string outputPath(const std::string& input, const std::string& outputDir,
+                       const Dithering::Parameters& params, const Options& options);
+
+// Load, dither and save one image, as processBatch does for each file.
+// memoryLimit is this image's share of options.memoryLimit (0 = off)
+FileResult processFile(const std::string& input, const std::string& output,
+                       const Dithering::Parameters& params, const Options& options, size_t memoryLimit = 0);
+
+// Dither every input with the same settings into outputDir (created if
+// missing, like any folders in the name template), several files at a time.
+// Inputs that would get the same output name are numbered: photo.png,
+// photo_2.png, ... Returns one result per input, in order
+std::vector<FileResult> processBatch(const std::vector<std::string>& inputs, const std::string& outputDir,
+                                     const Dithering::Parameters& params, const Options& options,
+                                     ProgressCallback progress = nullptr);
+
+// Keep dithering images that appear in (or change in) folder into outputDir
+// until keepRunning returns false. An image is processed once its size and
+// modification time have stayed the same for one scan, so files still being
+// copied aren't read half-written, and only if its output is missing or older
+void watchFolder(const std::string& folder, const std::string& outputDir,
+                 const Dithering::Parameters& params, const Options& options,
+                 std::function<bool()> keepRunning, ProgressCallback progress = nullptr);
+
+} // namespace Batch
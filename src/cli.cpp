@@ -1,21 +1,348 @@
 #include <iostream>
 #include <string>
+#include <vector>
+#include <algorithm>
+#include <atomic>
+#include <chrono>
+#include <csignal>
+#include <filesystem>
+#include <cstdio>
+#include <thread>
 #include <opencv2/opencv.hpp>
+#include "batch.h"
 #include "dithering.h"
+#include "errors.h"
+#include "matrices.h"
+#include "compare.h"
+#include "descreen.h"
+#include "devices.h"
+#include "halftone.h"
+#include "ascii.h"
+#include "automation.h"
+#include "plotter.h"
+#include "embedded.h"
+#include "palettefile.h"
+#include "poster.h"
+#include "settings.h"
+#include "transform.h"
+#include "imageio.h"
+#include "lospec.h"
+#include "platform.h"
+#include "plugins.h"
+#include "script.h"
+#include "video.h"
+#include "recent.h"
+#include "stats.h"
+#include "tiles.h"
+#include "wallpaper.h"
 
 void printUsage(const char* program) {
     std::cout << "Dither's Boyfriend - CLI Version\n";
     std::cout << "Usage: " << program << " [options] input_file output_file\n\n";
     std::cout << "Options:\n";
     std::cout << "  -a, --algorithm <name>    Dithering algorithm (default: floyd-steinberg)\n";
+    std::cout << "  --matrix <name>           Threshold matrix for -a matrix (default: dots45)\n";
+    std::cout << "  --pattern-matrix <m>      Threshold matrix for -a pattern: [[0,2],[3,1]] or a grayscale image\n";
+    std::cout << "  --noise-texture <file>    Tile a pre-made blue noise texture instead of generating one\n";
+    std::cout << "                            (PNG, 16-bit or HDR, up to 1024x1024); switches to -a blue-noise\n";
+    std::cout << "  --kernel <triples>        Error diffusion kernel for -a kernel as [dx,dy,weight] triples,\n";
+    std::cout << "                            e.g. [[1,0,7],[-1,1,3],[0,1,5],[1,1,1]] (weights scaled to sum to 1)\n";
+    std::cout << "  --kernel-raw              Use the kernel's weights as given instead of scaling them\n";
+    std::cout << "  --plugin-option <k=v>     Option passed to a plugin algorithm (repeatable)\n";
     std::cout << "  -p, --palette <name>      Color palette (default: monochrome)\n";
+    std::cout << "  --colors <hex,hex,...>    Custom palette colors (implies -p custom)\n";
+    std::cout << "  --palette-file <file>     Custom palette from a .gpl, .ase, .act, .pal, .txt, .hex or .png file\n";
+    std::cout << "  --lospec <name>           Custom palette from lospec.com by its URL name, e.g. pico-8 (cached)\n";
+    std::cout << "  --lospec-search <text>    Search lospec.com palettes by title and exit\n";
+    std::cout << "  --lospec-colors <n>       Only find palettes of exactly n colors\n";
+    std::cout << "  --export-palette <file>   Save the palette as .gpl, .ase, .hex or .png (with --extract-palette,\n";
+    std::cout << "                            the extracted one)\n";
+    std::cout << "  --protect <hex,hex,...>   Palette colors kept only where the image matches them exactly\n";
+    std::cout << "  --ink <hex>               Dark color for monochrome output (default: 000000)\n";
+    std::cout << "  --paper <hex>             Light color for monochrome and --inks output (default: ffffff)\n";
+    std::cout << "  --inks <hex,hex[,hex]>    Duotone/tritone: print tones with 2-3 spot inks on --paper\n";
+    std::cout << "  --palette-size <int>      Colors generated by the adaptive palette (default: 16)\n";
+    std::cout << "  --palette-method <name>   Palette generation: kmeans, median-cut (default: kmeans)\n";
+    std::cout << "  --palette-from <file>     Lock the adaptive palette to one generated from this image,\n";
+    std::cout << "                            so a batch of files shares the same colors\n";
     std::cout << "  -s, --strength <float>    Strength (0.0-2.0, default: 1.0)\n";
+    std::cout << "  --threshold <float|auto>  Rounding point between levels (0.0-1.0, default: 0.5),\n";
+    std::cout << "                            auto picks it with Otsu's method\n";
+    std::cout << "  --local-window <int>      Window for sauvola, niblack, bradley in pixels (default: 25)\n";
+    std::cout << "  --local-k <float>         Local threshold sensitivity k (default: 0.2)\n";
+    std::cout << "  --edge-sensitivity <f>    How strongly edges stop error with structure (0-4, default: 1)\n";
+    std::cout << "  --curve <name>            Riemersma pixel order: hilbert, peano, z-order (default: hilbert)\n";
+    std::cout << "  --riemersma-history <n>   Errors Riemersma remembers along the curve (default: 16)\n";
+    std::cout << "  --riemersma-decay <f>     Newest to oldest error weight ratio (default: 16)\n";
     std::cout << "  -g, --gamma <float>       Gamma correction (0.1-3.0, default: 1.0)\n";
+    std::cout << "  --levels <b>,<mid>,<w>    Input levels as in a Levels dialog: black and white points 0-255 and\n";
+    std::cout << "                            midtone gamma, >1 lightens (e.g. 20,1.2,235)\n";
+    std::cout << "  --curve <[[in,out],...]>  Tone curve through points in 0-1, after the levels, e.g. an S curve\n";
+    std::cout << "                            [[0,0],[0.25,0.18],[0.75,0.82],[1,1]]\n";
     std::cout << "  -c, --contrast <float>    Contrast (0.0-3.0, default: 1.0)\n";
     std::cout << "  -b, --brightness <float>  Brightness (-1.0-1.0, default: 0.0)\n";
     std::cout << "  --saturation <float>      Saturation (0.0-2.0, default: 1.0)\n";
+    std::cout << "  --exposure <stops>        Exposure adjustment (-4.0-4.0, default: 0)\n";
+    std::cout << "  --denoise <method>        Filter sensor noise first so it isn't dithered into speckle: none,\n";
+    std::cout << "                            median, bilateral (default: none)\n";
+    std::cout << "  --denoise-strength <f>    Noise filter strength (0.0-1.0, default: 0.5)\n";
+    std::cout << "  --blur <sigma>            Gaussian blur before dithering in pixels (default: 0)\n";
+    std::cout << "  --sharpen <amount>        Unsharp mask strength (0.0-4.0, default: 0)\n";
+    std::cout << "  --sharpen-radius <sigma>  Unsharp mask radius in pixels (default: 1.0)\n";
+    std::cout << "  --clip-black <percent>    Force at least this share of pixels to pure black (default: 0)\n";
+    std::cout << "  --clip-white <percent>    Force at least this share of pixels to pure white (default: 0)\n";
+    std::cout << "  --protect-highlights <f>  Luma above this (0-1) stays pure white, no stray dots (default: off)\n";
+    std::cout << "  --protect-shadows <f>     Luma below this (0-1) stays pure black (default: off)\n";
+    std::cout << "  --dot-gain <percent>      Compensate print dot gain, as % added at 50% ink (default: off)\n";
+    std::cout << "  --rounding <mode>         Palette rounding: nearest, floor, ceil, stochastic (default: nearest)\n";
+    std::cout << "  --distance <metric>       Color matching: rgb, weighted-rgb, cie76, ciede2000 (default: rgb)\n";
+    std::cout << "  --intent <name>           Palette mapping: relative, perceptual (default: relative)\n";
+    std::cout << "  --working-space <name>    Quantize and diffuse error in: rgb, lab, ycbcr, hsl (default: rgb)\n";
+    std::cout << "  --gray <name>             Color to gray for grayscale/monochrome output: rec601, rec709,\n";
+    std::cout << "                            average, red, green, blue (default: rec601)\n";
+    std::cout << "  --gray-weights <r,g,b>    Custom channel weights for the gray conversion, e.g. 1,1,0\n";
+    std::cout << "  --honor-profiles          Convert images from their embedded color profile to the profile space\n";
+    std::cout << "  --profile-space <name>    Space --honor-profiles converts to: srgb, display-p3, adobe-rgb\n";
+    std::cout << "                            (default: srgb; other spaces tag JPEG and PNG output)\n";
+    std::cout << "  --linear                  Dither in linear light (keeps midtones from darkening)\n";
     std::cout << "  --serpentine              Enable serpentine scanning\n";
+    std::cout << "  --error-edge <mode>       Error diffused past the left or right edge: discard, reflect, wrap\n";
+    std::cout << "                            (default: discard)\n";
+    std::cout << "  --no-error-clamp          Let error that pushes past black or white ring on to the neighbors\n";
+    std::cout << "                            instead of dropping it; keeps color at high-contrast edges\n";
+    std::cout << "  --channel <spec>          Per-channel override: <plane>:<algorithm>[:strength[:threshold]],\n";
+    std::cout << "                            plane is b, g, r or y, cb, cr (e.g. y:floyd-steinberg, cb:bayer-4x4)\n";
+    std::cout << "  --levels <int>            Levels per plane with --channel (default: 2)\n";
     std::cout << "  --seed <int>              Random seed (default: 42)\n";
+    std::cout << "  --phase <x>,<y>           Ordered dither pattern offset in pixels (default: 0,0)\n";
+    std::cout << "  --halftone-shape <name>   Halftone dot shape: circle, diamond (default: circle)\n";
+    std::cout << "  --cell-size <float>       Halftone cell size in pixels (default: 8)\n";
+    std::cout << "  --lpi <float>             Halftone or clustered Bayer screen ruling in lines per inch; sets the\n";
+    std::cout << "                            cell size from --pdf-dpi and warns when it won't print well\n";
+    std::cout << "  --screen-angles <c,m,y>   Halftone angle per channel in degrees (default: 15,75,0)\n";
+    std::cout << "  --screen-angle <float>    Halftone angle for grayscale palettes (default: 45)\n";
+    std::cout << "  --stipple-dots <int>      Number of dots for -a stipple (default: 5000)\n";
+    std::cout << "  --stipple-size <float>    Stipple dot radius in pixels where the ink is solid (default: 1.5)\n";
+    std::cout << "  --stipple-iterations <n>  Stipple relaxation steps, more is more even (default: 30)\n";
+    std::cout << "  --color-mode <name>       Color output: rgb, cmyk (screened C, M, Y and K plates)\n";
+    std::cout << "  --pixel-format <name>     Quantize to a display format: rgb565, rgb332, rgba4444\n";
+    std::cout << "  --separations             With cmyk, also save each plate as <output>_c/_m/_y/_k\n";
+    std::cout << "  --bayer-size <int>        Bayer matrix size, any of 2-32 (default: from -a, e.g. 8 for bayer-8x8)\n";
+    std::cout << "  --clustered               Bayer algorithms grow one round dot per matrix instead\n";
+    std::cout << "  --matrix-angle <float>    Rotate Bayer, pattern and matrix screens by degrees (default: 0)\n";
+    std::cout << "  --pattern-scale <int>     Pixels per Bayer/pattern/matrix cell, for chunky output (default: 1)\n";
+    std::cout << "  --pixelate <int>          Dither at 1/N size, then scale up with hard pixel edges (default: 1)\n";
+    std::cout << "  --blend <float>           Mix the adjusted source back in for a subtle texture (0-1, default: 0)\n";
+    std::cout << "  --stage <type[:value]>    Extra pass on the result, in order (repeatable): dither (again\n";
+    std::cout << "                            with the same settings), dither:<settings.json>, blur:<sigma>,\n";
+    std::cout << "                            sharpen:<amount>\n";
+    std::cout << "  --stage-mix <float>       Blend the last --stage's output over its input (0-1, default: 1)\n";
+    std::cout << "  --backend <name>          Ordered/threshold algorithms on: auto, cpu, gpu (default: auto)\n";
+    std::cout << "  --alpha-threshold <int>   Alpha cutoff for binary transparency (0-255, default: 128)\n";
+    std::cout << "  --matte <hex>             Blend semi-transparent pixels onto this color (e.g. ffffff)\n";
+    std::cout << "  --binary-alpha            Force binary transparency on output (default for GIF)\n";
+    std::cout << "  --truecolor-png           Save PNGs as 24-bit even when 256 colors or fewer would fit a palette\n";
+    std::cout << "  --jpeg-quality <int>      JPEG quality (1-100, default: 95)\n";
+    std::cout << "  --png-compression <int>   PNG zlib level, 0 fastest to 9 smallest (default: 6)\n";
+    std::cout << "  --png-strategy <name>     default, filtered, huffman, rle, fixed (default: default)\n";
+    std::cout << "  --webp-quality <int>      Save lossy WebP at this quality (1-100, default: lossless)\n";
+    std::cout << "  --avif-quality <int>      AVIF quality (0-100, default: 100 = lossless)\n";
+    std::cout << "  --jxl-quality <int>       JPEG XL quality (0-100, default: 100 = lossless)\n";
+    std::cout << "  --truecolor-tiff          Save TIFFs as 24-bit even when two colors would fit in 1 bit\n";
+    std::cout << "  --strip-metadata          Don't copy the input's EXIF and ICC profile into JPEG and PNG output\n";
+    std::cout << "  --tag-profile             Embed a color profile in JPEG and PNG output that has none\n";
+    std::cout << "  --gif-transparent-index <int>\n";
+    std::cout << "                            GIF palette index for transparent pixels (0-255, default: any)\n";
+    std::cout << "  --dither-alpha            Dither transparency to 1-bit instead of cutting it off\n";
+    std::cout << "  --crop <x>,<y>,<w>,<h>    Crop the input before dithering\n";
+    std::cout << "  --resize <w>x<h>          Resize the input; leave out w or h to keep the aspect (e.g. 640x)\n";
+    std::cout << "  --resize-filter <name>    nearest, bilinear, bicubic, area, lanczos (default: area)\n";
+    std::cout << "  --rotate <degrees>        Rotate the input clockwise: 90, 180, 270\n";
+    std::cout << "  --flip <h|v>              Mirror the input horizontally or vertically\n";
+    std::cout << "                            (transforms run in this order: crop, resize, rotate, flip)\n";
+    std::cout << "  --device <id>             Emulate a retro screen: its palette and algorithm, and the input\n";
+    std::cout << "                            cropped and scaled to its resolution after the other transforms\n";
+    std::cout << "                            (gameboy, macintosh, eink, cga-composite; see --list-devices)\n";
+    std::cout << "  --descreen <method>       Smooth away the screen of a scanned print before dithering:\n";
+    std::cout << "                            gaussian, sinc, fft\n";
+    std::cout << "  --reconstruct <method>    Save the input with its dither or halftone smoothed away (the tones\n";
+    std::cout << "                            it gives) instead of dithering it; methods as for --descreen\n";
+    std::cout << "  --descreen-period <px>    Screen or pattern period for both (default: detected)\n";
+    std::cout << "  --tile <x>,<y>,<w>,<h>    Dither and save only this region of the image\n";
+    std::cout << "  --tile-margin <int>       Context pixels processed around the tile (default: 32)\n";
+    std::cout << "  --mask <file>             Dither only where this grayscale image is white, keep the original\n";
+    std::cout << "                            where it's black; grays blend (scaled to the input)\n";
+    std::cout << "  --mask-rect <x,y,w,h>     Dither only inside this rectangle of the input (repeatable)\n";
+    std::cout << "  --memory-limit <MB>       Dither huge images in bands to stay under this (0 = off, default: 4096)\n";
+    std::cout << "  --decode-limit <MB>       Refuse images that would need more than this to open (0 = off,\n";
+    std::cout << "                            default: 2048)\n";
+    std::cout << "  --downscale-huge          Open images over --decode-limit at the largest size that fits instead\n";
+    std::cout << "  --svg-width <px>          Width SVG inputs are rasterized at (default: the size they give)\n";
+    std::cout << "  --compare <file>          Measure how closely the result matches another image\n";
+    std::cout << "  --diff <file>             With --compare, save a heat map of where the tones differ\n";
+    std::cout << "  --quality                 Print PSNR, SSIM and mean CIEDE2000 of the result against the original\n";
+    std::cout << "  --quality-against <file>  Print the same metrics for the input against another image (the\n";
+    std::cout << "                            original or another result) and exit\n";
+    std::cout << "  --count-colors            List the colors the result uses; exits with 1 if it has more than\n";
+    std::cout << "                            the palette allows or colors that aren't in it\n";
+    std::cout << "  --svg <file.svg>          Also export halftone dots as vector shapes (halftone algorithm or the\n";
+    std::cout << "                            dots45, spiral and newspaper matrices)\n";
+    std::cout << "  --poster <file.pdf>       Also export the result as a multi-page poster PDF\n";
+    std::cout << "  --poster-paper <name>     Poster sheet size: letter, legal, tabloid, a4, a3 (default: letter)\n";
+    std::cout << "  --poster-landscape        Turn poster sheets sideways\n";
+    std::cout << "  --poster-width <inches>   Width of the assembled poster (default: one pixel per dot)\n";
+    std::cout << "  --poster-dpi <float>      Poster print resolution (default: 300)\n";
+    std::cout << "  --poster-overlap <inches> Image repeated on neighbouring sheets (default: 0.25)\n";
+    std::cout << "  --pdf <file.pdf>          Also export the result on one page at its printed size\n";
+    std::cout << "  --pdf-paper <name>        PDF page size: letter, legal, tabloid, a4, a3 (default: letter)\n";
+    std::cout << "  --pdf-landscape           Turn the PDF page sideways\n";
+    std::cout << "  --pdf-dpi <float>         Image pixels per inch on the page, also for --lpi; kept in saved\n";
+    std::cout << "                            settings (default: 300)\n";
+    std::cout << "  --pdf-vector              Draw halftone dots in the PDF as vector shapes (as with --svg)\n";
+    std::cout << "  --ascii <file.txt>        Also export ASCII art, one character per block (- prints it)\n";
+    std::cout << "  --ascii-image <file>      Also render the ASCII art as an image in a monospaced grid\n";
+    std::cout << "  --ascii-columns <int>     Characters per line (default: 80)\n";
+    std::cout << "  --ascii-ramp <chars>      Characters from lightest to densest (default: \" .:-=+*#%@\")\n";
+    std::cout << "  --ascii-invert            Dense characters for light areas, for light text on dark\n";
+    std::cout << "  --ascii-braille           Pack 2x4 dithered dots into each character with Unicode braille\n";
+    std::cout << "  --ansi <file>             Also export the result as ANSI colored half blocks (- prints it)\n";
+    std::cout << "  --ansi-colors <mode>      16, 256, truecolor (default: truecolor)\n";
+    std::cout << "  --ansi-columns <int>      Scale wider results down to this many characters (default: 80,\n";
+    std::cout << "                            0 = one per pixel)\n";
+    std::cout << "  --plot <file>             Also export dark pixels as pen plotter dots: .csv, .json, .gcode,\n";
+    std::cout << "                            .nc, .hpgl or .plt\n";
+    std::cout << "  --plot-width <mm>         Width of the plotted drawing (default: 200)\n";
+    std::cout << "  --plot-rows               Plot dots row by row instead of nearest-first\n";
+    std::cout << "  --plot-z <up>,<down>      G-code pen up and down heights in mm (default: 2,0)\n";
+    std::cout << "  --plot-feed <mm/min>      G-code pen down speed (default: 1500)\n";
+    std::cout << "  --tiles <file.dzi>        Also write a Deep Zoom pyramid of 256px PNG tiles, for panning and\n";
+    std::cout << "                            zooming very large results in a web viewer\n";
+    std::cout << "  --wallpaper <mode>        Also dither the input at the screen's resolution and make it the\n";
+    std::cout << "                            desktop wallpaper: fill (crop to cover), fit, center\n";
+    std::cout << "  --wallpaper-background <hex>\n";
+    std::cout << "                            Color around fit and center wallpapers (default: 000000)\n";
+    std::cout << "  --screen-size <WxH>       Wallpaper size (default: the primary display's resolution)\n";
+    std::cout << "  --progression <file>      Also animate the input being dithered, in the order error diffusion\n";
+    std::cout << "                            finishes pixels: .gif, .webp, .apng or a video (uses --fps, default\n";
+    std::cout << "                            20, and --upscale)\n";
+    std::cout << "  --progression-rows <n>    Rows finished between frames (default: fit --progression-seconds)\n";
+    std::cout << "  --progression-pixels <n>  Pixels finished between frames\n";
+    std::cout << "  --progression-seconds <s> Length before the result is held for 2 s (default: 6)\n";
+    std::cout << "  --embedded <file>         Also pack the result for a display: .h or .c (C array), .bin\n";
+    std::cout << "  --embedded-layout <name>  horizontal (1-bit rows), ssd1306 (8-row pages), gray2 (e-ink 2-bit)\n";
+    std::cout << "  --embedded-bit-order <o>  default, msb, lsb: where the first pixel goes in each byte (default:\n";
+    std::cout << "                            lsb for ssd1306, msb otherwise)\n";
+    std::cout << "  --embedded-invert         Set bits for dark pixels instead of light ones\n";
+    std::cout << "  --embedded-name <name>    C array name (default: from the file name)\n";
+    std::cout << "  --info                    Print image or video information and exit (no output file needed)\n";
+    std::cout << "  --extract-palette <int>   Print the palette of the image (or video) as hex for --colors and exit\n";
+    std::cout << "  --histogram <mode>        Print luma and R, G, B histograms as CSV and exit. original, or\n";
+    std::cout << "                            adjusted for after brightness, contrast, etc.\n";
+    std::cout << "  --contact-sheet <file>    Save a labeled grid of the input dithered with every algorithm\n";
+    std::cout << "                            (other settings apply) and exit\n";
+    std::cout << "  --contact-sheet-size <px> Size of each thumbnail in the grid (default: 256)\n";
+    std::cout << "  --montage <file>          Save the input dithered with each --variant on one sheet, the\n";
+    std::cout << "                            settings that differ captioned under each, and exit\n";
+    std::cout << "  --variant <name>          A preset, a settings file (.json) or current for the other\n";
+    std::cout << "                            options; repeat for each panel (at least two)\n";
+    std::cout << "  --montage-layout <mode>   row, column or grid (default: row)\n";
+    std::cout << "  --montage-size <px>       Longest side each panel is dithered at (default: 512, 0 = full)\n";
+    std::cout << "  --montage-original        Start the montage with the undithered input\n";
+    std::cout << "  --benchmark <all|a,b,..>  Time each algorithm on the input with the other settings, print\n";
+    std::cout << "                            speed and memory, and exit (videos: the first frame)\n";
+    std::cout << "  --benchmark-runs <int>    Runs per algorithm, the fastest counts (default: 1)\n";
+    std::cout << "  --max-download <MB>       Size limit when input is an http(s) URL (default: 64)\n";
+    std::cout << "  --settings <file.json>    Load settings; other options override them\n";
+    std::cout << "  --save-settings <file>    Save the final settings as JSON (no input needed)\n";
+    std::cout << "  --settings-schema         Print the JSON schema of settings files and exit\n";
+    std::cout << "  --preset <name>           Load a saved preset; other options override it\n";
+    std::cout << "  --save-preset <name>      Save the final settings as a named preset (no input needed)\n";
+    std::cout << "  --list-presets            Print the saved presets and exit\n";
+    std::cout << "  --list-algorithms         Print algorithms with their category and the options they use\n";
+    std::cout << "  --list-devices            Print the --device screens and exit\n";
+    std::cout << "  --list-plugins            Print plugin algorithms, and plugins that failed to load, and exit\n";
+    std::cout << "  --run-script <file> ...   Run a pipeline script with the arguments after it and exit;\n";
+    std::cout << "                            options before it set the settings it starts with\n";
+    std::cout << "  --delete-preset <name>    Delete a saved preset and exit\n";
+    std::cout << "  --serve                   Answer HTTP API requests on 127.0.0.1 until Ctrl+C; the other\n";
+    std::cout << "                            options are the settings for requests that send none\n";
+    std::cout << "  --serve-port <n>          Port for --serve (default: " << Automation::DEFAULT_PORT << ")\n";
+    std::cout << "  --serve-token <token>     Token requests must send (default: a random one, printed)\n";
+    std::cout << "  --stats                   Print local usage statistics and exit\n";
+    std::cout << "  --clear-stats             Delete local usage statistics and exit\n";
+    std::cout << "  --recent                  Print the files recently opened in the GUI and exit\n";
+    std::cout << "  --clear-recent            Forget recently opened files and their thumbnails, and exit\n";
+    std::cout << "\nVideo options (input is .mp4, .avi, .mov, .mkv, .webm, .m4v):\n";
+    std::cout << "  --fps <float>             Output frame rate (default: same as source)\n";
+    std::cout << "  --fps-mode <mode>         Frame rate conversion: nearest, blend (default: nearest)\n";
+    std::cout << "  --loop <mode>             Loop-friendly export: pingpong, seamless (default: off)\n";
+    std::cout << "  --start <time>            Process from this time, in seconds or [hh:]mm:ss (default: start)\n";
+    std::cout << "  --end <time>              Process up to this time (default: end of video)\n";
+    std::cout << "  --duration <time>         Process this long from --start, instead of --end\n";
+    std::cout << "  --rotate <degrees>        Override rotation: 0, 90, 180, 270 (default: from metadata)\n";
+    std::cout << "  --preview-frame <time>    Dither only the frame at this time and save it as an image\n";
+    std::cout << "  --lock-palette            Generate the adaptive palette once from sampled frames\n";
+    std::cout << "  --palette-samples <int>   Frames sampled across the clip for --lock-palette (default: 8)\n";
+    std::cout << "  --vary-seed               New noise pattern every frame (default: same seed for all frames)\n";
+    std::cout << "  --cycle-matrix            Shift ordered and blue noise patterns every frame (default: fixed)\n";
+    std::cout << "  --temporal-carry <f>      Experimental: carry this share of each frame's error into the next\n";
+    std::cout << "                            (0-1, default: 0); frames are then dithered one at a time\n";
+    std::cout << "  --cache <dir>             Reuse frames already dithered with the same settings\n";
+    std::cout << "  --resize <w>x<h>          Scale frames before dithering, e.g. 1280x for 4K sources\n";
+    std::cout << "  --scale <factor>          Or scale them by a factor, e.g. 0.5\n";
+    std::cout << "  --max-width <px>          Scale frames down to at most this width, e.g. 480 for GIFs to share\n";
+    std::cout << "  --dither-height <px>      Dither at this height (e.g. 480), then upscale with nearest-neighbour\n";
+    std::cout << "  --upscale <n>             Enlarge dithered frames n times with nearest-neighbour (default: 1)\n";
+    std::cout << "  --sprite-sheet            Save the frames in a grid as one image, plus a .json descriptor\n";
+    std::cout << "  --sheet-columns <int>     Frames per row of the sprite sheet (default: as square as possible)\n";
+    std::cout << "  --sheet-padding <px>      Transparent pixels between sprite sheet frames (default: 0)\n";
+    std::cout << "  --frame-step <n>          Keep every nth frame in the sprite sheet (default: 1)\n";
+    std::cout << "  --max-frames <int>        Stop the sprite sheet after this many frames (default: no limit)\n";
+    std::cout << "  --codec <name>            Video codec: opencv, h264, h265, vp9, av1, prores (default: opencv);\n";
+    std::cout << "                            all but opencv are encoded with ffmpeg\n";
+    std::cout << "  --hw-encoder <name>       Encode on the GPU: none, auto, nvenc, qsv, videotoolbox, amf\n";
+    std::cout << "                            (default: none; H.264 unless --codec is given, software if unavailable)\n";
+    std::cout << "  --detect-encoders         Test which hardware encoders work here, print them and exit\n";
+    std::cout << "  --check-ffmpeg            Print which ffmpeg and ffprobe are used, with versions, and exit\n";
+    std::cout << "  --set-ffmpeg-path <path>  Use the ffmpeg and ffprobe in this folder (or next to this binary)\n";
+    std::cout << "                            from now on; \"\" goes back to a bundled copy or the PATH\n";
+    std::cout << "  --crf <int>               Constant quality for ffmpeg codecs, lower is better (default: 16)\n";
+    std::cout << "  --bitrate <kbps>          Target bitrate instead of --crf\n";
+    std::cout << "  --encoder-preset <name>   x264/x265 speed preset, e.g. slow, veryslow (default: medium)\n";
+    std::cout << "  --pix-fmt <name>          Pixel format, e.g. yuv444p for sharp color dither (default: yuv420p)\n";
+    std::cout << "  --no-audio                Leave out the source's sound (default: copied with ffmpeg if installed)\n";
+    std::cout << "  --nice <int>              Run the job at lower priority (0-19, default: 0)\n";
+    std::cout << "  --threads <int>           Limit worker threads (default: all cores)\n";
+    std::cout << "  --jobs <int>              Frames dithered at the same time (default: --threads, or one per\n";
+    std::cout << "                            core up to 8)\n";
+    std::cout << "Videos saved as .gif, .webp or .apng become animated images (held in memory, for short clips).\n";
+    std::cout << "\nAnimated GIFs saved as .gif, .webp or .apng are dithered frame by frame, keeping frame delays\n";
+    std::cout << "and looping. --lock-palette, --vary-seed, --cycle-matrix and --dither-height apply; with\n";
+    std::cout << "--lock-palette (or a fixed palette) all frames share one palette.\n";
+    std::cout << "\nSlideshow options (input is a folder of images):\n";
+    std::cout << "  --slideshow               Assemble the folder's images into a video\n";
+    std::cout << "  --seconds-per-image <f>   Time each image is shown (default: 2)\n";
+    std::cout << "  --crossfade               Fade between images instead of cutting\n";
+    std::cout << "--fps and the encoder options above (--codec, --hw-encoder, --crf, ...) apply as for videos.\n";
+    std::cout << "\nScreen capture options (the only path given is the output):\n";
+    std::cout << "  --capture-screen          Dither a screenshot; output as a video, .gif, .webp or .apng\n";
+    std::cout << "                            records for --duration (default: 10 s) at --fps (default: 5)\n";
+    std::cout << "  --capture-region <rect>   Capture only this x,y,w,h rectangle of the screen\n";
+    std::cout << "\nText options (the only path given is the output):\n";
+    std::cout << "  --text <string>           Dither this text rendered as an image; \\n starts a new line\n";
+    std::cout << "  --font <name|file>        Font of the text (needs ImageMagick, default: its default)\n";
+    std::cout << "  --font-size <px>          Height of the text (default: 96)\n";
+    std::cout << "  --text-color <hex>        Color of the text (default: 000000)\n";
+    std::cout << "  --text-background <hex>   Color around the text (default: ffffff)\n";
+    std::cout << "\nBatch options (inputs are image files or folders, the last path is the output folder):\n";
+    std::cout << "  --batch                   Dither every input image into the output folder\n";
+    std::cout << "  --jobs <int>              Images processed at the same time (default: up to 4)\n";
+    std::cout << "  --format <ext>            Output format, e.g. png (default: same as each input)\n";
+    std::cout << "  --output-name <t>         Output name template (default: {name}.{ext}); fills in {name},\n";
+    std::cout << "                            {ext}, {algorithm}, {palette}, {colors}, {seed} and may\n";
+    std::cout << "                            contain folders, e.g. {algorithm}/{name}_{colors}c.{ext}\n";
+    std::cout << "  --watch                   Keep running and dither images as they appear in the\n";
+    std::cout << "                            input folder (stop with Ctrl+C)\n";
     std::cout << "  -h, --help                Show this help message\n\n";
 
     std::cout << "Algorithms:\n";
@@ -23,48 +350,111 @@ void printUsage(const char* program) {
     std::cout << "  sierra-two, sierra-lite, bayer-2x2, bayer-4x4, bayer-8x8,\n";
     std::cout << "  bayer-16x16, blue-noise, white-noise, random, pattern,\n";
     std::cout << "  dot-diffusion, riemersma, gradient, variable, ostromoukhov,\n";
-    std::cout << "  fan, shiau-fan, steven-pigeon\n\n";
+    std::cout << "  fan, shiau-fan, steven-pigeon, matrix, ign, halftone, threshold,\n";
+    std::cout << "  sauvola, niblack, bradley, structure, posterize, stipple, kernel\n";
+    std::cout << "  and plugin algorithm ids (see --list-plugins)\n\n";
+
+    std::cout << "Matrices (for -a matrix):\n";
+    for (const auto& info : Matrices::getMatrixLibrary()) {
+        std::printf("  %-26s%s\n", info.id.c_str(), info.description.c_str());
+    }
+    std::cout << "\n";
 
     std::cout << "Palettes:\n";
     std::cout << "  monochrome, gray4, gray8, gray16, cga, ega, vga,\n";
-    std::cout << "  gameboy, pico8\n\n";
+    std::cout << "  gameboy, pico8, custom, adaptive\n\n";
+
+    std::cout << "Script commands (for --run-script):\n";
+    for (const auto& command : Script::getCommands()) {
+        std::printf("  %-26s%s\n", command.usage.c_str(), command.description.c_str());
+    }
+    std::cout << "\n";
 
     std::cout << "Examples:\n";
     std::cout << "  " << program << " input.jpg output.png\n";
     std::cout << "  " << program << " -a atkinson -p gameboy input.jpg output.png\n";
     std::cout << "  " << program << " -a bayer-8x8 -p pico8 -s 1.5 input.jpg output.png\n";
+    std::cout << "  " << program << " -a bayer-4x4 --fps 12 input.mp4 output.mp4\n";
+    std::cout << "  " << program << " https://example.com/photo.jpg output.png\n";
+    std::cout << "  " << program << " --slideshow --crossfade dithered/ reel.mp4\n";
+    std::cout << "  " << program << " --capture-screen --fps 4 --duration 30 -p gameboy screen.gif\n";
+    std::cout << "  " << program << " --batch --format png -a atkinson photos/ dithered/\n";
+    std::cout << "  " << program << " --batch --watch --settings preset.json inbox/ dithered/\n";
+    std::cout << "  " << program << " --pixelate 4 --stage dither:bayer.json --stage-mix 0.5 input.jpg output.png\n";
+    std::cout << "  " << program << " --preset Dusk --run-script duotone.txt photos/\n";
+
+    std::cout << "\nExit status:\n";
+    std::cout << "  0 success, 1 invalid options or other errors, 2 input not found,\n";
+    std::cout << "  3 input can't be decoded, 4 unsupported format, 5 read/write error,\n";
+    std::cout << "  6 no video encoder, 7 out of memory. A batch with failures exits with\n";
+    std::cout << "  the status of the first failed file\n";
+}
+
+// Look up an algorithm by its command line name; false if there's none by that name
+bool findAlgorithm(const std::string& name, Dithering::Algorithm& algorithm) {
+    static const std::vector<std::pair<std::string, Dithering::Algorithm>> names = {
+        {"floyd-steinberg", Dithering::Algorithm::FLOYD_STEINBERG},
+        {"atkinson", Dithering::Algorithm::ATKINSON},
+        {"jarvis", Dithering::Algorithm::JARVIS_JUDICE_NINKE},
+        {"stucki", Dithering::Algorithm::STUCKI},
+        {"burkes", Dithering::Algorithm::BURKES},
+        {"sierra", Dithering::Algorithm::SIERRA},
+        {"sierra-two", Dithering::Algorithm::SIERRA_TWO_ROW},
+        {"sierra-lite", Dithering::Algorithm::SIERRA_LITE},
+        {"bayer-2x2", Dithering::Algorithm::ORDERED_BAYER_2X2},
+        {"bayer-4x4", Dithering::Algorithm::ORDERED_BAYER_4X4},
+        {"bayer-8x8", Dithering::Algorithm::ORDERED_BAYER_8X8},
+        {"bayer-16x16", Dithering::Algorithm::ORDERED_BAYER_16X16},
+        {"blue-noise", Dithering::Algorithm::BLUE_NOISE},
+        {"white-noise", Dithering::Algorithm::WHITE_NOISE},
+        {"random", Dithering::Algorithm::RANDOM_DITHER},
+        {"pattern", Dithering::Algorithm::PATTERN_DITHER},
+        {"dot-diffusion", Dithering::Algorithm::DOT_DIFFUSION},
+        {"riemersma", Dithering::Algorithm::RIEMERSMA},
+        {"gradient", Dithering::Algorithm::GRADIENT_BASED},
+        {"variable", Dithering::Algorithm::VARIABLE_ERROR_DIFFUSION},
+        {"ostromoukhov", Dithering::Algorithm::OSTROMOUKHOV},
+        {"fan", Dithering::Algorithm::FAN},
+        {"shiau-fan", Dithering::Algorithm::SHIAU_FAN},
+        {"steven-pigeon", Dithering::Algorithm::STEVENPIGEON},
+        {"matrix", Dithering::Algorithm::ORDERED_MATRIX},
+        {"ign", Dithering::Algorithm::INTERLEAVED_GRADIENT_NOISE},
+        {"halftone", Dithering::Algorithm::HALFTONE},
+        {"threshold", Dithering::Algorithm::THRESHOLD},
+        {"sauvola", Dithering::Algorithm::SAUVOLA},
+        {"niblack", Dithering::Algorithm::NIBLACK},
+        {"bradley", Dithering::Algorithm::BRADLEY},
+        {"structure", Dithering::Algorithm::STRUCTURE_AWARE},
+        {"posterize", Dithering::Algorithm::POSTERIZE},
+        {"none", Dithering::Algorithm::POSTERIZE},
+        {"stipple", Dithering::Algorithm::STIPPLE},
+        {"kernel", Dithering::Algorithm::CUSTOM_KERNEL},
+    };
+    for (const auto& entry : names) {
+        if (entry.first == name) {
+            algorithm = entry.second;
+            return true;
+        }
+    }
+    return false;
 }
 
 Dithering::Algorithm parseAlgorithm(const std::string& name) {
-    if (name == "floyd-steinberg") return Dithering::Algorithm::FLOYD_STEINBERG;
-    if (name == "atkinson") return Dithering::Algorithm::ATKINSON;
-    if (name == "jarvis") return Dithering::Algorithm::JARVIS_JUDICE_NINKE;
-    if (name == "stucki") return Dithering::Algorithm::STUCKI;
-    if (name == "burkes") return Dithering::Algorithm::BURKES;
-    if (name == "sierra") return Dithering::Algorithm::SIERRA;
-    if (name == "sierra-two") return Dithering::Algorithm::SIERRA_TWO_ROW;
-    if (name == "sierra-lite") return Dithering::Algorithm::SIERRA_LITE;
-    if (name == "bayer-2x2") return Dithering::Algorithm::ORDERED_BAYER_2X2;
-    if (name == "bayer-4x4") return Dithering::Algorithm::ORDERED_BAYER_4X4;
-    if (name == "bayer-8x8") return Dithering::Algorithm::ORDERED_BAYER_8X8;
-    if (name == "bayer-16x16") return Dithering::Algorithm::ORDERED_BAYER_16X16;
-    if (name == "blue-noise") return Dithering::Algorithm::BLUE_NOISE;
-    if (name == "white-noise") return Dithering::Algorithm::WHITE_NOISE;
-    if (name == "random") return Dithering::Algorithm::RANDOM_DITHER;
-    if (name == "pattern") return Dithering::Algorithm::PATTERN_DITHER;
-    if (name == "dot-diffusion") return Dithering::Algorithm::DOT_DIFFUSION;
-    if (name == "riemersma") return Dithering::Algorithm::RIEMERSMA;
-    if (name == "gradient") return Dithering::Algorithm::GRADIENT_BASED;
-    if (name == "variable") return Dithering::Algorithm::VARIABLE_ERROR_DIFFUSION;
-    if (name == "ostromoukhov") return Dithering::Algorithm::OSTROMOUKHOV;
-    if (name == "fan") return Dithering::Algorithm::FAN;
-    if (name == "shiau-fan") return Dithering::Algorithm::SHIAU_FAN;
-    if (name == "steven-pigeon") return Dithering::Algorithm::STEVENPIGEON;
+    Dithering::Algorithm algorithm;
+    if (findAlgorithm(name, algorithm)) return algorithm;
 
     std::cerr << "Unknown algorithm: " << name << ", using floyd-steinberg\n";
     return Dithering::Algorithm::FLOYD_STEINBERG;
 }
 
+// Display name of the algorithm in use, a plugin's if one is selected
+std::string getAlgorithmLabel(const Dithering::Parameters& params) {
+    for (const auto& info : Plugins::getAlgorithms()) {
+        if (info.id == params.plugin) return info.name + " (plugin)";
+    }
+    return Dithering::getAlgorithmName(params.algorithm);
+}
+
 Dithering::PaletteMode parsePalette(const std::string& name) {
     if (name == "monochrome") return Dithering::PaletteMode::MONOCHROME;
     if (name == "gray4") return Dithering::PaletteMode::GRAYSCALE_4;
@@ -75,23 +465,301 @@ Dithering::PaletteMode parsePalette(const std::string& name) {
     if (name == "vga") return Dithering::PaletteMode::VGA;
     if (name == "gameboy") return Dithering::PaletteMode::GAMEBOY;
     if (name == "pico8") return Dithering::PaletteMode::PICO8;
+    if (name == "custom") return Dithering::PaletteMode::CUSTOM;
+    if (name == "adaptive") return Dithering::PaletteMode::ADAPTIVE;
 
     std::cerr << "Unknown palette: " << name << ", using monochrome\n";
     return Dithering::PaletteMode::MONOCHROME;
 }
 
-int main(int argc, char** argv) {
+bool parseHexColor(const std::string& hex, cv::Vec3b& color) {
+    std::string digits = (!hex.empty() && hex[0] == '#') ? hex.substr(1) : hex;
+    if (digits.size() != 6 || digits.find_first_not_of("0123456789abcdefABCDEF") != std::string::npos) {
+        return false;
+    }
+
+    unsigned long rgb = std::stoul(digits, nullptr, 16);
+    color = cv::Vec3b(
+        static_cast<uchar>(rgb & 0xFF),
+        static_cast<uchar>((rgb >> 8) & 0xFF),
+        static_cast<uchar>((rgb >> 16) & 0xFF)
+    );
+    return true;
+}
+
+// Parse a comma-separated list of hex colors, skipping invalid entries
+std::vector<cv::Vec3b> parseHexColors(const std::string& list) {
+    std::vector<cv::Vec3b> colors;
+    size_t start = 0;
+    while (start <= list.size()) {
+        size_t comma = list.find(',', start);
+        std::string hex = list.substr(start, comma - start);
+        cv::Vec3b color;
+        if (parseHexColor(hex, color)) {
+            colors.push_back(color);
+        } else if (!hex.empty()) {
+            std::cerr << "Invalid palette color: " << hex << ", ignoring\n";
+        }
+        if (comma == std::string::npos) break;
+        start = comma + 1;
+    }
+    return colors;
+}
+
+// Parse "<x>,<y>,<w>,<h>" (missing values are 0)
+cv::Rect parseRect(const std::string& value) {
+    int values[4] = {0, 0, 0, 0};
+    size_t start = 0;
+    for (int v = 0; v < 4 && start <= value.size(); ++v) {
+        size_t comma = value.find(',', start);
+        values[v] = std::stoi(value.substr(start, comma - start));
+        if (comma == std::string::npos) break;
+        start = comma + 1;
+    }
+    return cv::Rect(values[0], values[1], values[2], values[3]);
+}
+
+// Print a path validation error; returns whether the path is usable
+bool checkPath(const Platform::PathCheck& check) {
+    if (!check.ok()) {
+        std::cerr << "Error: " << check.message << "\n";
+    }
+    return check.ok();
+}
+
+// Print the metrics of --quality and --quality-against
+void printQuality(const Compare::Quality& quality) {
+    std::printf("  PSNR:              %.2f dB\n", quality.psnr);
+    std::printf("  SSIM:              %.4f\n", quality.ssim);
+    std::printf("  Mean CIEDE2000:    %.2f\n", quality.deltaE);
+}
+
+// Print the report of --count-colors, the most used colors first
+void printColorReport(const Compare::ColorReport& report) {
+    const size_t maxListed = 64;
+    double total = 0.0;
+    for (const auto& count : report.colors) total += count.pixels;
+    std::printf("Output colors: %zu", report.colors.size());
+    if (report.limit > 0) std::printf(" (palette allows %d)", report.limit);
+    std::printf("\n");
+    for (size_t i = 0; i < report.colors.size() && i < maxListed; ++i) {
+        const Compare::ColorCount& count = report.colors[i];
+        std::printf("  #%02x%02x%02x  %10d px  %6.2f%%%s\n", count.color[2], count.color[1], count.color[0],
+                    count.pixels, 100.0 * count.pixels / total, count.inPalette ? "" : "  not in palette");
+    }
+    if (report.colors.size() > maxListed) std::printf("  ... and %zu more\n", report.colors.size() - maxListed);
+}
+
+// Print where ffmpeg and ffprobe are run from; false if ffmpeg wasn't found
+bool printFfmpegTools() {
+    std::string configured = Platform::getFfmpegPath();
+    if (!configured.empty()) std::cout << "Configured path: " << configured << "\n";
+    bool found = true;
+    for (const char* name : {"ffmpeg", "ffprobe"}) {
+        Platform::ToolInfo tool = Platform::checkFfmpegTool(name);
+        if (tool.path.empty()) {
+            std::printf("  %-10snot found\n", name);
+            if (tool.name == "ffmpeg") found = false;
+            continue;
+        }
+        std::printf("  %-10s%-14s%s (%s)\n", name, tool.version.empty() ? "unknown" : tool.version.c_str(),
+                    tool.path.c_str(), tool.source.c_str());
+    }
+    if (!found) {
+        std::cout << "Without ffmpeg, videos use OpenCV's MPEG-4 writer and are silent. Install it, or point\n"
+                     "--set-ffmpeg-path at a copy\n";
+    }
+    return found;
+}
+
+// Print the algorithms plugins registered and the libraries that failed to load
+void printPlugins() {
+    std::cout << "Plugin folder: " << Plugins::getPluginDir() << "\n";
+    const auto& algorithms = Plugins::getAlgorithms();
+    if (algorithms.empty()) std::cout << "No plugin algorithms\n";
+    for (const auto& info : algorithms) {
+        std::printf("  %-18s%-28s%s\n", info.id.c_str(), info.name.c_str(), info.description.c_str());
+        std::printf("  %-18s%s\n", "", info.library.c_str());
+    }
+    for (const auto& error : Plugins::getLoadErrors()) std::cerr << "Warning: " << error << "\n";
+}
+
+// Print the locally recorded usage statistics
+void printUsageStats() {
+    UsageStats::Stats stats = UsageStats::getUsageStats();
+
+    std::cout << "Usage statistics (stored locally in " << UsageStats::getStatsPath() << ")\n";
+    std::cout << "Images processed: " << stats.imagesProcessed << "\n";
+    std::cout << "Videos processed: " << stats.videosProcessed << "\n";
+    if (stats.jobs() == 0) return;
+
+    std::cout << "Average size: " << stats.averageMegapixels() << " MP\n";
+    std::cout << "Average job time: " << stats.averageSeconds() << " s\n";
+
+    // Most used algorithms first
+    std::vector<std::pair<std::string, int>> algorithms(stats.algorithmCounts.begin(),
+                                                        stats.algorithmCounts.end());
+    std::sort(algorithms.begin(), algorithms.end(),
+              [](const auto& a, const auto& b) { return a.second > b.second; });
+    std::cout << "Algorithms:\n";
+    for (const auto& [name, count] : algorithms) {
+        std::cout << "  " << name << ": " << count << "\n";
+    }
+}
+
+void printRecentFiles() {
+    std::vector<RecentFiles::RecentFile> files = RecentFiles::getRecentFiles();
+    if (files.empty()) {
+        std::cout << "No recent files\n";
+        return;
+    }
+    std::cout << "Recent files (newest first, thumbnails in " << RecentFiles::getThumbnailDir() << "):\n";
+    for (const auto& recent : files) {
+        std::cout << "  " << recent.path << "\n";
+    }
+}
+
+// Set by Ctrl+C while a video, animation or slideshow is written
+static std::atomic<bool> interrupted{false};
+
+// Stage, frames, speed and time left for videos, animations and slideshows on
+// one line; false stops the job after Ctrl+C
+bool printFrameProgress(const Video::Progress& progress) {
+    char line[128];
+    if (progress.etaSeconds < 0.0) {
+        std::snprintf(line, sizeof(line), "%s...", progress.stage.c_str());
+    } else {
+        int eta = static_cast<int>(progress.etaSeconds + 0.5);
+        std::snprintf(line, sizeof(line), "%s: frame %d / %d, %.1f fps, %d:%02d left", progress.stage.c_str(),
+                      progress.frame, progress.totalFrames, progress.fps, eta / 60, eta % 60);
+    }
+    std::printf("\r%-64s", line);
+    std::fflush(stdout);
+    return !interrupted;
+}
+
+// Everything main does. parsing holds the argument being read, so main can
+// name it when its value is malformed
+static int run(int argc, char** argv, std::string& parsing) {
     if (argc < 2) {
         printUsage(argv[0]);
         return 1;
     }
 
     Dithering::Parameters params;
+    int bayerSize = 0;                      // --bayer-size, wins over the size in the -a name
+    ImageIO::ExportOptions exportOptions;
+    Video::Options videoOptions;
+    Video::SlideshowOptions slideshowOptions;
+    bool slideshow = false;
+    bool screenCapture = false;
+    cv::Rect captureRegion;                 // Empty = the whole screen
+    std::string text;                       // --text, rendered as the input
+    ImageIO::TextOptions textOptions;
+    Batch::Options batchOptions;
+    bool batch = false;
+    bool watch = false;
+    std::vector<std::string> extraFiles;    // Further inputs for --batch
+    bool separations = false;
+    bool ditherAlpha = false;
     std::string inputFile, outputFile;
+    bool infoOnly = false;
+    int extractColors = 0;
+    std::string exportPaletteFile;
+    std::string lospecQuery;
+    int lospecColors = 0;
+    std::string histogram;     // "original" or "adjusted", empty = off
+    std::string contactSheetFile;
+    int contactSheetSize = 256;
+    std::string montageFile;
+    std::vector<std::string> montageVariants;     // Preset names, settings files or "current"
+    Compare::MontageOptions montageOptions;
+    bool benchmark = false;
+    std::vector<Dithering::Algorithm> benchmarkAlgorithms;    // Empty = all
+    int benchmarkRuns = 1;
+    cv::Rect tile;
+    int tileMargin = 32;
+    size_t memoryLimitMB = 4096;
+    std::string maskFile;
+    std::vector<cv::Rect> maskRects;
+    cv::Rect crop;
+    cv::Size resize;
+    Transform::Filter resizeFilter = Transform::Filter::AREA;
+    int rotation = 0;
+    std::vector<Transform::FlipAxis> flips;
+    const Devices::DeviceInfo* device = nullptr;
+    bool descreen = false;
+    bool reconstruct = false;
+    Descreen::Options descreenOptions;
+    std::string paletteReference;
+    std::string compareFile, diffFile;
+    bool quality = false;
+    bool countColors = false;
+    std::string qualityFile;
+    std::string posterFile;
+    std::string svgFile;
+    std::string saveSettingsFile;
+    std::string savePresetName;
+    bool serve = false;
+    Automation::Options serverOptions;
+    Poster::Options posterOptions;
+    std::string pdfFile;
+    Poster::PrintOptions printOptions;
+    bool pdfVector = false;
+    std::string asciiFile, asciiImageFile;
+    Ascii::Options asciiOptions;
+    std::string ansiFile;
+    Ascii::AnsiColors ansiColors = Ascii::AnsiColors::TRUECOLOR;
+    int ansiColumns = 80;
+    std::string plotFile;
+    Plotter::Options plotOptions;
+    std::string embeddedFile;
+    Embedded::Options embeddedOptions;
+    std::string tilesFile;
+    std::string progressionFile;
+    Video::ProgressionOptions progressionOptions;
+    bool setWallpaper = false;
+    Wallpaper::Options wallpaperOptions;
+    size_t maxDownloadBytes = ImageIO::DEFAULT_MAX_DOWNLOAD_BYTES;
+    double clipDuration = 0.0;
+    double previewSeconds = -1.0;
+    std::string kernelText;
+
+    // Settings files and presets go first, in the order given, so options
+    // override them wherever they appear on the command line
+    for (int i = 1; i + 1 < argc; ++i) {
+        std::string arg = argv[i];
+        if (arg == "--run-script") break;   // The rest are the script's arguments
+        if (arg == "--settings") {
+            std::string path = argv[++i];
+            std::string error;
+            std::vector<std::string> warnings;
+            if (!checkPath(Platform::validateInputFile(path, {"json"})) ||
+                !Settings::loadSettings(path, params, &error, &warnings)) {
+                if (!error.empty()) std::cerr << "Error: " << path << ": " << error << "\n";
+                return 1;
+            }
+            for (const auto& warning : warnings) {
+                std::cerr << "Warning: " << path << ": " << warning << "\n";
+            }
+        } else if (arg == "--preset") {
+            std::string name = argv[++i];
+            std::string error;
+            std::vector<std::string> warnings;
+            if (!Settings::loadPreset(name, params, &error, &warnings)) {
+                std::cerr << "Error: " << error << "\n";
+                return 1;
+            }
+            for (const auto& warning : warnings) {
+                std::cerr << "Warning: preset \"" << name << "\": " << warning << "\n";
+            }
+        }
+    }
 
     // Parse arguments
     for (int i = 1; i < argc; ++i) {
         std::string arg = argv[i];
+        parsing = arg;
 
         if (arg == "-h" || arg == "--help") {
             printUsage(argv[0]);
@@ -99,19 +767,201 @@ int main(int argc, char** argv) {
         }
         else if (arg == "-a" || arg == "--algorithm") {
             if (i + 1 < argc) {
-                params.algorithm = parseAlgorithm(argv[++i]);
+                std::string name = argv[++i];
+                params.plugin = Plugins::hasAlgorithm(name) ? name : "";
+                if (params.plugin.empty()) params.algorithm = parseAlgorithm(name);
+                if (params.plugin.empty() && Dithering::getBayerSize(params.algorithm) > 0 && bayerSize == 0) {
+                    params.bayerSize = Dithering::getBayerSize(params.algorithm);
+                }
+            }
+        }
+        else if (arg == "--bayer-size") {
+            if (i + 1 < argc) {
+                bayerSize = std::clamp(std::stoi(argv[++i]), 2, Dithering::MAX_BAYER_SIZE);
+                params.bayerSize = bayerSize;
+                if (Dithering::getBayerSize(params.algorithm) == 0) {
+                    params.algorithm = Dithering::Algorithm::ORDERED_BAYER_8X8;
+                }
+            }
+        }
+        else if (arg == "--clustered") {
+            params.bayerClustered = true;
+        }
+        else if (arg == "--matrix-angle") {
+            if (i + 1 < argc) {
+                params.ditherAngle = std::stof(argv[++i]);
+            }
+        }
+        else if (arg == "--plugin-option") {
+            if (i + 1 < argc) {
+                std::string option = argv[++i];
+                size_t equals = option.find('=');
+                if (equals == std::string::npos || equals == 0) {
+                    std::cerr << "Error: Invalid plugin option: " << option << ", expected key=value\n";
+                    return Errors::getExitCode(Errors::ErrorCode::INVALID_ARGUMENT);
+                }
+                params.pluginOptions[option.substr(0, equals)] = option.substr(equals + 1);
+            }
+        }
+        else if (arg == "--matrix") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (Matrices::findMatrix(name)) {
+                    params.matrix = name;
+                    params.algorithm = Dithering::Algorithm::ORDERED_MATRIX;
+                } else {
+                    std::cerr << "Unknown matrix: " << name << ", using " << params.matrix << "\n";
+                }
+            }
+        }
+        else if (arg == "--pattern-matrix") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                bool ok = (!value.empty() && value[0] == '[')
+                    ? Matrices::parseMatrix(value, params.patternMatrix)
+                    : Matrices::loadMatrixImage(value, params.patternMatrix);
+                if (ok) {
+                    params.algorithm = Dithering::Algorithm::PATTERN_DITHER;
+                } else {
+                    std::cerr << "Invalid pattern matrix: " << value << ", using the built-in 4x4\n";
+                }
+            }
+        }
+        else if (arg == "--noise-texture") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                cv::Mat texture;
+                std::string error;
+                if (Matrices::loadNoiseTexture(value, texture, error)) {
+                    params.noiseTexture = value;
+                    params.algorithm = Dithering::Algorithm::BLUE_NOISE;
+                } else {
+                    std::cerr << "Invalid noise texture: " << error << ", using the generated one\n";
+                }
+            }
+        }
+        else if (arg == "--kernel") {
+            if (i + 1 < argc) {
+                // Checked after the loop, as --kernel-raw may come after it
+                kernelText = argv[++i];
+                params.algorithm = Dithering::Algorithm::CUSTOM_KERNEL;
             }
         }
+        else if (arg == "--kernel-raw") {
+            params.normalizeKernel = false;
+        }
         else if (arg == "-p" || arg == "--palette") {
             if (i + 1 < argc) {
                 params.paletteMode = parsePalette(argv[++i]);
             }
         }
+        else if (arg == "--colors") {
+            if (i + 1 < argc) {
+                params.customPalette = parseHexColors(argv[++i]);
+                params.paletteMode = Dithering::PaletteMode::CUSTOM;
+            }
+        }
+        else if (arg == "--palette-file") {
+            if (i + 1 < argc) {
+                Errors::Error error;
+                if (!PaletteFile::loadPalette(argv[++i], params.customPalette, &error)) {
+                    std::cerr << "Error: " << error.message << "\n";
+                    return Errors::getExitCode(error.code);
+                }
+                params.paletteMode = Dithering::PaletteMode::CUSTOM;
+            }
+        }
+        else if (arg == "--protect") {
+            if (i + 1 < argc) {
+                params.protectedColors = parseHexColors(argv[++i]);
+            }
+        }
+        else if (arg == "--inks") {
+            if (i + 1 < argc) {
+                params.inks = parseHexColors(argv[++i]);
+                if (params.inks.size() < 2 || params.inks.size() > 3) {
+                    std::cerr << "Error: --inks takes 2 or 3 colors\n";
+                    return 1;
+                }
+                params.colorMode = Dithering::ColorMode::MULTI_INK;
+            }
+        }
+        else if (arg == "--palette-size") {
+            if (i + 1 < argc) {
+                params.paletteSize = std::clamp(std::stoi(argv[++i]), 2, 256);
+            }
+        }
+        else if (arg == "--palette-method") {
+            if (i + 1 < argc) {
+                std::string method = argv[++i];
+                if (method == "kmeans") {
+                    params.paletteMethod = Dithering::PaletteMethod::KMEANS;
+                } else if (method == "median-cut") {
+                    params.paletteMethod = Dithering::PaletteMethod::MEDIAN_CUT;
+                } else {
+                    std::cerr << "Unknown palette method: " << method << ", using kmeans\n";
+                }
+            }
+        }
+        else if (arg == "--palette-from") {
+            if (i + 1 < argc) {
+                paletteReference = argv[++i];
+                params.paletteMode = Dithering::PaletteMode::ADAPTIVE;
+            }
+        }
         else if (arg == "-s" || arg == "--strength") {
             if (i + 1 < argc) {
                 params.strength = std::stof(argv[++i]);
             }
         }
+        else if (arg == "--threshold") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                params.autoThreshold = (value == "auto");
+                if (!params.autoThreshold) {
+                    params.threshold = std::clamp(std::stof(value), 0.0f, 1.0f);
+                }
+            }
+        }
+        else if (arg == "--local-window") {
+            if (i + 1 < argc) {
+                params.localWindow = std::clamp(std::stoi(argv[++i]), 3, 255);
+            }
+        }
+        else if (arg == "--local-k") {
+            if (i + 1 < argc) {
+                params.localK = std::clamp(std::stof(argv[++i]), -1.0f, 1.0f);
+            }
+        }
+        else if (arg == "--curve") {
+            if (i + 1 < argc) {
+                std::string curve = argv[++i];
+                if (curve == "hilbert") {
+                    params.curve = Dithering::CurveType::HILBERT;
+                } else if (curve == "peano") {
+                    params.curve = Dithering::CurveType::PEANO;
+                } else if (curve == "z-order") {
+                    params.curve = Dithering::CurveType::Z_ORDER;
+                } else {
+                    std::cerr << "Unknown curve: " << curve << ", using hilbert\n";
+                }
+            }
+        }
+        else if (arg == "--riemersma-history") {
+            if (i + 1 < argc) {
+                params.riemersmaHistory = std::clamp(std::stoi(argv[++i]), 1, 256);
+            }
+        }
+        else if (arg == "--riemersma-decay") {
+            if (i + 1 < argc) {
+                params.riemersmaDecay = std::clamp(std::stof(argv[++i]), 1.0f, 256.0f);
+            }
+        }
+        else if (arg == "--edge-sensitivity") {
+            if (i + 1 < argc) {
+                params.edgeSensitivity = std::clamp(std::stof(argv[++i]), 0.0f, 4.0f);
+            }
+        }
         else if (arg == "-g" || arg == "--gamma") {
             if (i + 1 < argc) {
                 params.gamma = std::stof(argv[++i]);
@@ -127,61 +977,2579 @@ int main(int argc, char** argv) {
                 params.brightness = std::stof(argv[++i]);
             }
         }
+        else if (arg == "--exposure") {
+            if (i + 1 < argc) {
+                params.exposure = std::clamp(std::stof(argv[++i]), -4.0f, 4.0f);
+            }
+        }
+        else if (arg == "--denoise") {
+            if (i + 1 < argc) {
+                std::string method = argv[++i];
+                if (method == "none") {
+                    params.denoise = Dithering::Denoise::NONE;
+                } else if (method == "median") {
+                    params.denoise = Dithering::Denoise::MEDIAN;
+                } else if (method == "bilateral") {
+                    params.denoise = Dithering::Denoise::BILATERAL;
+                } else {
+                    std::cerr << "Unknown denoise method: " << method << ", not denoising\n";
+                }
+            }
+        }
+        else if (arg == "--denoise-strength") {
+            if (i + 1 < argc) {
+                params.denoiseStrength = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--blur") {
+            if (i + 1 < argc) {
+                params.blurRadius = std::clamp(std::stof(argv[++i]), 0.0f, 50.0f);
+            }
+        }
+        else if (arg == "--sharpen") {
+            if (i + 1 < argc) {
+                params.sharpenAmount = std::clamp(std::stof(argv[++i]), 0.0f, 4.0f);
+            }
+        }
+        else if (arg == "--sharpen-radius") {
+            if (i + 1 < argc) {
+                params.sharpenRadius = std::clamp(std::stof(argv[++i]), 0.1f, 50.0f);
+            }
+        }
         else if (arg == "--saturation") {
             if (i + 1 < argc) {
                 params.saturation = std::stof(argv[++i]);
             }
         }
+        else if (arg == "--levels") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                float black = 0.0f, midtone = 1.0f, white = 255.0f;
+                if (std::sscanf(value.c_str(), "%f,%f,%f", &black, &midtone, &white) == 3 &&
+                    black >= 0.0f && white <= 255.0f && black < white && midtone > 0.0f) {
+                    params.levelsBlack = black / 255.0f;
+                    params.levelsWhite = white / 255.0f;
+                    params.levelsMidtone = std::clamp(midtone, 0.1f, 10.0f);
+                } else {
+                    std::cerr << "Invalid levels: " << value << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--curve") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                std::string error;
+                if (!Dithering::parseToneCurve(value, params.toneCurve, error)) {
+                    std::cerr << "Invalid tone curve: " << error << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--clip-black") {
+            if (i + 1 < argc) {
+                params.clipBlack = std::clamp(std::stof(argv[++i]), 0.0f, 100.0f);
+            }
+        }
+        else if (arg == "--clip-white") {
+            if (i + 1 < argc) {
+                params.clipWhite = std::clamp(std::stof(argv[++i]), 0.0f, 100.0f);
+            }
+        }
+        else if (arg == "--protect-highlights") {
+            if (i + 1 < argc) {
+                params.highlightProtect = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--protect-shadows") {
+            if (i + 1 < argc) {
+                params.shadowProtect = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--dot-gain") {
+            if (i + 1 < argc) {
+                params.dotGain = std::clamp(std::stof(argv[++i]), 0.0f, 45.0f);
+            }
+        }
+        else if (arg == "--rounding") {
+            if (i + 1 < argc) {
+                std::string mode = argv[++i];
+                if (mode == "nearest") {
+                    params.rounding = Dithering::RoundingMode::NEAREST;
+                } else if (mode == "floor") {
+                    params.rounding = Dithering::RoundingMode::FLOOR;
+                } else if (mode == "ceil") {
+                    params.rounding = Dithering::RoundingMode::CEIL;
+                } else if (mode == "stochastic") {
+                    params.rounding = Dithering::RoundingMode::STOCHASTIC;
+                } else {
+                    std::cerr << "Unknown rounding mode: " << mode << ", using nearest\n";
+                }
+            }
+        }
+        else if (arg == "--distance") {
+            if (i + 1 < argc) {
+                std::string metric = argv[++i];
+                if (metric == "rgb") {
+                    params.colorDistance = Dithering::ColorDistance::RGB;
+                } else if (metric == "weighted-rgb") {
+                    params.colorDistance = Dithering::ColorDistance::WEIGHTED_RGB;
+                } else if (metric == "cie76") {
+                    params.colorDistance = Dithering::ColorDistance::CIE76;
+                } else if (metric == "ciede2000") {
+                    params.colorDistance = Dithering::ColorDistance::CIEDE2000;
+                } else {
+                    std::cerr << "Unknown color distance: " << metric << ", using rgb\n";
+                }
+            }
+        }
+        else if (arg == "--gray") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                const char* names[] = {"rec601", "rec709", "average", "red", "green", "blue"};
+                int index = 0;
+                while (index < 6 && name != names[index]) index++;
+                if (index < 6) {
+                    params.grayConversion = static_cast<Dithering::GrayConversion>(index);
+                } else {
+                    std::cerr << "Unknown gray conversion: " << name << ", using rec601\n";
+                }
+            }
+        }
+        else if (arg == "--gray-weights") {
+            if (i + 1 < argc) {
+                // Given in R, G, B order; stored per B, G, R channel
+                std::string value = argv[++i];
+                float weights[3] = {0.0f, 0.0f, 0.0f};
+                size_t start = 0;
+                for (int w = 0; w < 3 && start <= value.size(); ++w) {
+                    size_t comma = value.find(',', start);
+                    weights[w] = std::max(0.0f, std::stof(value.substr(start, comma - start)));
+                    if (comma == std::string::npos) break;
+                    start = comma + 1;
+                }
+                params.grayWeights = cv::Vec3f(weights[2], weights[1], weights[0]);
+                params.grayConversion = Dithering::GrayConversion::CUSTOM;
+            }
+        }
+        else if (arg == "--working-space") {
+            if (i + 1 < argc) {
+                std::string space = argv[++i];
+                if (space == "rgb") {
+                    params.workingSpace = Dithering::WorkingSpace::RGB;
+                } else if (space == "lab") {
+                    params.workingSpace = Dithering::WorkingSpace::LAB;
+                } else if (space == "ycbcr") {
+                    params.workingSpace = Dithering::WorkingSpace::YCRCB;
+                } else if (space == "hsl") {
+                    params.workingSpace = Dithering::WorkingSpace::HSL;
+                } else {
+                    std::cerr << "Unknown working space: " << space << ", using rgb\n";
+                }
+            }
+        }
+        else if (arg == "--intent") {
+            if (i + 1 < argc) {
+                std::string intent = argv[++i];
+                if (intent == "relative") {
+                    params.renderingIntent = Dithering::RenderingIntent::RELATIVE_COLORIMETRIC;
+                } else if (intent == "perceptual") {
+                    params.renderingIntent = Dithering::RenderingIntent::PERCEPTUAL;
+                } else {
+                    std::cerr << "Unknown rendering intent: " << intent << ", using relative\n";
+                }
+            }
+        }
+        else if (arg == "--honor-profiles") {
+            ImageIO::setColorManagement(ImageIO::ColorManagement::HONOR_PROFILES);
+        }
+        else if (arg == "--profile-space") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                ColorProfile::Space space = ColorProfile::Space::SRGB;
+                if (!ColorProfile::parseSpace(name, space)) {
+                    std::cerr << "Unknown profile space: " << name << ", using srgb\n";
+                }
+                ImageIO::setProfileSpace(space);
+            }
+        }
+        else if (arg == "--linear") {
+            params.linearize = true;
+        }
         else if (arg == "--serpentine") {
             params.serpentine = 1.0f;
         }
-        else if (arg == "--seed") {
+        else if (arg == "--error-edge") {
             if (i + 1 < argc) {
-                params.seed = std::stoi(argv[++i]);
+                std::string mode = argv[++i];
+                if (mode == "discard") {
+                    params.errorEdge = Dithering::ErrorEdge::DISCARD;
+                } else if (mode == "reflect") {
+                    params.errorEdge = Dithering::ErrorEdge::REFLECT;
+                } else if (mode == "wrap") {
+                    params.errorEdge = Dithering::ErrorEdge::WRAP;
+                } else {
+                    std::cerr << "Unknown error edge: " << mode << ", using discard\n";
+                }
             }
         }
-        else if (inputFile.empty()) {
-            inputFile = arg;
-        }
-        else if (outputFile.empty()) {
-            outputFile = arg;
+        else if (arg == "--no-error-clamp") {
+            params.clampError = false;
         }
-    }
-
-    if (inputFile.empty() || outputFile.empty()) {
-        std::cerr << "Error: Input and output files are required\n";
-        printUsage(argv[0]);
-        return 1;
-    }
-
-    // Load image
-    std::cout << "Loading " << inputFile << "...\n";
-    cv::Mat input = cv::imread(inputFile, cv::IMREAD_COLOR);
-    if (input.empty()) {
-        std::cerr << "Error: Could not load image: " << inputFile << "\n";
-        return 1;
-    }
-
-    std::cout << "Image size: " << input.cols << "x" << input.rows << "\n";
-    std::cout << "Algorithm: " << Dithering::getAlgorithmName(params.algorithm) << "\n";
-    std::cout << "Palette: " << Dithering::getPaletteModeName(params.paletteMode) << "\n";
-
-    // Process image
-    std::cout << "Processing...\n";
-    auto start = std::chrono::high_resolution_clock::now();
-    cv::Mat output = Dithering::ditherImage(input, params);
-    auto end = std::chrono::high_resolution_clock::now();
+        else if (arg == "--channel") {
+            if (i + 1 < argc) {
+                // <plane>:<algorithm>[:strength[:threshold]]; the plane name picks the space
+                std::string value = argv[++i];
+                std::vector<std::string> fields;
+                size_t start = 0;
+                while (true) {
+                    size_t colon = value.find(':', start);
+                    fields.push_back(value.substr(start, colon - start));
+                    if (colon == std::string::npos) break;
+                    start = colon + 1;
+                }
 
-    float elapsed = std::chrono::duration<float, std::milli>(end - start).count();
-    std::cout << "Processing time: " << elapsed << " ms\n";
+                const std::vector<std::string> bgrPlanes = {"b", "g", "r"};
+                const std::vector<std::string> ycrcbPlanes = {"y", "cr", "cb"};
+                auto bgrIt = std::find(bgrPlanes.begin(), bgrPlanes.end(), fields[0]);
+                auto ycrcbIt = std::find(ycrcbPlanes.begin(), ycrcbPlanes.end(), fields[0]);
+                int plane;
+                Dithering::ChannelSpace space;
+                if (bgrIt != bgrPlanes.end()) {
+                    plane = static_cast<int>(bgrIt - bgrPlanes.begin());
+                    space = Dithering::ChannelSpace::BGR;
+                } else if (ycrcbIt != ycrcbPlanes.end()) {
+                    plane = static_cast<int>(ycrcbIt - ycrcbPlanes.begin());
+                    space = Dithering::ChannelSpace::YCRCB;
+                } else {
+                    std::cerr << "Unknown channel: " << fields[0] << ", expected b, g, r, y, cb or cr\n";
+                    return 1;
+                }
 
-    // Save image
-    std::cout << "Saving to " << outputFile << "...\n";
-    if (!cv::imwrite(outputFile, output)) {
-        std::cerr << "Error: Could not save image: " << outputFile << "\n";
-        return 1;
-    }
+                // A space set by --settings, --preset or an earlier --channel was chosen on
+                // purpose, and any overrides made for it now land on other planes
+                bool chosen = params.channelSpace != Dithering::ChannelSpace::BGR;
+                for (const auto& channel : params.channelOverrides) chosen = chosen || channel.enabled;
+                if (space != params.channelSpace && chosen) {
+                    auto spaceName = [](Dithering::ChannelSpace s) {
+                        return s == Dithering::ChannelSpace::YCRCB ? "Y/Cr/Cb" : "B/G/R";
+                    };
+                    std::cerr << "Warning: --channel " << fields[0] << " switches the channel planes from "
+                              << spaceName(params.channelSpace) << " to " << spaceName(space)
+                              << "; earlier channel overrides now apply to " << spaceName(space) << "\n";
+                }
+                params.channelSpace = space;
 
-    std::cout << "Done!\n";
-    return 0;
+                Dithering::ChannelOverride& channel = params.channelOverrides[plane];
+                channel.enabled = true;
+                channel.algorithm = fields.size() > 1 ? parseAlgorithm(fields[1]) : params.algorithm;
+                if (fields.size() > 2) channel.strength = std::clamp(std::stof(fields[2]), 0.0f, 2.0f);
+                if (fields.size() > 3) channel.threshold = std::clamp(std::stof(fields[3]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--levels") {
+            if (i + 1 < argc) {
+                params.levels = std::clamp(std::stoi(argv[++i]), 2, 256);
+            }
+        }
+        else if (arg == "--seed") {
+            if (i + 1 < argc) {
+                params.seed = std::stoi(argv[++i]);
+            }
+        }
+        else if (arg == "--halftone-shape") {
+            if (i + 1 < argc) {
+                std::string shape = argv[++i];
+                if (shape == "diamond") {
+                    params.halftoneShape = Dithering::HalftoneShape::DIAMOND;
+                } else if (shape == "circle") {
+                    params.halftoneShape = Dithering::HalftoneShape::CIRCLE;
+                } else {
+                    std::cerr << "Unknown halftone shape: " << shape << ", using circle\n";
+                }
+            }
+        }
+        else if (arg == "--cell-size") {
+            if (i + 1 < argc) {
+                params.halftoneCellSize = std::clamp(std::stof(argv[++i]), 2.0f, 256.0f);
+                params.screenLpi = 0.0f;
+            }
+        }
+        else if (arg == "--lpi") {
+            if (i + 1 < argc) {
+                params.screenLpi = std::clamp(std::stof(argv[++i]), 0.0f, 600.0f);
+            }
+        }
+        else if (arg == "--stipple-dots") {
+            if (i + 1 < argc) {
+                params.stippleDots = std::clamp(std::stoi(argv[++i]), 1, 200000);
+            }
+        }
+        else if (arg == "--stipple-size") {
+            if (i + 1 < argc) {
+                params.stippleDotSize = std::clamp(std::stof(argv[++i]), 0.5f, 64.0f);
+            }
+        }
+        else if (arg == "--stipple-iterations") {
+            if (i + 1 < argc) {
+                params.stippleIterations = std::clamp(std::stoi(argv[++i]), 0, 500);
+            }
+        }
+        else if (arg == "--screen-angles") {
+            if (i + 1 < argc) {
+                // Given in C, M, Y order; stored per B, G, R channel
+                std::string value = argv[++i];
+                float angles[3] = {15.0f, 75.0f, 0.0f};
+                size_t start = 0;
+                for (int a = 0; a < 3 && start <= value.size(); ++a) {
+                    size_t comma = value.find(',', start);
+                    angles[a] = std::stof(value.substr(start, comma - start));
+                    if (comma == std::string::npos) break;
+                    start = comma + 1;
+                }
+                params.screenAngles = cv::Vec3f(angles[2], angles[1], angles[0]);
+            }
+        }
+        else if (arg == "--screen-angle") {
+            if (i + 1 < argc) {
+                params.grayScreenAngle = std::stof(argv[++i]);
+            }
+        }
+        else if (arg == "--svg") {
+            if (i + 1 < argc) {
+                svgFile = argv[++i];
+            }
+        }
+        else if (arg == "--poster") {
+            if (i + 1 < argc) {
+                posterFile = argv[++i];
+            }
+        }
+        else if (arg == "--poster-paper") {
+            if (i + 1 < argc) {
+                std::string paper = argv[++i];
+                if (Poster::findPaperSize(paper)) {
+                    posterOptions.paper = paper;
+                } else {
+                    std::cerr << "Unknown paper size: " << paper << ", using letter\n";
+                }
+            }
+        }
+        else if (arg == "--poster-landscape") {
+            posterOptions.landscape = true;
+        }
+        else if (arg == "--poster-width") {
+            if (i + 1 < argc) {
+                posterOptions.posterWidthInches = std::max(0.0, std::stod(argv[++i]));
+            }
+        }
+        else if (arg == "--poster-dpi") {
+            if (i + 1 < argc) {
+                posterOptions.dpi = std::clamp(std::stod(argv[++i]), 36.0, 2400.0);
+            }
+        }
+        else if (arg == "--poster-overlap") {
+            if (i + 1 < argc) {
+                posterOptions.overlapInches = std::clamp(std::stod(argv[++i]), 0.0, 2.0);
+            }
+        }
+        else if (arg == "--pdf") {
+            if (i + 1 < argc) {
+                pdfFile = argv[++i];
+            }
+        }
+        else if (arg == "--pdf-paper") {
+            if (i + 1 < argc) {
+                std::string paper = argv[++i];
+                if (Poster::findPaperSize(paper)) {
+                    printOptions.paper = paper;
+                } else {
+                    std::cerr << "Unknown paper size: " << paper << ", using letter\n";
+                }
+            }
+        }
+        else if (arg == "--pdf-landscape") {
+            printOptions.landscape = true;
+        }
+        else if (arg == "--pdf-dpi") {
+            if (i + 1 < argc) {
+                params.printDpi = std::clamp(std::stof(argv[++i]), 36.0f, 2400.0f);
+            }
+        }
+        else if (arg == "--pdf-vector") {
+            pdfVector = true;
+        }
+        else if (arg == "--ascii") {
+            if (i + 1 < argc) {
+                asciiFile = argv[++i];
+            }
+        }
+        else if (arg == "--ascii-image") {
+            if (i + 1 < argc) {
+                asciiImageFile = argv[++i];
+            }
+        }
+        else if (arg == "--ascii-columns") {
+            if (i + 1 < argc) {
+                asciiOptions.columns = std::clamp(std::stoi(argv[++i]), 1, 1000);
+            }
+        }
+        else if (arg == "--ascii-ramp") {
+            if (i + 1 < argc) {
+                std::string ramp = argv[++i];
+                if (ramp.size() >= 2) {
+                    asciiOptions.ramp = ramp;
+                } else {
+                    std::cerr << "ASCII ramp needs at least 2 characters, using the default\n";
+                }
+            }
+        }
+        else if (arg == "--ascii-invert") {
+            asciiOptions.invert = true;
+        }
+        else if (arg == "--ascii-braille") {
+            asciiOptions.braille = true;
+        }
+        else if (arg == "--ansi") {
+            if (i + 1 < argc) {
+                ansiFile = argv[++i];
+            }
+        }
+        else if (arg == "--ansi-colors") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Ascii::parseAnsiColors(name, ansiColors)) {
+                    std::cerr << "Unknown ANSI colors: " << name << ", using truecolor\n";
+                    ansiColors = Ascii::AnsiColors::TRUECOLOR;
+                }
+            }
+        }
+        else if (arg == "--ansi-columns") {
+            if (i + 1 < argc) {
+                ansiColumns = std::clamp(std::stoi(argv[++i]), 0, 2000);
+            }
+        }
+        else if (arg == "--plot") {
+            if (i + 1 < argc) {
+                plotFile = argv[++i];
+            }
+        }
+        else if (arg == "--plot-width") {
+            if (i + 1 < argc) {
+                plotOptions.widthMm = std::clamp(std::stod(argv[++i]), 1.0, 10000.0);
+            }
+        }
+        else if (arg == "--plot-rows") {
+            plotOptions.shortenTravel = false;
+        }
+        else if (arg == "--plot-z") {
+            if (i + 1 < argc) {
+                double up, down;
+                if (std::sscanf(argv[++i], "%lf,%lf", &up, &down) == 2) {
+                    plotOptions.penUpZ = up;
+                    plotOptions.penDownZ = down;
+                } else {
+                    std::cerr << "Invalid pen heights: " << argv[i] << ", using 2,0\n";
+                }
+            }
+        }
+        else if (arg == "--plot-feed") {
+            if (i + 1 < argc) {
+                plotOptions.feedRate = std::clamp(std::stod(argv[++i]), 1.0, 100000.0);
+            }
+        }
+        else if (arg == "--tiles") {
+            if (i + 1 < argc) {
+                tilesFile = argv[++i];
+            }
+        }
+        else if (arg == "--progression") {
+            if (i + 1 < argc) {
+                progressionFile = argv[++i];
+            }
+        }
+        else if (arg == "--progression-rows") {
+            if (i + 1 < argc) {
+                progressionOptions.rowsPerFrame = std::max(1, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--progression-pixels") {
+            if (i + 1 < argc) {
+                progressionOptions.pixelsPerFrame = std::max(1, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--progression-seconds") {
+            if (i + 1 < argc) {
+                progressionOptions.seconds = std::clamp(std::stod(argv[++i]), 0.5, 600.0);
+            }
+        }
+        else if (arg == "--wallpaper") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                setWallpaper = true;
+                if (!Wallpaper::parseFitMode(name, wallpaperOptions.mode)) {
+                    std::cerr << "Unknown wallpaper mode: " << name << ", using fill\n";
+                    wallpaperOptions.mode = Wallpaper::FitMode::FILL;
+                }
+            }
+        }
+        else if (arg == "--wallpaper-background") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                if (!parseHexColor(value, wallpaperOptions.background)) {
+                    std::cerr << "Invalid wallpaper background: " << value << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--screen-size") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                size_t x = value.find('x');
+                if (x != std::string::npos) {
+                    wallpaperOptions.screen = cv::Size(std::max(0, std::stoi(value.substr(0, x))),
+                                                       std::max(0, std::stoi(value.substr(x + 1))));
+                }
+            }
+        }
+        else if (arg == "--embedded") {
+            if (i + 1 < argc) {
+                embeddedFile = argv[++i];
+            }
+        }
+        else if (arg == "--embedded-layout") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Embedded::parseLayout(name, embeddedOptions.layout)) {
+                    std::cerr << "Unknown embedded layout: " << name << ", using horizontal\n";
+                    embeddedOptions.layout = Embedded::Layout::HORIZONTAL;
+                }
+            }
+        }
+        else if (arg == "--embedded-bit-order") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Embedded::parseBitOrder(name, embeddedOptions.bitOrder)) {
+                    std::cerr << "Unknown bit order: " << name << ", using default\n";
+                    embeddedOptions.bitOrder = Embedded::BitOrder::DEFAULT;
+                }
+            }
+        }
+        else if (arg == "--embedded-invert") {
+            embeddedOptions.invert = true;
+        }
+        else if (arg == "--embedded-name") {
+            if (i + 1 < argc) {
+                embeddedOptions.name = argv[++i];
+            }
+        }
+        else if (arg == "--compare") {
+            if (i + 1 < argc) {
+                compareFile = argv[++i];
+            }
+        }
+        else if (arg == "--diff") {
+            if (i + 1 < argc) {
+                diffFile = argv[++i];
+            }
+        }
+        else if (arg == "--quality") {
+            quality = true;
+        }
+        else if (arg == "--count-colors") {
+            countColors = true;
+        }
+        else if (arg == "--quality-against") {
+            if (i + 1 < argc) {
+                qualityFile = argv[++i];
+            }
+        }
+        else if (arg == "--color-mode") {
+            if (i + 1 < argc) {
+                std::string mode = argv[++i];
+                if (mode == "cmyk") {
+                    params.colorMode = Dithering::ColorMode::CMYK;
+                } else if (mode == "rgb") {
+                    params.colorMode = Dithering::ColorMode::RGB;
+                } else {
+                    std::cerr << "Unknown color mode: " << mode << ", using rgb\n";
+                }
+            }
+        }
+        else if (arg == "--pixel-format") {
+            if (i + 1 < argc) {
+                std::string format = argv[++i];
+                params.colorMode = Dithering::ColorMode::PIXEL_FORMAT;
+                if (format == "rgb565") {
+                    params.pixelFormat = Dithering::PixelFormat::RGB565;
+                } else if (format == "rgb332") {
+                    params.pixelFormat = Dithering::PixelFormat::RGB332;
+                } else if (format == "rgba4444") {
+                    params.pixelFormat = Dithering::PixelFormat::RGBA4444;
+                } else {
+                    std::cerr << "Unknown pixel format: " << format << ", using rgb565\n";
+                    params.pixelFormat = Dithering::PixelFormat::RGB565;
+                }
+            }
+        }
+        else if (arg == "--separations") {
+            separations = true;
+            params.colorMode = Dithering::ColorMode::CMYK;
+        }
+        else if (arg == "--pixelate") {
+            if (i + 1 < argc) {
+                params.pixelate = std::clamp(std::stoi(argv[++i]), 1, 64);
+            }
+        }
+        else if (arg == "--blend") {
+            if (i + 1 < argc) {
+                params.blend = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--stage") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                size_t colon = value.find(':');
+                std::string type = value.substr(0, colon);
+                std::string detail = colon != std::string::npos ? value.substr(colon + 1) : "";
+                Dithering::Stage stage;
+                if (type == "dither") {
+                    stage.type = Dithering::StageType::DITHER;
+                    if (!detail.empty()) {
+                        auto stageParams = std::make_shared<Dithering::Parameters>();
+                        std::string error;
+                        if (!Settings::loadSettings(detail, *stageParams, &error)) {
+                            std::cerr << "Error: " << detail << ": " << error << "\n";
+                            return 1;
+                        }
+                        stageParams->stages.clear();
+                        stage.params = stageParams;
+                    }
+                } else if (type == "blur" || type == "sharpen") {
+                    stage.type = type == "blur" ? Dithering::StageType::BLUR : Dithering::StageType::SHARPEN;
+                    if (!detail.empty()) stage.amount = std::clamp(std::stof(detail), 0.0f, 20.0f);
+                } else {
+                    std::cerr << "Unknown stage: " << type << ", expected dither, blur or sharpen\n";
+                    return 1;
+                }
+                params.stages.push_back(stage);
+            }
+        }
+        else if (arg == "--stage-mix") {
+            if (i + 1 < argc) {
+                float mix = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+                if (params.stages.empty()) {
+                    std::cerr << "Warning: --stage-mix needs a --stage before it, ignoring\n";
+                } else {
+                    params.stages.back().mix = mix;
+                }
+            }
+        }
+        else if (arg == "--backend") {
+            if (i + 1 < argc) {
+                std::string backend = argv[++i];
+                if (backend == "auto") {
+                    params.backend = Dithering::Backend::AUTO;
+                } else if (backend == "cpu") {
+                    params.backend = Dithering::Backend::CPU;
+                } else if (backend == "gpu") {
+                    params.backend = Dithering::Backend::GPU;
+                } else {
+                    std::cerr << "Unknown backend: " << backend << ", using auto\n";
+                }
+            }
+        }
+        else if (arg == "--pattern-scale") {
+            if (i + 1 < argc) {
+                int scale = std::clamp(std::stoi(argv[++i]), 1, Dithering::MAX_PATTERN_SCALE);
+                params.ditherScale = static_cast<float>(scale);
+            }
+        }
+        else if (arg == "--phase") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                size_t comma = value.find(',');
+                params.phaseX = std::stoi(value.substr(0, comma));
+                params.phaseY = (comma != std::string::npos) ? std::stoi(value.substr(comma + 1)) : 0;
+            }
+        }
+        else if (arg == "--alpha-threshold") {
+            if (i + 1 < argc) {
+                exportOptions.alphaThreshold = std::clamp(std::stoi(argv[++i]), 0, 255);
+            }
+        }
+        else if (arg == "--ink" || arg == "--paper") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                cv::Vec3b& color = (arg == "--ink") ? params.inkColor : params.paperColor;
+                if (!parseHexColor(value, color)) {
+                    std::cerr << "Invalid " << arg.substr(2) << " color: " << value << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--matte") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                if (parseHexColor(value, exportOptions.matteColor)) {
+                    exportOptions.useMatte = true;
+                } else {
+                    std::cerr << "Invalid matte color: " << value << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--binary-alpha") {
+            exportOptions.binaryAlpha = true;
+        }
+        else if (arg == "--truecolor-png") {
+            exportOptions.indexedPng = false;
+        }
+        else if (arg == "--jpeg-quality") {
+            if (i + 1 < argc) {
+                exportOptions.encode.jpegQuality = std::clamp(std::stoi(argv[++i]), 1, 100);
+            }
+        }
+        else if (arg == "--png-compression") {
+            if (i + 1 < argc) {
+                exportOptions.encode.pngCompression = std::clamp(std::stoi(argv[++i]), 0, 9);
+            }
+        }
+        else if (arg == "--png-strategy") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!ImageIO::parsePngStrategy(name, exportOptions.encode.pngStrategy)) {
+                    std::cerr << "Unknown PNG strategy: " << name << ", using default\n";
+                    exportOptions.encode.pngStrategy = ImageIO::PngStrategy::DEFAULT;
+                }
+            }
+        }
+        else if (arg == "--webp-quality") {
+            if (i + 1 < argc) {
+                exportOptions.encode.webpQuality = std::clamp(std::stoi(argv[++i]), 1, 100);
+                exportOptions.encode.webpLossless = false;
+            }
+        }
+        else if (arg == "--avif-quality") {
+            if (i + 1 < argc) {
+                exportOptions.encode.avifQuality = std::clamp(std::stoi(argv[++i]), 0, 100);
+            }
+        }
+        else if (arg == "--jxl-quality") {
+            if (i + 1 < argc) {
+                exportOptions.encode.jxlQuality = std::clamp(std::stoi(argv[++i]), 0, 100);
+            }
+        }
+        else if (arg == "--truecolor-tiff") {
+            exportOptions.encode.bilevelTiff = false;
+        }
+        else if (arg == "--strip-metadata") {
+            exportOptions.encode.keepMetadata = false;
+        }
+        else if (arg == "--tag-profile") {
+            exportOptions.encode.tagProfile = true;
+        }
+        else if (arg == "--gif-transparent-index") {
+            if (i + 1 < argc) {
+                exportOptions.gifTransparentIndex = std::clamp(std::stoi(argv[++i]), 0, 255);
+            }
+        }
+        else if (arg == "--dither-alpha") {
+            ditherAlpha = true;
+            exportOptions.binaryAlpha = true;
+        }
+        else if (arg == "--slideshow") {
+            slideshow = true;
+        }
+        else if (arg == "--capture-screen") {
+            screenCapture = true;
+        }
+        else if (arg == "--capture-region") {
+            if (i + 1 < argc) {
+                captureRegion = parseRect(argv[++i]);
+                screenCapture = true;
+            }
+        }
+        else if (arg == "--text") {
+            if (i + 1 < argc) {
+                // Typed on a command line, so \n is the two characters
+                text = argv[++i];
+                for (size_t at = text.find("\\n"); at != std::string::npos; at = text.find("\\n", at + 1)) {
+                    text.replace(at, 2, "\n");
+                }
+            }
+        }
+        else if (arg == "--font") {
+            if (i + 1 < argc) {
+                textOptions.font = argv[++i];
+            }
+        }
+        else if (arg == "--font-size") {
+            if (i + 1 < argc) {
+                textOptions.size = std::clamp(std::stoi(argv[++i]), 4, 2000);
+            }
+        }
+        else if (arg == "--text-color" || arg == "--text-background") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                cv::Vec3b& color = arg == "--text-color" ? textOptions.color : textOptions.background;
+                if (!parseHexColor(value, color)) {
+                    std::cerr << "Invalid color for " << arg << ": " << value << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--seconds-per-image") {
+            if (i + 1 < argc) {
+                slideshowOptions.secondsPerImage = std::max(0.1, std::stod(argv[++i]));
+            }
+        }
+        else if (arg == "--crossfade") {
+            slideshowOptions.crossfade = true;
+        }
+        else if (arg == "--batch") {
+            batch = true;
+        }
+        else if (arg == "--watch") {
+            batch = true;
+            watch = true;
+        }
+        else if (arg == "--jobs") {
+            if (i + 1 < argc) {
+                batchOptions.jobs = std::max(0, std::stoi(argv[++i]));
+                videoOptions.jobs = batchOptions.jobs;
+            }
+        }
+        else if (arg == "--output-name") {
+            if (i + 1 < argc) {
+                batchOptions.nameTemplate = argv[++i];
+            }
+        }
+        else if (arg == "--format") {
+            if (i + 1 < argc) {
+                std::string format = argv[++i];
+                std::transform(format.begin(), format.end(), format.begin(), ::tolower);
+                if (!format.empty() && format[0] == '.') format.erase(0, 1);
+                const auto& formats = ImageIO::getSaveExtensions();
+                if (std::find(formats.begin(), formats.end(), format) != formats.end()) {
+                    batchOptions.outputFormat = format;
+                } else {
+                    std::cerr << "Unknown format: " << format << ", keeping each input's format\n";
+                }
+            }
+        }
+        else if (arg == "--fps") {
+            if (i + 1 < argc) {
+                videoOptions.outputFps = std::stod(argv[++i]);
+            }
+        }
+        else if (arg == "--fps-mode") {
+            if (i + 1 < argc) {
+                std::string mode = argv[++i];
+                if (mode == "blend") {
+                    videoOptions.frameRateMode = Video::FrameRateMode::BLEND;
+                } else if (mode == "nearest") {
+                    videoOptions.frameRateMode = Video::FrameRateMode::NEAREST;
+                } else {
+                    std::cerr << "Unknown fps mode: " << mode << ", using nearest\n";
+                }
+            }
+        }
+        else if (arg == "--loop") {
+            if (i + 1 < argc) {
+                std::string mode = argv[++i];
+                if (mode == "pingpong") {
+                    videoOptions.loopMode = Video::LoopMode::PING_PONG;
+                } else if (mode == "seamless") {
+                    videoOptions.loopMode = Video::LoopMode::SEAMLESS_TRIM;
+                } else {
+                    std::cerr << "Unknown loop mode: " << mode << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--start" || arg == "--end" || arg == "--duration") {
+            if (i + 1 < argc) {
+                std::string time = argv[++i];
+                double seconds = 0.0;
+                if (!Video::parseTime(time, seconds)) {
+                    std::cerr << "Invalid time: " << time << ", ignoring " << arg << "\n";
+                } else if (arg == "--start") {
+                    videoOptions.startSeconds = seconds;
+                } else if (arg == "--end") {
+                    videoOptions.endSeconds = seconds;
+                } else {
+                    clipDuration = seconds;
+                }
+            }
+        }
+        else if (arg == "--preview-frame") {
+            if (i + 1 < argc) {
+                std::string time = argv[++i];
+                if (!Video::parseTime(time, previewSeconds)) {
+                    std::cerr << "Invalid time: " << time << ", ignoring " << arg << "\n";
+                    previewSeconds = -1.0;
+                }
+            }
+        }
+        else if (arg == "--rotate") {
+            if (i + 1 < argc) {
+                videoOptions.rotation = std::stoi(argv[++i]);
+                rotation = videoOptions.rotation;
+            }
+        }
+        else if (arg == "--lock-palette") {
+            videoOptions.lockPalette = true;
+        }
+        else if (arg == "--palette-samples") {
+            if (i + 1 < argc) {
+                videoOptions.paletteSampleFrames = std::clamp(std::stoi(argv[++i]), 1, 256);
+            }
+        }
+        else if (arg == "--vary-seed") {
+            videoOptions.varySeed = true;
+        }
+        else if (arg == "--cycle-matrix") {
+            videoOptions.cycleMatrix = true;
+        }
+        else if (arg == "--temporal-carry") {
+            if (i + 1 < argc) {
+                videoOptions.temporalCarry = std::clamp(std::stof(argv[++i]), 0.0f, 1.0f);
+            }
+        }
+        else if (arg == "--codec") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Video::parseCodec(name, videoOptions.encoder.codec)) {
+                    std::cerr << "Unknown codec: " << name << ", using opencv\n";
+                    videoOptions.encoder.codec = Video::Codec::OPENCV;
+                }
+            }
+        }
+        else if (arg == "--hw-encoder") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Video::parseHardware(name, videoOptions.encoder.hardware)) {
+                    std::cerr << "Unknown hardware encoder: " << name << ", using none\n";
+                    videoOptions.encoder.hardware = Video::Hardware::NONE;
+                }
+            }
+        }
+        else if (arg == "--detect-encoders") {
+            std::cout << "Testing hardware encoders...\n";
+            std::vector<Video::HardwareEncoder> encoders = Video::detectHardwareEncoders();
+            for (const auto& encoder : encoders) {
+                const std::string& codec = Video::getCodecIds()[static_cast<int>(encoder.codec)];
+                std::printf("  %-20s%-8s%s\n", encoder.name.c_str(), codec.c_str(),
+                            Video::getHardwareName(encoder.hardware).c_str());
+            }
+            if (encoders.empty()) std::cout << "None found (needs ffmpeg built with them and a supported GPU)\n";
+            return 0;
+        }
+        else if (arg == "--check-ffmpeg") {
+            return printFfmpegTools() ? 0 : 1;
+        }
+        else if (arg == "--set-ffmpeg-path") {
+            if (i + 1 < argc) {
+                std::string path = argv[++i];
+                std::error_code ec;
+                if (!path.empty() && !std::filesystem::exists(path, ec)) {
+                    std::cerr << "Error: No such file or folder: " << path << "\n";
+                    return Errors::getExitCode(Errors::ErrorCode::NOT_FOUND);
+                }
+                if (!Platform::setFfmpegPath(path)) {
+                    std::cerr << "Error: Could not save the ffmpeg path\n";
+                    return 1;
+                }
+                return printFfmpegTools() ? 0 : 1;
+            }
+        }
+        else if (arg == "--crf") {
+            if (i + 1 < argc) {
+                videoOptions.encoder.crf = std::clamp(std::stoi(argv[++i]), 0, 63);
+            }
+        }
+        else if (arg == "--bitrate") {
+            if (i + 1 < argc) {
+                videoOptions.encoder.bitrateKbps = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--encoder-preset") {
+            if (i + 1 < argc) {
+                videoOptions.encoder.preset = argv[++i];
+            }
+        }
+        else if (arg == "--pix-fmt") {
+            if (i + 1 < argc) {
+                videoOptions.encoder.pixelFormat = argv[++i];
+            }
+        }
+        else if (arg == "--no-audio") {
+            videoOptions.keepAudio = false;
+        }
+        else if (arg == "--scale") {
+            if (i + 1 < argc) {
+                videoOptions.scale = std::stod(argv[++i]);
+                if (videoOptions.scale <= 0.0) {
+                    std::cerr << "Warning: Scale must be above 0, ignoring\n";
+                    videoOptions.scale = 1.0;
+                }
+            }
+        }
+        else if (arg == "--max-width") {
+            if (i + 1 < argc) {
+                videoOptions.maxWidth = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--upscale") {
+            if (i + 1 < argc) {
+                videoOptions.upscale = std::clamp(std::stoi(argv[++i]), 1, 16);
+            }
+        }
+        else if (arg == "--sprite-sheet") {
+            videoOptions.spriteSheet = true;
+        }
+        else if (arg == "--sheet-columns") {
+            if (i + 1 < argc) {
+                videoOptions.sheet.columns = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--sheet-padding") {
+            if (i + 1 < argc) {
+                videoOptions.sheet.padding = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--frame-step") {
+            if (i + 1 < argc) {
+                videoOptions.sheet.frameStep = std::max(1, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--max-frames") {
+            if (i + 1 < argc) {
+                videoOptions.sheet.maxFrames = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--dither-height") {
+            if (i + 1 < argc) {
+                videoOptions.ditherHeight = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--cache") {
+            if (i + 1 < argc) {
+                videoOptions.cacheDir = argv[++i];
+            }
+        }
+        else if (arg == "--nice") {
+            if (i + 1 < argc) {
+                videoOptions.niceness = std::clamp(std::stoi(argv[++i]), 0, 19);
+            }
+        }
+        else if (arg == "--threads") {
+            if (i + 1 < argc) {
+                videoOptions.threads = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--max-download") {
+            if (i + 1 < argc) {
+                maxDownloadBytes = static_cast<size_t>(std::max(1, std::stoi(argv[++i]))) * 1024 * 1024;
+            }
+        }
+        else if (arg == "--tile") {
+            if (i + 1 < argc) {
+                tile = parseRect(argv[++i]);
+            }
+        }
+        else if (arg == "--mask") {
+            if (i + 1 < argc) {
+                maskFile = argv[++i];
+            }
+        }
+        else if (arg == "--mask-rect") {
+            if (i + 1 < argc) {
+                maskRects.push_back(parseRect(argv[++i]));
+            }
+        }
+        else if (arg == "--crop") {
+            if (i + 1 < argc) {
+                crop = parseRect(argv[++i]);
+            }
+        }
+        else if (arg == "--resize") {
+            if (i + 1 < argc) {
+                std::string value = argv[++i];
+                size_t x = value.find('x');
+                std::string width = value.substr(0, x);
+                std::string height = (x != std::string::npos) ? value.substr(x + 1) : "";
+                resize.width = width.empty() ? 0 : std::max(0, std::stoi(width));
+                resize.height = height.empty() ? 0 : std::max(0, std::stoi(height));
+                videoOptions.frameSize = resize;
+            }
+        }
+        else if (arg == "--resize-filter") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Transform::parseFilter(name, resizeFilter)) {
+                    std::cerr << "Unknown resize filter: " << name << ", using area\n";
+                    resizeFilter = Transform::Filter::AREA;
+                }
+            }
+        }
+        else if (arg == "--flip") {
+            if (i + 1 < argc) {
+                std::string axis = argv[++i];
+                if (axis == "h" || axis == "horizontal") {
+                    flips.push_back(Transform::FlipAxis::HORIZONTAL);
+                } else if (axis == "v" || axis == "vertical") {
+                    flips.push_back(Transform::FlipAxis::VERTICAL);
+                } else {
+                    std::cerr << "Unknown flip axis: " << axis << ", ignoring\n";
+                }
+            }
+        }
+        else if (arg == "--device") {
+            if (i + 1 < argc) {
+                std::string id = argv[++i];
+                const Devices::DeviceInfo* found = Devices::findDevice(id);
+                if (found) {
+                    device = found;
+                    Devices::applyDevice(*device, params);
+                    videoOptions.frameSize = device->resolution;     // Fitted, not cropped, for videos
+                } else {
+                    std::cerr << "Unknown device: " << id << ", ignoring (see --list-devices)\n";
+                }
+            }
+        }
+        else if (arg == "--descreen" || arg == "--reconstruct") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Descreen::parseMethod(name, descreenOptions.method)) {
+                    std::cerr << "Unknown descreen method: " << name << ", using fft\n";
+                    descreenOptions.method = Descreen::Method::FREQUENCY;
+                }
+                (arg == "--descreen" ? descreen : reconstruct) = true;
+            }
+        }
+        else if (arg == "--descreen-period") {
+            if (i + 1 < argc) {
+                descreenOptions.period = std::clamp(std::stof(argv[++i]), 0.0f, 64.0f);
+            }
+        }
+        else if (arg == "--tile-margin") {
+            if (i + 1 < argc) {
+                tileMargin = std::max(0, std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--memory-limit") {
+            if (i + 1 < argc) {
+                memoryLimitMB = static_cast<size_t>(std::max(0, std::stoi(argv[++i])));
+            }
+        }
+        else if (arg == "--decode-limit") {
+            if (i + 1 < argc) {
+                ImageIO::setDecodeMemoryLimit(static_cast<size_t>(std::max(0, std::stoi(argv[++i]))) << 20);
+            }
+        }
+        else if (arg == "--downscale-huge") {
+            ImageIO::setDownscaleOversized(true);
+        }
+        else if (arg == "--svg-width") {
+            if (i + 1 < argc) {
+                ImageIO::setSvgWidth(std::stoi(argv[++i]));
+            }
+        }
+        else if (arg == "--info") {
+            infoOnly = true;
+        }
+        else if (arg == "--settings") {
+            ++i;    // Applied before the other options
+        }
+        else if (arg == "--save-settings") {
+            if (i + 1 < argc) {
+                saveSettingsFile = argv[++i];
+            }
+        }
+        else if (arg == "--preset") {
+            ++i;    // Applied before the other options
+        }
+        else if (arg == "--save-preset") {
+            if (i + 1 < argc) {
+                savePresetName = argv[++i];
+            }
+        }
+        else if (arg == "--run-script") {
+            if (i + 1 < argc) {
+                std::string path = argv[++i];
+                std::vector<std::string> scriptArgs(argv + i + 1, argv + argc);
+                Errors::Error error;
+                auto log = [](const std::string& message) { std::cout << message << std::endl; };
+                if (!Script::runScript(path, scriptArgs, params, log, &error)) {
+                    std::cerr << "Error: " << error.message << "\n";
+                    return Errors::getExitCode(error.code);
+                }
+                return 0;
+            }
+        }
+        else if (arg == "--serve") {
+            serve = true;
+        }
+        else if (arg == "--serve-port") {
+            if (i + 1 < argc) {
+                serverOptions.port = std::clamp(std::stoi(argv[++i]), 1, 65535);
+            }
+        }
+        else if (arg == "--serve-token") {
+            if (i + 1 < argc) {
+                serverOptions.token = argv[++i];
+            }
+        }
+        else if (arg == "--list-plugins") {
+            printPlugins();
+            return 0;
+        }
+        else if (arg == "--list-algorithms") {
+            for (const auto& info : Dithering::getAlgorithmLibrary()) {
+                std::string uses;
+                auto add = [&uses](bool used, const char* option) {
+                    if (used) uses += (uses.empty() ? "" : ", ") + std::string(option);
+                };
+                add(info.usesStrength, "--strength");
+                add(info.usesThreshold, "--threshold");
+                add(info.usesSerpentine, "--serpentine");
+                add(info.usesSeed, "--seed");
+                add(info.usesPatternScale, "--pattern-scale");
+                add(info.usesPhase, "--phase");
+                add(info.usesErrorEdges, "--error-edge");
+                std::printf("  %-18s%-17s%-28s%s\n", Settings::getAlgorithmId(info.algorithm).c_str(),
+                            Dithering::getAlgorithmCategoryName(info.category).c_str(), info.name.c_str(),
+                            uses.c_str());
+            }
+            return 0;
+        }
+        else if (arg == "--list-devices") {
+            for (const auto& info : Devices::getDeviceLibrary()) {
+                std::printf("  %-16s%-20s%s\n", info.id.c_str(), info.name.c_str(), info.description.c_str());
+            }
+            return 0;
+        }
+        else if (arg == "--list-presets") {
+            for (const auto& name : Settings::listPresets()) {
+                std::cout << name << "\n";
+            }
+            return 0;
+        }
+        else if (arg == "--delete-preset") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Settings::deletePreset(name)) {
+                    std::cerr << "Error: No preset named \"" << name << "\"\n";
+                    return 1;
+                }
+                std::cout << "Deleted preset " << name << "\n";
+                return 0;
+            }
+        }
+        else if (arg == "--settings-schema") {
+            std::cout << Settings::getSettingsSchema();
+            return 0;
+        }
+        else if (arg == "--stats") {
+            printUsageStats();
+            return 0;
+        }
+        else if (arg == "--clear-stats") {
+            if (!UsageStats::clearUsageStats()) {
+                std::cerr << "Error: Could not clear usage statistics\n";
+                return 1;
+            }
+            std::cout << "Usage statistics cleared\n";
+            return 0;
+        }
+        else if (arg == "--recent") {
+            printRecentFiles();
+            return 0;
+        }
+        else if (arg == "--clear-recent") {
+            if (!RecentFiles::clearRecentFiles()) {
+                std::cerr << "Error: Could not clear recent files\n";
+                return 1;
+            }
+            std::cout << "Recent files cleared\n";
+            return 0;
+        }
+        else if (arg == "--histogram") {
+            if (i + 1 < argc) {
+                histogram = argv[++i];
+                if (histogram != "original" && histogram != "adjusted") {
+                    std::cerr << "Unknown histogram: " << histogram << ", using original\n";
+                    histogram = "original";
+                }
+            }
+        }
+        else if (arg == "--contact-sheet") {
+            if (i + 1 < argc) {
+                contactSheetFile = argv[++i];
+            }
+        }
+        else if (arg == "--contact-sheet-size") {
+            if (i + 1 < argc) {
+                contactSheetSize = std::clamp(std::stoi(argv[++i]), 32, 1024);
+            }
+        }
+        else if (arg == "--montage") {
+            if (i + 1 < argc) {
+                montageFile = argv[++i];
+            }
+        }
+        else if (arg == "--variant") {
+            if (i + 1 < argc) {
+                montageVariants.push_back(argv[++i]);
+            }
+        }
+        else if (arg == "--montage-layout") {
+            if (i + 1 < argc) {
+                std::string name = argv[++i];
+                if (!Compare::parseMontageLayout(name, montageOptions.layout)) {
+                    std::cerr << "Unknown montage layout: " << name << ", using row\n";
+                    montageOptions.layout = Compare::MontageLayout::ROW;
+                }
+            }
+        }
+        else if (arg == "--montage-size") {
+            if (i + 1 < argc) {
+                montageOptions.panelSize = std::clamp(std::stoi(argv[++i]), 0, 8192);
+            }
+        }
+        else if (arg == "--montage-original") {
+            montageOptions.includeOriginal = true;
+        }
+        else if (arg == "--benchmark") {
+            if (i + 1 < argc) {
+                benchmark = true;
+                std::string list = argv[++i];
+                size_t start = 0;
+                while (list != "all") {
+                    size_t comma = list.find(',', start);
+                    std::string name = list.substr(start, comma - start);
+                    Dithering::Algorithm algorithm;
+                    if (!findAlgorithm(name, algorithm)) {
+                        std::cerr << "Error: Unknown algorithm for --benchmark: " << name << "\n";
+                        return Errors::getExitCode(Errors::ErrorCode::INVALID_ARGUMENT);
+                    }
+                    benchmarkAlgorithms.push_back(algorithm);
+                    if (comma == std::string::npos) break;
+                    start = comma + 1;
+                }
+            }
+        }
+        else if (arg == "--benchmark-runs") {
+            if (i + 1 < argc) {
+                benchmarkRuns = std::clamp(std::stoi(argv[++i]), 1, 100);
+            }
+        }
+        else if (arg == "--lospec") {
+            if (i + 1 < argc) {
+                Lospec::PaletteInfo palette;
+                Errors::Error error;
+                bool offline = false;
+                if (!Lospec::downloadPalette(argv[++i], palette, &offline, &error)) {
+                    std::cerr << "Error: " << error.message << "\n";
+                    return Errors::getExitCode(error.code);
+                }
+                if (offline) std::cerr << "Warning: lospec.com unreachable, using the downloaded copy\n";
+                params.customPalette = palette.colors;
+                params.paletteMode = Dithering::PaletteMode::CUSTOM;
+            }
+        }
+        else if (arg == "--lospec-search") {
+            if (i + 1 < argc) {
+                lospecQuery = argv[++i];
+            }
+        }
+        else if (arg == "--lospec-colors") {
+            if (i + 1 < argc) {
+                lospecColors = std::clamp(std::stoi(argv[++i]), 0, 256);
+            }
+        }
+        else if (arg == "--export-palette") {
+            if (i + 1 < argc) {
+                exportPaletteFile = argv[++i];
+            }
+        }
+        else if (arg == "--extract-palette") {
+            if (i + 1 < argc) {
+                extractColors = std::clamp(std::stoi(argv[++i]), 2, 256);
+            }
+        }
+        else if (inputFile.empty()) {
+            inputFile = arg;
+        }
+        else if (outputFile.empty()) {
+            outputFile = arg;
+        }
+        else {
+            extraFiles.push_back(arg);
+        }
+    }
+    parsing.clear();
+
+    if (!kernelText.empty()) {
+        std::string error;
+        if (!Dithering::parseKernel(kernelText, params.diffusionKernel, error) ||
+            !Dithering::validateKernel(params.diffusionKernel, params.normalizeKernel, error)) {
+            std::cerr << "Error: --kernel " << error << "\n";
+            return Errors::getExitCode(Errors::ErrorCode::INVALID_ARGUMENT);
+        }
+    }
+
+    if (clipDuration > 0.0) videoOptions.endSeconds = videoOptions.startSeconds + clipDuration;
+    // The screen or the text is the input, so the one path given is the output
+    bool generatedInput = screenCapture || !text.empty();
+    if (generatedInput && outputFile.empty()) std::swap(inputFile, outputFile);
+    const double recordSeconds = clipDuration > 0.0 ? clipDuration : 10.0;
+
+    // Validate every path up front, so bad paths fail before any work is done
+    std::vector<std::string> inputExtensions = ImageIO::getImageExtensions();
+    const auto& videoExtensions = Video::getVideoExtensions();
+    inputExtensions.insert(inputExtensions.end(), videoExtensions.begin(), videoExtensions.end());
+    if (!inputFile.empty() && !Platform::isRemoteUrl(inputFile) && !batch) {
+        Platform::PathCheck check = slideshow ? Platform::validateDirectory(inputFile)
+                                              : Platform::validateInputFile(inputFile, inputExtensions);
+        if (!checkPath(check)) return 1;
+    }
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    if (!outputFile.empty() && !infoOnly && extractColors == 0 && !batch) {
+        // Videos and GIFs can also become animated images; sprite sheets are still images
+        std::vector<std::string> outputExtensions = ImageIO::getSaveExtensions();
+        if (slideshow || (Video::isVideoFile(inputFile) && previewSeconds < 0.0 && !videoOptions.spriteSheet)) {
+            outputExtensions = videoExtensions;
+            if (!slideshow) {
+                outputExtensions.insert(outputExtensions.end(), animationExtensions.begin(), animationExtensions.end());
+            }
+        } else if (screenCapture) {
+            outputExtensions.insert(outputExtensions.end(), videoExtensions.begin(), videoExtensions.end());
+            outputExtensions.insert(outputExtensions.end(), animationExtensions.begin(), animationExtensions.end());
+        } else if (ImageIO::getExtension(inputFile) == "gif") {
+            outputExtensions.push_back("apng");
+        }
+        if (!checkPath(Platform::validateOutputFile(outputFile, outputExtensions))) {
+            return 1;
+        }
+    }
+    if (!paletteReference.empty() &&
+        !checkPath(Platform::validateInputFile(paletteReference, ImageIO::getImageExtensions()))) {
+        return 1;
+    }
+    if (!posterFile.empty() && !checkPath(Platform::validateOutputFile(posterFile, {"pdf"}))) {
+        return 1;
+    }
+    if (!pdfFile.empty() && !checkPath(Platform::validateOutputFile(pdfFile, {"pdf"}))) {
+        return 1;
+    }
+    if (params.algorithm == Dithering::Algorithm::CUSTOM_KERNEL && !params.diffusionKernel.empty()) {
+        std::string error;
+        if (!Dithering::validateKernel(params.diffusionKernel, params.normalizeKernel, error)) {
+            std::cerr << "Error: --kernel " << error << "\n";
+            return Errors::getExitCode(Errors::ErrorCode::INVALID_ARGUMENT);
+        }
+    }
+    if (params.screenLpi > 0.0f) {
+        if (!Dithering::usesScreenRuling(params)) {
+            std::cerr << "Warning: --lpi applies to halftone and clustered Bayer only, ignoring\n";
+        } else {
+            Dithering::Parameters ruled = params;
+            std::vector<std::string> warnings;
+            Dithering::applyScreenRuling(ruled, &warnings);
+            for (const auto& warning : warnings) std::cerr << "Warning: " << warning << "\n";
+        }
+    }
+    printOptions.dpi = params.printDpi;
+    if (pdfVector && !Halftone::supportsVectorHalftone(params)) {
+        std::cerr << "Error: --pdf-vector needs halftone, stipple, clustered Bayer or a dots45, spiral or "
+                     "newspaper matrix\n";
+        return 1;
+    }
+    if (!asciiFile.empty() && asciiFile != "-" && !checkPath(Platform::validateOutputFile(asciiFile, {"txt"}))) {
+        return 1;
+    }
+    if (!ansiFile.empty() && ansiFile != "-" && !checkPath(Platform::validateOutputFile(ansiFile, {"ans", "txt"}))) {
+        return 1;
+    }
+    if (!plotFile.empty() && !checkPath(Platform::validateOutputFile(plotFile, Plotter::getPlotterExtensions()))) {
+        return 1;
+    }
+    if (!embeddedFile.empty() &&
+        !checkPath(Platform::validateOutputFile(embeddedFile, Embedded::getEmbeddedExtensions()))) {
+        return 1;
+    }
+    if (!tilesFile.empty() && !checkPath(Platform::validateOutputFile(tilesFile, {"dzi"}))) {
+        return 1;
+    }
+    if (!progressionFile.empty()) {
+        std::vector<std::string> progressionExtensions = videoExtensions;
+        progressionExtensions.insert(progressionExtensions.end(), animationExtensions.begin(),
+                                     animationExtensions.end());
+        if (!checkPath(Platform::validateOutputFile(progressionFile, progressionExtensions))) return 1;
+    }
+    if (!asciiImageFile.empty() &&
+        !checkPath(Platform::validateOutputFile(asciiImageFile, ImageIO::getSaveExtensions()))) {
+        return 1;
+    }
+    if (!svgFile.empty() && !checkPath(Platform::validateOutputFile(svgFile, {"svg"}))) {
+        return 1;
+    }
+    if (!svgFile.empty() && !Halftone::supportsVectorHalftone(params)) {
+        std::cerr << "Error: --svg needs halftone, stipple, clustered Bayer or a dots45, spiral or "
+                     "newspaper matrix\n";
+        return 1;
+    }
+    if (!compareFile.empty() &&
+        !checkPath(Platform::validateInputFile(compareFile, ImageIO::getImageExtensions()))) {
+        return 1;
+    }
+    if (!maskFile.empty() &&
+        !checkPath(Platform::validateInputFile(maskFile, ImageIO::getImageExtensions()))) {
+        return 1;
+    }
+    if (!contactSheetFile.empty() &&
+        !checkPath(Platform::validateOutputFile(contactSheetFile, ImageIO::getSaveExtensions()))) {
+        return 1;
+    }
+    if (!montageFile.empty()) {
+        if (!checkPath(Platform::validateOutputFile(montageFile, ImageIO::getSaveExtensions()))) return 1;
+        if (montageVariants.size() < 2) {
+            std::cerr << "Error: --montage needs at least two --variant options\n";
+            return 1;
+        }
+    }
+    if (!qualityFile.empty() &&
+        !checkPath(Platform::validateInputFile(qualityFile, ImageIO::getImageExtensions()))) {
+        return 1;
+    }
+    if (!diffFile.empty() && !checkPath(Platform::validateOutputFile(diffFile, ImageIO::getSaveExtensions()))) {
+        return 1;
+    }
+    if (!videoOptions.cacheDir.empty() && !checkPath(Platform::validateDirectory(videoOptions.cacheDir, true))) {
+        return 1;
+    }
+
+    if (!saveSettingsFile.empty()) {
+        if (!checkPath(Platform::validateOutputFile(saveSettingsFile, {"json"}))) return 1;
+        if (!Settings::saveSettings(saveSettingsFile, params)) {
+            std::cerr << "Error: Could not save settings: " << saveSettingsFile << "\n";
+            return 1;
+        }
+        std::cout << "Saved settings to " << saveSettingsFile << "\n";
+        if (inputFile.empty()) return 0;
+    }
+    if (!savePresetName.empty()) {
+        std::string error;
+        if (!Settings::savePreset(savePresetName, params, &error)) {
+            std::cerr << "Error: " << error << "\n";
+            return 1;
+        }
+        std::cout << "Saved preset " << savePresetName << "\n";
+        if (inputFile.empty()) return 0;
+    }
+    if (!lospecQuery.empty()) {
+        std::vector<Lospec::PaletteInfo> palettes;
+        Errors::Error error;
+        bool offline = false;
+        if (!Lospec::searchPalettes(lospecQuery, lospecColors, 0, palettes, &offline, &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        if (offline) std::cout << "lospec.com unreachable, showing cached palettes\n";
+        if (palettes.empty()) std::cout << "No palettes found\n";
+        for (const auto& palette : palettes) {
+            std::cout << palette.slug << "  " << palette.title << " (" << palette.colors.size() << " colors";
+            if (!palette.author.empty()) std::cout << ", by " << palette.author;
+            std::cout << ")\n";
+        }
+        return 0;
+    }
+    if (!exportPaletteFile.empty()) {
+        if (!checkPath(Platform::validateOutputFile(exportPaletteFile, PaletteFile::getPaletteSaveExtensions()))) {
+            return 1;
+        }
+    }
+    // The settings' own palette; extracted palettes are saved further down
+    if (!exportPaletteFile.empty() && extractColors == 0) {
+        if (params.paletteMode == Dithering::PaletteMode::ADAPTIVE) {
+            std::cerr << "Error: An adaptive palette comes from an image, use --extract-palette <n> with an input\n";
+            return 1;
+        }
+        Errors::Error error;
+        if (!PaletteFile::savePalette(exportPaletteFile, Dithering::getPalette(params), &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Saved palette to " << exportPaletteFile << "\n";
+        if (inputFile.empty()) return 0;
+    }
+
+    if (serve) {
+        serverOptions.defaults = params;
+        Automation::Server server;
+        std::string error;
+        auto log = [](const std::string& message) { std::cout << message << std::endl; };
+        if (!server.start(serverOptions, log, &error)) {
+            std::cerr << "Error: " << error << "\n";
+            return 1;
+        }
+        static std::atomic<bool> stopServing{false};
+        std::signal(SIGINT, [](int) { stopServing = true; });
+        std::cout << "Listening on http://127.0.0.1:" << server.getPort() << "/api/ (Ctrl+C to stop)\n";
+        std::cout << "Token: " << server.getToken() << "\n";
+        while (!stopServing) std::this_thread::sleep_for(std::chrono::milliseconds(200));
+        server.stop();
+        std::cout << "\nStopped serving\n";
+        return 0;
+    }
+
+    if (infoOnly && Video::isVideoFile(inputFile)) {
+        Video::VideoInfo info;
+        Errors::Error error;
+        if (!Video::getVideoInfo(inputFile, info, &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        std::cout << "File: " << inputFile << "\n";
+        std::cout << "Size: " << info.size.width << "x" << info.size.height << "\n";
+        if (info.rotation != 0) std::cout << "Rotation: " << info.rotation << " degrees\n";
+        std::cout << "Frame rate: " << info.fps << " fps\n";
+        std::cout << "Frames: " << info.frameCount << "\n";
+        std::cout << "Duration: " << info.durationSeconds << " s\n";
+        std::cout << "Codec: " << (info.codec.empty() ? "unknown" : info.codec) << "\n";
+        if (!info.probed) {
+            std::cout << "Audio: unknown (install ffmpeg to read it)\n";
+        } else if (info.hasAudio) {
+            std::cout << "Audio: " << info.audioCodec << ", " << info.audioChannels << " channels, "
+                      << info.audioSampleRate << " Hz\n";
+        } else {
+            std::cout << "Audio: none\n";
+        }
+        return 0;
+    }
+
+    if (infoOnly && !inputFile.empty()) {
+        ImageIO::ImageInfo info;
+        if (!ImageIO::getImageInfo(inputFile, info)) {
+            std::cerr << "Error: Could not read image: " << inputFile << "\n";
+            return 1;
+        }
+
+        std::cout << "File: " << inputFile << "\n";
+        std::cout << "Format: " << info.format << "\n";
+        std::cout << "Size: " << info.width << "x" << info.height << "\n";
+        std::cout << "Channels: " << info.channels << "\n";
+        std::cout << "Bit depth: " << info.bitDepth << "\n";
+        std::cout << "Color space: " << info.colorSpace << "\n";
+        if (info.hasIccProfile) {
+            std::cout << "ICC profile: " << (info.iccProfileName.empty() ? "embedded" : info.iccProfileName) << "\n";
+        }
+        for (const auto& warning : info.warnings) {
+            std::cout << "Warning: " << warning.message << "\n";
+        }
+        return 0;
+    }
+
+    if (extractColors > 0 && !inputFile.empty()) {
+        std::vector<cv::Vec3b> palette;
+        if (Video::isVideoFile(inputFile)) {
+            // One palette for the whole clip, from frames sampled across it
+            Dithering::Parameters clipParams = params;
+            clipParams.paletteSize = extractColors;
+            Errors::Error error;
+            if (!Video::analyzeClip(inputFile, clipParams, videoOptions, palette, nullptr, &error)) {
+                std::cerr << "Error: " << error.message << "\n";
+                return Errors::getExitCode(error.code);
+            }
+        } else {
+            cv::Mat image, imageAlpha;
+            if (!ImageIO::loadImage(inputFile, image, imageAlpha)) {
+                std::cerr << "Error: Could not load image: " << inputFile << "\n";
+                return 1;
+            }
+            palette = Dithering::generatePalette(Dithering::preprocessImage(image, params), extractColors,
+                                                 params.paletteMethod, params.seed);
+        }
+
+        if (!exportPaletteFile.empty()) {
+            Errors::Error error;
+            if (!PaletteFile::savePalette(exportPaletteFile, palette, &error)) {
+                std::cerr << "Error: " << error.message << "\n";
+                return Errors::getExitCode(error.code);
+            }
+            std::cout << "Saved " << palette.size() << " colors to " << exportPaletteFile << "\n";
+            return 0;
+        }
+
+        // Comma-separated RGB hex, ready to pass to --colors
+        char hex[8];
+        for (size_t i = 0; i < palette.size(); ++i) {
+            std::snprintf(hex, sizeof(hex), "%02x%02x%02x", palette[i][2], palette[i][1], palette[i][0]);
+            std::cout << (i > 0 ? "," : "") << hex;
+        }
+        std::cout << "\n";
+        return 0;
+    }
+
+    if (!histogram.empty() && !inputFile.empty()) {
+        cv::Mat image, imageAlpha;
+        if (!ImageIO::loadImage(inputFile, image, imageAlpha)) {
+            Errors::Error error = ImageIO::describeLoadFailure(inputFile);
+            std::cerr << "Error: " << error.message << ": " << inputFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        if (histogram == "adjusted") image = Dithering::preprocessImage(image, params);
+
+        Dithering::Histogram counts = Dithering::computeHistogram(image);
+        std::cout << "level,luma,red,green,blue\n";
+        for (int level = 0; level < 256; ++level) {
+            std::cout << level << "," << counts.luma[level] << "," << counts.channels[2][level] << ","
+                      << counts.channels[1][level] << "," << counts.channels[0][level] << "\n";
+        }
+        return 0;
+    }
+
+    if (!qualityFile.empty() && !inputFile.empty()) {
+        cv::Mat image, other, imageAlpha;
+        std::string failed;
+        if (!ImageIO::loadImage(inputFile, image, imageAlpha)) failed = inputFile;
+        else if (!ImageIO::loadImage(qualityFile, other, imageAlpha)) failed = qualityFile;
+        if (!failed.empty()) {
+            Errors::Error error = ImageIO::describeLoadFailure(failed);
+            std::cerr << "Error: " << error.message << ": " << failed << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        Compare::Quality measured;
+        Compare::measureQuality(image, other, measured);
+        std::printf("%s against %s\n", inputFile.c_str(), qualityFile.c_str());
+        printQuality(measured);
+        return 0;
+    }
+
+    if (!contactSheetFile.empty() && !inputFile.empty()) {
+        cv::Mat image, imageAlpha;
+        if (!ImageIO::loadImage(inputFile, image, imageAlpha)) {
+            Errors::Error error = ImageIO::describeLoadFailure(inputFile);
+            std::cerr << "Error: " << error.message << ": " << inputFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        std::vector<Dithering::AlgorithmThumbnail> thumbnails = Dithering::renderAlgorithmThumbnails(
+            image, params, contactSheetSize, {},
+            [](float, const std::string& stage) {
+                std::printf("\r%-60s", (stage + "...").c_str());
+                std::fflush(stdout);
+                return true;
+            });
+        std::printf("\r%-60s\r", "");
+
+        if (!ImageIO::saveImage(contactSheetFile, Dithering::composeContactSheet(thumbnails), cv::Mat(),
+                                exportOptions)) {
+            Errors::Error error = ImageIO::describeSaveFailure(contactSheetFile);
+            std::cerr << "Error: " << error.message << ": " << contactSheetFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Saved " << thumbnails.size() << " algorithms to " << contactSheetFile << "\n";
+        return 0;
+    }
+
+    if (!montageFile.empty() && !inputFile.empty()) {
+        // Resolved here so "current" has every other option applied
+        std::vector<Compare::Variant> variants;
+        for (const auto& name : montageVariants) {
+            Compare::Variant variant;
+            std::string error;
+            std::vector<std::string> warnings;
+            if (name == "current") {
+                variant.params = params;
+            } else if (std::filesystem::path(name).extension() == ".json") {
+                if (!checkPath(Platform::validateInputFile(name, {"json"})) ||
+                    !Settings::loadSettings(name, variant.params, &error, &warnings)) {
+                    if (!error.empty()) std::cerr << "Error: " << name << ": " << error << "\n";
+                    return 1;
+                }
+                variant.label = std::filesystem::path(name).stem().string();
+            } else {
+                if (!Settings::loadPreset(name, variant.params, &error, &warnings)) {
+                    std::cerr << "Error: " << error << "\n";
+                    return 1;
+                }
+                variant.label = name;
+            }
+            for (const auto& warning : warnings) std::cerr << "Warning: " << name << ": " << warning << "\n";
+            variants.push_back(variant);
+        }
+
+        cv::Mat image, imageAlpha;
+        if (!ImageIO::loadImage(inputFile, image, imageAlpha)) {
+            Errors::Error error = ImageIO::describeLoadFailure(inputFile);
+            std::cerr << "Error: " << error.message << ": " << inputFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        cv::Mat montage = Compare::renderMontage(image, variants, montageOptions,
+            [](float, const std::string& stage) {
+                std::printf("\r%-60s", (stage + "...").c_str());
+                std::fflush(stdout);
+                return true;
+            });
+        std::printf("\r%-60s\r", "");
+        if (montage.empty()) {
+            std::cerr << "Error: Could not dither the montage\n";
+            return Errors::getExitCode(Errors::ErrorCode::INTERNAL);
+        }
+
+        if (!ImageIO::saveImage(montageFile, montage, cv::Mat(), exportOptions)) {
+            Errors::Error error = ImageIO::describeSaveFailure(montageFile);
+            std::cerr << "Error: " << error.message << ": " << montageFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Saved " << variants.size() << " variants to " << montageFile << "\n";
+        return 0;
+    }
+
+    if (benchmark && !inputFile.empty()) {
+        cv::Mat image;
+        if (Video::isVideoFile(inputFile)) {
+            // The first frame, at the size video processing would dither it
+            cv::VideoCapture capture(inputFile);
+            if (!capture.read(image)) {
+                std::cerr << "Error: Could not read frames from " << inputFile << "\n";
+                return Errors::getExitCode(Errors::ErrorCode::DECODE);
+            }
+            cv::Size frameSize = Video::getFrameSize(image.size(), videoOptions);
+            if (frameSize != image.size()) cv::resize(image, image, frameSize, 0, 0, cv::INTER_AREA);
+            if (videoOptions.ditherHeight > 0 && videoOptions.ditherHeight < image.rows) {
+                int width = std::max(1, image.cols * videoOptions.ditherHeight / image.rows);
+                cv::resize(image, image, cv::Size(width, videoOptions.ditherHeight), 0, 0, cv::INTER_AREA);
+            }
+        } else {
+            cv::Mat imageAlpha;
+            if (!ImageIO::loadImage(inputFile, image, imageAlpha)) {
+                Errors::Error error = ImageIO::describeLoadFailure(inputFile);
+                std::cerr << "Error: " << error.message << ": " << inputFile << "\n";
+                return Errors::getExitCode(error.code);
+            }
+        }
+
+        std::vector<Dithering::BenchmarkResult> results = Dithering::benchmarkAlgorithms(
+            image, params, benchmarkAlgorithms, benchmarkRuns,
+            [](float, const std::string& stage) {
+                std::printf("\r%-60s", (stage + "...").c_str());
+                std::fflush(stdout);
+                return true;
+            });
+        std::sort(results.begin(), results.end(),
+                  [](const auto& a, const auto& b) { return a.seconds < b.seconds; });
+
+        double megapixels = image.total() / 1e6;
+        std::printf("\r%-60s\r", "");
+        std::printf("Benchmark at %dx%d (%.1f MP), fastest of %d run%s\n", image.cols, image.rows,
+                    megapixels, benchmarkRuns, benchmarkRuns == 1 ? "" : "s");
+        std::printf("  %-18s%10s%10s%10s%12s\n", "Algorithm", "ms", "MP/s", "Frames/s", "Memory MB");
+        for (const auto& result : results) {
+            double seconds = std::max(result.seconds, 1e-6);
+            std::printf("  %-18s%10.1f%10.1f%10.1f%12.1f\n", Settings::getAlgorithmId(result.algorithm).c_str(),
+                        seconds * 1000.0, megapixels / seconds, 1.0 / seconds,
+                        result.memory / (1024.0 * 1024.0));
+        }
+        return 0;
+    }
+
+    if ((inputFile.empty() && !generatedInput) || outputFile.empty()) {
+        std::cerr << "Error: Input and output files are required\n";
+        printUsage(argv[0]);
+        return 1;
+    }
+    // Animated GIFs stay animated when saved as GIF, WebP or APNG, otherwise the first frame is used
+    bool animatedGif = !slideshow && !batch && ImageIO::isAnimatedGif(inputFile) &&
+                       std::find(animationExtensions.begin(), animationExtensions.end(),
+                                 ImageIO::getExtension(outputFile)) != animationExtensions.end();
+    if ((!maskFile.empty() || !maskRects.empty()) &&
+        (slideshow || batch || animatedGif || Video::isVideoFile(inputFile))) {
+        std::cerr << "Warning: Masks only apply to single images, ignoring\n";
+    }
+    if (reconstruct && (slideshow || batch || animatedGif || Video::isVideoFile(inputFile))) {
+        std::cerr << "Warning: --reconstruct only applies to single images, ignoring\n";
+    }
+    if (countColors && (slideshow || batch || animatedGif || Video::isVideoFile(inputFile))) {
+        std::cerr << "Warning: --count-colors only applies to single images, ignoring\n";
+    }
+    if (setWallpaper && (slideshow || batch || animatedGif || Video::isVideoFile(inputFile))) {
+        std::cerr << "Warning: --wallpaper only applies to single images, ignoring\n";
+    }
+
+    if (slideshow) {
+        if (videoOptions.outputFps > 0.0) slideshowOptions.fps = videoOptions.outputFps;
+        slideshowOptions.encoder = videoOptions.encoder;
+
+        std::cout << "Creating slideshow from " << inputFile << "...\n";
+        std::signal(SIGINT, [](int) { interrupted = true; });
+        Errors::Error error;
+        bool ok = Video::createSlideshow(inputFile, outputFile, slideshowOptions,
+            printFrameProgress, &error);
+        std::cout << "\n";
+
+        if (!ok) {
+            std::cerr << "Error: Slideshow creation failed: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Done!\n";
+        return 0;
+    }
+
+    // Generate the palette from the reference image once and reuse it as-is
+    if (!paletteReference.empty()) {
+        cv::Mat reference, referenceAlpha;
+        if (!ImageIO::loadImage(paletteReference, reference, referenceAlpha)) {
+            std::cerr << "Error: Could not load palette reference: " << paletteReference << "\n";
+            return 1;
+        }
+        params.customPalette = Dithering::generatePalette(
+            Dithering::preprocessImage(reference, params), params.paletteSize,
+            params.paletteMethod, params.seed);
+        params.paletteMode = Dithering::PaletteMode::CUSTOM;
+        std::cout << "Palette locked to " << params.customPalette.size() << " colors from " << paletteReference << "\n";
+    }
+
+    if (params.colorMode == Dithering::ColorMode::PIXEL_FORMAT &&
+        params.pixelFormat == Dithering::PixelFormat::RGBA4444) {
+        exportOptions.alphaLevels = 16;
+    }
+
+    if (batch) {
+        // The last path is the output folder, the others images or folders of images
+        std::vector<std::string> paths = {inputFile, outputFile};
+        paths.insert(paths.end(), extraFiles.begin(), extraFiles.end());
+        std::string outputDir = paths.back();
+        paths.pop_back();
+
+        auto reportFile = [&params](int done, int total, const Batch::FileResult& file) {
+            std::cout << "[" << done << "/" << total << "] ";
+            if (file.ok()) {
+                std::cout << file.input << " -> " << file.output << " (" << file.seconds << " s)\n";
+                UsageStats::recordJob(getAlgorithmLabel(params),
+                                      file.size.width, file.size.height, file.seconds, false);
+            } else {
+                std::cout << "Error: " << file.error.message << ": " << file.input << "\n";
+            }
+        };
+
+        if (watch) {
+            std::error_code ec;
+            if (paths.size() != 1 || !std::filesystem::is_directory(paths[0], ec)) {
+                std::cerr << "Error: --watch needs one input folder and an output folder\n";
+                return 1;
+            }
+            if (std::filesystem::equivalent(paths[0], outputDir, ec)) {
+                std::cerr << "Error: The output folder must differ from the watched folder\n";
+                return 1;
+            }
+        }
+
+        std::vector<std::string> inputs;
+        for (const auto& path : paths) {
+            std::error_code ec;
+            if (std::filesystem::is_directory(path, ec)) {
+                std::vector<std::string> images = Batch::collectImages(path);
+                inputs.insert(inputs.end(), images.begin(), images.end());
+            } else {
+                Platform::PathCheck check = Platform::validateInputFile(path, ImageIO::getImageExtensions());
+                if (!checkPath(check)) return 1;
+                inputs.push_back(path);
+            }
+        }
+        if (inputs.empty() && !watch) {
+            std::cerr << "Error: No images to process\n";
+            return 1;
+        }
+        if (!checkPath(Platform::validateDirectory(outputDir, true))) return 1;
+
+        batchOptions.memoryLimit = memoryLimitMB * 1024 * 1024;
+        batchOptions.ditherAlpha = ditherAlpha;
+        batchOptions.exportOptions = exportOptions;
+        if (rotation % 90 != 0) {
+            std::cerr << "Warning: Rotation must be 90, 180 or 270 degrees, ignoring\n";
+            rotation = 0;
+        }
+        if (!crop.empty() || resize.width > 0 || resize.height > 0 || rotation % 360 != 0 || !flips.empty() ||
+            device || descreen) {
+            batchOptions.transform = [&](const cv::Mat& image) {
+                cv::Mat result = image;
+                if (!crop.empty()) {
+                    if ((crop & cv::Rect(0, 0, result.cols, result.rows)).empty()) return cv::Mat();
+                    result = Transform::cropImage(result, crop);
+                }
+                if (resize.width > 0 || resize.height > 0) {
+                    result = Transform::resizeImage(result, Transform::fitSize(result.size(), resize), resizeFilter);
+                }
+                if (rotation % 360 != 0) result = Transform::rotateImage(result, rotation);
+                for (Transform::FlipAxis axis : flips) result = Transform::flipImage(result, axis);
+                if (device) result = Devices::fitToScreen(result, *device);
+                // Alpha is the single channel image here and keeps its edges
+                if (descreen && result.channels() > 1) result = Descreen::reconstruct(result, descreenOptions);
+                return result;
+            };
+        }
+
+        std::cout << "Algorithm: " << getAlgorithmLabel(params) << "\n";
+        std::cout << "Palette: " << Dithering::getPaletteModeName(params.paletteMode) << "\n";
+
+        // Existing images whose output is missing or out of date are processed too
+        if (watch) {
+            static std::atomic<bool> stopWatching{false};
+            std::signal(SIGINT, [](int) { stopWatching = true; });
+            std::cout << "Watching " << paths[0] << " (Ctrl+C to stop)...\n";
+            Batch::watchFolder(paths[0], outputDir, params, batchOptions,
+                               []() { return !stopWatching; }, reportFile);
+            std::cout << "\nStopped watching\n";
+            return 0;
+        }
+
+        std::cout << "Dithering " << inputs.size() << " images into " << outputDir << "...\n";
+        std::vector<Batch::FileResult> results = Batch::processBatch(inputs, outputDir, params, batchOptions,
+                                                                     reportFile);
+
+        auto firstFailure = std::find_if(results.begin(), results.end(),
+            [](const Batch::FileResult& file) { return !file.ok(); });
+        size_t failed = static_cast<size_t>(std::count_if(results.begin(), results.end(),
+            [](const Batch::FileResult& file) { return !file.ok(); }));
+        std::cout << "Done! " << results.size() - failed << " of " << results.size() << " images dithered\n";
+        return firstFailure == results.end() ? 0 : Errors::getExitCode(firstFailure->error.code);
+    }
+
+    videoOptions.encode = exportOptions.encode;
+
+    // One frame with the video settings, to try them before the full job
+    if (previewSeconds >= 0.0 && Video::isVideoFile(inputFile)) {
+        cv::Mat preview;
+        Errors::Error error;
+        if (!Video::previewFrame(inputFile, previewSeconds, params, videoOptions, preview, &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        if (!ImageIO::saveImage(outputFile, preview, cv::Mat(), exportOptions)) {
+            Errors::Error saveError = ImageIO::describeSaveFailure(outputFile);
+            std::cerr << "Error: " << saveError.message << ": " << outputFile << "\n";
+            return Errors::getExitCode(saveError.code);
+        }
+        std::cout << "Saved preview frame to " << outputFile << "\n";
+        return 0;
+    }
+    bool recording = screenCapture && (Video::isVideoFile(outputFile) ||
+                                       std::find(animationExtensions.begin(), animationExtensions.end(),
+                                                 ImageIO::getExtension(outputFile)) != animationExtensions.end());
+    if (recording) {
+        std::cout << "Recording the screen for " << recordSeconds << " s (Ctrl+C cancels)...\n";
+        std::cout << "Algorithm: " << getAlgorithmLabel(params) << "\n";
+
+        std::signal(SIGINT, [](int) { interrupted = true; });
+        Errors::Error error;
+        bool ok = Video::recordScreen(captureRegion, recordSeconds, outputFile, params, videoOptions,
+            printFrameProgress, &error);
+        std::cout << "\n";
+
+        if (!ok) {
+            std::cerr << "Error: Screen recording failed: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Done!\n";
+        return 0;
+    }
+
+    if (Video::isVideoFile(inputFile)) {
+        std::cout << "Processing video " << inputFile << "...\n";
+        std::cout << "Algorithm: " << getAlgorithmLabel(params) << "\n";
+        std::cout << "Palette: " << Dithering::getPaletteModeName(params.paletteMode) << "\n";
+
+        auto start = std::chrono::high_resolution_clock::now();
+        std::signal(SIGINT, [](int) { interrupted = true; });
+        Errors::Error error;
+        bool ok = Video::processVideo(inputFile, outputFile, params, videoOptions,
+            printFrameProgress, &error);
+        auto end = std::chrono::high_resolution_clock::now();
+        std::cout << "\n";
+
+        if (!ok) {
+            std::cerr << "Error: Video processing failed: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        float elapsed = std::chrono::duration<float>(end - start).count();
+        std::cout << "Processing time: " << elapsed << " s\n";
+        if (videoOptions.spriteSheet) {
+            std::cout << "Sprite sheet descriptor: " << Video::getSpriteSheetDescriptor(outputFile) << "\n";
+        }
+
+        cv::VideoCapture probe(inputFile);
+        UsageStats::recordJob(getAlgorithmLabel(params),
+                              static_cast<int>(probe.get(cv::CAP_PROP_FRAME_WIDTH)),
+                              static_cast<int>(probe.get(cv::CAP_PROP_FRAME_HEIGHT)), elapsed, true);
+        std::cout << "Done!\n";
+        return 0;
+    }
+
+    if (animatedGif) {
+        std::cout << "Processing animation " << inputFile << "...\n";
+        std::cout << "Algorithm: " << getAlgorithmLabel(params) << "\n";
+        std::cout << "Palette: " << Dithering::getPaletteModeName(params.paletteMode) << "\n";
+
+        auto start = std::chrono::high_resolution_clock::now();
+        std::signal(SIGINT, [](int) { interrupted = true; });
+        Errors::Error error;
+        bool ok = Video::processAnimation(inputFile, outputFile, params, videoOptions,
+            printFrameProgress, &error);
+        auto end = std::chrono::high_resolution_clock::now();
+        std::cout << "\n";
+
+        if (!ok) {
+            std::cerr << "Error: Animation processing failed: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+
+        float elapsed = std::chrono::duration<float>(end - start).count();
+        std::cout << "Processing time: " << elapsed << " s\n";
+
+        ImageIO::ImageInfo info;
+        ImageIO::getImageInfo(inputFile, info);
+        UsageStats::recordJob(getAlgorithmLabel(params), info.width, info.height, elapsed, true);
+        std::cout << "Done!\n";
+        return 0;
+    }
+
+    // Load image
+    cv::Mat input, alpha, precise;
+    std::vector<ImageIO::Warning> warnings;
+    bool loaded = false;
+    if (screenCapture) {
+        std::cout << "Capturing the screen...\n";
+        Errors::Error error;
+        if (!Video::captureScreen(captureRegion, input, &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        loaded = true;
+    } else if (!text.empty()) {
+        std::cout << "Rendering text...\n";
+        if (!ImageIO::renderText(text, textOptions, input, &warnings)) {
+            std::cerr << "Error: Could not render the text\n";
+            return Errors::getExitCode(Errors::ErrorCode::INTERNAL);
+        }
+        loaded = true;
+    } else if (Platform::isRemoteUrl(inputFile)) {
+        std::cout << "Loading " << inputFile << "...\n";
+        loaded = ImageIO::loadImageFromUrl(inputFile, input, alpha, &warnings, maxDownloadBytes,
+            [](size_t received, size_t total) {
+                std::cout << "\rDownloaded " << received / 1024 << " KB";
+                if (total > 0) std::cout << " / " << total / 1024 << " KB";
+                std::cout << std::flush;
+            }, &precise);
+        std::cout << "\n";
+    } else {
+        std::cout << "Loading " << inputFile << "...\n";
+        loaded = ImageIO::loadImage(inputFile, input, alpha, &warnings, &precise);
+    }
+    if (!loaded) {
+        Errors::Error error = Platform::isRemoteUrl(inputFile)
+                                  ? Errors::Error(Errors::ErrorCode::IO, "Could not download the image")
+                                  : ImageIO::describeLoadFailure(inputFile);
+        std::cerr << "Error: " << error.message << ": " << inputFile << "\n";
+        return Errors::getExitCode(error.code);
+    }
+    for (const auto& warning : warnings) {
+        std::cerr << "Warning: " << warning.message << "\n";
+    }
+    if (exportOptions.encode.keepMetadata && !generatedInput && !Platform::isRemoteUrl(inputFile)) {
+        ImageIO::readMetadata(inputFile, exportOptions.metadata);
+    }
+
+    // The mask is drawn on the input as loaded
+    cv::Mat mask;
+    if (!maskFile.empty()) {
+        if (!ImageIO::loadMask(maskFile, mask)) {
+            Errors::Error error = ImageIO::describeLoadFailure(maskFile);
+            std::cerr << "Error: " << error.message << ": " << maskFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        if (mask.size() != input.size()) {
+            cv::resize(mask, mask, input.size(), 0, 0, cv::INTER_LINEAR);
+        }
+    }
+    if (!maskRects.empty()) {
+        cv::Mat rects = Dithering::maskFromRects(input.size(), maskRects);
+        if (mask.empty()) {
+            mask = rects;
+        } else {
+            cv::max(mask, rects, mask);
+        }
+    }
+
+    // Color, alpha, full-precision color and the mask go through the same transforms
+    auto transformAll = [&](auto transform) {
+        input = transform(input);
+        if (!alpha.empty()) alpha = transform(alpha);
+        if (!precise.empty()) precise = transform(precise);
+        if (!mask.empty()) mask = transform(mask);
+    };
+    if (!crop.empty()) {
+        if ((crop & cv::Rect(0, 0, input.cols, input.rows)).empty()) {
+            std::cerr << "Error: Crop lies outside the image\n";
+            return 1;
+        }
+        transformAll([&](const cv::Mat& image) { return Transform::cropImage(image, crop); });
+    }
+    if (resize.width > 0 || resize.height > 0) {
+        cv::Size size = Transform::fitSize(input.size(), resize);
+        transformAll([&](const cv::Mat& image) { return Transform::resizeImage(image, size, resizeFilter); });
+    }
+    if (rotation % 90 != 0) {
+        std::cerr << "Warning: Rotation must be 90, 180 or 270 degrees, ignoring\n";
+    } else if (rotation % 360 != 0) {
+        transformAll([&](const cv::Mat& image) { return Transform::rotateImage(image, rotation); });
+    }
+    for (Transform::FlipAxis axis : flips) {
+        transformAll([&](const cv::Mat& image) { return Transform::flipImage(image, axis); });
+    }
+    if (device) {
+        transformAll([&](const cv::Mat& image) { return Devices::fitToScreen(image, *device); });
+    }
+
+    if (descreen || reconstruct) {
+        float period = 0.0f;
+        input = Descreen::reconstruct(input, descreenOptions, &period);
+        if (!precise.empty()) {
+            Descreen::Options same = descreenOptions;
+            same.period = period;
+            precise = Descreen::reconstruct(precise, same);
+        }
+        std::printf("Descreened: %s, %.1f px period%s\n", Descreen::getMethodName(descreenOptions.method).c_str(),
+                    period, descreenOptions.period > 0.0f ? "" : " (detected)");
+    }
+    if (reconstruct) {
+        std::cout << "Saving to " << outputFile << "...\n";
+        if (!ImageIO::saveImage(outputFile, input, alpha, exportOptions)) {
+            Errors::Error error = ImageIO::describeSaveFailure(outputFile);
+            std::cerr << "Error: " << error.message << ": " << outputFile << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Done!\n";
+        return 0;
+    }
+
+    std::cout << "Image size: " << input.cols << "x" << input.rows << "\n";
+    if (!precise.empty()) {
+        std::cout << "Precision: high bit depth source, dithered at full precision\n";
+    }
+    std::cout << "Algorithm: " << getAlgorithmLabel(params) << "\n";
+    std::cout << "Palette: " << Dithering::getPaletteModeName(params.paletteMode) << "\n";
+    if (!mask.empty()) {
+        std::cout << "Mask: " << static_cast<int>(cv::mean(mask)[0] / 2.55 + 0.5) << "% dithered\n";
+    }
+    if (params.autoThreshold) {
+        std::cout << "Threshold: " << Dithering::computeAutoThreshold(input, params) << " (auto)\n";
+    }
+    if (params.colorMode != Dithering::ColorMode::RGB) {
+        std::cout << "Color mode: " << Dithering::getColorModeName(params.colorMode);
+        if (params.colorMode == Dithering::ColorMode::PIXEL_FORMAT) {
+            std::cout << " (" << Dithering::getPixelFormatName(params.pixelFormat) << ")";
+        }
+        std::cout << "\n";
+    }
+    if (!alpha.empty()) {
+        std::cout << "Alpha: preserved";
+        if (ditherAlpha) {
+            std::cout << " (dithered to 1-bit)";
+        } else if (exportOptions.binaryAlpha || ImageIO::formatHasBinaryAlpha(outputFile)) {
+            std::cout << " (binary, cutoff " << exportOptions.alphaThreshold << ")";
+        } else if (!ImageIO::formatSupportsAlpha(outputFile)) {
+            std::cout << " (flattened, output format has no alpha)";
+        }
+        std::cout << "\n";
+    }
+
+    ImageIO::applyMatte(input, alpha, exportOptions);
+    cv::Mat original = input;   // 8-bit, for --quality
+    if (!precise.empty()) {
+        ImageIO::applyMatte(precise, alpha, exportOptions);
+        input = precise;
+    }
+
+    // Process image
+    std::cout << "Processing...\n";
+    auto start = std::chrono::high_resolution_clock::now();
+    cv::Mat output;
+    std::vector<cv::Mat> plates;
+    if (separations && !tile.empty()) {
+        std::cerr << "Error: --separations can't be combined with --tile\n";
+        return 1;
+    }
+    if (separations && !mask.empty()) {
+        std::cerr << "Error: --separations can't be combined with a mask\n";
+        return 1;
+    }
+    if (separations) {
+        plates = Dithering::ditherSeparations(input, params);
+        output = Dithering::combineSeparations(plates);
+    } else if (!tile.empty()) {
+        tile = tile & cv::Rect(0, 0, input.cols, input.rows);
+        if (tile.empty()) {
+            std::cerr << "Error: Tile lies outside the image\n";
+            return 1;
+        }
+        std::cout << "Tile: " << tile.width << "x" << tile.height << " at " << tile.x << "," << tile.y << "\n";
+        output = Dithering::ditherTile(input, tile, params, tileMargin);
+        if (!alpha.empty()) alpha = alpha(tile).clone();
+    } else {
+        size_t memoryLimit = memoryLimitMB * 1024 * 1024;
+        if (memoryLimit > 0 && params.pixelate <= 1 &&
+            Dithering::estimateMemory(input.size(), params) > memoryLimit) {
+            std::cout << "Dithering in bands to stay under " << memoryLimitMB << " MB\n";
+        }
+        output = Dithering::ditherWithinMemory(input, params, memoryLimit,
+            [](float fraction, const std::string& stage) {
+                std::cout << "\r" << stage << " (" << static_cast<int>(fraction * 100.0f) << "%)   " << std::flush;
+                return true;
+            });
+        std::cout << "\n";
+    }
+    if (!mask.empty()) {
+        output = Dithering::applyMask(output, tile.empty() ? original : original(tile),
+                                      tile.empty() ? mask : mask(tile));
+    }
+    if (ditherAlpha && !alpha.empty()) {
+        alpha = Dithering::ditherAlpha(alpha, params);
+    }
+    auto end = std::chrono::high_resolution_clock::now();
+
+    float elapsed = std::chrono::duration<float, std::milli>(end - start).count();
+    std::cout << "Processing time: " << elapsed << " ms\n";
+
+    // Save image
+    std::cout << "Saving to " << outputFile << "...\n";
+    uintmax_t fileSize = 0;
+    if (!ImageIO::saveImage(outputFile, output, alpha, exportOptions, &fileSize)) {
+        Errors::Error error = ImageIO::describeSaveFailure(outputFile);
+        std::cerr << "Error: " << error.message << ": " << outputFile << "\n";
+        return Errors::getExitCode(error.code);
+    }
+    std::cout << "File size: " << ImageIO::formatFileSize(fileSize) << "\n";
+
+    // Plates go next to the output, named after it: poster.png -> poster_c.png, ...
+    const char* plateNames[4] = {"c", "m", "y", "k"};
+    for (size_t p = 0; p < plates.size(); ++p) {
+        std::filesystem::path platePath(outputFile);
+        platePath.replace_filename(platePath.stem().string() + "_" + plateNames[p] +
+                                   platePath.extension().string());
+        std::cout << "Saving plate to " << platePath.string() << "...\n";
+        if (!ImageIO::saveImage(platePath.string(), plates[p], cv::Mat(), exportOptions)) {
+            std::cerr << "Error: Could not save plate: " << platePath.string() << "\n";
+            return 1;
+        }
+    }
+
+    if (!posterFile.empty()) {
+        Poster::Layout layout;
+        if (!Poster::computeLayout(output.size(), posterOptions, layout)) {
+            std::cerr << "Error: Overlap and margins don't fit on the poster paper, or the poster is over "
+                      << Poster::MAX_POSTER_PIXELS << " dots wide or tall\n";
+            return 1;
+        }
+        std::cout << "Saving " << layout.columns << "x" << layout.rows << " sheet poster to " << posterFile << "...\n";
+        if (!Poster::exportPosterPdf(output, posterFile, posterOptions)) {
+            std::cerr << "Error: Could not save poster: " << posterFile << "\n";
+            return 1;
+        }
+    }
+
+    if (!pdfFile.empty()) {
+        double widthInches = 0.0, heightInches = 0.0;
+        if (!Poster::computePrintSize(output.size(), printOptions, widthInches, heightInches)) {
+            std::fprintf(stderr, "Error: A %dx%d image is %.2fx%.2f in at %g dpi, too big for the page. "
+                         "Raise --pdf-dpi or use --poster\n", output.cols, output.rows, widthInches, heightInches,
+                         printOptions.dpi);
+            return 1;
+        }
+        std::printf("Saving %.2fx%.2f in print to %s...\n", widthInches, heightInches, pdfFile.c_str());
+        Halftone::VectorHalftone halftone;
+        if (pdfVector) halftone = Halftone::traceHalftone(input, params);
+        if (!Poster::exportPrintPdf(output, pdfFile, printOptions, pdfVector ? &halftone : nullptr)) {
+            std::cerr << "Error: Could not save PDF: " << pdfFile << "\n";
+            return 1;
+        }
+    }
+
+    if (!svgFile.empty()) {
+        std::cout << "Saving vector halftone to " << svgFile << "...\n";
+        if (!Halftone::exportSvg(input, params, svgFile)) {
+            std::cerr << "Error: Could not save SVG: " << svgFile << "\n";
+            return 1;
+        }
+    }
+
+    if (!asciiFile.empty() || !asciiImageFile.empty()) {
+        std::vector<std::string> lines = Ascii::convertToText(input, params, asciiOptions);
+        if (asciiFile == "-") {
+            std::cout << Ascii::joinLines(lines);
+        } else if (!asciiFile.empty()) {
+            std::cout << "Saving ASCII art to " << asciiFile << "...\n";
+            if (!Ascii::exportText(lines, asciiFile)) {
+                std::cerr << "Error: Could not save ASCII art: " << asciiFile << "\n";
+                return 1;
+            }
+        }
+        if (!asciiImageFile.empty()) {
+            std::cout << "Saving ASCII art image to " << asciiImageFile << "...\n";
+            cv::Mat rendered = Ascii::renderText(lines, asciiOptions, params.inkColor, params.paperColor);
+            if (!ImageIO::saveImage(asciiImageFile, rendered, cv::Mat(), exportOptions)) {
+                std::cerr << "Error: Could not save ASCII art image: " << asciiImageFile << "\n";
+                return 1;
+            }
+        }
+    }
+
+    if (!ansiFile.empty()) {
+        std::vector<std::string> lines = Ascii::convertToAnsi(output, ansiColumns, ansiColors);
+        if (ansiFile == "-") {
+            std::cout << Ascii::joinLines(lines);
+        } else {
+            std::cout << "Saving ANSI text to " << ansiFile << "...\n";
+            if (!Ascii::exportText(lines, ansiFile)) {
+                std::cerr << "Error: Could not save ANSI text: " << ansiFile << "\n";
+                return 1;
+            }
+        }
+    }
+
+    if (!plotFile.empty()) {
+        Plotter::Plot plot = Plotter::traceDots(output, params, plotOptions);
+        std::printf("Saving %zu plotter dots (%.2f mm pitch, %.1f m pen travel) to %s...\n", plot.dots.size(),
+                    plot.pitchMm, Plotter::travelDistance(plot) / 1000.0, plotFile.c_str());
+        if (!Plotter::exportPlot(plot, plotFile, plotOptions)) {
+            std::cerr << "Error: Could not save plot: " << plotFile << "\n";
+            return 1;
+        }
+    }
+
+    if (!embeddedFile.empty()) {
+        size_t byteCount = 0;
+        std::cout << "Saving " << Embedded::getLayoutName(embeddedOptions.layout) << " data to " << embeddedFile
+                  << "...\n";
+        if (!Embedded::exportEmbedded(output, embeddedFile, embeddedOptions, &byteCount)) {
+            std::cerr << "Error: Could not save embedded data: " << embeddedFile << "\n";
+            return 1;
+        }
+        std::cout << "Packed size: " << byteCount << " bytes\n";
+    }
+
+    if (!tilesFile.empty()) {
+        std::cout << "Saving zoomable tiles to " << tilesFile << "...\n";
+        Errors::Error error;
+        if (!Tiles::exportDeepZoom(output, tilesFile, &error)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+    }
+
+    if (!progressionFile.empty()) {
+        std::cout << "Saving the dithering progression to " << progressionFile << " (Ctrl+C cancels)...\n";
+        if (videoOptions.outputFps > 0.0) progressionOptions.fps = videoOptions.outputFps;
+        progressionOptions.upscale = videoOptions.upscale;
+        progressionOptions.encoder = videoOptions.encoder;
+        std::signal(SIGINT, [](int) { interrupted = true; });
+        Errors::Error error;
+        bool ok = Video::exportProgression(tile.empty() ? input : input(tile), progressionFile, params,
+                                           progressionOptions, printFrameProgress, &error);
+        std::cout << "\n";
+        if (!ok) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+    }
+
+    if (setWallpaper) {
+        std::cout << "Setting the desktop wallpaper...\n";
+        Errors::Error error;
+        std::string wallpaperPath;
+        const cv::Mat& source = tile.empty() ? input : input(tile);
+        if (!Wallpaper::setWallpaper(source, params, wallpaperOptions, &error, &wallpaperPath)) {
+            std::cerr << "Error: " << error.message << "\n";
+            return Errors::getExitCode(error.code);
+        }
+        std::cout << "Wallpaper: " << wallpaperPath << "\n";
+    }
+
+    if (!compareFile.empty()) {
+        cv::Mat reference, referenceAlpha;
+        Compare::Result comparison;
+        if (!ImageIO::loadImage(compareFile, reference, referenceAlpha) ||
+            !Compare::compareImages(output, reference, comparison)) {
+            std::cerr << "Error: Could not compare with: " << compareFile << "\n";
+            return 1;
+        }
+
+        std::printf("Compared with %s\n", compareFile.c_str());
+        if (comparison.offset.x != 0.0 || comparison.offset.y != 0.0) {
+            std::printf("  Alignment offset:  %+.0f,%+.0f px\n", comparison.offset.x, comparison.offset.y);
+        }
+        std::printf("  Tone SSIM:         %.4f\n", comparison.toneSsim);
+        std::printf("  Tone PSNR:         %.2f dB\n", comparison.tonePsnr);
+        std::printf("  Pixel PSNR:        %.2f dB\n", comparison.psnr);
+        std::printf("  Mean difference:   %.2f\n", comparison.meanDifference);
+        std::printf("  Matching pixels:   %.1f%%\n", comparison.matchingPixels);
+
+        if (!diffFile.empty()) {
+            if (!ImageIO::saveImage(diffFile, comparison.diff, cv::Mat(), ImageIO::ExportOptions())) {
+                Errors::Error error = ImageIO::describeSaveFailure(diffFile);
+                std::cerr << "Error: Could not save diff: " << error.message << ": " << diffFile << "\n";
+                return Errors::getExitCode(error.code);
+            }
+            std::cout << "Saved diff to " << diffFile << "\n";
+        }
+    }
+
+    if (quality) {
+        Compare::Quality measured;
+        Compare::measureQuality(output, tile.empty() ? original : original(tile), measured);
+        std::printf("Quality against the original\n");
+        printQuality(measured);
+    }
+
+    Compare::ColorReport colorReport;
+    // Outside a mask the result keeps the original's colors, which aren't dithered
+    cv::Mat countMask = mask.empty() || tile.empty() ? mask : mask(tile);
+    if (countColors && Compare::countOutputColors(output, params, colorReport, countMask)) {
+        printColorReport(colorReport);
+    }
+
+    UsageStats::recordJob(getAlgorithmLabel(params), output.cols, output.rows,
+                          elapsed / 1000.0f, false);
+    if (colorReport.exceedsPalette()) {
+        std::cerr << "Error: The result has " << colorReport.colors.size() << " colors";
+        if (colorReport.outsidePalette > 0) {
+            std::cerr << ", " << colorReport.outsidePalette << " of them not in the palette\n";
+        } else {
+            std::cerr << ", more than the palette's " << colorReport.limit << "\n";
+        }
+        return 1;
+    }
+    std::cout << "Done!\n";
+    return 0;
+}
+
+int main(int argc, char** argv) {
+    ImageIO::enableOpenExr();
+    Platform::ignoreBrokenPipes();
+    // Malformed values (e.g. "--fps abc") throw from std::stoi/std::stod while
+    // the arguments are read; name the argument instead of aborting. Later
+    // failures (e.g. running out of memory) exit with their documented status
+    std::string parsing;
+    try {
+        return run(argc, argv, parsing);
+    } catch (const std::exception& e) {
+        if (!parsing.empty()) {
+            std::cerr << "Error: Invalid value for " << parsing << "\n";
+            return Errors::getExitCode(Errors::ErrorCode::INVALID_ARGUMENT);
+        }
+        Errors::Error error = Errors::fromException(e);
+        std::cerr << "Error: " << error.message << "\n";
+        return Errors::getExitCode(error.code);
+    }
 }
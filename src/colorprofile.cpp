@@ -0,0 +1,407 @@
+#include "colorprofile.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdint>
+#include <cstring>
+
+namespace ColorProfile {
+
+const std::vector<std::string>& getSpaceIds() {
+    static const std::vector<std::string> ids = {"srgb", "display-p3", "adobe-rgb"};
+    return ids;
+}
+
+bool parseSpace(const std::string& id, Space& space) {
+    const auto& ids = getSpaceIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    space = static_cast<Space>(match - ids.begin());
+    return true;
+}
+
+std::string getSpaceName(Space space) {
+    switch (space) {
+        case Space::SRGB: return "sRGB";
+        case Space::DISPLAY_P3: return "Display P3";
+        case Space::ADOBE_RGB: return "Adobe RGB (1998)";
+        default: return "Unknown";
+    }
+}
+
+// White points in XYZ; D50 is the profile connection space's
+static const cv::Vec3d D50(0.9642, 1.0, 0.8249);
+static const cv::Vec3d D65(0.9505, 1.0, 1.0890);
+
+static uint32_t readUint32(const unsigned char* p) {
+    return uint32_t(p[0]) << 24 | uint32_t(p[1]) << 16 | uint32_t(p[2]) << 8 | p[3];
+}
+
+static uint16_t readUint16(const unsigned char* p) {
+    return static_cast<uint16_t>(p[0] << 8 | p[1]);
+}
+
+// s15Fixed16Number
+static double readFixed(const unsigned char* p) {
+    return static_cast<int32_t>(readUint32(p)) / 65536.0;
+}
+
+// Data of the tag with this signature, if it lies inside the profile
+static bool findTag(const std::vector<unsigned char>& icc, const char* signature, const unsigned char*& data,
+                    size_t& size) {
+    if (icc.size() < 132) return false;
+    size_t count = readUint32(icc.data() + 128);
+    if (count > (icc.size() - 132) / 12) return false;
+    for (size_t i = 0; i < count; ++i) {
+        const unsigned char* entry = icc.data() + 132 + 12 * i;
+        if (std::memcmp(entry, signature, 4) != 0) continue;
+        size_t offset = readUint32(entry + 4);
+        size = readUint32(entry + 8);
+        if (offset > icc.size() || size > icc.size() - offset || size < 8) return false;
+        data = icc.data() + offset;
+        return true;
+    }
+    return false;
+}
+
+// ICC parametric curve types 0-4: g, a, b, c, d, e, f in that order
+static double evaluateParametric(int type, const double* p, double x) {
+    auto power = [&](double value) { return std::pow(std::max(value, 0.0), p[0]); };
+    switch (type) {
+        case 0: return power(x);
+        case 1: return x >= -p[2] / p[1] ? power(p[1] * x + p[2]) : 0.0;
+        case 2: return x >= -p[2] / p[1] ? power(p[1] * x + p[2]) + p[3] : p[3];
+        case 3: return x >= p[4] ? power(p[1] * x + p[2]) : p[3] * x;
+        default: return x >= p[4] ? power(p[1] * x + p[2]) + p[5] : p[3] * x + p[6];
+    }
+}
+
+static void sampleParametric(int type, const double* params, std::vector<float>& curve) {
+    curve.resize(CURVE_POINTS);
+    for (int i = 0; i < CURVE_POINTS; ++i) {
+        double value = evaluateParametric(type, params, i / double(CURVE_POINTS - 1));
+        curve[i] = static_cast<float>(std::clamp(value, 0.0, 1.0));
+    }
+}
+
+// Sample a curv (gamma or table) or para tag
+static bool readCurve(const unsigned char* data, size_t size, std::vector<float>& curve) {
+    if (size >= 12 && std::memcmp(data, "curv", 4) == 0) {
+        size_t count = readUint32(data + 8);
+        if (count > (size - 12) / 2) return false;
+        curve.resize(CURVE_POINTS);
+        for (int i = 0; i < CURVE_POINTS; ++i) {
+            double x = i / double(CURVE_POINTS - 1);
+            double y = x;
+            if (count == 1) {
+                y = std::pow(x, readUint16(data + 12) / 256.0);
+            } else if (count > 1) {
+                double position = x * (count - 1);
+                size_t index = std::min(static_cast<size_t>(position), count - 2);
+                double low = readUint16(data + 12 + 2 * index) / 65535.0;
+                double high = readUint16(data + 14 + 2 * index) / 65535.0;
+                y = low + (high - low) * (position - index);
+            }
+            curve[i] = static_cast<float>(y);
+        }
+        return true;
+    }
+    if (size >= 12 && std::memcmp(data, "para", 4) == 0) {
+        static const size_t paramCounts[5] = {1, 3, 4, 5, 7};
+        int type = readUint16(data + 8);
+        if (type > 4 || size < 12 + 4 * paramCounts[type]) return false;
+        double params[7] = {};
+        for (size_t i = 0; i < paramCounts[type]; ++i) params[i] = readFixed(data + 12 + 4 * i);
+        if ((type == 1 || type == 2) && params[1] == 0.0) return false;
+        sampleParametric(type, params, curve);
+        return true;
+    }
+    return false;
+}
+
+static bool readXyz(const std::vector<unsigned char>& icc, const char* signature, cv::Vec3d& xyz) {
+    const unsigned char* data = nullptr;
+    size_t size = 0;
+    if (!findTag(icc, signature, data, size) || size < 20 || std::memcmp(data, "XYZ ", 4) != 0) return false;
+    for (int c = 0; c < 3; ++c) xyz[c] = readFixed(data + 8 + 4 * c);
+    return true;
+}
+
+std::string readDescription(const std::vector<unsigned char>& icc) {
+    const unsigned char* data = nullptr;
+    size_t size = 0;
+    if (!findTag(icc, "desc", data, size)) return "";
+
+    std::string text;
+    if (std::memcmp(data, "desc", 4) == 0 && size >= 12) {
+        // ICC v2: ASCII, with its length including the terminator
+        size_t length = readUint32(data + 8);
+        if (length <= size - 12) text.assign(reinterpret_cast<const char*>(data + 12), length);
+    } else if (std::memcmp(data, "mluc", 4) == 0 && size >= 28) {
+        // ICC v4: UTF-16 strings per language. The first is used, and
+        // characters outside ASCII become question marks
+        size_t length = readUint32(data + 20);
+        size_t offset = readUint32(data + 24);
+        if (offset <= size && length <= size - offset) {
+            for (size_t i = 0; i + 1 < length; i += 2) {
+                uint16_t character = readUint16(data + offset + i);
+                text += character < 128 ? static_cast<char>(character) : '?';
+            }
+        }
+    }
+    text.erase(std::find(text.begin(), text.end(), '\0'), text.end());
+    return text;
+}
+
+bool parseProfile(const std::vector<unsigned char>& icc, Profile& profile) {
+    if (icc.size() < 132 || std::memcmp(icc.data() + 36, "acsp", 4) != 0) return false;
+    // Matrix/TRC profiles connect through XYZ
+    if (std::memcmp(icc.data() + 20, "XYZ ", 4) != 0) return false;
+
+    Profile parsed;
+    parsed.description = readDescription(icc);
+    const unsigned char* data = nullptr;
+    size_t size = 0;
+    if (std::memcmp(icc.data() + 16, "RGB ", 4) == 0) {
+        static const char* columns[3] = {"rXYZ", "gXYZ", "bXYZ"};
+        static const char* curves[3] = {"rTRC", "gTRC", "bTRC"};
+        for (int c = 0; c < 3; ++c) {
+            cv::Vec3d column;
+            if (!readXyz(icc, columns[c], column) || !findTag(icc, curves[c], data, size) ||
+                !readCurve(data, size, parsed.curves[c])) {
+                return false;
+            }
+            for (int r = 0; r < 3; ++r) parsed.toXyz(r, c) = column[r];
+        }
+        if (std::abs(cv::determinant(parsed.toXyz)) < 1e-6) return false;
+    } else if (std::memcmp(icc.data() + 16, "GRAY", 4) == 0) {
+        if (!findTag(icc, "kTRC", data, size) || !readCurve(data, size, parsed.curves[0])) return false;
+        parsed.curves[1] = parsed.curves[2] = parsed.curves[0];
+        for (int r = 0; r < 3; ++r) {
+            for (int c = 0; c < 3; ++c) parsed.toXyz(r, c) = D50[r] / 3.0;
+        }
+    } else {
+        return false;
+    }
+
+    profile = parsed;
+    return true;
+}
+
+// Bradford chromatic adaptation from one white point to another
+static cv::Matx33d adaptation(const cv::Vec3d& from, const cv::Vec3d& to) {
+    const cv::Matx33d bradford(0.8951, 0.2664, -0.1614,
+                               -0.7502, 1.7135, 0.0367,
+                               0.0389, -0.0685, 1.0296);
+    cv::Vec3d source = bradford * from;
+    cv::Vec3d destination = bradford * to;
+    cv::Vec3d scale(destination[0] / source[0], destination[1] / source[1], destination[2] / source[2]);
+    return bradford.inv() * cv::Matx33d::diag(scale) * bradford;
+}
+
+// Linear RGB to D50 XYZ for primaries given as xy chromaticities, adapted
+// from the space's own white point
+static cv::Matx33d primariesToXyz(const double primaries[3][2], const cv::Vec3d& white) {
+    cv::Matx33d matrix;
+    for (int c = 0; c < 3; ++c) {
+        double x = primaries[c][0], y = primaries[c][1];
+        matrix(0, c) = x / y;
+        matrix(1, c) = 1.0;
+        matrix(2, c) = (1.0 - x - y) / y;
+    }
+    // Scale the primaries so that full RGB adds up to the white point
+    cv::Vec3d scale = matrix.inv() * white;
+    for (int r = 0; r < 3; ++r) {
+        for (int c = 0; c < 3; ++c) matrix(r, c) *= scale[c];
+    }
+    return adaptation(white, D50) * matrix;
+}
+
+// A space's tone curve as an ICC parametric curve
+static int spaceCurve(Space space, double params[7]) {
+    std::fill(params, params + 7, 0.0);
+    if (space == Space::ADOBE_RGB) {
+        params[0] = 563.0 / 256.0;
+        return 0;
+    }
+    // The sRGB curve, a power function with a linear toe
+    params[0] = 2.4;
+    params[1] = 1.0 / 1.055;
+    params[2] = 0.055 / 1.055;
+    params[3] = 1.0 / 12.92;
+    params[4] = 0.04045;
+    return 3;
+}
+
+Profile getSpaceProfile(Space space) {
+    static const double srgb[3][2] = {{0.64, 0.33}, {0.30, 0.60}, {0.15, 0.06}};
+    static const double displayP3[3][2] = {{0.680, 0.320}, {0.265, 0.690}, {0.150, 0.060}};
+    static const double adobeRgb[3][2] = {{0.64, 0.33}, {0.21, 0.71}, {0.15, 0.06}};
+
+    Profile profile;
+    profile.description = getSpaceName(space);
+    profile.toXyz = primariesToXyz(space == Space::DISPLAY_P3 ? displayP3 : space == Space::ADOBE_RGB ? adobeRgb : srgb,
+                                   D65);
+    double params[7];
+    int type = spaceCurve(space, params);
+    sampleParametric(type, params, profile.curves[0]);
+    profile.curves[1] = profile.curves[2] = profile.curves[0];
+    return profile;
+}
+
+bool isEquivalent(const Profile& a, const Profile& b) {
+    for (int r = 0; r < 3; ++r) {
+        for (int c = 0; c < 3; ++c) {
+            if (std::abs(a.toXyz(r, c) - b.toXyz(r, c)) > 0.003) return false;
+        }
+    }
+    for (int c = 0; c < 3; ++c) {
+        for (size_t i = 0; i < a.curves[c].size() && i < b.curves[c].size(); ++i) {
+            if (std::abs(a.curves[c][i] - b.curves[c][i]) > 0.002f) return false;
+        }
+    }
+    return true;
+}
+
+// Linear interpolation in a table of evenly spaced samples from 0 to 1
+static float lookup(const std::vector<float>& table, float x) {
+    float position = std::clamp(x, 0.0f, 1.0f) * (table.size() - 1);
+    size_t index = std::min(static_cast<size_t>(position), table.size() - 2);
+    return table[index] + (table[index + 1] - table[index]) * (position - index);
+}
+
+// Linear light to encoded value, by inverting a sampled (rising) tone curve
+static std::vector<float> invertCurve(const std::vector<float>& curve) {
+    std::vector<float> inverse(16384);
+    size_t segment = 0;
+    for (size_t i = 0; i < inverse.size(); ++i) {
+        float linear = i / float(inverse.size() - 1);
+        while (segment + 2 < curve.size() && curve[segment + 1] < linear) ++segment;
+        float low = curve[segment], high = curve[segment + 1];
+        float t = high > low ? std::clamp((linear - low) / (high - low), 0.0f, 1.0f) : 0.0f;
+        inverse[i] = (segment + t) / float(curve.size() - 1);
+    }
+    return inverse;
+}
+
+void convert(cv::Mat& image, const Profile& from, const Profile& to) {
+    if (image.empty()) return;
+    if (image.channels() == 1) cv::cvtColor(image, image, cv::COLOR_GRAY2BGR);
+
+    const bool bytes = image.depth() == CV_8U;
+    cv::Mat work;
+    image.convertTo(work, CV_32F, bytes ? 1.0 / 255.0 : 1.0);
+
+    // Linear source RGB straight to linear destination RGB
+    const cv::Matx33f matrix(to.toXyz.inv() * from.toXyz);
+    const std::vector<float> encode[3] = {invertCurve(to.curves[0]), invertCurve(to.curves[1]),
+                                          invertCurve(to.curves[2])};
+    const int channels = work.channels();
+    cv::parallel_for_(cv::Range(0, work.rows), [&](const cv::Range& rows) {
+        for (int y = rows.start; y < rows.end; ++y) {
+            float* row = work.ptr<float>(y);
+            for (int x = 0; x < work.cols; ++x) {
+                // Pixels are BGR, profiles RGB
+                float* pixel = row + x * channels;
+                cv::Vec3f linear(lookup(from.curves[0], pixel[2]), lookup(from.curves[1], pixel[1]),
+                                 lookup(from.curves[2], pixel[0]));
+                cv::Vec3f converted = matrix * linear;
+                pixel[2] = lookup(encode[0], converted[0]);
+                pixel[1] = lookup(encode[1], converted[1]);
+                pixel[0] = lookup(encode[2], converted[2]);
+            }
+        }
+    });
+    work.convertTo(image, image.depth(), bytes ? 255.0 : 1.0);
+}
+
+static void appendUint32(std::vector<unsigned char>& out, uint32_t value) {
+    for (int shift = 24; shift >= 0; shift -= 8) out.push_back(static_cast<unsigned char>(value >> shift));
+}
+
+static void appendFixed(std::vector<unsigned char>& out, double value) {
+    appendUint32(out, static_cast<uint32_t>(static_cast<int32_t>(std::lround(value * 65536.0))));
+}
+
+static void putUint32(std::vector<unsigned char>& out, size_t offset, uint32_t value) {
+    for (int i = 0; i < 4; ++i) out[offset + i] = static_cast<unsigned char>(value >> (24 - 8 * i));
+}
+
+// Tag type signature and the reserved bytes after it
+static std::vector<unsigned char> tagData(const char* type) {
+    std::vector<unsigned char> data(type, type + 4);
+    data.resize(8, 0);
+    return data;
+}
+
+static std::vector<unsigned char> textTag(const std::string& text) {
+    std::vector<unsigned char> data = tagData("mluc");
+    appendUint32(data, 1);      // One record, of 12 bytes
+    appendUint32(data, 12);
+    data.insert(data.end(), {'e', 'n', 'U', 'S'});
+    appendUint32(data, static_cast<uint32_t>(text.size() * 2));
+    appendUint32(data, 28);
+    for (char c : text) data.insert(data.end(), {0, static_cast<unsigned char>(c)});
+    return data;
+}
+
+static std::vector<unsigned char> xyzTag(const cv::Vec3d& xyz) {
+    std::vector<unsigned char> data = tagData("XYZ ");
+    for (int c = 0; c < 3; ++c) appendFixed(data, xyz[c]);
+    return data;
+}
+
+std::vector<unsigned char> buildProfile(Space space) {
+    Profile profile = getSpaceProfile(space);
+    std::vector<std::pair<std::string, std::vector<unsigned char>>> tags;
+    tags.emplace_back("desc", textTag(profile.description));
+    tags.emplace_back("cprt", textTag("No copyright, use freely"));
+    tags.emplace_back("wtpt", xyzTag(D50));
+
+    std::vector<unsigned char> chad = tagData("sf32");
+    cv::Matx33d adapt = adaptation(D65, D50);
+    for (int r = 0; r < 3; ++r) {
+        for (int c = 0; c < 3; ++c) appendFixed(chad, adapt(r, c));
+    }
+    tags.emplace_back("chad", chad);
+
+    static const char* columns[3] = {"rXYZ", "gXYZ", "bXYZ"};
+    for (int c = 0; c < 3; ++c) {
+        tags.emplace_back(columns[c], xyzTag(cv::Vec3d(profile.toXyz(0, c), profile.toXyz(1, c), profile.toXyz(2, c))));
+    }
+
+    double params[7];
+    int type = spaceCurve(space, params);
+    std::vector<unsigned char> curve = tagData("para");
+    curve.insert(curve.end(), {0, static_cast<unsigned char>(type), 0, 0});
+    static const int paramCounts[5] = {1, 3, 4, 5, 7};
+    for (int i = 0; i < paramCounts[type]; ++i) appendFixed(curve, params[i]);
+    for (const char* signature : {"rTRC", "gTRC", "bTRC"}) tags.emplace_back(signature, curve);
+
+    // Header, tag table, then the tag data, each tag starting on a 4-byte boundary
+    std::vector<unsigned char> table, data;
+    size_t dataStart = 128 + 4 + 12 * tags.size();
+    appendUint32(table, static_cast<uint32_t>(tags.size()));
+    for (const auto& tag : tags) {
+        table.insert(table.end(), tag.first.begin(), tag.first.end());
+        appendUint32(table, static_cast<uint32_t>(dataStart + data.size()));
+        appendUint32(table, static_cast<uint32_t>(tag.second.size()));
+        data.insert(data.end(), tag.second.begin(), tag.second.end());
+        data.resize((data.size() + 3) & ~size_t(3), 0);
+    }
+
+    std::vector<unsigned char> icc(128, 0);
+    icc.insert(icc.end(), table.begin(), table.end());
+    icc.insert(icc.end(), data.begin(), data.end());
+    putUint32(icc, 0, static_cast<uint32_t>(icc.size()));
+    putUint32(icc, 8, 0x04300000);                     // Version 4.3
+    std::memcpy(icc.data() + 12, "mntrRGB XYZ ", 12);  // Display class, RGB data, XYZ connection space
+    static const unsigned char created[12] = {0x07, 0xE8, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0};  // 2024-01-01
+    std::memcpy(icc.data() + 24, created, sizeof(created));
+    std::memcpy(icc.data() + 36, "acsp", 4);
+    std::vector<unsigned char> illuminant;
+    for (int c = 0; c < 3; ++c) appendFixed(illuminant, D50[c]);
+    std::memcpy(icc.data() + 68, illuminant.data(), illuminant.size());
+    return icc;
+}
+
+} // namespace ColorProfile
@@ -0,0 +1,55 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace ColorProfile {
+
+// RGB spaces loaded images can be converted to
+enum class Space {
+    SRGB,
+    DISPLAY_P3,     // Wide gamut of recent phones and Macs, with the sRGB tone curve
+    ADOBE_RGB       // Wide gamut for print work, gamma 2.2
+};
+
+// Lookup by command line id: srgb, display-p3, adobe-rgb
+const std::vector<std::string>& getSpaceIds();
+bool parseSpace(const std::string& id, Space& space);
+std::string getSpaceName(Space space);
+
+// Points each tone curve is sampled at
+constexpr int CURVE_POINTS = 4096;
+
+// A matrix/TRC profile: per-channel tone curves to linear light, then a
+// matrix from linear RGB to D50 XYZ. Gray profiles have the same curve on
+// all three channels and map to the D50 white point
+struct Profile {
+    std::string description;
+    cv::Matx33d toXyz;
+    std::vector<float> curves[3];   // R, G, B: linear light at CURVE_POINTS even steps from 0 to 1
+};
+
+// Parse an ICC profile. Matrix/TRC RGB and gray profiles, which cameras,
+// phones and displays use, are supported; LUT-based ones (CMYK, Lab and some
+// printer profiles) are not
+bool parseProfile(const std::vector<unsigned char>& icc, Profile& profile);
+
+// The profile's description as far as it can be read ("" if none)
+std::string readDescription(const std::vector<unsigned char>& icc);
+
+Profile getSpaceProfile(Space space);
+
+// Whether converting between the profiles would leave colors practically
+// unchanged, e.g. for images tagged with some vendor's sRGB profile
+bool isEquivalent(const Profile& a, const Profile& b);
+
+// Convert BGR or BGRA pixels (8-bit, or float on a 0-1 scale) from one profile
+// to another, relative colorimetric: out of gamut colors are clipped. Gray
+// images come back as BGR
+void convert(cv::Mat& image, const Profile& from, const Profile& to);
+
+// ICC v4 profile describing a space, for tagging saved files
+std::vector<unsigned char> buildProfile(Space space);
+
+} // namespace ColorProfile
@@ -0,0 +1,371 @@
+#include "compare.h"
+#include "dithering.h"
+#include "settings.h"
+#include <algorithm>
+#include <cmath>
+#include <limits>
+#include <unordered_map>
+
+namespace Compare {
+
+// Blur that roughly averages a dither pattern into the tone it represents
+static cv::Mat toneImage(const cv::Mat& gray) {
+    cv::Mat tone;
+    gray.convertTo(tone, CV_32F);
+    cv::GaussianBlur(tone, tone, cv::Size(0, 0), 1.5);
+    return tone;
+}
+
+static double psnr(const cv::Mat& a, const cv::Mat& b) {
+    cv::Mat diff;
+    cv::absdiff(a, b, diff);
+    diff.convertTo(diff, CV_32F);
+    diff = diff.mul(diff);
+    cv::Scalar sums = cv::sum(diff);
+    double mse = (sums[0] + sums[1] + sums[2]) / (static_cast<double>(a.total()) * a.channels());
+    if (mse <= 1e-10) return std::numeric_limits<double>::infinity();
+    return 10.0 * std::log10(255.0 * 255.0 / mse);
+}
+
+// Mean SSIM over 11x11 Gaussian windows (Wang et al. 2004)
+static double ssim(const cv::Mat& a, const cv::Mat& b) {
+    const double c1 = 6.5025, c2 = 58.5225;  // (0.01 * 255)^2, (0.03 * 255)^2
+    cv::Size window(11, 11);
+
+    cv::Mat muA, muB;
+    cv::GaussianBlur(a, muA, window, 1.5);
+    cv::GaussianBlur(b, muB, window, 1.5);
+    cv::Mat muA2 = muA.mul(muA), muB2 = muB.mul(muB), muAB = muA.mul(muB);
+
+    cv::Mat sigmaA2, sigmaB2, sigmaAB;
+    cv::GaussianBlur(a.mul(a), sigmaA2, window, 1.5);
+    cv::GaussianBlur(b.mul(b), sigmaB2, window, 1.5);
+    cv::GaussianBlur(a.mul(b), sigmaAB, window, 1.5);
+    sigmaA2 -= muA2;
+    sigmaB2 -= muB2;
+    sigmaAB -= muAB;
+
+    cv::Mat numerator = (2.0 * muAB + c1).mul(2.0 * sigmaAB + c2);
+    cv::Mat denominator = (muA2 + muB2 + c1).mul(sigmaA2 + sigmaB2 + c2);
+    cv::Mat map;
+    cv::divide(numerator, denominator, map);
+    return cv::mean(map)[0];
+}
+
+// The reference scaled to an image's size
+static cv::Mat scaleReference(const cv::Mat& reference, const cv::Size& size) {
+    cv::Mat scaled;
+    cv::resize(reference, scaled, size, 0, 0, reference.cols > size.width ? cv::INTER_AREA : cv::INTER_NEAREST);
+    return scaled;
+}
+
+// Crops and exports from other tools are often off by a few pixels. Find the
+// shift on the tone images, and only trust it when the correlation is clear
+static cv::Point2d findShift(const cv::Mat& imageTone, const cv::Mat& referenceTone) {
+    cv::Mat window;
+    cv::createHanningWindow(window, imageTone.size(), CV_32F);
+    double response = 0.0;
+    cv::Point2d shift = cv::phaseCorrelate(referenceTone, imageTone, window, &response);
+    if (response < 0.1 || std::abs(shift.x) > imageTone.cols / 4.0 || std::abs(shift.y) > imageTone.rows / 4.0) {
+        return cv::Point2d(0.0, 0.0);
+    }
+    return shift;
+}
+
+cv::Point2d alignReference(const cv::Mat& image, const cv::Mat& reference) {
+    if (image.empty() || reference.empty()) return cv::Point2d(0.0, 0.0);
+
+    cv::Mat imageGray, referenceGray;
+    cv::cvtColor(image, imageGray, cv::COLOR_BGR2GRAY);
+    cv::cvtColor(scaleReference(reference, image.size()), referenceGray, cv::COLOR_BGR2GRAY);
+    return findShift(toneImage(imageGray), toneImage(referenceGray));
+}
+
+bool compareImages(const cv::Mat& result, const cv::Mat& reference, Result& comparison,
+                   const cv::Point2d* offset) {
+    if (result.empty() || reference.empty()) return false;
+
+    cv::Mat scaled = scaleReference(reference, result.size());
+
+    cv::Mat resultGray, referenceGray;
+    cv::cvtColor(result, resultGray, cv::COLOR_BGR2GRAY);
+    cv::cvtColor(scaled, referenceGray, cv::COLOR_BGR2GRAY);
+    cv::Mat resultTone = toneImage(resultGray);
+    cv::Mat referenceTone = toneImage(referenceGray);
+
+    cv::Point2d shift = offset ? *offset : findShift(resultTone, referenceTone);
+    shift = cv::Point2d(std::round(shift.x), std::round(shift.y));
+    comparison.offset = shift;
+
+    if (shift.x != 0.0 || shift.y != 0.0) {
+        cv::Mat translation = (cv::Mat_<double>(2, 3) << 1, 0, shift.x, 0, 1, shift.y);
+        cv::warpAffine(scaled, scaled, translation, scaled.size(), cv::INTER_NEAREST, cv::BORDER_REPLICATE);
+        cv::cvtColor(scaled, referenceGray, cv::COLOR_BGR2GRAY);
+        referenceTone = toneImage(referenceGray);
+    }
+    comparison.aligned = scaled;
+
+    comparison.psnr = psnr(result, scaled);
+    comparison.tonePsnr = psnr(resultTone, referenceTone);
+    comparison.toneSsim = ssim(resultTone, referenceTone);
+
+    cv::Mat lumaDiff;
+    cv::absdiff(resultGray, referenceGray, lumaDiff);
+    comparison.meanDifference = cv::mean(lumaDiff)[0];
+
+    cv::Mat colorDiff, anyDiff;
+    cv::absdiff(result, scaled, colorDiff);
+    cv::cvtColor(colorDiff, anyDiff, cv::COLOR_BGR2GRAY);
+    comparison.matchingPixels = 100.0 * (anyDiff.total() - cv::countNonZero(anyDiff)) / anyDiff.total();
+
+    // Heat map of where the tones differ; exact pattern differences are left out
+    // since they'd light up the whole image
+    cv::Mat toneDiff;
+    cv::absdiff(resultTone, referenceTone, toneDiff);
+    toneDiff.convertTo(toneDiff, CV_8U, 2.0);
+    cv::applyColorMap(toneDiff, comparison.diff, cv::COLORMAP_INFERNO);
+    return true;
+}
+
+bool measureQuality(const cv::Mat& image, const cv::Mat& other, Quality& quality) {
+    if (image.empty() || other.empty()) return false;
+
+    cv::Mat scaled = other;
+    if (other.size() != image.size()) {
+        cv::resize(other, scaled, image.size(), 0, 0,
+                   other.cols > image.cols ? cv::INTER_AREA : cv::INTER_LINEAR);
+    }
+
+    cv::Mat imageTone, otherTone;
+    cv::GaussianBlur(image, imageTone, cv::Size(0, 0), 1.5);
+    cv::GaussianBlur(scaled, otherTone, cv::Size(0, 0), 1.5);
+    quality.psnr = psnr(imageTone, otherTone);
+
+    cv::Mat imageGray, otherGray;
+    cv::cvtColor(imageTone, imageGray, cv::COLOR_BGR2GRAY);
+    cv::cvtColor(otherTone, otherGray, cv::COLOR_BGR2GRAY);
+    imageGray.convertTo(imageGray, CV_32F);
+    otherGray.convertTo(otherGray, CV_32F);
+    quality.ssim = ssim(imageGray, otherGray);
+
+    // CIEDE2000 is costly, so rows are summed in parallel
+    std::vector<double> rowSums(imageTone.rows, 0.0);
+    cv::parallel_for_(cv::Range(0, imageTone.rows), [&](const cv::Range& rows) {
+        for (int y = rows.start; y < rows.end; ++y) {
+            const cv::Vec3b* a = imageTone.ptr<cv::Vec3b>(y);
+            const cv::Vec3b* b = otherTone.ptr<cv::Vec3b>(y);
+            double sum = 0.0;
+            for (int x = 0; x < imageTone.cols; ++x) sum += Dithering::colorDifference(a[x], b[x]);
+            rowSums[y] = sum;
+        }
+    });
+    double total = 0.0;
+    for (double sum : rowSums) total += sum;
+    quality.deltaE = total / imageTone.total();
+    return true;
+}
+
+bool countOutputColors(const cv::Mat& result, const Dithering::Parameters& params, ColorReport& report,
+                       const cv::Mat& mask) {
+    report = ColorReport();
+    if (result.empty() || result.type() != CV_8UC3) return false;
+
+    cv::Mat weights = mask;
+    if (!weights.empty() && weights.channels() == 3) cv::cvtColor(weights, weights, cv::COLOR_BGR2GRAY);
+    if (!weights.empty() && weights.size() != result.size()) {
+        cv::resize(weights, weights, result.size(), 0, 0, cv::INTER_NEAREST);
+    }
+
+    std::unordered_map<uint32_t, int> counts;
+    for (int y = 0; y < result.rows; ++y) {
+        const cv::Vec3b* row = result.ptr<cv::Vec3b>(y);
+        const uchar* weight = weights.empty() ? nullptr : weights.ptr<uchar>(y);
+        for (int x = 0; x < result.cols; ++x) {
+            if (weight && weight[x] < 255) continue;
+            counts[row[x][0] | row[x][1] << 8 | row[x][2] << 16]++;
+        }
+    }
+
+    std::vector<cv::Vec3b> palette;
+    bool fixedPalette = params.colorMode == Dithering::ColorMode::RGB && !Dithering::hasChannelOverrides(params);
+    if (fixedPalette && params.paletteMode == Dithering::PaletteMode::ADAPTIVE) {
+        report.limit = params.paletteSize;
+    } else if (fixedPalette) {
+        palette = Dithering::getPalette(params);
+        report.limit = static_cast<int>(palette.size());
+    }
+
+    for (const auto& count : counts) {
+        ColorCount color;
+        color.color = cv::Vec3b(count.first & 0xFF, (count.first >> 8) & 0xFF, (count.first >> 16) & 0xFF);
+        color.pixels = count.second;
+        color.inPalette = palette.empty() || std::find(palette.begin(), palette.end(), color.color) != palette.end();
+        if (!color.inPalette) report.outsidePalette++;
+        report.colors.push_back(color);
+    }
+    std::sort(report.colors.begin(), report.colors.end(),
+              [](const ColorCount& a, const ColorCount& b) { return a.pixels > b.pixels; });
+    return true;
+}
+
+bool compareCrops(const cv::Mat& original, const cv::Mat& result, cv::Rect rect, int zoom,
+                  cv::Mat& originalCrop, cv::Mat& resultCrop) {
+    if (original.empty() || result.empty()) return false;
+    rect &= cv::Rect(0, 0, result.cols, result.rows);
+    if (rect.empty()) return false;
+    zoom = std::clamp(zoom, 1, MAX_CROP_ZOOM);
+
+    // The same region of the original, rounded outwards when the sizes differ
+    double scaleX = static_cast<double>(original.cols) / result.cols;
+    double scaleY = static_cast<double>(original.rows) / result.rows;
+    int left = static_cast<int>(std::floor(rect.x * scaleX));
+    int top = static_cast<int>(std::floor(rect.y * scaleY));
+    int right = std::min(original.cols, static_cast<int>(std::ceil(rect.br().x * scaleX)));
+    int bottom = std::min(original.rows, static_cast<int>(std::ceil(rect.br().y * scaleY)));
+    cv::Rect source(left, top, std::max(1, right - left), std::max(1, bottom - top));
+    source &= cv::Rect(0, 0, original.cols, original.rows);
+
+    cv::Size size(rect.width * zoom, rect.height * zoom);
+    cv::resize(result(rect), resultCrop, size, 0, 0, cv::INTER_NEAREST);
+    if (source.size() == rect.size()) {
+        cv::resize(original(source), originalCrop, size, 0, 0, cv::INTER_NEAREST);
+    } else {
+        cv::resize(original(source), originalCrop, size, 0, 0, cv::INTER_AREA);
+    }
+    return true;
+}
+
+
+const std::vector<std::string>& getMontageLayoutIds() {
+    static const std::vector<std::string> ids = {"row", "column", "grid"};
+    return ids;
+}
+
+bool parseMontageLayout(const std::string& id, MontageLayout& layout) {
+    const auto& ids = getMontageLayoutIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    layout = static_cast<MontageLayout>(match - ids.begin());
+    return true;
+}
+
+// Join caption parts into lines no wider than width, breaking between parts
+static std::vector<std::string> wrapCaption(const std::vector<std::string>& parts, int width, double fontScale) {
+    std::vector<std::string> lines;
+    std::string line;
+    for (const auto& part : parts) {
+        std::string joined = line.empty() ? part : line + ", " + part;
+        int baseline = 0;
+        if (!line.empty() && cv::getTextSize(joined, cv::FONT_HERSHEY_SIMPLEX, fontScale, 1, &baseline).width > width) {
+            lines.push_back(line + ",");
+            line = part;
+        } else {
+            line = joined;
+        }
+    }
+    if (!line.empty()) lines.push_back(line);
+    return lines;
+}
+
+// A caption line, shrunk if a single part is still too wide
+static void drawCaptionLine(cv::Mat& sheet, const std::string& text, cv::Point origin, int width, double fontScale,
+                            const cv::Scalar& color) {
+    int baseline = 0;
+    cv::Size size = cv::getTextSize(text, cv::FONT_HERSHEY_SIMPLEX, fontScale, 1, &baseline);
+    if (size.width > width) fontScale *= static_cast<double>(width) / size.width;
+    cv::putText(sheet, text, origin, cv::FONT_HERSHEY_SIMPLEX, fontScale, color, 1, cv::LINE_AA);
+}
+
+cv::Mat renderMontage(const cv::Mat& image, const std::vector<Variant>& variants, const MontageOptions& options,
+                      const Dithering::ProgressCallback& progress) {
+    if (image.empty() || variants.empty()) return cv::Mat();
+
+    cv::Mat scaled = image;
+    if (options.panelSize > 0) {
+        double scale = static_cast<double>(options.panelSize) / std::max(image.cols, image.rows);
+        if (scale < 1.0) cv::resize(image, scaled, cv::Size(), scale, scale, cv::INTER_AREA);
+    }
+
+    // Settings shared by every variant would only repeat, so captions list
+    // the ones that vary. The algorithm is named separately
+    std::vector<std::string> varying;
+    for (const auto& variant : variants) {
+        for (const auto& name : Settings::getChangedFields(variants[0].params, variant.params)) {
+            if (name == "algorithm") continue;
+            if (std::find(varying.begin(), varying.end(), name) == varying.end()) varying.push_back(name);
+        }
+    }
+
+    struct Panel {
+        cv::Mat image;
+        std::string title;
+        std::vector<std::string> details;
+    };
+    std::vector<Panel> panels;
+    if (options.includeOriginal) panels.push_back({scaled, "Original", {}});
+    for (size_t i = 0; i < variants.size(); ++i) {
+        const Variant& variant = variants[i];
+        std::string algorithm = Dithering::getAlgorithmName(variant.params.algorithm);
+        Panel panel{cv::Mat(), variant.label.empty() ? algorithm : variant.label, {}};
+        if (progress && !progress(static_cast<float>(i) / variants.size(), "Dithering " + panel.title)) {
+            return cv::Mat();
+        }
+
+        panel.image = Dithering::ditherImage(scaled, variant.params);
+        if (panel.image.empty()) return cv::Mat();
+        if (!variant.label.empty()) panel.details.push_back(algorithm);
+        for (const auto& name : varying) {
+            panel.details.push_back(name + " " + Settings::describeField(variant.params, name));
+        }
+        panels.push_back(panel);
+    }
+
+    int count = static_cast<int>(panels.size());
+    int columns = count;
+    if (options.layout == MontageLayout::COLUMN) columns = 1;
+    if (options.layout == MontageLayout::GRID) {
+        columns = static_cast<int>(std::ceil(std::sqrt(static_cast<double>(count))));
+    }
+    int rows = (count + columns - 1) / columns;
+
+    int cellWidth = 0, cellHeight = 0;
+    for (const auto& panel : panels) {
+        cellWidth = std::max(cellWidth, panel.image.cols);
+        cellHeight = std::max(cellHeight, panel.image.rows);
+    }
+
+    // Text grows with the panels so it stays readable on full-size montages
+    const int spacing = 12;
+    double fontScale = std::max(0.45, cellWidth / 1100.0);
+    int baseline = 0;
+    cv::Size letter = cv::getTextSize("Ag", cv::FONT_HERSHEY_SIMPLEX, fontScale, 1, &baseline);
+    int lineHeight = letter.height + baseline + 6;
+
+    std::vector<std::vector<std::string>> captions;
+    size_t maxLines = 0;
+    for (const auto& panel : panels) {
+        captions.push_back(wrapCaption(panel.details, cellWidth, fontScale));
+        maxLines = std::max(maxLines, captions.back().size());
+    }
+    int captionHeight = static_cast<int>(1 + maxLines) * lineHeight + spacing / 2;
+
+    cv::Mat sheet(spacing + rows * (cellHeight + captionHeight + spacing),
+                  spacing + columns * (cellWidth + spacing), CV_8UC3, cv::Scalar(32, 32, 32));
+    for (int i = 0; i < count; ++i) {
+        const cv::Mat& panelImage = panels[i].image;
+        int x = spacing + (i % columns) * (cellWidth + spacing);
+        int y = spacing + (i / columns) * (cellHeight + captionHeight + spacing);
+        panelImage.copyTo(sheet(cv::Rect(x + (cellWidth - panelImage.cols) / 2, y, panelImage.cols, panelImage.rows)));
+
+        int lineY = y + cellHeight + spacing / 2 + letter.height + 2;
+        drawCaptionLine(sheet, panels[i].title, cv::Point(x, lineY), cellWidth, fontScale, cv::Scalar(240, 240, 240));
+        for (const auto& line : captions[i]) {
+            lineY += lineHeight;
+            drawCaptionLine(sheet, line, cv::Point(x, lineY), cellWidth, fontScale, cv::Scalar(170, 170, 170));
+        }
+    }
+    if (progress) progress(1.0f, "Done");
+    return sheet;
+}
+
+} // namespace Compare
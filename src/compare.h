@@ -0,0 +1,112 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Compare {
+
+// Difference between a dithered result and an external reference image
+struct Result {
+    cv::Point2d offset;         // Shift applied to the reference to line it up (pixels)
+    double psnr = 0.0;          // Pixel-exact PSNR in dB (infinite when identical)
+    double tonePsnr = 0.0;      // PSNR after blurring both, i.e. as seen from a distance
+    double toneSsim = 0.0;      // Structural similarity of the blurred images (1 = identical)
+    double meanDifference = 0.0;    // Average absolute luma difference (0-255)
+    double matchingPixels = 0.0;    // % of pixels with exactly the same color
+    cv::Mat aligned;            // Reference scaled and shifted onto the result (BGR)
+    cv::Mat diff;               // Heat map of the tone difference (BGR)
+};
+
+// Scale the reference to the result's size, align it and measure the difference.
+// Dither patterns rarely match pixel for pixel, so the tone metrics compare
+// blurred copies and are the better guide when reproducing a look. An offset
+// from alignReference (in result pixels) skips aligning again
+bool compareImages(const cv::Mat& result, const cv::Mat& reference, Result& comparison,
+                   const cv::Point2d* offset = nullptr);
+
+// Shift that lines the reference up with an image, in that image's pixels;
+// (0, 0) unless the match is clear. Tones are compared, so the image can be
+// the undithered source and the shift reused for every result of it
+cv::Point2d alignReference(const cv::Mat& image, const cv::Mat& reference);
+
+// Objective quality of a result against the original, or of two results
+// against each other. Measured on blurred copies, as seen from a distance:
+// a dither is judged by the tones it gives, not by matching pixel for pixel
+struct Quality {
+    double psnr = 0.0;          // dB, higher is closer (infinite when identical)
+    double ssim = 0.0;          // Structural similarity of the luma (1 = identical)
+    double deltaE = 0.0;        // Mean CIEDE2000 color difference (under 1 is invisible)
+};
+
+// The second image is scaled to the first's size if they differ
+bool measureQuality(const cv::Mat& image, const cv::Mat& other, Quality& quality);
+
+// Colors a result actually uses, checked against the palette its settings ask
+// for, for hard limits such as a console's or display's palette
+struct ColorCount {
+    cv::Vec3b color;            // BGR
+    int pixels = 0;
+    bool inPalette = true;      // False if the palette doesn't have it
+};
+
+struct ColorReport {
+    std::vector<ColorCount> colors;     // Most used first
+    int limit = 0;              // Colors the settings allow (0 = no fixed palette, e.g. CMYK)
+    int outsidePalette = 0;     // Colors not in the palette, from blending, blur stages or a bug
+
+    bool exceedsPalette() const {
+        return outsidePalette > 0 || (limit > 0 && static_cast<int>(colors.size()) > limit);
+    }
+};
+
+// Count the exact colors of an 8-bit BGR result. Palette output is checked
+// color by color, adaptive palettes by count only; other color modes and
+// channel overrides have no fixed palette and are only counted. With a dither
+// mask, only the pixels it fully dithers are counted
+bool countOutputColors(const cv::Mat& result, const Dithering::Parameters& params, ColorReport& report,
+                       const cv::Mat& mask = cv::Mat());
+
+constexpr int MAX_CROP_ZOOM = 16;
+
+// Matching crops of the original and the result for a magnifier or split
+// slider. rect is in result pixels and is clipped to the image; the original
+// is cropped at the same place, scaled if it has another size. Both crops are
+// enlarged zoom times (1-16) with nearest-neighbour so the dither pattern
+// stays pixel-exact. False if the rect misses the image
+bool compareCrops(const cv::Mat& original, const cv::Mat& result, cv::Rect rect, int zoom,
+                  cv::Mat& originalCrop, cv::Mat& resultCrop);
+
+// One set of settings in a montage
+struct Variant {
+    Dithering::Parameters params;
+    std::string label;          // Caption title; empty = the algorithm's name
+};
+
+// How the panels of a montage are arranged
+enum class MontageLayout {
+    ROW,            // Side by side
+    COLUMN,         // One above the other
+    GRID            // Rows of about the square root of the count
+};
+
+// Lookup by command line id (row, column, grid)
+const std::vector<std::string>& getMontageLayoutIds();
+bool parseMontageLayout(const std::string& id, MontageLayout& layout);
+
+struct MontageOptions {
+    MontageLayout layout = MontageLayout::ROW;
+    int panelSize = 512;            // Longest side each variant is dithered at (0 = the image's own size)
+    bool includeOriginal = false;   // The undithered image as the first panel
+};
+
+// Dither the image with each variant and lay the results out on one labeled
+// sheet for sharing. The image is scaled down before dithering, so every
+// panel shows the pattern at its real pixels. Each caption has the variant's
+// title, then the settings that differ between the variants and their values
+cv::Mat renderMontage(const cv::Mat& image, const std::vector<Variant>& variants, const MontageOptions& options,
+                      const Dithering::ProgressCallback& progress = nullptr);
+
+} // namespace Compare
@@ -0,0 +1,215 @@
+#include "descreen.h"
+#include <algorithm>
+#include <cmath>
+
+namespace Descreen {
+
+// Periods are looked for in this range; anything coarser is part of the picture
+static const float MIN_PERIOD = 2.0f;
+static const float MAX_PERIOD = 64.0f;
+
+const std::vector<std::string>& getMethodIds() {
+    static const std::vector<std::string> ids = {"gaussian", "sinc", "fft"};
+    return ids;
+}
+
+bool parseMethod(const std::string& id, Method& method) {
+    const auto& ids = getMethodIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    method = static_cast<Method>(match - ids.begin());
+    return true;
+}
+
+std::string getMethodName(Method method) {
+    switch (method) {
+        case Method::GAUSSIAN: return "Gaussian";
+        case Method::SINC: return "Windowed Sinc";
+        case Method::FREQUENCY: return "Frequency (FFT)";
+        default: return "Unknown";
+    }
+}
+
+// Luma as 32-bit float, whatever the depth and channel count
+static cv::Mat luma(const cv::Mat& image) {
+    cv::Mat gray;
+    if (image.channels() == 3) {
+        cv::cvtColor(image, gray, cv::COLOR_BGR2GRAY);
+    } else if (image.channels() == 4) {
+        cv::cvtColor(image, gray, cv::COLOR_BGRA2GRAY);
+    } else {
+        gray = image;
+    }
+    gray.convertTo(gray, CV_32F);
+    return gray;
+}
+
+// Cycles per pixel of a DFT row or column index (DC at 0, not shifted)
+static float frequency(int index, int size) {
+    return static_cast<float>(index <= size / 2 ? index : index - size) / size;
+}
+
+static cv::Mat magnitudeSpectrum(const cv::Mat& plane) {
+    cv::Mat spectrum, parts[2], magnitude;
+    cv::dft(plane, spectrum, cv::DFT_COMPLEX_OUTPUT);
+    cv::split(spectrum, parts);
+    cv::magnitude(parts[0], parts[1], magnitude);
+    return magnitude;
+}
+
+float estimatePeriod(const cv::Mat& image) {
+    if (image.empty()) return MIN_PERIOD;
+
+    // A centered square is enough to find a screen and keeps the FFT small
+    cv::Mat gray = luma(image);
+    int size = std::min({gray.cols, gray.rows, 512});
+    if (size < 16) return MIN_PERIOD;
+    cv::Mat crop = gray(cv::Rect((gray.cols - size) / 2, (gray.rows - size) / 2, size, size)).clone();
+    crop -= cv::mean(crop)[0];
+    cv::Mat window;
+    cv::createHanningWindow(window, crop.size(), CV_32F);
+    cv::Mat magnitude = magnitudeSpectrum(crop.mul(window));
+
+    // Photos have most of their energy at low frequencies, falling off about
+    // as 1/f, so weighting by frequency leaves the pattern's peak on top
+    float best = 0.0f, bestFrequency = 1.0f / MIN_PERIOD;
+    for (int y = 0; y < size; ++y) {
+        float fy = frequency(y, size);
+        const float* row = magnitude.ptr<float>(y);
+        for (int x = 0; x < size; ++x) {
+            float f = std::hypot(frequency(x, size), fy);
+            if (f < 1.0f / MAX_PERIOD || row[x] * f <= best) continue;
+            best = row[x] * f;
+            bestFrequency = f;
+        }
+    }
+    return std::clamp(1.0f / bestFrequency, MIN_PERIOD, MAX_PERIOD);
+}
+
+// At sigma = 0.4 periods the pattern's frequency keeps about 4% of its contrast
+static cv::Mat gaussian(const cv::Mat& image, float period) {
+    cv::Mat result;
+    cv::GaussianBlur(image, result, cv::Size(0, 0), std::max(0.8, 0.4 * period), 0, cv::BORDER_REFLECT);
+    return result;
+}
+
+// Separable sinc low-pass a little below the pattern's frequency, with a
+// three-lobe Lanczos window
+static cv::Mat windowedSinc(const cv::Mat& image, float period) {
+    const int lobes = 3;
+    double cutoff = 0.7 / period;
+    int radius = static_cast<int>(std::ceil(lobes / (2.0 * cutoff)));
+    auto sinc = [](double x) { return x == 0.0 ? 1.0 : std::sin(CV_PI * x) / (CV_PI * x); };
+
+    cv::Mat kernel(2 * radius + 1, 1, CV_32F);
+    double sum = 0.0;
+    for (int i = -radius; i <= radius; ++i) {
+        double x = 2.0 * cutoff * i;
+        double weight = sinc(x) * sinc(x / lobes);
+        kernel.at<float>(i + radius) = static_cast<float>(weight);
+        sum += weight;
+    }
+    kernel /= sum;
+
+    cv::Mat result;
+    cv::sepFilter2D(image, result, -1, kernel, kernel, cv::Point(-1, -1), 0, cv::BORDER_REFLECT);
+    return result;
+}
+
+// Sharp spikes in the spectrum (the screen, its harmonics and moire) are cut
+// out with small notches, then a Butterworth low-pass removes what's left of
+// the pattern. Keeps more detail than blurring at the same strength
+static cv::Mat frequencyFilter(const cv::Mat& image, float period) {
+    // Reflected padding to a fast DFT size, so opposite edges don't bleed into each other
+    int margin = static_cast<int>(std::ceil(2.0f * period));
+    int rows = cv::getOptimalDFTSize(image.rows + 2 * margin);
+    int cols = cv::getOptimalDFTSize(image.cols + 2 * margin);
+    cv::Mat padded;
+    cv::copyMakeBorder(image, padded, margin, rows - image.rows - margin, margin, cols - image.cols - margin,
+                       cv::BORDER_REFLECT);
+
+    float cutoff = 0.8f / period;
+    cv::Mat mask(rows, cols, CV_32F);
+    for (int y = 0; y < rows; ++y) {
+        float fy = frequency(y, rows);
+        float* row = mask.ptr<float>(y);
+        for (int x = 0; x < cols; ++x) {
+            float f = std::hypot(frequency(x, cols), fy) / cutoff;
+            row[x] = 1.0f / (1.0f + std::pow(f, 8.0f));
+        }
+    }
+
+    // Peaks are local maxima well above the average away from the center
+    cv::Mat magnitude = magnitudeSpectrum(luma(padded));
+    cv::Mat maxima;
+    cv::dilate(magnitude, maxima, cv::Mat::ones(5, 5, CV_8U));
+    double level = 0.0;
+    int count = 0;
+    std::vector<std::pair<float, cv::Point>> peaks;
+    for (int pass = 0; pass < 2; ++pass) {
+        for (int y = 0; y < rows; ++y) {
+            float fy = frequency(y, rows);
+            const float* row = magnitude.ptr<float>(y);
+            const float* rowMax = maxima.ptr<float>(y);
+            for (int x = 0; x < cols; ++x) {
+                if (std::hypot(frequency(x, cols), fy) < 0.5f * cutoff) continue;
+                if (pass == 0) {
+                    level += row[x];
+                    count++;
+                } else if (row[x] == rowMax[x] && row[x] > 8.0 * level) {
+                    peaks.emplace_back(row[x], cv::Point(x, y));
+                }
+            }
+        }
+        if (pass == 0) level = count > 0 ? level / count : 0.0;
+    }
+    std::sort(peaks.begin(), peaks.end(), [](const auto& a, const auto& b) { return a.first > b.first; });
+    if (peaks.size() > 64) peaks.resize(64);
+
+    float sigma = std::max(1.5f, 0.003f * std::min(rows, cols));
+    int reach = static_cast<int>(std::ceil(3.0f * sigma));
+    for (const auto& peak : peaks) {
+        for (int dy = -reach; dy <= reach; ++dy) {
+            float* row = mask.ptr<float>(((peak.second.y + dy) % rows + rows) % rows);
+            for (int dx = -reach; dx <= reach; ++dx) {
+                row[((peak.second.x + dx) % cols + cols) % cols] *=
+                    1.0f - std::exp(-(dx * dx + dy * dy) / (2.0f * sigma * sigma));
+            }
+        }
+    }
+
+    std::vector<cv::Mat> planes;
+    cv::split(padded, planes);
+    for (auto& plane : planes) {
+        cv::Mat spectrum, parts[2];
+        cv::dft(plane, spectrum, cv::DFT_COMPLEX_OUTPUT);
+        cv::split(spectrum, parts);
+        parts[0] = parts[0].mul(mask);
+        parts[1] = parts[1].mul(mask);
+        cv::merge(parts, 2, spectrum);
+        cv::idft(spectrum, plane, cv::DFT_SCALE | cv::DFT_REAL_OUTPUT);
+    }
+    cv::Mat result;
+    cv::merge(planes, result);
+    return result(cv::Rect(margin, margin, image.cols, image.rows)).clone();
+}
+
+cv::Mat reconstruct(const cv::Mat& image, const Options& options, float* period) {
+    if (image.empty()) return cv::Mat();
+
+    float used = options.period > 0.0f ? std::clamp(options.period, 1.0f, MAX_PERIOD) : estimatePeriod(image);
+    if (period) *period = used;
+
+    cv::Mat work, filtered, result;
+    image.convertTo(work, CV_32F);
+    switch (options.method) {
+        case Method::GAUSSIAN: filtered = gaussian(work, used); break;
+        case Method::SINC: filtered = windowedSinc(work, used); break;
+        case Method::FREQUENCY:
+        default: filtered = frequencyFilter(work, used); break;
+    }
+    filtered.convertTo(result, image.depth());
+    return result;
+}
+
+} // namespace Descreen
@@ -0,0 +1,37 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace Descreen {
+
+// How the dots are smoothed away
+enum class Method {
+    GAUSSIAN,           // Gaussian blur: softest, never rings
+    SINC,               // Lanczos-windowed sinc low-pass: keeps more detail, may ring slightly at edges
+    FREQUENCY           // Notches the screen's peaks out of the spectrum, then a gentle low-pass (FFT)
+};
+
+// Method lookup by command line id (gaussian, sinc, fft)
+const std::vector<std::string>& getMethodIds();
+bool parseMethod(const std::string& id, Method& method);
+std::string getMethodName(Method method);
+
+struct Options {
+    Method method = Method::FREQUENCY;
+    float period = 0.0f;        // Pixels per repeat of the screen or dither pattern (0 = detect)
+};
+
+// Period of the strongest regular pattern in an image, in pixels: the screen
+// of a scanned print or the matrix of an ordered dither. Error diffusion and
+// noise have no single period and come out at about 2-3 pixels
+float estimatePeriod(const cv::Mat& image);
+
+// Approximate the continuous-tone image behind a dithered or halftoned one,
+// to preview the tones it gives from a distance or clean up a scanned print
+// before dithering it again. Works on any depth and channel count and returns
+// the same type; period, if given, receives the period used
+cv::Mat reconstruct(const cv::Mat& image, const Options& options, float* period = nullptr);
+
+} // namespace Descreen
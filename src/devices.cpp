@@ -0,0 +1,73 @@
+#include "devices.h"
+#include "transform.h"
+#include <algorithm>
+#include <cmath>
+
+namespace Devices {
+
+using Dithering::Algorithm;
+using Dithering::PaletteMode;
+
+const std::vector<DeviceInfo>& getDeviceLibrary() {
+    static const std::vector<DeviceInfo> library = {
+        {"gameboy", "Game Boy", "160x144 in four shades of green, Bayer 4x4",
+         cv::Size(160, 144), 1.0f, Algorithm::ORDERED_BAYER_4X4, PaletteMode::GAMEBOY, {}},
+        {"macintosh", "Macintosh (1984)", "512x342 black and white, Atkinson as in MacPaint",
+         cv::Size(512, 342), 1.0f, Algorithm::ATKINSON, PaletteMode::MONOCHROME, {}},
+        {"eink", "E-ink Reader", "600x800 in 16 grays, Floyd-Steinberg",
+         cv::Size(600, 800), 1.0f, Algorithm::FLOYD_STEINBERG, PaletteMode::GRAYSCALE_16, {}},
+        // Artifact colors of an early CGA card on an NTSC monitor; they varied
+        // with the card and the monitor's tint, so these are approximate. 160
+        // columns and 200 lines fill a 4:3 picture, so pixels are 5:3 wide
+        {"cga-composite", "CGA Composite", "160x200 wide pixels in composite artifact colors, Bayer 4x4",
+         cv::Size(160, 200), 5.0f / 3.0f, Algorithm::ORDERED_BAYER_4X4, PaletteMode::CUSTOM,
+         {cv::Vec3b(0, 0, 0), cv::Vec3b(49, 110, 0), cv::Vec3b(255, 9, 49), cv::Vec3b(255, 138, 0),
+          cv::Vec3b(49, 0, 167), cv::Vec3b(118, 118, 118), cv::Vec3b(255, 17, 236), cv::Vec3b(255, 146, 187),
+          cv::Vec3b(0, 90, 49), cv::Vec3b(0, 219, 0), cv::Vec3b(187, 247, 69), cv::Vec3b(0, 99, 236),
+          cv::Vec3b(0, 228, 187), cv::Vec3b(187, 127, 255), cv::Vec3b(255, 255, 255)}},
+    };
+    return library;
+}
+
+const DeviceInfo* findDevice(const std::string& id) {
+    for (const auto& device : getDeviceLibrary()) {
+        if (device.id == id) return &device;
+    }
+    return nullptr;
+}
+
+void applyDevice(const DeviceInfo& device, Dithering::Parameters& params) {
+    params = Dithering::getRecommendedParameters(params, device.algorithm);
+    params.paletteMode = device.paletteMode;
+    if (device.paletteMode == PaletteMode::CUSTOM) params.customPalette = device.palette;
+    if (device.paletteMode == PaletteMode::MONOCHROME) {
+        params.inkColor = cv::Vec3b(0, 0, 0);
+        params.paperColor = cv::Vec3b(255, 255, 255);
+    }
+    int bayerSize = Dithering::getBayerSize(device.algorithm);
+    if (bayerSize > 0) {
+        params.bayerSize = bayerSize;
+        params.bayerClustered = false;
+    }
+    params.colorMode = Dithering::ColorMode::RGB;
+    for (auto& channel : params.channelOverrides) channel.enabled = false;
+    params.pixelate = 1;
+}
+
+cv::Mat fitToScreen(const cv::Mat& image, const DeviceInfo& device) {
+    if (image.empty()) return image;
+
+    double screenAspect = device.resolution.width * static_cast<double>(device.pixelAspect) /
+                          device.resolution.height;
+    cv::Rect rect(0, 0, image.cols, image.rows);
+    if (static_cast<double>(image.cols) / image.rows > screenAspect) {
+        rect.width = std::max(1, static_cast<int>(std::lround(image.rows * screenAspect)));
+        rect.x = (image.cols - rect.width) / 2;
+    } else {
+        rect.height = std::max(1, static_cast<int>(std::lround(image.cols / screenAspect)));
+        rect.y = (image.rows - rect.height) / 2;
+    }
+    return Transform::resizeImage(Transform::cropImage(image, rect), device.resolution, Transform::Filter::AREA);
+}
+
+} // namespace Devices
@@ -0,0 +1,40 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Devices {
+
+// A retro screen to emulate: its resolution and pixel shape, plus the palette
+// and algorithm that give its look
+struct DeviceInfo {
+    std::string id;                 // Name used on the command line and in scripts
+    std::string name;               // Display name
+    std::string description;        // One-line summary
+    cv::Size resolution;            // Screen size in pixels
+    float pixelAspect;              // Pixel width over height as the screen shows it
+    Dithering::Algorithm algorithm;
+    Dithering::PaletteMode paletteMode;
+    std::vector<cv::Vec3b> palette; // Colors for PaletteMode::CUSTOM (BGR)
+};
+
+// Every device, in display order
+const std::vector<DeviceInfo>& getDeviceLibrary();
+
+// Look up a device by id (nullptr if unknown)
+const DeviceInfo* findDevice(const std::string& id);
+
+// Switch params to the device's algorithm and palette. Settings that don't
+// suit the algorithm are reset as when picking it by hand, and color modes,
+// channel overrides and pixelation are turned off so the palette is used as is
+void applyDevice(const DeviceInfo& device, Dithering::Parameters& params);
+
+// Crop the image to the shape of the device's screen as it looks (wider than
+// its resolution when pixels are wide), keeping the middle, then scale it to
+// the screen's resolution
+cv::Mat fitToScreen(const cv::Mat& image, const DeviceInfo& device);
+
+} // namespace Devices
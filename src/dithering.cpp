@@ -1,17 +1,380 @@
 #include "dithering.h"
+#include "gpu.h"
+#include "matrices.h"
+#include "plugins.h"
+#include "stipple.h"
+#include <opencv2/core/hal/intrin.hpp>
 #include <algorithm>
+#include <atomic>
+#include <cctype>
+#include <chrono>
 #include <cmath>
+#include <cstdio>
+#include <filesystem>
+#include <future>
+#include <iostream>
+#include <limits>
+#include <list>
+#include <map>
+#include <mutex>
 #include <random>
+#include <set>
+#include <thread>
+#include <unordered_map>
 
 namespace Dithering {
 
-// Helper function to apply preprocessing (gamma, contrast, brightness, saturation)
-cv::Mat preprocessImage(const cv::Mat& input, const Parameters& params) {
-    cv::Mat processed = input.clone();
+// Rec. 601 luma of a BGR color
+static float luma(const cv::Vec3b& color) {
+    return 0.114f * color[0] + 0.587f * color[1] + 0.299f * color[2];
+}
+
+// sRGB-encoded channel (0-255) to linear light (0-1)
+static float srgbToLinear(float value) {
+    value /= 255.0f;
+    return value <= 0.04045f ? value / 12.92f : std::pow((value + 0.055f) / 1.055f, 2.4f);
+}
+
+// BGR (sRGB, D65, or already linear light) to CIELAB
+static cv::Vec3f bgrToLab(const cv::Vec3b& color, bool linear = false) {
+    float r = linear ? color[2] / 255.0f : srgbToLinear(color[2]);
+    float g = linear ? color[1] / 255.0f : srgbToLinear(color[1]);
+    float b = linear ? color[0] / 255.0f : srgbToLinear(color[0]);
+
+    float x = (0.4124f * r + 0.3576f * g + 0.1805f * b) / 0.95047f;
+    float y = 0.2126f * r + 0.7152f * g + 0.0722f * b;
+    float z = (0.0193f * r + 0.1192f * g + 0.9505f * b) / 1.08883f;
+
+    auto f = [](float t) { return t > 0.008856f ? std::cbrt(t) : 7.787f * t + 16.0f / 116.0f; };
+    float fx = f(x), fy = f(y), fz = f(z);
+    return cv::Vec3f(116.0f * fy - 16.0f, 500.0f * (fx - fy), 200.0f * (fy - fz));
+}
+
+// CIEDE2000 color difference (Sharma, Wu and Dalal formulation)
+static float ciede2000(const cv::Vec3f& lab1, const cv::Vec3f& lab2) {
+    const double deg = CV_PI / 180.0;
+    const double pow25_7 = 6103515625.0;  // 25^7
+
+    double l1 = lab1[0], a1 = lab1[1], b1 = lab1[2];
+    double l2 = lab2[0], a2 = lab2[1], b2 = lab2[2];
+
+    double cBar = (std::hypot(a1, b1) + std::hypot(a2, b2)) / 2.0;
+    double cBar7 = std::pow(cBar, 7.0);
+    double g = 0.5 * (1.0 - std::sqrt(cBar7 / (cBar7 + pow25_7)));
+
+    double a1p = (1.0 + g) * a1;
+    double a2p = (1.0 + g) * a2;
+    double c1p = std::hypot(a1p, b1);
+    double c2p = std::hypot(a2p, b2);
+
+    auto hue = [deg](double b, double a) {
+        if (a == 0.0 && b == 0.0) return 0.0;
+        double h = std::atan2(b, a) / deg;
+        return h < 0.0 ? h + 360.0 : h;
+    };
+    double h1p = hue(b1, a1p);
+    double h2p = hue(b2, a2p);
+
+    double deltaLp = l2 - l1;
+    double deltaCp = c2p - c1p;
+    double deltahp = 0.0;
+    if (c1p * c2p != 0.0) {
+        deltahp = h2p - h1p;
+        if (deltahp > 180.0) deltahp -= 360.0;
+        else if (deltahp < -180.0) deltahp += 360.0;
+    }
+    double deltaHp = 2.0 * std::sqrt(c1p * c2p) * std::sin(deltahp * deg / 2.0);
+
+    double lBarp = (l1 + l2) / 2.0;
+    double cBarp = (c1p + c2p) / 2.0;
+    double hBarp = h1p + h2p;
+    if (c1p * c2p != 0.0) {
+        if (std::fabs(h1p - h2p) > 180.0) hBarp += (hBarp < 360.0) ? 360.0 : -360.0;
+        hBarp /= 2.0;
+    }
+
+    double t = 1.0 - 0.17 * std::cos((hBarp - 30.0) * deg) + 0.24 * std::cos(2.0 * hBarp * deg) +
+               0.32 * std::cos((3.0 * hBarp + 6.0) * deg) - 0.20 * std::cos((4.0 * hBarp - 63.0) * deg);
+    double deltaTheta = 30.0 * std::exp(-std::pow((hBarp - 275.0) / 25.0, 2.0));
+    double cBarp7 = std::pow(cBarp, 7.0);
+    double rc = 2.0 * std::sqrt(cBarp7 / (cBarp7 + pow25_7));
+    double lTerm = (lBarp - 50.0) * (lBarp - 50.0);
+    double sl = 1.0 + 0.015 * lTerm / std::sqrt(20.0 + lTerm);
+    double sc = 1.0 + 0.045 * cBarp;
+    double sh = 1.0 + 0.015 * cBarp * t;
+    double rt = -std::sin(2.0 * deltaTheta * deg) * rc;
+
+    double dl = deltaLp / sl, dc = deltaCp / sc, dh = deltaHp / sh;
+    return static_cast<float>(std::sqrt(dl * dl + dc * dc + dh * dh + rt * dc * dh));
+}
+
+// Maps pixels to palette colors, applying the color distance metric and rounding mode
+struct Quantizer {
+    std::vector<cv::Vec3b> palette;
+    std::vector<cv::Vec3f> paletteLab;
+    std::vector<float> paletteLuma;
+    ColorDistance metric;
+    RoundingMode rounding;
+    bool linear;                    // Colors are linear light, not sRGB
+    std::mt19937 rng;
+    std::uniform_real_distribution<float> dist{0.0f, 1.0f};
+    std::unordered_map<uint32_t, int> nearestCache;  // Lab lookups are costly, memoize them
+    cv::Mat protectMask;            // Pixels that already match a protected color exactly, or clipped tones
+
+    Quantizer(const Parameters& params, const cv::Mat& image = cv::Mat())
+        : palette(getPalette(params)), metric(params.colorDistance),
+          rounding(params.rounding), linear(params.linearPalette), rng(params.seed) {
+        // Protected colors are kept only where the image already has them, so
+        // they're left out of the colors other pixels can be mapped to
+        if (!params.protectedColors.empty()) {
+            std::vector<cv::Vec3b> selectable, kept;
+            for (const auto& color : palette) {
+                bool isProtected = std::find(params.protectedColors.begin(), params.protectedColors.end(),
+                                             color) != params.protectedColors.end();
+                (isProtected ? kept : selectable).push_back(color);
+            }
+
+            if (!kept.empty() && !selectable.empty()) {
+                palette = selectable;
+                if (!image.empty()) {
+                    protectMask = cv::Mat::zeros(image.rows, image.cols, CV_8U);
+                    for (const auto& color : kept) {
+                        cv::Mat match;
+                        cv::inRange(image, cv::Scalar(color[0], color[1], color[2]),
+                                    cv::Scalar(color[0], color[1], color[2]), match);
+                        cv::bitwise_or(protectMask, match, protectMask);
+                    }
+                }
+            }
+        }
+
+        // Highlight and shadow protection snaps clipped tones to pure white and
+        // black before dithering; keep those out of the error diffusion too
+        if (!image.empty() && (params.highlightProtect < 1.0f || params.shadowProtect > 0.0f)) {
+            if (protectMask.empty()) protectMask = cv::Mat::zeros(image.rows, image.cols, CV_8U);
+            cv::Mat match;
+            if (params.highlightProtect < 1.0f) {
+                cv::inRange(image, cv::Scalar::all(255), cv::Scalar::all(255), match);
+                cv::bitwise_or(protectMask, match, protectMask);
+            }
+            if (params.shadowProtect > 0.0f) {
+                cv::inRange(image, cv::Scalar::all(0), cv::Scalar::all(0), match);
+                cv::bitwise_or(protectMask, match, protectMask);
+            }
+        }
+
+        for (const auto& color : palette) {
+            paletteLuma.push_back(luma(color));
+            paletteLab.push_back(bgrToLab(color, linear));
+        }
+    }
+
+    // Protected pixels keep their color and neither receive nor spread error
+    bool isProtected(int x, int y) const {
+        return !protectMask.empty() && protectMask.at<uchar>(y, x) != 0;
+    }
+
+    bool usesLab() const {
+        return metric == ColorDistance::CIE76 || metric == ColorDistance::CIEDE2000;
+    }
+
+    // Distance from a color (with its Lab value, if the metric needs it) to a palette entry
+    float distance(const cv::Vec3b& color, const cv::Vec3f& lab, size_t index) const {
+        const cv::Vec3b& entry = palette[index];
+        switch (metric) {
+            case ColorDistance::WEIGHTED_RGB: {
+                // "Redmean" approximation of perceptual distance
+                float rMean = (color[2] + entry[2]) / 2.0f;
+                float db = static_cast<float>(color[0]) - entry[0];
+                float dg = static_cast<float>(color[1]) - entry[1];
+                float dr = static_cast<float>(color[2]) - entry[2];
+                return std::sqrt((2.0f + rMean / 256.0f) * dr * dr + 4.0f * dg * dg +
+                                 (2.0f + (255.0f - rMean) / 256.0f) * db * db);
+            }
+            case ColorDistance::CIE76:
+                return static_cast<float>(cv::norm(lab - paletteLab[index]));
+            case ColorDistance::CIEDE2000:
+                return ciede2000(lab, paletteLab[index]);
+            case ColorDistance::RGB:
+            default: {
+                float d0 = static_cast<float>(color[0]) - entry[0];
+                float d1 = static_cast<float>(color[1]) - entry[1];
+                float d2 = static_cast<float>(color[2]) - entry[2];
+                return std::sqrt(d0 * d0 + d1 * d1 + d2 * d2);
+            }
+        }
+    }
+
+    int nearest(const cv::Vec3b& color, const cv::Vec3f& lab) {
+        uint32_t key = (color[0] << 16) | (color[1] << 8) | color[2];
+        if (usesLab()) {
+            auto cached = nearestCache.find(key);
+            if (cached != nearestCache.end()) return cached->second;
+        }
+
+        int best = 0;
+        float bestDist = std::numeric_limits<float>::max();
+        for (size_t i = 0; i < palette.size(); ++i) {
+            float d = distance(color, lab, i);
+            if (d < bestDist) {
+                bestDist = d;
+                best = static_cast<int>(i);
+            }
+        }
+
+        if (usesLab()) nearestCache[key] = best;
+        return best;
+    }
+
+    cv::Vec3b operator()(const cv::Vec3b& color) {
+        if (metric == ColorDistance::RGB && rounding == RoundingMode::NEAREST) {
+            return findClosestColor(color, palette);
+        }
+
+        cv::Vec3f lab = usesLab() ? bgrToLab(color, linear) : cv::Vec3f();
+
+        switch (rounding) {
+            case RoundingMode::FLOOR:
+            case RoundingMode::CEIL: {
+                // Nearest color on one side of the pixel's brightness
+                float pixelLuma = luma(color);
+                int best = -1;
+                float bestDist = std::numeric_limits<float>::max();
+                for (size_t i = 0; i < palette.size(); ++i) {
+                    bool allowed = (rounding == RoundingMode::FLOOR) ? paletteLuma[i] <= pixelLuma + 0.5f
+                                                                     : paletteLuma[i] >= pixelLuma - 0.5f;
+                    if (!allowed) continue;
+                    float d = distance(color, lab, i);
+                    if (d < bestDist) {
+                        bestDist = d;
+                        best = static_cast<int>(i);
+                    }
+                }
+                return palette[best >= 0 ? best : nearest(color, lab)];
+            }
+
+            case RoundingMode::STOCHASTIC: {
+                // Pick one of the two closest colors, the nearer one more often
+                int first = -1, second = -1;
+                float firstDist = std::numeric_limits<float>::max();
+                float secondDist = std::numeric_limits<float>::max();
+                for (size_t i = 0; i < palette.size(); ++i) {
+                    float d = distance(color, lab, i);
+                    if (d < firstDist) {
+                        second = first;
+                        secondDist = firstDist;
+                        first = static_cast<int>(i);
+                        firstDist = d;
+                    } else if (d < secondDist) {
+                        second = static_cast<int>(i);
+                        secondDist = d;
+                    }
+                }
+                if (second < 0 || firstDist + secondDist <= 0.0f) return palette[first];
+                return dist(rng) < firstDist / (firstDist + secondDist) ? palette[second] : palette[first];
+            }
+
+            case RoundingMode::NEAREST:
+            default:
+                return palette[nearest(color, lab)];
+        }
+    }
+};
+
+// Round a high-precision image (CV_32FC3, 0-255) to 8 bits; 8-bit images are copied
+static cv::Mat roundTo8Bit(const cv::Mat& image) {
+    cv::Mat rounded;
+    image.convertTo(rounded, CV_8UC3);
+    return rounded;
+}
+
+// Starting error buffer for error diffusion. High-precision input is rounded to
+// 8 bits for quantizing, and what rounding threw away seeds the error buffer so
+// the extra precision still steers the result
+static cv::Mat initialErrors(const cv::Mat& input) {
+    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+    if (input.depth() == CV_32F) {
+        cv::Mat rounded;
+        roundTo8Bit(input).convertTo(rounded, CV_32FC3);
+        errors = input - rounded;
+    }
+    return errors;
+}
+
+// Levels, then the tone curve, on each channel of a 0-1 float image. Both go
+// into one table, read with linear interpolation so 16-bit sources keep
+// their precision
+static void applyToneMapping(cv::Mat& image, const Parameters& params) {
+    bool levels = params.levelsBlack > 0.0f || params.levelsWhite < 1.0f || params.levelsMidtone != 1.0f;
+    std::string error;
+    bool curve = !params.toneCurve.empty() && validateToneCurve(params.toneCurve, error);
+    if (!levels && !curve) return;
+
+    float black = std::clamp(params.levelsBlack, 0.0f, 0.99f);
+    float white = std::clamp(params.levelsWhite, black + 0.01f, 1.0f);
+    float midtone = std::clamp(params.levelsMidtone, 0.1f, 10.0f);
+    const int size = 4096;
+    std::vector<float> table(size);
+    for (int i = 0; i < size; ++i) {
+        float value = std::clamp((static_cast<float>(i) / (size - 1) - black) / (white - black), 0.0f, 1.0f);
+        if (midtone != 1.0f) value = std::pow(value, 1.0f / midtone);
+        table[i] = curve ? evaluateToneCurve(params.toneCurve, value) : value;
+    }
+
+    cv::Mat values = image.reshape(1);
+    for (int y = 0; y < values.rows; ++y) {
+        float* row = values.ptr<float>(y);
+        for (int x = 0; x < values.cols; ++x) {
+            float position = std::clamp(row[x], 0.0f, 1.0f) * (size - 1);
+            int index = std::min(static_cast<int>(position), size - 2);
+            float t = position - index;
+            row[x] = table[index] + (table[index + 1] - table[index]) * t;
+        }
+    }
+}
+
+// Median or bilateral denoise pre-pass; median sizes above 5 need 8-bit input,
+// so high bit depth sources stop there
+static cv::Mat denoiseImage(const cv::Mat& input, const Parameters& params) {
+    float strength = std::clamp(params.denoiseStrength, 0.0f, 1.0f);
+    cv::Mat denoised;
+    if (params.denoise == Denoise::MEDIAN) {
+        int size = 3 + 2 * static_cast<int>(std::lround(strength * 3.0f));
+        if (input.depth() == CV_32F) size = std::min(size, 5);
+        cv::medianBlur(input, denoised, size);
+    } else if (params.denoise == Denoise::BILATERAL) {
+        cv::bilateralFilter(input, denoised, -1, 5.0 + 45.0 * strength, 1.5 + 4.5 * strength);
+    } else {
+        denoised = input.clone();
+    }
+    return denoised;
+}
+
+// Adjustments (exposure, blur, sharpening, gamma, contrast, brightness, saturation).
+// 8-bit input gives 8-bit output; high-precision input (CV_32FC3, 0-255) stays in float
+static cv::Mat adjustImage(const cv::Mat& input, const Parameters& params) {
+    cv::Mat processed = denoiseImage(input, params);
 
     // Convert to float for processing
     processed.convertTo(processed, CV_32FC3, 1.0/255.0);
 
+    // Exposure in stops
+    if (params.exposure != 0.0f) {
+        processed *= std::pow(2.0f, params.exposure);
+    }
+
+    // Soften noise and texture that would otherwise be dithered as detail
+    if (params.blurRadius > 0.0f) {
+        cv::GaussianBlur(processed, processed, cv::Size(), params.blurRadius);
+    }
+
+    // Unsharp mask: add back the difference from a blurred copy
+    if (params.sharpenAmount > 0.0f && params.sharpenRadius > 0.0f) {
+        cv::Mat blurred;
+        cv::GaussianBlur(processed, blurred, cv::Size(), params.sharpenRadius);
+        cv::addWeighted(processed, 1.0 + params.sharpenAmount, blurred, -params.sharpenAmount, 0.0, processed);
+    }
+
     // Brightness and contrast
     processed = processed * params.contrast + params.brightness;
 
@@ -20,6 +383,8 @@ cv::Mat preprocessImage(const cv::Mat& input, const Parameters& params) {
         cv::pow(processed, params.gamma, processed);
     }
 
+    applyToneMapping(processed, params);
+
     // Saturation
     if (params.saturation != 1.0f) {
         cv::Mat hsv;
@@ -31,34 +396,219 @@ cv::Mat preprocessImage(const cv::Mat& input, const Parameters& params) {
         cv::cvtColor(hsv, processed, cv::COLOR_HSV2BGR);
     }
 
-    // Clamp and convert back
+    // Clamp and convert back (HDR highlights above 1.0 clip to white)
     processed = cv::max(cv::min(processed, 1.0), 0.0);
-    processed.convertTo(processed, CV_8UC3, 255.0);
+    processed.convertTo(processed, input.depth() == CV_32F ? CV_32FC3 : CV_8UC3, 255.0);
 
     return processed;
 }
 
-// Main dithering function dispatcher
-cv::Mat ditherImage(const cv::Mat& input, const Parameters& params) {
-    cv::Mat preprocessed = preprocessImage(input, params);
+// Black/white points from the luma histogram such that at least the requested
+// share of pixels clips to pure black/white (0 and 255 when disabled)
+static std::pair<int, int> computeClipPoints(const cv::Mat& image, const Parameters& params) {
+    int blackPoint = 0;
+    int whitePoint = 255;
+    if (params.clipBlack <= 0.0f && params.clipWhite <= 0.0f) {
+        return {blackPoint, whitePoint};
+    }
+
+    cv::Mat gray;
+    cv::cvtColor(roundTo8Bit(image), gray, cv::COLOR_BGR2GRAY);
+
+    std::vector<int> histogram(256, 0);
+    for (int y = 0; y < gray.rows; ++y) {
+        for (int x = 0; x < gray.cols; ++x) {
+            histogram[gray.at<uchar>(y, x)]++;
+        }
+    }
+
+    double total = static_cast<double>(gray.total());
+    if (params.clipBlack > 0.0f) {
+        double target = total * params.clipBlack / 100.0;
+        double count = 0.0;
+        for (; blackPoint < 255; ++blackPoint) {
+            count += histogram[blackPoint];
+            if (count >= target) break;
+        }
+    }
+
+    if (params.clipWhite > 0.0f) {
+        double target = total * params.clipWhite / 100.0;
+        double count = 0.0;
+        for (; whitePoint > 0; --whitePoint) {
+            count += histogram[whitePoint];
+            if (count >= target) break;
+        }
+    }
+
+    return {blackPoint, whitePoint};
+}
+
+// Stretch levels so the clip points map to pure black/white, keeping shadows
+// and highlights from plugging in 1-bit output
+static void applyClipPoints(cv::Mat& image, const std::pair<int, int>& clipPoints) {
+    auto [blackPoint, whitePoint] = clipPoints;
+    if (whitePoint <= blackPoint || (blackPoint == 0 && whitePoint == 255)) return;
+
+    double scale = 255.0 / (whitePoint - blackPoint);
+    image.convertTo(image, -1, scale, -blackPoint * scale);
+    if (image.depth() == CV_32F) image = cv::max(cv::min(image, 255.0), 0.0);
+}
+
+// Printed dots spread, so a 50% dot prints as 50% + dotGain. Modeled per ink
+// as paper showing through = v^k, with k from the gain at 50%, and undone by
+// v^(1/k) on every channel: paper and solid ink stay, midtones get lighter
+static void compensateDotGain(cv::Mat& image, float dotGain) {
+    if (dotGain <= 0.0f) return;
+    double gain = std::min(dotGain, 45.0f) / 100.0;
+    double exponent = std::log(0.5) / std::log(0.5 - gain);
+
+    if (image.depth() == CV_8U) {
+        cv::Mat table(1, 256, CV_8U);
+        for (int i = 0; i < 256; ++i) {
+            table.at<uchar>(i) = cv::saturate_cast<uchar>(255.0 * std::pow(i / 255.0, exponent));
+        }
+        cv::LUT(image, table, image);
+        return;
+    }
+    cv::Mat normalized = cv::max(image, 0.0) / 255.0;
+    cv::pow(normalized, exponent, normalized);
+    image = normalized * 255.0;
+}
+
+cv::Vec3f getGrayWeights(const Parameters& params) {
+    switch (params.grayConversion) {
+        case GrayConversion::REC709: return cv::Vec3f(0.0722f, 0.7152f, 0.2126f);
+        case GrayConversion::AVERAGE: return cv::Vec3f(1.0f, 1.0f, 1.0f) / 3.0f;
+        case GrayConversion::RED: return cv::Vec3f(0.0f, 0.0f, 1.0f);
+        case GrayConversion::GREEN: return cv::Vec3f(0.0f, 1.0f, 0.0f);
+        case GrayConversion::BLUE: return cv::Vec3f(1.0f, 0.0f, 0.0f);
+        case GrayConversion::CUSTOM: {
+            // Negative weights would invert a channel; all zero falls back to Rec.601
+            cv::Vec3f weights(std::max(0.0f, params.grayWeights[0]), std::max(0.0f, params.grayWeights[1]),
+                              std::max(0.0f, params.grayWeights[2]));
+            float sum = weights[0] + weights[1] + weights[2];
+            if (sum > 0.0f) return weights / sum;
+            return cv::Vec3f(0.114f, 0.587f, 0.299f);
+        }
+        case GrayConversion::REC601:
+        default: return cv::Vec3f(0.114f, 0.587f, 0.299f);
+    }
+}
+
+cv::Mat convertToGray(const cv::Mat& image, const Parameters& params) {
+    if (image.channels() == 1) return image.clone();
+    cv::Vec3f weights = getGrayWeights(params);
+    cv::Mat matrix = (cv::Mat_<float>(1, 3) << weights[0], weights[1], weights[2]);
+    cv::Mat bgr = image, gray;
+    if (image.channels() == 4) cv::cvtColor(image, bgr, cv::COLOR_BGRA2BGR);
+    cv::transform(bgr, gray, matrix);
+    return gray;
+}
+
+// Helper function to apply preprocessing (adjustments, black/white point
+// clipping, then dot gain compensation)
+cv::Mat preprocessImage(const cv::Mat& input, const Parameters& params) {
+    cv::Mat processed = adjustImage(input, params);
+    applyClipPoints(processed, computeClipPoints(processed, params));
+    compensateDotGain(processed, params.dotGain);
+    return processed;
+}
+
+// Check whether every palette entry is a neutral gray
+static bool isGrayscalePalette(const std::vector<cv::Vec3b>& palette) {
+    for (const auto& color : palette) {
+        if (color[0] != color[1] || color[1] != color[2]) return false;
+    }
+    return true;
+}
+
+// Adaptive palettes are generated from the image, then used like a custom palette
+static void resolveAdaptivePalette(Parameters& params, const cv::Mat& preprocessed) {
+    if (params.paletteMode == PaletteMode::ADAPTIVE) {
+        params.customPalette = generatePalette(roundTo8Bit(preprocessed), params.paletteSize,
+                                               params.paletteMethod, params.seed);
+        params.paletteMode = PaletteMode::CUSTOM;
+    }
+}
+
+static std::vector<cv::Mat> separatePreprocessed(const cv::Mat& preprocessed, const Parameters& params);
+static cv::Mat ditherChannels(const cv::Mat& preprocessed, const Parameters& params,
+                              const std::array<int, 3>& levels);
+
+// Auto threshold: Otsu's method on the preprocessed image, which all later steps
+// then use like a manually set threshold
+static void resolveAutoThreshold(Parameters& params, const cv::Mat& preprocessed) {
+    if (params.autoThreshold) {
+        params.threshold = computeOtsuThreshold(preprocessed);
+        params.autoThreshold = false;
+    }
+}
+
+// Moving the rounding point between two output levels is the same as shifting
+// the image the other way by that share of the level spacing. Planes dithered
+// separately (pixel formats, channel overrides) use the per-plane thresholds
+static void applyThreshold(cv::Mat& preprocessed, const Parameters& params) {
+    if (params.threshold == 0.5f || params.colorMode == ColorMode::PIXEL_FORMAT || hasChannelOverrides(params) ||
+        ((params.algorithm == Algorithm::POSTERIZE || params.algorithm == Algorithm::STIPPLE) &&
+         params.plugin.empty())) {
+        return;
+    }
+
+    float step = 255.0f;    // CMYK plates are 1-bit
+    if (params.colorMode == ColorMode::MULTI_INK && !params.inks.empty()) {
+        step = 255.0f / params.inks.size();
+    } else if (params.colorMode == ColorMode::RGB) {
+        std::vector<cv::Vec3b> palette = getPalette(params);
+        float darkest = 255.0f, lightest = 0.0f;
+        for (const auto& color : palette) {
+            darkest = std::min(darkest, luma(color));
+            lightest = std::max(lightest, luma(color));
+        }
+        if (palette.size() > 1) step = (lightest - darkest) / (palette.size() - 1);
+    }
+    preprocessed.convertTo(preprocessed, -1, 1.0, (0.5f - params.threshold) * step);
+}
+
+// Each distinct plugin failure is printed once, not once per video frame or band
+static void warnPluginFailure(const Errors::Error& error, Algorithm fallback) {
+    static std::mutex warnedMutex;
+    static std::set<std::string> warned;
+    std::lock_guard<std::mutex> lock(warnedMutex);
+    if (!warned.insert(error.message).second) return;
+    std::cerr << "Warning: " << error.message << ", using " << getAlgorithmName(fallback) << " instead" << std::endl;
+}
+
+// Dispatch to the selected algorithm
+static cv::Mat runAlgorithm(const cv::Mat& preprocessed, const Parameters& params, const BandCallback& onBand) {
+    // Plugins get 8-bit pixels; one that fails is reported and the built-in algorithm is used instead
+    if (!params.plugin.empty()) {
+        cv::Mat result = roundTo8Bit(preprocessed);
+        Errors::Error error;
+        if (Plugins::runAlgorithm(params.plugin, result, getPalette(params), params.strength, params.threshold,
+                                  params.seed, params.phaseX, params.phaseY, params.pluginOptions, &error)) {
+            return result;
+        }
+        warnPluginFailure(error, params.algorithm);
+    }
 
     switch (params.algorithm) {
         case Algorithm::FLOYD_STEINBERG:
-            return floydSteinberg(preprocessed, params);
+            return floydSteinberg(preprocessed, params, onBand);
         case Algorithm::ATKINSON:
-            return atkinson(preprocessed, params);
+            return atkinson(preprocessed, params, onBand);
         case Algorithm::JARVIS_JUDICE_NINKE:
-            return jarvisJudiceNinke(preprocessed, params);
+            return jarvisJudiceNinke(preprocessed, params, onBand);
         case Algorithm::STUCKI:
-            return stucki(preprocessed, params);
+            return stucki(preprocessed, params, onBand);
         case Algorithm::BURKES:
-            return burkes(preprocessed, params);
+            return burkes(preprocessed, params, onBand);
         case Algorithm::SIERRA:
-            return sierra(preprocessed, params);
+            return sierra(preprocessed, params, onBand);
         case Algorithm::SIERRA_TWO_ROW:
-            return sierraTwo(preprocessed, params);
+            return sierraTwo(preprocessed, params, onBand);
         case Algorithm::SIERRA_LITE:
-            return sierraLite(preprocessed, params);
+            return sierraLite(preprocessed, params, onBand);
         case Algorithm::ORDERED_BAYER_2X2:
         case Algorithm::ORDERED_BAYER_4X4:
         case Algorithm::ORDERED_BAYER_8X8:
@@ -79,125 +629,1290 @@ cv::Mat ditherImage(const cv::Mat& input, const Parameters& params) {
         case Algorithm::GRADIENT_BASED:
             return gradientBased(preprocessed, params);
         case Algorithm::VARIABLE_ERROR_DIFFUSION:
-            return variableErrorDiffusion(preprocessed, params);
+            return variableErrorDiffusion(preprocessed, params, onBand);
         case Algorithm::OSTROMOUKHOV:
-            return ostromoukhov(preprocessed, params);
+            return ostromoukhov(preprocessed, params, onBand);
         case Algorithm::FAN:
-            return fan(preprocessed, params);
+            return fan(preprocessed, params, onBand);
         case Algorithm::SHIAU_FAN:
-            return shiauFan(preprocessed, params);
+            return shiauFan(preprocessed, params, onBand);
         case Algorithm::STEVENPIGEON:
-            return stevenPigeon(preprocessed, params);
+            return stevenPigeon(preprocessed, params, onBand);
+        case Algorithm::ORDERED_MATRIX:
+            return matrixDither(preprocessed, params);
+        case Algorithm::INTERLEAVED_GRADIENT_NOISE:
+            return interleavedGradientNoise(preprocessed, params);
+        case Algorithm::HALFTONE:
+            return halftone(preprocessed, params);
+        case Algorithm::THRESHOLD:
+            return simpleThreshold(preprocessed, params);
+        case Algorithm::SAUVOLA:
+            return sauvola(preprocessed, params);
+        case Algorithm::NIBLACK:
+            return niblack(preprocessed, params);
+        case Algorithm::BRADLEY:
+            return bradley(preprocessed, params);
+        case Algorithm::STRUCTURE_AWARE:
+            return structureAware(preprocessed, params);
+        case Algorithm::POSTERIZE:
+            return posterize(preprocessed, params);
+        case Algorithm::STIPPLE:
+            return stipple(preprocessed, params);
+        case Algorithm::CUSTOM_KERNEL:
+            return customKernel(preprocessed, params, onBand);
         default:
-            return floydSteinberg(preprocessed, params);
+            return floydSteinberg(preprocessed, params, onBand);
+    }
+}
+
+// Set while ditherWithSnapshots runs, on its thread: the step each pixel of
+// the working image was finished at (-1 = not yet), in the first pass only
+struct DiffusionRecorder {
+    cv::Mat order;
+    int steps = 0;
+};
+static thread_local DiffusionRecorder* recorder = nullptr;
+
+// Called by the diffusion loops as each pixel is quantized. Passes over
+// working images of another size, such as extra stages at full size after a
+// pixelated run, are ignored
+static void recordVisit(const cv::Mat& result, int x, int y) {
+    if (!recorder) return;
+    if (recorder->order.empty()) recorder->order = cv::Mat(result.size(), CV_32S, cv::Scalar(-1));
+    if (recorder->order.size() != result.size()) return;
+    int& step = recorder->order.at<int>(y, x);
+    if (step < 0) step = recorder->steps++;
+}
+
+// Set by ditherWithinMemory while it renders, on its thread and on the workers
+// it fans out to: the row loops of the algorithms report through it and stop
+// early once the render is cancelled
+struct RenderProgress {
+    ProgressCallback callback;
+    std::thread::id owner = std::this_thread::get_id();    // Only this thread calls back
+    int rows = 1;                                           // Rows expected, for the fraction
+    std::atomic<int> done{0};
+    std::atomic<bool> cancelled{false};
+};
+static thread_local RenderProgress* rendering = nullptr;
+
+// Makes progress the current render's on this thread while in scope
+struct RenderScope {
+    RenderProgress* previous;
+    explicit RenderScope(RenderProgress* progress) : previous(rendering) { rendering = progress; }
+    ~RenderScope() { rendering = previous; }
+};
+
+// Called as a row loop starts a row. False once the render is cancelled, so
+// the loop can stop; its partial result is thrown away
+static bool nextRow() {
+    if (!rendering) return true;
+    int done = ++rendering->done;
+    if (rendering->callback && !rendering->cancelled && std::this_thread::get_id() == rendering->owner) {
+        float fraction = std::min(0.99f, static_cast<float>(done) / std::max(1, rendering->rows));
+        if (!rendering->callback(fraction, "Dithering")) rendering->cancelled = true;
+    }
+    return !rendering->cancelled;
+}
+
+// Error diffusion hands finished rows to onBand in blocks of this many
+static const int diffusionBandRows = 128;
+
+// Smaller images aren't worth spreading over the worker threads
+static const int parallelDiffusionPixels = 256 * 256;
+
+// Highlight and shadow protection: pixels lighter than highlightProtect or darker
+// than shadowProtect are marked in the mask (255 = highlight, 128 = shadow),
+// measured before the threshold shift
+static cv::Mat clippedTones(const cv::Mat& preprocessed, const Parameters& params) {
+    if (params.highlightProtect >= 1.0f && params.shadowProtect <= 0.0f) return cv::Mat();
+
+    cv::Mat gray = convertToGray(preprocessed, params);
+    cv::Mat mask = cv::Mat::zeros(gray.size(), CV_8U);
+    if (params.highlightProtect < 1.0f) mask.setTo(255, gray > params.highlightProtect * 255.0f);
+    if (params.shadowProtect > 0.0f) mask.setTo(128, gray < params.shadowProtect * 255.0f);
+    return mask;
+}
+
+// Clipped pixels become pure white or black, which the quantizer then leaves
+// alone: they take no error from their neighbours and give none back
+static void snapClippedTones(cv::Mat& preprocessed, const cv::Mat& mask) {
+    if (mask.empty()) return;
+    preprocessed.setTo(cv::Scalar::all(255), mask == 255);
+    preprocessed.setTo(cv::Scalar::all(0), mask == 128);
+}
+
+// Pixels of the source that have a protected palette color exactly, found
+// before adjustments, clip points and the threshold shift move their values
+static cv::Mat protectedPixels(const cv::Mat& input, const Parameters& params) {
+    if (params.protectedColors.empty()) return cv::Mat();
+
+    std::vector<cv::Vec3b> palette = getPalette(params);
+    cv::Mat source = roundTo8Bit(input);
+    cv::Mat mask;
+    for (const auto& color : params.protectedColors) {
+        if (std::find(palette.begin(), palette.end(), color) == palette.end()) continue;
+        cv::Mat match;
+        cv::inRange(source, cv::Scalar(color[0], color[1], color[2]),
+                    cv::Scalar(color[0], color[1], color[2]), match);
+        if (mask.empty()) mask = match;
+        else cv::bitwise_or(mask, match, mask);
+    }
+    return mask;
+}
+
+// Give protected pixels their source colors back so the quantizer still finds them
+static void restoreProtectedPixels(cv::Mat& preprocessed, const cv::Mat& input, const cv::Mat& mask) {
+    if (mask.empty()) return;
+    cv::Mat original;
+    roundTo8Bit(input).convertTo(original, preprocessed.type());
+    original.copyTo(preprocessed, mask);
+}
+
+// Give clipped pixels the lightest and darkest colors the output can have
+static void fillClippedTones(cv::Mat& result, const cv::Mat& mask, const Parameters& params) {
+    if (mask.empty()) return;
+
+    cv::Vec3b darkest(0, 0, 0), lightest(255, 255, 255);
+    if (params.colorMode == ColorMode::MULTI_INK && !params.inks.empty()) {
+        darkest = *std::min_element(params.inks.begin(), params.inks.end(),
+                                    [](const cv::Vec3b& a, const cv::Vec3b& b) { return luma(a) < luma(b); });
+        lightest = params.paperColor;
+    } else if (params.colorMode == ColorMode::RGB && !hasChannelOverrides(params)) {
+        std::vector<cv::Vec3b> palette = getPalette(params);
+        auto byLuma = [](const cv::Vec3b& a, const cv::Vec3b& b) { return luma(a) < luma(b); };
+        darkest = *std::min_element(palette.begin(), palette.end(), byLuma);
+        lightest = *std::max_element(palette.begin(), palette.end(), byLuma);
+    }
+    result.setTo(cv::Scalar(lightest[0], lightest[1], lightest[2]), mask == 255);
+    result.setTo(cv::Scalar(darkest[0], darkest[1], darkest[2]), mask == 128);
+}
+
+// Mix the pre-quantized image back into a result: a dither texture over the
+// image instead of full quantization
+static void blendWithSource(cv::Mat& result, const cv::Mat& source, float amount) {
+    if (amount <= 0.0f || source.empty()) return;
+    cv::Mat mixed;
+    cv::addWeighted(result, 1.0 - std::min(amount, 1.0f), roundTo8Bit(source), std::min(amount, 1.0f), 0.0, mixed);
+    result = mixed;
+}
+
+// Run the selected algorithm on an already preprocessed image. Only the plain
+// path reports bands; modes that remap the result afterwards don't
+static cv::Mat ditherPreprocessed(cv::Mat preprocessed, const Parameters& params,
+                                  const BandCallback& onBand = nullptr) {
+    if (params.colorMode == ColorMode::CMYK) {
+        return combineSeparations(separatePreprocessed(roundTo8Bit(preprocessed), params));
+    }
+
+    // Display pixel formats: every channel is its own plane with its own bit depth
+    if (params.colorMode == ColorMode::PIXEL_FORMAT) {
+        Parameters formatParams = params;
+        formatParams.channelSpace = ChannelSpace::BGR;
+        return ditherChannels(preprocessed, formatParams, getPixelFormatLevels(params.pixelFormat));
+    }
+
+    if (hasChannelOverrides(params)) {
+        int levels = std::clamp(params.levels, 2, 256);
+        return ditherChannels(preprocessed, params, {levels, levels, levels});
+    }
+
+    // Duotone/tritone: the darkest ink takes the shadows and paper the highlights,
+    // each ink an equal share of the tonal range. Dither against evenly spaced
+    // grays so error diffuses between neighbouring inks, then swap in the inks
+    if (params.colorMode == ColorMode::MULTI_INK && !params.inks.empty()) {
+        std::vector<cv::Vec3b> inks = params.inks;
+        std::stable_sort(inks.begin(), inks.end(),
+                         [](const cv::Vec3b& a, const cv::Vec3b& b) { return luma(a) < luma(b); });
+        inks.push_back(params.paperColor);
+        int steps = static_cast<int>(inks.size()) - 1;
+
+        Parameters toneParams = params;
+        toneParams.colorMode = ColorMode::RGB;
+        toneParams.paletteMode = PaletteMode::CUSTOM;
+        toneParams.customPalette.clear();
+        for (int i = 0; i <= steps; ++i) {
+            uchar value = static_cast<uchar>(i * 255 / steps);
+            toneParams.customPalette.push_back(cv::Vec3b(value, value, value));
+        }
+        toneParams.protectedColors.clear();
+        toneParams.linearize = false;
+        cv::Mat result = ditherPreprocessed(preprocessed, toneParams);
+
+        for (int y = 0; y < result.rows; ++y) {
+            for (int x = 0; x < result.cols; ++x) {
+                cv::Vec3b& pixel = result.at<cv::Vec3b>(y, x);
+                pixel = inks[(pixel[1] * steps + 127) / 255];
+            }
+        }
+        return result;
+    }
+
+    // Ink and paper: dither in plain black and white, then swap in the two colors,
+    // so the result follows brightness whatever colors were picked
+    if (params.paletteMode == PaletteMode::MONOCHROME &&
+        (params.inkColor != cv::Vec3b(0, 0, 0) || params.paperColor != cv::Vec3b(255, 255, 255))) {
+        Parameters monoParams = params;
+        monoParams.inkColor = cv::Vec3b(0, 0, 0);
+        monoParams.paperColor = cv::Vec3b(255, 255, 255);
+        cv::Mat result = ditherPreprocessed(preprocessed, monoParams);
+
+        for (int y = 0; y < result.rows; ++y) {
+            for (int x = 0; x < result.cols; ++x) {
+                cv::Vec3b& pixel = result.at<cv::Vec3b>(y, x);
+                pixel = pixel[1] < 128 ? params.inkColor : params.paperColor;
+            }
+        }
+        return result;
+    }
+
+    // Monochrome/grayscale palettes: dither luma, so every channel carries the same
+    // value and error instead of R, G and B being thresholded independently
+    if (isGrayscalePalette(getPalette(params))) {
+        cv::cvtColor(convertToGray(preprocessed, params), preprocessed, cv::COLOR_GRAY2BGR);
+    }
+
+    // Perceptual intent: compress the image's tonal range into the palette's, so
+    // palettes without true black or white keep shadow and highlight detail
+    if (params.renderingIntent == RenderingIntent::PERCEPTUAL) {
+        std::vector<cv::Vec3b> palette = getPalette(params);
+        float darkest = 255.0f, lightest = 0.0f;
+        for (const auto& color : palette) {
+            darkest = std::min(darkest, luma(color));
+            lightest = std::max(lightest, luma(color));
+        }
+        if (lightest > darkest) {
+            preprocessed.convertTo(preprocessed, -1, (lightest - darkest) / 255.0f, darkest);
+        }
+    }
+
+    // Working color space: convert the image and palette, quantize and diffuse error
+    // on those channels, then map back to the original palette colors. Grayscale
+    // palettes only ever dither luma, so the space makes no difference to them
+    if (params.workingSpace != WorkingSpace::RGB && !isGrayscalePalette(getPalette(params))) {
+        int toSpace = cv::COLOR_BGR2Lab;
+        cv::Vec3f scale(255.0f / 100.0f, 1.0f, 1.0f), offset(0.0f, 128.0f, 128.0f);
+        if (params.workingSpace == WorkingSpace::YCRCB) {
+            toSpace = cv::COLOR_BGR2YCrCb;
+            scale = cv::Vec3f(255.0f, 255.0f, 255.0f);
+            offset = cv::Vec3f();
+        } else if (params.workingSpace == WorkingSpace::HSL) {
+            toSpace = cv::COLOR_BGR2HLS_FULL;
+            scale = cv::Vec3f(255.0f / 360.0f, 255.0f, 255.0f);
+            offset = cv::Vec3f();
+        }
+
+        // Converted in float and scaled to the 8-bit ranges of the space, so
+        // palette colors are rounded once and the pixels keep their precision
+        auto convert = [toSpace, scale, offset](const cv::Mat& bgr) {
+            cv::Mat converted;
+            bgr.convertTo(converted, CV_32F, 1.0 / 255.0);
+            cv::cvtColor(converted, converted, toSpace);
+            for (int y = 0; y < converted.rows; ++y) {
+                cv::Vec3f* row = converted.ptr<cv::Vec3f>(y);
+                for (int x = 0; x < converted.cols; ++x) {
+                    for (int c = 0; c < 3; ++c) row[x][c] = row[x][c] * scale[c] + offset[c];
+                }
+            }
+            return converted;
+        };
+        auto convertColors = [&convert](const std::vector<cv::Vec3b>& colors) {
+            std::vector<cv::Vec3b> converted;
+            if (colors.empty()) return converted;
+            cv::Mat row(1, static_cast<int>(colors.size()), CV_8UC3);
+            for (size_t i = 0; i < colors.size(); ++i) row.at<cv::Vec3b>(0, static_cast<int>(i)) = colors[i];
+            cv::Mat space = convert(row);
+            for (int i = 0; i < space.cols; ++i) {
+                const cv::Vec3f& color = space.at<cv::Vec3f>(0, i);
+                converted.emplace_back(cv::saturate_cast<uchar>(color[0]), cv::saturate_cast<uchar>(color[1]),
+                                       cv::saturate_cast<uchar>(color[2]));
+            }
+            return converted;
+        };
+
+        std::vector<cv::Vec3b> palette = getPalette(params);
+        Parameters spaceParams = params;
+        spaceParams.workingSpace = WorkingSpace::RGB;
+        spaceParams.linearize = false;
+        spaceParams.renderingIntent = RenderingIntent::RELATIVE_COLORIMETRIC;
+        spaceParams.colorDistance = ColorDistance::RGB;
+        spaceParams.paletteMode = PaletteMode::CUSTOM;
+        spaceParams.customPalette = convertColors(palette);
+        spaceParams.protectedColors = convertColors(params.protectedColors);
+        spaceParams.highlightProtect = 1.0f;    // Pure white and black aren't pure here
+        spaceParams.shadowProtect = 0.0f;
+
+        std::unordered_map<uint32_t, cv::Vec3b> toSrgb;
+        for (size_t i = 0; i < palette.size(); ++i) {
+            const cv::Vec3b& converted = spaceParams.customPalette[i];
+            toSrgb.emplace((converted[0] << 16) | (converted[1] << 8) | converted[2], palette[i]);
+        }
+
+        cv::Mat spaceInput = convert(preprocessed);
+        cv::Mat result = ditherPreprocessed(spaceInput, spaceParams);
+
+        for (int y = 0; y < result.rows; ++y) {
+            for (int x = 0; x < result.cols; ++x) {
+                cv::Vec3b& pixel = result.at<cv::Vec3b>(y, x);
+                auto match = toSrgb.find((pixel[0] << 16) | (pixel[1] << 8) | pixel[2]);
+                if (match != toSrgb.end()) pixel = match->second;
+            }
+        }
+        return result;
+    }
+
+    // Linear light: dither linearized pixels against a linearized palette so the
+    // error math happens in linear light, then map back to the sRGB palette colors.
+    // The pixels stay in float, as linear light squeezes the shadows into the
+    // bottom few 8-bit levels; Lab distances take the palette as linear
+    if (params.linearize) {
+        cv::Mat lut(1, 256, CV_8U);
+        std::array<float, 256> table;
+        for (int i = 0; i < 256; ++i) {
+            table[i] = srgbToLinear(static_cast<float>(i)) * 255.0f;
+            lut.at<uchar>(0, i) = cv::saturate_cast<uchar>(table[i]);
+        }
+
+        std::vector<cv::Vec3b> palette = getPalette(params);
+        Parameters linearParams = params;
+        linearParams.linearize = false;
+        linearParams.linearPalette = true;
+        linearParams.renderingIntent = RenderingIntent::RELATIVE_COLORIMETRIC;
+        linearParams.paletteMode = PaletteMode::CUSTOM;
+        linearParams.customPalette.clear();
+        for (auto& color : linearParams.protectedColors) {
+            color = cv::Vec3b(lut.at<uchar>(0, color[0]), lut.at<uchar>(0, color[1]), lut.at<uchar>(0, color[2]));
+        }
+
+        std::unordered_map<uint32_t, cv::Vec3b> toSrgb;
+        for (const auto& color : palette) {
+            cv::Vec3b linear(lut.at<uchar>(0, color[0]), lut.at<uchar>(0, color[1]), lut.at<uchar>(0, color[2]));
+            linearParams.customPalette.push_back(linear);
+            toSrgb.emplace((linear[0] << 16) | (linear[1] << 8) | linear[2], color);
+        }
+
+        cv::Mat linearInput(preprocessed.size(), CV_32FC3);
+        for (int y = 0; y < preprocessed.rows; ++y) {
+            for (int x = 0; x < preprocessed.cols; ++x) {
+                cv::Vec3f& out = linearInput.at<cv::Vec3f>(y, x);
+                if (preprocessed.depth() == CV_32F) {
+                    const cv::Vec3f& pixel = preprocessed.at<cv::Vec3f>(y, x);
+                    out = cv::Vec3f(srgbToLinear(pixel[0]) * 255.0f, srgbToLinear(pixel[1]) * 255.0f,
+                                    srgbToLinear(pixel[2]) * 255.0f);
+                } else {
+                    const cv::Vec3b& pixel = preprocessed.at<cv::Vec3b>(y, x);
+                    out = cv::Vec3f(table[pixel[0]], table[pixel[1]], table[pixel[2]]);
+                }
+            }
+        }
+        cv::Mat result = ditherPreprocessed(linearInput, linearParams);
+
+        for (int y = 0; y < result.rows; ++y) {
+            for (int x = 0; x < result.cols; ++x) {
+                cv::Vec3b& pixel = result.at<cv::Vec3b>(y, x);
+                auto match = toSrgb.find((pixel[0] << 16) | (pixel[1] << 8) | pixel[2]);
+                if (match != toSrgb.end()) pixel = match->second;
+            }
+        }
+        return result;
+    }
+
+    // Error diffusion carries high-precision input through its error buffer;
+    // threshold-based algorithms compare against 8-bit thresholds anyway
+    if (preprocessed.depth() == CV_32F && !isErrorDiffusion(params.algorithm)) {
+        preprocessed = roundTo8Bit(preprocessed);
+    }
+
+    return runAlgorithm(preprocessed, params, onBand);
+}
+
+// Split into CMYK plates with full gray component replacement (K carries the
+// shared darkness, C, M and Y only the remaining color), then dither each plate
+// as monochrome. Halftones use the plate's screen angle: C, M and Y take the
+// R, G and B channel angles, K the grayscale angle. Threshold matrices turn by
+// the same angles relative to K, so the plates' dots don't land on each other
+static std::vector<cv::Mat> separatePreprocessed(const cv::Mat& preprocessed, const Parameters& params) {
+    std::vector<cv::Mat> tones(4);
+    for (auto& tone : tones) tone.create(preprocessed.size(), CV_8UC1);
+
+    for (int y = 0; y < preprocessed.rows; ++y) {
+        for (int x = 0; x < preprocessed.cols; ++x) {
+            const cv::Vec3b& pixel = preprocessed.at<cv::Vec3b>(y, x);
+            float b = pixel[0] / 255.0f, g = pixel[1] / 255.0f, r = pixel[2] / 255.0f;
+            float k = 1.0f - std::max({r, g, b});
+            float scale = k < 1.0f ? 1.0f / (1.0f - k) : 0.0f;
+            float ink[4] = {(1.0f - r - k) * scale, (1.0f - g - k) * scale, (1.0f - b - k) * scale, k};
+
+            // Plates are stored as tones, so full coverage is black like the ink
+            for (int p = 0; p < 4; ++p) {
+                tones[p].at<uchar>(y, x) = cv::saturate_cast<uchar>((1.0f - ink[p]) * 255.0f);
+            }
+        }
+    }
+
+    const float angles[4] = {params.screenAngles[2], params.screenAngles[1],
+                             params.screenAngles[0], params.grayScreenAngle};
+    std::vector<cv::Mat> plates(4);
+    for (int p = 0; p < 4; ++p) {
+        Parameters plateParams = params;
+        plateParams.colorMode = ColorMode::RGB;
+        plateParams.paletteMode = PaletteMode::MONOCHROME;
+        plateParams.inkColor = cv::Vec3b(0, 0, 0);
+        plateParams.paperColor = cv::Vec3b(255, 255, 255);
+        plateParams.protectedColors.clear();
+        plateParams.highlightProtect = 1.0f;    // Clipped tones are already flat on every plate
+        plateParams.shadowProtect = 0.0f;
+        plateParams.channelOverrides = {};
+        plateParams.linearize = false;
+        plateParams.grayScreenAngle = angles[p];
+        plateParams.ditherAngle = params.ditherAngle + angles[p] - angles[3];
+        plateParams.seed = params.seed + p;  // Keep noise-based plates from lining up
+        plateParams.phaseX = params.phaseX + p * 17;  // Loaded noise textures don't change with the seed
+        plateParams.phaseY = params.phaseY + p * 29;
+
+        cv::Mat tone;
+        cv::cvtColor(tones[p], tone, cv::COLOR_GRAY2BGR);
+        cv::extractChannel(ditherPreprocessed(tone, plateParams), plates[p], 0);
+    }
+    return plates;
+}
+
+std::array<int, 3> getPixelFormatLevels(PixelFormat format) {
+    switch (format) {
+        case PixelFormat::RGB332: return {4, 8, 8};
+        case PixelFormat::RGBA4444: return {16, 16, 16};
+        case PixelFormat::RGB565:
+        default: return {32, 64, 32};
+    }
+}
+
+bool hasChannelOverrides(const Parameters& params) {
+    for (const auto& channel : params.channelOverrides) {
+        if (channel.enabled) return true;
+    }
+    return false;
+}
+
+// Dither each plane as its own grayscale image, with that plane's algorithm,
+// strength and threshold, e.g. error diffusion on luma and Bayer on chroma
+static cv::Mat ditherChannels(const cv::Mat& preprocessed, const Parameters& params,
+                              const std::array<int, 3>& levels) {
+    cv::Mat converted = preprocessed;
+    if (params.channelSpace == ChannelSpace::YCRCB) {
+        cv::cvtColor(roundTo8Bit(preprocessed), converted, cv::COLOR_BGR2YCrCb);
+    }
+    std::vector<cv::Mat> planes;
+    cv::split(converted, planes);
+
+    Parameters planeParams = params;
+    planeParams.colorMode = ColorMode::RGB;
+    planeParams.paletteMode = PaletteMode::CUSTOM;
+    planeParams.protectedColors.clear();
+    planeParams.highlightProtect = 1.0f;    // Only whole pixels are protected, not single planes
+    planeParams.shadowProtect = 0.0f;
+    planeParams.linearize = false;
+    planeParams.customPalette.clear();
+    planeParams.channelOverrides = {};
+
+    for (int c = 0; c < 3; ++c) {
+        const ChannelOverride& channel = params.channelOverrides[c];
+        Parameters current = planeParams;
+        current.seed = params.seed + c;  // Keep noise-based planes from lining up
+
+        // Evenly spaced levels across the full 0-255 range
+        int planeLevels = std::clamp(levels[c], 2, 256);
+        float step = 255.0f / (planeLevels - 1);
+        for (int i = 0; i < planeLevels; ++i) {
+            uchar value = static_cast<uchar>(std::round(i * step));
+            current.customPalette.push_back(cv::Vec3b(value, value, value));
+        }
+        float threshold = 0.5f;
+        if (channel.enabled) {
+            current.algorithm = channel.algorithm;
+            current.plugin.clear();
+            current.strength = channel.strength;
+            bool thresholdless = channel.algorithm == Algorithm::POSTERIZE || channel.algorithm == Algorithm::STIPPLE;
+            threshold = thresholdless ? 0.5f : channel.threshold;
+        }
+
+        // Moving the threshold is the same as shifting the plane the other way
+        cv::Mat plane;
+        planes[c].convertTo(plane, -1, 1.0, (0.5f - threshold) * step);
+        cv::cvtColor(plane, plane, cv::COLOR_GRAY2BGR);
+        cv::extractChannel(ditherPreprocessed(plane, current), planes[c], 0);
+    }
+
+    cv::Mat result;
+    cv::merge(planes, result);
+    if (params.channelSpace == ChannelSpace::YCRCB) {
+        cv::cvtColor(result, result, cv::COLOR_YCrCb2BGR);
+    }
+    return result;
+}
+
+// Pixel-art look: shrink by the factor, dither, then blow every pixel up to a
+// factor x factor block (cropped back to the original size)
+static cv::Size pixelateSize(const cv::Size& size, int factor) {
+    return cv::Size((size.width + factor - 1) / factor, (size.height + factor - 1) / factor);
+}
+
+static cv::Mat shrinkForPixelate(const cv::Mat& input, int factor) {
+    cv::Mat small;
+    cv::resize(input, small, pixelateSize(input.size(), factor), 0, 0, cv::INTER_AREA);
+    return small;
+}
+
+cv::Size getWorkingSize(const cv::Size& size, const Parameters& params) {
+    return params.pixelate > 1 ? pixelateSize(size, params.pixelate) : size;
+}
+
+static cv::Mat growFromPixelate(const cv::Mat& small, int factor, const cv::Size& size) {
+    cv::Mat blocks;
+    cv::resize(small, blocks, cv::Size(small.cols * factor, small.rows * factor), 0, 0, cv::INTER_NEAREST);
+    return blocks(cv::Rect(0, 0, size.width, size.height)).clone();
+}
+
+std::vector<cv::Mat> ditherSeparations(const cv::Mat& input, const Parameters& inputParams) {
+    if (inputParams.screenLpi > 0.0f && usesScreenRuling(inputParams)) {
+        Parameters params = inputParams;
+        applyScreenRuling(params);
+        params.screenLpi = 0.0f;
+        return ditherSeparations(input, params);
+    }
+
+    if (inputParams.pixelate > 1) {
+        Parameters params = inputParams;
+        params.pixelate = 1;
+        std::vector<cv::Mat> plates = ditherSeparations(shrinkForPixelate(input, inputParams.pixelate), params);
+        for (auto& plate : plates) plate = growFromPixelate(plate, inputParams.pixelate, input.size());
+        return plates;
+    }
+
+    cv::Mat preprocessed = preprocessImage(input, inputParams);
+
+    Parameters params = inputParams;
+    resolveAutoThreshold(params, preprocessed);
+    cv::Mat clipped = clippedTones(preprocessed, params);
+    applyThreshold(preprocessed, params);
+    snapClippedTones(preprocessed, clipped);
+    std::vector<cv::Mat> plates = separatePreprocessed(roundTo8Bit(preprocessed), params);
+
+    // Protected highlights carry no ink, protected shadows black ink only
+    if (!clipped.empty()) {
+        for (int p = 0; p < 4; ++p) plates[p].setTo(255, clipped == 255);
+        for (int p = 0; p < 3; ++p) plates[p].setTo(255, clipped == 128);
+        plates[3].setTo(0, clipped == 128);
+    }
+    return plates;
+}
+
+cv::Mat combineSeparations(const std::vector<cv::Mat>& plates) {
+    if (plates.size() != 4 || plates[0].empty()) return cv::Mat();
+
+    // Each ink absorbs its complementary channel: cyan removes red, magenta
+    // green, yellow blue, and black all three
+    cv::Mat result(plates[0].size(), CV_8UC3);
+    for (int y = 0; y < result.rows; ++y) {
+        for (int x = 0; x < result.cols; ++x) {
+            float k = plates[3].at<uchar>(y, x) / 255.0f;
+            result.at<cv::Vec3b>(y, x) = cv::Vec3b(
+                cv::saturate_cast<uchar>(plates[2].at<uchar>(y, x) * k),
+                cv::saturate_cast<uchar>(plates[1].at<uchar>(y, x) * k),
+                cv::saturate_cast<uchar>(plates[0].at<uchar>(y, x) * k)
+            );
+        }
+    }
+    return result;
+}
+
+// Run the extra stages on a result. Stages without settings of their own
+// dither with main; the others have their pattern phase moved by offset, for tiles
+static cv::Mat runStages(const cv::Mat& input, const Parameters& main, const cv::Point& offset) {
+    cv::Mat result = input;
+    for (const auto& stage : main.stages) {
+        cv::Mat output;
+        if (stage.type == StageType::DITHER) {
+            Parameters params = stage.params ? *stage.params : main;
+            params.stages.clear();
+            if (stage.params) {
+                params.phaseX += offset.x;
+                params.phaseY += offset.y;
+            }
+            output = ditherImage(result, params);
+        } else if (stage.type == StageType::BLUR) {
+            cv::GaussianBlur(result, output, cv::Size(), std::max(stage.amount, 0.1f));
+        } else {
+            cv::Mat blurred;
+            cv::GaussianBlur(result, blurred, cv::Size(), 1.0);
+            cv::addWeighted(result, 1.0 + stage.amount, blurred, -stage.amount, 0.0, output);
+        }
+
+        float mix = std::clamp(stage.mix, 0.0f, 1.0f);
+        if (mix < 1.0f) cv::addWeighted(output, mix, result, 1.0f - mix, 0.0, output);
+        result = output;
+    }
+    return result;
+}
+
+// Main dithering function dispatcher
+cv::Mat ditherImage(const cv::Mat& input, const Parameters& inputParams, BandCallback onBand) {
+    if (inputParams.screenLpi > 0.0f && usesScreenRuling(inputParams)) {
+        Parameters params = inputParams;
+        applyScreenRuling(params);
+        params.screenLpi = 0.0f;
+        return ditherImage(input, params, onBand);
+    }
+
+    if (!inputParams.stages.empty()) {
+        Parameters params = inputParams;
+        params.stages.clear();
+        cv::Mat result = ditherImage(input, params, onBand);
+        params.stages = inputParams.stages;
+        return runStages(result, params, cv::Point());
+    }
+
+    if (inputParams.pixelate > 1) {
+        Parameters params = inputParams;
+        params.pixelate = 1;
+        cv::Mat small = ditherImage(shrinkForPixelate(input, inputParams.pixelate), params);
+        return growFromPixelate(small, inputParams.pixelate, input.size());
+    }
+
+    cv::Mat preprocessed = preprocessImage(input, inputParams);
+
+    Parameters params = inputParams;
+    resolveAdaptivePalette(params, preprocessed);
+    resolveAutoThreshold(params, preprocessed);
+    cv::Mat source = params.blend > 0.0f ? preprocessed.clone() : cv::Mat();
+    cv::Mat clipped = clippedTones(preprocessed, params);
+    applyThreshold(preprocessed, params);
+    snapClippedTones(preprocessed, clipped);
+    restoreProtectedPixels(preprocessed, input, protectedPixels(input, params));
+
+    cv::Mat result = ditherPreprocessed(preprocessed, params, onBand);
+    fillClippedTones(result, clipped, params);
+    blendWithSource(result, source, params.blend);
+    return result;
+}
+
+cv::Mat ditherWithSnapshots(const cv::Mat& input, const Parameters& params, int interval,
+                            const SnapshotCallback& onSnapshot) {
+    DiffusionRecorder recording;
+    // Cleared even if dithering throws, since the thread may dither again
+    struct RecorderGuard {
+        ~RecorderGuard() { recorder = nullptr; }
+    } guard;
+    recorder = &recording;
+    cv::Mat result = ditherImage(input, params);
+    recorder = nullptr;
+    if (result.empty()) return result;
+
+    cv::Mat order = recording.order;
+    int steps = recording.steps;
+    if (steps == 0) {
+        order.create(result.size(), CV_32S);
+        for (int y = 0; y < order.rows; ++y) {
+            for (int x = 0; x < order.cols; ++x) order.at<int>(y, x) = steps++;
+        }
+    } else if (order.size() != result.size()) {
+        cv::resize(order, order, result.size(), 0, 0, cv::INTER_NEAREST);
+    }
+
+    // Pixels by the snapshot they first show in; ones never visited keep
+    // their color, so they show from the start
+    interval = std::max(1, interval);
+    int snapshots = (steps + interval - 1) / interval;
+    std::vector<std::vector<int>> finished(snapshots + 1);
+    for (int i = 0; i < static_cast<int>(order.total()); ++i) {
+        int step = order.at<int>(i);
+        finished[step < 0 ? 0 : step / interval + 1].push_back(i);
+    }
+
+    cv::Mat frame = roundTo8Bit(input);
+    if (frame.size() != result.size()) cv::resize(frame, frame, result.size(), 0, 0, cv::INTER_AREA);
+    for (const auto& pixels : finished) {
+        for (int i : pixels) frame.at<cv::Vec3b>(i) = result.at<cv::Vec3b>(i);
+        if (onSnapshot && !onSnapshot(frame)) return cv::Mat();
+    }
+    return result;
+}
+
+bool usesScreenRuling(const Parameters& params) {
+    return params.algorithm == Algorithm::HALFTONE || (getBayerSize(params.algorithm) > 0 && params.bayerClustered);
+}
+
+void applyScreenRuling(Parameters& params, std::vector<std::string>* warnings) {
+    if (params.screenLpi <= 0.0f || !usesScreenRuling(params)) return;
+    auto warn = [warnings](const char* format, auto... values) {
+        char text[256];
+        std::snprintf(text, sizeof(text), format, values...);
+        if (warnings) warnings->push_back(text);
+    };
+
+    // Cell size in printed dots; pixelated images have pixelate dots per pixel
+    float dpi = std::clamp(params.printDpi, 36.0f, 2400.0f);
+    int pixelate = std::max(1, params.pixelate);
+    float cell = dpi / params.screenLpi;
+    int levels = 0;
+    if (params.algorithm == Algorithm::HALFTONE) {
+        params.halftoneCellSize = std::clamp(cell / pixelate, 2.0f, 256.0f);
+        levels = static_cast<int>(cell * cell) + 1;
+    } else {
+        int size = std::clamp(params.bayerSize, 2, MAX_BAYER_SIZE);
+        int scale = std::clamp(static_cast<int>(std::lround(cell / (size * pixelate))), 1, MAX_PATTERN_SCALE);
+        params.ditherScale = static_cast<float>(scale);
+        levels = size * size + 1;       // Scaling enlarges the dot steps, it doesn't add any
+        float actual = dpi / (size * scale * pixelate);
+        if (std::abs(actual - params.screenLpi) > 0.05f * params.screenLpi) {
+            warn("%.0f lpi at %.0f dpi rounds to %.0f lpi: clustered Bayer cells are whole multiples of the "
+                 "%d pixel matrix", params.screenLpi, dpi, actual, size * pixelate);
+        }
+        cell = static_cast<float>(size * scale * pixelate);
+    }
+
+    if (cell < 2.0f) {
+        warn("%.0f lpi at %.0f dpi is under 2 dots per cell and can't be printed; lower the LPI or raise the DPI",
+             params.screenLpi, dpi);
+    } else if (levels < 33) {
+        warn("%.0f lpi at %.0f dpi leaves %d gray levels per cell, so gradients will band; lower the LPI or "
+             "raise the DPI", params.screenLpi, dpi, levels);
+    }
+    if (params.screenLpi > 200.0f) {
+        warn("%.0f lpi is finer than most presses hold (about 200 lpi; laser printers 85-133)", params.screenLpi);
+    }
+}
+
+float computeAutoThreshold(const cv::Mat& input, const Parameters& params) {
+    return computeOtsuThreshold(preprocessImage(input, params));
+}
+
+cv::Mat ditherAlpha(const cv::Mat& alpha, const Parameters& params) {
+    if (alpha.empty()) return cv::Mat();
+    if (params.pixelate > 1) {
+        Parameters alphaParams = params;
+        alphaParams.pixelate = 1;
+        cv::Mat small = ditherAlpha(shrinkForPixelate(alpha, params.pixelate), alphaParams);
+        return growFromPixelate(small, params.pixelate, alpha.size());
+    }
+
+    // Coverage is dithered as is: no tone adjustments, just transparent and opaque
+    Parameters alphaParams = params;
+    alphaParams.colorMode = ColorMode::RGB;
+    alphaParams.paletteMode = PaletteMode::CUSTOM;
+    alphaParams.customPalette = {cv::Vec3b(0, 0, 0), cv::Vec3b(255, 255, 255)};
+    alphaParams.protectedColors.clear();
+    alphaParams.highlightProtect = 1.0f;
+    alphaParams.shadowProtect = 0.0f;
+    alphaParams.linearize = false;
+    alphaParams.workingSpace = WorkingSpace::RGB;
+    alphaParams.channelOverrides = {};
+
+    cv::Mat coverage;
+    cv::cvtColor(alpha, coverage, cv::COLOR_GRAY2BGR);
+    cv::Mat result;
+    cv::extractChannel(ditherPreprocessed(coverage, alphaParams), result, 0);
+    return result;
+}
+
+cv::Mat applyMask(const cv::Mat& dithered, const cv::Mat& original, const cv::Mat& mask) {
+    if (dithered.empty() || original.empty() || mask.empty()) return dithered;
+
+    cv::Mat source = roundTo8Bit(original);
+    if (source.size() != dithered.size()) {
+        cv::resize(source, source, dithered.size(), 0, 0, cv::INTER_AREA);
+    }
+    cv::Mat weights = mask;
+    if (weights.channels() == 3) cv::cvtColor(weights, weights, cv::COLOR_BGR2GRAY);
+    if (weights.size() != dithered.size()) {
+        cv::resize(weights, weights, dithered.size(), 0, 0, cv::INTER_LINEAR);
+    }
+
+    cv::Mat result = dithered.clone();
+    for (int y = 0; y < result.rows; ++y) {
+        cv::Vec3b* out = result.ptr<cv::Vec3b>(y);
+        const cv::Vec3b* in = source.ptr<cv::Vec3b>(y);
+        const uchar* weight = weights.ptr<uchar>(y);
+        for (int x = 0; x < result.cols; ++x) {
+            if (weight[x] == 255) continue;
+            float w = weight[x] / 255.0f;
+            for (int c = 0; c < 3; ++c) {
+                out[x][c] = cv::saturate_cast<uchar>(w * out[x][c] + (1.0f - w) * in[x][c]);
+            }
+        }
+    }
+    return result;
+}
+
+cv::Mat maskFromRects(const cv::Size& size, const std::vector<cv::Rect>& rects) {
+    cv::Mat mask = cv::Mat::zeros(size, CV_8U);
+    for (const auto& rect : rects) {
+        cv::Rect clipped = rect & cv::Rect(0, 0, size.width, size.height);
+        if (!clipped.empty()) mask(clipped).setTo(255);
+    }
+    return mask;
+}
+
+// Dither one tile of a larger image, processing a margin of surrounding context
+// so that tiles rendered separately line up without visible seams
+cv::Mat ditherTile(const cv::Mat& input, const cv::Rect& tile, const Parameters& inputParams,
+                   int contextMargin) {
+    if (inputParams.screenLpi > 0.0f && usesScreenRuling(inputParams)) {
+        Parameters params = inputParams;
+        applyScreenRuling(params);
+        params.screenLpi = 0.0f;
+        return ditherTile(input, tile, params, contextMargin);
+    }
+
+    cv::Rect bounds(0, 0, input.cols, input.rows);
+    cv::Rect region = tile & bounds;
+    if (region.empty()) return cv::Mat();
+
+    // Pixelated output is rendered whole at the reduced size, then cut
+    if (inputParams.pixelate > 1) {
+        return ditherImage(input, inputParams)(region).clone();
+    }
+
+    int margin = std::max(0, contextMargin);
+    cv::Rect context = cv::Rect(region.x - margin, region.y - margin,
+                                region.width + 2 * margin, region.height + 2 * margin) & bounds;
+
+    // Whole-image statistics (clip points, adaptive palette) come from a downscaled
+    // overview so that every tile agrees on them
+    const double maxOverviewPixels = 512.0 * 512.0;
+    cv::Mat overview = input;
+    if (input.total() > maxOverviewPixels) {
+        double scale = std::sqrt(maxOverviewPixels / input.total());
+        cv::resize(input, overview, cv::Size(), scale, scale, cv::INTER_AREA);
+    }
+    cv::Mat adjustedOverview = adjustImage(overview, inputParams);
+    std::pair<int, int> clipPoints = computeClipPoints(adjustedOverview, inputParams);
+
+    Parameters params = inputParams;
+    if (params.paletteMode == PaletteMode::ADAPTIVE || params.autoThreshold) {
+        applyClipPoints(adjustedOverview, clipPoints);
+        compensateDotGain(adjustedOverview, params.dotGain);
+        resolveAdaptivePalette(params, adjustedOverview);
+        resolveAutoThreshold(params, adjustedOverview);
+    }
+
+    // Keep threshold patterns anchored to image coordinates rather than the tile
+    params.phaseX += context.x;
+    params.phaseY += context.y;
+
+    cv::Mat preprocessed = adjustImage(input(context), params);
+    applyClipPoints(preprocessed, clipPoints);
+    compensateDotGain(preprocessed, params.dotGain);
+    cv::Mat source = params.blend > 0.0f ? preprocessed.clone() : cv::Mat();
+    cv::Mat clipped = clippedTones(preprocessed, params);
+    applyThreshold(preprocessed, params);
+    snapClippedTones(preprocessed, clipped);
+    restoreProtectedPixels(preprocessed, input(context), protectedPixels(input(context), params));
+
+    cv::Mat result = ditherPreprocessed(preprocessed, params);
+    fillClippedTones(result, clipped, params);
+    blendWithSource(result, source, params.blend);
+    result = runStages(result, params, context.tl());
+    cv::Rect inner(region.x - context.x, region.y - context.y, region.width, region.height);
+    return result(inner).clone();
+}
+
+// Per pixel: the float working copy and error buffer (12 bytes each) for error
+// diffusion, plus a few 8-bit copies along the way (rounding, result, colour
+// space conversions)
+size_t estimateMemory(const cv::Size& size, const Parameters& params) {
+    size_t bytesPerPixel = isErrorDiffusion(params.algorithm) ? 40 : 20;
+    int factor = std::max(1, params.pixelate);
+    return static_cast<size_t>(size.width / factor + 1) * (size.height / factor + 1) * bytesPerPixel;
+}
+
+std::vector<BenchmarkResult> benchmarkAlgorithms(const cv::Mat& input, const Parameters& params,
+                                                 std::vector<Algorithm> algorithms, int runs,
+                                                 ProgressCallback progress) {
+    if (algorithms.empty()) {
+        for (const auto& info : getAlgorithmLibrary()) algorithms.push_back(info.algorithm);
+    }
+
+    std::vector<BenchmarkResult> results;
+    for (size_t i = 0; i < algorithms.size(); ++i) {
+        float fraction = static_cast<float>(i) / algorithms.size();
+        if (progress && !progress(fraction, getAlgorithmName(algorithms[i]))) return results;
+
+        Parameters current = params;
+        current.algorithm = algorithms[i];
+        current.plugin.clear();
+        BenchmarkResult result;
+        result.algorithm = algorithms[i];
+        result.memory = estimateMemory(input.size(), current);
+        result.seconds = std::numeric_limits<double>::max();
+        for (int run = 0; run < std::max(1, runs); ++run) {
+            auto start = std::chrono::steady_clock::now();
+            ditherImage(input, current);
+            result.seconds = std::min(result.seconds,
+                std::chrono::duration<double>(std::chrono::steady_clock::now() - start).count());
+        }
+        results.push_back(result);
+    }
+    if (progress) progress(1.0f, "Done");
+    return results;
+}
+
+std::vector<AlgorithmThumbnail> renderAlgorithmThumbnails(const cv::Mat& input, const Parameters& params,
+                                                          int size, std::vector<Algorithm> algorithms,
+                                                          ProgressCallback progress) {
+    if (algorithms.empty()) {
+        for (const auto& info : getAlgorithmLibrary()) algorithms.push_back(info.algorithm);
+    }
+
+    std::vector<AlgorithmThumbnail> thumbnails;
+    if (input.empty()) return thumbnails;
+
+    cv::Mat scaled = input;
+    double scale = static_cast<double>(std::max(1, size)) / std::max(input.cols, input.rows);
+    if (scale < 1.0) {
+        cv::resize(input, scaled, cv::Size(), scale, scale, cv::INTER_AREA);
+    }
+
+    for (size_t i = 0; i < algorithms.size(); ++i) {
+        float fraction = static_cast<float>(i) / algorithms.size();
+        if (progress && !progress(fraction, getAlgorithmName(algorithms[i]))) return thumbnails;
+
+        Parameters current = getRecommendedParameters(params, algorithms[i]);
+        // The Bayer algorithms differ only in matrix size, which the GUI sets on selection
+        if (getBayerSize(algorithms[i]) > 0) current.bayerSize = getBayerSize(algorithms[i]);
+        thumbnails.push_back({algorithms[i], ditherImage(scaled, current)});
+    }
+    if (progress) progress(1.0f, "Done");
+    return thumbnails;
+}
+
+cv::Mat composeContactSheet(const std::vector<AlgorithmThumbnail>& thumbnails, int columns) {
+    if (thumbnails.empty()) return cv::Mat();
+
+    const int spacing = 8;
+    const int labelHeight = 22;
+    int count = static_cast<int>(thumbnails.size());
+    if (columns <= 0) columns = static_cast<int>(std::ceil(std::sqrt(static_cast<double>(count))));
+    columns = std::min(columns, count);
+    int rows = (count + columns - 1) / columns;
+
+    int cellWidth = 0, cellHeight = 0;
+    for (const auto& thumbnail : thumbnails) {
+        cellWidth = std::max(cellWidth, thumbnail.image.cols);
+        cellHeight = std::max(cellHeight, thumbnail.image.rows);
+    }
+
+    cv::Mat sheet(spacing + rows * (cellHeight + labelHeight + spacing),
+                  spacing + columns * (cellWidth + spacing), CV_8UC3, cv::Scalar(32, 32, 32));
+    for (int i = 0; i < count; ++i) {
+        const cv::Mat& image = thumbnails[i].image;
+        int x = spacing + (i % columns) * (cellWidth + spacing);
+        int y = spacing + (i / columns) * (cellHeight + labelHeight + spacing);
+        if (!image.empty()) {
+            image.copyTo(sheet(cv::Rect(x + (cellWidth - image.cols) / 2, y, image.cols, image.rows)));
+        }
+
+        // Shrink long names to fit under the thumbnail
+        std::string label = getAlgorithmName(thumbnails[i].algorithm);
+        double fontScale = 0.45;
+        int baseline = 0;
+        cv::Size textSize = cv::getTextSize(label, cv::FONT_HERSHEY_SIMPLEX, fontScale, 1, &baseline);
+        if (textSize.width > cellWidth) {
+            fontScale *= static_cast<double>(cellWidth) / textSize.width;
+            textSize = cv::getTextSize(label, cv::FONT_HERSHEY_SIMPLEX, fontScale, 1, &baseline);
+        }
+        cv::Point origin(x + (cellWidth - textSize.width) / 2,
+                         y + cellHeight + (labelHeight + textSize.height) / 2);
+        cv::putText(sheet, label, origin, cv::FONT_HERSHEY_SIMPLEX, fontScale,
+                    cv::Scalar(230, 230, 230), 1, cv::LINE_AA);
+    }
+    return sheet;
+}
+
+// Each band starts its error diffusion afresh in the context rows above it, so
+// its dither pattern doesn't line up with the band above's. Over the rows both
+// rendered, pixels switch from the band above to this one along an 8x8 Bayer
+// ramp, so the two patterns interleave rather than meet along a line
+static void fadeInBand(cv::Mat& band, const cv::Mat& above, int top) {
+    static const cv::Mat thresholds = generateBayerMatrix(8);
+    int rows = std::min(above.rows, band.rows);
+    for (int y = 0; y < rows; ++y) {
+        float weight = (y + 0.5f) / above.rows;
+        for (int x = 0; x < band.cols; ++x) {
+            if (weight <= thresholds.at<float>((top + y) % 8, x % 8)) {
+                band.at<cv::Vec3b>(y, x) = above.at<cv::Vec3b>(y, x);
+            }
+        }
+    }
+}
+
+cv::Mat ditherWithinMemory(const cv::Mat& input, const Parameters& params, size_t memoryLimit,
+                           ProgressCallback progress, BandCallback onBand) {
+    auto report = [&progress](float fraction, const std::string& stage) {
+        return !progress || progress(fraction, stage);
+    };
+
+    // Pixelated output is already dithered at the reduced size, and stipple dots
+    // are relaxed on a map of bounded size over the whole image
+    if (memoryLimit == 0 || params.pixelate > 1 || params.algorithm == Algorithm::STIPPLE ||
+        estimateMemory(input.size(), params) <= memoryLimit) {
+        if (!report(0.0f, "Dithering")) return cv::Mat();
+        RenderProgress rowProgress;
+        rowProgress.callback = progress;
+        rowProgress.rows = input.rows;
+        RenderScope scope(&rowProgress);
+        cv::Mat result = ditherImage(input, params, onBand);
+        if (rowProgress.cancelled) return cv::Mat();
+        report(1.0f, "Done");
+        return result;
+    }
+
+    // Enough context for error to build up and for local threshold windows
+    const int margin = 64;
+    size_t perRow = estimateMemory(cv::Size(input.cols, 1), params);
+    int bandRows = static_cast<int>(std::min<size_t>(memoryLimit / std::max<size_t>(perRow, 1), input.rows));
+    bandRows = std::max(16, bandRows - 3 * margin);
+
+    int bands = (input.rows + bandRows - 1) / bandRows;
+    cv::Mat result(input.size(), CV_8UC3);
+    cv::Mat below;      // The band above's rendering of the first rows of this one
+    for (int i = 0; i < bands; ++i) {
+        std::string stage = "Band " + std::to_string(i + 1) + " of " + std::to_string(bands);
+        if (!report(static_cast<float>(i) / bands, stage)) return cv::Mat();
+
+        cv::Rect band(0, i * bandRows, input.cols, std::min(bandRows, input.rows - i * bandRows));
+        // Rendered margin rows past its end, for the next band to fade in over
+        cv::Rect rendered = cv::Rect(band.x, band.y, band.width, band.height + margin) &
+                            cv::Rect(0, 0, input.cols, input.rows);
+        RenderProgress rowProgress;
+        rowProgress.callback = [&](float fraction, const std::string&) {
+            return report((i + fraction) / bands, stage);
+        };
+        rowProgress.rows = rendered.height + 2 * margin;
+        RenderScope scope(&rowProgress);
+        cv::Mat tile = ditherTile(input, rendered, params, margin);
+        if (rowProgress.cancelled) return cv::Mat();
+        if (!below.empty()) fadeInBand(tile, below, band.y);
+        below = tile.rowRange(band.height, tile.rows).clone();
+        tile.rowRange(0, band.height).copyTo(result(band));
+        if (onBand) onBand(result(band), band.y);
     }
+    report(1.0f, "Done");
+    return result;
+}
+
+// Column that error sent to x lands on, or -1 when it's dropped
+static int edgeColumn(int x, int cols, ErrorEdge edge) {
+    if (x >= 0 && x < cols) return x;
+    switch (edge) {
+        case ErrorEdge::REFLECT: return cv::borderInterpolate(x, cols, cv::BORDER_REFLECT);
+        case ErrorEdge::WRAP: return cv::borderInterpolate(x, cols, cv::BORDER_WRAP);
+        default: return -1;
+    }
+}
+
+// Pixel plus carried error, bounded. Clamping to 0-255 drops the error that
+// pushes past black or white, which washes out colors at hard edges; without
+// it that error rings on to the neighbors, up to one full range beyond either
+// end so strong kernels can't run away
+static cv::Vec3f boundError(const cv::Vec3f& value, const Parameters& params) {
+    float low = params.clampError ? 0.0f : -255.0f;
+    float high = params.clampError ? 255.0f : 510.0f;
+    return cv::Vec3f(std::clamp(value[0], low, high), std::clamp(value[1], low, high),
+                     std::clamp(value[2], low, high));
+}
+
+// The 8-bit color to quantize for a bounded pixel
+static cv::Vec3b toPixel(const cv::Vec3f& value) {
+    return cv::Vec3b(static_cast<uchar>(std::clamp(value[0], 0.0f, 255.0f)),
+                     static_cast<uchar>(std::clamp(value[1], 0.0f, 255.0f)),
+                     static_cast<uchar>(std::clamp(value[2], 0.0f, 255.0f)));
 }
 
 // Error diffusion helper
 void diffuseError(cv::Mat& errors, int x, int y, const cv::Vec3f& error,
                   const std::vector<std::pair<int, int>>& offsets,
-                  const std::vector<float>& weights, float strength, bool serpentine) {
+                  const std::vector<float>& weights, float strength, bool serpentine, ErrorEdge edge) {
     int direction = (serpentine && (y % 2 == 1)) ? -1 : 1;
 
     for (size_t i = 0; i < offsets.size(); ++i) {
-        int nx = x + offsets[i].first * direction;
+        int nx = edgeColumn(x + offsets[i].first * direction, errors.cols, edge);
         int ny = y + offsets[i].second;
 
-        if (nx >= 0 && nx < errors.cols && ny >= 0 && ny < errors.rows) {
+        if (nx >= 0 && ny >= 0 && ny < errors.rows) {
             errors.at<cv::Vec3f>(ny, nx) += error * weights[i] * strength;
         }
     }
 }
 
+// Farthest column any error goes from the pixel it comes from
+static int kernelReach(const std::vector<std::pair<int, int>>& offsets) {
+    int reach = 0;
+    for (const auto& offset : offsets) reach = std::max(reach, std::abs(offset.first));
+    return reach;
+}
+
+// Where a row of diffuseRows has got to, and what it waits on
+struct RowFront {
+    std::atomic<int>* above;    // Pixels the row above has finished, null on the first row
+    std::atomic<int>* own;
+    int cols;
+    int lag;                    // How far ahead the row above has to stay
+    int ready = 0;              // Pixels of the row above known to be finished
+
+    // Called before each pixel with its position in the row's scan order: says
+    // the ones before it are done, then waits until the row above is clear
+    void at(int position) {
+        own->store(position, std::memory_order_release);
+        int needed = std::min(cols, position + lag);
+        while (above && ready < needed) {
+            ready = above->load(std::memory_order_acquire);
+            if (ready < needed) std::this_thread::yield();
+        }
+    }
+};
+
+// Runs row(y, quantize, front) over every row as a wavefront: a row starts once
+// the one above is more than twice the kernel's reach ahead, so neither sends
+// error where the other is working, and each pixel gets the same error, added
+// in the same order, as in one pass. Rows are spread over the worker threads,
+// each with its own copy of the quantizer. A serpentine row runs the other way
+// from the row above and wrapped error lands at the start of the row below, so
+// both need the row above finished, and run one row at a time, as do recorded
+// runs. Finished blocks of diffusionBandRows rows go to onBand, in order
+template <typename Row>
+static void diffuseRows(const cv::Mat& result, Quantizer& quantizer, const Parameters& params, int reach,
+                        bool serpentine, const BandCallback& onBand, Row row) {
+    const int rows = result.rows;
+    std::vector<std::atomic<int>> finished(rows);
+    for (auto& count : finished) count.store(0);
+    std::atomic<int> next{0};
+    std::atomic<bool> stopped{false};
+    std::mutex publishing;
+    int published = 0;
+
+    auto publish = [&]() {
+        if (!onBand || stopped) return;
+        std::lock_guard<std::mutex> lock(publishing);
+        while (published < rows) {
+            int end = std::min(published + diffusionBandRows, rows);
+            if (finished[end - 1].load(std::memory_order_acquire) < result.cols) break;
+            onBand(result.rowRange(published, end), published);
+            published = end;
+        }
+    };
+
+    // A cancelled render still marks each row it takes as finished, so no row
+    // below waits on it forever
+    RenderProgress* progress = rendering;
+    auto work = [&](Quantizer& quantize) {
+        RenderScope scope(progress);
+        for (int y = next++; y < rows; y = next++) {
+            if (stopped || !nextRow()) {
+                stopped = true;
+            } else {
+                if (params.rounding == RoundingMode::STOCHASTIC) quantize.rng.seed(params.seed + y);
+                RowFront front{y > 0 ? &finished[y - 1] : nullptr, &finished[y], result.cols, 2 * reach + 1};
+                row(y, quantize, front);
+            }
+            finished[y].store(result.cols, std::memory_order_release);
+            publish();
+        }
+    };
+
+    int workers = std::min(cv::getNumThreads(), rows);
+    if (serpentine || params.errorEdge == ErrorEdge::WRAP || recorder || workers < 2 ||
+        result.total() < static_cast<size_t>(parallelDiffusionPixels)) {
+        work(quantizer);
+        return;
+    }
+    cv::parallel_for_(cv::Range(0, workers), [&](const cv::Range& range) {
+        for (int i = range.start; i < range.end; ++i) {
+            Quantizer local = quantizer;
+            work(local);
+        }
+    }, workers);
+}
+
 // Floyd-Steinberg dithering
-cv::Mat floydSteinberg(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat floydSteinberg(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
     std::vector<float> weights = {7.0f/16.0f, 3.0f/16.0f, 5.0f/16.0f, 1.0f/16.0f};
+    bool serpentine = params.serpentine > 0.5f;
 
-    for (int y = 0; y < input.rows; ++y) {
-        bool reverse = (params.serpentine > 0.5f) && (y % 2 == 1);
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
+        bool reverse = serpentine && (y % 2 == 1);
         int start = reverse ? input.cols - 1 : 0;
         int end = reverse ? -1 : input.cols;
         int step = reverse ? -1 : 1;
 
         for (int x = start; x != end; x += step) {
+            front.at(reverse ? input.cols - 1 - x : x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, params.serpentine > 0.5f);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, serpentine, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), serpentine, onBand, row);
 
     return result;
 }
 
 // Atkinson dithering (used in early Mac systems)
-cv::Mat atkinson(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat atkinson(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {{1, 0}, {2, 0}, {-1, 1}, {0, 1}, {1, 1}, {0, 2}};
     std::vector<float> weights(6, 1.0f/8.0f);
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Jarvis-Judice-Ninke dithering
-cv::Mat jarvisJudiceNinke(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat jarvisJudiceNinke(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0}, {2, 0},
@@ -210,41 +1925,36 @@ cv::Mat jarvisJudiceNinke(const cv::Mat& input, const Parameters& params) {
         1.0f/48.0f, 3.0f/48.0f, 5.0f/48.0f, 3.0f/48.0f, 1.0f/48.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Stucki dithering
-cv::Mat stucki(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat stucki(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0}, {2, 0},
@@ -257,41 +1967,36 @@ cv::Mat stucki(const cv::Mat& input, const Parameters& params) {
         1.0f/42.0f, 2.0f/42.0f, 4.0f/42.0f, 2.0f/42.0f, 1.0f/42.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Burkes dithering
-cv::Mat burkes(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat burkes(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0}, {2, 0},
@@ -302,41 +2007,36 @@ cv::Mat burkes(const cv::Mat& input, const Parameters& params) {
         2.0f/32.0f, 4.0f/32.0f, 8.0f/32.0f, 4.0f/32.0f, 2.0f/32.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Sierra dithering
-cv::Mat sierra(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat sierra(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0}, {2, 0},
@@ -349,41 +2049,36 @@ cv::Mat sierra(const cv::Mat& input, const Parameters& params) {
         2.0f/32.0f, 3.0f/32.0f, 2.0f/32.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Sierra Two-Row dithering
-cv::Mat sierraTwo(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat sierraTwo(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0}, {2, 0},
@@ -394,41 +2089,36 @@ cv::Mat sierraTwo(const cv::Mat& input, const Parameters& params) {
         1.0f/16.0f, 2.0f/16.0f, 3.0f/16.0f, 2.0f/16.0f, 1.0f/16.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Sierra Lite dithering
-cv::Mat sierraLite(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat sierraLite(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {
         {1, 0},
@@ -439,125 +2129,281 @@ cv::Mat sierraLite(const cv::Mat& input, const Parameters& params) {
         1.0f/4.0f, 1.0f/4.0f
     };
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
-// Generate Bayer matrix
-cv::Mat generateBayerMatrix(int size) {
-    if (size == 2) {
-        cv::Mat bayer = (cv::Mat_<float>(2, 2) << 0, 2, 3, 1);
-        return bayer / 4.0f;
+// Bayer's recursion, as ranks 0 .. size^2 - 1. Any product of 2s and 3s
+// works: each cell of the 2x2 or 3x3 base is filled with a copy of the
+// matrix for the remaining factor. Other factors (5, 7, ...) start from a
+// small void-and-cluster matrix, which is dispersed in the same way
+static cv::Mat bayerRanks(int size) {
+    if (size == 1) return cv::Mat::zeros(1, 1, CV_32F);
+
+    cv::Mat base;
+    if (size % 2 == 0) {
+        base = (cv::Mat_<float>(2, 2) << 0, 2, 3, 1);
+    } else if (size % 3 == 0) {
+        base = (cv::Mat_<float>(3, 3) << 0, 7, 3, 6, 5, 2, 4, 1, 8);
+    } else {
+        cv::Mat ranks = generateBlueNoiseTexture(size, 1) * static_cast<float>(size * size) - 0.5f;
+        return ranks;
     }
 
-    int halfSize = size / 2;
-    cv::Mat smaller = generateBayerMatrix(halfSize);
-    cv::Mat bayer = cv::Mat::zeros(size, size, CV_32F);
-
-    for (int y = 0; y < halfSize; ++y) {
-        for (int x = 0; x < halfSize; ++x) {
-            float val = smaller.at<float>(y, x);
-            bayer.at<float>(y, x) = 4 * val + 0;
-            bayer.at<float>(y, x + halfSize) = 4 * val + 2;
-            bayer.at<float>(y + halfSize, x) = 4 * val + 3;
-            bayer.at<float>(y + halfSize, x + halfSize) = 4 * val + 1;
+    int factor = base.rows;
+    int rest = size / factor;
+    cv::Mat smaller = bayerRanks(rest);
+    cv::Mat ranks(size, size, CV_32F);
+    for (int y = 0; y < size; ++y) {
+        for (int x = 0; x < size; ++x) {
+            ranks.at<float>(y, x) = factor * factor * smaller.at<float>(y % rest, x % rest) +
+                                    base.at<float>(y / rest, x / rest);
         }
     }
+    return ranks;
+}
 
-    return bayer / (size * size);
+// Generate Bayer matrix
+cv::Mat generateBayerMatrix(int size) {
+    size = std::clamp(size, 2, MAX_BAYER_SIZE);
+    return bayerRanks(size) / static_cast<float>(size * size);
 }
 
-// Ordered dithering (Bayer matrix)
-cv::Mat orderedDither(const cv::Mat& input, const Parameters& params) {
-    int size = params.bayerSize;
-    cv::Mat bayerMatrix = generateBayerMatrix(size);
+// One round dot in the middle of the cell, growing outward ring by ring; ties
+// go round the dot by angle so it grows evenly
+cv::Mat generateClusteredMatrix(int size) {
+    size = std::clamp(size, 2, MAX_BAYER_SIZE);
+    std::vector<std::pair<std::pair<float, float>, int>> order;
+    float center = (size - 1) / 2.0f;
+    for (int y = 0; y < size; ++y) {
+        for (int x = 0; x < size; ++x) {
+            float dx = x - center, dy = y - center;
+            order.push_back({{std::round((dx * dx + dy * dy) * 1000.0f), std::atan2(dy, dx)}, y * size + x});
+        }
+    }
+    std::sort(order.begin(), order.end());
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
-    cv::Mat result = input.clone();
+    cv::Mat matrix(size, size, CV_32F);
+    for (size_t rank = 0; rank < order.size(); ++rank) {
+        matrix.at<float>(order[rank].second / size, order[rank].second % size) =
+            static_cast<float>(rank) / (size * size);
+    }
+    return matrix;
+}
 
-    for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
-            float threshold = bayerMatrix.at<float>(y % size, x % size);
-            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
+// Threshold matrix index for a pixel coordinate, shifted by the phase offset.
+// With scale > 1 each matrix cell covers a scale x scale block of pixels
+static int phaseIndex(int coord, int phase, int size, int scale = 1) {
+    int shifted = coord + phase;
+    int cell = shifted >= 0 ? shifted / scale : -((-shifted + scale - 1) / scale);
+    int index = cell % size;
+    return index < 0 ? index + size : index;
+}
 
-            cv::Vec3f pixelF(pixel[0], pixel[1], pixel[2]);
-            cv::Vec3f adjusted = pixelF + cv::Vec3f(threshold * 255.0f - 127.5f) * params.strength;
+// Integer block size for threshold matrices (ditherScale rounded, at least 1)
+static int patternScale(const Parameters& params) {
+    return std::max(1, static_cast<int>(std::lround(params.ditherScale)));
+}
 
-            adjusted = cv::Vec3f(
-                std::clamp(adjusted[0], 0.0f, 255.0f),
-                std::clamp(adjusted[1], 0.0f, 255.0f),
-                std::clamp(adjusted[2], 0.0f, 255.0f)
-            );
+static bool isRotated(const Parameters& params) {
+    return std::fmod(params.ditherAngle, 360.0f) != 0.0f;
+}
 
-            cv::Vec3b adjustedPixel(
-                static_cast<uchar>(adjusted[0]),
-                static_cast<uchar>(adjusted[1]),
-                static_cast<uchar>(adjusted[2])
-            );
+// Threshold of a tiled matrix at a pixel, for the threshold-map algorithms.
+// A rotated screen is sampled at pixel centers along axes turned by
+// ditherAngle, so its cells no longer line up with the pixel grid
+static auto matrixLookup(const cv::Mat& matrix, const Parameters& params, int scale) {
+    float radians = params.ditherAngle * static_cast<float>(CV_PI) / 180.0f;
+    float c = std::cos(radians), s = std::sin(radians);
+    bool rotated = isRotated(params);
+    return [&matrix, &params, scale, c, s, rotated](int x, int y) {
+        if (!rotated) {
+            return matrix.at<float>(phaseIndex(y, params.phaseY, matrix.rows, scale),
+                                    phaseIndex(x, params.phaseX, matrix.cols, scale));
+        }
+        float px = x + params.phaseX + 0.5f, py = y + params.phaseY + 0.5f;
+        int u = static_cast<int>(std::floor((px * c + py * s) / scale));
+        int v = static_cast<int>(std::floor((-px * s + py * c) / scale));
+        return matrix.at<float>(phaseIndex(v, 0, matrix.rows), phaseIndex(u, 0, matrix.cols));
+    };
+}
 
-            result.at<cv::Vec3b>(y, x) = findClosestColor(adjustedPixel, palette);
+// Range the threshold offset of ordered algorithms has to cover: the widest gap
+// between adjacent levels of any channel in the palette. A pixel between two
+// levels then dithers between just those two, so multi-level palettes give
+// multi-level output rather than full-range noise
+static float thresholdSpread(const std::vector<cv::Vec3b>& palette) {
+    int spread = 0;
+    for (int c = 0; c < 3; ++c) {
+        std::vector<int> values;
+        for (const auto& color : palette) values.push_back(color[c]);
+        std::sort(values.begin(), values.end());
+        values.erase(std::unique(values.begin(), values.end()), values.end());
+
+        for (size_t i = 1; i < values.size(); ++i) {
+            spread = std::max(spread, values[i] - values[i - 1]);
         }
     }
+    return spread > 0 ? static_cast<float>(spread) : 255.0f;
+}
 
-    return result;
+// Add per-channel threshold offsets to a row of 8-bit channel values and clamp
+// to 0-255, 16 values at a time where the CPU has 128-bit SIMD. Truncates like
+// the scalar tail, so both give identical results
+static void offsetRow(const uchar* in, const float* offsets, uchar* out, int count) {
+    int i = 0;
+#if CV_SIMD128
+    const cv::v_float32x4 zero = cv::v_setzero_f32(), top = cv::v_setall_f32(255.0f);
+    for (; i + 16 <= count; i += 16) {
+        cv::v_uint16x8 low, high;
+        cv::v_expand(cv::v_load(in + i), low, high);
+        cv::v_uint32x4 values[4];
+        cv::v_expand(low, values[0], values[1]);
+        cv::v_expand(high, values[2], values[3]);
+        cv::v_int32x4 adjusted[4];
+        for (int k = 0; k < 4; ++k) {
+            cv::v_float32x4 sum = cv::v_cvt_f32(cv::v_reinterpret_as_s32(values[k])) + cv::v_load(offsets + i + 4 * k);
+            adjusted[k] = cv::v_trunc(cv::v_min(cv::v_max(sum, zero), top));
+        }
+        cv::v_uint16x8 packedLow = cv::v_pack_u(adjusted[0], adjusted[1]);
+        cv::v_uint16x8 packedHigh = cv::v_pack_u(adjusted[2], adjusted[3]);
+        cv::v_store(out + i, cv::v_pack(packedLow, packedHigh));
+    }
+#endif
+    for (; i < count; ++i) {
+        out[i] = static_cast<uchar>(std::clamp(in[i] + offsets[i], 0.0f, 255.0f));
+    }
 }
 
-// Blue noise dithering
-cv::Mat blueNoiseDither(const cv::Mat& input, const Parameters& params) {
-    cv::Mat blueNoise = generateBlueNoiseTexture(256, params.seed);
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+// Shared by the threshold-map algorithms: offset each pixel by its threshold
+// (0-1, from thresholdAt) times the palette spread, then quantize. Pixels don't
+// depend on each other, so rows run in parallel on raw row pointers, each worker
+// with its own copy of the quantizer (color cache and random state aren't
+// shareable). Stochastic rounding is reseeded per row so the output doesn't
+// depend on how rows were split between threads.
+//
+// Per row, the thresholds are gathered first, then offsetRow applies them with
+// SIMD. Gray pixels with a grayscale palette (the common case) then map through
+// a 256-entry table instead of searching the palette for every pixel
+template <typename ThresholdAt>
+static cv::Mat ditherThresholdMap(const cv::Mat& input, const Parameters& params, ThresholdAt thresholdAt) {
+    Quantizer quantize(params, input);
+    const float amount = thresholdSpread(quantize.palette) * params.strength;
     cv::Mat result = input.clone();
 
-    for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
-            float noise = blueNoise.at<float>(y % 256, x % 256);
-            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
+    // Same table the GPU path uses: the color each gray level quantizes to
+    bool useTable = isGrayscalePalette(quantize.palette) && params.rounding != RoundingMode::STOCHASTIC;
+    std::array<cv::Vec3b, 256> table;
+    if (useTable) {
+        for (int v = 0; v < 256; ++v) table[v] = quantize(cv::Vec3b(v, v, v));
+    }
 
-            cv::Vec3f pixelF(pixel[0], pixel[1], pixel[2]);
-            cv::Vec3f adjusted = pixelF + cv::Vec3f(noise * 255.0f - 127.5f) * params.strength;
+    RenderProgress* progress = rendering;
+    cv::parallel_for_(cv::Range(0, input.rows), [&](const cv::Range& rows) {
+        RenderScope scope(progress);
+        Quantizer local = quantize;
+        std::vector<float> offsets(3 * input.cols);
+        std::vector<uchar> adjusted(3 * input.cols);
+        for (int y = rows.start; y < rows.end; ++y) {
+            if (!nextRow()) return;
+            if (params.rounding == RoundingMode::STOCHASTIC) local.rng.seed(params.seed + y);
+            for (int x = 0; x < input.cols; ++x) {
+                float offset = (thresholdAt(x, y) - 0.5f) * amount;
+                offsets[3 * x] = offsets[3 * x + 1] = offsets[3 * x + 2] = offset;
+            }
+            offsetRow(input.ptr<uchar>(y), offsets.data(), adjusted.data(), 3 * input.cols);
+
+            const cv::Vec3b* in = reinterpret_cast<const cv::Vec3b*>(adjusted.data());
+            cv::Vec3b* out = result.ptr<cv::Vec3b>(y);
+            for (int x = 0; x < input.cols; ++x) {
+                if (local.isProtected(x, y)) continue;
+                const cv::Vec3b& pixel = in[x];
+                bool gray = pixel[0] == pixel[1] && pixel[1] == pixel[2];
+                out[x] = useTable && gray ? table[pixel[0]] : local(pixel);
+            }
+        }
+    });
 
-            adjusted = cv::Vec3f(
-                std::clamp(adjusted[0], 0.0f, 255.0f),
-                std::clamp(adjusted[1], 0.0f, 255.0f),
-                std::clamp(adjusted[2], 0.0f, 255.0f)
-            );
+    return result;
+}
 
-            cv::Vec3b adjustedPixel(
-                static_cast<uchar>(adjusted[0]),
-                static_cast<uchar>(adjusted[1]),
-                static_cast<uchar>(adjusted[2])
-            );
+// GPU path for the threshold-map algorithms. Grayscale palettes only: the kernel
+// maps each offset gray level through a 256-entry table filled by the quantizer,
+// so it picks exactly the colors the CPU would. AUTO uses it from about 1080p up.
+// Rotated screens stay on the CPU
+static const double gpuMinPixels = 2.0e6;
 
-            result.at<cv::Vec3b>(y, x) = findClosestColor(adjustedPixel, palette);
-        }
+static bool ditherThresholdMapGpu(const cv::Mat& input, const Parameters& params, const cv::Mat& thresholds,
+                                  int scale, cv::Mat& result) {
+    if (params.backend == Backend::CPU || isRotated(params)) return false;
+    if (params.backend == Backend::AUTO && input.total() < gpuMinPixels) return false;
+    if (params.rounding == RoundingMode::STOCHASTIC || !Gpu::isAvailable()) return false;
+
+    Quantizer quantize(params, input);
+    if (!quantize.protectMask.empty() || !isGrayscalePalette(quantize.palette)) return false;
+
+    std::array<uchar, 256> lut;
+    for (int v = 0; v < 256; ++v) {
+        lut[v] = quantize(cv::Vec3b(v, v, v))[0];
     }
 
-    return result;
+    // Grayscale palettes are dithered on luma, so all three channels are equal
+    cv::Mat gray, dithered;
+    cv::extractChannel(input, gray, 0);
+    if (!Gpu::ditherThresholdMap(gray, thresholds, scale, cv::Point(params.phaseX, params.phaseY),
+                                 thresholdSpread(quantize.palette), params.strength, lut, dithered)) {
+        return false;
+    }
+    cv::cvtColor(dithered, result, cv::COLOR_GRAY2BGR);
+    return true;
+}
+
+// Ordered dithering (Bayer matrix, or one clustered dot per matrix)
+cv::Mat orderedDither(const cv::Mat& input, const Parameters& params) {
+    int scale = patternScale(params);
+    cv::Mat bayerMatrix = params.bayerClustered ? generateClusteredMatrix(params.bayerSize)
+                                                : generateBayerMatrix(params.bayerSize);
+
+    cv::Mat result;
+    if (ditherThresholdMapGpu(input, params, bayerMatrix, scale, result)) return result;
+    return ditherThresholdMap(input, params, matrixLookup(bayerMatrix, params, scale));
+}
+
+// Blue noise dithering, with the generated texture or one loaded from a file.
+// A texture that can't be loaded falls back to the generated one
+cv::Mat blueNoiseDither(const cv::Mat& input, const Parameters& params) {
+    cv::Mat blueNoise;          // Tiled over the image
+    std::string error;
+    if (params.noiseTexture.empty() || !Matrices::loadNoiseTexture(params.noiseTexture, blueNoise, error)) {
+        blueNoise = generateBlueNoiseTexture(64, params.seed);
+    }
+
+    cv::Mat result;
+    if (ditherThresholdMapGpu(input, params, blueNoise, 1, result)) return result;
+    return ditherThresholdMap(input, params, [&](int x, int y) {
+        return blueNoise.at<float>(phaseIndex(y, params.phaseY, blueNoise.rows),
+                                   phaseIndex(x, params.phaseX, blueNoise.cols));
+    });
 }
 
 // White noise dithering
@@ -565,16 +2411,18 @@ cv::Mat whiteNoiseDither(const cv::Mat& input, const Parameters& params) {
     std::mt19937 rng(params.seed);
     std::uniform_real_distribution<float> dist(0.0f, 1.0f);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantize(params, input);
+    float spread = thresholdSpread(quantize.palette);
     cv::Mat result = input.clone();
 
     for (int y = 0; y < input.rows; ++y) {
         for (int x = 0; x < input.cols; ++x) {
             float noise = dist(rng);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
 
             cv::Vec3f pixelF(pixel[0], pixel[1], pixel[2]);
-            cv::Vec3f adjusted = pixelF + cv::Vec3f(noise * 255.0f - 127.5f) * params.strength;
+            cv::Vec3f adjusted = pixelF + cv::Vec3f((noise - 0.5f) * spread) * params.strength;
 
             adjusted = cv::Vec3f(
                 std::clamp(adjusted[0], 0.0f, 255.0f),
@@ -588,44 +2436,67 @@ cv::Mat whiteNoiseDither(const cv::Mat& input, const Parameters& params) {
                 static_cast<uchar>(adjusted[2])
             );
 
-            result.at<cv::Vec3b>(y, x) = findClosestColor(adjustedPixel, palette);
+            result.at<cv::Vec3b>(y, x) = quantize(adjustedPixel);
         }
     }
 
     return result;
 }
 
-// Random dithering
-cv::Mat randomDither(const cv::Mat& input, const Parameters& params) {
-    return whiteNoiseDither(input, params);
+// Interleaved gradient noise (Jimenez 2014): a per-pixel hash with blue-noise-like
+// structure, no texture needed
+cv::Mat interleavedGradientNoise(const cv::Mat& input, const Parameters& params) {
+    return ditherThresholdMap(input, params, [&](int x, int y) {
+        float px = static_cast<float>(x + params.phaseX);
+        float py = static_cast<float>(y + params.phaseY);
+        float inner = 0.06711056f * px + 0.00583715f * py;
+        float noise = 52.9829189f * (inner - std::floor(inner));
+        return noise - std::floor(noise);
+    });
 }
 
-// Pattern dithering
-cv::Mat patternDither(const cv::Mat& input, const Parameters& params) {
+// Halftone spot threshold in [0, 1] for a pixel on a screen rotated by angle
+// degrees: lowest at the cell center, so dots grow outwards as tones darken
+static float halftoneSpot(float x, float y, float angle, float cellSize, HalftoneShape shape) {
+    float radians = angle * static_cast<float>(CV_PI) / 180.0f;
+    float c = std::cos(radians), s = std::sin(radians);
+    float u = (x * c + y * s) / cellSize;
+    float v = (-x * s + y * c) / cellSize;
+    float fu = u - std::floor(u) - 0.5f;
+    float fv = v - std::floor(v) - 0.5f;
+
+    switch (shape) {
+        case HalftoneShape::DIAMOND:
+            return std::min(std::fabs(fu) + std::fabs(fv), 1.0f);
+        case HalftoneShape::CIRCLE:
+        default:
+            return std::min(2.0f * (fu * fu + fv * fv), 1.0f);
+    }
+}
+
+// Halftone screen. Each channel gets its own screen angle (like the C, M and Y
+// plates in print) so the screens don't beat into moire; grayscale output uses
+// a single screen at the K angle
+cv::Mat halftone(const cv::Mat& input, const Parameters& params) {
+    Quantizer quantize(params, input);
+    float spread = thresholdSpread(quantize.palette);
+    float cellSize = std::max(2.0f, params.halftoneCellSize);
+    bool gray = isGrayscalePalette(quantize.palette);
     cv::Mat result = input.clone();
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
-
-    // Create a 4x4 pattern
-    float pattern[4][4] = {
-        {0.0f, 0.5f, 0.125f, 0.625f},
-        {0.75f, 0.25f, 0.875f, 0.375f},
-        {0.1875f, 0.6875f, 0.0625f, 0.5625f},
-        {0.9375f, 0.4375f, 0.8125f, 0.3125f}
-    };
 
     for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
-            float threshold = pattern[y % 4][x % 4];
-            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
-
-            cv::Vec3f pixelF(pixel[0], pixel[1], pixel[2]);
-            cv::Vec3f adjusted = pixelF + cv::Vec3f(threshold * 255.0f - 127.5f) * params.strength;
-
-            adjusted = cv::Vec3f(
-                std::clamp(adjusted[0], 0.0f, 255.0f),
-                std::clamp(adjusted[1], 0.0f, 255.0f),
-                std::clamp(adjusted[2], 0.0f, 255.0f)
-            );
+        for (int x = 0; x < input.cols; ++x) {
+            if (quantize.isProtected(x, y)) continue;
+            float px = static_cast<float>(x + params.phaseX) + 0.5f;
+            float py = static_cast<float>(y + params.phaseY) + 0.5f;
+            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
+
+            cv::Vec3f adjusted;
+            for (int c = 0; c < 3; ++c) {
+                float angle = gray ? params.grayScreenAngle : params.screenAngles[c];
+                float threshold = halftoneSpot(px, py, angle, cellSize, params.halftoneShape);
+                adjusted[c] = std::clamp(pixel[c] + (threshold - 0.5f) * spread * params.strength, 0.0f, 255.0f);
+            }
 
             cv::Vec3b adjustedPixel(
                 static_cast<uchar>(adjusted[0]),
@@ -633,19 +2504,55 @@ cv::Mat patternDither(const cv::Mat& input, const Parameters& params) {
                 static_cast<uchar>(adjusted[2])
             );
 
-            result.at<cv::Vec3b>(y, x) = findClosestColor(adjustedPixel, palette);
+            result.at<cv::Vec3b>(y, x) = quantize(adjustedPixel);
         }
     }
 
     return result;
 }
 
+// Random dithering
+cv::Mat randomDither(const cv::Mat& input, const Parameters& params) {
+    return whiteNoiseDither(input, params);
+}
+
+// Pattern dithering
+cv::Mat patternDither(const cv::Mat& input, const Parameters& params) {
+    // User-supplied matrix, or the built-in 4x4 pattern
+    int scale = patternScale(params);
+    cv::Mat pattern = params.patternMatrix;
+    if (pattern.empty()) {
+        pattern = (cv::Mat_<float>(4, 4) <<
+            0.0f, 0.5f, 0.125f, 0.625f,
+            0.75f, 0.25f, 0.875f, 0.375f,
+            0.1875f, 0.6875f, 0.0625f, 0.5625f,
+            0.9375f, 0.4375f, 0.8125f, 0.3125f);
+    }
+
+    cv::Mat result;
+    if (ditherThresholdMapGpu(input, params, pattern, scale, result)) return result;
+    return ditherThresholdMap(input, params, matrixLookup(pattern, params, scale));
+}
+
+// Ordered dithering with a threshold matrix from the library
+cv::Mat matrixDither(const cv::Mat& input, const Parameters& params) {
+    cv::Mat thresholds = Matrices::getMatrix(params.matrix);
+    if (thresholds.empty()) {
+        thresholds = generateBayerMatrix(8);
+    }
+    int scale = patternScale(params);
+
+    cv::Mat result;
+    if (ditherThresholdMapGpu(input, params, thresholds, scale, result)) return result;
+    return ditherThresholdMap(input, params, matrixLookup(thresholds, params, scale));
+}
+
 // Dot diffusion dithering
 cv::Mat dotDiffusion(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantize(params, result);
 
     // 8x8 class matrix for dot diffusion
     int classMatrix[8][8] = {
@@ -660,10 +2567,12 @@ cv::Mat dotDiffusion(const cv::Mat& input, const Parameters& params) {
     };
 
     for (int y = 0; y < input.rows; ++y) {
+        if (!nextRow()) break;
         for (int x = 0; x < input.cols; ++x) {
             int classVal = classMatrix[y % 8][x % 8];
             float threshold = classVal / 64.0f;
 
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
@@ -682,61 +2591,166 @@ cv::Mat dotDiffusion(const cv::Mat& input, const Parameters& params) {
                 static_cast<uchar>(newPixelF[2])
             );
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
         }
     }
 
     return result;
 }
 
-// Riemersma dithering (space-filling curve)
+// Space-filling curve walks over the image, visiting every pixel once. Each
+// square is described by its first cell and two unit axes, so sub-squares can
+// be mirrored and transposed; squares entirely outside the image are skipped
+template <typename Visit>
+struct CurveWalk {
+    cv::Size bounds;
+    Visit& visit;
+
+    bool outside(int x, int y, int size, int ux, int uy, int vx, int vy) const {
+        int farX = x + (size - 1) * (ux + vx);
+        int farY = y + (size - 1) * (uy + vy);
+        return std::max(x, farX) < 0 || std::min(x, farX) >= bounds.width ||
+               std::max(y, farY) < 0 || std::min(y, farY) >= bounds.height;
+    }
+
+    // Runs from the first cell to the far end of the u axis
+    void hilbert(int x, int y, int size, int ux, int uy, int vx, int vy) {
+        if (outside(x, y, size, ux, uy, vx, vy)) return;
+        if (size == 1) {
+            visit(x, y);
+            return;
+        }
+        int h = size / 2;
+        hilbert(x, y, h, vx, vy, ux, uy);
+        hilbert(x + h * vx, y + h * vy, h, ux, uy, vx, vy);
+        hilbert(x + h * (ux + vx), y + h * (uy + vy), h, ux, uy, vx, vy);
+        hilbert(x + (size - 1) * ux + (h - 1) * vx, y + (size - 1) * uy + (h - 1) * vy, h, -vx, -vy, -ux, -uy);
+    }
+
+    // Runs from the first cell to the opposite corner, through a serpentine of
+    // 3x3 sub-squares, each mirrored so its ends meet its neighbours
+    void peano(int x, int y, int size, int ux, int uy, int vx, int vy) {
+        if (outside(x, y, size, ux, uy, vx, vy)) return;
+        if (size == 1) {
+            visit(x, y);
+            return;
+        }
+        int t = size / 3;
+        for (int k = 0; k < 9; ++k) {
+            int a = k / 3;
+            int b = (a % 2 == 0) ? k % 3 : 2 - k % 3;
+            int ox = x + t * (a * ux + b * vx), oy = y + t * (a * uy + b * vy);
+            int su = 1, sv = 1;
+            if (b % 2 == 1) {
+                ox += (t - 1) * ux;
+                oy += (t - 1) * uy;
+                su = -1;
+            }
+            if (a % 2 == 1) {
+                ox += (t - 1) * vx;
+                oy += (t - 1) * vy;
+                sv = -1;
+            }
+            peano(ox, oy, t, su * ux, su * uy, sv * vx, sv * vy);
+        }
+    }
+
+    // Morton order: not continuous, but keeps every 2^n block together
+    void zOrder(int x, int y, int size) {
+        if (outside(x, y, size, 1, 0, 0, 1)) return;
+        if (size == 1) {
+            visit(x, y);
+            return;
+        }
+        int h = size / 2;
+        zOrder(x, y, h);
+        zOrder(x + h, y, h);
+        zOrder(x, y + h, h);
+        zOrder(x + h, y + h, h);
+    }
+};
+
+template <typename Visit>
+static void walkCurve(CurveType curve, const cv::Size& size, Visit visit) {
+    int factor = curve == CurveType::PEANO ? 3 : 2;
+    int side = 1;
+    while (side < std::max(size.width, size.height)) side *= factor;
+
+    CurveWalk<Visit> walk{size, visit};
+    switch (curve) {
+        case CurveType::PEANO:
+            walk.peano(0, 0, side, 1, 0, 0, 1);
+            break;
+        case CurveType::Z_ORDER:
+            walk.zOrder(0, 0, side);
+            break;
+        case CurveType::HILBERT:
+        default:
+            walk.hilbert(0, 0, side, 1, 0, 0, 1);
+            break;
+    }
+}
+
+// Riemersma dithering: walk a space-filling curve, adding a weighted sum of the
+// last few errors (original pixel minus output) to each pixel. Weights grow
+// exponentially from 1 for the oldest error to riemersmaDecay for the newest
 cv::Mat riemersma(const cv::Mat& input, const Parameters& params) {
-    // Simplified Riemersma using Hilbert curve approximation
     cv::Mat result = input.clone();
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantize(params, input);
 
-    // Use a simple serpentine scan as approximation
-    cv::Vec3f error(0, 0, 0);
-    const float decay = 0.8f;
+    int history = std::clamp(params.riemersmaHistory, 1, 256);
+    float maxWeight = std::max(1.0f, params.riemersmaDecay);
+    std::vector<float> weights(history);
+    for (int i = 0; i < history; ++i) {
+        weights[i] = history > 1 ? std::pow(maxWeight, static_cast<float>(i) / (history - 1)) : maxWeight;
+    }
 
-    for (int y = 0; y < input.rows; ++y) {
-        bool reverse = (y % 2 == 1);
-        int start = reverse ? input.cols - 1 : 0;
-        int end = reverse ? -1 : input.cols;
-        int step = reverse ? -1 : 1;
+    // Ring buffer of errors; oldest sits at 'head'
+    std::vector<cv::Vec3f> errors(history, cv::Vec3f(0, 0, 0));
+    int head = 0;
+
+    // The curve has no rows, so progress counts a row's worth of pixels at a time
+    int visited = 0;
+    bool stopped = false;
+    walkCurve(params.curve, input.size(), [&](int x, int y) {
+        if (stopped || (++visited % input.cols == 0 && !nextRow())) {
+            stopped = true;
+            return;
+        }
+        if (quantize.isProtected(x, y)) return;
 
-        for (int x = start; x != end; x += step) {
-            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
-            cv::Vec3f pixelF(pixel[0], pixel[1], pixel[2]);
-            pixelF += error * params.strength;
+        cv::Vec3f correction(0, 0, 0);
+        for (int i = 0; i < history; ++i) {
+            correction += errors[(head + i) % history] * weights[i];
+        }
 
-            pixelF = cv::Vec3f(
-                std::clamp(pixelF[0], 0.0f, 255.0f),
-                std::clamp(pixelF[1], 0.0f, 255.0f),
-                std::clamp(pixelF[2], 0.0f, 255.0f)
-            );
+        cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
+        cv::Vec3f original(pixel[0], pixel[1], pixel[2]);
+        cv::Vec3f pixelF = original + correction * (params.strength / maxWeight);
 
-            cv::Vec3b pixelB(
-                static_cast<uchar>(pixelF[0]),
-                static_cast<uchar>(pixelF[1]),
-                static_cast<uchar>(pixelF[2])
-            );
+        cv::Vec3b pixelB(
+            cv::saturate_cast<uchar>(pixelF[0]),
+            cv::saturate_cast<uchar>(pixelF[1]),
+            cv::saturate_cast<uchar>(pixelF[2])
+        );
 
-            cv::Vec3b quantized = findClosestColor(pixelB, palette);
-            result.at<cv::Vec3b>(y, x) = quantized;
+        cv::Vec3b quantized = quantize(pixelB);
+        result.at<cv::Vec3b>(y, x) = quantized;
+        recordVisit(result, x, y);
 
-            error = (pixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2])) * decay;
-        }
-    }
+        errors[head] = original - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
+        head = (head + 1) % history;
+    });
 
     return result;
 }
 
 // Gradient-based dithering
 cv::Mat gradientBased(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
     // Calculate gradients
     cv::Mat gray, gradX, gradY, gradient;
@@ -746,120 +2760,186 @@ cv::Mat gradientBased(const cv::Mat& input, const Parameters& params) {
     cv::magnitude(gradX, gradY, gradient);
     cv::normalize(gradient, gradient, 0, 1, cv::NORM_MINMAX);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantize(params, result);
 
     for (int y = 0; y < input.rows; ++y) {
+        if (!nextRow()) break;
         for (int x = 0; x < input.cols; ++x) {
             float gradWeight = gradient.at<float>(y, x);
             float adaptiveStrength = params.strength * (0.5f + gradWeight * 0.5f);
 
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
-
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
 
             // Use Floyd-Steinberg-like diffusion with adaptive strength
             std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
             std::vector<float> weights = {7.0f/16.0f, 3.0f/16.0f, 5.0f/16.0f, 1.0f/16.0f};
-            diffuseError(errors, x, y, quantError, offsets, weights, adaptiveStrength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, adaptiveStrength, false, params.errorEdge);
         }
     }
 
     return result;
 }
 
-// Variable error diffusion
-cv::Mat variableErrorDiffusion(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+// Structure-aware, tone-dependent error diffusion. Each neighbor's kernel weight
+// is scaled down by how much its luma differs from the current pixel, then the
+// weights are renormalized, so error stays on its own side of an edge and line
+// art and facial features keep their outlines. Midtones use the compact
+// Floyd-Steinberg kernel; shadows and highlights, where sparse dots form worms,
+// spread error over the wider Stucki kernel
+cv::Mat structureAware(const cv::Mat& input, const Parameters& params) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
+
+    cv::Mat gray;
+    cv::cvtColor(input, gray, cv::COLOR_BGR2GRAY);
+    gray.convertTo(gray, CV_32F);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
-    std::mt19937 rng(params.seed);
-    std::uniform_real_distribution<float> dist(0.7f, 1.3f);
+    Quantizer quantize(params, result);
+
+    const std::vector<std::pair<int, int>> midtoneOffsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
+    const std::vector<float> midtoneWeights = {7.0f/16.0f, 3.0f/16.0f, 5.0f/16.0f, 1.0f/16.0f};
+    const std::vector<std::pair<int, int>> extremeOffsets = {
+        {1, 0}, {2, 0},
+        {-2, 1}, {-1, 1}, {0, 1}, {1, 1}, {2, 1},
+        {-2, 2}, {-1, 2}, {0, 2}, {1, 2}, {2, 2}
+    };
+    const std::vector<float> extremeWeights = {
+        8.0f/42.0f, 4.0f/42.0f,
+        2.0f/42.0f, 4.0f/42.0f, 8.0f/42.0f, 4.0f/42.0f, 2.0f/42.0f,
+        1.0f/42.0f, 2.0f/42.0f, 4.0f/42.0f, 2.0f/42.0f, 1.0f/42.0f
+    };
+    float falloff = std::max(0.0f, params.edgeSensitivity) / 32.0f;
+    std::vector<float> weights;
 
     for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
+        if (!nextRow()) break;
+        bool reverse = (params.serpentine > 0.5f) && (y % 2 == 1);
+        int start = reverse ? input.cols - 1 : 0;
+        int end = reverse ? -1 : input.cols;
+        int step = reverse ? -1 : 1;
+
+        for (int x = start; x != end; x += step) {
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
+            cv::Vec3b quantized = quantize(newPixel);
+            result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
+
+            cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
+
+            float tone = gray.at<float>(y, x);
+            bool extreme = tone < 64.0f || tone > 191.0f;
+            const auto& offsets = extreme ? extremeOffsets : midtoneOffsets;
+            const auto& baseWeights = extreme ? extremeWeights : midtoneWeights;
+
+            // Edge-stopping weights, mirrored like diffuseError does on serpentine rows
+            weights.assign(baseWeights.size(), 0.0f);
+            float total = 0.0f;
+            for (size_t i = 0; i < offsets.size(); ++i) {
+                int nx = edgeColumn(x + offsets[i].first * (reverse ? -1 : 1), input.cols, params.errorEdge);
+                int ny = y + offsets[i].second;
+                if (nx < 0 || ny >= input.rows) continue;
+
+                float difference = std::abs(gray.at<float>(ny, nx) - tone);
+                weights[i] = baseWeights[i] * std::exp(-difference * falloff);
+                total += weights[i];
+            }
+            if (total <= 0.0f) continue;
+            for (auto& weight : weights) weight /= total;
+
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, params.serpentine > 0.5f,
+                         params.errorEdge);
+        }
+    }
+
+    return result;
+}
+
+// Variable error diffusion
+cv::Mat variableErrorDiffusion(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
+
+    Quantizer quantizer(params, result);
+    std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
+
+    // Seeded per row, so the rows can run on any thread in any order
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
+        std::mt19937 rng(params.seed + y);
+        std::uniform_real_distribution<float> dist(0.7f, 1.3f);
+        for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
+            cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
+            cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
+
+            cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
 
             // Variable weights
             float var = dist(rng);
-            std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
             std::vector<float> weights = {
                 7.0f/16.0f * var,
                 3.0f/16.0f * var,
                 5.0f/16.0f * var,
                 1.0f/16.0f * var
             };
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Ostromoukhov dithering
-cv::Mat ostromoukhov(const cv::Mat& input, const Parameters& params) {
+cv::Mat ostromoukhov(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
     // Simplified Ostromoukhov (using adaptive weights based on intensity)
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
+    std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
-
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             // Adaptive weights based on intensity
             float intensity = (newPixelF[0] + newPixelF[1] + newPixelF[2]) / (3.0f * 255.0f);
@@ -870,165 +2950,613 @@ cv::Mat ostromoukhov(const cv::Mat& input, const Parameters& params) {
             float sum = w1 + w2 + w3 + w4;
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            std::vector<std::pair<int, int>> offsets = {{1, 0}, {-1, 1}, {0, 1}, {1, 1}};
             std::vector<float> weights = {w1/sum, w2/sum, w3/sum, w4/sum};
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
 // Fan dithering
-cv::Mat fan(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+cv::Mat fan(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    Quantizer quantizer(params, result);
 
     std::vector<std::pair<int, int>> offsets = {{1, 0}, {0, 1}, {1, 1}, {-1, 1}};
     std::vector<float> weights = {7.0f/16.0f, 1.0f/16.0f, 5.0f/16.0f, 3.0f/16.0f};
 
-    for (int y = 0; y < input.rows; ++y) {
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
         for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
+            cv::Vec3b quantized = quantize(newPixel);
+            result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
+
+            cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
+        }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+    return result;
+}
+
+// Shiau-Fan dithering
+cv::Mat shiauFan(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
+
+    Quantizer quantizer(params, result);
+
+    std::vector<std::pair<int, int>> offsets = {
+        {1, 0}, {2, 0},
+        {-2, 1}, {-1, 1}, {0, 1}, {1, 1}, {2, 1}
+    };
+    std::vector<float> weights = {
+        4.0f/16.0f, 1.0f/16.0f,
+        1.0f/16.0f, 1.0f/16.0f, 2.0f/16.0f, 4.0f/16.0f, 2.0f/16.0f
+    };
+
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
+        for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
+            cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
+            cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
+
+            cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
+
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
+
+    return result;
+}
+
+// Steven Pigeon dithering
+cv::Mat stevenPigeon(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
+
+    Quantizer quantizer(params, result);
+
+    std::vector<std::pair<int, int>> offsets = {
+        {1, 0}, {2, 0},
+        {-2, 1}, {-1, 1}, {0, 1}, {1, 1}, {2, 1},
+        {-1, 2}, {0, 2}, {1, 2}
+    };
+    std::vector<float> weights = {
+        2.0f/14.0f, 1.0f/14.0f,
+        1.0f/14.0f, 2.0f/14.0f, 2.0f/14.0f, 2.0f/14.0f, 1.0f/14.0f,
+        1.0f/14.0f, 1.0f/14.0f, 1.0f/14.0f
+    };
+
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
+        for (int x = 0; x < input.cols; ++x) {
+            front.at(x);
+            if (quantize.isProtected(x, y)) continue;
+            cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
+            cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
+
+            cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
+
+            cv::Vec3b quantized = quantize(newPixel);
+            result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
+
+            cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false, params.errorEdge);
+        }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), false, onBand, row);
 
     return result;
 }
 
-// Shiau-Fan dithering
-cv::Mat shiauFan(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
+bool validateKernel(const std::vector<KernelWeight>& kernel, bool normalize, std::string& error) {
+    if (kernel.empty() || static_cast<int>(kernel.size()) > MAX_KERNEL_WEIGHTS) {
+        error = "needs 1 to " + std::to_string(MAX_KERNEL_WEIGHTS) + " weights";
+        return false;
+    }
+    float sum = 0.0f;
+    for (size_t i = 0; i < kernel.size(); ++i) {
+        const KernelWeight& entry = kernel[i];
+        std::string where = "[" + std::to_string(entry.dx) + ", " + std::to_string(entry.dy) + "]";
+        if (entry.dy < 0 || (entry.dy == 0 && entry.dx <= 0)) {
+            error = where + " points at a pixel already dithered; use dy > 0, or dy = 0 and dx > 0";
+            return false;
+        }
+        if (std::abs(entry.dx) > MAX_KERNEL_REACH || entry.dy > MAX_KERNEL_REACH) {
+            error = where + " is more than " + std::to_string(MAX_KERNEL_REACH) + " pixels away";
+            return false;
+        }
+        if (!std::isfinite(entry.weight)) {
+            error = where + " has a weight that isn't a number";
+            return false;
+        }
+        for (size_t j = 0; j < i; ++j) {
+            if (kernel[j].dx == entry.dx && kernel[j].dy == entry.dy) {
+                error = where + " appears twice";
+                return false;
+            }
+        }
+        sum += entry.weight;
+    }
+    if (sum <= 0.0f) {
+        error = "weights must add up to more than 0";
+        return false;
+    }
+    if (!normalize && sum > 2.0f) {
+        error = "weights add up to more than 2 without normalizing, so error would grow without bound";
+        return false;
+    }
+    return true;
+}
+
+bool parseKernel(const std::string& text, std::vector<KernelWeight>& kernel, std::string& error) {
+    std::vector<std::vector<float>> rows;
+    int depth = 0;
+    const char* p = text.c_str();
+    while (*p) {
+        if (*p == '[') {
+            depth++;
+            if (depth == 2) rows.emplace_back();
+            if (depth > 2) break;
+            p++;
+        } else if (*p == ']') {
+            depth--;
+            p++;
+        } else if (*p == ',' || std::isspace(static_cast<unsigned char>(*p))) {
+            p++;
+        } else {
+            char* end = nullptr;
+            float value = std::strtof(p, &end);
+            if (end == p || depth != 2) break;
+            rows.back().push_back(value);
+            p = end;
+        }
+        if (depth < 0) break;
+    }
+    if (*p || depth != 0 || rows.empty()) {
+        error = "must be a list of [dx, dy, weight] triples, e.g. [[1,0,7],[-1,1,3],[0,1,5],[1,1,1]]";
+        return false;
+    }
+
+    std::vector<KernelWeight> parsed;
+    for (const auto& row : rows) {
+        if (row.size() != 3 || row[0] != std::floor(row[0]) || row[1] != std::floor(row[1])) {
+            error = "every entry must be [dx, dy, weight] with whole pixel offsets";
+            return false;
+        }
+        parsed.push_back({static_cast<int>(row[0]), static_cast<int>(row[1]), row[2]});
+    }
+    kernel = parsed;
+    return true;
+}
+
+bool validateToneCurve(const std::vector<cv::Point2f>& points, std::string& error) {
+    if (points.size() < 2 || points.size() > static_cast<size_t>(MAX_CURVE_POINTS)) {
+        error = "needs 2 to " + std::to_string(MAX_CURVE_POINTS) + " points";
+        return false;
+    }
+    for (size_t i = 0; i < points.size(); ++i) {
+        const cv::Point2f& point = points[i];
+        if (!(point.x >= 0.0f && point.x <= 1.0f && point.y >= 0.0f && point.y <= 1.0f)) {
+            error = "points must be between 0 and 1";
+            return false;
+        }
+        if (i > 0 && point.x <= points[i - 1].x) {
+            error = "points must be in order of input, no two at the same input";
+            return false;
+        }
+    }
+    return true;
+}
+
+bool parseToneCurve(const std::string& text, std::vector<cv::Point2f>& points, std::string& error) {
+    // Same layout as a kernel, pairs instead of triples
+    std::vector<std::vector<float>> rows;
+    int depth = 0;
+    const char* p = text.c_str();
+    while (*p) {
+        if (*p == '[') {
+            depth++;
+            if (depth == 2) rows.emplace_back();
+            if (depth > 2) break;
+            p++;
+        } else if (*p == ']') {
+            depth--;
+            p++;
+        } else if (*p == ',' || std::isspace(static_cast<unsigned char>(*p))) {
+            p++;
+        } else {
+            char* end = nullptr;
+            float value = std::strtof(p, &end);
+            if (end == p || depth != 2) break;
+            rows.back().push_back(value);
+            p = end;
+        }
+        if (depth < 0) break;
+    }
+    if (*p || depth != 0 || rows.empty()) {
+        error = "must be a list of [input, output] pairs, e.g. [[0,0],[0.25,0.18],[0.75,0.82],[1,1]]";
+        return false;
+    }
+
+    std::vector<cv::Point2f> parsed;
+    for (const auto& row : rows) {
+        if (row.size() != 2) {
+            error = "every entry must be [input, output]";
+            return false;
+        }
+        parsed.emplace_back(row[0], row[1]);
+    }
+    std::sort(parsed.begin(), parsed.end(), [](const cv::Point2f& a, const cv::Point2f& b) { return a.x < b.x; });
+    if (!validateToneCurve(parsed, error)) return false;
+    points = parsed;
+    return true;
+}
+
+// Fritsch-Carlson: secant slopes, averaged into tangents at the points, which
+// are flattened at peaks and valleys and limited where they would overshoot
+float evaluateToneCurve(const std::vector<cv::Point2f>& points, float x) {
+    if (points.empty()) return x;
+    if (x <= points.front().x) return points.front().y;
+    if (x >= points.back().x) return points.back().y;
+
+    size_t count = points.size();
+    std::vector<float> secants(count - 1), tangents(count);
+    for (size_t i = 0; i + 1 < count; ++i) {
+        secants[i] = (points[i + 1].y - points[i].y) / (points[i + 1].x - points[i].x);
+    }
+    tangents[0] = secants[0];
+    tangents[count - 1] = secants[count - 2];
+    for (size_t i = 1; i + 1 < count; ++i) {
+        tangents[i] = secants[i - 1] * secants[i] <= 0.0f ? 0.0f : (secants[i - 1] + secants[i]) / 2.0f;
+    }
+    for (size_t i = 0; i + 1 < count; ++i) {
+        if (secants[i] == 0.0f) {
+            tangents[i] = tangents[i + 1] = 0.0f;
+            continue;
+        }
+        float a = tangents[i] / secants[i], b = tangents[i + 1] / secants[i];
+        float length = a * a + b * b;
+        if (length > 9.0f) {
+            float scale = 3.0f / std::sqrt(length);
+            tangents[i] = scale * a * secants[i];
+            tangents[i + 1] = scale * b * secants[i];
+        }
+    }
+
+    size_t i = 0;
+    while (x > points[i + 1].x) ++i;
+    float h = points[i + 1].x - points[i].x;
+    float t = (x - points[i].x) / h;
+    float t2 = t * t, t3 = t2 * t;
+    float y = (2 * t3 - 3 * t2 + 1) * points[i].y + (t3 - 2 * t2 + t) * h * tangents[i] +
+              (-2 * t3 + 3 * t2) * points[i + 1].y + (t3 - t2) * h * tangents[i + 1];
+    return std::clamp(y, 0.0f, 1.0f);
+}
+
+// Error diffusion with the user's kernel. One that doesn't validate (settings
+// edited by hand, say) falls back to Floyd-Steinberg
+cv::Mat customKernel(const cv::Mat& input, const Parameters& params, const BandCallback& onBand) {
+    std::string error;
+    if (!validateKernel(params.diffusionKernel, params.normalizeKernel, error)) {
+        return floydSteinberg(input, params, onBand);
+    }
+
+    cv::Mat result = roundTo8Bit(input);
+    cv::Mat errors = initialErrors(input);
+
+    Quantizer quantizer(params, result);
 
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+    std::vector<std::pair<int, int>> offsets;
+    std::vector<float> weights;
+    float sum = 0.0f;
+    for (const auto& entry : params.diffusionKernel) {
+        offsets.emplace_back(entry.dx, entry.dy);
+        weights.push_back(entry.weight);
+        sum += entry.weight;
+    }
+    if (params.normalizeKernel) {
+        for (float& weight : weights) weight /= sum;
+    }
+    bool serpentine = params.serpentine > 0.5f;
 
-    std::vector<std::pair<int, int>> offsets = {
-        {1, 0}, {2, 0},
-        {-2, 1}, {-1, 1}, {0, 1}, {1, 1}, {2, 1}
-    };
-    std::vector<float> weights = {
-        4.0f/16.0f, 1.0f/16.0f,
-        1.0f/16.0f, 1.0f/16.0f, 2.0f/16.0f, 4.0f/16.0f, 2.0f/16.0f
-    };
+    auto row = [&](int y, Quantizer& quantize, RowFront& front) {
+        bool reverse = serpentine && (y % 2 == 1);
+        int start = reverse ? input.cols - 1 : 0;
+        int end = reverse ? -1 : input.cols;
+        int step = reverse ? -1 : 1;
 
-    for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
+        for (int x = start; x != end; x += step) {
+            front.at(reverse ? input.cols - 1 - x : x);
+            if (quantize.isProtected(x, y)) continue;
             cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
             cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
 
             cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+            newPixelF = boundError(newPixelF, params);
+            cv::Vec3b newPixel = toPixel(newPixelF);
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
-
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
+            cv::Vec3b quantized = quantize(newPixel);
             result.at<cv::Vec3b>(y, x) = quantized;
+            recordVisit(result, x, y);
 
             cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
+            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, serpentine, params.errorEdge);
         }
-    }
+    };
+    diffuseRows(result, quantizer, params, kernelReach(offsets), serpentine, onBand, row);
 
     return result;
 }
 
-// Steven Pigeon dithering
-cv::Mat stevenPigeon(const cv::Mat& input, const Parameters& params) {
-    cv::Mat result = input.clone();
-    cv::Mat errors = cv::Mat::zeros(input.rows, input.cols, CV_32FC3);
-
-    std::vector<cv::Vec3b> palette = getPalette(params.paletteMode);
+// Void-and-cluster blue noise (Ulichney 1993): rank every pixel of a tileable
+// texture so that thresholding it at any level leaves evenly spread dots
+static cv::Mat voidAndCluster(int size, unsigned int seed) {
+    const int count = size * size;
+    const float sigma = 1.5f;
+
+    // Gaussian energy kernel with wrap-around distances, so the texture tiles
+    std::vector<float> kernel(count);
+    for (int dy = 0; dy < size; ++dy) {
+        for (int dx = 0; dx < size; ++dx) {
+            int wx = std::min(dx, size - dx);
+            int wy = std::min(dy, size - dy);
+            kernel[dy * size + dx] = std::exp(-(wx * wx + wy * wy) / (2.0f * sigma * sigma));
+        }
+    }
 
-    std::vector<std::pair<int, int>> offsets = {
-        {1, 0}, {2, 0},
-        {-2, 1}, {-1, 1}, {0, 1}, {1, 1}, {2, 1},
-        {-1, 2}, {0, 2}, {1, 2}
+    std::vector<char> points(count, 0);
+    std::vector<float> energy(count, 0.0f);
+
+    auto setPoint = [&](int index, bool on) {
+        points[index] = on;
+        float sign = on ? 1.0f : -1.0f;
+        int px = index % size, py = index / size;
+        for (int y = 0; y < size; ++y) {
+            const float* row = &kernel[((y - py + size) % size) * size];
+            for (int x = 0; x < size; ++x) {
+                energy[y * size + x] += sign * row[(x - px + size) % size];
+            }
+        }
     };
-    std::vector<float> weights = {
-        2.0f/14.0f, 1.0f/14.0f,
-        1.0f/14.0f, 2.0f/14.0f, 2.0f/14.0f, 2.0f/14.0f, 1.0f/14.0f,
-        1.0f/14.0f, 1.0f/14.0f, 1.0f/14.0f
+    auto tightestCluster = [&]() {
+        int best = -1;
+        for (int i = 0; i < count; ++i) {
+            if (points[i] && (best < 0 || energy[i] > energy[best])) best = i;
+        }
+        return best;
+    };
+    auto largestVoid = [&]() {
+        int best = -1;
+        for (int i = 0; i < count; ++i) {
+            if (!points[i] && (best < 0 || energy[i] < energy[best])) best = i;
+        }
+        return best;
     };
 
-    for (int y = 0; y < input.rows; ++y) {
-        for (int x = 0; x < input.cols; ++x) {
-            cv::Vec3b oldPixel = result.at<cv::Vec3b>(y, x);
-            cv::Vec3f errorVal = errors.at<cv::Vec3f>(y, x);
+    // Initial pattern: a random tenth of the pixels
+    std::mt19937 rng(seed);
+    std::vector<int> order(count);
+    for (int i = 0; i < count; ++i) order[i] = i;
+    std::shuffle(order.begin(), order.end(), rng);
 
-            cv::Vec3f newPixelF = cv::Vec3f(oldPixel[0], oldPixel[1], oldPixel[2]) + errorVal;
-            newPixelF = cv::Vec3f(
-                std::clamp(newPixelF[0], 0.0f, 255.0f),
-                std::clamp(newPixelF[1], 0.0f, 255.0f),
-                std::clamp(newPixelF[2], 0.0f, 255.0f)
-            );
+    int initial = std::max(1, count / 10);
+    for (int i = 0; i < initial; ++i) {
+        setPoint(order[i], true);
+    }
 
-            cv::Vec3b newPixel(
-                static_cast<uchar>(newPixelF[0]),
-                static_cast<uchar>(newPixelF[1]),
-                static_cast<uchar>(newPixelF[2])
-            );
+    // Even it out: move the tightest cluster into the largest void until that
+    // no longer changes anything
+    for (int iteration = 0; iteration < count; ++iteration) {
+        int cluster = tightestCluster();
+        setPoint(cluster, false);
+        int gap = largestVoid();
+        setPoint(gap, true);
+        if (gap == cluster) break;
+    }
 
-            cv::Vec3b quantized = findClosestColor(newPixel, palette);
-            result.at<cv::Vec3b>(y, x) = quantized;
+    std::vector<int> rank(count);
+    std::vector<char> prototypePoints = points;
+    std::vector<float> prototypeEnergy = energy;
 
-            cv::Vec3f quantError = newPixelF - cv::Vec3f(quantized[0], quantized[1], quantized[2]);
-            diffuseError(errors, x, y, quantError, offsets, weights, params.strength, false);
-        }
+    // Rank the initial points by removing the tightest cluster first
+    for (int r = initial - 1; r >= 0; --r) {
+        int cluster = tightestCluster();
+        setPoint(cluster, false);
+        rank[cluster] = r;
     }
 
-    return result;
+    // Then fill the largest remaining void until every pixel is ranked
+    points = prototypePoints;
+    energy = prototypeEnergy;
+    for (int r = initial; r < count; ++r) {
+        int gap = largestVoid();
+        setPoint(gap, true);
+        rank[gap] = r;
+    }
+
+    cv::Mat texture(size, size, CV_32F);
+    for (int i = 0; i < count; ++i) {
+        texture.at<float>(i / size, i % size) = (rank[i] + 0.5f) / count;
+    }
+    return texture;
 }
 
-// Generate blue noise texture
-cv::Mat generateBlueNoiseTexture(int size, unsigned int seed) {
-    // Simplified blue noise generation using void-and-cluster method approximation
-    cv::Mat noise = cv::Mat::zeros(size, size, CV_32F);
-    std::mt19937 rng(seed);
-    std::uniform_real_distribution<float> dist(0.0f, 1.0f);
+// Every seed makes a new texture, so only the most recently used few are kept
+static const size_t blueNoiseCacheSize = 8;
 
-    // Start with white noise
-    for (int y = 0; y < size; ++y) {
-        for (int x = 0; x < size; ++x) {
-            noise.at<float>(y, x) = dist(rng);
-        }
+// Generate blue noise texture. Generation is slow, so the textures for the last
+// few size/seed pairs are kept and reused. Callers get their own copy so
+// writing into it can't change the cached texture
+cv::Mat generateBlueNoiseTexture(int size, unsigned int seed) {
+    static std::mutex cacheMutex;
+    static std::list<std::pair<std::pair<int, unsigned int>, cv::Mat>> cache;    // Most recently used first
+
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    auto found = std::find_if(cache.begin(), cache.end(),
+                              [&](const auto& entry) { return entry.first == std::make_pair(size, seed); });
+    if (found != cache.end()) {
+        cache.splice(cache.begin(), cache, found);
+    } else {
+        cache.emplace_front(std::make_pair(size, seed), voidAndCluster(size, seed));
+        if (cache.size() > blueNoiseCacheSize) cache.pop_back();
     }
+    return cache.front().second.clone();
+}
 
-    // Apply Gaussian blur to create more uniform distribution
-    cv::GaussianBlur(noise, noise, cv::Size(5, 5), 1.0);
-    cv::normalize(noise, noise, 0, 1, cv::NORM_MINMAX);
+// Hash every setting that affects the output (FNV-1a), for caching results.
+// Keep in sync with Parameters
+uint64_t hashParameters(const Parameters& params) {
+    uint64_t hash = 14695981039346656037ULL;
+    auto mix = [&hash](const void* data, size_t size) {
+        const unsigned char* bytes = static_cast<const unsigned char*>(data);
+        for (size_t i = 0; i < size; ++i) {
+            hash = (hash ^ bytes[i]) * 1099511628211ULL;
+        }
+    };
+    auto add = [&mix](const auto& value) { mix(&value, sizeof(value)); };
 
-    return noise;
+    add(params.algorithm);
+    mix(params.plugin.data(), params.plugin.size());
+    for (const auto& option : params.pluginOptions) {
+        mix(option.first.data(), option.first.size() + 1);
+        mix(option.second.data(), option.second.size() + 1);
+    }
+    add(params.paletteMode);
+    for (const auto& color : params.customPalette) add(color);
+    add(params.paletteSize);
+    add(params.paletteMethod);
+    add(params.rounding);
+    add(params.colorDistance);
+    add(params.renderingIntent);
+    add(params.workingSpace);
+    add(params.grayConversion);
+    add(params.grayWeights);
+    add(params.inkColor);
+    add(params.paperColor);
+    add(params.colorMode);
+    add(params.pixelFormat);
+    for (const auto& color : params.inks) add(color);
+    for (const auto& color : params.protectedColors) add(color);
+    add(params.strength);
+    add(params.threshold);
+    add(params.autoThreshold);
+    add(params.localWindow);
+    add(params.localK);
+    add(params.edgeSensitivity);
+    add(params.curve);
+    add(params.riemersmaHistory);
+    add(params.riemersmaDecay);
+    add(params.serpentine);
+    add(params.errorEdge);
+    add(params.clampError);
+    add(params.colorization);
+    add(params.levels);
+    add(params.channelSpace);
+    for (const auto& channel : params.channelOverrides) {
+        add(channel.enabled);
+        add(channel.algorithm);
+        add(channel.strength);
+        add(channel.threshold);
+    }
+    add(params.gamma);
+    add(params.levelsBlack);
+    add(params.levelsWhite);
+    add(params.levelsMidtone);
+    for (const auto& point : params.toneCurve) {
+        add(point.x);
+        add(point.y);
+    }
+    add(params.toneCurve.size());
+    add(params.contrast);
+    add(params.brightness);
+    add(params.saturation);
+    add(params.exposure);
+    add(params.denoise);
+    add(params.denoiseStrength);
+    add(params.blurRadius);
+    add(params.sharpenAmount);
+    add(params.sharpenRadius);
+    add(params.linearize);
+    add(params.linearPalette);
+    add(params.clipBlack);
+    add(params.clipWhite);
+    add(params.highlightProtect);
+    add(params.shadowProtect);
+    add(params.dotGain);
+    add(params.bayerSize);
+    add(params.bayerClustered);
+    add(params.ditherAngle);
+    mix(params.matrix.data(), params.matrix.size());
+    for (const auto& weight : params.diffusionKernel) {
+        add(weight.dx);
+        add(weight.dy);
+        add(weight.weight);
+    }
+    add(params.diffusionKernel.size());
+    add(params.normalizeKernel);
+    add(params.patternMatrix.rows);
+    add(params.patternMatrix.cols);
+    for (int y = 0; y < params.patternMatrix.rows; ++y) {
+        mix(params.patternMatrix.ptr<float>(y), params.patternMatrix.cols * sizeof(float));
+    }
+    add(params.seed);
+    add(params.useBlueNoise);
+    mix(params.noiseTexture.data(), params.noiseTexture.size() + 1);
+    if (!params.noiseTexture.empty()) {
+        // Editing the texture file in place changes the result too
+        std::error_code ec;
+        add(std::filesystem::last_write_time(params.noiseTexture, ec).time_since_epoch().count());
+        add(std::filesystem::file_size(params.noiseTexture, ec));
+    }
+    add(params.ditherScale);
+    add(params.pixelate);
+    add(params.blend);
+    add(params.halftoneShape);
+    add(params.halftoneCellSize);
+    add(params.screenLpi);
+    add(params.printDpi);
+    add(params.screenAngles);
+    add(params.grayScreenAngle);
+    add(params.stippleDots);
+    add(params.stippleDotSize);
+    add(params.stippleIterations);
+    add(params.phaseX);
+    add(params.phaseY);
+    for (const auto& stage : params.stages) {
+        add(stage.type);
+        add(stage.amount);
+        add(stage.mix);
+        uint64_t stageHash = stage.params ? hashParameters(*stage.params) : 0;
+        add(stageHash);
+    }
+    return hash;
 }
 
 // Get palette based on mode
@@ -1101,6 +3629,121 @@ std::vector<cv::Vec3b> getPalette(PaletteMode mode) {
     return palette;
 }
 
+// K-means: cluster colors and use the cluster centers
+static std::vector<cv::Vec3b> kmeansPalette(const cv::Mat& image, int colors, unsigned int seed) {
+    cv::Mat samples;
+    image.reshape(1, static_cast<int>(image.total())).convertTo(samples, CV_32F);
+    colors = std::clamp(colors, 1, std::min(256, samples.rows));
+
+    // Seed the k-means++ initialization so results are reproducible
+    cv::theRNG().state = seed;
+    cv::Mat labels, centers;
+    cv::kmeans(samples, colors, labels,
+               cv::TermCriteria(cv::TermCriteria::EPS + cv::TermCriteria::COUNT, 20, 1.0),
+               3, cv::KMEANS_PP_CENTERS, centers);
+
+    std::vector<cv::Vec3b> palette;
+    for (int i = 0; i < centers.rows; ++i) {
+        palette.push_back(cv::Vec3b(
+            cv::saturate_cast<uchar>(centers.at<float>(i, 0)),
+            cv::saturate_cast<uchar>(centers.at<float>(i, 1)),
+            cv::saturate_cast<uchar>(centers.at<float>(i, 2))
+        ));
+    }
+    return palette;
+}
+
+// Median cut: repeatedly split the box with the widest channel range at its
+// median, then use the average color of each box
+static std::vector<cv::Vec3b> medianCutPalette(const cv::Mat& image, int colors) {
+    std::vector<cv::Vec3b> pixels(image.begin<cv::Vec3b>(), image.end<cv::Vec3b>());
+    if (pixels.empty()) return {};
+
+    std::vector<std::pair<size_t, size_t>> boxes = {{0, pixels.size()}};
+    while (static_cast<int>(boxes.size()) < colors) {
+        int bestBox = -1;
+        int bestChannel = 0;
+        int bestRange = 0;
+
+        for (size_t b = 0; b < boxes.size(); ++b) {
+            if (boxes[b].second - boxes[b].first < 2) continue;
+
+            cv::Vec3b low(255, 255, 255), high(0, 0, 0);
+            for (size_t i = boxes[b].first; i < boxes[b].second; ++i) {
+                for (int c = 0; c < 3; ++c) {
+                    low[c] = std::min(low[c], pixels[i][c]);
+                    high[c] = std::max(high[c], pixels[i][c]);
+                }
+            }
+            for (int c = 0; c < 3; ++c) {
+                if (high[c] - low[c] > bestRange) {
+                    bestRange = high[c] - low[c];
+                    bestChannel = c;
+                    bestBox = static_cast<int>(b);
+                }
+            }
+        }
+        if (bestBox < 0) break;  // Every box holds a single color
+
+        auto [first, last] = boxes[bestBox];
+        size_t middle = first + (last - first) / 2;
+        std::nth_element(pixels.begin() + first, pixels.begin() + middle, pixels.begin() + last,
+                         [bestChannel](const cv::Vec3b& a, const cv::Vec3b& b) {
+                             return a[bestChannel] < b[bestChannel];
+                         });
+        boxes[bestBox] = {first, middle};
+        boxes.push_back({middle, last});
+    }
+
+    std::vector<cv::Vec3b> palette;
+    for (const auto& [first, last] : boxes) {
+        cv::Vec3d sum(0, 0, 0);
+        for (size_t i = first; i < last; ++i) {
+            sum += cv::Vec3d(pixels[i][0], pixels[i][1], pixels[i][2]);
+        }
+        double count = static_cast<double>(last - first);
+        palette.push_back(cv::Vec3b(
+            cv::saturate_cast<uchar>(sum[0] / count),
+            cv::saturate_cast<uchar>(sum[1] / count),
+            cv::saturate_cast<uchar>(sum[2] / count)
+        ));
+    }
+    return palette;
+}
+
+// Generate a palette from the image's dominant colors
+std::vector<cv::Vec3b> generatePalette(const cv::Mat& image, int colors,
+                                       PaletteMethod method, unsigned int seed) {
+    // Cost grows with pixel count, a downscaled copy is plenty
+    const double maxPixels = 256.0 * 256.0;
+    cv::Mat small;
+    if (image.total() > maxPixels) {
+        double scale = std::sqrt(maxPixels / image.total());
+        cv::resize(image, small, cv::Size(), scale, scale, cv::INTER_AREA);
+    } else {
+        small = image.clone();
+    }
+
+    switch (method) {
+        case PaletteMethod::MEDIAN_CUT:
+            return medianCutPalette(small, colors);
+        case PaletteMethod::KMEANS:
+        default:
+            return kmeansPalette(small, colors, seed);
+    }
+}
+
+// Get palette for the given parameters, including user-supplied custom palettes
+std::vector<cv::Vec3b> getPalette(const Parameters& params) {
+    if (params.paletteMode == PaletteMode::CUSTOM && !params.customPalette.empty()) {
+        return params.customPalette;
+    }
+    if (params.paletteMode == PaletteMode::MONOCHROME) {
+        return {params.inkColor, params.paperColor};
+    }
+    return getPalette(params.paletteMode);
+}
+
 // Find closest color in palette
 cv::Vec3b findClosestColor(const cv::Vec3b& color, const std::vector<cv::Vec3b>& palette) {
     float minDist = std::numeric_limits<float>::max();
@@ -1120,6 +3763,306 @@ cv::Vec3b findClosestColor(const cv::Vec3b& color, const std::vector<cv::Vec3b>&
     return closest;
 }
 
+float colorDifference(const cv::Vec3b& a, const cv::Vec3b& b) {
+    return ciede2000(bgrToLab(a), bgrToLab(b));
+}
+
+// Plain thresholding: every pixel takes its nearest palette color, no dithering
+cv::Mat simpleThreshold(const cv::Mat& input, const Parameters& params) {
+    cv::Mat result;
+    if (ditherThresholdMapGpu(input, params, cv::Mat(1, 1, CV_32F, cv::Scalar(0.5)), 1, result)) return result;
+    return ditherThresholdMap(input, params, [](int, int) { return 0.5f; });
+}
+
+// Local thresholding: each pixel is compared against a threshold T from the mean
+// and standard deviation of luma in the window around it. Like the global
+// threshold, T moves the rounding point, so with black and white the output is
+// light exactly where luma >= T, and other palettes get a local tone shift
+static cv::Mat localThreshold(const cv::Mat& input, const Parameters& params, Algorithm method) {
+    int window = std::max(3, params.localWindow | 1);
+    float k = params.localK;
+
+    cv::Mat gray, grayF, mean, squareMean;
+    cv::cvtColor(input, gray, cv::COLOR_BGR2GRAY);
+    gray.convertTo(grayF, CV_32F);
+    cv::boxFilter(grayF, mean, CV_32F, cv::Size(window, window), cv::Point(-1, -1), true, cv::BORDER_REFLECT);
+    cv::boxFilter(grayF.mul(grayF), squareMean, CV_32F, cv::Size(window, window), cv::Point(-1, -1), true,
+                  cv::BORDER_REFLECT);
+
+    Quantizer quantize(params, input);
+    cv::Mat result = input.clone();
+
+    for (int y = 0; y < input.rows; ++y) {
+        for (int x = 0; x < input.cols; ++x) {
+            if (quantize.isProtected(x, y)) continue;
+
+            float m = mean.at<float>(y, x);
+            float deviation = std::sqrt(std::max(0.0f, squareMean.at<float>(y, x) - m * m));
+            float threshold;
+            switch (method) {
+                case Algorithm::SAUVOLA:
+                    threshold = m * (1.0f + k * (deviation / 128.0f - 1.0f));
+                    break;
+                case Algorithm::NIBLACK:
+                    threshold = m - k * deviation;
+                    break;
+                case Algorithm::BRADLEY:
+                default:
+                    threshold = m * (1.0f - k);
+                    break;
+            }
+
+            cv::Vec3b pixel = input.at<cv::Vec3b>(y, x);
+            float shift = 127.5f - threshold;
+            cv::Vec3b shifted(
+                cv::saturate_cast<uchar>(pixel[0] + shift),
+                cv::saturate_cast<uchar>(pixel[1] + shift),
+                cv::saturate_cast<uchar>(pixel[2] + shift)
+            );
+            result.at<cv::Vec3b>(y, x) = quantize(shifted);
+        }
+    }
+
+    return result;
+}
+
+// Sauvola: T = mean * (1 + k * (deviation / 128 - 1)); flat regions fall just
+// below the mean, so paper stays clean (k around 0.2-0.5)
+cv::Mat sauvola(const cv::Mat& input, const Parameters& params) {
+    return localThreshold(input, params, Algorithm::SAUVOLA);
+}
+
+// Niblack: T = mean - k * deviation (k around 0.2); keeps faint strokes but
+// picks up noise in empty areas
+cv::Mat niblack(const cv::Mat& input, const Parameters& params) {
+    return localThreshold(input, params, Algorithm::NIBLACK);
+}
+
+// Bradley-Roth: T = mean * (1 - k), dark only when more than k below the window
+// average (k around 0.15)
+cv::Mat bradley(const cv::Mat& input, const Parameters& params) {
+    return localThreshold(input, params, Algorithm::BRADLEY);
+}
+
+// Flat colors, to see how much the dithering itself adds. The threshold was
+// left out in preprocessing; floor, ceil and stochastic rounding would still
+// shift or scatter colors, so pixels always take the nearest one
+cv::Mat posterize(const cv::Mat& input, const Parameters& params) {
+    Parameters nearest = params;
+    nearest.rounding = RoundingMode::NEAREST;
+    return simpleThreshold(input, nearest);
+}
+
+// Stippling places its dots over the whole image at once, see stipple.h
+cv::Mat stipple(const cv::Mat& input, const Parameters& params) {
+    return Stipple::drawStipples(Stipple::placeStipples(input, params));
+}
+
+Histogram computeHistogram(const cv::Mat& image) {
+    Histogram histogram;
+    histogram.luma.assign(256, 0);
+    for (auto& channel : histogram.channels) channel.assign(256, 0);
+    if (image.empty()) return histogram;
+
+    cv::Mat rounded = roundTo8Bit(image);
+    cv::Mat gray;
+    cv::cvtColor(rounded, gray, cv::COLOR_BGR2GRAY);
+    for (int y = 0; y < rounded.rows; ++y) {
+        const cv::Vec3b* row = rounded.ptr<cv::Vec3b>(y);
+        const uchar* grayRow = gray.ptr<uchar>(y);
+        for (int x = 0; x < rounded.cols; ++x) {
+            histogram.luma[grayRow[x]]++;
+            for (int c = 0; c < 3; ++c) histogram.channels[c][row[x][c]]++;
+        }
+    }
+    return histogram;
+}
+
+// Otsu's method: the luma level that best splits the histogram into two classes
+// (largest between-class variance), as a 0-1 threshold
+float computeOtsuThreshold(const cv::Mat& image) {
+    if (image.empty()) return 0.5f;
+
+    std::vector<int> histogram = computeHistogram(image).luma;
+
+    double total = static_cast<double>(image.total());
+    double sumAll = 0.0;
+    for (int i = 0; i < 256; ++i) sumAll += i * static_cast<double>(histogram[i]);
+
+    double weightDark = 0.0, sumDark = 0.0, bestVariance = -1.0;
+    int best = 127;
+    for (int t = 0; t < 255; ++t) {
+        weightDark += histogram[t];
+        sumDark += t * static_cast<double>(histogram[t]);
+        double weightLight = total - weightDark;
+        if (weightDark <= 0.0 || weightLight <= 0.0) continue;
+
+        double meanDark = sumDark / weightDark;
+        double meanLight = (sumAll - sumDark) / weightLight;
+        double variance = weightDark * weightLight * (meanDark - meanLight) * (meanDark - meanLight);
+        if (variance > bestVariance) {
+            bestVariance = variance;
+            best = t;
+        }
+    }
+
+    // Levels up to and including best are the dark class
+    return (best + 0.5f) / 255.0f;
+}
+
+int getBayerSize(Algorithm algo) {
+    switch (algo) {
+        case Algorithm::ORDERED_BAYER_2X2: return 2;
+        case Algorithm::ORDERED_BAYER_4X4: return 4;
+        case Algorithm::ORDERED_BAYER_8X8: return 8;
+        case Algorithm::ORDERED_BAYER_16X16: return 16;
+        default: return 0;
+    }
+}
+
+// Algorithms too slow for interactive preview of very large images
+bool isSlowAlgorithm(Algorithm algo) {
+    switch (algo) {
+        case Algorithm::JARVIS_JUDICE_NINKE:
+        case Algorithm::STUCKI:
+        case Algorithm::SIERRA:
+        case Algorithm::RIEMERSMA:
+        case Algorithm::GRADIENT_BASED:
+        case Algorithm::VARIABLE_ERROR_DIFFUSION:
+        case Algorithm::OSTROMOUKHOV:
+        case Algorithm::STIPPLE:
+            return true;
+        default:
+            return false;
+    }
+}
+
+// Algorithms that diffuse quantization error through a float buffer, and so
+// accept high-precision (CV_32FC3) input
+bool isErrorDiffusion(Algorithm algo) {
+    switch (algo) {
+        case Algorithm::FLOYD_STEINBERG:
+        case Algorithm::ATKINSON:
+        case Algorithm::JARVIS_JUDICE_NINKE:
+        case Algorithm::STUCKI:
+        case Algorithm::BURKES:
+        case Algorithm::SIERRA:
+        case Algorithm::SIERRA_TWO_ROW:
+        case Algorithm::SIERRA_LITE:
+        case Algorithm::DOT_DIFFUSION:
+        case Algorithm::GRADIENT_BASED:
+        case Algorithm::VARIABLE_ERROR_DIFFUSION:
+        case Algorithm::OSTROMOUKHOV:
+        case Algorithm::FAN:
+        case Algorithm::SHIAU_FAN:
+        case Algorithm::STEVENPIGEON:
+        case Algorithm::STRUCTURE_AWARE:
+        case Algorithm::CUSTOM_KERNEL:
+            return true;
+        default:
+            return false;
+    }
+}
+
+const std::vector<AlgorithmInfo>& getAlgorithmLibrary() {
+    using C = AlgorithmCategory;
+    enum Uses { STRENGTH = 1, THRESHOLD = 2, SERPENTINE = 4, SEED = 8, SCALE = 16, PHASE = 32, EDGES = 64 };
+    auto info = [](Algorithm algo, AlgorithmCategory category, int uses) {
+        return AlgorithmInfo{algo, getAlgorithmName(algo), category,
+                             (uses & STRENGTH) != 0, (uses & THRESHOLD) != 0, (uses & SERPENTINE) != 0,
+                             (uses & SEED) != 0, (uses & SCALE) != 0, (uses & PHASE) != 0, (uses & EDGES) != 0};
+    };
+    const int diffusion = STRENGTH | THRESHOLD | EDGES;
+    const int ordered = STRENGTH | THRESHOLD | SCALE | PHASE;
+
+    static const std::vector<AlgorithmInfo> library = {
+        info(Algorithm::FLOYD_STEINBERG, C::ERROR_DIFFUSION, diffusion | SERPENTINE),
+        info(Algorithm::ATKINSON, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::JARVIS_JUDICE_NINKE, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::STUCKI, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::BURKES, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::SIERRA, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::SIERRA_TWO_ROW, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::SIERRA_LITE, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::ORDERED_BAYER_2X2, C::ORDERED, ordered),
+        info(Algorithm::ORDERED_BAYER_4X4, C::ORDERED, ordered),
+        info(Algorithm::ORDERED_BAYER_8X8, C::ORDERED, ordered),
+        info(Algorithm::ORDERED_BAYER_16X16, C::ORDERED, ordered),
+        info(Algorithm::BLUE_NOISE, C::ORDERED, STRENGTH | THRESHOLD | SEED | PHASE),
+        info(Algorithm::WHITE_NOISE, C::NOISE, STRENGTH | THRESHOLD | SEED),
+        info(Algorithm::RANDOM_DITHER, C::NOISE, STRENGTH | THRESHOLD | SEED),
+        info(Algorithm::PATTERN_DITHER, C::ORDERED, ordered),
+        // No error buffer: dot diffusion keeps no error, Riemersma carries it along the curve
+        info(Algorithm::DOT_DIFFUSION, C::ERROR_DIFFUSION, STRENGTH | THRESHOLD),
+        info(Algorithm::RIEMERSMA, C::ERROR_DIFFUSION, STRENGTH | THRESHOLD),
+        info(Algorithm::GRADIENT_BASED, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::VARIABLE_ERROR_DIFFUSION, C::ERROR_DIFFUSION, diffusion | SEED),
+        info(Algorithm::OSTROMOUKHOV, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::FAN, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::SHIAU_FAN, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::STEVENPIGEON, C::ERROR_DIFFUSION, diffusion),
+        info(Algorithm::ORDERED_MATRIX, C::ORDERED, ordered),
+        info(Algorithm::INTERLEAVED_GRADIENT_NOISE, C::ORDERED, STRENGTH | THRESHOLD | PHASE),
+        info(Algorithm::HALFTONE, C::HALFTONE, STRENGTH | THRESHOLD | PHASE),
+        info(Algorithm::THRESHOLD, C::THRESHOLD, THRESHOLD),
+        // The local threshold replaces the global one
+        info(Algorithm::SAUVOLA, C::THRESHOLD, 0),
+        info(Algorithm::NIBLACK, C::THRESHOLD, 0),
+        info(Algorithm::BRADLEY, C::THRESHOLD, 0),
+        info(Algorithm::STRUCTURE_AWARE, C::ERROR_DIFFUSION, diffusion | SERPENTINE),
+        info(Algorithm::POSTERIZE, C::THRESHOLD, 0),
+        info(Algorithm::STIPPLE, C::HALFTONE, SEED),
+        info(Algorithm::CUSTOM_KERNEL, C::ERROR_DIFFUSION, diffusion | SERPENTINE),
+    };
+    return library;
+}
+
+const AlgorithmInfo& getAlgorithmInfo(Algorithm algo) {
+    const auto& library = getAlgorithmLibrary();
+    size_t index = static_cast<size_t>(algo);
+    return index < library.size() ? library[index] : library[0];
+}
+
+Parameters getRecommendedParameters(const Parameters& current, Algorithm algo) {
+    const Parameters defaults;
+    Parameters params = current;
+    params.algorithm = algo;
+    params.plugin.clear();
+
+    // Strength scales diffused error in one family and threshold amplitude in
+    // another, so values only carry over between algorithms of the same family
+    if (getAlgorithmInfo(algo).category != getAlgorithmInfo(current.algorithm).category) {
+        params.strength = defaults.strength;
+        if (!params.autoThreshold) params.threshold = defaults.threshold;
+        params.serpentine = defaults.serpentine;
+        params.ditherScale = defaults.ditherScale;
+    }
+
+    switch (algo) {
+        case Algorithm::HALFTONE:
+            // Smaller cells can't grow dots through enough sizes to show tones
+            if (params.halftoneCellSize < 6.0f) params.halftoneCellSize = defaults.halftoneCellSize;
+            break;
+        case Algorithm::SAUVOLA:
+        case Algorithm::NIBLACK:
+        case Algorithm::BRADLEY:
+            // k is a deviation factor for Sauvola and Niblack but a fraction of
+            // the mean for Bradley, where the paper's 15% works best
+            if (algo != current.algorithm) {
+                params.localK = algo == Algorithm::BRADLEY ? 0.15f : defaults.localK;
+            }
+            break;
+        default:
+            break;
+    }
+    return params;
+}
+
+// Fast algorithm with a similar look, used in place of a slow one for preview
+Algorithm getPreviewProxy(Algorithm algo) {
+    return isSlowAlgorithm(algo) ? Algorithm::FLOYD_STEINBERG : algo;
+}
+
 // Get algorithm name
 std::string getAlgorithmName(Algorithm algo) {
     switch (algo) {
@@ -1147,6 +4090,28 @@ std::string getAlgorithmName(Algorithm algo) {
         case Algorithm::FAN: return "Fan";
         case Algorithm::SHIAU_FAN: return "Shiau-Fan";
         case Algorithm::STEVENPIGEON: return "Steven Pigeon";
+        case Algorithm::ORDERED_MATRIX: return "Ordered Matrix";
+        case Algorithm::INTERLEAVED_GRADIENT_NOISE: return "Interleaved Gradient Noise";
+        case Algorithm::HALFTONE: return "Halftone";
+        case Algorithm::THRESHOLD: return "Threshold";
+        case Algorithm::SAUVOLA: return "Sauvola";
+        case Algorithm::NIBLACK: return "Niblack";
+        case Algorithm::BRADLEY: return "Bradley";
+        case Algorithm::STRUCTURE_AWARE: return "Structure-Aware";
+        case Algorithm::POSTERIZE: return "Posterize";
+        case Algorithm::STIPPLE: return "Stippling";
+        case Algorithm::CUSTOM_KERNEL: return "Custom Kernel";
+        default: return "Unknown";
+    }
+}
+
+std::string getAlgorithmCategoryName(AlgorithmCategory category) {
+    switch (category) {
+        case AlgorithmCategory::ERROR_DIFFUSION: return "Error Diffusion";
+        case AlgorithmCategory::ORDERED: return "Ordered";
+        case AlgorithmCategory::NOISE: return "Noise";
+        case AlgorithmCategory::HALFTONE: return "Halftone";
+        case AlgorithmCategory::THRESHOLD: return "Threshold";
         default: return "Unknown";
     }
 }
@@ -1164,6 +4129,98 @@ std::string getPaletteModeName(PaletteMode mode) {
         case PaletteMode::GAMEBOY: return "Game Boy";
         case PaletteMode::PICO8: return "PICO-8";
         case PaletteMode::CUSTOM: return "Custom";
+        case PaletteMode::ADAPTIVE: return "Adaptive";
+        default: return "Unknown";
+    }
+}
+
+// Get palette generation method name
+std::string getPaletteMethodName(PaletteMethod method) {
+    switch (method) {
+        case PaletteMethod::KMEANS: return "K-Means";
+        case PaletteMethod::MEDIAN_CUT: return "Median Cut";
+        default: return "Unknown";
+    }
+}
+
+// Get rounding mode name
+std::string getRoundingModeName(RoundingMode mode) {
+    switch (mode) {
+        case RoundingMode::NEAREST: return "Nearest";
+        case RoundingMode::FLOOR: return "Floor";
+        case RoundingMode::CEIL: return "Ceil";
+        case RoundingMode::STOCHASTIC: return "Stochastic";
+        default: return "Unknown";
+    }
+}
+
+// Get halftone shape name
+std::string getHalftoneShapeName(HalftoneShape shape) {
+    switch (shape) {
+        case HalftoneShape::CIRCLE: return "Circle";
+        case HalftoneShape::DIAMOND: return "Diamond";
+        default: return "Unknown";
+    }
+}
+
+// Get color mode name
+std::string getColorModeName(ColorMode mode) {
+    switch (mode) {
+        case ColorMode::RGB: return "RGB";
+        case ColorMode::CMYK: return "CMYK";
+        case ColorMode::MULTI_INK: return "Multi-Ink";
+        case ColorMode::PIXEL_FORMAT: return "Pixel Format";
+        default: return "Unknown";
+    }
+}
+
+// Get pixel format name
+std::string getPixelFormatName(PixelFormat format) {
+    switch (format) {
+        case PixelFormat::RGB565: return "RGB565";
+        case PixelFormat::RGB332: return "RGB332";
+        case PixelFormat::RGBA4444: return "RGBA4444";
+        default: return "Unknown";
+    }
+}
+
+// Name of a plane in the per-channel space
+std::string getChannelName(ChannelSpace space, int channel) {
+    static const char* bgr[] = {"Blue", "Green", "Red"};
+    static const char* ycrcb[] = {"Luma (Y)", "Chroma (Cr)", "Chroma (Cb)"};
+    if (channel < 0 || channel > 2) return "Unknown";
+    return space == ChannelSpace::YCRCB ? ycrcb[channel] : bgr[channel];
+}
+
+std::string getStageTypeName(StageType type) {
+    switch (type) {
+        case StageType::DITHER: return "Dither";
+        case StageType::BLUR: return "Blur";
+        case StageType::SHARPEN: return "Sharpen";
+        default: return "Unknown";
+    }
+}
+
+// Get color distance name
+std::string getColorDistanceName(ColorDistance distance) {
+    switch (distance) {
+        case ColorDistance::RGB: return "RGB";
+        case ColorDistance::WEIGHTED_RGB: return "Weighted RGB";
+        case ColorDistance::CIE76: return "CIE76";
+        case ColorDistance::CIEDE2000: return "CIEDE2000";
+        default: return "Unknown";
+    }
+}
+
+std::string getGrayConversionName(GrayConversion conversion) {
+    switch (conversion) {
+        case GrayConversion::REC601: return "Rec. 601";
+        case GrayConversion::REC709: return "Rec. 709";
+        case GrayConversion::AVERAGE: return "Average";
+        case GrayConversion::RED: return "Red Channel";
+        case GrayConversion::GREEN: return "Green Channel";
+        case GrayConversion::BLUE: return "Blue Channel";
+        case GrayConversion::CUSTOM: return "Custom";
         default: return "Unknown";
     }
 }
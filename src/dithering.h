@@ -1,6 +1,11 @@
 #pragma once
 
 #include <opencv2/opencv.hpp>
+#include <array>
+#include <cstdint>
+#include <functional>
+#include <map>
+#include <memory>
 #include <vector>
 #include <string>
 #include <cmath>
@@ -33,7 +38,42 @@ enum class Algorithm {
     OSTROMOUKHOV,
     FAN,
     SHIAU_FAN,
-    STEVENPIGEON
+    STEVENPIGEON,
+    ORDERED_MATRIX,     // Threshold matrix from the library, see matrices.h
+    INTERLEAVED_GRADIENT_NOISE,
+    HALFTONE,           // Amplitude-modulated screen with rotatable angles
+    THRESHOLD,          // Plain threshold, no dithering
+    SAUVOLA,            // Local threshold from window mean and deviation, for unevenly lit scans
+    NIBLACK,            // Local threshold at window mean minus k deviations
+    BRADLEY,            // Local threshold k below the window mean
+    STRUCTURE_AWARE,    // Error diffusion that keeps error from crossing edges, kernel chosen by tone
+    POSTERIZE,          // Nearest color only, ignoring threshold and rounding: the result without any dithering
+    STIPPLE,            // Dots of the darkest color spread by weighted Voronoi relaxation, sized by tone
+    CUSTOM_KERNEL       // Error diffusion with Parameters::diffusionKernel
+};
+
+// Algorithm families, for grouping in menus
+enum class AlgorithmCategory {
+    ERROR_DIFFUSION,    // Pass quantization error on to neighboring pixels
+    ORDERED,            // Compare against a tiled threshold matrix or texture
+    NOISE,              // Compare against random noise
+    HALFTONE,           // Grow dots on a screen
+    THRESHOLD           // Round every pixel on its own, no dithering
+};
+
+// What an algorithm is and which general settings change its output. Settings
+// specific to one algorithm (matrix, curve, halftone cells, ...) aren't listed
+struct AlgorithmInfo {
+    Algorithm algorithm;
+    std::string name;               // Display name, as getAlgorithmName
+    AlgorithmCategory category;
+    bool usesStrength;              // strength
+    bool usesThreshold;             // threshold and autoThreshold
+    bool usesSerpentine;            // serpentine
+    bool usesSeed;                  // seed (noise); stochastic rounding and adaptive palettes use it for any algorithm
+    bool usesPatternScale;          // ditherScale
+    bool usesPhase;                 // phaseX, phaseY
+    bool usesErrorEdges;            // errorEdge, clampError
 };
 
 // Palette modes
@@ -47,42 +87,363 @@ enum class PaletteMode {
     VGA,
     GAMEBOY,
     PICO8,
-    CUSTOM
+    CUSTOM,
+    ADAPTIVE            // Generated from the image's dominant colors
+};
+
+// How pixels are rounded to palette colors
+enum class RoundingMode {
+    NEAREST,            // Closest palette color
+    FLOOR,              // Closest color no brighter than the pixel
+    CEIL,               // Closest color no darker than the pixel
+    STOCHASTIC          // Random pick between the two closest, weighted by distance
+};
+
+// Color difference used to pick the nearest palette color
+enum class ColorDistance {
+    RGB,                // Euclidean distance on sRGB values
+    WEIGHTED_RGB,       // Weighted "redmean" RGB approximation
+    CIE76,              // Euclidean distance in CIELAB
+    CIEDE2000           // CIEDE2000 perceptual difference
+};
+
+// Color space that quantization and error diffusion work in
+enum class WorkingSpace {
+    RGB,                // sRGB values as they are
+    LAB,                // CIELAB, perceptually uniform
+    YCRCB,              // Luma and chroma
+    HSL                 // Hue, lightness, saturation (hue wraps, so errors can jump across red)
+};
+
+// One neighbor of a user error diffusion kernel: the pixel dx right and dy down
+// (dx mirrored on reversed serpentine rows) gets this share of the error
+struct KernelWeight {
+    int dx;
+    int dy;
+    float weight;
+};
+
+// Where error diffused past the left or right edge of the image goes. Error
+// past the bottom row, or landing on a pixel already quantized, is lost either way
+enum class ErrorEdge {
+    DISCARD,            // Dropped, so edge columns get less error than the rest
+    REFLECT,            // Mirrored back into the image
+    WRAP                // Onto the opposite edge, as if the image were tiled horizontally
+};
+
+// Noise filter run on the input first, so sensor noise isn't dithered into
+// speckle. Both keep edges, unlike a blur
+enum class Denoise {
+    NONE,
+    MEDIAN,             // Median of each pixel's neighborhood; best on salt-and-pepper noise
+    BILATERAL           // Averages neighbors of similar tone; smooths grain, slower
+};
+
+// How color becomes a single gray level for grayscale and monochrome output
+enum class GrayConversion {
+    REC601,             // 0.299 R + 0.587 G + 0.114 B, as in SD video and most image tools
+    REC709,             // 0.2126 R + 0.7152 G + 0.0722 B, as in HD video
+    AVERAGE,            // Equal weights, so saturated colors don't turn dark
+    RED,                // One channel only, like a color filter on black and white film
+    GREEN,
+    BLUE,
+    CUSTOM              // Parameters::grayWeights
+};
+
+// How the image's tonal range is mapped onto the palette
+enum class RenderingIntent {
+    RELATIVE_COLORIMETRIC,  // Map colors as-is, clipping what the palette can't reach
+    PERCEPTUAL              // Compress the image's range into the palette's first
+};
+
+// Dot shape of the halftone screen
+enum class HalftoneShape {
+    CIRCLE,
+    DIAMOND
+};
+
+// Space-filling curves for Riemersma dithering
+enum class CurveType {
+    HILBERT,
+    PEANO,
+    Z_ORDER         // Morton order
+};
+
+// Where the threshold-map algorithms run
+enum class Backend {
+    AUTO,           // GPU for large images when an OpenCL device is available
+    CPU,
+    GPU             // Whenever the image and palette allow it
+};
+
+// How color output is produced
+enum class ColorMode {
+    RGB,                // Dither against the palette directly
+    CMYK,               // Separate into C, M, Y and K ink plates, each screened on its own
+    MULTI_INK,          // Duotone/tritone: tonal ranges mapped onto a few spot inks and paper
+    PIXEL_FORMAT        // Per-channel bit depths of a display pixel format
+};
+
+// Embedded display pixel formats for ColorMode::PIXEL_FORMAT
+enum class PixelFormat {
+    RGB565,             // 5 bits red, 6 green, 5 blue
+    RGB332,             // 3 bits red, 3 green, 2 blue
+    RGBA4444            // 4 bits per channel, alpha reduced to 16 levels on export
+};
+
+// Planes that per-channel overrides apply to
+enum class ChannelSpace {
+    BGR,                // Blue, green and red
+    YCRCB               // Luma and two chroma planes (OpenCV order: Y, Cr, Cb)
+};
+
+// Settings for one plane. When any plane has an override, each plane is
+// dithered on its own to `levels` levels and the palette is not used
+struct ChannelOverride {
+    bool enabled = false;
+    Algorithm algorithm = Algorithm::FLOYD_STEINBERG;
+    float strength = 1.0f;
+    float threshold = 0.5f;     // Rounding point between two levels (lower = brighter)
+};
+
+// Extra passes run on a result after the main dithering pass
+enum class StageType {
+    DITHER,             // Dither the result again, with the stage's own settings or the main ones
+    BLUR,               // Gaussian blur, amount = sigma in pixels
+    SHARPEN             // Unsharp mask, amount = strength
+};
+
+struct Parameters;
+
+struct Stage {
+    StageType type = StageType::DITHER;
+    float amount = 1.0f;        // Blur sigma or sharpen strength
+    float mix = 1.0f;           // Share of the stage's output blended over its input (0-1)
+    std::shared_ptr<const Parameters> params;   // DITHER settings (null = the main pass's; their stages are unused)
+};
+
+// Palette generation methods
+enum class PaletteMethod {
+    KMEANS,             // Cluster colors with k-means
+    MEDIAN_CUT          // Recursively split the color space at the median
 };
 
 // Dithering parameters
 struct Parameters {
     Algorithm algorithm = Algorithm::FLOYD_STEINBERG;
+    std::string plugin;             // Plugin algorithm id, used instead of algorithm when set
+    std::map<std::string, std::string> pluginOptions;   // Passed to the plugin as key/value pairs
     PaletteMode paletteMode = PaletteMode::MONOCHROME;
-    std::vector<cv::Vec3b> customPalette;
+    std::vector<cv::Vec3b> customPalette;   // Used with PaletteMode::CUSTOM (BGR)
+    std::vector<cv::Vec3b> protectedColors; // Palette colors used only where the image has them exactly
+    int paletteSize = 16;           // Colors generated for PaletteMode::ADAPTIVE
+    PaletteMethod paletteMethod = PaletteMethod::KMEANS;
+    RoundingMode rounding = RoundingMode::NEAREST;
+    ColorDistance colorDistance = ColorDistance::RGB;
+    RenderingIntent renderingIntent = RenderingIntent::RELATIVE_COLORIMETRIC;
+    WorkingSpace workingSpace = WorkingSpace::RGB;  // Non-RGB spaces match colors by Euclidean distance there
+    GrayConversion grayConversion = GrayConversion::REC601;
+    cv::Vec3f grayWeights = cv::Vec3f(0.114f, 0.587f, 0.299f);  // B, G, R weights for GrayConversion::CUSTOM
+    cv::Vec3b inkColor = cv::Vec3b(0, 0, 0);          // Monochrome dark color (BGR)
+    cv::Vec3b paperColor = cv::Vec3b(255, 255, 255);  // Monochrome light color (BGR)
+    ColorMode colorMode = ColorMode::RGB;
+    PixelFormat pixelFormat = PixelFormat::RGB565;
+    std::vector<cv::Vec3b> inks;    // Spot inks for ColorMode::MULTI_INK, 2-3 colors (BGR), printed on paperColor
 
     // Adjustable parameters
     float strength = 1.0f;          // Error diffusion strength
+    float threshold = 0.5f;         // Rounding point between palette levels (lower = brighter)
+    bool autoThreshold = false;     // Pick the threshold with Otsu's method instead
+    int localWindow = 25;           // Window size in pixels for Sauvola, Niblack and Bradley
+    float localK = 0.2f;            // Local threshold sensitivity (k)
+    float edgeSensitivity = 1.0f;   // How strongly edges stop error in STRUCTURE_AWARE (0 = plain diffusion)
+    CurveType curve = CurveType::HILBERT;   // Pixel order for RIEMERSMA
+    int riemersmaHistory = 16;      // Errors remembered along the curve
+    float riemersmaDecay = 16.0f;   // Weight of the newest error relative to the oldest
     float serpentine = 1.0f;        // Serpentine scanning (0=off, 1=on)
+    ErrorEdge errorEdge = ErrorEdge::DISCARD;   // Error diffusion at the left and right edges
+    bool clampError = true;         // Clamp pixel plus error to 0-255 before quantizing (false = let it ring)
     float colorization = 0.0f;      // Add slight color tint
-    int levels = 2;                 // Number of intensity levels
+    int levels = 2;                 // Number of intensity levels per plane, with channel overrides
+    ChannelSpace channelSpace = ChannelSpace::BGR;
+    std::array<ChannelOverride, 3> channelOverrides;  // Indexed like the channelSpace planes
     float gamma = 1.0f;             // Gamma correction
+    float levelsBlack = 0.0f;       // Input levels: this tone (0-1) and darker become black ...
+    float levelsWhite = 1.0f;       // ... this one and lighter white ...
+    float levelsMidtone = 1.0f;     // ... and midtones are raised to 1/this (>1 lightens)
+    std::vector<cv::Point2f> toneCurve;  // (input, output) points in 0-1 after levels (empty = none)
     float contrast = 1.0f;          // Contrast adjustment
     float brightness = 0.0f;        // Brightness adjustment
     float saturation = 1.0f;        // Saturation adjustment
-    int bayerSize = 8;              // Bayer matrix size
+    float exposure = 0.0f;          // Exposure in stops, applied before the other adjustments
+    Denoise denoise = Denoise::NONE;    // Noise filter before the other adjustments
+    float denoiseStrength = 0.5f;   // 0-1: median size 3 to 9, or bilateral smoothing
+    float blurRadius = 0.0f;        // Gaussian blur sigma in pixels (0 = off)
+    float sharpenAmount = 0.0f;     // Unsharp mask strength (0 = off)
+    float sharpenRadius = 1.0f;     // Unsharp mask sigma in pixels
+    bool linearize = false;         // Quantize and diffuse error in linear light instead of sRGB
+    bool linearPalette = false;     // Palette and pixels are already linear light (set while linearizing)
+    float clipBlack = 0.0f;         // Minimum % of pixels forced to pure black
+    float clipWhite = 0.0f;         // Minimum % of pixels forced to pure white
+    float highlightProtect = 1.0f;  // Luma above this (0-1) comes out pure white, no dots (1 = off)
+    float shadowProtect = 0.0f;     // Luma below this (0-1) comes out pure black, no dots (0 = off)
+    float dotGain = 0.0f;           // Print dot gain in % at 50% ink, compensated before dithering (0 = off)
+    int bayerSize = 8;              // Bayer matrix size, 2 to MAX_BAYER_SIZE
+    bool bayerClustered = false;    // One round dot per Bayer matrix instead of dispersed pixels
+    float ditherAngle = 0.0f;       // Rotation of the Bayer, pattern and library matrices in degrees
+    std::string matrix = "dots45";  // Matrix library id for Algorithm::ORDERED_MATRIX
+    cv::Mat patternMatrix;          // User thresholds for PATTERN_DITHER (CV_32F, empty = built-in 4x4)
+    std::vector<KernelWeight> diffusionKernel;  // Error weights for CUSTOM_KERNEL (empty = Floyd-Steinberg)
+    bool normalizeKernel = true;    // Scale the custom kernel's weights to sum to 1
     unsigned int seed = 42;         // Random seed
     bool useBlueNoise = true;       // Use blue noise for ordered dithering
-    float ditherScale = 1.0f;       // Scale factor for dither pattern
+    std::string noiseTexture;       // Threshold texture file tiled by BLUE_NOISE instead of the generated one
+    float ditherScale = 1.0f;       // Scale factor for dither pattern (whole pixels per matrix cell)
+    int pixelate = 1;               // Dither at 1/N resolution, then scale up with nearest neighbor
+    float blend = 0.0f;             // Mix the adjusted source back in for a subtle texture (0 = fully dithered, 1 = source)
+    Backend backend = Backend::AUTO;    // Where ordered/threshold algorithms run (same output either way)
+    HalftoneShape halftoneShape = HalftoneShape::CIRCLE;
+    float halftoneCellSize = 8.0f;  // Halftone cell size in pixels
+    float screenLpi = 0.0f;         // Screen ruling in lines per inch at printDpi, sets the cell size (0 = off)
+    float printDpi = 300.0f;        // Image pixels per inch when printed, for screenLpi and print PDFs
+    cv::Vec3f screenAngles = cv::Vec3f(0.0f, 75.0f, 15.0f);  // Per-channel screen angle in degrees (B, G, R = Y, M, C inks)
+    float grayScreenAngle = 45.0f;  // Screen angle for grayscale palettes (K ink)
+    int stippleDots = 5000;         // Number of stipple dots
+    float stippleDotSize = 1.5f;    // Stipple dot radius in pixels where the ink is solid
+    int stippleIterations = 30;     // Relaxation steps; more spreads the dots more evenly
+    int phaseX = 0;                 // Threshold matrix horizontal offset (pixels)
+    int phaseY = 0;                 // Threshold matrix vertical offset (pixels)
+    std::vector<Stage> stages;      // Run in order on the result, e.g. blur then dither again with Bayer
+};
+
+// Rows of a result as they are finished: (rows, index of the first row). Called
+// in order, but may be called from a worker thread
+using BandCallback = std::function<void(const cv::Mat&, int)>;
+
+// Core dithering function. Input is 8-bit BGR, or CV_32FC3 on a 0-255 scale for
+// 16-bit and HDR sources, whose extra precision error diffusion keeps.
+// Large error diffusion jobs hand each finished band to onBand for progressive
+// display; those rows are final except for highlight/shadow protection and blend
+cv::Mat ditherImage(const cv::Mat& input, const Parameters& params, BandCallback onBand = nullptr);
+
+// A result part way through dithering: the pixels finished so far, the rest
+// still the input. Return false to stop
+using SnapshotCallback = std::function<bool(const cv::Mat&)>;
+
+// Size of the image the diffusion loops work on: the input's, or smaller when pixelated
+cv::Size getWorkingSize(const cv::Size& size, const Parameters& params);
+
+// Dither like ditherImage on one thread, recording the order error diffusion
+// finishes pixels in, then replay it: onSnapshot gets the input first, then a
+// snapshot every interval pixels, and the finished result last. Algorithms
+// without a diffusion loop finish every pixel on their own, so they're shown
+// row by row. Returns the result, or an empty image when stopped
+cv::Mat ditherWithSnapshots(const cv::Mat& input, const Parameters& params, int interval,
+                            const SnapshotCallback& onSnapshot);
+
+// Dither a single tile of the image (for rendering huge results piecewise).
+// contextMargin pixels around the tile are processed too so tiles join seamlessly
+cv::Mat ditherTile(const cv::Mat& input, const cv::Rect& tile, const Parameters& params,
+                   int contextMargin = 32);
+
+// Rough peak working memory in bytes for dithering an image of this size in one go
+size_t estimateMemory(const cv::Size& size, const Parameters& params);
+
+// Progress of a long job: (fraction done 0-1, stage). Return false to cancel
+using ProgressCallback = std::function<bool(float, const std::string&)>;
+
+// Dither in full-width bands (each rendered like a tile, with context rows above
+// and below, and faded into the band above over the rows they share) sized so
+// working memory stays under memoryLimit bytes. Images that
+// fit go through ditherImage unchanged. Progress is reported as the
+// algorithms' row loops go (error diffusion and threshold maps), and
+// cancelling stops them at the next row and returns an empty image. onBand
+// receives finished rows as in ditherImage
+cv::Mat ditherWithinMemory(const cv::Mat& input, const Parameters& params, size_t memoryLimit,
+                           ProgressCallback progress = nullptr, BandCallback onBand = nullptr);
+
+// Timing of one algorithm on one image, see benchmarkAlgorithms
+struct BenchmarkResult {
+    Algorithm algorithm;
+    double seconds = 0.0;       // Fastest of the runs
+    size_t memory = 0;          // estimateMemory for the image
+};
+
+// Dither input with each of algorithms (every algorithm when empty) and the
+// rest of params, runs times each, keeping the fastest run. Progress is
+// reported per algorithm; cancelling returns the algorithms finished so far
+std::vector<BenchmarkResult> benchmarkAlgorithms(const cv::Mat& input, const Parameters& params,
+                                                 std::vector<Algorithm> algorithms = {}, int runs = 1,
+                                                 ProgressCallback progress = nullptr);
+
+// One algorithm's result in an algorithm contact sheet
+struct AlgorithmThumbnail {
+    Algorithm algorithm;
+    cv::Mat image;
+};
+
+// Dither a copy of input scaled to fit size x size with each of algorithms
+// (every algorithm when empty), starting from params with the adjustments of
+// getRecommendedParameters. Progress is reported per algorithm; cancelling
+// returns the thumbnails finished so far
+std::vector<AlgorithmThumbnail> renderAlgorithmThumbnails(const cv::Mat& input, const Parameters& params,
+                                                          int size = 256, std::vector<Algorithm> algorithms = {},
+                                                          ProgressCallback progress = nullptr);
+
+// Thumbnails in a grid, each labeled with its algorithm's name. columns = 0
+// picks a roughly square grid
+cv::Mat composeContactSheet(const std::vector<AlgorithmThumbnail>& thumbnails, int columns = 0);
+
+// Pixel counts per level (256 bins each), for picking thresholds and color counts
+struct Histogram {
+    std::vector<int> luma;                      // Rec. 601
+    std::array<std::vector<int>, 3> channels;   // B, G, R
 };
 
-// Core dithering function
-cv::Mat ditherImage(const cv::Mat& input, const Parameters& params);
+// Histograms of an 8-bit or float BGR image. Pass preprocessImage's result to
+// see the levels the dithering actually works on
+Histogram computeHistogram(const cv::Mat& image);
+
+// Dither an alpha channel (8-bit, single channel) to fully opaque or fully
+// transparent with the chosen algorithm, for formats with 1-bit transparency
+cv::Mat ditherAlpha(const cv::Mat& alpha, const Parameters& params);
+
+// Keep the dither only inside a region: where mask is 255 the result is
+// dithered, where it's 0 the original shows, and levels in between blend for
+// soft edges. The mask and original are scaled to the dithered image's size
+cv::Mat applyMask(const cv::Mat& dithered, const cv::Mat& original, const cv::Mat& mask);
+
+// Mask for applyMask that is 255 inside any of the rectangles
+cv::Mat maskFromRects(const cv::Size& size, const std::vector<cv::Rect>& rects);
+
+// Dither an image as CMYK separations. Returns the C, M, Y and K plates as
+// single-channel images (0 = ink, 255 = paper), each screened at its own angle
+std::vector<cv::Mat> ditherSeparations(const cv::Mat& input, const Parameters& params);
+
+// Overprint CMYK plates on white paper to preview the printed result (BGR)
+cv::Mat combineSeparations(const std::vector<cv::Mat>& plates);
+
+// Whether screenLpi sizes this algorithm's screen: HALFTONE and clustered Bayer
+bool usesScreenRuling(const Parameters& params);
+
+// Set halftoneCellSize (HALFTONE) or ditherScale (clustered Bayer) from
+// screenLpi at printDpi, allowing for pixelate. Clustered Bayer only scales by
+// whole pixels, so its ruling is rounded. Warnings say when the combination
+// won't print well: cells under 2 pixels, too few gray levels per cell, or
+// rulings finer than presses hold. Does nothing with screenLpi 0; ditherImage,
+// traceHalftone and traceDots apply it themselves
+void applyScreenRuling(Parameters& params, std::vector<std::string>* warnings = nullptr);
 
-// Individual algorithm implementations
-cv::Mat floydSteinberg(const cv::Mat& input, const Parameters& params);
-cv::Mat atkinson(const cv::Mat& input, const Parameters& params);
-cv::Mat jarvisJudiceNinke(const cv::Mat& input, const Parameters& params);
-cv::Mat stucki(const cv::Mat& input, const Parameters& params);
-cv::Mat burkes(const cv::Mat& input, const Parameters& params);
-cv::Mat sierra(const cv::Mat& input, const Parameters& params);
-cv::Mat sierraTwo(const cv::Mat& input, const Parameters& params);
-cv::Mat sierraLite(const cv::Mat& input, const Parameters& params);
+// Individual algorithm implementations. Row-by-row error diffusion spreads its
+// rows over the worker threads and hands each finished block of rows to onBand
+cv::Mat floydSteinberg(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat atkinson(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat jarvisJudiceNinke(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat stucki(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat burkes(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat sierra(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat sierraTwo(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat sierraLite(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
 cv::Mat orderedDither(const cv::Mat& input, const Parameters& params);
 cv::Mat blueNoiseDither(const cv::Mat& input, const Parameters& params);
 cv::Mat whiteNoiseDither(const cv::Mat& input, const Parameters& params);
@@ -91,18 +452,97 @@ cv::Mat patternDither(const cv::Mat& input, const Parameters& params);
 cv::Mat dotDiffusion(const cv::Mat& input, const Parameters& params);
 cv::Mat riemersma(const cv::Mat& input, const Parameters& params);
 cv::Mat gradientBased(const cv::Mat& input, const Parameters& params);
-cv::Mat variableErrorDiffusion(const cv::Mat& input, const Parameters& params);
-cv::Mat ostromoukhov(const cv::Mat& input, const Parameters& params);
-cv::Mat fan(const cv::Mat& input, const Parameters& params);
-cv::Mat shiauFan(const cv::Mat& input, const Parameters& params);
-cv::Mat stevenPigeon(const cv::Mat& input, const Parameters& params);
+cv::Mat structureAware(const cv::Mat& input, const Parameters& params);
+cv::Mat variableErrorDiffusion(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat ostromoukhov(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat fan(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat shiauFan(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat stevenPigeon(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+cv::Mat matrixDither(const cv::Mat& input, const Parameters& params);
+cv::Mat interleavedGradientNoise(const cv::Mat& input, const Parameters& params);
+cv::Mat halftone(const cv::Mat& input, const Parameters& params);
+cv::Mat simpleThreshold(const cv::Mat& input, const Parameters& params);
+cv::Mat sauvola(const cv::Mat& input, const Parameters& params);
+cv::Mat niblack(const cv::Mat& input, const Parameters& params);
+cv::Mat bradley(const cv::Mat& input, const Parameters& params);
+cv::Mat posterize(const cv::Mat& input, const Parameters& params);
+cv::Mat stipple(const cv::Mat& input, const Parameters& params);
+cv::Mat customKernel(const cv::Mat& input, const Parameters& params, const BandCallback& onBand = nullptr);
+
+// Largest reach of a custom kernel in pixels, and most neighbors it may have
+const int MAX_KERNEL_REACH = 8;
+const int MAX_KERNEL_WEIGHTS = 64;
+
+// Check a custom kernel: only pixels not yet visited (dy > 0, or dy = 0 and
+// dx > 0), each once, within MAX_KERNEL_REACH; finite weights with a positive
+// sum, at most 2 if the kernel isn't normalized, as more makes error grow
+// without bound. error says what's wrong
+bool validateKernel(const std::vector<KernelWeight>& kernel, bool normalize, std::string& error);
+
+// Parse a kernel written as [dx, dy, weight] triples, e.g. Floyd-Steinberg as
+// [[1,0,7],[-1,1,3],[0,1,5],[1,1,1]] (normalized, the weights need not sum to 1)
+bool parseKernel(const std::string& text, std::vector<KernelWeight>& kernel, std::string& error);
+
+// Most points a tone curve may have
+const int MAX_CURVE_POINTS = 16;
+
+// Check a tone curve: 2 to MAX_CURVE_POINTS points in 0-1, in order of
+// input, no two at the same input. error says what's wrong
+bool validateToneCurve(const std::vector<cv::Point2f>& points, std::string& error);
+
+// Parse a tone curve written as [input, output] pairs in 0-1, e.g. an S curve
+// as [[0,0],[0.25,0.18],[0.75,0.82],[1,1]]. Sorted by input, then validated
+bool parseToneCurve(const std::string& text, std::vector<cv::Point2f>& points, std::string& error);
+
+// The curve through points at x: a monotone cubic, so it doesn't overshoot
+// between points, flat beyond the first and last. Empty is the identity
+float evaluateToneCurve(const std::vector<cv::Point2f>& points, float x);
+
+// Every algorithm, in Algorithm enum order
+const std::vector<AlgorithmInfo>& getAlgorithmLibrary();
+const AlgorithmInfo& getAlgorithmInfo(Algorithm algo);
+
+// Settings for switching from current.algorithm to algo. General settings tuned
+// for another family (strength, threshold, serpentine, pattern scale) go back
+// to their defaults, and values the new algorithm looks broken with are
+// replaced with recommended ones; everything else carries over
+Parameters getRecommendedParameters(const Parameters& current, Algorithm algo);
 
 // Utility functions
+bool isSlowAlgorithm(Algorithm algo);
+bool isErrorDiffusion(Algorithm algo);
+bool hasChannelOverrides(const Parameters& params);
+std::array<int, 3> getPixelFormatLevels(PixelFormat format);   // Levels per B, G, R channel
+Algorithm getPreviewProxy(Algorithm algo);
+float computeOtsuThreshold(const cv::Mat& image);   // 0-1, from the luma histogram
+float computeAutoThreshold(const cv::Mat& input, const Parameters& params);  // Otsu after preprocessing
+uint64_t hashParameters(const Parameters& params);
+cv::Mat preprocessImage(const cv::Mat& input, const Parameters& params);
+cv::Vec3f getGrayWeights(const Parameters& params);    // B, G, R, summing to 1
+cv::Mat convertToGray(const cv::Mat& image, const Parameters& params);  // Single channel, same depth
+std::vector<cv::Vec3b> generatePalette(const cv::Mat& image, int colors,
+                                       PaletteMethod method, unsigned int seed);
 std::vector<cv::Vec3b> getPalette(PaletteMode mode);
+std::vector<cv::Vec3b> getPalette(const Parameters& params);
 cv::Vec3b findClosestColor(const cv::Vec3b& color, const std::vector<cv::Vec3b>& palette);
+float colorDifference(const cv::Vec3b& a, const cv::Vec3b& b);     // CIEDE2000 between two BGR colors
 cv::Mat generateBlueNoiseTexture(int size, unsigned int seed);
-cv::Mat generateBayerMatrix(int size);
+const int MAX_BAYER_SIZE = 32;
+const int MAX_PATTERN_SCALE = 16;             // Largest ditherScale
+cv::Mat generateBayerMatrix(int size);          // Any size; products of 2s and 3s follow Bayer exactly
+cv::Mat generateClusteredMatrix(int size);      // One round dot growing from the middle
+int getBayerSize(Algorithm algo);               // Matrix size of the ORDERED_BAYER algorithms (0 = not one)
 std::string getAlgorithmName(Algorithm algo);
+std::string getAlgorithmCategoryName(AlgorithmCategory category);
 std::string getPaletteModeName(PaletteMode mode);
+std::string getPaletteMethodName(PaletteMethod method);
+std::string getRoundingModeName(RoundingMode mode);
+std::string getColorDistanceName(ColorDistance distance);
+std::string getGrayConversionName(GrayConversion conversion);
+std::string getHalftoneShapeName(HalftoneShape shape);
+std::string getColorModeName(ColorMode mode);
+std::string getPixelFormatName(PixelFormat format);
+std::string getChannelName(ChannelSpace space, int channel);
+std::string getStageTypeName(StageType type);
 
 } // namespace Dithering
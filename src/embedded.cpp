@@ -0,0 +1,159 @@
+#include "embedded.h"
+#include "imageio.h"
+#include <algorithm>
+#include <cctype>
+#include <cstdio>
+#include <filesystem>
+#include <fstream>
+
+namespace Embedded {
+
+const std::vector<std::string>& getLayoutIds() {
+    static const std::vector<std::string> ids = {"horizontal", "ssd1306", "gray2"};
+    return ids;
+}
+
+bool parseLayout(const std::string& id, Layout& layout) {
+    const auto& ids = getLayoutIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    layout = static_cast<Layout>(match - ids.begin());
+    return true;
+}
+
+std::string getLayoutName(Layout layout) {
+    switch (layout) {
+        case Layout::HORIZONTAL: return "Horizontal 1-bit";
+        case Layout::SSD1306: return "SSD1306 Pages";
+        case Layout::GRAY2: return "E-ink 2-bit Gray";
+        default: return "Unknown";
+    }
+}
+
+const std::vector<std::string>& getBitOrderIds() {
+    static const std::vector<std::string> ids = {"default", "msb", "lsb"};
+    return ids;
+}
+
+bool parseBitOrder(const std::string& id, BitOrder& order) {
+    const auto& ids = getBitOrderIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    order = static_cast<BitOrder>(match - ids.begin());
+    return true;
+}
+
+static bool lsbFirst(const Options& options) {
+    if (options.bitOrder == BitOrder::DEFAULT) return options.layout == Layout::SSD1306;
+    return options.bitOrder == BitOrder::LSB_FIRST;
+}
+
+std::vector<uint8_t> packImage(const cv::Mat& image, const Options& options) {
+    std::vector<uint8_t> bytes;
+    if (image.empty()) return bytes;
+
+    cv::Mat gray = image;
+    if (image.channels() == 3) cv::cvtColor(image, gray, cv::COLOR_BGR2GRAY);
+    const bool lsb = lsbFirst(options);
+    auto lit = [&](int x, int y) { return (gray.at<uchar>(y, x) >= 128) != options.invert; };
+
+    if (options.layout == Layout::SSD1306) {
+        // Page p holds rows 8p to 8p + 7; a short last page is padded with unlit pixels
+        int pages = (gray.rows + 7) / 8;
+        bytes.assign(static_cast<size_t>(pages) * gray.cols, 0);
+        for (int y = 0; y < gray.rows; ++y) {
+            uint8_t bit = static_cast<uint8_t>(lsb ? 1 << (y % 8) : 0x80 >> (y % 8));
+            for (int x = 0; x < gray.cols; ++x) {
+                if (lit(x, y)) bytes[static_cast<size_t>(y / 8) * gray.cols + x] |= bit;
+            }
+        }
+    } else if (options.layout == Layout::GRAY2) {
+        int stride = (gray.cols + 3) / 4;
+        bytes.assign(static_cast<size_t>(stride) * gray.rows, 0);
+        for (int y = 0; y < gray.rows; ++y) {
+            for (int x = 0; x < gray.cols; ++x) {
+                int level = std::clamp((gray.at<uchar>(y, x) + 42) / 85, 0, 3);
+                if (options.invert) level = 3 - level;
+                int shift = lsb ? 2 * (x % 4) : 6 - 2 * (x % 4);
+                bytes[static_cast<size_t>(y) * stride + x / 4] |= static_cast<uint8_t>(level << shift);
+            }
+        }
+    } else {
+        int stride = (gray.cols + 7) / 8;
+        bytes.assign(static_cast<size_t>(stride) * gray.rows, 0);
+        for (int y = 0; y < gray.rows; ++y) {
+            for (int x = 0; x < gray.cols; ++x) {
+                if (!lit(x, y)) continue;
+                bytes[static_cast<size_t>(y) * stride + x / 8] |=
+                    static_cast<uint8_t>(lsb ? 1 << (x % 8) : 0x80 >> (x % 8));
+            }
+        }
+    }
+    return bytes;
+}
+
+std::string toCArray(const std::vector<uint8_t>& bytes, const cv::Size& size, const std::string& name,
+                     const Options& options, bool header) {
+    std::string macro = name;
+    std::transform(macro.begin(), macro.end(), macro.begin(), ::toupper);
+
+    char line[160];
+    std::string source;
+    std::snprintf(line, sizeof(line), "// %dx%d, %s, %s first: %zu bytes\n", size.width, size.height,
+                  getLayoutName(options.layout).c_str(), lsbFirst(options) ? "LSB" : "MSB", bytes.size());
+    source += line;
+    if (header) source += "#pragma once\n\n";
+    source += "#include <stdint.h>\n\n";
+    std::snprintf(line, sizeof(line), "#define %s_WIDTH %d\n#define %s_HEIGHT %d\n\n", macro.c_str(), size.width,
+                  macro.c_str(), size.height);
+    source += line;
+    std::snprintf(line, sizeof(line), "static const uint8_t %s[%zu] = {\n", name.c_str(), bytes.size());
+    source += line;
+    for (size_t i = 0; i < bytes.size(); i += 12) {
+        source += "   ";
+        for (size_t j = i; j < std::min(i + 12, bytes.size()); ++j) {
+            std::snprintf(line, sizeof(line), " 0x%02x,", bytes[j]);
+            source += line;
+        }
+        source += "\n";
+    }
+    source += "};\n";
+    return source;
+}
+
+const std::vector<std::string>& getEmbeddedExtensions() {
+    static const std::vector<std::string> extensions = {"h", "c", "bin"};
+    return extensions;
+}
+
+// C identifier from a name: other characters become underscores
+static std::string makeIdentifier(std::string name) {
+    for (char& c : name) {
+        if (!std::isalnum(static_cast<unsigned char>(c))) c = '_';
+    }
+    if (name.empty() || std::isdigit(static_cast<unsigned char>(name[0]))) name = "image_" + name;
+    return name;
+}
+
+bool exportEmbedded(const cv::Mat& image, const std::string& path, const Options& options, size_t* byteCount) {
+    std::string ext = ImageIO::getExtension(path);
+    if (std::find(getEmbeddedExtensions().begin(), getEmbeddedExtensions().end(), ext) ==
+        getEmbeddedExtensions().end()) {
+        return false;
+    }
+
+    std::vector<uint8_t> bytes = packImage(image, options);
+    if (bytes.empty()) return false;
+    if (byteCount) *byteCount = bytes.size();
+
+    std::ofstream file(path, std::ios::binary);
+    if (ext == "bin") {
+        file.write(reinterpret_cast<const char*>(bytes.data()), static_cast<std::streamsize>(bytes.size()));
+    } else {
+        std::string name = options.name.empty() ? std::filesystem::path(path).stem().string() : options.name;
+        file << toCArray(bytes, image.size(), makeIdentifier(name), options, ext == "h");
+    }
+    return static_cast<bool>(file);
+}
+
+} // namespace Embedded
@@ -0,0 +1,57 @@
+#pragma once
+
+#include <cstdint>
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace Embedded {
+
+// How pixels are packed into bytes
+enum class Layout {
+    HORIZONTAL,     // 1 bit per pixel, rows left to right, each row padded to whole bytes
+    SSD1306,        // 1 bit per pixel, pages of 8 rows; each byte is one column of a page
+    GRAY2           // 2 bits per pixel (e-ink 4-level gray), rows padded to whole bytes
+};
+
+// Which end of a byte the first pixel goes in
+enum class BitOrder {
+    DEFAULT,        // LSB first for SSD1306 pages (top pixel in bit 0), MSB first otherwise
+    MSB_FIRST,
+    LSB_FIRST
+};
+
+struct Options {
+    Layout layout = Layout::HORIZONTAL;
+    BitOrder bitOrder = BitOrder::DEFAULT;
+    bool invert = false;        // 1 bits (and gray 3) for dark pixels instead of light ones
+    std::string name;           // C array name (empty = from the file name)
+};
+
+// Lookup by command line id: layouts horizontal, ssd1306, gray2; bit orders
+// default, msb, lsb
+const std::vector<std::string>& getLayoutIds();
+bool parseLayout(const std::string& id, Layout& layout);
+std::string getLayoutName(Layout layout);
+const std::vector<std::string>& getBitOrderIds();
+bool parseBitOrder(const std::string& id, BitOrder& order);
+
+// Pack a result into display bytes. Luma is thresholded at 50% for 1-bit
+// layouts and rounded to 4 levels for GRAY2, so the result should already
+// have been dithered to 2 or 4 grays. Light pixels are 1 (lit on an OLED,
+// white on e-ink) unless inverted
+std::vector<uint8_t> packImage(const cv::Mat& image, const Options& options);
+
+// C source declaring the bytes as a const uint8_t array, with the image size
+// as NAME_WIDTH and NAME_HEIGHT macros (and #pragma once for a header)
+std::string toCArray(const std::vector<uint8_t>& bytes, const cv::Size& size, const std::string& name,
+                     const Options& options, bool header = true);
+
+// Output types by extension: h and c (C array), bin (raw bytes)
+const std::vector<std::string>& getEmbeddedExtensions();
+
+// Pack and write; byteCount receives the packed size
+bool exportEmbedded(const cv::Mat& image, const std::string& path, const Options& options,
+                    size_t* byteCount = nullptr);
+
+} // namespace Embedded
@@ -0,0 +1,60 @@
+#include "errors.h"
+#include <opencv2/core.hpp>
+#include <new>
+
+namespace Errors {
+
+Error fromException(const std::exception& e) {
+    if (dynamic_cast<const std::bad_alloc*>(&e)) {
+        return {ErrorCode::OUT_OF_MEMORY, "Out of memory"};
+    }
+    if (auto cvError = dynamic_cast<const cv::Exception*>(&e)) {
+        if (cvError->code == cv::Error::StsNoMem) return {ErrorCode::OUT_OF_MEMORY, "Out of memory"};
+    }
+    return {ErrorCode::INTERNAL, e.what()};
+}
+
+std::string getErrorCodeId(ErrorCode code) {
+    switch (code) {
+        case ErrorCode::NONE: return "none";
+        case ErrorCode::INVALID_ARGUMENT: return "invalid-argument";
+        case ErrorCode::NOT_FOUND: return "not-found";
+        case ErrorCode::IO: return "io";
+        case ErrorCode::DECODE: return "decode";
+        case ErrorCode::UNSUPPORTED_FORMAT: return "unsupported-format";
+        case ErrorCode::ENCODER_MISSING: return "encoder-missing";
+        case ErrorCode::CANCELLED: return "cancelled";
+        case ErrorCode::OUT_OF_MEMORY: return "out-of-memory";
+        case ErrorCode::INTERNAL: return "internal";
+        default: return "unknown";
+    }
+}
+
+std::string getErrorHint(ErrorCode code) {
+    switch (code) {
+        case ErrorCode::NOT_FOUND: return "Check that the file still exists";
+        case ErrorCode::IO: return "Check permissions and free disk space";
+        case ErrorCode::DECODE: return "The file may be damaged or not really an image";
+        case ErrorCode::UNSUPPORTED_FORMAT: return "Convert it to PNG or JPEG first";
+        case ErrorCode::ENCODER_MISSING:
+            return "Install an OpenCV build with video encoding (FFmpeg or GStreamer), or ffmpeg for --codec";
+        case ErrorCode::OUT_OF_MEMORY: return "Lower the memory limit so the image is dithered in bands, or resize it";
+        default: return "";
+    }
+}
+
+int getExitCode(ErrorCode code) {
+    switch (code) {
+        case ErrorCode::NONE: return 0;
+        case ErrorCode::NOT_FOUND: return 2;
+        case ErrorCode::DECODE: return 3;
+        case ErrorCode::UNSUPPORTED_FORMAT: return 4;
+        case ErrorCode::IO: return 5;
+        case ErrorCode::ENCODER_MISSING: return 6;
+        case ErrorCode::OUT_OF_MEMORY: return 7;
+        case ErrorCode::CANCELLED: return 130;     // Like a shell reports Ctrl+C
+        default: return 1;
+    }
+}
+
+} // namespace Errors
@@ -0,0 +1,47 @@
+#pragma once
+
+#include <exception>
+#include <string>
+#include <utility>
+
+namespace Errors {
+
+// Why an operation failed, for callers that react differently to each kind
+// (the GUI's hints, the CLI's exit status) instead of matching messages
+enum class ErrorCode {
+    NONE,
+    INVALID_ARGUMENT,       // Bad option, setting or combination of them
+    NOT_FOUND,              // Input file or folder doesn't exist
+    IO,                     // Reading or writing failed (permissions, disk full, ...)
+    DECODE,                 // Input exists but isn't a readable image or video
+    UNSUPPORTED_FORMAT,     // File type that can't be read or written at all
+    ENCODER_MISSING,        // No video encoder for the output (in this OpenCV build, or ffmpeg)
+    CANCELLED,              // Stopped by the user
+    OUT_OF_MEMORY,          // Ran out of memory while processing
+    INTERNAL                // Anything else a library threw
+};
+
+struct Error {
+    ErrorCode code = ErrorCode::NONE;
+    std::string message;
+
+    Error() = default;
+    Error(ErrorCode code, std::string message) : code(code), message(std::move(message)) {}
+
+    bool ok() const { return code == ErrorCode::NONE; }
+};
+
+// Failure from an exception thrown while processing: out of memory for
+// std::bad_alloc and OpenCV allocation errors, internal otherwise
+Error fromException(const std::exception& e);
+
+// Stable lowercase name, e.g. "out-of-memory"
+std::string getErrorCodeId(ErrorCode code);
+
+// What the user can do about it (empty if nothing useful to suggest)
+std::string getErrorHint(ErrorCode code);
+
+// CLI exit status for an error, documented in the --help output
+int getExitCode(ErrorCode code);
+
+} // namespace Errors
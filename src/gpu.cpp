@@ -0,0 +1,64 @@
+#include "gpu.h"
+
+namespace Gpu {
+
+// One work item per pixel, doing the same float math as the CPU path so both
+// produce identical output
+static const char* thresholdMapSource = R"(
+__kernel void thresholdMap(__global const uchar* src, int srcStep, int srcOffset,
+                           __global uchar* dst, int dstStep, int dstOffset, int rows, int cols,
+                           __global const float* thresholds, int mapRows, int mapCols, int scale,
+                           int phaseX, int phaseY, float spread, float strength,
+                           __global const uchar* lut) {
+    int x = get_global_id(0);
+    int y = get_global_id(1);
+    if (x >= cols || y >= rows) return;
+
+    int sx = x + phaseX;
+    int sy = y + phaseY;
+    int cellX = sx >= 0 ? sx / scale : -((-sx + scale - 1) / scale);
+    int cellY = sy >= 0 ? sy / scale : -((-sy + scale - 1) / scale);
+    int col = cellX % mapCols;
+    int row = cellY % mapRows;
+    if (col < 0) col += mapCols;
+    if (row < 0) row += mapRows;
+
+    float offset = (thresholds[row * mapCols + col] - 0.5f) * spread * strength;
+    float value = clamp((float)src[srcOffset + y * srcStep + x] + offset, 0.0f, 255.0f);
+    dst[dstOffset + y * dstStep + x] = lut[(int)value];
+}
+)";
+
+bool isAvailable() {
+    return cv::ocl::haveOpenCL() && cv::ocl::useOpenCL();
+}
+
+bool ditherThresholdMap(const cv::Mat& gray, const cv::Mat& thresholds, int scale, cv::Point phase,
+                        float spread, float strength, const std::array<uchar, 256>& lut, cv::Mat& result) {
+    if (!isAvailable() || gray.empty() || thresholds.empty()) return false;
+
+    // OpenCV caches the compiled program, so only the first call pays for the build
+    cv::ocl::Kernel kernel("thresholdMap", cv::ocl::ProgramSource(thresholdMapSource));
+    if (kernel.empty()) return false;
+
+    cv::Mat map;
+    thresholds.convertTo(map, CV_32F);
+    cv::Mat table(1, 256, CV_8U, const_cast<uchar*>(lut.data()));
+
+    cv::UMat source = gray.getUMat(cv::ACCESS_READ);
+    cv::UMat mapBuffer = map.getUMat(cv::ACCESS_READ);
+    cv::UMat tableBuffer = table.getUMat(cv::ACCESS_READ);
+    cv::UMat output(gray.size(), CV_8UC1);
+
+    kernel.args(cv::ocl::KernelArg::ReadOnlyNoSize(source), cv::ocl::KernelArg::WriteOnly(output),
+                cv::ocl::KernelArg::PtrReadOnly(mapBuffer), map.rows, map.cols, std::max(1, scale),
+                phase.x, phase.y, spread, strength, cv::ocl::KernelArg::PtrReadOnly(tableBuffer));
+
+    size_t globalSize[2] = {static_cast<size_t>(gray.cols), static_cast<size_t>(gray.rows)};
+    if (!kernel.run(2, globalSize, nullptr, true)) return false;
+
+    output.copyTo(result);
+    return true;
+}
+
+} // namespace Gpu
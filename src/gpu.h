@@ -0,0 +1,19 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <array>
+
+namespace Gpu {
+
+// Whether an OpenCL device is present and enabled (OPENCV_OPENCL_DEVICE=disabled
+// turns it off)
+bool isAvailable();
+
+// Threshold-map dithering of a single-channel image on the GPU. The CV_32F
+// thresholds matrix is tiled over the image (each cell scale x scale pixels,
+// shifted by phase), each pixel is offset by (threshold - 0.5) * spread * strength
+// and the result is mapped through lut. Returns false when the GPU couldn't run it
+bool ditherThresholdMap(const cv::Mat& gray, const cv::Mat& thresholds, int scale, cv::Point phase,
+                        float spread, float strength, const std::array<uchar, 256>& lut, cv::Mat& result);
+
+} // namespace Gpu
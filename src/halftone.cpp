@@ -0,0 +1,221 @@
+#include "halftone.h"
+#include "stipple.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdio>
+#include <fstream>
+
+namespace Halftone {
+
+// Grid the dots sit on, in the coordinates of the spot function: dot centers
+// are at (i + offset, j + offset) * cell along axes rotated by angle degrees,
+// and a spot position maps to the image at spot * scale - phase
+struct Grid {
+    float cell;
+    float angle;
+    float offset;
+    float scale;
+};
+
+// Clustered matrices that grow round dots, laid out as in matrices.cpp. The
+// cosine spot function of dots45 and newspaper puts dots on a 45 degree grid
+// period / sqrt(2) apart, starting at the matrix origin; spiral has one dot
+// in the middle of each cell
+static bool findMatrixGrid(const std::string& id, Grid& grid) {
+    const float root2 = std::sqrt(2.0f);
+    if (id == "dots45") grid = {8.0f / root2, 45.0f, 0.0f, 1.0f};
+    else if (id == "newspaper") grid = {5.0f / root2, 45.0f, 0.0f, 1.0f};
+    else if (id == "spiral") grid = {8.0f, 0.0f, 0.5f, 1.0f};
+    else return false;
+    return true;
+}
+
+bool supportsVectorHalftone(const Dithering::Parameters& params) {
+    Grid grid;
+    return params.algorithm == Dithering::Algorithm::HALFTONE || params.algorithm == Dithering::Algorithm::STIPPLE ||
+           (params.algorithm == Dithering::Algorithm::ORDERED_MATRIX && findMatrixGrid(params.matrix, grid)) ||
+           (Dithering::getBayerSize(params.algorithm) > 0 && params.bayerClustered);
+}
+
+// Dots of one screen. coverage is ink coverage (0-1) per pixel, already
+// averaged over about a cell
+static Screen traceScreen(const cv::Mat& coverage, const Grid& grid, Dithering::HalftoneShape shape,
+                          const Dithering::Parameters& params, const cv::Vec3b& ink) {
+    Screen screen;
+    screen.ink = ink;
+    screen.angle = grid.angle;
+
+    float radians = grid.angle * static_cast<float>(CV_PI) / 180.0f;
+    float c = std::cos(radians), s = std::sin(radians);
+    float cellPixels = grid.cell * grid.scale;
+
+    // Grid cells covering the image, found from its corners
+    float minU = 1e9f, maxU = -1e9f, minV = 1e9f, maxV = -1e9f;
+    for (float x : {0.0f, static_cast<float>(coverage.cols)}) {
+        for (float y : {0.0f, static_cast<float>(coverage.rows)}) {
+            float sx = (x + params.phaseX) / grid.scale;
+            float sy = (y + params.phaseY) / grid.scale;
+            float u = (sx * c + sy * s) / grid.cell;
+            float v = (-sx * s + sy * c) / grid.cell;
+            minU = std::min(minU, u);
+            maxU = std::max(maxU, u);
+            minV = std::min(minV, v);
+            maxV = std::max(maxV, v);
+        }
+    }
+
+    for (int j = static_cast<int>(std::floor(minV)) - 1; j <= static_cast<int>(std::ceil(maxV)); ++j) {
+        for (int i = static_cast<int>(std::floor(minU)) - 1; i <= static_cast<int>(std::ceil(maxU)); ++i) {
+            float u = i + grid.offset, v = j + grid.offset;
+            float x = (u * c - v * s) * grid.cell * grid.scale - params.phaseX;
+            float y = (u * s + v * c) * grid.cell * grid.scale - params.phaseY;
+            if (x < -cellPixels || y < -cellPixels ||
+                x > coverage.cols + cellPixels || y > coverage.rows + cellPixels) {
+                continue;
+            }
+
+            int sampleX = std::clamp(static_cast<int>(x), 0, coverage.cols - 1);
+            int sampleY = std::clamp(static_cast<int>(y), 0, coverage.rows - 1);
+            float amount = std::clamp(coverage.at<float>(sampleY, sampleX), 0.0f, 1.0f);
+            if (amount < 0.005f) continue;
+
+            // The raster inks where the spot function is below the coverage:
+            // 2 (u^2 + v^2) for circles, |u| + |v| for diamonds, in cells
+            float size = shape == Dithering::HalftoneShape::DIAMOND ? amount * cellPixels
+                                                                    : std::sqrt(amount / 2.0f) * cellPixels;
+            screen.dots.push_back({cv::Point2f(x, y), size});
+        }
+    }
+    return screen;
+}
+
+static float luma(const cv::Vec3b& color) {
+    return 0.114f * color[0] + 0.587f * color[1] + 0.299f * color[2];
+}
+
+VectorHalftone traceHalftone(const cv::Mat& input, const Dithering::Parameters& inputParams) {
+    Dithering::Parameters params = inputParams;
+    Dithering::applyScreenRuling(params);
+    VectorHalftone halftone;
+    halftone.size = input.size();
+    if (input.empty() || !supportsVectorHalftone(params)) return halftone;
+
+    // Stipple dots aren't on a grid; the same ones the raster draws
+    if (params.algorithm == Dithering::Algorithm::STIPPLE) {
+        Stipple::Stipples stipples = Stipple::placeStipples(Dithering::preprocessImage(input, params), params);
+        halftone.paper = stipples.paper;
+        halftone.screens.push_back({stipples.ink, 0.0f, std::move(stipples.dots)});
+        return halftone;
+    }
+
+    bool clustered = Dithering::getBayerSize(params.algorithm) > 0;
+    bool matrix = params.algorithm == Dithering::Algorithm::ORDERED_MATRIX || clustered;
+    Grid grid;
+    if (matrix) {
+        // Clustered Bayer cells have their dot in the middle; the matrix turns with ditherAngle
+        if (clustered) {
+            grid = {static_cast<float>(std::clamp(params.bayerSize, 2, Dithering::MAX_BAYER_SIZE)), 0.0f, 0.5f, 1.0f};
+        } else {
+            findMatrixGrid(params.matrix, grid);
+        }
+        grid.angle += params.ditherAngle;
+        grid.scale = static_cast<float>(std::max(1, static_cast<int>(std::lround(params.ditherScale))));
+    } else {
+        grid = {std::max(2.0f, params.halftoneCellSize), 0.0f, 0.5f, 1.0f};
+        halftone.shape = params.halftoneShape;
+    }
+
+    cv::Mat adjusted;
+    Dithering::preprocessImage(input, params).convertTo(adjusted, CV_32F, 1.0 / 255.0);
+    int window = std::max(1, static_cast<int>(std::lround(grid.cell * grid.scale)));
+    auto cellAverage = [window](const cv::Mat& image) {
+        cv::Mat averaged;
+        cv::blur(image, averaged, cv::Size(window, window));
+        return averaged;
+    };
+
+    std::vector<cv::Vec3b> palette = Dithering::getPalette(params);
+    bool gray = !palette.empty() && std::all_of(palette.begin(), palette.end(), [](const cv::Vec3b& color) {
+        return color[0] == color[1] && color[1] == color[2];
+    });
+
+    if (gray || palette.size() == 2) {
+        // Darkest palette color on the lightest
+        auto byLuma = [](const cv::Vec3b& a, const cv::Vec3b& b) { return luma(a) < luma(b); };
+        cv::Vec3b ink = *std::min_element(palette.begin(), palette.end(), byLuma);
+        halftone.paper = *std::max_element(palette.begin(), palette.end(), byLuma);
+        cv::Vec3f weights = Dithering::getGrayWeights(params);
+        float inkLevel = cv::Vec3f(ink).dot(weights) / 255.0f;
+        float paperLevel = cv::Vec3f(halftone.paper).dot(weights) / 255.0f;
+
+        cv::Mat coverage, level = Dithering::convertToGray(adjusted, params);
+        float range = std::max(paperLevel - inkLevel, 1e-3f);
+        level.convertTo(coverage, CV_32F, -1.0 / range, paperLevel / range);
+
+        if (!matrix) grid.angle = params.grayScreenAngle;
+        halftone.screens.push_back(traceScreen(cellAverage(coverage), grid, halftone.shape, params, ink));
+        return halftone;
+    }
+
+    // Cyan, magenta and yellow take away red, green and blue
+    const cv::Vec3b inks[3] = {cv::Vec3b(0, 255, 255), cv::Vec3b(255, 0, 255), cv::Vec3b(255, 255, 0)};
+    std::vector<cv::Mat> channels;
+    cv::split(adjusted, channels);
+    for (int channel : {2, 1, 0}) {
+        cv::Mat coverage;
+        channels[channel].convertTo(coverage, CV_32F, -1.0, 1.0);
+        if (!matrix) grid.angle = params.screenAngles[channel];
+        halftone.screens.push_back(traceScreen(cellAverage(coverage), grid, halftone.shape, params, inks[channel]));
+    }
+    return halftone;
+}
+
+static std::string hexColor(const cv::Vec3b& color) {
+    char text[8];
+    std::snprintf(text, sizeof(text), "#%02x%02x%02x", color[2], color[1], color[0]);
+    return text;
+}
+
+std::string toSvg(const VectorHalftone& halftone) {
+    std::string svg;
+    char line[160];
+    std::snprintf(line, sizeof(line),
+                  "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"%d\" height=\"%d\" viewBox=\"0 0 %d %d\">\n",
+                  halftone.size.width, halftone.size.height, halftone.size.width, halftone.size.height);
+    svg += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    svg += line;
+    svg += "<rect width=\"100%\" height=\"100%\" fill=\"" + hexColor(halftone.paper) + "\"/>\n";
+
+    bool overprint = halftone.screens.size() > 1;
+    for (const Screen& screen : halftone.screens) {
+        svg += "<g fill=\"" + hexColor(screen.ink) + "\"" +
+               (overprint ? " style=\"mix-blend-mode:multiply\"" : "") + ">\n";
+        float radians = screen.angle * static_cast<float>(CV_PI) / 180.0f;
+        float c = std::cos(radians), s = std::sin(radians);
+        for (const Dot& dot : screen.dots) {
+            float x = dot.center.x, y = dot.center.y, r = dot.size;
+            if (halftone.shape == Dithering::HalftoneShape::DIAMOND) {
+                // Corners on the screen's axes
+                std::snprintf(line, sizeof(line), "<path d=\"M%.2f %.2fL%.2f %.2fL%.2f %.2fL%.2f %.2fZ\"/>\n",
+                              x + r * c, y + r * s, x - r * s, y + r * c, x - r * c, y - r * s, x + r * s, y - r * c);
+            } else {
+                std::snprintf(line, sizeof(line), "<circle cx=\"%.2f\" cy=\"%.2f\" r=\"%.2f\"/>\n", x, y, r);
+            }
+            svg += line;
+        }
+        svg += "</g>\n";
+    }
+    svg += "</svg>\n";
+    return svg;
+}
+
+bool exportSvg(const cv::Mat& input, const Dithering::Parameters& params, const std::string& path) {
+    if (!supportsVectorHalftone(params)) return false;
+
+    std::string svg = toSvg(traceHalftone(input, params));
+    std::ofstream file(path, std::ios::binary);
+    file << svg;
+    return static_cast<bool>(file);
+}
+
+} // namespace Halftone
@@ -0,0 +1,53 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Halftone {
+
+// One dot of a screen, in image pixel coordinates
+struct Dot {
+    cv::Point2f center;
+    float size;                 // Radius for circles, center to corner for diamonds
+};
+
+// Dots of one ink, all on the same rotated grid
+struct Screen {
+    cv::Vec3b ink;              // BGR
+    float angle;                // Degrees; diamonds have their corners on the screen axes
+    std::vector<Dot> dots;
+};
+
+// Halftone as vector shapes: screens drawn in order over the paper color.
+// With more than one screen the inks overprint (multiply), like C, M and Y
+struct VectorHalftone {
+    cv::Size size;              // Image size in pixels
+    cv::Vec3b paper = cv::Vec3b(255, 255, 255);
+    Dithering::HalftoneShape shape = Dithering::HalftoneShape::CIRCLE;
+    std::vector<Screen> screens;
+};
+
+// Whether the settings produce dots that can be drawn as shapes: the halftone
+// or stipple algorithm, a clustered dot matrix (dots45, spiral, newspaper) or
+// clustered Bayer
+bool supportsVectorHalftone(const Dithering::Parameters& params);
+
+// Trace the screens the settings would dither input with. Each dot is sized
+// from the mean tone of its cell after the usual adjustments, following the
+// same spot function as the raster. Grayscale and two-color palettes get a
+// single screen of the darkest color on the lightest; anything else is
+// separated into cyan, magenta and yellow screens on white. Stippling gives
+// one screen of its dots, placed as for the raster
+VectorHalftone traceHalftone(const cv::Mat& input, const Dithering::Parameters& params);
+
+// SVG document for a traced halftone, one user unit per image pixel
+std::string toSvg(const VectorHalftone& halftone);
+
+// Trace and write an SVG file; false if the settings have no vector form or
+// the file can't be written
+bool exportSvg(const cv::Mat& input, const Dithering::Parameters& params, const std::string& path);
+
+} // namespace Halftone
@@ -0,0 +1,2442 @@
+#include "imageio.h"
+#include <algorithm>
+#include <atomic>
+#include <cctype>
+#include <cmath>
+#include <cstdio>
+#include <cstdint>
+#include <cstdlib>
+#include <cstring>
+#include <chrono>
+#include <filesystem>
+#include <fstream>
+#include <unordered_map>
+#include <vector>
+
+namespace ImageIO {
+
+// Session-wide settings: set from the UI thread, read by loads on worker threads
+static std::atomic<ColorManagement> colorManagement{ColorManagement::ASSUME_SRGB};
+
+void setColorManagement(ColorManagement mode) {
+    colorManagement = mode;
+}
+
+ColorManagement getColorManagement() {
+    return colorManagement;
+}
+
+static std::atomic<ColorProfile::Space> profileSpace{ColorProfile::Space::SRGB};
+
+void setProfileSpace(ColorProfile::Space space) {
+    profileSpace = space;
+}
+
+ColorProfile::Space getProfileSpace() {
+    return profileSpace;
+}
+
+static std::atomic<size_t> decodeMemoryLimit{DEFAULT_DECODE_MEMORY_LIMIT};
+static std::atomic<bool> downscaleOversized{false};
+
+void setDecodeMemoryLimit(size_t bytes) {
+    decodeMemoryLimit = bytes;
+}
+
+size_t getDecodeMemoryLimit() {
+    return decodeMemoryLimit;
+}
+
+void setDownscaleOversized(bool downscale) {
+    downscaleOversized = downscale;
+}
+
+bool getDownscaleOversized() {
+    return downscaleOversized;
+}
+
+// Set from the UI thread, read by loads on worker threads
+static std::atomic<int> svgWidth{0};
+
+void setSvgWidth(int width) {
+    svgWidth = std::max(0, width);
+}
+
+int getSvgWidth() {
+    return svgWidth;
+}
+
+// How an image fits the decode memory limit
+enum class DecodePlan {
+    FULL,           // Fits, or its header can't be read
+    DOWNSCALE,      // Open at scale times its size
+    REFUSE          // Doesn't fit, even downscaled
+};
+
+static DecodePlan planDecode(const std::string& filename, ImageInfo& header, double& scale) {
+    scale = 1.0;
+    size_t limit = decodeMemoryLimit;
+    if (limit == 0 || !readImageHeader(filename, header)) return DecodePlan::FULL;
+    size_t needed = estimateDecodeMemory(header);
+    if (needed <= limit) return DecodePlan::FULL;
+    if (!downscaleOversized) return DecodePlan::REFUSE;
+
+    // Memory follows the pixel count, so sides shrink by its square root
+    scale = std::sqrt(static_cast<double>(limit) / needed);
+    if (header.format == "jpeg") {
+        // libjpeg decodes straight to 1/2, 1/4 or 1/8 size, never holding the full image
+        if (scale < 0.125) return DecodePlan::REFUSE;
+        scale = scale >= 0.5 ? 0.5 : scale >= 0.25 ? 0.25 : 0.125;
+        return DecodePlan::DOWNSCALE;
+    }
+
+    // Other decoders hold the whole image before it can be scaled
+    size_t sampleBytes = header.bitDepth > 16 ? 4 : header.bitDepth > 8 ? 2 : 1;
+    size_t decoded = static_cast<size_t>(header.width) * header.height * header.channels * sampleBytes;
+    return decoded > limit ? DecodePlan::REFUSE : DecodePlan::DOWNSCALE;
+}
+
+std::string getExtension(const std::string& filename) {
+    size_t dot = filename.find_last_of('.');
+    if (dot == std::string::npos) return "";
+
+    std::string ext = filename.substr(dot + 1);
+    std::transform(ext.begin(), ext.end(), ext.begin(), ::tolower);
+    return ext;
+}
+
+// Extensions of the image formats that can be loaded
+const std::vector<std::string>& getImageExtensions() {
+    static const std::vector<std::string> extensions = []() {
+        std::vector<std::string> list = {
+            "png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp", "gif", "exr", "avif", "jxl", "heic", "heif",
+            "pbm", "pgm", "ppm", "pnm", "svg"
+        };
+        const auto& raw = getRawExtensions();
+        list.insert(list.end(), raw.begin(), raw.end());
+        return list;
+    }();
+    return extensions;
+}
+
+const std::vector<std::string>& getRawExtensions() {
+    static const std::vector<std::string> extensions = {
+        "dng", "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "raf", "orf", "rw2", "pef", "srw", "3fr",
+        "iiq", "x3f"
+    };
+    return extensions;
+}
+
+bool isRawFile(const std::string& filename) {
+    const auto& extensions = getRawExtensions();
+    return std::find(extensions.begin(), extensions.end(), getExtension(filename)) != extensions.end();
+}
+
+// HEIC and RAW are read only; AVIF and JPEG XL need an OpenCV build with libavif/libjxl
+const std::vector<std::string>& getSaveExtensions() {
+    static const std::vector<std::string> extensions = {
+        "png", "jpg", "jpeg", "bmp", "tif", "tiff", "webp", "gif", "exr", "avif", "jxl", "ico", "pbm", "pgm",
+        "ppm", "pnm", "xbm"
+    };
+    return extensions;
+}
+
+// Formats that go through a command line decoder: RAW always (DNG would
+// otherwise open as a TIFF of undemosaiced sensor data or its preview), SVG
+// always, the others only when OpenCV can't read them
+static bool needsConversion(const std::string& filename) {
+    std::string ext = getExtension(filename);
+    if (isRawFile(filename) || ext == "svg") return true;
+    return (ext == "avif" || ext == "jxl" || ext == "heic" || ext == "heif") && !cv::haveImageReader(filename);
+}
+
+// Decode with a system tool to a temp PNG (16-bit TIFF for RAW), returns its
+// path ("" on failure)
+static std::string convertToTempFile(const std::string& filename) {
+    bool raw = isRawFile(filename);
+    std::string tempPath = Platform::createTempFile(raw ? ".tiff" : ".png");
+    if (tempPath.empty()) return "";
+    if (Platform::convertImage(filename, tempPath, raw ? "raw" : getExtension(filename), svgWidth)) {
+        return tempPath;
+    }
+    std::error_code ec;
+    std::filesystem::remove(tempPath, ec);
+    return "";
+}
+
+bool isImageFile(const std::string& filename) {
+    const auto& extensions = getImageExtensions();
+    return std::find(extensions.begin(), extensions.end(), getExtension(filename)) != extensions.end();
+}
+
+Errors::Error describeLoadFailure(const std::string& filename) {
+    using Errors::ErrorCode;
+    std::error_code ec;
+    if (!std::filesystem::exists(filename, ec)) return {ErrorCode::NOT_FOUND, "No such file"};
+    if (!isImageFile(filename)) return {ErrorCode::UNSUPPORTED_FORMAT, "Not a supported image format"};
+    ImageInfo header;
+    double scale = 1.0;
+    if (planDecode(filename, header, scale) == DecodePlan::REFUSE) {
+        char message[256];
+        std::snprintf(message, sizeof(message),
+                      "The image is %dx%d and needs about %zu MB to open, over the %zu MB decode memory limit%s",
+                      header.width, header.height, estimateDecodeMemory(header) >> 20, decodeMemoryLimit >> 20,
+                      downscaleOversized ? " even downscaled" : "; raise the limit or allow downscaling");
+        return {ErrorCode::OUT_OF_MEMORY, message};
+    }
+    std::string ext = getExtension(filename);
+    if (isRawFile(filename) && !Platform::hasImageConverter("raw")) {
+        return {ErrorCode::UNSUPPORTED_FORMAT, "No camera RAW decoder (install LibRaw, dcraw or ImageMagick)"};
+    }
+    if (ext == "svg" && !Platform::hasImageConverter("svg")) {
+        return {ErrorCode::UNSUPPORTED_FORMAT, "No SVG rasterizer (install resvg, librsvg, Inkscape or ImageMagick)"};
+    }
+    if (needsConversion(filename) && !isRawFile(filename) && !Platform::hasImageConverter(ext)) {
+        return {ErrorCode::UNSUPPORTED_FORMAT,
+                "No decoder for " + ext + " files (install libheif, libavif or libjxl tools, or ImageMagick)"};
+    }
+    if (!std::ifstream(filename, std::ios::binary)) return {ErrorCode::IO, "Could not read the file"};
+    return {ErrorCode::DECODE, "Could not decode the image"};
+}
+
+Errors::Error describeSaveFailure(const std::string& filename) {
+    std::string ext = getExtension(filename);
+    if ((ext == "avif" || ext == "jxl") && !cv::haveImageWriter(filename)) {
+        return {Errors::ErrorCode::UNSUPPORTED_FORMAT,
+                std::string("This OpenCV build can't write ") + (ext == "avif" ? "AVIF" : "JPEG XL")};
+    }
+    if (ext != "ico" && ext != "apng" && !cv::haveImageWriter(filename)) {
+        return {Errors::ErrorCode::UNSUPPORTED_FORMAT, "Can't write this image format"};
+    }
+    return {Errors::ErrorCode::IO, "Could not write the file"};
+}
+
+bool formatSupportsAlpha(const std::string& filename) {
+    std::string ext = getExtension(filename);
+    return ext == "png" || ext == "webp" || ext == "tif" || ext == "tiff" || ext == "gif" || ext == "exr" ||
+           ext == "ico" || ext == "avif" || ext == "jxl";
+}
+
+bool formatHasBinaryAlpha(const std::string& filename) {
+    return getExtension(filename) == "gif";
+}
+
+// In PngStrategy order
+const std::vector<std::string>& getPngStrategyIds() {
+    static const std::vector<std::string> ids = {"default", "filtered", "huffman", "rle", "fixed"};
+    return ids;
+}
+
+bool parsePngStrategy(const std::string& id, PngStrategy& strategy) {
+    const auto& ids = getPngStrategyIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    strategy = static_cast<PngStrategy>(match - ids.begin());
+    return true;
+}
+
+std::string getPngStrategyName(PngStrategy strategy) {
+    switch (strategy) {
+        case PngStrategy::DEFAULT: return "Default";
+        case PngStrategy::FILTERED: return "Filtered";
+        case PngStrategy::HUFFMAN_ONLY: return "Huffman Only";
+        case PngStrategy::RLE: return "Run Length";
+        case PngStrategy::FIXED: return "Fixed Codes";
+        default: return "Unknown";
+    }
+}
+
+std::string formatFileSize(uintmax_t bytes) {
+    char text[32];
+    if (bytes < 1024) {
+        std::snprintf(text, sizeof(text), "%ju B", bytes);
+    } else if (bytes < 1024 * 1024) {
+        std::snprintf(text, sizeof(text), "%.1f KB", bytes / 1024.0);
+    } else {
+        std::snprintf(text, sizeof(text), "%.1f MB", bytes / (1024.0 * 1024.0));
+    }
+    return text;
+}
+
+// imwrite/imencode parameters for a file's format
+static std::vector<int> getEncoderParams(const std::string& filename, const EncodeOptions& options) {
+    std::string ext = getExtension(filename);
+    if (ext == "jpg" || ext == "jpeg") {
+        return {cv::IMWRITE_JPEG_QUALITY, std::clamp(options.jpegQuality, 1, 100)};
+    }
+    if (ext == "png" || ext == "apng") {
+        static const int strategies[] = {
+            cv::IMWRITE_PNG_STRATEGY_DEFAULT, cv::IMWRITE_PNG_STRATEGY_FILTERED, cv::IMWRITE_PNG_STRATEGY_HUFFMAN_ONLY,
+            cv::IMWRITE_PNG_STRATEGY_RLE, cv::IMWRITE_PNG_STRATEGY_FIXED
+        };
+        return {cv::IMWRITE_PNG_COMPRESSION, std::clamp(options.pngCompression, 0, 9),
+                cv::IMWRITE_PNG_STRATEGY, strategies[static_cast<int>(options.pngStrategy)]};
+    }
+    if (ext == "webp") {
+        // OpenCV switches to lossless above quality 100
+        return {cv::IMWRITE_WEBP_QUALITY, options.webpLossless ? 101 : std::clamp(options.webpQuality, 1, 100)};
+    }
+    // OpenCV has had the AVIF and JPEG XL flags since 4.11; older builds that
+    // write those formats at all use their defaults
+#if CV_VERSION_MAJOR > 4 || (CV_VERSION_MAJOR == 4 && CV_VERSION_MINOR >= 11)
+    if (ext == "avif") {
+        return {cv::IMWRITE_AVIF_QUALITY, std::clamp(options.avifQuality, 0, 100), cv::IMWRITE_AVIF_DEPTH, 8};
+    }
+    if (ext == "jxl") {
+        return {cv::IMWRITE_JPEGXL_QUALITY, std::clamp(options.jxlQuality, 0, 100)};
+    }
+#endif
+    return {};
+}
+
+// Header fields of interest from a JPEG file's SOF and APP14 segments
+struct JpegHeader {
+    int width = 0;
+    int height = 0;
+    int precision = 8;
+    int components = 0;
+    bool adobe = false;
+    int adobeTransform = -1;
+};
+
+// Walk the JPEG marker segments up to the start of scan
+static bool parseJpegHeader(const std::string& filename, JpegHeader& header) {
+    std::ifstream file(filename, std::ios::binary);
+    if (!file) return false;
+
+    if (file.get() != 0xFF || file.get() != 0xD8) return false;
+
+    bool foundFrame = false;
+    while (file) {
+        if (file.get() != 0xFF) break;
+
+        int marker = file.get();
+        while (marker == 0xFF) marker = file.get();
+        if (marker == EOF || marker == 0xD9 || marker == 0xDA) break;
+        if (marker == 0x01 || (marker >= 0xD0 && marker <= 0xD7)) continue;
+
+        int lengthHigh = file.get();
+        int lengthLow = file.get();
+        int length = (lengthHigh << 8) | lengthLow;
+        if (!file || length < 2) break;
+
+        std::vector<unsigned char> data(length - 2);
+        if (!file.read(reinterpret_cast<char*>(data.data()), data.size())) break;
+
+        bool isFrame = marker >= 0xC0 && marker <= 0xCF &&
+                       marker != 0xC4 && marker != 0xC8 && marker != 0xCC;
+        if (isFrame && data.size() >= 6) {
+            header.precision = data[0];
+            header.height = (data[1] << 8) | data[2];
+            header.width = (data[3] << 8) | data[4];
+            header.components = data[5];
+            foundFrame = true;
+        } else if (marker == 0xEE && data.size() >= 12 && std::memcmp(data.data(), "Adobe", 5) == 0) {
+            header.adobe = true;
+            header.adobeTransform = data[11];
+        }
+    }
+
+    return foundFrame;
+}
+
+bool readImageHeader(const std::string& filename, ImageInfo& info) {
+    JpegHeader jpeg;
+    if (parseJpegHeader(filename, jpeg)) {
+        info.format = "jpeg";
+        info.width = jpeg.width;
+        info.height = jpeg.height;
+        info.channels = jpeg.components == 1 ? 1 : 3;
+        info.bitDepth = jpeg.precision;
+        return info.width > 0 && info.height > 0;
+    }
+
+    std::ifstream file(filename, std::ios::binary);
+    unsigned char head[32] = {};
+    file.read(reinterpret_cast<char*>(head), sizeof(head));
+    size_t length = static_cast<size_t>(file.gcount());
+    auto le16 = [&head](size_t at) { return head[at] | head[at + 1] << 8; };
+    auto le32 = [&le16](size_t at) {
+        return static_cast<uint32_t>(le16(at)) | static_cast<uint32_t>(le16(at + 2)) << 16;
+    };
+    auto be32 = [&head](size_t at) {
+        return static_cast<uint32_t>(head[at]) << 24 | head[at + 1] << 16 | head[at + 2] << 8 | head[at + 3];
+    };
+
+    info.bitDepth = 8;
+    if (length >= 26 && std::memcmp(head, "\x89PNG\r\n\x1a\n", 8) == 0) {
+        // IHDR; color types 0 gray, 2 RGB, 3 palette, 4 gray + alpha, 6 RGBA
+        info.format = "png";
+        info.width = static_cast<int>(be32(16));
+        info.height = static_cast<int>(be32(20));
+        info.bitDepth = head[24] == 16 ? 16 : 8;
+        info.channels = head[25] == 0 ? 1 : head[25] == 2 ? 3 : 4;
+    } else if (length >= 10 && std::memcmp(head, "GIF8", 4) == 0) {
+        info.format = "gif";
+        info.width = le16(6);
+        info.height = le16(8);
+        info.channels = 4;
+    } else if (length >= 30 && head[0] == 'B' && head[1] == 'M') {
+        info.format = "bmp";
+        info.width = std::abs(static_cast<int32_t>(le32(18)));
+        info.height = std::abs(static_cast<int32_t>(le32(22)));
+        info.channels = le16(28) == 32 ? 4 : 3;
+    } else if (length >= 30 && std::memcmp(head, "RIFF", 4) == 0 && std::memcmp(head + 8, "WEBP", 4) == 0) {
+        info.format = "webp";
+        info.channels = 4;
+        if (std::memcmp(head + 12, "VP8 ", 4) == 0) {
+            info.width = le16(26) & 0x3FFF;
+            info.height = le16(28) & 0x3FFF;
+        } else if (std::memcmp(head + 12, "VP8L", 4) == 0) {
+            info.width = 1 + (head[21] | (head[22] & 0x3F) << 8);
+            info.height = 1 + (head[22] >> 6 | head[23] << 2 | (head[24] & 0x0F) << 10);
+        } else if (std::memcmp(head + 12, "VP8X", 4) == 0) {
+            info.width = 1 + (head[24] | head[25] << 8 | head[26] << 16);
+            info.height = 1 + (head[27] | head[28] << 8 | head[29] << 16);
+        }
+    } else if (length >= 8 && (std::memcmp(head, "II*\0", 4) == 0 || std::memcmp(head, "MM\0*", 4) == 0 ||
+                               (length >= 16 && (std::memcmp(head, "II+\0", 4) == 0 ||
+                                                 std::memcmp(head, "MM\0+", 4) == 0)))) {
+        // First IFD: entries of tag, type, count and a value field, 12 bytes
+        // with 4-byte fields, or 20 with 8-byte ones in BigTIFF. The IFD can be
+        // anywhere, often after the image data
+        bool little = head[0] == 'I';
+        bool big = head[2] == '+' || head[3] == '+';
+        auto read = [&file](uint64_t offset, unsigned char* out, size_t size) {
+            file.clear();
+            file.seekg(static_cast<std::streamoff>(offset));
+            return static_cast<bool>(file.read(reinterpret_cast<char*>(out), size));
+        };
+        auto u16 = [little](const unsigned char* p) { return little ? p[0] | p[1] << 8 : p[0] << 8 | p[1]; };
+        auto u32 = [little, &u16](const unsigned char* p) {
+            uint32_t first = static_cast<uint32_t>(u16(p)), second = static_cast<uint32_t>(u16(p + 2));
+            return little ? first | second << 16 : first << 16 | second;
+        };
+        auto u64 = [little, &u32](const unsigned char* p) {
+            uint64_t first = u32(p), second = u32(p + 4);
+            return little ? first | second << 32 : first << 32 | second;
+        };
+        size_t fieldSize = big ? 8 : 4;
+        size_t entrySize = big ? 20 : 12;
+
+        // An entry's first value: in the field when all of them fit there,
+        // otherwise at the offset the field holds (BitsPerSample of RGB)
+        auto firstValue = [&](const unsigned char* entry) -> uint64_t {
+            int type = u16(entry + 2);
+            uint64_t count = big ? u64(entry + 4) : u32(entry + 4);
+            size_t size = type == 3 ? 2 : type == 16 ? 8 : 4;    // SHORT, LONG8, LONG
+            const unsigned char* field = entry + 4 + fieldSize;
+            unsigned char stored[8];
+            if (count * size > fieldSize) {
+                if (!read(big ? u64(field) : u32(field), stored, size)) return 0;
+                field = stored;
+            }
+            return size == 2 ? u16(field) : size == 8 ? u64(field) : u32(field);
+        };
+
+        info.format = "tiff";
+        info.channels = 1;
+        uint64_t ifd = big ? u64(head + 8) : u32(head + 4);
+        unsigned char count[8];
+        if (!read(ifd, count, big ? 8 : 2)) return false;
+        int entries = static_cast<int>(std::min<uint64_t>(big ? u64(count) : u16(count), 1000));
+        std::vector<unsigned char> table(static_cast<size_t>(entries) * entrySize);
+        if (!read(ifd + (big ? 8 : 2), table.data(), table.size())) return false;
+        for (int i = 0; i < entries; ++i) {
+            const unsigned char* entry = table.data() + i * entrySize;
+            int tag = u16(entry);
+            if (tag == 256) info.width = static_cast<int>(firstValue(entry));
+            if (tag == 257) info.height = static_cast<int>(firstValue(entry));
+            if (tag == 258) info.bitDepth = static_cast<int>(firstValue(entry));
+            if (tag == 277) info.channels = static_cast<int>(firstValue(entry));
+        }
+        if (info.channels == 2) info.channels = 4;      // Gray + alpha decodes as BGRA
+    } else {
+        return false;
+    }
+    return info.width > 0 && info.height > 0 && info.channels > 0;
+}
+
+size_t estimateDecodeMemory(const ImageInfo& info) {
+    double pixels = static_cast<double>(info.width) * info.height;
+    int sampleBytes = info.bitDepth > 16 ? 4 : info.bitDepth > 8 ? 2 : 1;
+    double total = pixels * info.channels * sampleBytes;    // Decoder output
+    if (info.bitDepth > 8) {
+        // Float copy, the 8-bit working image and the precise color
+        total += pixels * (info.channels * 5 + 12);
+    }
+    total += pixels * 4;                                    // Color and alpha split apart
+    return static_cast<size_t>(total);
+}
+
+// Parse an embedded profile. When managed (HONOR_PROFILES), warn about one
+// that can't be applied (LUT-based, CMYK or Lab profiles)
+static bool checkIccProfile(const std::vector<uchar>& icc, ColorProfile::Profile& profile, bool managed,
+                            std::vector<Warning>& warnings) {
+    if (icc.empty()) return false;
+    if (ColorProfile::parseProfile(icc, profile)) return true;
+    if (!managed) return false;
+
+    std::string name = ColorProfile::readDescription(icc);
+    std::string label = name.empty() ? "Embedded ICC profile" : "Embedded ICC profile '" + name + "'";
+    warnings.push_back({WarningCode::UNMANAGED_PROFILE,
+        label + " can't be applied; colors are interpreted as sRGB"});
+    return false;
+}
+
+static std::string jpegColorSpace(const JpegHeader& header) {
+    switch (header.components) {
+        case 1: return "Gray";
+        case 3: return (header.adobe && header.adobeTransform == 0) ? "RGB" : "YCbCr";
+        case 4: return (header.adobe && header.adobeTransform == 2) ? "YCCK" : "CMYK";
+        default: return "Unknown";
+    }
+}
+
+// Collect warnings for JPEG layouts that OpenCV decodes lossily or not at all
+static void checkJpegHeader(const JpegHeader& header, std::vector<Warning>& warnings) {
+    if (header.components == 4) {
+        warnings.push_back({WarningCode::CMYK_CONVERTED,
+            jpegColorSpace(header) + " JPEG converted to RGB without color management; colors may shift"});
+        if (!header.adobe) {
+            warnings.push_back({WarningCode::CMYK_UNTAGGED,
+                "CMYK JPEG has no Adobe marker; ink polarity is assumed inverted and may be wrong"});
+        }
+    } else if (header.components != 1 && header.components != 3) {
+        warnings.push_back({WarningCode::UNUSUAL_COLORSPACE,
+            "JPEG with " + std::to_string(header.components) + " components decoded as-is"});
+    }
+
+    if (header.precision > 8) {
+        warnings.push_back({WarningCode::HIGH_BIT_DEPTH,
+            std::to_string(header.precision) + "-bit JPEG reduced to 8 bits per channel"});
+    }
+}
+
+// Linear light (0-1) to sRGB-encoded (0-1), for scene-referred formats like EXR
+static float linearToSrgb(float value) {
+    value = std::clamp(value, 0.0f, 1.0f);
+    return value <= 0.0031308f ? value * 12.92f : 1.055f * std::pow(value, 1.0f / 2.4f) - 0.055f;
+}
+
+// Turn pixels stored in camera orientation upright (EXIF orientation 2-8).
+// Transposing can't work in place on non-square images, hence the copy
+static void applyOrientation(cv::Mat& image, int orientation) {
+    cv::Mat upright;
+    switch (orientation) {
+        case 2: cv::flip(image, upright, 1); break;
+        case 3: cv::rotate(image, upright, cv::ROTATE_180); break;
+        case 4: cv::flip(image, upright, 0); break;
+        case 5: cv::transpose(image, upright); break;
+        case 6: cv::rotate(image, upright, cv::ROTATE_90_CLOCKWISE); break;
+        case 7:
+            cv::transpose(image, upright);
+            cv::flip(upright, upright, -1);
+            break;
+        case 8: cv::rotate(image, upright, cv::ROTATE_90_COUNTERCLOCKWISE); break;
+        default: return;
+    }
+    image = upright;
+}
+
+void enableOpenExr() {
+#ifdef _WIN32
+    _putenv_s("OPENCV_IO_ENABLE_OPENEXR", "1");
+#else
+    setenv("OPENCV_IO_ENABLE_OPENEXR", "1", 0);
+#endif
+}
+
+// Load image file, keeping alpha separate from color
+bool loadImage(const std::string& filename, cv::Mat& color, cv::Mat& alpha,
+               std::vector<Warning>* warnings, cv::Mat* precise) {
+    if (Platform::isRemoteUrl(filename)) {
+        return loadImageFromUrl(filename, color, alpha, warnings, DEFAULT_MAX_DOWNLOAD_BYTES, nullptr, precise);
+    }
+    if (precise) precise->release();
+
+    // Camera RAW, and phone and modern formats OpenCV can't read, are decoded
+    // by a system tool first
+    if (needsConversion(filename)) {
+        std::string converted = convertToTempFile(filename);
+        if (converted.empty()) return false;
+        bool loaded = loadImage(converted, color, alpha, warnings, precise);
+        std::remove(converted.c_str());
+        return loaded;
+    }
+
+    std::vector<Warning> found;
+    std::string ext = getExtension(filename);
+
+    // Only decode unchanged for formats that can carry alpha, so that other
+    // formats keep OpenCV's default handling (e.g. EXIF orientation for JPEG)
+    int flags = formatSupportsAlpha(filename) ? cv::IMREAD_UNCHANGED : cv::IMREAD_COLOR;
+
+    JpegHeader jpeg;
+    bool isJpeg = (ext == "jpg" || ext == "jpeg" || ext == "jpe") && parseJpegHeader(filename, jpeg);
+    if (isJpeg) {
+        checkJpegHeader(jpeg, found);
+        // 12-bit JPEGs only decode when any depth is allowed
+        if (jpeg.precision > 8) flags = cv::IMREAD_COLOR | cv::IMREAD_ANYDEPTH;
+    }
+
+    // Stay within the decode memory limit, decoding JPEGs at reduced size
+    ImageInfo header;
+    double scale = 1.0;
+    DecodePlan plan = planDecode(filename, header, scale);
+    if (plan == DecodePlan::REFUSE) return false;
+    bool reduced = plan == DecodePlan::DOWNSCALE && header.format == "jpeg";
+    if (reduced) {
+        flags = scale == 0.5 ? cv::IMREAD_REDUCED_COLOR_2 :
+                scale == 0.25 ? cv::IMREAD_REDUCED_COLOR_4 : cv::IMREAD_REDUCED_COLOR_8;
+    }
+
+    // Decoders throw when they can't allocate; that's a failed load, not a crash
+    cv::Mat img;
+    try {
+        img = cv::imread(filename, flags);
+    } catch (const std::exception&) {
+        return false;
+    }
+    if (img.empty()) return false;
+    if (plan == DecodePlan::DOWNSCALE) {
+        if (!reduced) {
+            cv::Size size(std::max(1, static_cast<int>(std::lround(img.cols * scale))),
+                          std::max(1, static_cast<int>(std::lround(img.rows * scale))));
+            cv::resize(img, img, size, 0, 0, cv::INTER_AREA);
+        }
+        char message[160];
+        std::snprintf(message, sizeof(message),
+                      "Opened at %dx%d (%.0f%% size) to stay under the %zu MB decode memory limit",
+                      img.cols, img.rows, scale * 100.0, decodeMemoryLimit >> 20);
+        found.push_back({WarningCode::DOWNSCALED, message});
+    }
+
+    // OpenCV applies EXIF orientation itself, except when decoding unchanged
+    bool managed = colorManagement == ColorManagement::HONOR_PROFILES;
+    Metadata metadata;
+    bool hasMetadata = (flags == cv::IMREAD_UNCHANGED || managed) && readMetadata(filename, metadata);
+    if (hasMetadata && flags == cv::IMREAD_UNCHANGED) applyOrientation(img, metadata.orientation);
+
+    // Bring high bit depths to a 0-1 float scale, sRGB encoded
+    cv::Mat highDepth;
+    if (img.depth() == CV_16U) {
+        // 12-bit JPEG samples are stored unscaled in a 16-bit container
+        double scale = (isJpeg && jpeg.precision == 12) ? 1.0 / 4095.0 : 1.0 / 65535.0;
+        img.convertTo(highDepth, CV_32F, scale);
+    } else if (img.depth() == CV_32F) {
+        // EXR holds linear light; highlights above 1.0 clip to white
+        highDepth = img.clone();
+        int colorChannels = std::min(highDepth.channels(), 3);
+        for (int y = 0; y < highDepth.rows; ++y) {
+            float* row = highDepth.ptr<float>(y);
+            for (int x = 0; x < highDepth.cols; ++x) {
+                for (int c = 0; c < colorChannels; ++c) {
+                    float& value = row[x * highDepth.channels() + c];
+                    value = ext == "exr" ? linearToSrgb(value) : std::clamp(value, 0.0f, 1.0f);
+                }
+            }
+        }
+    }
+
+    // Colors go from the embedded profile, or sRGB without one, to the
+    // profile space, before high bit depths are reduced
+    if (managed) {
+        ColorProfile::Profile source = ColorProfile::getSpaceProfile(ColorProfile::Space::SRGB);
+        if (hasMetadata) checkIccProfile(metadata.icc, source, managed, found);
+        ColorProfile::Profile target = ColorProfile::getSpaceProfile(profileSpace);
+        if (!ColorProfile::isEquivalent(source, target)) {
+            ColorProfile::convert(highDepth.empty() ? img : highDepth, source, target);
+        }
+    }
+
+    // The working image is 8-bit; callers that ask for it also get the full
+    // precision color as CV_32FC3 on a 0-255 scale
+    if (!highDepth.empty()) {
+        highDepth.convertTo(img, CV_8U, 255.0);
+        if (precise) {
+            cv::Mat scaled;
+            highDepth.convertTo(scaled, CV_32F, 255.0);
+            if (scaled.channels() == 1) {
+                cv::cvtColor(scaled, *precise, cv::COLOR_GRAY2BGR);
+            } else if (scaled.channels() == 4) {
+                cv::cvtColor(scaled, *precise, cv::COLOR_BGRA2BGR);
+            } else {
+                *precise = scaled;
+            }
+        } else if (!isJpeg) {
+            found.push_back({WarningCode::HIGH_BIT_DEPTH, "High bit depth image reduced to 8 bits per channel"});
+        }
+    }
+
+    alpha.release();
+
+    if (img.channels() == 1) {
+        cv::cvtColor(img, color, cv::COLOR_GRAY2BGR);
+    } else if (img.channels() == 4) {
+        std::vector<cv::Mat> channels;
+        cv::split(img, channels);
+        alpha = channels[3];
+        channels.pop_back();
+        cv::merge(channels, color);
+
+        // Fully opaque images don't need an alpha channel
+        double minAlpha = 0.0;
+        cv::minMaxLoc(alpha, &minAlpha);
+        if (minAlpha >= 255.0) {
+            alpha.release();
+        }
+    } else {
+        color = img;
+    }
+
+    if (warnings) {
+        warnings->insert(warnings->end(), found.begin(), found.end());
+    }
+    return true;
+}
+
+bool renderText(const std::string& text, const TextOptions& options, cv::Mat& color,
+                std::vector<Warning>* warnings) {
+    if (text.empty()) return false;
+    auto hex = [](const cv::Vec3b& bgr) {
+        char value[8];
+        std::snprintf(value, sizeof(value), "%02x%02x%02x", bgr[2], bgr[1], bgr[0]);
+        return std::string(value);
+    };
+
+    std::string tempPath = Platform::createTempFile(".png");
+    int size = std::clamp(options.size, 4, 2000);
+    int padding = std::clamp(options.padding, 0, 2000);
+    if (!tempPath.empty()) {
+        if (Platform::renderText(text, options.font, size, hex(options.color), hex(options.background), padding,
+                                 tempPath)) {
+            color = cv::imread(tempPath, cv::IMREAD_COLOR);
+        }
+        std::error_code ec;
+        std::filesystem::remove(tempPath, ec);
+        if (!color.empty()) return true;
+    }
+
+    // Without ImageMagick, OpenCV's built-in stroke font
+    if (warnings) {
+        std::string font = options.font.empty() ? "the default" : options.font;
+        warnings->push_back({WarningCode::FALLBACK_FONT,
+                             "ImageMagick isn't installed, so the text uses a built-in font instead of " + font});
+    }
+    std::vector<std::string> lines;
+    size_t start = 0;
+    while (start <= text.size()) {
+        size_t end = text.find('\n', start);
+        if (end == std::string::npos) end = text.size();
+        lines.push_back(text.substr(start, end - start));
+        start = end + 1;
+    }
+    const int font = cv::FONT_HERSHEY_SIMPLEX;
+    double scale = cv::getFontScaleFromHeight(font, size);
+    int thickness = std::max(1, size / 12);
+    int lineHeight = static_cast<int>(size * 1.4);
+    int width = 1;
+    for (const auto& line : lines) {
+        int baseline = 0;
+        width = std::max(width, cv::getTextSize(line, font, scale, thickness, &baseline).width);
+    }
+    color = cv::Mat(lineHeight * static_cast<int>(lines.size()) + 2 * padding, width + 2 * padding, CV_8UC3,
+                    cv::Scalar(options.background[0], options.background[1], options.background[2]));
+    for (size_t i = 0; i < lines.size(); ++i) {
+        cv::Point origin(padding, padding + lineHeight * static_cast<int>(i) + size);
+        cv::putText(color, lines[i], origin, font, scale,
+                    cv::Scalar(options.color[0], options.color[1], options.color[2]), thickness, cv::LINE_AA);
+    }
+    return true;
+}
+
+bool loadImageFromUrl(const std::string& url, cv::Mat& color, cv::Mat& alpha,
+                      std::vector<Warning>* warnings, size_t maxBytes,
+                      Platform::DownloadProgress progress, cv::Mat* precise) {
+    std::string tempFile = Platform::downloadToTempFile(url, maxBytes, progress);
+    if (tempFile.empty()) return false;
+
+    bool loaded = loadImage(tempFile, color, alpha, warnings, precise);
+    std::remove(tempFile.c_str());
+    return loaded;
+}
+
+// File extension for encoded image data, from its signature ("" if unknown)
+static std::string sniffExtension(const std::vector<unsigned char>& data) {
+    auto startsWith = [&data](const char* magic, size_t length, size_t offset = 0) {
+        return data.size() >= offset + length && std::memcmp(data.data() + offset, magic, length) == 0;
+    };
+    if (startsWith("\x89PNG", 4)) return "png";
+    if (startsWith("\xFF\xD8\xFF", 3)) return "jpg";
+    if (startsWith("GIF8", 4)) return "gif";
+    if (startsWith("BM", 2)) return "bmp";
+    if (startsWith("II*\0", 4) || startsWith("MM\0*", 4)) return "tiff";
+    if (startsWith("RIFF", 4) && startsWith("WEBP", 4, 8)) return "webp";
+    if (startsWith("\x76\x2F\x31\x01", 4)) return "exr";
+    if (startsWith("\xFF\x0A", 2) || startsWith("\0\0\0\x0CJXL \r\n\x87\n", 12)) return "jxl";
+    if (startsWith("ftyp", 4, 4)) {
+        // ISO media container; the major brand tells AVIF from HEIF
+        if (startsWith("avif", 4, 8) || startsWith("avis", 4, 8)) return "avif";
+        for (const char* brand : {"heic", "heix", "heim", "heis", "hevc", "hevx", "mif1", "msf1"}) {
+            if (startsWith(brand, 4, 8)) return "heic";
+        }
+    }
+    return "";
+}
+
+static bool readBytes(const std::string& filename, std::vector<uchar>& data) {
+    std::ifstream file(filename, std::ios::binary | std::ios::ate);
+    if (!file) return false;
+    data.resize(static_cast<size_t>(file.tellg()));
+    file.seekg(0);
+    return static_cast<bool>(file.read(reinterpret_cast<char*>(data.data()), static_cast<std::streamsize>(data.size())));
+}
+
+static uint16_t readUint16(const uchar* p, bool bigEndian) {
+    return bigEndian ? static_cast<uint16_t>(p[0] << 8 | p[1]) : static_cast<uint16_t>(p[1] << 8 | p[0]);
+}
+
+static uint32_t readUint32(const uchar* p, bool bigEndian) {
+    return bigEndian ? uint32_t(p[0]) << 24 | uint32_t(p[1]) << 16 | uint32_t(p[2]) << 8 | p[3]
+                     : uint32_t(p[3]) << 24 | uint32_t(p[2]) << 16 | uint32_t(p[1]) << 8 | p[0];
+}
+
+// Find a tag in the first IFD of a TIFF structure (an EXIF block or a whole
+// TIFF file); entry receives the offset of its 12-byte directory entry
+static bool findTiffTag(const std::vector<uchar>& tiff, uint16_t tag, size_t& entry, bool& bigEndian) {
+    if (tiff.size() < 8) return false;
+    if (std::memcmp(tiff.data(), "MM\0*", 4) == 0) {
+        bigEndian = true;
+    } else if (std::memcmp(tiff.data(), "II*\0", 4) == 0) {
+        bigEndian = false;
+    } else {
+        return false;
+    }
+
+    size_t ifd = readUint32(tiff.data() + 4, bigEndian);
+    if (ifd + 2 > tiff.size()) return false;
+    int count = readUint16(tiff.data() + ifd, bigEndian);
+    for (int i = 0; i < count; ++i) {
+        entry = ifd + 2 + 12 * static_cast<size_t>(i);
+        if (entry + 12 > tiff.size()) return false;
+        if (readUint16(tiff.data() + entry, bigEndian) == tag) return true;
+    }
+    return false;
+}
+
+static int readOrientation(const std::vector<uchar>& tiff) {
+    size_t entry = 0;
+    bool bigEndian = false;
+    if (!findTiffTag(tiff, 0x0112, entry, bigEndian)) return 1;
+    int orientation = readUint16(tiff.data() + entry + 8, bigEndian);
+    return orientation >= 1 && orientation <= 8 ? orientation : 1;
+}
+
+// Just enough inflate (RFC 1950/1951) for the zlib-compressed profiles in PNG
+// iCCP chunks, decoding canonical Huffman codes the way zlib's puff.c does
+struct Inflater {
+    struct Huffman {
+        std::vector<short> count;   // Codes of each length, 0-15
+        std::vector<short> symbol;  // Symbols ordered by code
+    };
+
+    static constexpr size_t MAX_OUTPUT = 64 * 1024 * 1024;
+
+    const uchar* data;
+    size_t size;
+    size_t pos = 0;
+    uint32_t bitBuffer = 0;
+    int bitCount = 0;
+    bool failed = false;
+
+    int bits(int count) {
+        while (bitCount < count) {
+            if (pos >= size) {
+                failed = true;
+                return 0;
+            }
+            bitBuffer |= uint32_t(data[pos++]) << bitCount;
+            bitCount += 8;
+        }
+        int value = static_cast<int>(bitBuffer & ((1u << count) - 1));
+        bitBuffer >>= count;
+        bitCount -= count;
+        return value;
+    }
+
+    static Huffman build(const short* lengths, int symbols) {
+        Huffman huffman;
+        huffman.count.assign(16, 0);
+        huffman.symbol.assign(symbols, 0);
+        for (int i = 0; i < symbols; ++i) huffman.count[lengths[i]]++;
+        std::vector<int> offsets(16, 0);
+        for (int length = 1; length < 15; ++length) offsets[length + 1] = offsets[length] + huffman.count[length];
+        for (int i = 0; i < symbols; ++i) {
+            if (lengths[i] != 0) huffman.symbol[offsets[lengths[i]]++] = static_cast<short>(i);
+        }
+        return huffman;
+    }
+
+    int decode(const Huffman& huffman) {
+        int code = 0, first = 0, index = 0;
+        for (int length = 1; length <= 15; ++length) {
+            code |= bits(1);
+            if (failed) return -1;
+            int count = huffman.count[length];
+            if (code - count < first) return huffman.symbol[index + (code - first)];
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return -1;
+    }
+
+    bool codes(std::vector<uchar>& out, const Huffman& lengthCode, const Huffman& distanceCode) {
+        static const short lengthBase[29] = {3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+                                             35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258};
+        static const short lengthExtra[29] = {0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+                                              3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0};
+        static const short distanceBase[30] = {1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+                                               385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289,
+                                               16385, 24577};
+        static const short distanceExtra[30] = {0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8,
+                                                9, 9, 10, 10, 11, 11, 12, 12, 13, 13};
+        for (;;) {
+            int symbol = decode(lengthCode);
+            if (symbol < 0 || out.size() > MAX_OUTPUT) return false;
+            if (symbol < 256) {
+                out.push_back(static_cast<uchar>(symbol));
+            } else if (symbol == 256) {
+                return true;
+            } else {
+                symbol -= 257;
+                if (symbol >= 29) return false;
+                int length = lengthBase[symbol] + bits(lengthExtra[symbol]);
+                int distanceSymbol = decode(distanceCode);
+                if (distanceSymbol < 0 || distanceSymbol >= 30) return false;
+                size_t distance = distanceBase[distanceSymbol] + bits(distanceExtra[distanceSymbol]);
+                if (failed || distance > out.size()) return false;
+                for (int i = 0; i < length; ++i) out.push_back(out[out.size() - distance]);
+            }
+        }
+    }
+
+    bool dynamicBlock(std::vector<uchar>& out) {
+        static const short order[19] = {16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15};
+        int lengthCount = bits(5) + 257, distanceCount = bits(5) + 1, codeCount = bits(4) + 4;
+        if (failed || lengthCount > 286 || distanceCount > 30) return false;
+
+        short lengths[286 + 30] = {};
+        for (int i = 0; i < codeCount; ++i) lengths[order[i]] = static_cast<short>(bits(3));
+        Huffman lengthLengths = build(lengths, 19);
+
+        int index = 0;
+        while (index < lengthCount + distanceCount) {
+            int symbol = decode(lengthLengths);
+            if (symbol < 0) return false;
+            if (symbol < 16) {
+                lengths[index++] = static_cast<short>(symbol);
+                continue;
+            }
+            short repeated = 0;
+            int repeat = 0;
+            if (symbol == 16) {
+                if (index == 0) return false;
+                repeated = lengths[index - 1];
+                repeat = 3 + bits(2);
+            } else {
+                repeat = symbol == 17 ? 3 + bits(3) : 11 + bits(7);
+            }
+            if (index + repeat > lengthCount + distanceCount) return false;
+            while (repeat-- > 0) lengths[index++] = repeated;
+        }
+        return codes(out, build(lengths, lengthCount), build(lengths + lengthCount, distanceCount));
+    }
+
+    bool fixedBlock(std::vector<uchar>& out) {
+        static const std::pair<Huffman, Huffman> fixed = []() {
+            short lengths[288 + 30];
+            for (int i = 0; i < 288; ++i) lengths[i] = i < 144 ? 8 : i < 256 ? 9 : i < 280 ? 7 : 8;
+            for (int i = 288; i < 288 + 30; ++i) lengths[i] = 5;
+            return std::make_pair(build(lengths, 288), build(lengths + 288, 30));
+        }();
+        return codes(out, fixed.first, fixed.second);
+    }
+
+    bool run(std::vector<uchar>& out) {
+        // zlib header: deflate method, and a check value over both bytes
+        if (size < 2 || (data[0] & 0x0f) != 8 || ((data[0] << 8) | data[1]) % 31 != 0) return false;
+        pos = 2;
+        int last = 0;
+        do {
+            last = bits(1);
+            int type = bits(2);
+            if (failed) return false;
+            if (type == 0) {
+                // Stored block, starting at the next byte
+                bitBuffer = 0;
+                bitCount = 0;
+                if (pos + 4 > size) return false;
+                size_t length = data[pos] | data[pos + 1] << 8;
+                pos += 4;
+                if (length > size - pos || out.size() > MAX_OUTPUT) return false;
+                out.insert(out.end(), data + pos, data + pos + length);
+                pos += length;
+            } else if (type == 1) {
+                if (!fixedBlock(out)) return false;
+            } else if (type == 2) {
+                if (!dynamicBlock(out)) return false;
+            } else {
+                return false;
+            }
+        } while (!last);
+        return true;
+    }
+};
+
+bool readMetadata(const std::string& filename, Metadata& metadata) {
+    metadata = Metadata();
+    std::vector<uchar> data;
+    if (!readBytes(filename, data)) return false;
+    std::string type = sniffExtension(data);
+
+    if (type == "jpg") {
+        // APP1 holds EXIF; a profile too big for one APP2 segment is split
+        // over several, numbered from 1
+        std::vector<std::pair<int, std::vector<uchar>>> profileParts;
+        size_t pos = 2;
+        while (pos + 4 <= data.size() && data[pos] == 0xFF) {
+            int marker = data[pos + 1];
+            if (marker == 0xFF) {
+                ++pos;
+                continue;
+            }
+            if (marker == 0xD9 || marker == 0xDA) break;
+            size_t length = (data[pos + 2] << 8) | data[pos + 3];
+            if (length < 2 || pos + 2 + length > data.size()) break;
+
+            const uchar* segment = data.data() + pos + 4;
+            size_t size = length - 2;
+            if (marker == 0xE1 && size > 6 && std::memcmp(segment, "Exif\0\0", 6) == 0 && metadata.exif.empty()) {
+                metadata.exif.assign(segment + 6, segment + size);
+            } else if (marker == 0xE2 && size > 14 && std::memcmp(segment, "ICC_PROFILE", 12) == 0) {
+                profileParts.emplace_back(segment[12], std::vector<uchar>(segment + 14, segment + size));
+            }
+            pos += 2 + length;
+        }
+        std::stable_sort(profileParts.begin(), profileParts.end(),
+                         [](const auto& a, const auto& b) { return a.first < b.first; });
+        for (const auto& part : profileParts) {
+            metadata.icc.insert(metadata.icc.end(), part.second.begin(), part.second.end());
+        }
+    } else if (type == "png") {
+        size_t pos = 8;
+        while (pos + 12 <= data.size()) {
+            uint32_t length = readUint32(data.data() + pos, true);
+            if (length > data.size() - pos - 12) break;
+            std::string chunkType(reinterpret_cast<const char*>(data.data() + pos + 4), 4);
+            const uchar* chunk = data.data() + pos + 8;
+            if (chunkType == "IEND") break;
+            if (chunkType == "eXIf") {
+                metadata.exif.assign(chunk, chunk + length);
+            } else if (chunkType == "iCCP") {
+                // Profile name, its null terminator and the compression method come first
+                const uchar* end = chunk + length;
+                const uchar* name = std::find(chunk, end, 0);
+                if (end - name > 2) {
+                    Inflater inflater{name + 2, static_cast<size_t>(end - name - 2)};
+                    if (!inflater.run(metadata.icc)) metadata.icc.clear();
+                }
+            }
+            pos += 12 + length;
+        }
+    } else if (type == "webp") {
+        size_t pos = 12;
+        while (pos + 8 <= data.size()) {
+            uint32_t length = readUint32(data.data() + pos + 4, false);
+            if (length > data.size() - pos - 8) break;
+            std::string fourcc(reinterpret_cast<const char*>(data.data() + pos), 4);
+            const uchar* chunk = data.data() + pos + 8;
+            if (fourcc == "EXIF") {
+                // Some writers keep the "Exif\0\0" prefix from JPEG
+                size_t skip = length > 6 && std::memcmp(chunk, "Exif\0\0", 6) == 0 ? 6 : 0;
+                metadata.exif.assign(chunk + skip, chunk + length);
+            } else if (fourcc == "ICCP") {
+                metadata.icc.assign(chunk, chunk + length);
+            }
+            pos += 8 + length + (length & 1);
+        }
+    } else if (type == "tiff") {
+        metadata.orientation = readOrientation(data);
+        size_t entry = 0;
+        bool bigEndian = false;
+        if (findTiffTag(data, 0x8773, entry, bigEndian)) {
+            uint32_t count = readUint32(data.data() + entry + 4, bigEndian);
+            uint32_t offset = readUint32(data.data() + entry + 8, bigEndian);
+            if (count > 4 && offset <= data.size() && count <= data.size() - offset) {
+                metadata.icc.assign(data.begin() + offset, data.begin() + offset + count);
+            }
+        }
+        return metadata.orientation != 1 || !metadata.icc.empty();
+    }
+
+    if (!metadata.exif.empty()) metadata.orientation = readOrientation(metadata.exif);
+    return !metadata.exif.empty() || !metadata.icc.empty();
+}
+
+// Decode GIF LZW data into pixelCount palette indices. Data that ends early
+// leaves the remaining pixels at index 0, the way viewers show cut-off GIFs
+static void decodeGifLzw(const std::vector<uchar>& data, int minCodeSize, size_t pixelCount,
+                         std::vector<uchar>& out) {
+    out.assign(pixelCount, 0);
+    if (minCodeSize < 1 || minCodeSize > 8) return;
+
+    const int clearCode = 1 << minCodeSize;
+    const int endCode = clearCode + 1;
+    std::vector<uint16_t> prefix(4096);
+    std::vector<uchar> suffix(4096), first(4096), stack;
+    for (int i = 0; i < clearCode; ++i) suffix[i] = first[i] = static_cast<uchar>(i);
+
+    int codeSize = minCodeSize + 1;
+    int nextCode = endCode + 1;
+    int previous = -1;
+    uint32_t bitBuffer = 0;
+    int bitCount = 0;
+    size_t pos = 0, written = 0;
+    while (written < pixelCount) {
+        while (bitCount < codeSize && pos < data.size()) {
+            bitBuffer |= static_cast<uint32_t>(data[pos++]) << bitCount;
+            bitCount += 8;
+        }
+        if (bitCount < codeSize) break;
+        int code = static_cast<int>(bitBuffer & ((1u << codeSize) - 1));
+        bitBuffer >>= codeSize;
+        bitCount -= codeSize;
+
+        if (code == clearCode) {
+            codeSize = minCodeSize + 1;
+            nextCode = endCode + 1;
+            previous = -1;
+            continue;
+        }
+        if (code == endCode) break;
+        if (previous < 0) {
+            if (code > endCode) break;
+            out[written++] = static_cast<uchar>(code);
+            previous = code;
+            continue;
+        }
+
+        // A code can refer to the entry it is about to define: the previous
+        // string followed by its own first index
+        stack.clear();
+        int entry = code;
+        if (code >= nextCode) {
+            if (code > nextCode) break;
+            stack.push_back(first[previous]);
+            entry = previous;
+        }
+        while (entry > endCode) {
+            stack.push_back(suffix[entry]);
+            entry = prefix[entry];
+        }
+        if (entry >= clearCode) break;
+        stack.push_back(static_cast<uchar>(entry));
+        for (auto it = stack.rbegin(); it != stack.rend() && written < pixelCount; ++it) out[written++] = *it;
+
+        if (nextCode < 4096) {
+            prefix[nextCode] = static_cast<uint16_t>(previous);
+            suffix[nextCode] = static_cast<uchar>(entry);
+            first[nextCode] = first[previous];
+            nextCode++;
+            if (nextCode == (1 << codeSize) && codeSize < 12) codeSize++;
+        }
+        previous = code;
+    }
+}
+
+// Walk a GIF's blocks, counting frames. With an animation the frames are
+// also decoded and drawn onto the canvas in turn, so each one comes out as
+// the complete picture shown at that point
+static bool readGif(const std::vector<uchar>& data, Animation* animation, int& frameCount) {
+    frameCount = 0;
+    if (data.size() < 13 || (std::memcmp(data.data(), "GIF87a", 6) != 0 &&
+                             std::memcmp(data.data(), "GIF89a", 6) != 0)) {
+        return false;
+    }
+    const int width = readUint16(data.data() + 6, false);
+    const int height = readUint16(data.data() + 8, false);
+    if (width == 0 || height == 0) return false;
+
+    size_t pos = 13;
+    auto readColorTable = [&](uchar flags, std::vector<uchar>& table) {
+        if (!(flags & 0x80)) return true;
+        size_t size = 3u << ((flags & 7) + 1);
+        if (size > data.size() - pos) return false;
+        table.assign(data.begin() + pos, data.begin() + pos + size);
+        pos += size;
+        return true;
+    };
+    // Sub-blocks up to the empty terminator, joined (out may be null to skip them)
+    auto readSubBlocks = [&](std::vector<uchar>* out) {
+        while (pos < data.size()) {
+            size_t length = data[pos++];
+            if (length == 0) return true;
+            if (length > data.size() - pos) return false;
+            if (out) out->insert(out->end(), data.begin() + pos, data.begin() + pos + length);
+            pos += length;
+        }
+        return false;
+    };
+
+    std::vector<uchar> globalTable;
+    if (!readColorTable(data[10], globalTable)) return false;
+
+    cv::Mat canvas;
+    if (animation) {
+        animation->frames.clear();
+        animation->loopCount = -1;
+        canvas = cv::Mat(height, width, CV_8UC4, cv::Scalar::all(0));
+    }
+
+    // Graphic control extension settings, which apply to the next frame only
+    int delay = 0, disposal = 0, transparent = -1;
+    while (pos < data.size()) {
+        uchar block = data[pos++];
+        if (block == 0x3b) break;
+        if (block == 0x21) {
+            if (pos >= data.size()) return false;
+            uchar label = data[pos++];
+            std::vector<uchar> content;
+            if (!readSubBlocks(&content)) return false;
+            if (label == 0xf9 && content.size() >= 4) {
+                disposal = (content[0] >> 2) & 7;
+                delay = content[1] | (content[2] << 8);
+                transparent = (content[0] & 1) ? content[3] : -1;
+            } else if (label == 0xff && content.size() >= 14 && content[11] == 1 &&
+                       (std::memcmp(content.data(), "NETSCAPE2.0", 11) == 0 ||
+                        std::memcmp(content.data(), "ANIMEXTS1.0", 11) == 0)) {
+                if (animation) animation->loopCount = content[12] | (content[13] << 8);
+            }
+            continue;
+        }
+        if (block != 0x2c || data.size() - pos < 9) return false;
+
+        const uchar* descriptor = data.data() + pos;
+        const int left = readUint16(descriptor, false), top = readUint16(descriptor + 2, false);
+        const int frameWidth = readUint16(descriptor + 4, false), frameHeight = readUint16(descriptor + 6, false);
+        const uchar flags = data[pos + 8];
+        pos += 9;
+        std::vector<uchar> localTable;
+        if (!readColorTable(flags, localTable) || pos >= data.size()) return false;
+        const int minCodeSize = data[pos++];
+        std::vector<uchar> compressed;
+        if (!readSubBlocks(animation ? &compressed : nullptr)) return false;
+        frameCount++;
+
+        if (animation) {
+            const std::vector<uchar>& table = localTable.empty() ? globalTable : localTable;
+            std::vector<uchar> indices;
+            decodeGifLzw(compressed, minCodeSize, static_cast<size_t>(frameWidth) * frameHeight, indices);
+
+            // Interlaced frames store every 8th row from 0, every 8th from 4,
+            // every 4th from 2, then the odd rows
+            std::vector<int> rows;
+            if (flags & 0x40) {
+                for (int start : {0, 4, 2, 1}) {
+                    for (int y = start; y < frameHeight; y += start == 0 ? 8 : start * 2) rows.push_back(y);
+                }
+            } else {
+                for (int y = 0; y < frameHeight; ++y) rows.push_back(y);
+            }
+
+            cv::Mat previous = disposal == 3 ? canvas.clone() : cv::Mat();
+            for (int i = 0; i < frameHeight; ++i) {
+                const int y = top + rows[i];
+                if (y >= height) continue;
+                cv::Vec4b* row = canvas.ptr<cv::Vec4b>(y);
+                const uchar* indexRow = indices.data() + static_cast<size_t>(i) * frameWidth;
+                for (int x = 0; x < frameWidth && left + x < width; ++x) {
+                    const int index = indexRow[x];
+                    if (index == transparent || static_cast<size_t>(index) * 3 + 2 >= table.size()) continue;
+                    row[left + x] = cv::Vec4b(table[index * 3 + 2], table[index * 3 + 1], table[index * 3], 255);
+                }
+            }
+
+            AnimationFrame frame;
+            std::vector<cv::Mat> channels;
+            cv::split(canvas, channels);
+            double minAlpha = 0.0;
+            cv::minMaxLoc(channels[3], &minAlpha);
+            if (minAlpha < 255.0) frame.alpha = channels[3];
+            channels.pop_back();
+            cv::merge(channels, frame.color);
+            frame.delayMs = delay < 2 ? 100 : delay * 10;
+            animation->frames.push_back(frame);
+
+            // Disposal 2 clears the frame's area before the next one, 3 puts
+            // back what was there before it
+            if (disposal == 2) {
+                canvas(cv::Rect(left, top, frameWidth, frameHeight) & cv::Rect(0, 0, width, height))
+                    .setTo(cv::Scalar::all(0));
+            } else if (disposal == 3) {
+                canvas = previous;
+            }
+        }
+        delay = 0;
+        disposal = 0;
+        transparent = -1;
+    }
+    return frameCount > 0;
+}
+
+bool loadGifAnimation(const std::string& filename, Animation& animation) {
+    std::vector<uchar> data;
+    int frameCount = 0;
+    return readBytes(filename, data) && readGif(data, &animation, frameCount);
+}
+
+bool isAnimatedGif(const std::string& filename) {
+    if (getExtension(filename) != "gif") return false;
+    std::vector<uchar> data;
+    int frameCount = 0;
+    return readBytes(filename, data) && readGif(data, nullptr, frameCount) && frameCount > 1;
+}
+
+bool loadMask(const std::string& filename, cv::Mat& mask) {
+    cv::Mat color, alpha;
+    if (!loadImage(filename, color, alpha)) return false;
+    cv::cvtColor(color, mask, cv::COLOR_BGR2GRAY);
+    return true;
+}
+
+bool loadImageFromMemory(const std::vector<unsigned char>& data, cv::Mat& color, cv::Mat& alpha,
+                         std::vector<Warning>* warnings, cv::Mat* precise) {
+    std::string ext = sniffExtension(data);
+    if (ext.empty()) return false;
+
+    // Go through a temp file like downloads do, so format checks (JPEG header,
+    // ICC profile, EXR) work the same as for files on disk
+    std::string tempPath = Platform::createTempFile("." + ext);
+    if (tempPath.empty()) return false;
+    bool loaded = false;
+    {
+        std::ofstream out(tempPath, std::ios::binary);
+        out.write(reinterpret_cast<const char*>(data.data()), static_cast<std::streamsize>(data.size()));
+        loaded = static_cast<bool>(out);
+    }
+
+    loaded = loaded && loadImage(tempPath, color, alpha, warnings, precise);
+    std::error_code ec;
+    std::filesystem::remove(tempPath, ec);
+    return loaded;
+}
+
+bool decodeDataUrl(const std::string& url, std::vector<unsigned char>& data) {
+    data.clear();
+    if (url.rfind("data:", 0) != 0) return false;
+    size_t comma = url.find(',');
+    if (comma == std::string::npos) return false;
+    std::string header = url.substr(5, comma - 5);
+    if (header.size() < 7 || header.compare(header.size() - 7, 7, ";base64") != 0) return false;
+
+    unsigned int buffer = 0;
+    int bits = 0;
+    for (size_t i = comma + 1; i < url.size(); ++i) {
+        char c = url[i];
+        int value;
+        if (c >= 'A' && c <= 'Z') value = c - 'A';
+        else if (c >= 'a' && c <= 'z') value = c - 'a' + 26;
+        else if (c >= '0' && c <= '9') value = c - '0' + 52;
+        else if (c == '+' || c == '-') value = 62;
+        else if (c == '/' || c == '_') value = 63;
+        else if (c == '=') break;
+        else if (std::isspace(static_cast<unsigned char>(c))) continue;
+        else return false;
+
+        buffer = (buffer << 6) | static_cast<unsigned int>(value);
+        bits += 6;
+        if (bits >= 8) {
+            bits -= 8;
+            data.push_back(static_cast<unsigned char>((buffer >> bits) & 0xFF));
+        }
+    }
+    return !data.empty();
+}
+
+// Fill in the profile fields of an ImageInfo from the file's metadata
+static void describeProfile(const std::string& filename, ImageInfo& info) {
+    Metadata metadata;
+    ColorProfile::Profile profile;
+    if (!readMetadata(filename, metadata) || metadata.icc.empty()) return;
+    info.hasIccProfile = true;
+    info.iccProfileName = ColorProfile::readDescription(metadata.icc);
+    checkIccProfile(metadata.icc, profile, colorManagement == ColorManagement::HONOR_PROFILES, info.warnings);
+}
+
+// Inspect image file without dithering it
+bool getImageInfo(const std::string& filename, ImageInfo& info) {
+    info = ImageInfo();
+    info.format = getExtension(filename);
+
+    // JPEG headers carry everything we need, no full decode required
+    JpegHeader jpeg;
+    if ((info.format == "jpg" || info.format == "jpeg" || info.format == "jpe") &&
+        parseJpegHeader(filename, jpeg)) {
+        info.width = jpeg.width;
+        info.height = jpeg.height;
+        info.channels = jpeg.components;
+        info.bitDepth = jpeg.precision;
+        info.colorSpace = jpegColorSpace(jpeg);
+        checkJpegHeader(jpeg, info.warnings);
+        describeProfile(filename, info);
+        return true;
+    }
+
+    // Images too big to open are described from their header
+    ImageInfo header;
+    double scale = 1.0;
+    if (planDecode(filename, header, scale) != DecodePlan::FULL) {
+        info.width = header.width;
+        info.height = header.height;
+        info.channels = header.channels;
+        info.bitDepth = header.bitDepth;
+        info.colorSpace = (info.channels == 1) ? "Gray" : (info.channels == 4) ? "RGBA" : "RGB";
+        describeProfile(filename, info);
+        return true;
+    }
+
+    // Camera RAW is described from its header; demosaicing the whole file
+    // just for its size takes seconds. Decoders give 16-bit RGB
+    int rawWidth = 0, rawHeight = 0;
+    if (isRawFile(filename) && Platform::identifyRawImage(filename, rawWidth, rawHeight)) {
+        info.width = rawWidth;
+        info.height = rawHeight;
+        info.channels = 3;
+        info.bitDepth = 16;
+        info.colorSpace = "RGB";
+        return true;
+    }
+
+    // Formats decoded through a temp file report its profile
+    std::string converted = needsConversion(filename) ? convertToTempFile(filename) : "";
+    std::string source = converted.empty() ? filename : converted;
+    cv::Mat img = cv::imread(source, cv::IMREAD_UNCHANGED);
+    if (!img.empty()) describeProfile(source, info);
+    if (!converted.empty()) std::remove(converted.c_str());
+    if (img.empty()) return false;
+
+    info.width = img.cols;
+    info.height = img.rows;
+    info.channels = img.channels();
+    info.bitDepth = (img.depth() == CV_16U) ? 16 : (img.depth() == CV_32F) ? 32 : 8;
+    info.colorSpace = (info.channels == 1) ? "Gray" : (info.channels == 4) ? "RGBA" : "RGB";
+    return true;
+}
+
+// Composite semi-transparent pixels onto the matte color
+template <typename T>
+static void blendMatte(cv::Mat& color, const cv::Mat& alpha, const cv::Vec3f& matte) {
+    for (int y = 0; y < color.rows; ++y) {
+        for (int x = 0; x < color.cols; ++x) {
+            uchar a = alpha.at<uchar>(y, x);
+            if (a == 0 || a == 255) continue;
+
+            float t = a / 255.0f;
+            cv::Vec<T, 3>& pixel = color.at<cv::Vec<T, 3>>(y, x);
+            cv::Vec3f blended = cv::Vec3f(pixel[0], pixel[1], pixel[2]) * t + matte * (1.0f - t);
+            for (int c = 0; c < 3; ++c) {
+                pixel[c] = cv::saturate_cast<T>(std::clamp(blended[c], 0.0f, 255.0f));
+            }
+        }
+    }
+}
+
+void applyMatte(cv::Mat& color, const cv::Mat& alpha, const ExportOptions& options) {
+    if (!options.useMatte || alpha.empty()) return;
+
+    cv::Vec3f matte(options.matteColor[0], options.matteColor[1], options.matteColor[2]);
+    if (color.depth() == CV_32F) {
+        blendMatte<float>(color, alpha, matte);
+    } else {
+        blendMatte<uchar>(color, alpha, matte);
+    }
+}
+
+// CRC-32 as used by PNG chunks
+static uint32_t crc32(const uchar* data, size_t length) {
+    static const std::vector<uint32_t> table = []() {
+        std::vector<uint32_t> values(256);
+        for (uint32_t n = 0; n < 256; ++n) {
+            uint32_t c = n;
+            for (int k = 0; k < 8; ++k) c = (c & 1) ? 0xedb88320u ^ (c >> 1) : c >> 1;
+            values[n] = c;
+        }
+        return values;
+    }();
+
+    uint32_t crc = 0xffffffffu;
+    for (size_t i = 0; i < length; ++i) crc = table[(crc ^ data[i]) & 0xff] ^ (crc >> 8);
+    return crc ^ 0xffffffffu;
+}
+
+static void appendUint32(std::vector<uchar>& out, uint32_t value) {
+    for (int shift = 24; shift >= 0; shift -= 8) out.push_back(static_cast<uchar>(value >> shift));
+}
+
+static void appendChunk(std::vector<uchar>& png, const char* type, const std::vector<uchar>& data) {
+    appendUint32(png, static_cast<uint32_t>(data.size()));
+    size_t start = png.size();
+    png.insert(png.end(), type, type + 4);
+    png.insert(png.end(), data.begin(), data.end());
+    appendUint32(png, crc32(png.data() + start, png.size() - start));
+}
+
+// Distinct colors of an 8-bit BGR image with its alpha (may be empty), as
+// packed B, G, R, A entries in order of first appearance, and each pixel's
+// index into them. Fully transparent pixels share one entry whatever their
+// color. False if there are more than maxColors
+static bool buildPalette(const cv::Mat& color, const cv::Mat& alpha, size_t maxColors,
+                         std::vector<uint32_t>& entries, cv::Mat& indices) {
+    std::unordered_map<uint32_t, uchar> lookup;
+    entries.clear();
+    indices.create(color.size(), CV_8U);
+    for (int y = 0; y < color.rows; ++y) {
+        const cv::Vec3b* row = color.ptr<cv::Vec3b>(y);
+        const uchar* alphaRow = alpha.empty() ? nullptr : alpha.ptr<uchar>(y);
+        uchar* indexRow = indices.ptr<uchar>(y);
+        for (int x = 0; x < color.cols; ++x) {
+            uint32_t a = alphaRow ? alphaRow[x] : 255;
+            uint32_t key = a == 0 ? 0 : row[x][0] | (row[x][1] << 8) | (row[x][2] << 16) | (a << 24);
+            auto found = lookup.find(key);
+            if (found == lookup.end()) {
+                if (entries.size() == maxColors) return false;
+                found = lookup.emplace(key, static_cast<uchar>(entries.size())).first;
+                entries.push_back(key);
+            }
+            indexRow[x] = found->second;
+        }
+    }
+    return true;
+}
+
+static bool writeBytes(const std::string& filename, const std::vector<uchar>& data) {
+    std::ofstream file(filename, std::ios::binary);
+    file.write(reinterpret_cast<const char*>(data.data()), static_cast<std::streamsize>(data.size()));
+    return static_cast<bool>(file);
+}
+
+// Encode palette indices and their entries (see buildPalette) as an indexed
+// PNG. OpenCV has no palette output, so the indices are packed into rows and
+// encoded as 8-bit gray: PNG filters work on bytes at any bit depth, so the
+// compressed IDAT data is the same and only the header and palette change
+static bool encodeIndexedPng(const std::vector<uint32_t>& entries, const cv::Mat& indices,
+                             const std::vector<int>& params, std::vector<uchar>& png) {
+    int bits = entries.size() <= 2 ? 1 : entries.size() <= 4 ? 2 : entries.size() <= 16 ? 4 : 8;
+    int perByte = 8 / bits;
+    cv::Mat packed = cv::Mat::zeros(indices.rows, (indices.cols + perByte - 1) / perByte, CV_8U);
+    for (int y = 0; y < indices.rows; ++y) {
+        const uchar* indexRow = indices.ptr<uchar>(y);
+        uchar* packedRow = packed.ptr<uchar>(y);
+        for (int x = 0; x < indices.cols; ++x) {
+            packedRow[x / perByte] |= indexRow[x] << (8 - bits * (x % perByte + 1));   // Leftmost pixel in the high bits
+        }
+    }
+
+    std::vector<uchar> gray;
+    if (!cv::imencode(".png", packed, gray, params)) return false;
+    if (gray.size() < 33 || gray[24] != 8 || gray[25] != 0 || gray[28] != 0) return false;  // 8-bit gray, not interlaced
+
+    png = {0x89, 'P', 'N', 'G', '\r', '\n', 0x1a, '\n'};
+    std::vector<uchar> header;
+    appendUint32(header, static_cast<uint32_t>(indices.cols));
+    appendUint32(header, static_cast<uint32_t>(indices.rows));
+    header.insert(header.end(), {static_cast<uchar>(bits), 3, 0, 0, 0});   // Palette color, no interlace
+    appendChunk(png, "IHDR", header);
+
+    std::vector<uchar> palette, transparency;
+    size_t lastTranslucent = 0;
+    for (size_t i = 0; i < entries.size(); ++i) {
+        uint32_t entry = entries[i];
+        palette.insert(palette.end(), {static_cast<uchar>(entry >> 16), static_cast<uchar>(entry >> 8),
+                                       static_cast<uchar>(entry)});
+        transparency.push_back(static_cast<uchar>(entry >> 24));
+        if (transparency.back() != 255) lastTranslucent = i + 1;
+    }
+    appendChunk(png, "PLTE", palette);
+    if (lastTranslucent > 0) {
+        // Entries past the last translucent one default to opaque
+        transparency.resize(lastTranslucent);
+        appendChunk(png, "tRNS", transparency);
+    }
+
+    size_t pos = 8;
+    while (pos + 12 <= gray.size()) {
+        size_t length = (static_cast<size_t>(gray[pos]) << 24) | (gray[pos + 1] << 16) |
+                        (gray[pos + 2] << 8) | gray[pos + 3];
+        if (pos + 12 + length > gray.size()) return false;
+        if (std::string(gray.begin() + pos + 4, gray.begin() + pos + 8) == "IDAT") {
+            png.insert(png.end(), gray.begin() + pos, gray.begin() + pos + 12 + length);
+        }
+        pos += 12 + length;
+    }
+    appendChunk(png, "IEND", {});
+    return true;
+}
+
+// Encode color (8-bit BGR) and alpha (may be empty) as an indexed PNG. False
+// when the image has more than 256 colors and has to be saved some other way
+static bool encodeIndexedPng(const cv::Mat& color, const cv::Mat& alpha, const std::vector<int>& params,
+                             std::vector<uchar>& png) {
+    if (color.type() != CV_8UC3) return false;
+
+    std::vector<uint32_t> entries;
+    cv::Mat indices;
+    return buildPalette(color, alpha, 256, entries, indices) && encodeIndexedPng(entries, indices, params, png);
+}
+
+// PackBits run-length encoding of one row, the simplest TIFF compression
+static void appendPackBits(std::vector<uchar>& out, const uchar* data, size_t length) {
+    size_t i = 0;
+    while (i < length) {
+        size_t run = 1;
+        while (i + run < length && run < 128 && data[i + run] == data[i]) run++;
+        if (run >= 2) {
+            out.push_back(static_cast<uchar>(257 - run));   // -(run - 1): repeat the next byte
+            out.push_back(data[i]);
+            i += run;
+            continue;
+        }
+        size_t start = i;
+        while (i < length && i - start < 128 && !(i + 1 < length && data[i] == data[i + 1])) i++;
+        out.push_back(static_cast<uchar>(i - start - 1));   // Literal bytes follow
+        out.insert(out.end(), data + start, data + i);
+    }
+}
+
+// Encode a two-color 8-bit BGR image as a 1-bit TIFF: black and white as plain
+// bilevel, any other pair through a two-entry color map. False when the image
+// has more than two colors
+static bool encodeBilevelTiff(const cv::Mat& color, std::vector<uchar>& tiff) {
+    if (color.type() != CV_8UC3) return false;
+
+    std::vector<uint32_t> entries;
+    cv::Mat indices;
+    if (!buildPalette(color, cv::Mat(), 2, entries, indices)) return false;
+    entries.resize(2, entries[0]);
+
+    // Black and white (in either order) need no color map; bit 1 is white
+    const uint32_t black = 0xff000000u, white = 0xffffffffu;
+    bool bilevel = (entries[0] == black && entries[1] == white) || (entries[0] == white && entries[1] == black);
+    int oneIndex = bilevel && entries[0] == white ? 0 : 1;
+
+    auto appendUint16 = [&tiff](uint32_t value) {
+        tiff.insert(tiff.end(), {static_cast<uchar>(value), static_cast<uchar>(value >> 8)});
+    };
+    auto appendLe32 = [&tiff](uint32_t value) {
+        for (int shift = 0; shift < 32; shift += 8) tiff.push_back(static_cast<uchar>(value >> shift));
+    };
+
+    tiff = {'I', 'I', 42, 0, 0, 0, 0, 0};      // Little-endian; IFD offset filled in below
+    std::vector<uchar> row((color.cols + 7) / 8);
+    for (int y = 0; y < color.rows; ++y) {
+        std::fill(row.begin(), row.end(), 0);
+        const uchar* indexRow = indices.ptr<uchar>(y);
+        for (int x = 0; x < color.cols; ++x) {
+            if (indexRow[x] == oneIndex) row[x / 8] |= 0x80 >> (x % 8);
+        }
+        appendPackBits(tiff, row.data(), row.size());
+    }
+    uint32_t stripBytes = static_cast<uint32_t>(tiff.size() - 8);
+    if (tiff.size() % 2) tiff.push_back(0);     // Offsets must be even
+
+    uint32_t resolutionOffset = static_cast<uint32_t>(tiff.size());
+    appendLe32(72);
+    appendLe32(1);
+    uint32_t colorMapOffset = static_cast<uint32_t>(tiff.size());
+    if (!bilevel) {
+        // 16-bit red, then green, then blue values for both entries
+        for (int shift : {16, 8, 0}) {
+            for (uint32_t entry : entries) appendUint16(((entry >> shift) & 0xff) * 257);
+        }
+    }
+
+    uint32_t ifdOffset = static_cast<uint32_t>(tiff.size());
+    for (int i = 0; i < 4; ++i) tiff[4 + i] = static_cast<uchar>(ifdOffset >> (8 * i));
+
+    // Tags in ascending order: tag, type (3 short, 4 long, 5 rational), count, value or offset
+    struct Tag { uint16_t tag, type; uint32_t count, value; };
+    std::vector<Tag> tags = {
+        {256, 4, 1, static_cast<uint32_t>(color.cols)},
+        {257, 4, 1, static_cast<uint32_t>(color.rows)},
+        {258, 3, 1, 1},                     // Bits per sample
+        {259, 3, 1, 32773},                 // PackBits
+        {262, 3, 1, bilevel ? 1u : 3u},     // Black is zero, or palette
+        {273, 4, 1, 8},                     // Strip offset
+        {277, 3, 1, 1},                     // Samples per pixel
+        {278, 4, 1, static_cast<uint32_t>(color.rows)},
+        {279, 4, 1, stripBytes},
+        {282, 5, 1, resolutionOffset},
+        {283, 5, 1, resolutionOffset},
+        {296, 3, 1, 2},                     // Resolution in inches
+    };
+    if (!bilevel) tags.push_back({320, 3, 6, colorMapOffset});
+
+    appendUint16(static_cast<uint32_t>(tags.size()));
+    for (const Tag& tag : tags) {
+        appendUint16(tag.tag);
+        appendUint16(tag.type);
+        appendLe32(tag.count);
+        appendLe32(tag.value);      // Shorts sit in the low bytes, as little-endian expects
+    }
+    appendLe32(0);                  // No further IFDs
+    return true;
+}
+
+// Write a single-image ICO holding a PNG, which every icon reader since
+// Windows Vista accepts. Icons are at most 256x256, so larger images are
+// scaled down to fit, with nearest neighbor to keep the pattern crisp
+static bool writeIco(const std::string& filename, const cv::Mat& color, const cv::Mat& alpha,
+                     const ExportOptions& options) {
+    cv::Mat iconColor = color, iconAlpha = alpha;
+    if (color.cols > 256 || color.rows > 256) {
+        double scale = 256.0 / std::max(color.cols, color.rows);
+        cv::Size size(std::max(1, static_cast<int>(color.cols * scale)),
+                      std::max(1, static_cast<int>(color.rows * scale)));
+        cv::resize(color, iconColor, size, 0, 0, cv::INTER_NEAREST);
+        if (!alpha.empty()) cv::resize(alpha, iconAlpha, size, 0, 0, cv::INTER_NEAREST);
+    }
+
+    std::vector<int> params = getEncoderParams("icon.png", options.encode);
+    std::vector<uchar> png;
+    if (!options.indexedPng || !encodeIndexedPng(iconColor, iconAlpha, params, png)) {
+        cv::Mat image = iconColor;
+        if (!iconAlpha.empty()) {
+            std::vector<cv::Mat> channels;
+            cv::split(iconColor, channels);
+            channels.push_back(iconAlpha);
+            cv::merge(channels, image);
+        }
+        if (!cv::imencode(".png", image, png, params)) return false;
+    }
+
+    std::vector<uchar> ico = {0, 0, 1, 0, 1, 0};    // Icon resource with one image
+    ico.push_back(static_cast<uchar>(iconColor.cols & 0xff));      // 0 means 256
+    ico.push_back(static_cast<uchar>(iconColor.rows & 0xff));
+    ico.insert(ico.end(), {0, 0, 1, 0, 32, 0});     // No palette count, 1 plane, 32 bits per pixel
+    for (uint32_t value : {static_cast<uint32_t>(png.size()), 22u}) {
+        for (int shift = 0; shift < 32; shift += 8) ico.push_back(static_cast<uchar>(value >> shift));
+    }
+    ico.insert(ico.end(), png.begin(), png.end());
+    return writeBytes(filename, ico);
+}
+
+// GIF's variable-length LZW: codes start at minCodeSize + 1 bits and grow
+// with the dictionary up to 12 bits, after which it starts over
+static std::vector<uchar> encodeGifLzw(const cv::Mat& indices, int minCodeSize) {
+    const int clearCode = 1 << minCodeSize;
+    const int endCode = clearCode + 1;
+    std::vector<uchar> out;
+    uint32_t bitBuffer = 0;
+    int bitCount = 0;
+    int codeSize = minCodeSize + 1;
+    auto emit = [&](int code) {
+        bitBuffer |= static_cast<uint32_t>(code) << bitCount;
+        bitCount += codeSize;
+        while (bitCount >= 8) {
+            out.push_back(static_cast<uchar>(bitBuffer & 0xff));
+            bitBuffer >>= 8;
+            bitCount -= 8;
+        }
+    };
+
+    std::unordered_map<uint32_t, int> dictionary;   // (prefix code << 8 | next index) -> code
+    int nextCode = endCode + 1;
+    int prefix = -1;
+    emit(clearCode);
+    for (int y = 0; y < indices.rows; ++y) {
+        const uchar* row = indices.ptr<uchar>(y);
+        for (int x = 0; x < indices.cols; ++x) {
+            if (prefix < 0) {
+                prefix = row[x];
+                continue;
+            }
+            uint32_t key = (static_cast<uint32_t>(prefix) << 8) | row[x];
+            auto found = dictionary.find(key);
+            if (found != dictionary.end()) {
+                prefix = found->second;
+                continue;
+            }
+
+            emit(prefix);
+            dictionary[key] = nextCode++;
+            if (nextCode - 1 >= (1 << codeSize)) codeSize++;
+            if (nextCode == 4096) {
+                emit(clearCode);
+                dictionary.clear();
+                nextCode = endCode + 1;
+                codeSize = minCodeSize + 1;
+            }
+            prefix = row[x];
+        }
+    }
+    if (prefix >= 0) emit(prefix);
+    emit(endCode);
+    if (bitCount > 0) out.push_back(static_cast<uchar>(bitBuffer & 0xff));
+    return out;
+}
+
+static void appendUint16Le(std::vector<uchar>& out, int value) {
+    out.push_back(static_cast<uchar>(value & 0xff));
+    out.push_back(static_cast<uchar>((value >> 8) & 0xff));
+}
+
+// Color table bits for a palette: GIF tables hold 2^bits entries, at least 2
+static int gifTableBits(const std::vector<uint32_t>& entries) {
+    int tableBits = 1;
+    while ((1u << tableBits) < entries.size()) tableBits++;
+    return tableBits;
+}
+
+// Color table of 2^tableBits R, G, B entries, padded with black
+static void appendGifColorTable(std::vector<uchar>& gif, const std::vector<uint32_t>& entries, int tableBits) {
+    for (int i = 0; i < (1 << tableBits); ++i) {
+        uint32_t entry = i < static_cast<int>(entries.size()) ? entries[i] : 0;
+        gif.insert(gif.end(), {static_cast<uchar>(entry >> 16), static_cast<uchar>(entry >> 8),
+                               static_cast<uchar>(entry)});
+    }
+}
+
+// LZW code size and the compressed indices in sub-blocks of up to 255 bytes
+static void appendGifImageData(std::vector<uchar>& gif, const cv::Mat& indices, int tableBits) {
+    int minCodeSize = std::max(2, tableBits);
+    std::vector<uchar> data = encodeGifLzw(indices, minCodeSize);
+    gif.push_back(static_cast<uchar>(minCodeSize));
+    for (size_t pos = 0; pos < data.size(); pos += 255) {
+        size_t length = std::min<size_t>(255, data.size() - pos);
+        gif.push_back(static_cast<uchar>(length));
+        gif.insert(gif.end(), data.begin() + pos, data.begin() + pos + length);
+    }
+    gif.push_back(0);
+}
+
+// Write a single-frame GIF whose color table is exactly the image's colors, so
+// the dither isn't quantized a second time. Alpha (may be empty) is binary by
+// now; transparent pixels get palette index transparentIndex (-1 = wherever
+// they first appear). Returns false, without writing anything, when the image
+// has more than 256 colors; saved tells whether writing worked
+static bool writeGif(const std::string& filename, const cv::Mat& color, const cv::Mat& alpha,
+                     int transparentIndex, bool& saved) {
+    saved = false;
+    if (color.type() != CV_8UC3) return false;
+
+    std::vector<uint32_t> entries;
+    cv::Mat indices;
+    if (!buildPalette(color, alpha, 256, entries, indices)) return false;
+
+    // Move the transparent entry to the requested index, padding the table if needed
+    auto transparent = std::find(entries.begin(), entries.end(), 0u);
+    int current = transparent == entries.end() ? -1 : static_cast<int>(transparent - entries.begin());
+    if (current >= 0 && transparentIndex >= 0 && transparentIndex != current) {
+        transparentIndex = std::min(transparentIndex, 255);
+        if (static_cast<int>(entries.size()) <= transparentIndex) entries.resize(transparentIndex + 1, 0xff000000u);
+        std::swap(entries[current], entries[transparentIndex]);
+        for (int y = 0; y < indices.rows; ++y) {
+            uchar* row = indices.ptr<uchar>(y);
+            for (int x = 0; x < indices.cols; ++x) {
+                if (row[x] == current) row[x] = static_cast<uchar>(transparentIndex);
+                else if (row[x] == transparentIndex) row[x] = static_cast<uchar>(current);
+            }
+        }
+        current = transparentIndex;
+    }
+
+    int tableBits = gifTableBits(entries);
+    std::vector<uchar> gif = {'G', 'I', 'F', '8', '9', 'a'};
+
+    // Logical screen with a global color table of 2^tableBits entries
+    appendUint16Le(gif, color.cols);
+    appendUint16Le(gif, color.rows);
+    gif.insert(gif.end(), {static_cast<uchar>(0xf0 | (tableBits - 1)), 0, 0});
+    appendGifColorTable(gif, entries, tableBits);
+
+    if (current >= 0) {
+        // Graphic control extension: transparent color flag and index
+        gif.insert(gif.end(), {0x21, 0xf9, 0x04, 0x01, 0, 0, static_cast<uchar>(current), 0});
+    }
+
+    gif.push_back(0x2c);    // Image descriptor covering the whole screen
+    appendUint16Le(gif, 0);
+    appendUint16Le(gif, 0);
+    appendUint16Le(gif, color.cols);
+    appendUint16Le(gif, color.rows);
+    gif.push_back(0);
+    appendGifImageData(gif, indices, tableBits);
+    gif.push_back(0x3b);
+
+    saved = writeBytes(filename, gif);
+    return true;
+}
+
+// Frames stacked top to bottom, so they can share one palette. When any frame
+// has alpha, alphas gets one for every frame (cut at 50% for binary formats).
+// False unless all frames are 8-bit BGR of the same size
+static bool stackFrames(const Animation& animation, bool binaryAlpha, cv::Mat& colors, cv::Mat& alphas) {
+    if (animation.frames.empty()) return false;
+    const cv::Size size = animation.frames[0].color.size();
+    bool hasAlpha = false;
+    for (const AnimationFrame& frame : animation.frames) {
+        if (frame.color.type() != CV_8UC3 || frame.color.size() != size) return false;
+        if (!frame.alpha.empty()) hasAlpha = true;
+    }
+
+    std::vector<cv::Mat> colorList, alphaList;
+    for (const AnimationFrame& frame : animation.frames) {
+        colorList.push_back(frame.color);
+        if (!hasAlpha) continue;
+        cv::Mat alpha(size, CV_8U, cv::Scalar(255));
+        if (binaryAlpha && !frame.alpha.empty()) cv::threshold(frame.alpha, alpha, 127, 255, cv::THRESH_BINARY);
+        else if (!frame.alpha.empty()) alpha = frame.alpha;
+        alphaList.push_back(alpha);
+    }
+    cv::vconcat(colorList, colors);
+    alphas.release();
+    if (hasAlpha) cv::vconcat(alphaList, alphas);
+    return true;
+}
+
+// Rows of frame i in stacked frames
+static cv::Range frameRows(const Animation& animation, size_t i) {
+    const int height = animation.frames[0].color.rows;
+    return cv::Range(static_cast<int>(i) * height, static_cast<int>(i + 1) * height);
+}
+
+// Plays in total for APNG and WebP, which count the first one too (0 = forever)
+static int getPlayCount(int loopCount) {
+    if (loopCount < 0) return 1;
+    return loopCount == 0 ? 0 : std::min(loopCount + 1, 0xffff);
+}
+
+static bool writeGifAnimation(const std::string& filename, const Animation& animation) {
+    cv::Mat colors, alphas;
+    if (!stackFrames(animation, true, colors, alphas)) return false;
+    const cv::Size size = animation.frames[0].color.size();
+    const bool hasAlpha = !alphas.empty();
+
+    std::vector<uint32_t> globalEntries;
+    cv::Mat globalIndices;
+    if (!buildPalette(colors, alphas, 256, globalEntries, globalIndices)) globalIndices.release();
+    const bool global = !globalIndices.empty();
+
+    // Opaque frames sharing a palette with a spare entry store only the area
+    // that changed since the previous frame, with the pixels in it that stayed
+    // the same made transparent, so that they compress to long runs
+    const bool deltas = global && !hasAlpha && globalEntries.size() < 256;
+    if (deltas) globalEntries.push_back(0);
+    const uchar unchangedIndex = static_cast<uchar>(globalEntries.size() - 1);
+    const int globalBits = global ? gifTableBits(globalEntries) : 1;
+
+    std::vector<uchar> gif = {'G', 'I', 'F', '8', '9', 'a'};
+    appendUint16Le(gif, size.width);
+    appendUint16Le(gif, size.height);
+    gif.insert(gif.end(), {static_cast<uchar>(global ? 0xf0 | (globalBits - 1) : 0x70), 0, 0});
+    if (global) appendGifColorTable(gif, globalEntries, globalBits);
+
+    if (animation.loopCount >= 0) {
+        // NETSCAPE2.0 application extension: how many times to loop
+        const char* identifier = "NETSCAPE2.0";
+        gif.insert(gif.end(), {0x21, 0xff, 0x0b});
+        gif.insert(gif.end(), identifier, identifier + 11);
+        gif.insert(gif.end(), {0x03, 0x01});
+        appendUint16Le(gif, std::min(animation.loopCount, 0xffff));
+        gif.push_back(0);
+    }
+
+    int elapsedCs = 0;
+    int elapsedMs = 0;
+    for (size_t i = 0; i < animation.frames.size(); ++i) {
+        const cv::Range rows = frameRows(animation, i);
+        std::vector<uint32_t> entries;
+        cv::Mat indices;
+        cv::Rect area(0, 0, size.width, size.height);
+        if (global) {
+            entries = globalEntries;
+            indices = globalIndices.rowRange(rows);
+        } else if (!buildPalette(colors.rowRange(rows), hasAlpha ? alphas.rowRange(rows) : cv::Mat(), 256,
+                                 entries, indices)) {
+            return false;
+        }
+        if (deltas && i > 0) {
+            // A frame identical to the last one is a single transparent pixel
+            cv::Mat previous = globalIndices.rowRange(frameRows(animation, i - 1));
+            std::vector<cv::Point> changed;
+            cv::findNonZero(indices != previous, changed);
+            area = changed.empty() ? cv::Rect(0, 0, 1, 1) : cv::boundingRect(changed);
+            cv::Mat unchanged = indices(area) == previous(area);
+            indices = indices(area).clone();
+            indices.setTo(unchangedIndex, unchanged);
+        }
+        int tableBits = global ? globalBits : gifTableBits(entries);
+
+        // Graphic control extension: opaque frames stay (disposal 1) for the
+        // next one to draw its changes over. Frames with transparency cover the
+        // whole screen and clear it afterwards (disposal 2) rather than let the
+        // previous frame show through the next one. Delays are rounded to
+        // centiseconds from the start, so they don't drift
+        auto transparent = std::find(entries.begin(), entries.end(), 0u);
+        bool frameHasAlpha = !animation.frames[i].alpha.empty();
+        elapsedMs += animation.frames[i].delayMs;
+        int delay = (elapsedMs + 5) / 10 - elapsedCs;
+        elapsedCs += delay;
+        uchar packed = static_cast<uchar>((frameHasAlpha ? 2 : 1) << 2 | (transparent != entries.end() ? 1 : 0));
+        gif.insert(gif.end(), {0x21, 0xf9, 0x04, packed});
+        appendUint16Le(gif, std::clamp(delay, 0, 0xffff));
+        gif.push_back(transparent != entries.end() ? static_cast<uchar>(transparent - entries.begin()) : 0);
+        gif.push_back(0);
+
+        gif.push_back(0x2c);
+        appendUint16Le(gif, area.x);
+        appendUint16Le(gif, area.y);
+        appendUint16Le(gif, area.width);
+        appendUint16Le(gif, area.height);
+        if (global) {
+            gif.push_back(0);
+        } else {
+            gif.push_back(static_cast<uchar>(0x80 | (tableBits - 1)));
+            appendGifColorTable(gif, entries, tableBits);
+        }
+        appendGifImageData(gif, indices, tableBits);
+    }
+    gif.push_back(0x3b);
+    return writeBytes(filename, gif);
+}
+
+// Animated PNG: each frame is encoded as a PNG of its own, whose IDAT data
+// becomes the frame's fdAT chunks. The first frame keeps plain IDAT chunks,
+// which is what viewers without APNG support show. Frames share an indexed
+// palette when their colors fit in 256 together, otherwise they're truecolor
+static bool writeApng(const std::string& filename, const Animation& animation, const std::vector<int>& params) {
+    cv::Mat colors, alphas;
+    if (!stackFrames(animation, false, colors, alphas)) return false;
+    std::vector<uint32_t> entries;
+    cv::Mat indices;
+    const bool indexed = buildPalette(colors, alphas, 256, entries, indices);
+
+    std::vector<uchar> apng = {0x89, 'P', 'N', 'G', '\r', '\n', 0x1a, '\n'};
+    uint32_t sequence = 0;
+    for (size_t i = 0; i < animation.frames.size(); ++i) {
+        const cv::Range rows = frameRows(animation, i);
+        std::vector<uchar> png;
+        if (indexed) {
+            if (!encodeIndexedPng(entries, indices.rowRange(rows), params, png)) return false;
+        } else {
+            cv::Mat image = colors.rowRange(rows);
+            if (!alphas.empty()) {
+                std::vector<cv::Mat> channels;
+                cv::split(image, channels);
+                channels.push_back(alphas.rowRange(rows));
+                cv::merge(channels, image);
+            }
+            if (!cv::imencode(".png", image, png, params)) return false;
+        }
+
+        bool frameControl = false;
+        size_t pos = 8;
+        while (pos + 12 <= png.size()) {
+            size_t length = readUint32(png.data() + pos, true);
+            if (length > png.size() - pos - 12) return false;
+            std::string type(png.begin() + pos + 4, png.begin() + pos + 8);
+            std::vector<uchar> data(png.begin() + pos + 8, png.begin() + pos + 8 + length);
+            pos += 12 + length;
+
+            if (type == "IDAT") {
+                if (!frameControl) {
+                    // Full-frame, replacing the previous one outright
+                    std::vector<uchar> control;
+                    appendUint32(control, sequence++);
+                    appendUint32(control, static_cast<uint32_t>(animation.frames[i].color.cols));
+                    appendUint32(control, static_cast<uint32_t>(animation.frames[i].color.rows));
+                    appendUint32(control, 0);
+                    appendUint32(control, 0);
+                    int delay = std::clamp(animation.frames[i].delayMs, 0, 0xffff);
+                    control.insert(control.end(), {static_cast<uchar>(delay >> 8), static_cast<uchar>(delay),
+                                                   0x03, 0xe8, 0, 0});   // Delay in 1/1000 s, no disposal, no blending
+                    appendChunk(apng, "fcTL", control);
+                    frameControl = true;
+                }
+                if (i == 0) {
+                    appendChunk(apng, "IDAT", data);
+                } else {
+                    std::vector<uchar> frameData;
+                    appendUint32(frameData, sequence++);
+                    frameData.insert(frameData.end(), data.begin(), data.end());
+                    appendChunk(apng, "fdAT", frameData);
+                }
+            } else if (i == 0 && type != "IEND") {
+                // Header chunks (IHDR, PLTE, tRNS) come from the first frame
+                appendChunk(apng, type.c_str(), data);
+                if (type == "IHDR") {
+                    std::vector<uchar> control;
+                    appendUint32(control, static_cast<uint32_t>(animation.frames.size()));
+                    appendUint32(control, static_cast<uint32_t>(getPlayCount(animation.loopCount)));
+                    appendChunk(apng, "acTL", control);
+                }
+            }
+        }
+        if (!frameControl) return false;
+    }
+    appendChunk(apng, "IEND", {});
+    return writeBytes(filename, apng);
+}
+
+// Animated WebP: each frame is encoded by OpenCV as a WebP of its own, and its
+// image chunks (VP8L, or ALPH and VP8 when lossy) are wrapped in an ANMF frame
+static bool writeWebpAnimation(const std::string& filename, const Animation& animation,
+                               const std::vector<int>& params) {
+    cv::Mat colors, alphas;
+    if (!stackFrames(animation, false, colors, alphas) || !cv::haveImageWriter(filename)) return false;
+    const cv::Size size = animation.frames[0].color.size();
+
+    auto appendUint24Le = [](std::vector<uchar>& out, uint32_t value) {
+        out.insert(out.end(), {static_cast<uchar>(value), static_cast<uchar>(value >> 8),
+                               static_cast<uchar>(value >> 16)});
+    };
+    auto appendWebpChunk = [&appendUint24Le](std::vector<uchar>& out, const char* type, const std::vector<uchar>& data) {
+        out.insert(out.end(), type, type + 4);
+        appendUint24Le(out, static_cast<uint32_t>(data.size()));
+        out.push_back(static_cast<uchar>(data.size() >> 24));
+        out.insert(out.end(), data.begin(), data.end());
+        if (data.size() % 2) out.push_back(0);   // Chunks are padded to an even size
+    };
+
+    std::vector<uchar> body = {'W', 'E', 'B', 'P'};
+    std::vector<uchar> header = {static_cast<uchar>(alphas.empty() ? 0x02 : 0x12), 0, 0, 0};   // Animation, alpha
+    appendUint24Le(header, static_cast<uint32_t>(size.width - 1));
+    appendUint24Le(header, static_cast<uint32_t>(size.height - 1));
+    appendWebpChunk(body, "VP8X", header);
+
+    std::vector<uchar> loop = {0, 0, 0, 0};   // Transparent background
+    appendUint16Le(loop, getPlayCount(animation.loopCount));
+    appendWebpChunk(body, "ANIM", loop);
+
+    for (size_t i = 0; i < animation.frames.size(); ++i) {
+        const cv::Range rows = frameRows(animation, i);
+        cv::Mat image = colors.rowRange(rows);
+        if (!alphas.empty()) {
+            std::vector<cv::Mat> channels;
+            cv::split(image, channels);
+            channels.push_back(alphas.rowRange(rows));
+            cv::merge(channels, image);
+        }
+        std::vector<uchar> webp;
+        if (!cv::imencode(".webp", image, webp, params) || webp.size() < 12) return false;
+
+        // Full-frame at the origin, replacing the previous one (no blending)
+        std::vector<uchar> frame;
+        appendUint24Le(frame, 0);
+        appendUint24Le(frame, 0);
+        appendUint24Le(frame, static_cast<uint32_t>(size.width - 1));
+        appendUint24Le(frame, static_cast<uint32_t>(size.height - 1));
+        appendUint24Le(frame, static_cast<uint32_t>(std::clamp(animation.frames[i].delayMs, 0, 0xffffff)));
+        frame.push_back(0x02);
+
+        size_t frameStart = frame.size();
+        size_t pos = 12;
+        while (pos + 8 <= webp.size()) {
+            size_t length = readUint32(webp.data() + pos + 4, false);
+            if (length > webp.size() - pos - 8) return false;
+            std::string type(webp.begin() + pos, webp.begin() + pos + 4);
+            if (type == "ALPH" || type == "VP8 " || type == "VP8L") {
+                appendWebpChunk(frame, type.c_str(), std::vector<uchar>(webp.begin() + pos + 8,
+                                                                        webp.begin() + pos + 8 + length));
+            }
+            pos += 8 + length + length % 2;
+        }
+        if (frame.size() == frameStart) return false;
+        appendWebpChunk(body, "ANMF", frame);
+    }
+
+    std::vector<uchar> riff = {'R', 'I', 'F', 'F'};
+    appendUint24Le(riff, static_cast<uint32_t>(body.size()));
+    riff.push_back(static_cast<uchar>(body.size() >> 24));
+    riff.insert(riff.end(), body.begin(), body.end());
+    return writeBytes(filename, riff);
+}
+
+const std::vector<std::string>& getAnimationExtensions() {
+    static const std::vector<std::string> extensions = {"gif", "webp", "apng"};
+    return extensions;
+}
+
+bool saveAnimation(const std::string& filename, const Animation& animation, const EncodeOptions& options) {
+    std::string ext = getExtension(filename);
+    if (ext == "gif") return writeGifAnimation(filename, animation);
+    if (ext == "apng") return writeApng(filename, animation, getEncoderParams(filename, options));
+    if (ext == "webp") return writeWebpAnimation(filename, animation, getEncoderParams(filename, options));
+    return false;
+}
+
+// Binary NetPBM: P4 (1 bit, 1 = black) for pbm, P5 gray for pgm, P6 color for
+// ppm. pnm picks the smallest one that holds the image exactly. Results are
+// thresholded at mid gray for pbm and converted to luma for pgm
+static bool encodeNetpbm(const cv::Mat& color, const std::string& ext, std::vector<uchar>& out) {
+    if (color.type() != CV_8UC3) return false;
+
+    cv::Mat gray;
+    cv::cvtColor(color, gray, cv::COLOR_BGR2GRAY);
+    std::string type = ext;
+    if (ext == "pnm") {
+        std::vector<cv::Mat> channels;
+        cv::split(color, channels);
+        bool isGray = cv::countNonZero(channels[0] != channels[1]) == 0 &&
+                      cv::countNonZero(channels[1] != channels[2]) == 0;
+        bool isBilevel = isGray && cv::countNonZero((gray != 0) & (gray != 255)) == 0;
+        type = isBilevel ? "pbm" : isGray ? "pgm" : "ppm";
+    }
+
+    std::string header = (type == "pbm" ? "P4\n" : type == "pgm" ? "P5\n" : "P6\n") +
+                         std::to_string(color.cols) + " " + std::to_string(color.rows) + "\n" +
+                         (type == "pbm" ? "" : "255\n");
+    out.assign(header.begin(), header.end());
+    for (int y = 0; y < color.rows; ++y) {
+        if (type == "pbm") {
+            std::vector<uchar> row((color.cols + 7) / 8, 0);
+            const uchar* pixels = gray.ptr<uchar>(y);
+            for (int x = 0; x < color.cols; ++x) {
+                if (pixels[x] < 128) row[x / 8] |= static_cast<uchar>(0x80 >> (x % 8));
+            }
+            out.insert(out.end(), row.begin(), row.end());
+        } else if (type == "pgm") {
+            out.insert(out.end(), gray.ptr<uchar>(y), gray.ptr<uchar>(y) + color.cols);
+        } else {
+            const cv::Vec3b* pixels = color.ptr<cv::Vec3b>(y);
+            for (int x = 0; x < color.cols; ++x) out.insert(out.end(), {pixels[x][2], pixels[x][1], pixels[x][0]});
+        }
+    }
+    return true;
+}
+
+// X11 bitmap: C source with a 1-bit array named after the file, least
+// significant bit first, 1 = foreground (dark)
+static bool encodeXbm(const std::string& filename, const cv::Mat& color, std::vector<uchar>& out) {
+    if (color.type() != CV_8UC3) return false;
+
+    std::string name = std::filesystem::path(filename).stem().string();
+    for (char& c : name) {
+        if (!std::isalnum(static_cast<unsigned char>(c))) c = '_';
+    }
+    if (name.empty() || std::isdigit(static_cast<unsigned char>(name[0]))) name = "image_" + name;
+
+    cv::Mat gray;
+    cv::cvtColor(color, gray, cv::COLOR_BGR2GRAY);
+    std::string text = "#define " + name + "_width " + std::to_string(color.cols) + "\n#define " + name +
+                       "_height " + std::to_string(color.rows) + "\nstatic unsigned char " + name + "_bits[] = {";
+    int count = 0;
+    for (int y = 0; y < gray.rows; ++y) {
+        const uchar* pixels = gray.ptr<uchar>(y);
+        for (int x = 0; x < gray.cols; x += 8) {
+            int byte = 0;
+            for (int bit = 0; bit < 8 && x + bit < gray.cols; ++bit) {
+                if (pixels[x + bit] < 128) byte |= 1 << bit;
+            }
+            char hex[8];
+            std::snprintf(hex, sizeof(hex), "0x%02x", byte);
+            text += std::string(count > 0 ? "," : "") + (count % 12 == 0 ? "\n   " : " ") + hex;
+            count++;
+        }
+    }
+    text += " };\n";
+    out.assign(text.begin(), text.end());
+    return true;
+}
+
+// Save an image without alpha in the format's own encoder, where this file
+// has one, or through OpenCV
+static bool writeOpaque(const std::string& filename, const cv::Mat& color, const std::vector<int>& params,
+                        const ExportOptions& options) {
+    std::string ext = getExtension(filename);
+    std::vector<uchar> encoded;
+    bool saved = false;
+    if (options.indexedPng && ext == "png" && encodeIndexedPng(color, cv::Mat(), params, encoded)) {
+        return writeBytes(filename, encoded);
+    }
+    if (ext == "gif" && writeGif(filename, color, cv::Mat(), options.gifTransparentIndex, saved)) return saved;
+    if (options.encode.bilevelTiff && (ext == "tif" || ext == "tiff") && encodeBilevelTiff(color, encoded)) {
+        return writeBytes(filename, encoded);
+    }
+    if (ext == "ico") return writeIco(filename, color, cv::Mat(), options);
+    if ((ext == "pbm" || ext == "pgm" || ext == "ppm" || ext == "pnm") && encodeNetpbm(color, ext, encoded)) {
+        return writeBytes(filename, encoded);
+    }
+    if (ext == "xbm") return encodeXbm(filename, color, encoded) && writeBytes(filename, encoded);
+    return cv::imwrite(filename, color, params);
+}
+
+// Save image file, handling alpha per output format
+static bool writeImage(const std::string& filename, const cv::Mat& color, const cv::Mat& alpha,
+                       const ExportOptions& options) {
+    std::vector<int> params = getEncoderParams(filename, options.encode);
+    std::string ext = getExtension(filename);
+    bool indexed = options.indexedPng && ext == "png";
+    bool gif = ext == "gif";
+    std::vector<uchar> encoded;
+    if (alpha.empty()) return writeOpaque(filename, color, params, options);
+
+    bool binary = options.binaryAlpha || formatHasBinaryAlpha(filename);
+
+    // Pixels at or above the cutoff become fully opaque, the rest fully transparent
+    cv::Mat binaryAlpha;
+    cv::threshold(alpha, binaryAlpha, options.alphaThreshold - 1, 255, cv::THRESH_BINARY);
+
+    if (formatSupportsAlpha(filename)) {
+        std::vector<cv::Mat> channels;
+        cv::split(color, channels);
+        cv::Mat fullAlpha = alpha;
+        if (options.alphaLevels >= 2 && options.alphaLevels < 256) {
+            // Round to the nearest level, e.g. multiples of 17 for 4-bit alpha
+            double step = 255.0 / (options.alphaLevels - 1);
+            alpha.convertTo(fullAlpha, CV_8U, 1.0 / step);
+            fullAlpha.convertTo(fullAlpha, CV_8U, step);
+        }
+        channels.push_back(binary ? binaryAlpha : fullAlpha);
+
+        bool saved = false;
+        if (indexed && encodeIndexedPng(color, channels.back(), params, encoded)) return writeBytes(filename, encoded);
+        if (gif && writeGif(filename, color, channels.back(), options.gifTransparentIndex, saved)) return saved;
+        if (ext == "ico") return writeIco(filename, color, channels.back(), options);
+
+        cv::Mat bgra;
+        cv::merge(channels, bgra);
+        return cv::imwrite(filename, bgra, params);
+    }
+
+    // No alpha support: transparent pixels take the matte color
+    cv::Mat flattened = color.clone();
+    flattened.setTo(cv::Scalar(options.matteColor[0], options.matteColor[1], options.matteColor[2]),
+                    binaryAlpha == 0);
+    return writeOpaque(filename, flattened, params, options);
+}
+
+// zlib stream of stored (uncompressed) blocks, for iCCP chunks. Profiles are
+// small next to the image data, so compressing them isn't worth an encoder
+static std::vector<uchar> storeZlib(const std::vector<uchar>& data) {
+    std::vector<uchar> out = {0x78, 0x01};
+    size_t pos = 0;
+    do {
+        size_t length = std::min<size_t>(data.size() - pos, 65535);
+        bool last = pos + length == data.size();
+        out.push_back(last ? 1 : 0);
+        out.push_back(static_cast<uchar>(length));
+        out.push_back(static_cast<uchar>(length >> 8));
+        out.push_back(static_cast<uchar>(~length));
+        out.push_back(static_cast<uchar>(~length >> 8));
+        out.insert(out.end(), data.begin() + pos, data.begin() + pos + length);
+        pos += length;
+    } while (pos < data.size());
+
+    // Adler-32 of the uncompressed data
+    uint32_t a = 1, b = 0;
+    for (uchar value : data) {
+        a = (a + value) % 65521;
+        b = (b + a) % 65521;
+    }
+    appendUint32(out, b << 16 | a);
+    return out;
+}
+
+// Add the source's EXIF block and ICC profile to a JPEG (APP1 and APP2
+// segments after the JFIF header) or PNG (iCCP and eXIf chunks after IHDR,
+// since iCCP has to come before the palette) that was just written
+static bool embedMetadata(const std::string& filename, const ExportOptions& options) {
+    std::string ext = getExtension(filename);
+    bool jpeg = ext == "jpg" || ext == "jpeg" || ext == "jpe";
+    if (!jpeg && ext != "png") return true;
+
+    Metadata metadata = options.encode.keepMetadata ? options.metadata : Metadata();
+    bool managed = options.colorManagement == ColorManagement::HONOR_PROFILES;
+    ColorProfile::Profile profile;
+    if (managed && !metadata.icc.empty() && ColorProfile::parseProfile(metadata.icc, profile)) {
+        // loadImage converted the pixels to the profile space
+        metadata.icc = ColorProfile::buildProfile(options.profileSpace);
+    }
+    // Wide gamut results are always tagged, or they'd be taken for sRGB
+    bool wideGamut = managed && options.profileSpace != ColorProfile::Space::SRGB;
+    if ((options.encode.tagProfile || wideGamut) && metadata.icc.empty()) {
+        metadata.icc = ColorProfile::buildProfile(managed ? options.profileSpace : ColorProfile::Space::SRGB);
+    }
+    if (metadata.exif.empty() && metadata.icc.empty()) return true;
+
+    std::vector<uchar> data;
+    if (!readBytes(filename, data)) return false;
+
+    // The pixels were turned upright on load
+    std::vector<uchar> exif = metadata.exif;
+    size_t entry = 0;
+    bool bigEndian = false;
+    if (findTiffTag(exif, 0x0112, entry, bigEndian)) {
+        exif[entry + 8] = bigEndian ? 0 : 1;
+        exif[entry + 9] = bigEndian ? 1 : 0;
+    }
+
+    std::vector<uchar> inserted;
+    size_t position = 0;
+    if (jpeg) {
+        if (data.size() < 4 || data[0] != 0xFF || data[1] != 0xD8) return false;
+        position = 2;
+        if (data.size() >= 6 && data[2] == 0xFF && data[3] == 0xE0) position += 2 + (data[4] << 8 | data[5]);
+
+        // A segment holds at most 65533 bytes; bigger EXIF blocks are left out
+        auto appendSegment = [&inserted](uchar marker, const std::vector<uchar>& payload) {
+            size_t length = payload.size() + 2;
+            inserted.insert(inserted.end(), {0xFF, marker, static_cast<uchar>(length >> 8),
+                                             static_cast<uchar>(length)});
+            inserted.insert(inserted.end(), payload.begin(), payload.end());
+        };
+        if (!exif.empty() && exif.size() + 6 <= 65533) {
+            std::vector<uchar> payload = {'E', 'x', 'i', 'f', 0, 0};
+            payload.insert(payload.end(), exif.begin(), exif.end());
+            appendSegment(0xE1, payload);
+        }
+        const size_t partSize = 65533 - 14;
+        size_t parts = (metadata.icc.size() + partSize - 1) / partSize;
+        for (size_t i = 0; parts <= 255 && i < parts; ++i) {
+            const char* id = "ICC_PROFILE";
+            std::vector<uchar> payload(id, id + 12);
+            payload.push_back(static_cast<uchar>(i + 1));
+            payload.push_back(static_cast<uchar>(parts));
+            auto start = metadata.icc.begin() + i * partSize;
+            payload.insert(payload.end(), start, start + std::min(partSize, metadata.icc.size() - i * partSize));
+            appendSegment(0xE2, payload);
+        }
+    } else {
+        position = 8 + 12 + 13;
+        if (data.size() < position || std::memcmp(data.data() + 12, "IHDR", 4) != 0) return false;
+        if (!metadata.icc.empty()) {
+            const char* name = "ICC Profile";
+            std::vector<uchar> chunk(name, name + std::strlen(name) + 1);
+            chunk.push_back(0);  // Deflate
+            std::vector<uchar> compressed = storeZlib(metadata.icc);
+            chunk.insert(chunk.end(), compressed.begin(), compressed.end());
+            appendChunk(inserted, "iCCP", chunk);
+        }
+        if (!exif.empty()) appendChunk(inserted, "eXIf", exif);
+    }
+
+    data.insert(data.begin() + static_cast<std::ptrdiff_t>(position), inserted.begin(), inserted.end());
+    return writeBytes(filename, data);
+}
+
+bool saveImage(const std::string& filename, const cv::Mat& color, const cv::Mat& alpha,
+               const ExportOptions& options, uintmax_t* fileSize) {
+    if (!writeImage(filename, color, alpha, options)) return false;
+    if (!embedMetadata(filename, options)) return false;
+    if (fileSize) {
+        std::error_code ec;
+        *fileSize = std::filesystem::file_size(filename, ec);
+        if (ec) *fileSize = 0;
+    }
+    return true;
+}
+
+} // namespace ImageIO
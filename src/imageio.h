@@ -0,0 +1,270 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <cstddef>
+#include <cstdint>
+#include <string>
+#include <vector>
+
+#include "colorprofile.h"
+#include "errors.h"
+#include "platform.h"
+
+namespace ImageIO {
+
+// Non-fatal problems detected while loading, for the UI to surface
+enum class WarningCode {
+    CMYK_CONVERTED,         // CMYK/YCCK data converted to RGB without color management
+    CMYK_UNTAGGED,          // CMYK without Adobe marker, channel polarity may be inverted
+    HIGH_BIT_DEPTH,         // More than 8 bits per sample, reduced to 8-bit (precision not requested)
+    UNUSUAL_COLORSPACE,     // Component layout not recognized, decoded as-is
+    UNMANAGED_PROFILE,      // Embedded ICC profile that can't be applied
+    DOWNSCALED,             // Too big for the decode memory limit, opened at a smaller size
+    FALLBACK_FONT           // Text rendered with the built-in font, the one asked for isn't available
+};
+
+// Session-wide handling of embedded color profiles
+enum class ColorManagement {
+    ASSUME_SRGB,            // Treat all images as sRGB, ignore embedded profiles
+    HONOR_PROFILES          // Convert images from their embedded profile (untagged ones from sRGB) to the
+                            // profile space, and report profiles that can't be applied
+};
+
+// Session-wide color management setting, used by loadImage and getImageInfo
+void setColorManagement(ColorManagement mode);
+ColorManagement getColorManagement();
+
+// Space images are converted to under HONOR_PROFILES (default sRGB)
+void setProfileSpace(ColorProfile::Space space);
+ColorProfile::Space getProfileSpace();
+
+struct Warning {
+    WarningCode code;
+    std::string message;
+};
+
+// Basic file information gathered without running the dithering pipeline
+struct ImageInfo {
+    int width = 0;
+    int height = 0;
+    int channels = 0;
+    int bitDepth = 8;
+    std::string format;
+    std::string colorSpace;
+    bool hasIccProfile = false;
+    std::string iccProfileName;     // Empty if the profile has no readable name
+    std::vector<Warning> warnings;
+};
+
+// zlib strategy for PNG encoding. Filtered and RLE suit the long runs and
+// repeating patterns of dithered images; Huffman only is fastest
+enum class PngStrategy {
+    DEFAULT,
+    FILTERED,
+    HUFFMAN_ONLY,
+    RLE,
+    FIXED
+};
+
+// Encoder settings for formats that take them
+struct EncodeOptions {
+    int jpegQuality = 95;           // 1-100
+    int pngCompression = 6;         // zlib level, 0 (none, fastest) to 9 (smallest)
+    PngStrategy pngStrategy = PngStrategy::DEFAULT;
+    bool webpLossless = true;       // Lossy WebP smears dither patterns
+    int webpQuality = 90;           // 1-100, when not lossless
+    int avifQuality = 100;          // 0-100, 100 = lossless
+    int jxlQuality = 100;           // 0-100, 100 = lossless
+    bool bilevelTiff = true;        // Opaque TIFFs with two colors or fewer are written at 1 bit per pixel
+    bool keepMetadata = true;       // Copy the source's EXIF and ICC profile into JPEG and PNG results
+    bool tagProfile = false;        // Give JPEG and PNG results without a profile the profile space's
+                                    // (always done when that isn't sRGB)
+};
+
+// PNG strategy lookup by command line id (default, filtered, huffman, rle, fixed)
+const std::vector<std::string>& getPngStrategyIds();
+bool parsePngStrategy(const std::string& id, PngStrategy& strategy);
+std::string getPngStrategyName(PngStrategy strategy);
+
+// Metadata carried over from the source image
+struct Metadata {
+    std::vector<unsigned char> exif;    // TIFF-structured EXIF block, as in a JPEG APP1 after "Exif\0\0"
+    std::vector<unsigned char> icc;     // Embedded ICC profile
+    int orientation = 1;                // EXIF orientation, 1 = upright (loadImage has already applied it)
+};
+
+// Export options for formats with limited transparency support
+struct ExportOptions {
+    int alphaThreshold = 128;       // Alpha cutoff for binary transparency (0-255)
+    bool useMatte = false;          // Composite semi-transparent pixels onto the matte color
+    cv::Vec3b matteColor = cv::Vec3b(255, 255, 255);  // Matte color (BGR)
+    bool binaryAlpha = false;       // Force binary transparency even if the format supports full alpha
+    int alphaLevels = 256;          // Evenly spaced alpha levels kept on export (16 for RGBA4444)
+    bool indexedPng = true;         // PNGs with 256 colors or fewer get a palette (1, 2, 4 or 8 bits per pixel)
+    int gifTransparentIndex = -1;   // GIF palette index for transparent pixels (-1 = any)
+    EncodeOptions encode;
+    Metadata metadata;              // Written to JPEG and PNG results when encode.keepMetadata is set
+
+    // Color management the result was made under, which decides the profile
+    // it's tagged with. Taken from the session settings when the options are
+    // made, so a queued job keeps the ones it was queued with
+    ColorManagement colorManagement = getColorManagement();
+    ColorProfile::Space profileSpace = getProfileSpace();
+};
+
+// One frame of an animation, as the complete picture shown at that time
+struct AnimationFrame {
+    cv::Mat color;                  // 8-bit BGR
+    cv::Mat alpha;                  // 8-bit, empty if the frame is opaque
+    int delayMs = 100;              // How long the frame is shown
+};
+
+struct Animation {
+    std::vector<AnimationFrame> frames;
+    int loopCount = 0;              // Extra plays after the first (0 = forever, -1 = play once)
+};
+
+// Session-wide memory budget for opening an image, checked against its header
+// before decoding. Images that would need more are refused (describeLoadFailure
+// reports OUT_OF_MEMORY), or with downscaling on, opened at the largest size
+// that fits: JPEGs decode straight to 1/2, 1/4 or 1/8 size, other formats are
+// decoded whole and scaled down at once, so they're refused if even that won't
+// fit. Formats without a readable header (EXR, HEIC, RAW) aren't checked.
+// 0 = no limit
+constexpr size_t DEFAULT_DECODE_MEMORY_LIMIT = size_t(2048) << 20;
+void setDecodeMemoryLimit(size_t bytes);
+size_t getDecodeMemoryLimit();
+void setDownscaleOversized(bool downscale);
+bool getDownscaleOversized();
+
+// Session-wide width SVG files are rasterized at, in pixels, keeping their
+// aspect ratio (0 = the size the file gives)
+void setSvgWidth(int width);
+int getSvgWidth();
+
+// OpenCV only decodes OpenEXR when asked to through the environment, and it
+// reads the setting once, so main calls this before any image is touched
+void enableOpenExr();
+
+// Default size limit for images opened from http(s) URLs
+constexpr size_t DEFAULT_MAX_DOWNLOAD_BYTES = 64 * 1024 * 1024;
+
+// Load an image, splitting color (8-bit BGR) and alpha (8-bit, empty if opaque).
+// For 16-bit and floating-point sources (16-bit PNG/TIFF, EXR), precise receives
+// the full precision color as CV_32FC3 on a 0-255 scale; it's left empty for
+// 8-bit sources. http(s) URLs are downloaded first with the default size limit.
+// HEIC, and AVIF/JPEG XL when OpenCV lacks their codecs, are decoded with a
+// system tool (see Platform::convertImage), and SVG is rasterized by one at
+// the SVG width, keeping its transparency. So are camera RAW files, demosaiced
+// with camera white balance to 16 bits, which makes them high precision sources.
+// EXIF orientation is applied, so portrait photos come out upright
+bool loadImage(const std::string& filename, cv::Mat& color, cv::Mat& alpha,
+               std::vector<Warning>* warnings = nullptr, cv::Mat* precise = nullptr);
+
+// Download an image from an http(s) URL into a temp file and load it
+bool loadImageFromUrl(const std::string& url, cv::Mat& color, cv::Mat& alpha,
+                      std::vector<Warning>* warnings = nullptr,
+                      size_t maxBytes = DEFAULT_MAX_DOWNLOAD_BYTES,
+                      Platform::DownloadProgress progress = nullptr,
+                      cv::Mat* precise = nullptr);
+
+// Load encoded image data (PNG, JPEG, GIF, BMP, TIFF, WebP or EXR, told apart by
+// their signatures) held in memory, e.g. from the clipboard
+bool loadImageFromMemory(const std::vector<unsigned char>& data, cv::Mat& color, cv::Mat& alpha,
+                         std::vector<Warning>* warnings = nullptr, cv::Mat* precise = nullptr);
+
+// Load any image loadImage reads as an 8-bit single-channel mask (its luma)
+bool loadMask(const std::string& filename, cv::Mat& mask);
+
+// Text to render as an image, for dithering logos and lettering at the exact
+// size needed
+struct TextOptions {
+    std::string font;               // Font name or file ("" = default)
+    int size = 96;                  // Pixels
+    cv::Vec3b color = cv::Vec3b(0, 0, 0);           // BGR
+    cv::Vec3b background = cv::Vec3b(255, 255, 255);
+    int padding = 16;               // Background around the text, in pixels
+};
+
+// Render text, with line breaks for several lines, as an 8-bit BGR image
+// sized to fit it. Uses ImageMagick (see Platform::renderText); without it,
+// OpenCV's built-in font with a FALLBACK_FONT warning
+bool renderText(const std::string& text, const TextOptions& options, cv::Mat& color,
+                std::vector<Warning>* warnings = nullptr);
+
+// Read the EXIF block, ICC profile and orientation of a JPEG, PNG, WebP or
+// TIFF file. TIFFs are their own EXIF structure, so only their orientation and
+// profile are kept. False if the file has none of them
+bool readMetadata(const std::string& filename, Metadata& metadata);
+
+// Decode every frame of a GIF, applying each frame's disposal so they come out
+// as complete pictures. Delays under 20 ms are read as 100 ms, as browsers do
+bool loadGifAnimation(const std::string& filename, Animation& animation);
+
+// Whether a file is a GIF with more than one frame
+bool isAnimatedGif(const std::string& filename);
+
+// Formats animations can be written in: gif, webp (animated WebP) and apng
+// (animated PNG; .png stays a still image)
+const std::vector<std::string>& getAnimationExtensions();
+
+// Write an animation by extension. Frames share one palette when their colors
+// fit in 256 together (e.g. a fixed or locked palette): GIF's global color
+// table, or an indexed APNG. Otherwise GIF frames get a table each, failing for
+// frames with more than 256 colors, and APNGs are truecolor. GIF alpha is cut
+// at 50%. Opaque GIFs sharing a palette of under 256 colors store only what
+// changed in each frame. WebP frames are encoded by OpenCV with options' WebP
+// settings (lossless by default), APNG frames with its PNG settings
+bool saveAnimation(const std::string& filename, const Animation& animation,
+                   const EncodeOptions& options = EncodeOptions());
+
+// Decode a base64 data URL ("data:image/png;base64,...") to its bytes
+bool decodeDataUrl(const std::string& url, std::vector<unsigned char>& data);
+
+// Inspect an image file (dimensions, depth, color space, potential problems).
+// Images over the decode memory limit are described from their header alone
+bool getImageInfo(const std::string& filename, ImageInfo& info);
+
+// Size, channels and bit depth from the header of a PNG, JPEG, GIF, BMP, TIFF,
+// BigTIFF or WebP file, without decoding it. Channels are as loadImage decodes them
+// (palette PNGs count as 4 in case they have transparency)
+bool readImageHeader(const std::string& filename, ImageInfo& info);
+
+// Rough peak memory in bytes loadImage needs for an image with this header
+size_t estimateDecodeMemory(const ImageInfo& info);
+
+// Composite semi-transparent pixels onto the matte color (call before dithering
+// so the matte blend is quantized along with the rest of the image)
+void applyMatte(cv::Mat& color, const cv::Mat& alpha, const ExportOptions& options);
+
+// Save an image, reattaching alpha according to the output format's capabilities.
+// Dithered results rarely use more than 256 colors, so PNGs are written as
+// indexed images when they fit, several times smaller than 24-bit, and GIFs
+// take their color table from the result instead of quantizing it again.
+// JPEG and PNG results keep the source's metadata (see ExportOptions), with the
+// EXIF orientation reset to upright since the pixels already are. A source
+// profile that was applied on load is replaced by the profile space's.
+// fileSize receives the size of the written file in bytes
+bool saveImage(const std::string& filename, const cv::Mat& color, const cv::Mat& alpha,
+               const ExportOptions& options, uintmax_t* fileSize = nullptr);
+
+// File size for display, e.g. "512 B", "48.2 KB", "3.1 MB"
+std::string formatFileSize(uintmax_t bytes);
+
+// Why loadImage or saveImage failed for a file (the message leaves out the
+// file name). The codecs only report success, so this is judged from the file
+// system and the extension
+Errors::Error describeLoadFailure(const std::string& filename);
+Errors::Error describeSaveFailure(const std::string& filename);
+
+// Format capability helpers (based on file extension)
+std::string getExtension(const std::string& filename);
+const std::vector<std::string>& getImageExtensions();
+const std::vector<std::string>& getRawExtensions();   // Camera RAW, part of getImageExtensions
+bool isRawFile(const std::string& filename);
+const std::vector<std::string>& getSaveExtensions();  // Formats that can be written (no HEIC, adds ICO)
+bool isImageFile(const std::string& filename);
+bool formatSupportsAlpha(const std::string& filename);
+bool formatHasBinaryAlpha(const std::string& filename);
+
+} // namespace ImageIO
@@ -0,0 +1,302 @@
+#include "json.h"
+#include <algorithm>
+#include <cctype>
+#include <cmath>
+#include <cstdio>
+#include <cstdlib>
+
+// --- Writing ---
+
+static void writeString(const std::string& value, std::string& out) {
+    out += '"';
+    for (char c : value) {
+        switch (c) {
+            case '"': out += "\\\""; break;
+            case '\\': out += "\\\\"; break;
+            case '\n': out += "\\n"; break;
+            case '\t': out += "\\t"; break;
+            case '\r': out += "\\r"; break;
+            default:
+                if (static_cast<unsigned char>(c) < 0x20) {
+                    char escaped[8];
+                    std::snprintf(escaped, sizeof(escaped), "\\u%04x", static_cast<unsigned char>(c));
+                    out += escaped;
+                } else {
+                    out += c;
+                }
+        }
+    }
+    out += '"';
+}
+
+static bool isScalar(const Json& value) {
+    return value.type != Json::Type::ARRAY && value.type != Json::Type::OBJECT;
+}
+
+void writeJson(const Json& value, std::string& out, int indent) {
+    std::string pad(indent + 2, ' ');
+    switch (value.type) {
+        case Json::Type::NUL: out += "null"; break;
+        case Json::Type::BOOLEAN: out += value.boolean ? "true" : "false"; break;
+        case Json::Type::NUMBER: {
+            char buffer[32];
+            if (std::floor(value.number) == value.number && std::fabs(value.number) < 1e15) {
+                std::snprintf(buffer, sizeof(buffer), "%.0f", value.number);
+            } else {
+                std::snprintf(buffer, sizeof(buffer), "%.9g", value.number);
+            }
+            out += buffer;
+            break;
+        }
+        case Json::Type::STRING: writeString(value.string, out); break;
+        case Json::Type::ARRAY: {
+            // Short lists of plain values (colors, matrix rows) stay on one line
+            bool oneLine = std::all_of(value.items.begin(), value.items.end(), isScalar);
+            out += '[';
+            for (size_t i = 0; i < value.items.size(); ++i) {
+                if (i > 0) out += oneLine ? ", " : ",";
+                if (!oneLine) out += "\n" + pad;
+                writeJson(value.items[i], out, indent + 2);
+            }
+            if (!oneLine && !value.items.empty()) out += "\n" + std::string(indent, ' ');
+            out += ']';
+            break;
+        }
+        case Json::Type::OBJECT: {
+            out += '{';
+            for (size_t i = 0; i < value.members.size(); ++i) {
+                out += (i > 0 ? ",\n" : "\n") + pad;
+                writeString(value.members[i].first, out);
+                out += ": ";
+                writeJson(value.members[i].second, out, indent + 2);
+            }
+            if (!value.members.empty()) out += "\n" + std::string(indent, ' ');
+            out += '}';
+            break;
+        }
+    }
+}
+
+// --- Parsing ---
+
+class Parser {
+public:
+    explicit Parser(const std::string& text) : text_(text) {}
+
+    bool parse(Json& out) {
+        skipSpace();
+        if (!value(out, 0)) return false;
+        skipSpace();
+        if (pos_ != text_.size()) return fail("unexpected text after the document");
+        return true;
+    }
+
+    const std::string& error() const { return error_; }
+
+private:
+    const std::string& text_;
+    size_t pos_ = 0;
+    std::string error_;
+
+    bool fail(const std::string& message) {
+        int line = 1, column = 1;
+        for (size_t i = 0; i < pos_ && i < text_.size(); ++i) {
+            if (text_[i] == '\n') {
+                line++;
+                column = 1;
+            } else {
+                column++;
+            }
+        }
+        error_ = message + " at line " + std::to_string(line) + ", column " + std::to_string(column);
+        return false;
+    }
+
+    void skipSpace() {
+        while (pos_ < text_.size() && std::isspace(static_cast<unsigned char>(text_[pos_]))) pos_++;
+    }
+
+    bool consume(char c) {
+        skipSpace();
+        if (pos_ < text_.size() && text_[pos_] == c) {
+            pos_++;
+            return true;
+        }
+        return false;
+    }
+
+    bool literal(const char* word) {
+        size_t length = std::char_traits<char>::length(word);
+        if (text_.compare(pos_, length, word) != 0) return false;
+        pos_ += length;
+        return true;
+    }
+
+    bool value(Json& out, int depth) {
+        if (depth > 64) return fail("nesting too deep");
+        skipSpace();
+        if (pos_ >= text_.size()) return fail("unexpected end of document");
+
+        char c = text_[pos_];
+        if (c == '{') return object(out, depth);
+        if (c == '[') return array(out, depth);
+        if (c == '"') {
+            out = Json::of(std::string());
+            return string(out.string);
+        }
+        if (literal("true")) { out = Json::of(true); return true; }
+        if (literal("false")) { out = Json::of(false); return true; }
+        if (literal("null")) { out = Json(); return true; }
+
+        if (c == '-' || std::isdigit(static_cast<unsigned char>(c))) return number(out);
+        return fail("unexpected character");
+    }
+
+    bool digits() {
+        size_t start = pos_;
+        while (pos_ < text_.size() && std::isdigit(static_cast<unsigned char>(text_[pos_]))) pos_++;
+        return pos_ > start;
+    }
+
+    // JSON's number syntax only: no nan, inf, hex, leading '+' or bare '.'
+    bool number(Json& out) {
+        size_t start = pos_;
+        if (text_[pos_] == '-') pos_++;
+        if (pos_ < text_.size() && text_[pos_] == '0') {
+            pos_++;
+        } else if (!digits()) {
+            return fail("invalid number");
+        }
+        if (pos_ < text_.size() && text_[pos_] == '.') {
+            pos_++;
+            if (!digits()) return fail("invalid number");
+        }
+        if (pos_ < text_.size() && (text_[pos_] == 'e' || text_[pos_] == 'E')) {
+            pos_++;
+            if (pos_ < text_.size() && (text_[pos_] == '+' || text_[pos_] == '-')) pos_++;
+            if (!digits()) return fail("invalid number");
+        }
+        double number = std::strtod(text_.substr(start, pos_ - start).c_str(), nullptr);
+        if (!std::isfinite(number)) {
+            pos_ = start;
+            return fail("number out of range");
+        }
+        out = Json::of(number);
+        return true;
+    }
+
+    bool object(Json& out, int depth) {
+        out = Json::object();
+        pos_++;
+        if (consume('}')) return true;
+        do {
+            skipSpace();
+            std::string key;
+            if (pos_ >= text_.size() || text_[pos_] != '"') return fail("expected a property name");
+            if (!string(key)) return false;
+            if (!consume(':')) return fail("expected ':'");
+            Json member;
+            if (!value(member, depth + 1)) return false;
+            out.set(key, std::move(member));
+        } while (consume(','));
+        if (!consume('}')) return fail("expected ',' or '}'");
+        return true;
+    }
+
+    bool array(Json& out, int depth) {
+        out = Json::array();
+        pos_++;
+        if (consume(']')) return true;
+        do {
+            Json item;
+            if (!value(item, depth + 1)) return false;
+            out.push(std::move(item));
+        } while (consume(','));
+        if (!consume(']')) return fail("expected ',' or ']'");
+        return true;
+    }
+
+    static void appendUtf8(std::string& out, unsigned long code) {
+        if (code < 0x80) {
+            out += static_cast<char>(code);
+        } else if (code < 0x800) {
+            out += static_cast<char>(0xC0 | (code >> 6));
+            out += static_cast<char>(0x80 | (code & 0x3F));
+        } else if (code < 0x10000) {
+            out += static_cast<char>(0xE0 | (code >> 12));
+            out += static_cast<char>(0x80 | ((code >> 6) & 0x3F));
+            out += static_cast<char>(0x80 | (code & 0x3F));
+        } else {
+            out += static_cast<char>(0xF0 | (code >> 18));
+            out += static_cast<char>(0x80 | ((code >> 12) & 0x3F));
+            out += static_cast<char>(0x80 | ((code >> 6) & 0x3F));
+            out += static_cast<char>(0x80 | (code & 0x3F));
+        }
+    }
+
+    bool hex4(unsigned long& code) {
+        if (pos_ + 4 > text_.size()) return fail("incomplete \\u escape");
+        std::string digits = text_.substr(pos_, 4);
+        if (!std::all_of(digits.begin(), digits.end(), [](unsigned char c) { return std::isxdigit(c); })) {
+            return fail("invalid \\u escape");
+        }
+        code = std::strtoul(digits.c_str(), nullptr, 16);
+        pos_ += 4;
+        return true;
+    }
+
+    bool string(std::string& out) {
+        pos_++;  // Opening quote
+        while (pos_ < text_.size()) {
+            char c = text_[pos_++];
+            if (c == '"') return true;
+            if (c != '\\') {
+                out += c;
+                continue;
+            }
+            if (pos_ >= text_.size()) break;
+            char escape = text_[pos_++];
+            switch (escape) {
+                case '"': out += '"'; break;
+                case '\\': out += '\\'; break;
+                case '/': out += '/'; break;
+                case 'b': out += '\b'; break;
+                case 'f': out += '\f'; break;
+                case 'n': out += '\n'; break;
+                case 'r': out += '\r'; break;
+                case 't': out += '\t'; break;
+                case 'u': {
+                    unsigned long code;
+                    if (!hex4(code)) return false;
+                    // Characters outside the BMP come as a surrogate pair; a half on its own is an error
+                    if (code >= 0xDC00 && code < 0xE000) return fail("unpaired surrogate in \\u escape");
+                    if (code >= 0xD800 && code < 0xDC00) {
+                        if (text_.compare(pos_, 2, "\\u") != 0) return fail("unpaired surrogate in \\u escape");
+                        pos_ += 2;
+                        unsigned long low;
+                        if (!hex4(low)) return false;
+                        if (low < 0xDC00 || low >= 0xE000) return fail("unpaired surrogate in \\u escape");
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    appendUtf8(out, code);
+                    break;
+                }
+                default: return fail("invalid escape sequence");
+            }
+        }
+        return fail("unterminated string");
+    }
+};
+
+std::string writeJson(const Json& value) {
+    std::string out;
+    writeJson(value, out, 0);
+    return out;
+}
+
+bool parseJson(const std::string& text, Json& out, std::string* error) {
+    Parser parser(text);
+    if (parser.parse(out)) return true;
+    if (error) *error = parser.error();
+    return false;
+}
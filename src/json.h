@@ -0,0 +1,53 @@
+#pragma once
+
+#include <cmath>
+#include <string>
+#include <utility>
+#include <vector>
+
+// Minimal JSON document model, just enough for settings files, the automation
+// API and the web services the app reads
+struct Json {
+    enum class Type { NUL, BOOLEAN, NUMBER, STRING, ARRAY, OBJECT };
+
+    Type type = Type::NUL;
+    bool boolean = false;
+    double number = 0.0;
+    std::string string;
+    std::vector<Json> items;
+    std::vector<std::pair<std::string, Json>> members;  // Kept in insertion order
+
+    static Json of(bool value) { Json json; json.type = Type::BOOLEAN; json.boolean = value; return json; }
+    static Json of(double value) { Json json; json.type = Type::NUMBER; json.number = value; return json; }
+    static Json of(int value) { return of(static_cast<double>(value)); }
+    static Json of(const std::string& value) { Json json; json.type = Type::STRING; json.string = value; return json; }
+    static Json of(const char* value) { return of(std::string(value)); }
+    static Json array() { Json json; json.type = Type::ARRAY; return json; }
+    static Json object() { Json json; json.type = Type::OBJECT; return json; }
+
+    Json& set(const std::string& key, Json value) {
+        members.emplace_back(key, std::move(value));
+        return *this;
+    }
+    Json& push(Json value) {
+        items.push_back(std::move(value));
+        return *this;
+    }
+    const Json* find(const std::string& key) const {
+        for (const auto& member : members) {
+            if (member.first == key) return &member.second;
+        }
+        return nullptr;
+    }
+
+    bool isNumber() const { return type == Type::NUMBER; }
+    bool isInteger() const { return type == Type::NUMBER && std::floor(number) == number; }
+};
+
+// Write as text: objects one member per line, indented from indent, and short
+// lists of plain values (colors, matrix rows) on one line
+void writeJson(const Json& value, std::string& out, int indent = 0);
+std::string writeJson(const Json& value);
+
+// Parse a whole document. On failure error says what was wrong and where
+bool parseJson(const std::string& text, Json& out, std::string* error = nullptr);
@@ -0,0 +1,274 @@
+#include "lospec.h"
+#include "json.h"
+#include "platform.h"
+#include <algorithm>
+#include <cctype>
+#include <cstdio>
+#include <filesystem>
+#include <fstream>
+#include <iterator>
+#include <mutex>
+#include <sstream>
+
+namespace Lospec {
+
+using Errors::ErrorCode;
+
+// Lospec's answers are a few kilobytes; anything this large is something else
+static const size_t MAX_RESPONSE_BYTES = 4 << 20;
+
+// The cache keeps the most recently used palettes and searches up to these
+static const size_t MAX_CACHED_PALETTES = 500;
+static const size_t MAX_CACHED_SEARCHES = 200;
+
+// Searches and downloads run on worker threads, and palettes.txt is read,
+// changed and written back, so every cache access goes through this
+static std::mutex cacheMutex;
+
+// A string member of an object, empty if it's missing or isn't a string
+static std::string getText(const Json& object, const std::string& name) {
+    const Json* value = object.find(name);
+    return value && value->type == Json::Type::STRING ? value->string : "";
+}
+
+static std::string urlEncode(const std::string& text) {
+    std::string encoded;
+    for (unsigned char c : text) {
+        if (std::isalnum(c) || c == '-' || c == '_' || c == '.' || c == '~') {
+            encoded += static_cast<char>(c);
+        } else {
+            char escape[4];
+            std::snprintf(escape, sizeof(escape), "%%%02X", c);
+            encoded += escape;
+        }
+    }
+    return encoded;
+}
+
+static std::vector<cv::Vec3b> parseColors(const std::vector<std::string>& hexes) {
+    std::vector<cv::Vec3b> colors;
+    for (std::string hex : hexes) {
+        if (!hex.empty() && hex[0] == '#') hex = hex.substr(1);
+        if (hex.size() != 6 || hex.find_first_not_of("0123456789abcdefABCDEF") != std::string::npos) continue;
+        unsigned long rgb = std::stoul(hex, nullptr, 16);
+        colors.push_back(cv::Vec3b(rgb & 0xFF, (rgb >> 8) & 0xFF, (rgb >> 16) & 0xFF));
+    }
+    return colors;
+}
+
+// Body of a GET request, empty if it couldn't be fetched
+static std::string fetch(const std::string& url) {
+    std::string path = Platform::downloadToTempFile(url, MAX_RESPONSE_BYTES);
+    if (path.empty()) return "";
+    std::ifstream file(path, std::ios::binary);
+    std::string body((std::istreambuf_iterator<char>(file)), std::istreambuf_iterator<char>());
+    file.close();
+    std::error_code ec;
+    std::filesystem::remove(path, ec);
+    return body;
+}
+
+std::string getCacheDir() {
+    std::string dir = Platform::getAppDataDir();
+    return dir.empty() ? "" : (std::filesystem::path(dir) / "lospec").string();
+}
+
+// Cache files hold one palette per line: slug, title, author and the colors
+// as comma-separated hex, tab-separated. palettes.txt has every palette
+// downloaded; search-<key>.txt the results of one search
+static std::vector<PaletteInfo> readCache(const std::string& name) {
+    std::vector<PaletteInfo> palettes;
+    std::ifstream file(std::filesystem::path(getCacheDir()) / name);
+    std::string line;
+    while (std::getline(file, line)) {
+        std::istringstream fields(line);
+        PaletteInfo palette;
+        std::string colors;
+        if (!std::getline(fields, palette.slug, '\t') || !std::getline(fields, palette.title, '\t') ||
+            !std::getline(fields, palette.author, '\t') || !std::getline(fields, colors, '\t')) {
+            continue;
+        }
+        std::vector<std::string> hexes;
+        std::istringstream list(colors);
+        std::string hex;
+        while (std::getline(list, hex, ',')) hexes.push_back(hex);
+        palette.colors = parseColors(hexes);
+        if (!palette.colors.empty()) palettes.push_back(palette);
+    }
+    return palettes;
+}
+
+static void writeCache(const std::string& name, const std::vector<PaletteInfo>& palettes) {
+    std::string dir = getCacheDir();
+    if (dir.empty()) return;
+    std::error_code ec;
+    std::filesystem::create_directories(dir, ec);
+
+    // Written to the side and renamed over, so a reader never sees half a file
+    std::filesystem::path path = std::filesystem::path(dir) / name;
+    std::filesystem::path tempPath = path.string() + ".tmp";
+    std::ofstream file(tempPath);
+    for (const auto& palette : palettes) {
+        // Tabs and line breaks would split the line
+        auto clean = [](std::string text) {
+            std::replace_if(text.begin(), text.end(), [](char c) { return c == '\t' || c == '\n' || c == '\r'; }, ' ');
+            return text;
+        };
+        file << clean(palette.slug) << "\t" << clean(palette.title) << "\t" << clean(palette.author) << "\t";
+        for (size_t i = 0; i < palette.colors.size(); ++i) {
+            char hex[8];
+            const cv::Vec3b& color = palette.colors[i];
+            std::snprintf(hex, sizeof(hex), "%02x%02x%02x", color[2], color[1], color[0]);
+            file << (i > 0 ? "," : "") << hex;
+        }
+        file << "\n";
+    }
+    file.close();
+    if (!file) {
+        std::filesystem::remove(tempPath, ec);
+        return;
+    }
+    std::filesystem::rename(tempPath, path, ec);
+}
+
+// Drop the least recently written searches beyond MAX_CACHED_SEARCHES
+static void pruneSearches() {
+    std::error_code ec;
+    std::vector<std::pair<std::filesystem::file_time_type, std::filesystem::path>> searches;
+    for (const auto& entry : std::filesystem::directory_iterator(getCacheDir(), ec)) {
+        std::string name = entry.path().filename().string();
+        if (name.rfind("search-", 0) != 0) continue;
+        searches.emplace_back(entry.last_write_time(ec), entry.path());
+    }
+    if (searches.size() <= MAX_CACHED_SEARCHES) return;
+    std::sort(searches.begin(), searches.end(), [](const auto& a, const auto& b) { return a.first > b.first; });
+    for (size_t i = MAX_CACHED_SEARCHES; i < searches.size(); ++i) {
+        std::filesystem::remove(searches[i].second, ec);
+    }
+}
+
+static PaletteInfo readPalette(const Json& json) {
+    PaletteInfo palette;
+    palette.slug = getText(json, "slug");
+    palette.title = getText(json, "title").empty() ? getText(json, "name") : getText(json, "title");
+    const Json* user = json.find("user");
+    std::string userName = user ? getText(*user, "name") : "";
+    palette.author = userName.empty() ? getText(json, "author") : userName;
+
+    const Json* colors = json.find("colorsArray");
+    if (!colors || colors->items.empty()) colors = json.find("colors");
+    std::vector<std::string> hexes;
+    if (colors) {
+        for (const auto& item : colors->items) hexes.push_back(item.string);
+    }
+    palette.colors = parseColors(hexes);
+    return palette;
+}
+
+// Case-insensitive substring match for offline searches
+static bool matches(const PaletteInfo& palette, const std::string& query) {
+    auto lower = [](std::string text) {
+        std::transform(text.begin(), text.end(), text.begin(), [](unsigned char c) { return std::tolower(c); });
+        return text;
+    };
+    std::string needle = lower(query);
+    return lower(palette.title).find(needle) != std::string::npos || palette.slug.find(needle) != std::string::npos;
+}
+
+bool searchPalettes(const std::string& query, int colorCount, int page, std::vector<PaletteInfo>& results,
+                    bool* offline, Errors::Error* error) {
+    std::string url = "https://lospec.com/palette-list/load?colorNumberFilterType=" +
+                      std::string(colorCount > 0 ? "exact" : "any") + "&colorNumber=" +
+                      std::to_string(std::max(colorCount, 1)) + "&page=" + std::to_string(std::max(page, 0)) +
+                      "&tag=&sortingType=default&title=" + urlEncode(query);
+
+    // Cached under a hash of the URL (FNV-1a), which holds every search term
+    uint64_t hash = 14695981039346656037ULL;
+    for (unsigned char c : url) {
+        hash ^= c;
+        hash *= 1099511628211ULL;
+    }
+    char key[32];
+    std::snprintf(key, sizeof(key), "search-%016llx.txt", static_cast<unsigned long long>(hash));
+
+    Json json;
+    std::string body = fetch(url);
+    if (!body.empty() && parseJson(body, json)) {
+        results.clear();
+        const Json* palettes = json.find("palettes");
+        for (const auto& item : palettes ? palettes->items : std::vector<Json>()) {
+            PaletteInfo palette = readPalette(item);
+            if (!palette.slug.empty() && !palette.colors.empty()) results.push_back(palette);
+        }
+        std::lock_guard<std::mutex> lock(cacheMutex);
+        writeCache(key, results);
+        pruneSearches();
+        if (offline) *offline = false;
+        return true;
+    }
+
+    if (offline) *offline = true;
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    if (std::filesystem::exists(std::filesystem::path(getCacheDir()) / key)) {
+        results = readCache(key);
+        return true;
+    }
+    results.clear();
+    int skip = std::max(page, 0) * PAGE_SIZE;
+    for (const auto& palette : readCache("palettes.txt")) {
+        if (!matches(palette, query)) continue;
+        if (colorCount > 0 && static_cast<int>(palette.colors.size()) != colorCount) continue;
+        if (skip-- > 0) continue;
+        results.push_back(palette);
+        if (static_cast<int>(results.size()) == PAGE_SIZE) break;
+    }
+    if (results.empty() && page == 0) {
+        if (error) *error = {ErrorCode::IO, "Could not reach lospec.com, and no downloaded palette matches"};
+        return false;
+    }
+    return true;
+}
+
+void rememberPalette(const PaletteInfo& palette) {
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    std::vector<PaletteInfo> cached = readCache("palettes.txt");
+    cached.erase(std::remove_if(cached.begin(), cached.end(),
+                                [&](const PaletteInfo& item) { return item.slug == palette.slug; }),
+                 cached.end());
+    cached.insert(cached.begin(), palette);
+    if (cached.size() > MAX_CACHED_PALETTES) cached.resize(MAX_CACHED_PALETTES);
+    writeCache("palettes.txt", cached);
+}
+
+bool downloadPalette(const std::string& slug, PaletteInfo& palette, bool* offline, Errors::Error* error) {
+    if (slug.empty() || slug.find_first_not_of("abcdefghijklmnopqrstuvwxyz0123456789-_") != std::string::npos) {
+        if (error) *error = {ErrorCode::INVALID_ARGUMENT, "Invalid palette name: \"" + slug + "\""};
+        return false;
+    }
+
+    Json json;
+    std::string body = fetch("https://lospec.com/palette-list/" + slug + ".json");
+    if (!body.empty() && parseJson(body, json)) {
+        PaletteInfo downloaded = readPalette(json);
+        downloaded.slug = slug;
+        if (!downloaded.colors.empty()) {
+            palette = downloaded;
+            rememberPalette(palette);
+            if (offline) *offline = false;
+            return true;
+        }
+    }
+
+    if (offline) *offline = true;
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    std::vector<PaletteInfo> cached = readCache("palettes.txt");
+    auto known = std::find_if(cached.begin(), cached.end(), [&](const PaletteInfo& item) { return item.slug == slug; });
+    if (known != cached.end()) {
+        palette = *known;
+        return true;
+    }
+    if (error) *error = {ErrorCode::NOT_FOUND, "Could not download palette \"" + slug + "\" from lospec.com"};
+    return false;
+}
+
+} // namespace Lospec
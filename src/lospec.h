@@ -0,0 +1,42 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "errors.h"
+
+namespace Lospec {
+
+// A community palette from lospec.com
+struct PaletteInfo {
+    std::string slug;               // Id in Lospec URLs, e.g. "pico-8"
+    std::string title;
+    std::string author;             // Empty for palettes found offline
+    std::vector<cv::Vec3b> colors;  // BGR
+};
+
+// Palettes per page of search results, as Lospec sends them
+const int PAGE_SIZE = 10;
+
+// Search Lospec's palette list by title, most liked first. colorCount limits
+// the results to palettes of exactly that many colors (0 = any); page counts
+// from 0. Results are cached, and without a connection the cache answers
+// instead: the same search made before, else the downloaded palettes whose
+// name matches. offline tells which happened
+bool searchPalettes(const std::string& query, int colorCount, int page, std::vector<PaletteInfo>& results,
+                    bool* offline = nullptr, Errors::Error* error = nullptr);
+
+// Download one palette by slug and keep it in the cache, so it also loads
+// without a connection later
+bool downloadPalette(const std::string& slug, PaletteInfo& palette, bool* offline = nullptr,
+                     Errors::Error* error = nullptr);
+
+// Add a palette (from search results) to the cache of downloaded palettes,
+// so it can be found offline. The cache keeps the 500 most recent
+void rememberPalette(const PaletteInfo& palette);
+
+// Folder of the cache: "lospec" in the app data directory
+std::string getCacheDir();
+
+} // namespace Lospec
@@ -5,6 +5,13 @@
 #include <atomic>
 #include <chrono>
 #include <algorithm>
+#include <cfloat>
+#include <cstdio>
+#include <filesystem>
+#include <map>
+#include <memory>
+#include <mutex>
+#include <sstream>
 
 #include <opencv2/opencv.hpp>
 #include <opencv2/videoio.hpp>
@@ -15,32 +22,190 @@
 #include <GLFW/glfw3.h>
 #include <GL/gl.h>
 
+#include "ascii.h"
+#include "automation.h"
+#include "batch.h"
+#include "compare.h"
+#include "descreen.h"
+#include "devices.h"
 #include "dithering.h"
+#include "embedded.h"
+#include "errors.h"
+#include "halftone.h"
+#include "imageio.h"
+#include "lospec.h"
+#include "matrices.h"
+#include "palettefile.h"
 #include "platform.h"
+#include "plotter.h"
+#include "plugins.h"
+#include "poster.h"
+#include "queue.h"
+#include "recent.h"
+#include "settings.h"
+#include "stats.h"
+#include "tiles.h"
+#include "transform.h"
+#include "video.h"
+#include "wallpaper.h"
+
+// A preview render running on a worker thread
+struct DitherJob {
+    std::thread thread;
+    std::atomic<bool> cancel{false};
+    std::atomic<bool> finished{false};
+    std::atomic<float> progress{0.0f};
+    std::mutex stageMutex;
+    std::string stage = "Starting";
+    bool proxy = false;             // Rendered with the fast preview proxy
+    bool downscaled = false;        // Rendered from a downscaled copy of the image
+    cv::Mat result;                 // Empty if cancelled
+    float milliseconds = 0.0f;
+    float autoThreshold = -1.0f;    // Threshold Otsu's method picked, if it was asked for
+
+    // Previous preview with finished bands painted over it (empty if not streaming)
+    std::mutex partialMutex;
+    cv::Mat partial;
+    std::atomic<bool> partialChanged{false};
+};
+
+// Live webcam capture, dithered frame by frame on a worker thread
+struct WebcamSession {
+    std::thread thread;
+    std::atomic<bool> stop{false};
+    std::atomic<bool> failed{false};    // The camera couldn't be opened or stopped sending frames
+    std::mutex mutex;                   // Guards everything below
+    Dithering::Parameters params;       // Current settings, updated from the UI every frame
+    cv::Mat mask;                       // Dither mask, also from the UI, stretched over the frame
+    float previewBudgetMP = 4.0f;       // Above this size, slow algorithms use their preview proxy
+    cv::Mat source;                     // Latest frame, scaled down
+    cv::Mat result;                     // And dithered
+    bool proxy = false;                 // The result came from the preview proxy
+    bool frameChanged = false;
+    float fps = 0.0f;                   // Dithered frames per second, smoothed
+};
+
+// A Lospec palette search running on a worker thread, so the UI doesn't
+// wait for the network
+struct LospecSearch {
+    std::thread thread;
+    std::atomic<bool> finished{false};
+    std::vector<Lospec::PaletteInfo> results;
+    bool found = false;
+    bool offline = false;
+    Errors::Error error;
+};
+
+// New thumbnails for the recent files, made on a worker thread since loading
+// big images to shrink them would stall the UI
+struct ThumbnailUpdate {
+    std::thread thread;
+    std::atomic<bool> finished{false};
+    std::vector<RecentFiles::RecentFile> files;
+};
+
+// An image downloaded from an http(s) URL on a worker thread, so the UI
+// keeps drawing (with the progress) while it arrives
+struct UrlDownload {
+    std::thread thread;
+    std::atomic<bool> finished{false};
+    std::atomic<size_t> received{0};
+    std::atomic<size_t> total{0};       // 0 if the server didn't say
+    std::string url;
+    bool loaded = false;
+    cv::Mat img, alpha, precise;
+    std::vector<ImageIO::Warning> warnings;
+};
+
+// ffmpeg and ffprobe run for their versions on a worker thread, since the
+// menu would otherwise wait for both
+struct FfmpegCheck {
+    std::thread thread;
+    std::atomic<bool> finished{false};
+    std::vector<Platform::ToolInfo> tools;
+};
+
+// One step of the settings history. Only parameters are kept; restoring a
+// step dithers the original again
+struct HistoryEntry {
+    Dithering::Parameters params;
+    uint64_t hash = 0;                  // hashParameters(params)
+    std::string label;                  // What changed from the step before
+};
+
+// A line a queued export leaves for its window, written from the queue's thread
+struct ExportSummary {
+    std::mutex mutex;
+    std::string text;
+
+    void set(const std::string& value) {
+        std::lock_guard<std::mutex> lock(mutex);
+        text = value;
+    }
+
+    std::string get() {
+        std::lock_guard<std::mutex> lock(mutex);
+        return text;
+    }
+};
+
+// A descreen run on the job queue. pollDescreen applies it if the image it
+// started from is still the loaded one
+struct DescreenResult {
+    std::atomic<bool> finished{false};
+    cv::Mat source;                 // originalImage when the job was queued
+    cv::Mat image, precise;
+    Descreen::Method method = Descreen::Method::FREQUENCY;
+    float period = 0.0f;
+};
 
 // Application state
 struct AppState {
     cv::Mat originalImage;
+    cv::Mat originalAlpha;          // Empty if the image is opaque
+    cv::Mat preciseImage;           // CV_32FC3 color of 16-bit/HDR sources, used for saving
     cv::Mat processedImage;
     cv::Mat displayImage;
     GLuint originalTexture = 0;
     GLuint processedTexture = 0;
+    Tiles::Pyramid previewPyramid;  // Of what processedTexture shows
+    int previewLevel = 0;           // Pyramid level uploaded to processedTexture
 
     Dithering::Parameters params;
 
     std::string currentFile;
+    char urlInput[1024] = "";
+    std::unique_ptr<UrlDownload> download;  // Image being downloaded from a URL
+    char patternMatrixInput[1024] = "";
+    char kernelInput[1024] = "[[1,0,7],[-1,1,3],[0,1,5],[1,1,1]]";
+    std::string kernelError;            // Why the typed kernel was rejected
+    std::string noiseTextureError;      // Why the chosen noise texture couldn't be loaded
+    std::vector<ImageIO::Warning> loadWarnings;
+    std::string loadError;          // Why the last image failed to open
     bool imageLoaded = false;
     bool processing = false;
+    std::unique_ptr<DitherJob> job;     // Preview render in progress
+    bool restartPending = false;        // Settings changed while the job was running
     bool autoUpdate = true;
+    bool recommendedOnSwitch = true;    // Reset settings that don't suit a newly chosen algorithm
 
     // Video state
     cv::VideoCapture videoCapture;
     cv::VideoWriter videoWriter;
     bool isVideo = false;
-    bool videoProcessing = false;
-    int currentFrame = 0;
-    int totalFrames = 0;
-    float videoProgress = 0.0f;
+    std::string videoFile;              // Dropped video, dithered by Export Video
+    Video::Options videoOptions;
+
+    // Live webcam preview; while it runs, each frame replaces the image and
+    // settings changes show up on the next frame
+    std::unique_ptr<WebcamSession> webcam;
+    int webcamDevice = 0;
+    int webcamWidth = 640;          // Frames are scaled down to this width before dithering
+
+    // Where ffmpeg and ffprobe are run from (empty = not checked yet)
+    std::vector<Platform::ToolInfo> ffmpegTools;
+    std::unique_ptr<FfmpegCheck> ffmpegCheck;       // Check in progress
+    char ffmpegPath[1024] = "";
 
     // UI state
     int selectedAlgorithm = 0;
@@ -49,23 +214,365 @@ struct AppState {
     bool showOriginal = true;
     bool showProcessed = true;
     bool splitView = true;
+    bool showUsageStats = false;
+    UsageStats::Stats usageStats;       // As last read for the Usage Statistics window
+    int usageStatsRevision = -1;        // UsageStats::getRevision() when read, -1 to read again
+
+    // Magnifier over the preview: the same crop of the original and the result
+    bool magnifier = false;
+    int magnifierZoom = 4;
+    GLuint magnifierTextures[2] = {0, 0};   // Original, dithered
+    cv::Mat magnifierCrops[2];              // What the textures hold
+    cv::Vec3i magnifierShown{-1, -1, 0};    // Pixel and zoom of the crops
+    const uchar* magnifierSources[2] = {nullptr, nullptr};  // Images the crops came from
+
+    // Comparison against an external image (e.g. another tool's output)
+    cv::Mat referenceImage;
+    std::string referenceFile;
+    cv::Point2d referenceOffset;    // Alignment of the reference with the source, in source pixels
+    cv::Mat alignedSource;          // The source and reference it was found for; held so their
+    cv::Mat alignedReference;       // buffers can't be reused by other images
+    Compare::Result comparison;
+    bool comparisonValid = false;
+    bool showComparison = false;
+    bool showReferenceDiff = true;  // Show the heat map rather than the aligned reference
+    GLuint comparisonTexture = 0;
+
+    // Objective quality of the result, re-measured while the window is open
+    Compare::Quality quality;                // Against the original
+    Compare::Quality referenceQuality;       // Against the reference, if one is loaded
+    Compare::ColorReport colorReport;        // Colors the result uses
+    bool qualityValid = false;
+    bool showQuality = false;
+
+    // Set as Wallpaper, dithered on the job queue at the screen's resolution
+    Wallpaper::Options wallpaperOptions;
+
+    // Export Dithering Progression settings
+    Video::ProgressionOptions progressionOptions;
+
+    // Export Montage: the current settings and chosen presets on one sheet
+    std::vector<std::string> montagePresetList;     // Saved presets, read when the window opens
+    std::vector<std::string> montagePresets;        // Chosen, in the order they were ticked
+    bool montageCurrent = true;                     // The current settings as the first panel
+    Compare::MontageOptions montageOptions;
+    bool showMontage = false;
+
+    // Background exports, run one after another
+    Queue::JobQueue queue;
+    bool showQueue = false;
+
+    // Poster PDF export
+    bool showPosterExport = false;
+    bool ditherAlpha = false;       // Save transparency dithered to 1-bit
+    int gifTransparentIndex = -1;   // GIF palette index for transparent pixels (-1 = any)
+    ImageIO::EncodeOptions encodeOptions;
+    ImageIO::Metadata sourceMetadata;   // EXIF and ICC profile of the loaded file, copied into saves
+    bool animatedGif = false;           // Loaded file is an animated GIF (the preview shows its first frame)
+    uintmax_t savedFileSize = 0;    // Size of the last file saved with Save As (0 = none yet)
+    Poster::Options posterOptions;
+
+    // One-page print PDF export
+    bool showPrintExport = false;
+    bool printVector = false;       // Halftone dots as vector shapes
+    Poster::PrintOptions printOptions;
+
+    // ASCII art export, previewed in its window
+    bool showAsciiExport = false;
+    Ascii::Options asciiOptions;
+    char asciiRamp[64] = " .:-=+*#%@";
+    std::vector<std::string> asciiLines;
+    cv::Mat asciiRendered;          // Braille preview; the UI font has no braille glyphs
+    GLuint asciiTexture = 0;
+    int ansiColumns = 80;           // ANSI export width in characters (0 = one per pixel)
+
+    // Pen plotter export
+    bool showPlotterExport = false;
+    Plotter::Options plotterOptions;
+    std::shared_ptr<ExportSummary> plotterSummary = std::make_shared<ExportSummary>();  // Dots and travel
+
+    // Embedded display export
+    bool showEmbeddedExport = false;
+    Embedded::Options embeddedOptions;
+    char embeddedName[64] = "";
+    std::shared_ptr<ExportSummary> embeddedSummary = std::make_shared<ExportSummary>();    // Packed size
+    std::shared_ptr<DescreenResult> descreen;   // Running or finished descreen, if any
+
+    // Saving a named preset
+    bool showSavePreset = false;
+    char presetName[64] = "";
+    std::string presetError;
+
+    // Crop and resize dialogs
+    bool showCrop = false;
+    int cropRect[4] = {0, 0, 0, 0};             // x, y, width, height
+
+    // Only dither where the mask is set (empty = everywhere)
+    cv::Mat ditherMask;
+    bool showMaskRegion = false;
+    int maskRect[4] = {0, 0, 0, 0};             // x, y, width, height
+    bool showResize = false;
+    int resizeSize[2] = {0, 0};                 // width, height
+    bool resizeKeepAspect = true;
+    int resizeFilter = static_cast<int>(Transform::Filter::AREA);
 
     // Performance
     float processingTime = 0.0f;
+    float previewBudgetMP = 4.0f;   // Above this size, slow algorithms preview with a fast proxy
+    int previewMaxDimension = 0;    // Preview from a copy no larger than this (0 = full size)
+    int memoryLimitMB = 4096;       // Larger jobs are dithered in bands
+    bool previewIsProxy = false;    // processedImage came from the proxy, not the chosen algorithm
+    bool previewIsDownscaled = false;   // processedImage is smaller than the image; export renders full size
+
+    // Scopes of the dithered preview
+    std::vector<float> levelsHistogram;     // Luma distribution, 64 bins
+    GLuint waveformTexture = 0;             // Luma (vertical) per image column (horizontal)
+    float clippedBlack = 0.0f;              // % of pixels at pure black
+    float clippedWhite = 0.0f;              // % of pixels at pure white
+    float inkDensity = 0.0f;                // Average darkness, in %
+
+    // Histogram of the source, for picking thresholds and color counts
+    std::array<std::vector<float>, 4> sourceHistogram;  // Luma, R, G, B; 64 bins
+    bool histogramAdjusted = true;          // After brightness, contrast, etc.
+    bool showHistogram = false;
+
+    int curveDragPoint = -1;                // Tone curve point being dragged
+
+    // Every algorithm on a small copy of the image, to pick one by eye
+    std::vector<Dithering::AlgorithmThumbnail> explorerThumbnails;
+    std::vector<GLuint> explorerTextures;
+    bool showExplorer = false;
+
+    // Settings history for undo and redo, for the whole session
+    std::vector<HistoryEntry> history;
+    size_t historyIndex = 0;                // Step matching the current settings
+    bool showHistory = false;
+
+    // Files opened before, with their cached thumbnails (same order)
+    std::vector<RecentFiles::RecentFile> recentFiles;
+    std::vector<GLuint> recentTextures;
+    std::vector<cv::Size> recentSizes;
+    std::unique_ptr<ThumbnailUpdate> thumbnailUpdate;   // Stale thumbnails being remade
+
+    // Local HTTP API for other programs; requests are logged from its thread
+    std::mutex serverLogMutex;
+    std::vector<std::string> serverLog;     // Last requests, newest last
+    Automation::Server server;
+    int serverPort = Automation::DEFAULT_PORT;
+    std::string serverError;
+    bool showServer = false;
+
+    // Lospec palette browser
+    std::unique_ptr<LospecSearch> lospecSearch;     // Search in progress
+    char lospecQuery[128] = "";
+    int lospecColors = 0;                           // Exact color count, 0 = any
+    int lospecPage = 0;
+    std::vector<Lospec::PaletteInfo> lospecResults;
+    bool lospecOffline = false;                     // Results came from the cache
+    std::string lospecError;
+    bool showLospec = false;
+
+    // Text rendered as the image
+    char textInput[1024] = "";
+    char textFont[256] = "";
+    ImageIO::TextOptions textOptions;
+    bool showRenderText = false;
 };
 
-// Helper function to load texture from cv::Mat
-GLuint loadTextureFromMat(const cv::Mat& mat) {
-    if (mat.empty()) return 0;
-
-    cv::Mat rgba;
-    if (mat.channels() == 3) {
-        cv::cvtColor(mat, rgba, cv::COLOR_BGR2RGBA);
-    } else if (mat.channels() == 4) {
-        rgba = mat;
+// Search Lospec for one page in the background; pollLospecSearch picks up
+// the results. The page only changes if no search is already running
+void startLospecSearch(AppState& state, int page) {
+    if (state.lospecSearch) return;
+    state.lospecPage = page;
+    auto search = std::make_unique<LospecSearch>();
+    LospecSearch* running = search.get();
+    std::string query = state.lospecQuery;
+    int colors = state.lospecColors;
+    search->thread = std::thread([running, query, colors, page]() {
+        running->found = Lospec::searchPalettes(query, colors, page, running->results, &running->offline,
+                                                &running->error);
+        running->finished = true;
+    });
+    state.lospecSearch = std::move(search);
+}
+
+// Called every frame while the browser is open
+void pollLospecSearch(AppState& state) {
+    if (!state.lospecSearch || !state.lospecSearch->finished) return;
+    state.lospecSearch->thread.join();
+    state.lospecResults = state.lospecSearch->results;
+    state.lospecOffline = state.lospecSearch->offline;
+    state.lospecError = state.lospecSearch->found ? "" : state.lospecSearch->error.message;
+    state.lospecSearch.reset();
+}
+
+// Check ffmpeg and ffprobe in the background, unless that's already running
+void startFfmpegCheck(AppState& state) {
+    if (state.ffmpegCheck) return;
+    auto check = std::make_unique<FfmpegCheck>();
+    FfmpegCheck* running = check.get();
+    check->thread = std::thread([running]() {
+        running->tools = {Platform::checkFfmpegTool("ffmpeg"), Platform::checkFfmpegTool("ffprobe")};
+        running->finished = true;
+    });
+    state.ffmpegCheck = std::move(check);
+}
+
+// Called every frame while the FFmpeg menu is open
+void pollFfmpegCheck(AppState& state) {
+    if (!state.ffmpegCheck || !state.ffmpegCheck->finished) return;
+    state.ffmpegCheck->thread.join();
+    state.ffmpegTools = state.ffmpegCheck->tools;
+    state.ffmpegCheck.reset();
+}
+
+// Grows the string behind an InputText as the user types
+int resizeStringCallback(ImGuiInputTextCallbackData* data) {
+    if (data->EventFlag == ImGuiInputTextFlags_CallbackResize) {
+        auto* text = static_cast<std::string*>(data->UserData);
+        text->resize(data->BufTextLen);
+        data->Buf = text->data();
+    }
+    return 0;
+}
+
+// Multi-line text box editing a std::string, with no length limit
+bool inputTextMultiline(const char* label, std::string& text, const ImVec2& size) {
+    return ImGui::InputTextMultiline(label, text.data(), text.capacity() + 1, size,
+                                     ImGuiInputTextFlags_CallbackResize, resizeStringCallback, &text);
+}
+
+// Color picker for a BGR color
+bool colorEditBGR(const char* label, cv::Vec3b& color) {
+    float rgb[3] = {color[2] / 255.0f, color[1] / 255.0f, color[0] / 255.0f};
+    if (!ImGui::ColorEdit3(label, rgb, ImGuiColorEditFlags_NoInputs)) return false;
+
+    color = cv::Vec3b(
+        static_cast<uchar>(rgb[2] * 255.0f + 0.5f),
+        static_cast<uchar>(rgb[1] * 255.0f + 0.5f),
+        static_cast<uchar>(rgb[0] * 255.0f + 0.5f)
+    );
+    return true;
+}
+
+// Square tone curve editor: click to add a point, drag to move one, right
+// click to remove one. An empty curve is edited from the straight line
+bool editToneCurve(std::vector<cv::Point2f>& points, int& dragging) {
+    float size = std::min(ImGui::GetContentRegionAvail().x, 240.0f);
+    ImVec2 origin = ImGui::GetCursorScreenPos();
+    ImGui::InvisibleButton("##ToneCurve", ImVec2(size, size));
+    bool hovered = ImGui::IsItemHovered();
+    bool changed = false;
+
+    std::vector<cv::Point2f> edited = points.empty() ? std::vector<cv::Point2f>{{0.0f, 0.0f}, {1.0f, 1.0f}} : points;
+    auto toScreen = [&](float x, float y) { return ImVec2(origin.x + x * size, origin.y + (1.0f - y) * size); };
+    ImVec2 mouse = ImGui::GetIO().MousePos;
+    float mouseX = std::clamp((mouse.x - origin.x) / size, 0.0f, 1.0f);
+    float mouseY = std::clamp(1.0f - (mouse.y - origin.y) / size, 0.0f, 1.0f);
+    int nearest = -1;
+    for (size_t i = 0; i < edited.size(); ++i) {
+        ImVec2 at = toScreen(edited[i].x, edited[i].y);
+        if (std::abs(at.x - mouse.x) < 6.0f && std::abs(at.y - mouse.y) < 6.0f) nearest = static_cast<int>(i);
+    }
+
+    if (hovered && ImGui::IsMouseClicked(0)) {
+        dragging = nearest;
+        if (dragging < 0 && static_cast<int>(edited.size()) < Dithering::MAX_CURVE_POINTS) {
+            auto at = std::upper_bound(edited.begin(), edited.end(), mouseX,
+                                       [](float x, const cv::Point2f& point) { return x < point.x; });
+            bool taken = (at != edited.end() && at->x == mouseX) || (at != edited.begin() && (at - 1)->x == mouseX);
+            if (!taken) {
+                dragging = static_cast<int>(at - edited.begin());
+                edited.insert(at, cv::Point2f(mouseX, mouseY));
+                changed = true;
+            }
+        }
+    }
+    if (dragging >= 0 && dragging < static_cast<int>(edited.size()) && ImGui::IsItemActive()) {
+        // Points keep their order and can't meet
+        float low = dragging > 0 ? edited[dragging - 1].x + 0.01f : 0.0f;
+        float high = dragging + 1 < static_cast<int>(edited.size()) ? edited[dragging + 1].x - 0.01f : 1.0f;
+        cv::Point2f moved(std::clamp(mouseX, low, std::max(low, high)), mouseY);
+        if (moved != edited[dragging]) {
+            edited[dragging] = moved;
+            changed = true;
+        }
+    } else {
+        dragging = -1;
+    }
+    if (hovered && ImGui::IsMouseClicked(1) && nearest >= 0 && edited.size() > 2) {
+        edited.erase(edited.begin() + nearest);
+        changed = true;
+    }
+
+    ImDrawList* draw = ImGui::GetWindowDrawList();
+    draw->AddRectFilled(origin, ImVec2(origin.x + size, origin.y + size), IM_COL32(30, 30, 30, 255));
+    const ImU32 grid = IM_COL32(60, 60, 60, 255);
+    for (int i = 1; i < 4; ++i) {
+        float at = i * size / 4.0f;
+        draw->AddLine(ImVec2(origin.x + at, origin.y), ImVec2(origin.x + at, origin.y + size), grid);
+        draw->AddLine(ImVec2(origin.x, origin.y + at), ImVec2(origin.x + size, origin.y + at), grid);
+    }
+    const int segments = 64;
+    for (int i = 0; i < segments; ++i) {
+        float x0 = static_cast<float>(i) / segments, x1 = static_cast<float>(i + 1) / segments;
+        draw->AddLine(toScreen(x0, Dithering::evaluateToneCurve(edited, x0)),
+                      toScreen(x1, Dithering::evaluateToneCurve(edited, x1)), IM_COL32(230, 230, 230, 255), 1.5f);
+    }
+    for (size_t i = 0; i < edited.size(); ++i) {
+        bool active = static_cast<int>(i) == dragging || static_cast<int>(i) == nearest;
+        draw->AddCircleFilled(toScreen(edited[i].x, edited[i].y), 4.0f,
+                              active ? IM_COL32(255, 200, 60, 255) : IM_COL32(230, 230, 230, 255));
+    }
+
+    if (changed) points = edited;
+    return changed;
+}
+
+// Copy a cv::Mat into the bound texture, allocating its storage if asked.
+// Pixels go to GL in OpenCV's own BGR/BGRA layout and row stride, so a preview
+// upload is a single copy with no intermediate RGBA image
+static void uploadMat(const cv::Mat& mat, bool allocate) {
+    cv::Mat pixels = mat;
+    if (mat.channels() == 1) {
+        cv::cvtColor(mat, pixels, cv::COLOR_GRAY2BGR);
+    }
+    GLenum format = pixels.channels() == 4 ? GL_BGRA : GL_BGR;
+
+    glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+    glPixelStorei(GL_UNPACK_ROW_LENGTH, static_cast<GLint>(pixels.step / pixels.elemSize()));
+    if (allocate) {
+        glTexImage2D(GL_TEXTURE_2D, 0, GL_RGBA, pixels.cols, pixels.rows, 0, format, GL_UNSIGNED_BYTE, pixels.data);
     } else {
-        cv::cvtColor(mat, rgba, cv::COLOR_GRAY2RGBA);
+        glTexSubImage2D(GL_TEXTURE_2D, 0, 0, 0, pixels.cols, pixels.rows, format, GL_UNSIGNED_BYTE, pixels.data);
     }
+    glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+    glPixelStorei(GL_UNPACK_ALIGNMENT, 4);
+}
+
+// Images beyond the driver's texture size limit are shrunk for display only; the
+// preview is drawn at the image's own aspect ratio, so the smaller texture just
+// gets stretched back. Saving always uses the full-resolution cv::Mat
+static cv::Mat fitTextureLimit(const cv::Mat& mat) {
+    static GLint maxSize = 0;
+    if (maxSize == 0) {
+        glGetIntegerv(GL_MAX_TEXTURE_SIZE, &maxSize);
+        if (maxSize <= 0) maxSize = 4096;
+    }
+    int largest = std::max(mat.cols, mat.rows);
+    if (largest <= maxSize) return mat;
+
+    double scale = static_cast<double>(maxSize) / largest;
+    cv::Size size(std::max(1, static_cast<int>(mat.cols * scale)), std::max(1, static_cast<int>(mat.rows * scale)));
+    cv::Mat shrunk;
+    cv::resize(mat, shrunk, size, 0, 0, cv::INTER_AREA);
+    return shrunk;
+}
+
+// Helper function to load texture from cv::Mat
+GLuint loadTextureFromMat(const cv::Mat& image) {
+    if (image.empty()) return 0;
+    cv::Mat mat = fitTextureLimit(image);
 
     GLuint texture;
     glGenTextures(1, &texture);
@@ -76,217 +583,2015 @@ GLuint loadTextureFromMat(const cv::Mat& mat) {
     glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE);
     glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE);
 
-    glTexImage2D(GL_TEXTURE_2D, 0, GL_RGBA, rgba.cols, rgba.rows, 0, GL_RGBA, GL_UNSIGNED_BYTE, rgba.data);
+    uploadMat(mat, true);
 
     return texture;
 }
 
-// Update texture with new mat
-void updateTexture(GLuint& texture, const cv::Mat& mat) {
+// Update texture with new mat. A same-sized image overwrites the existing
+// storage in place instead of reallocating the texture on every preview
+void updateTexture(GLuint& texture, const cv::Mat& image) {
+    cv::Mat mat = image.empty() ? image : fitTextureLimit(image);
+    if (texture != 0 && !mat.empty()) {
+        GLint width = 0, height = 0;
+        glBindTexture(GL_TEXTURE_2D, texture);
+        glGetTexLevelParameteriv(GL_TEXTURE_2D, 0, GL_TEXTURE_WIDTH, &width);
+        glGetTexLevelParameteriv(GL_TEXTURE_2D, 0, GL_TEXTURE_HEIGHT, &height);
+        if (width == mat.cols && height == mat.rows) {
+            uploadMat(mat, false);
+            return;
+        }
+    }
     if (texture != 0) {
         glDeleteTextures(1, &texture);
     }
     texture = loadTextureFromMat(mat);
 }
 
-// Process image with current parameters
-void processImage(AppState& state) {
-    if (!state.imageLoaded || state.originalImage.empty()) return;
-
-    state.processing = true;
-    auto start = std::chrono::high_resolution_clock::now();
-
-    state.processedImage = Dithering::ditherImage(state.originalImage, state.params);
-    updateTexture(state.processedTexture, state.processedImage);
+// Show image in the dithered preview. A zoomed-out preview is drawn from an
+// area-averaged pyramid level, so it shows the tone rather than moire
+void updatePreviewTexture(AppState& state, const cv::Mat& image) {
+    state.previewPyramid = Tiles::Pyramid(image);
+    state.previewLevel = std::min(state.previewLevel, std::max(0, state.previewPyramid.getLevelCount() - 1));
+    updateTexture(state.processedTexture, state.previewPyramid.getLevel(state.previewLevel));
+}
 
-    auto end = std::chrono::high_resolution_clock::now();
-    state.processingTime = std::chrono::duration<float, std::milli>(end - start).count();
-    state.processing = false;
+// Pick the pyramid level for a preview drawn at scale times its size
+void selectPreviewLevel(AppState& state, double scale) {
+    int level = state.previewPyramid.getLevelForScale(scale);
+    if (level == state.previewLevel) return;
+    state.previewLevel = level;
+    updateTexture(state.processedTexture, state.previewPyramid.getLevel(level));
 }
 
-// Load image file
-bool loadImage(AppState& state, const std::string& filename) {
-    std::cout << "Loading image: " << filename << std::endl;
-    cv::Mat img = cv::imread(filename, cv::IMREAD_COLOR);
-    if (img.empty()) {
-        std::cerr << "Error: Could not load image: " << filename << std::endl;
-        return false;
+// Levels histogram, waveform and clipping of the dithered preview. Works on a
+// small nearest-neighbour copy, so it stays cheap next to the dithering itself
+void updatePreviewScopes(AppState& state) {
+    const int histogramBins = 64;
+    const int waveformWidth = 256;
+    const int waveformHeight = 64;
+
+    cv::Mat gray;
+    cv::cvtColor(state.processedImage, gray, cv::COLOR_BGR2GRAY);
+    if (gray.cols > waveformWidth) {
+        int height = std::max(1, gray.rows * waveformWidth / gray.cols);
+        cv::resize(gray, gray, cv::Size(waveformWidth, height), 0, 0, cv::INTER_NEAREST);
     }
 
-    state.originalImage = img;
-    state.currentFile = filename;
-    state.imageLoaded = true;
-    state.isVideo = false;
+    std::vector<float> histogram(histogramBins, 0.0f);
+    cv::Mat counts = cv::Mat::zeros(waveformHeight, gray.cols, CV_32F);
+    int black = 0, white = 0;
+    double lumaSum = 0.0;
 
-    updateTexture(state.originalTexture, state.originalImage);
-    processImage(state);
+    for (int y = 0; y < gray.rows; ++y) {
+        for (int x = 0; x < gray.cols; ++x) {
+            uchar value = gray.at<uchar>(y, x);
+            histogram[value * histogramBins / 256] += 1.0f;
+            counts.at<float>(waveformHeight - 1 - value * waveformHeight / 256, x) += 1.0f;
+            if (value == 0) black++;
+            if (value == 255) white++;
+            lumaSum += value;
+        }
+    }
 
-    std::cout << "Image loaded successfully: " << img.cols << "x" << img.rows << std::endl;
-    return true;
+    float total = static_cast<float>(gray.total());
+    state.levelsHistogram = histogram;
+    state.clippedBlack = 100.0f * black / total;
+    state.clippedWhite = 100.0f * white / total;
+    state.inkDensity = 100.0f * (1.0f - static_cast<float>(lumaSum / total) / 255.0f);
+
+    // Brightness of each waveform cell shows how many pixels of that column sit at that level
+    cv::Mat waveform;
+    counts.convertTo(waveform, CV_8U, 255.0 * 4.0 / std::max(1, gray.rows));
+    cv::cvtColor(waveform, waveform, cv::COLOR_GRAY2BGR);
+    updateTexture(state.waveformTexture, waveform);
 }
 
+// Re-measure the reference against the current result. Aligning it is the
+// slow part and doesn't depend on the settings, so it's done once per source
+// and reference
+void updateComparison(AppState& state) {
+    state.comparisonValid = false;
+    if (state.referenceImage.empty() || state.originalImage.empty() || state.processedImage.empty()) return;
 
-// Drag and drop callback
-void dropCallback(GLFWwindow* window, int count, const char** paths) {
-    AppState* state = static_cast<AppState*>(glfwGetWindowUserPointer(window));
-    if (count > 0 && state) {
-        std::string filepath = paths[0];
-        std::cout << "File dropped: " << filepath << std::endl;
+    if (state.alignedSource.data != state.originalImage.data ||
+        state.alignedReference.data != state.referenceImage.data) {
+        state.referenceOffset = Compare::alignReference(state.originalImage, state.referenceImage);
+        state.alignedSource = state.originalImage;
+        state.alignedReference = state.referenceImage;
+    }
+    cv::Point2d offset(state.referenceOffset.x * state.processedImage.cols / state.originalImage.cols,
+                       state.referenceOffset.y * state.processedImage.rows / state.originalImage.rows);
+    state.comparisonValid = Compare::compareImages(state.processedImage, state.referenceImage, state.comparison,
+                                                   &offset);
+    if (state.comparisonValid) {
+        updateTexture(state.comparisonTexture,
+                      state.showReferenceDiff ? state.comparison.diff : state.comparison.aligned);
+    }
+}
 
-        // Check if it's a video or image
-        std::string ext = filepath.substr(filepath.find_last_of(".") + 1);
-        std::transform(ext.begin(), ext.end(), ext.begin(), ::tolower);
+// Histogram of the source as the dithering sees it. Like the preview scopes it
+// samples a small nearest-neighbour copy, and only while the window is open
+void updateSourceHistogram(AppState& state) {
+    if (!state.showHistogram || state.originalImage.empty()) return;
+    const int bins = 64;
+    const int sampleWidth = 512;
 
-        if (ext == "mp4" || ext == "avi" || ext == "mov" || ext == "mkv") {
-            std::cout << "Video file detected. Use CLI for video processing." << std::endl;
-            state->isVideo = true;
-        } else {
-            loadImage(*state, filepath);
+    cv::Mat sample = state.originalImage;
+    if (sample.cols > sampleWidth) {
+        int height = std::max(1, sample.rows * sampleWidth / sample.cols);
+        cv::resize(sample, sample, cv::Size(sampleWidth, height), 0, 0, cv::INTER_NEAREST);
+    }
+    if (state.histogramAdjusted) sample = Dithering::preprocessImage(sample, state.params);
+
+    Dithering::Histogram histogram = Dithering::computeHistogram(sample);
+    const std::vector<int>* sources[4] = {&histogram.luma, &histogram.channels[2],
+                                          &histogram.channels[1], &histogram.channels[0]};
+    for (int i = 0; i < 4; ++i) {
+        state.sourceHistogram[i].assign(bins, 0.0f);
+        for (int level = 0; level < 256; ++level) {
+            state.sourceHistogram[i][level * bins / 256] += static_cast<float>((*sources[i])[level]);
         }
     }
 }
 
-// Save image file
-bool saveImage(AppState& state, const std::string& filename) {
-    if (state.processedImage.empty()) return false;
-    return cv::imwrite(filename, state.processedImage);
+// Measure the result against the original (and the reference). CIEDE2000 over
+// every pixel isn't free, so this only runs while the Quality window is open
+void updateQuality(AppState& state) {
+    state.qualityValid = state.showQuality && !state.processedImage.empty() &&
+        Compare::measureQuality(state.processedImage, state.originalImage, state.quality);
+    if (state.qualityValid && !state.referenceImage.empty()) {
+        Compare::measureQuality(state.processedImage, state.referenceImage, state.referenceQuality);
+    }
+    if (state.qualityValid) {
+        Compare::countOutputColors(state.processedImage, state.params, state.colorReport, state.ditherMask);
+    }
 }
 
+// Convert the image for the ASCII art window. Only a few thousand blocks are
+// dithered, so this runs on the UI thread, while the window is open
+void updateAsciiPreview(AppState& state) {
+    if (!state.showAsciiExport || !state.imageLoaded) return;
+    const cv::Mat& source = state.preciseImage.empty() ? state.originalImage : state.preciseImage;
+    state.asciiLines = Ascii::convertToText(source, state.params, state.asciiOptions);
+    if (state.asciiOptions.braille) {
+        state.asciiRendered = Ascii::renderText(state.asciiLines, state.asciiOptions, state.params.inkColor,
+                                                state.params.paperColor);
+        updateTexture(state.asciiTexture, state.asciiRendered);
+    }
+}
 
-// Process video
-void processVideo(AppState& state, const std::string& inputPath, const std::string& outputPath) {
-    state.videoProcessing = true;
-    state.videoProgress = 0.0f;
+// Process image with current parameters. Dithering runs on a worker thread so
+// the UI stays responsive; pollProcessing() picks up the result. A change while
+// a job is running cancels it and starts over with the new settings
+void processImage(AppState& state) {
+    if (!state.imageLoaded || state.originalImage.empty()) return;
+    if (state.webcam) return;       // The capture thread dithers each frame with the current settings
 
-    cv::VideoCapture cap(inputPath);
-    if (!cap.isOpened()) {
-        state.videoProcessing = false;
+    if (state.job) {
+        state.job->cancel = true;
+        state.restartPending = true;
         return;
     }
 
-    int frameWidth = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_WIDTH));
-    int frameHeight = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_HEIGHT));
-    double fps = cap.get(cv::CAP_PROP_FPS);
-    state.totalFrames = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_COUNT));
+    // Optionally preview from a downscaled copy so scrubbing sliders stays
+    // interactive; export always dithers the full-size image
+    cv::Mat source = state.originalImage;
+    bool downscaled = state.previewMaxDimension > 0 &&
+                      std::max(source.cols, source.rows) > state.previewMaxDimension;
+    if (downscaled) {
+        cv::Size limit = source.cols >= source.rows ? cv::Size(state.previewMaxDimension, 0)
+                                                    : cv::Size(0, state.previewMaxDimension);
+        source = Transform::resizeImage(source, Transform::fitSize(source.size(), limit), Transform::Filter::AREA);
+    }
 
-    cv::VideoWriter writer(outputPath, cv::VideoWriter::fourcc('m', 'p', '4', 'v'),
-                          fps, cv::Size(frameWidth, frameHeight));
+    // Large images preview with a fast proxy; the chosen algorithm still runs on export
+    double megapixels = source.total() / 1e6;
+    Dithering::Parameters previewParams = state.params;
+    bool proxy = megapixels > state.previewBudgetMP && Dithering::isSlowAlgorithm(state.params.algorithm);
 
-    if (!writer.isOpened()) {
-        state.videoProcessing = false;
-        return;
+    if (proxy) {
+        previewParams.algorithm = Dithering::getPreviewProxy(state.params.algorithm);
+    }
+
+    // The worker gets its own copies; cv::Mat shares the pixels, which are never
+    // modified in place (transforms replace the image)
+    auto job = std::make_unique<DitherJob>();
+    job->proxy = proxy;
+    job->downscaled = downscaled;
+    // Error diffusion paints its finished bands over the previous preview while
+    // it runs, as long as that preview has the same size
+    if (state.processedImage.size() == source.size() && state.processedImage.type() == CV_8UC3) {
+        job->partial = state.processedImage.clone();
     }
+    DitherJob* running = job.get();
+    size_t memoryLimit = static_cast<size_t>(state.memoryLimitMB) << 20;
+    job->thread = std::thread([running, source, previewParams, memoryLimit]() mutable {
+        // Found from the preview copy here rather than on the UI thread; the
+        // slider snaps to it when the job finishes
+        if (previewParams.autoThreshold) {
+            previewParams.threshold = Dithering::computeAutoThreshold(source, previewParams);
+            previewParams.autoThreshold = false;
+            running->autoThreshold = previewParams.threshold;
+        }
+
+        Dithering::BandCallback onBand;
+        if (!running->partial.empty()) {
+            onBand = [running](const cv::Mat& rows, int y) {
+                std::lock_guard<std::mutex> lock(running->partialMutex);
+                rows.copyTo(running->partial.rowRange(y, y + rows.rows));
+                running->partialChanged = true;
+            };
+        }
 
-    cv::Mat frame;
-    state.currentFrame = 0;
+        auto start = std::chrono::high_resolution_clock::now();
+        running->result = Dithering::ditherWithinMemory(source, previewParams, memoryLimit,
+            [running](float fraction, const std::string& stage) {
+                running->progress = fraction;
+                std::lock_guard<std::mutex> lock(running->stageMutex);
+                running->stage = stage;
+                return !running->cancel;
+            }, onBand);
+        auto end = std::chrono::high_resolution_clock::now();
+        running->milliseconds = std::chrono::duration<float, std::milli>(end - start).count();
+        running->finished = true;
+    });
 
-    while (cap.read(frame)) {
-        cv::Mat dithered = Dithering::ditherImage(frame, state.params);
-        writer.write(dithered);
+    state.job = std::move(job);
+    state.processing = true;
+}
 
-        state.currentFrame++;
-        state.videoProgress = static_cast<float>(state.currentFrame) / state.totalFrames;
+// Take the finished job's result (unless it was cancelled), then start the next
+// one if settings changed in the meantime. Blocks until the job is done
+void finishProcessing(AppState& state) {
+    if (!state.job) return;
+
+    state.job->thread.join();
+    if (!state.job->cancel && !state.job->result.empty()) {
+        state.processedImage = Dithering::applyMask(state.job->result, state.originalImage, state.ditherMask);
+        state.previewIsProxy = state.job->proxy;
+        state.previewIsDownscaled = state.job->downscaled;
+        state.processingTime = state.job->milliseconds;
+        if (state.params.autoThreshold && state.job->autoThreshold >= 0.0f) {
+            state.params.threshold = state.job->autoThreshold;
+        }
+        updatePreviewTexture(state, state.processedImage);
+        updatePreviewScopes(state);
+        updateComparison(state);
+        updateQuality(state);
+        updateAsciiPreview(state);
+        updateSourceHistogram(state);
+    } else if (!state.job->partial.empty()) {
+        updatePreviewTexture(state, state.processedImage);    // Drop the painted bands
     }
+    state.job.reset();
+    state.processing = false;
 
-    cap.release();
-    writer.release();
-    state.videoProcessing = false;
+    if (state.restartPending) {
+        state.restartPending = false;
+        processImage(state);
+    }
 }
 
-// Main GUI rendering
-void renderGUI(AppState& state) {
-    ImGuiIO& io = ImGui::GetIO();
+// Called every frame from the main loop
+void pollProcessing(AppState& state) {
+    if (state.job && state.job->finished) {
+        finishProcessing(state);
+    } else if (state.job && state.job->partialChanged) {
+        std::lock_guard<std::mutex> lock(state.job->partialMutex);
+        state.job->partialChanged = false;
+        updatePreviewTexture(state, state.job->partial.clone());
+    }
+}
 
-    // Main menu bar
-    if (ImGui::BeginMainMenuBar()) {
-        if (ImGui::BeginMenu("File")) {
-            if (ImGui::MenuItem("Open Image", "Ctrl+O")) {
-                std::string filepath = Platform::openFileDialog();
-                if (!filepath.empty()) {
-                    loadImage(state, filepath);
-                }
-            }
-            if (ImGui::MenuItem("Open Video")) {
-                std::cout << "Video processing available via CLI: ./dithers-boyfriend-cli" << std::endl;
+// Wait until the preview reflects the current settings (before saving/exporting it)
+void waitForProcessing(AppState& state) {
+    while (state.job) {
+        finishProcessing(state);
+    }
+}
+
+// Forget protected colors the new palette no longer has, so they don't come
+// back protected if the color is added again later. Adaptive palettes aren't
+// known until the image is dithered
+void pruneProtectedColors(AppState& state) {
+    if (state.params.paletteMode == Dithering::PaletteMode::ADAPTIVE) return;
+    std::vector<cv::Vec3b> palette = Dithering::getPalette(state.params);
+    std::vector<cv::Vec3b>& protectedColors = state.params.protectedColors;
+    protectedColors.erase(std::remove_if(protectedColors.begin(), protectedColors.end(),
+                                         [&](const cv::Vec3b& color) {
+                                             return std::find(palette.begin(), palette.end(), color) == palette.end();
+                                         }),
+                          protectedColors.end());
+}
+
+// Switch to another algorithm from the dropdown or the explorer
+void selectAlgorithm(AppState& state, Dithering::Algorithm algorithm) {
+    if (state.recommendedOnSwitch) {
+        state.params = Dithering::getRecommendedParameters(state.params, algorithm);
+    } else {
+        state.params.algorithm = algorithm;
+        state.params.plugin.clear();
+    }
+    if (Dithering::getBayerSize(algorithm) > 0) state.params.bayerSize = Dithering::getBayerSize(algorithm);
+    state.selectedAlgorithm = static_cast<int>(algorithm);
+    if (state.autoUpdate) processImage(state);
+}
+
+// Render the algorithm explorer's thumbnails with the current settings. Small
+// enough to run on the UI thread
+void renderExplorer(AppState& state) {
+    if (!state.imageLoaded) return;
+    state.explorerThumbnails = Dithering::renderAlgorithmThumbnails(state.originalImage, state.params, 160);
+    state.explorerTextures.resize(state.explorerThumbnails.size(), 0);
+    for (size_t i = 0; i < state.explorerThumbnails.size(); ++i) {
+        updateTexture(state.explorerTextures[i], state.explorerThumbnails[i].image);
+    }
+}
+
+// Open the camera and dither its frames at a reduced size until stopped. The
+// frames are dithered as still images: live settings matter more than
+// temporal coherence here
+void startWebcam(AppState& state) {
+    if (state.webcam) return;
+    if (state.job) {
+        state.job->cancel = true;
+        state.restartPending = false;
+        finishProcessing(state);
+    }
+
+    auto session = std::make_unique<WebcamSession>();
+    session->params = state.params;
+    session->mask = state.ditherMask;
+    session->previewBudgetMP = state.previewBudgetMP;
+    WebcamSession* running = session.get();
+    int device = state.webcamDevice;
+    int width = state.webcamWidth;
+    session->thread = std::thread([running, device, width]() {
+        cv::VideoCapture capture(device);
+        if (!capture.isOpened()) {
+            running->failed = true;
+            return;
+        }
+        cv::Mat frame;
+        auto last = std::chrono::high_resolution_clock::now();
+        while (!running->stop) {
+            if (!capture.read(frame) || frame.empty()) {
+                running->failed = true;
+                return;
             }
-            ImGui::Separator();
-            if (ImGui::MenuItem("Save As...", "Ctrl+S")) {
-                if (state.imageLoaded && !state.processedImage.empty()) {
-                    std::string filepath = Platform::saveFileDialog();
-                    if (!filepath.empty()) {
-                        if (saveImage(state, filepath)) {
-                            std::cout << "Saved to " << filepath << std::endl;
-                        } else {
-                            std::cerr << "Failed to save image" << std::endl;
-                        }
-                    }
-                }
+            cv::Mat source = frame;
+            if (frame.cols > width) {
+                source = Transform::resizeImage(frame, Transform::fitSize(frame.size(), cv::Size(width, 0)),
+                                                Transform::Filter::AREA);
             }
-            if (ImGui::MenuItem("Export Video")) {
-                std::cout << "Video export available via CLI: ./dithers-boyfriend-cli" << std::endl;
+            Dithering::Parameters params;
+            cv::Mat mask;
+            float budget;
+            {
+                std::lock_guard<std::mutex> lock(running->mutex);
+                params = running->params;
+                mask = running->mask;
+                budget = running->previewBudgetMP;
             }
-            ImGui::Separator();
-            if (ImGui::MenuItem("Exit", "Alt+F4")) {
-                glfwSetWindowShouldClose(glfwGetCurrentContext(), true);
+            // The same preview shortcuts as processImage, and the mask as finishProcessing applies it
+            bool proxy = source.total() / 1e6 > budget && Dithering::isSlowAlgorithm(params.algorithm);
+            if (proxy) params.algorithm = Dithering::getPreviewProxy(params.algorithm);
+            cv::Mat result = Dithering::applyMask(Dithering::ditherImage(source, params), source, mask);
+
+            auto now = std::chrono::high_resolution_clock::now();
+            float seconds = std::chrono::duration<float>(now - last).count();
+            last = now;
+            std::lock_guard<std::mutex> lock(running->mutex);
+            running->source = source.clone();
+            running->result = result;
+            running->proxy = proxy;
+            running->frameChanged = true;
+            if (seconds > 0.0f) {
+                running->fps = running->fps > 0.0f ? running->fps * 0.9f + 0.1f / seconds : 1.0f / seconds;
             }
-            ImGui::EndMenu();
         }
+    });
 
-        if (ImGui::BeginMenu("View")) {
-            ImGui::MenuItem("Split View", nullptr, &state.splitView);
-            ImGui::MenuItem("Show Original", nullptr, &state.showOriginal);
-            ImGui::MenuItem("Show Processed", nullptr, &state.showProcessed);
-            ImGui::EndMenu();
-        }
+    state.webcam = std::move(session);
+    std::cerr << "Starting webcam " << device << std::endl;
+}
 
-        if (ImGui::BeginMenu("Help")) {
-            if (ImGui::MenuItem("About")) {
-                std::cout << "Dither's Boyfriend - Advanced Dithering Application" << std::endl;
-            }
-            ImGui::EndMenu();
+// Stop the capture; the last frame stays loaded, so it can be saved or tweaked
+void stopWebcam(AppState& state) {
+    if (!state.webcam) return;
+    state.webcam->stop = true;
+    state.webcam->thread.join();
+    state.webcam.reset();
+}
+
+// Called every frame from the main loop: hands the settings to the capture
+// thread and shows its latest frame
+void pollWebcam(AppState& state) {
+    if (!state.webcam) return;
+    if (state.webcam->failed) {
+        std::cerr << "Error: Could not read from webcam " << state.webcamDevice << std::endl;
+        stopWebcam(state);
+        return;
+    }
+
+    std::lock_guard<std::mutex> lock(state.webcam->mutex);
+    state.webcam->params = state.params;
+    state.webcam->mask = state.ditherMask;
+    state.webcam->previewBudgetMP = state.previewBudgetMP;
+    if (!state.webcam->frameChanged) return;
+    state.webcam->frameChanged = false;
+
+    state.originalImage = state.webcam->source;
+    state.originalAlpha = cv::Mat();
+    state.preciseImage = cv::Mat();
+    state.processedImage = state.webcam->result;
+    state.previewIsProxy = state.webcam->proxy;
+    state.previewIsDownscaled = false;
+    if (!state.imageLoaded || state.currentFile != "Webcam") {
+        // A mask drawn for the previous image is kept, stretched to the frame
+        if (!state.ditherMask.empty() && state.ditherMask.size() != state.originalImage.size()) {
+            cv::resize(state.ditherMask, state.ditherMask, state.originalImage.size(), 0, 0, cv::INTER_LINEAR);
         }
+        state.sourceMetadata = ImageIO::Metadata();
+        state.animatedGif = false;
+        state.currentFile = "Webcam";
+        state.imageLoaded = true;
+        state.isVideo = false;
+        state.videoFile.clear();
+    }
+    updateTexture(state.originalTexture, state.originalImage);
+    updatePreviewTexture(state, state.processedImage);
+}
 
-        ImGui::EndMainMenuBar();
+// Make a decoded image the current one and dither it
+void setLoadedImage(AppState& state, const std::string& name, const cv::Mat& img, const cv::Mat& alpha,
+                    const cv::Mat& precise, const std::vector<ImageIO::Warning>& warnings) {
+    stopWebcam(state);
+    for (const auto& warning : warnings) {
+        std::cerr << "Warning: " << warning.message << std::endl;
     }
+    state.loadWarnings = warnings;
 
-    // Left panel - Controls
-    ImGui::SetNextWindowPos(ImVec2(0, 20));
-    ImGui::SetNextWindowSize(ImVec2(400, io.DisplaySize.y - 20));
-    ImGui::Begin("Controls", nullptr, ImGuiWindowFlags_NoMove | ImGuiWindowFlags_NoResize);
+    state.originalImage = img;
+    state.originalAlpha = alpha;
+    state.preciseImage = precise;
+    state.ditherMask = cv::Mat();
+    state.sourceMetadata = ImageIO::Metadata();
+    state.animatedGif = false;
+    state.savedFileSize = 0;
+    state.currentFile = name;
+    state.imageLoaded = true;
+    state.isVideo = false;
+    state.videoFile.clear();
 
-    ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.9f, 0.9f, 1.0f, 1.0f));
-    ImGui::TextWrapped("Dither's Boyfriend");
-    ImGui::PopStyleColor();
-    ImGui::Separator();
+    updateTexture(state.originalTexture, state.originalImage);
+    processImage(state);
+    if (state.showExplorer) {
+        renderExplorer(state);
+    } else {
+        state.explorerThumbnails.clear();
+    }
 
-    // Algorithm selection
-    ImGui::Text("Dithering Algorithm");
-    const char* algorithms[] = {
-        "Floyd-Steinberg", "Atkinson", "Jarvis-Judice-Ninke", "Stucki",
-        "Burkes", "Sierra", "Sierra Two-Row", "Sierra Lite",
-        "Ordered Bayer 2x2", "Ordered Bayer 4x4", "Ordered Bayer 8x8", "Ordered Bayer 16x16",
-        "Blue Noise", "White Noise", "Random", "Pattern",
-        "Dot Diffusion", "Riemersma", "Gradient-Based", "Variable Error",
-        "Ostromoukhov", "Fan", "Shiau-Fan", "Steven Pigeon"
-    };
+    std::cout << "Image loaded successfully: " << img.cols << "x" << img.rows << std::endl;
+}
 
-    if (ImGui::Combo("##Algorithm", &state.selectedAlgorithm, algorithms, IM_ARRAYSIZE(algorithms))) {
-        state.params.algorithm = static_cast<Dithering::Algorithm>(state.selectedAlgorithm);
-        if (state.autoUpdate) processImage(state);
+// Sync the UI with settings that were just loaded and dither with them
+void settingsLoaded(AppState& state, const std::vector<std::string>& warnings) {
+    for (const auto& warning : warnings) {
+        std::cerr << "Warning: " << warning << std::endl;
     }
+    state.selectedAlgorithm = static_cast<int>(state.params.algorithm);
+    state.selectedPalette = static_cast<int>(state.params.paletteMode);
+    processImage(state);
+}
 
-    ImGui::Separator();
+// Steps kept in the settings history; the oldest are dropped
+const size_t MAX_HISTORY_STEPS = 200;
 
-    // Palette selection
-    ImGui::Text("Color Palette");
-    const char* palettes[] = {
-        "Monochrome", "Grayscale 4", "Grayscale 8", "Grayscale 16",
-        "CGA", "EGA", "VGA", "Game Boy", "PICO-8", "Custom"
-    };
+// Add the current settings to the history once they differ from the current
+// step. Nothing is recorded while a control is held, so a slider drag is one step
+void recordHistory(AppState& state) {
+    if (ImGui::IsAnyItemActive()) return;
+    uint64_t hash = Dithering::hashParameters(state.params);
+    if (!state.history.empty() && state.history[state.historyIndex].hash == hash) return;
 
-    if (ImGui::Combo("##Palette", &state.selectedPalette, palettes, IM_ARRAYSIZE(palettes))) {
-        state.params.paletteMode = static_cast<Dithering::PaletteMode>(state.selectedPalette);
-        if (state.autoUpdate) processImage(state);
+    std::string label = "Initial settings";
+    if (!state.history.empty()) {
+        std::vector<std::string> changed =
+            Settings::getChangedFields(state.history[state.historyIndex].params, state.params);
+        label.clear();
+        for (size_t i = 0; i < changed.size() && i < 3; ++i) label += (i > 0 ? ", " : "") + changed[i];
+        if (changed.size() > 3) label += " and " + std::to_string(changed.size() - 3) + " more";
+        if (label.empty()) label = "Settings";
+        // A new change drops the steps that were undone
+        state.history.resize(state.historyIndex + 1);
+    }
+    state.history.push_back({state.params, hash, label});
+    if (state.history.size() > MAX_HISTORY_STEPS) state.history.erase(state.history.begin());
+    state.historyIndex = state.history.size() - 1;
+}
+
+// Go back or forward to a step of the history
+void restoreHistory(AppState& state, size_t index) {
+    if (index >= state.history.size() || index == state.historyIndex) return;
+    state.historyIndex = index;
+    state.params = state.history[index].params;
+    settingsLoaded(state, {});
+}
+
+void undo(AppState& state) {
+    if (state.historyIndex > 0) restoreHistory(state, state.historyIndex - 1);
+}
+
+void redo(AppState& state) {
+    restoreHistory(state, state.historyIndex + 1);
+}
+
+// Upload the thumbnails of state.recentFiles
+void uploadRecentThumbnails(AppState& state) {
+    for (GLuint& texture : state.recentTextures) {
+        if (texture) glDeleteTextures(1, &texture);
+    }
+    state.recentTextures.assign(state.recentFiles.size(), 0);
+    state.recentSizes.assign(state.recentFiles.size(), cv::Size());
+    for (size_t i = 0; i < state.recentFiles.size(); ++i) {
+        const std::string& thumbnail = state.recentFiles[i].thumbnail;
+        cv::Mat image = thumbnail.empty() ? cv::Mat() : cv::imread(thumbnail, cv::IMREAD_COLOR);
+        if (image.empty()) continue;
+        updateTexture(state.recentTextures[i], image);
+        state.recentSizes[i] = image.size();
+    }
+}
+
+// Read the recent files list and upload the thumbnails it has; stale ones are
+// remade in the background and show up once pollThumbnailUpdate sees them
+void refreshRecentFiles(AppState& state) {
+    state.recentFiles = RecentFiles::getRecentFiles(false);
+    uploadRecentThumbnails(state);
+
+    bool stale = std::any_of(state.recentFiles.begin(), state.recentFiles.end(),
+                             [](const RecentFiles::RecentFile& recent) { return recent.stale; });
+    if (!stale || state.thumbnailUpdate) return;
+    auto update = std::make_unique<ThumbnailUpdate>();
+    update->files = state.recentFiles;
+    ThumbnailUpdate* running = update.get();
+    update->thread = std::thread([running]() {
+        RecentFiles::updateThumbnails(running->files);
+        running->finished = true;
+    });
+    state.thumbnailUpdate = std::move(update);
+}
+
+// Called every frame. The list is read again rather than taken from the
+// update, in case a file was opened meanwhile; a thumbnail that couldn't be
+// made stays stale until the next refresh, so it isn't retried every frame
+void pollThumbnailUpdate(AppState& state) {
+    if (!state.thumbnailUpdate || !state.thumbnailUpdate->finished) return;
+    state.thumbnailUpdate->thread.join();
+    state.thumbnailUpdate.reset();
+    state.recentFiles = RecentFiles::getRecentFiles(false);
+    uploadRecentThumbnails(state);
+}
+
+void clearRecentFiles(AppState& state) {
+    if (!RecentFiles::clearRecentFiles()) {
+        std::cerr << "Error: Could not clear recent files" << std::endl;
+    }
+    refreshRecentFiles(state);
+}
+
+// Download and decode an image URL in the background; pollDownload shows it
+// once it's there
+void startDownload(AppState& state, const std::string& url) {
+    auto download = std::make_unique<UrlDownload>();
+    UrlDownload* running = download.get();
+    running->url = url;
+    download->thread = std::thread([running]() {
+        running->loaded = ImageIO::loadImageFromUrl(running->url, running->img, running->alpha, &running->warnings,
+                                                    ImageIO::DEFAULT_MAX_DOWNLOAD_BYTES,
+                                                    [running](size_t received, size_t total) {
+                                                        running->received = received;
+                                                        running->total = total;
+                                                    }, &running->precise);
+        running->finished = true;
+    });
+    state.download = std::move(download);
+}
+
+// Called every frame
+void pollDownload(AppState& state) {
+    if (!state.download || !state.download->finished) return;
+    state.download->thread.join();
+    std::unique_ptr<UrlDownload> download = std::move(state.download);
+    if (!download->loaded) {
+        state.loadError = "Could not download the image";
+        std::cerr << "Error: " << state.loadError << ": " << download->url << std::endl;
+        return;
+    }
+    state.loadError.clear();
+    setLoadedImage(state, download->url, download->img, download->alpha, download->precise, download->warnings);
+}
+
+// Load image file. URLs are downloaded in the background, so for them true
+// only means the download started
+bool loadImage(AppState& state, const std::string& filename) {
+    std::cout << "Loading image: " << filename << std::endl;
+    if (Platform::isRemoteUrl(filename)) {
+        if (state.download) {
+            std::cerr << "Error: Another image is still downloading" << std::endl;
+            return false;
+        }
+        startDownload(state, filename);
+        return true;
+    }
+
+    // Any extension is fine: ImageIO probes the contents, so formats OpenCV
+    // reads beyond the usual ones (.pbm, .hdr, .exr, ...) still open
+    Platform::PathCheck check = Platform::validateInputFile(filename, {});
+    if (!check.ok()) {
+        state.loadError = check.message;
+        std::cerr << "Error: " << check.message << std::endl;
+        return false;
+    }
+
+    cv::Mat img, alpha, precise;
+    std::vector<ImageIO::Warning> warnings;
+    if (!ImageIO::loadImage(filename, img, alpha, &warnings, &precise)) {
+        state.loadError = ImageIO::describeLoadFailure(filename).message;
+        std::cerr << "Error: " << state.loadError << ": " << filename << std::endl;
+        return false;
+    }
+    state.loadError.clear();
+    setLoadedImage(state, filename, img, alpha, precise, warnings);
+    ImageIO::readMetadata(filename, state.sourceMetadata);
+    state.animatedGif = ImageIO::isAnimatedGif(filename);
+    RecentFiles::recordFile(filename, img);
+    refreshRecentFiles(state);
+    return true;
+}
+
+// Paste an image from the clipboard. Copied text works too when it's a data
+// URL, an image URL or a path to an image file
+bool pasteImage(AppState& state) {
+    std::vector<unsigned char> data;
+    if (!Platform::readClipboardImage(data)) {
+        const char* text = ImGui::GetClipboardText();
+        std::string pasted = text ? text : "";
+        pasted.erase(pasted.find_last_not_of(" \t\r\n") + 1);
+        if (pasted.empty()) {
+            std::cerr << "Error: The clipboard holds no image" << std::endl;
+            return false;
+        }
+        if (!ImageIO::decodeDataUrl(pasted, data)) {
+            return loadImage(state, pasted);
+        }
+    }
+
+    cv::Mat img, alpha, precise;
+    std::vector<ImageIO::Warning> warnings;
+    if (!ImageIO::loadImageFromMemory(data, img, alpha, &warnings, &precise)) {
+        std::cerr << "Error: Could not decode the pasted image" << std::endl;
+        return false;
+    }
+    setLoadedImage(state, "pasted.png", img, alpha, precise, warnings);
+    return true;
+}
+
+
+// Drag and drop callback
+void dropCallback(GLFWwindow* window, int count, const char** paths) {
+    AppState* state = static_cast<AppState*>(glfwGetWindowUserPointer(window));
+    if (count > 0 && state) {
+        std::string filepath = paths[0];
+        std::cout << "File dropped: " << filepath << std::endl;
+
+        // Check if it's a video or image
+        if (Video::isVideoFile(filepath)) {
+            std::cout << "Video file loaded, export it with File > Export Video" << std::endl;
+            state->isVideo = true;
+            state->videoFile = filepath;
+        } else {
+            loadImage(*state, filepath);
+        }
+    }
+}
+
+// Alpha to save with the result, filling in the matching export options.
+// Transparency passes through unchanged unless it's dithered to 1-bit
+cv::Mat prepareExport(const cv::Mat& originalAlpha, const Dithering::Parameters& params, bool ditherAlpha,
+                      ImageIO::ExportOptions& exportOptions) {
+    cv::Mat alpha = originalAlpha;
+    if (ditherAlpha && !alpha.empty()) {
+        alpha = Dithering::ditherAlpha(alpha, params);
+        exportOptions.binaryAlpha = true;
+    }
+    if (params.colorMode == Dithering::ColorMode::PIXEL_FORMAT &&
+        params.pixelFormat == Dithering::PixelFormat::RGBA4444) {
+        exportOptions.alphaLevels = 16;
+    }
+    return alpha;
+}
+
+// What a full-resolution render of the current image and settings needs,
+// copied so it can run on the job queue while editing goes on
+struct FullRender {
+    cv::Mat preview;                // The preview, when it already is the full render
+    cv::Mat source;                 // Full precision for high bit depth sources
+    cv::Mat mask;
+    Dithering::Parameters params;
+    size_t memoryLimit = 0;
+};
+
+FullRender snapshotRender(AppState& state) {
+    waitForProcessing(state);
+    FullRender render;
+    render.source = state.preciseImage.empty() ? state.originalImage : state.preciseImage;
+    render.mask = state.ditherMask;
+    render.params = state.params;
+    render.memoryLimit = static_cast<size_t>(state.memoryLimitMB) << 20;
+    // The preview is dithered from the 8-bit image, and may be downscaled or
+    // use a proxy algorithm; otherwise it's what a render would give
+    if (!state.previewIsProxy && !state.previewIsDownscaled && state.preciseImage.empty()) {
+        render.preview = state.processedImage.clone();
+    }
+    return render;
+}
+
+// Dither at full size with the real algorithm, within the memory limit, and
+// apply the dither mask. Empty if cancelled
+cv::Mat renderFullResolution(const FullRender& render, const Dithering::ProgressCallback& progress = nullptr) {
+    if (!render.preview.empty()) return render.preview;
+    std::cout << "Rendering with " << Dithering::getAlgorithmName(render.params.algorithm) << "..." << std::endl;
+    cv::Mat output = Dithering::ditherWithinMemory(render.source, render.params, render.memoryLimit, progress);
+    if (output.empty()) return output;
+    return Dithering::applyMask(output, render.source, render.mask);
+}
+
+// Save image file; fileSize receives the size of the written file
+bool saveImage(AppState& state, const std::string& filename, uintmax_t* fileSize = nullptr) {
+    FullRender render = snapshotRender(state);
+    if (state.processedImage.empty()) return false;
+
+    ImageIO::ExportOptions exportOptions;
+    exportOptions.gifTransparentIndex = state.gifTransparentIndex;
+    exportOptions.encode = state.encodeOptions;
+    exportOptions.metadata = state.sourceMetadata;
+    cv::Mat alpha = prepareExport(state.originalAlpha, state.params, state.ditherAlpha, exportOptions);
+
+    float seconds = state.processingTime / 1000.0f;
+    auto start = std::chrono::high_resolution_clock::now();
+    cv::Mat output = renderFullResolution(render);
+    if (render.preview.empty()) {
+        seconds = std::chrono::duration<float>(std::chrono::high_resolution_clock::now() - start).count();
+    }
+    bool saved = ImageIO::saveImage(filename, output, alpha, exportOptions, fileSize);
+
+    if (saved) {
+        UsageStats::recordJob(Dithering::getAlgorithmName(state.params.algorithm),
+                              state.originalImage.cols, state.originalImage.rows, seconds, false);
+    }
+    return saved;
+}
+
+// Render the current image and settings to a file on the job queue. The job
+// keeps its own copy of both, so editing can go on while it waits and runs
+void queueExport(AppState& state, const std::string& filename) {
+    if (!state.imageLoaded || state.originalImage.empty()) return;
+
+    FullRender render = snapshotRender(state);
+    Dithering::Parameters params = state.params;
+    cv::Mat originalAlpha = state.originalAlpha;
+    bool ditherAlpha = state.ditherAlpha;
+    // Made now, so the color management settings are the ones at queue time
+    ImageIO::ExportOptions queuedOptions;
+    queuedOptions.gifTransparentIndex = state.gifTransparentIndex;
+    queuedOptions.encode = state.encodeOptions;
+    queuedOptions.metadata = state.sourceMetadata;
+    std::string name = std::filesystem::path(filename).filename().string();
+
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        auto start = std::chrono::high_resolution_clock::now();
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+
+        if (ImageIO::getExtension(filename) == "dzi") {
+            // Zoomable tiles for results too big to view whole
+            if (!Tiles::exportDeepZoom(output, filename, &error, progress)) return false;
+        } else {
+            ImageIO::ExportOptions exportOptions = queuedOptions;
+            cv::Mat alpha = prepareExport(originalAlpha, params, ditherAlpha, exportOptions);
+            if (!ImageIO::saveImage(filename, output, alpha, exportOptions)) {
+                error = ImageIO::describeSaveFailure(filename);
+                error.message += ": " + filename;
+                return false;
+            }
+        }
+        float seconds = std::chrono::duration<float>(std::chrono::high_resolution_clock::now() - start).count();
+        UsageStats::recordJob(Dithering::getAlgorithmName(params.algorithm), output.cols, output.rows, seconds, false);
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Render at full size and lay it out over several sheets as a PDF, on the job queue
+void queuePoster(AppState& state, const std::string& filename) {
+    FullRender render = snapshotRender(state);
+    Poster::Options options = state.posterOptions;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+        if (!Poster::exportPosterPdf(output, filename, options)) {
+            error = {Errors::ErrorCode::IO, "Could not write " + filename};
+            return false;
+        }
+        std::cout << "Saved poster to " << filename << std::endl;
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Render at full size onto one page of a print PDF, on the job queue. Vector
+// dots are traced from the full precision source
+void queuePrintPdf(AppState& state, const std::string& filename, bool vector) {
+    FullRender render = snapshotRender(state);
+    Poster::PrintOptions options = state.printOptions;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+        Halftone::VectorHalftone halftone;
+        if (vector) halftone = Halftone::traceHalftone(render.source, render.params);
+        if (!Poster::exportPrintPdf(output, filename, options, vector ? &halftone : nullptr)) {
+            error = {Errors::ErrorCode::IO, "Could not write " + filename};
+            return false;
+        }
+        std::cout << "Saved print PDF to " << filename << std::endl;
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Render at full size and write it as ANSI half-block text, on the job queue
+void queueAnsiText(AppState& state, const std::string& filename, const Ascii::AnsiColors& colors) {
+    FullRender render = snapshotRender(state);
+    int columns = state.ansiColumns;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+        if (!Ascii::exportText(Ascii::convertToAnsi(output, columns, colors), filename)) {
+            error = {Errors::ErrorCode::IO, "Could not write " + filename};
+            return false;
+        }
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Render at full size and write its dots for a pen plotter, on the job queue.
+// The plotter window shows how it went
+void queuePlotterDots(AppState& state, const std::string& filename) {
+    FullRender render = snapshotRender(state);
+    Plotter::Options options = state.plotterOptions;
+    std::shared_ptr<ExportSummary> summary = state.plotterSummary;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+        Plotter::Plot plot = Plotter::traceDots(output, render.params, options);
+        if (!Plotter::exportPlot(plot, filename, options)) {
+            error = {Errors::ErrorCode::UNSUPPORTED_FORMAT, "Use a .csv, .json, .gcode, .nc, .hpgl or .plt file"};
+            summary->set("Failed to export: " + error.message);
+            return false;
+        }
+        char text[128];
+        std::snprintf(text, sizeof(text), "%zu dots, %.2f mm pitch, %.1f m pen travel",
+                      plot.dots.size(), plot.pitchMm, Plotter::travelDistance(plot) / 1000.0);
+        summary->set(text);
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Render at full size and pack it for a display as a C array or raw bytes, on
+// the job queue. The display export window shows the packed size
+void queueEmbedded(AppState& state, const std::string& filename) {
+    FullRender render = snapshotRender(state);
+    Embedded::Options options = state.embeddedOptions;
+    std::shared_ptr<ExportSummary> summary = state.embeddedSummary;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat output = renderFullResolution(render, progress);
+        if (output.empty()) return false;
+        size_t byteCount = 0;
+        if (!Embedded::exportEmbedded(output, filename, options, &byteCount)) {
+            error = {Errors::ErrorCode::UNSUPPORTED_FORMAT, "Use a .h, .c or .bin file"};
+            summary->set("Failed to export: " + error.message);
+            return false;
+        }
+        summary->set("Saved " + std::to_string(byteCount) + " bytes");
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Dither the image for the screen and make it the desktop wallpaper, on the job queue
+void queueWallpaper(AppState& state) {
+    if (!state.imageLoaded || state.originalImage.empty()) return;
+
+    cv::Mat source = state.preciseImage.empty() ? state.originalImage : state.preciseImage;
+    Dithering::Parameters params = state.params;
+    Wallpaper::Options options = state.wallpaperOptions;
+    state.queue.enqueue("Wallpaper", [=](const Dithering::ProgressCallback&, Errors::Error& error) {
+        return Wallpaper::setWallpaper(source, params, options, &error);
+    });
+    state.showQueue = true;
+}
+
+// Animate the loaded image being dithered into a GIF, WebP, APNG or video (by
+// extension) on the job queue
+void queueProgression(AppState& state, const std::string& filename) {
+    if (!state.imageLoaded || state.originalImage.empty()) return;
+
+    cv::Mat source = state.originalImage;
+    Dithering::Parameters params = state.params;
+    Video::ProgressionOptions options = state.progressionOptions;
+    options.encoder = state.videoOptions.encoder;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        return Video::exportProgression(source, filename, params, options,
+            [&progress](const Video::Progress& status) {
+                float fraction = status.totalFrames > 0 ? static_cast<float>(status.frame) / status.totalFrames : 0.0f;
+                return progress(std::min(fraction, 1.0f), status.stage);
+            }, &error);
+    });
+    state.showQueue = true;
+}
+
+// Dither the loaded image with the current settings and chosen presets onto one
+// captioned sheet, on the job queue. Presets are read now, so the job keeps
+// the settings they had when it was queued
+void queueMontage(AppState& state, const std::string& filename) {
+    if (!state.imageLoaded || state.originalImage.empty()) return;
+
+    std::vector<Compare::Variant> variants;
+    if (state.montageCurrent) variants.push_back({state.params, ""});
+    for (const auto& name : state.montagePresets) {
+        Compare::Variant variant{Dithering::Parameters(), name};
+        std::string error;
+        if (!Settings::loadPreset(name, variant.params, &error)) {
+            std::cerr << "Error: " << error << std::endl;
+            continue;
+        }
+        variants.push_back(variant);
+    }
+    if (variants.size() < 2) return;
+
+    cv::Mat source = state.originalImage;
+    Compare::MontageOptions options = state.montageOptions;
+    std::string name = std::filesystem::path(filename).filename().string();
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        cv::Mat montage = Compare::renderMontage(source, variants, options, progress);
+        if (montage.empty()) return false;
+        if (!ImageIO::saveImage(filename, montage, cv::Mat(), ImageIO::ExportOptions())) {
+            error = ImageIO::describeSaveFailure(filename);
+            return false;
+        }
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Passes a video or animation job's progress on to its queue entry, with the
+// frame count and time left in the stage
+Video::ProgressCallback reportFrames(const Dithering::ProgressCallback& progress) {
+    return [&progress](const Video::Progress& status) {
+        float fraction = status.totalFrames > 0 ? static_cast<float>(status.frame) / status.totalFrames : 0.0f;
+        std::string stage = status.stage;
+        if (status.etaSeconds >= 0.0) {
+            stage += ": frame " + std::to_string(status.frame) + " of " + std::to_string(status.totalFrames) +
+                     ", " + std::to_string(static_cast<int>(status.etaSeconds + 0.5)) + " s left";
+        }
+        return progress(std::min(fraction, 1.0f), stage);
+    };
+}
+
+// Dither every frame of the loaded animated GIF into a new GIF, WebP or APNG
+// (by extension) on the job queue. Frames come from the file, so transforms
+// of the preview don't apply
+void queueAnimationExport(AppState& state, const std::string& filename) {
+    if (!state.animatedGif) return;
+
+    std::string inputPath = state.currentFile;
+    Dithering::Parameters params = state.params;
+    Video::Options options = state.videoOptions;
+    options.encode = state.encodeOptions;
+    std::string name = std::filesystem::path(filename).filename().string();
+
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        return Video::processAnimation(inputPath, filename, params, options, reportFrames(progress), &error);
+    });
+    state.showQueue = true;
+}
+
+// Put the dithered result on the clipboard, rendered exactly as Save As would
+// write it (PNG, so transparency survives)
+bool copyResult(AppState& state) {
+    std::string tempFile = Platform::createTempFile(".png");
+    if (tempFile.empty()) return false;
+    bool copied = saveImage(state, tempFile) && Platform::copyImageToClipboard(tempFile);
+    std::error_code ec;
+    std::filesystem::remove(tempFile, ec);
+    return copied;
+}
+
+// Apply a transform to the loaded image (color, alpha and full precision color)
+// and dither the result
+template <typename Operation>
+void transformImage(AppState& state, Operation transform) {
+    if (!state.imageLoaded || state.isVideo) return;
+
+    cv::Mat transformed = transform(state.originalImage);
+    if (transformed.empty()) return;
+    state.originalImage = transformed;
+    if (!state.originalAlpha.empty()) state.originalAlpha = transform(state.originalAlpha);
+    if (!state.preciseImage.empty()) state.preciseImage = transform(state.preciseImage);
+    if (!state.ditherMask.empty()) state.ditherMask = transform(state.ditherMask);
+
+    updateTexture(state.originalTexture, state.originalImage);
+    processImage(state);
+}
+
+// Smooth away the dither or halftone screen of the loaded image, e.g. a scanned
+// print, before dithering it again. The FFT of a large scan takes a while, so
+// it runs on the job queue. Alpha and the mask keep their edges
+void queueDescreen(AppState& state, Descreen::Method method) {
+    if (!state.imageLoaded || state.isVideo) return;
+
+    auto result = std::make_shared<DescreenResult>();
+    result->source = state.originalImage;
+    result->method = method;
+    cv::Mat precise = state.preciseImage;
+    state.descreen = result;
+    state.queue.enqueue("Descreen (" + Descreen::getMethodName(method) + ")",
+        [result, precise](const Dithering::ProgressCallback& progress, Errors::Error&) {
+            if (!progress(0.0f, "Descreening")) return false;
+            Descreen::Options options;
+            options.method = result->method;
+            result->image = Descreen::reconstruct(result->source, options, &options.period);
+            if (!precise.empty()) result->precise = Descreen::reconstruct(precise, options);
+            result->period = options.period;
+            result->finished = true;
+            return true;
+        });
+    state.showQueue = true;
+}
+
+// Called every frame
+void pollDescreen(AppState& state) {
+    if (!state.descreen || !state.descreen->finished) return;
+    std::shared_ptr<DescreenResult> result = std::move(state.descreen);
+    if (result->image.empty() || result->source.data != state.originalImage.data) {
+        std::cerr << "Warning: The image changed while it was descreened, discarding the result" << std::endl;
+        return;
+    }
+
+    state.originalImage = result->image;
+    if (!result->precise.empty()) state.preciseImage = result->precise;
+    std::cout << "Descreened (" << Descreen::getMethodName(result->method) << ", " << result->period << " px period)"
+              << std::endl;
+
+    updateTexture(state.originalTexture, state.originalImage);
+    processImage(state);
+}
+
+// Save the C, M, Y and K plates next to the chosen file: name_c.png, name_m.png, ...
+// They're written like saved results, with the encoder settings and metadata
+bool exportPlates(AppState& state, const std::string& filename, Errors::Error& error) {
+    if (!state.imageLoaded) return false;
+
+    ImageIO::ExportOptions exportOptions;
+    exportOptions.encode = state.encodeOptions;
+    exportOptions.metadata = state.sourceMetadata;
+    std::vector<cv::Mat> plates = Dithering::ditherSeparations(state.originalImage, state.params);
+    const char* plateNames[4] = {"c", "m", "y", "k"};
+    for (size_t p = 0; p < plates.size(); ++p) {
+        std::filesystem::path platePath(filename);
+        platePath.replace_filename(platePath.stem().string() + "_" + plateNames[p] +
+                                   platePath.extension().string());
+        if (!ImageIO::saveImage(platePath.string(), plates[p], cv::Mat(), exportOptions)) {
+            error = ImageIO::describeSaveFailure(platePath.string());
+            error.message += ": " + platePath.string();
+            return false;
+        }
+        std::cout << "Saved plate to " << platePath.string() << std::endl;
+    }
+    return !plates.empty();
+}
+
+// Dither the dropped video into a new video on the job queue. Cancelling it
+// stops the encoder and deletes the partial output
+void queueVideoExport(AppState& state, const std::string& filename) {
+    if (state.videoFile.empty()) return;
+
+    std::string inputPath = state.videoFile;
+    Dithering::Parameters params = state.params;
+    Video::Options options = state.videoOptions;
+    std::string name = std::filesystem::path(filename).filename().string();
+
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        return Video::processVideo(inputPath, filename, params, options, reportFrames(progress), &error);
+    });
+    state.showQueue = true;
+}
+
+// Dither every image in a folder into its "dithered" subfolder with the
+// current settings, on the job queue. Cancelling it skips the files not yet
+// started
+void queueBatchExport(AppState& state, const std::string& folder) {
+    std::vector<std::string> inputs = Batch::collectImages(folder);
+    if (inputs.empty()) {
+        std::cerr << "Error: No images in " << folder << std::endl;
+        return;
+    }
+
+    Dithering::Parameters params = state.params;
+    Batch::Options options;
+    options.memoryLimit = static_cast<size_t>(state.memoryLimitMB) << 20;
+    options.ditherAlpha = state.ditherAlpha;
+    options.exportOptions.encode = state.encodeOptions;
+    std::string outputDir = (std::filesystem::path(folder) / "dithered").string();
+    std::string name = "Batch " + std::filesystem::path(folder).filename().string();
+
+    state.queue.enqueue(name, [=](const Dithering::ProgressCallback& progress, Errors::Error& error) {
+        std::atomic<bool> cancelled{false};
+        Batch::Options run = options;
+        run.keepRunning = [&cancelled]() { return !cancelled; };
+        if (!progress(0.0f, "Dithering")) return false;
+
+        int failed = 0;
+        Batch::processBatch(inputs, outputDir, params, run,
+            [&](int finished, int total, const Batch::FileResult& result) {
+                if (!result.ok() && result.error.code != Errors::ErrorCode::CANCELLED) {
+                    ++failed;
+                    std::cerr << "Error: " << result.input << ": " << result.error.message << std::endl;
+                }
+                std::string stage = std::to_string(finished) + " of " + std::to_string(total) + " images";
+                if (!progress(static_cast<float>(finished) / total, stage)) cancelled = true;
+            });
+        if (cancelled) return false;
+        if (failed > 0) {
+            error = {Errors::ErrorCode::IO, std::to_string(failed) + " of " + std::to_string(inputs.size()) +
+                                            " images failed, see the log"};
+            return false;
+        }
+        std::cout << "Saved " << inputs.size() << " images to " << outputDir << std::endl;
+        return true;
+    });
+    state.showQueue = true;
+}
+
+// Magnifier tooltip for a hovered preview image shown at imageSize. Either
+// image maps onto the result by position, and only the crops are uploaded.
+// The preview doesn't scroll with the wheel while it's on, which zooms instead
+void showMagnifier(AppState& state, const ImVec2& imageSize) {
+    const float loupeSize = 160.0f;
+    ImGuiIO& io = ImGui::GetIO();
+    if (io.MouseWheel != 0.0f) {
+        state.magnifierZoom = std::clamp(state.magnifierZoom + (io.MouseWheel > 0.0f ? 1 : -1), 1,
+                                         Compare::MAX_CROP_ZOOM);
+    }
+
+    ImVec2 origin = ImGui::GetItemRectMin();
+    int x = static_cast<int>((io.MousePos.x - origin.x) / imageSize.x * state.processedImage.cols);
+    int y = static_cast<int>((io.MousePos.y - origin.y) / imageSize.y * state.processedImage.rows);
+    // Crops are only made and uploaded again when they would show something new
+    cv::Vec3i shown(x, y, state.magnifierZoom);
+    if (shown != state.magnifierShown || state.magnifierSources[0] != state.originalImage.data ||
+        state.magnifierSources[1] != state.processedImage.data) {
+        int size = std::max(1, static_cast<int>(loupeSize) / state.magnifierZoom);
+        cv::Rect rect(x - size / 2, y - size / 2, size, size);
+        state.magnifierShown = shown;
+        state.magnifierSources[0] = state.originalImage.data;
+        state.magnifierSources[1] = state.processedImage.data;
+        if (!Compare::compareCrops(state.originalImage, state.processedImage, rect, state.magnifierZoom,
+                                   state.magnifierCrops[0], state.magnifierCrops[1])) {
+            state.magnifierCrops[0] = cv::Mat();
+            state.magnifierCrops[1] = cv::Mat();
+        }
+        updateTexture(state.magnifierTextures[0], state.magnifierCrops[0]);
+        updateTexture(state.magnifierTextures[1], state.magnifierCrops[1]);
+    }
+    const cv::Mat& originalCrop = state.magnifierCrops[0];
+    const cv::Mat& resultCrop = state.magnifierCrops[1];
+    if (originalCrop.empty() || resultCrop.empty()) return;
+
+    ImGui::BeginTooltip();
+    ImGui::Image((void*)(intptr_t)state.magnifierTextures[0], ImVec2(originalCrop.cols, originalCrop.rows));
+    ImGui::SameLine();
+    ImGui::Image((void*)(intptr_t)state.magnifierTextures[1], ImVec2(resultCrop.cols, resultCrop.rows));
+    ImGui::TextDisabled("%d, %d at %dx (scroll to zoom)", x, y, state.magnifierZoom);
+    ImGui::EndTooltip();
+}
+
+// Main GUI rendering
+void renderGUI(AppState& state) {
+    ImGuiIO& io = ImGui::GetIO();
+
+    // Undo and redo, unless a text field has the keyboard
+    if (io.KeyCtrl && !io.WantTextInput) {
+        if (ImGui::IsKeyPressed(ImGuiKey_Z, false)) {
+            if (io.KeyShift) {
+                redo(state);
+            } else {
+                undo(state);
+            }
+        } else if (ImGui::IsKeyPressed(ImGuiKey_Y, false)) {
+            redo(state);
+        }
+    }
+
+    // Main menu bar
+    if (ImGui::BeginMainMenuBar()) {
+        if (ImGui::BeginMenu("File")) {
+            if (ImGui::MenuItem("Open Image", "Ctrl+O")) {
+                std::string filepath = Platform::openFileDialog();
+                if (!filepath.empty()) {
+                    loadImage(state, filepath);
+                }
+            }
+            if (ImGui::MenuItem("Paste Image")) {
+                pasteImage(state);
+            }
+            if (ImGui::MenuItem("Capture Screen")) {
+                cv::Mat screen;
+                Errors::Error error;
+                if (Video::captureScreen(cv::Rect(), screen, &error)) {
+                    setLoadedImage(state, "screen.png", screen, cv::Mat(), cv::Mat(), {});
+                } else {
+                    std::cerr << "Error: " << error.message << std::endl;
+                }
+            }
+            if (ImGui::MenuItem("Render Text")) {
+                state.showRenderText = true;
+            }
+            if (ImGui::BeginMenu("Open Recent", !state.recentFiles.empty())) {
+                std::string open;
+                for (const auto& recent : state.recentFiles) {
+                    if (ImGui::MenuItem(recent.path.c_str())) open = recent.path;
+                }
+                ImGui::Separator();
+                if (ImGui::MenuItem("Clear Recent Files")) clearRecentFiles(state);
+                ImGui::EndMenu();
+                // Opened after the loop, since loading refreshes the list
+                if (!open.empty()) loadImage(state, open);
+            }
+            if (ImGui::MenuItem("Open Video")) {
+                std::cout << "Video processing available via CLI: ./dithers-boyfriend-cli" << std::endl;
+            }
+            if (ImGui::BeginMenu("Webcam")) {
+                if (state.webcam) {
+                    if (ImGui::MenuItem("Stop Webcam")) stopWebcam(state);
+                } else {
+                    ImGui::InputInt("Device", &state.webcamDevice);
+                    state.webcamDevice = std::max(0, state.webcamDevice);
+                    ImGui::SliderInt("Width", &state.webcamWidth, 160, 1920);
+                    if (ImGui::MenuItem("Start Webcam")) startWebcam(state);
+                }
+                ImGui::EndMenu();
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Save As...", "Ctrl+S")) {
+                if (state.imageLoaded && !state.processedImage.empty()) {
+                    std::string filepath = Platform::saveFileDialog();
+                    if (!filepath.empty()) {
+                        if (saveImage(state, filepath, &state.savedFileSize)) {
+                            std::cout << "Saved to " << filepath << " ("
+                                      << ImageIO::formatFileSize(state.savedFileSize) << ")" << std::endl;
+                        } else {
+                            std::cerr << "Failed to save image" << std::endl;
+                        }
+                    }
+                }
+            }
+            if (ImGui::MenuItem("Add to Queue...", nullptr, false, state.imageLoaded)) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) {
+                    queueExport(state, filepath);
+                }
+            }
+            if (ImGui::MenuItem("Copy Result", nullptr, false, state.imageLoaded && !state.processedImage.empty())) {
+                if (copyResult(state)) {
+                    std::cout << "Copied result to the clipboard" << std::endl;
+                } else {
+                    std::cerr << "Failed to copy result to the clipboard" << std::endl;
+                }
+            }
+            ImGui::MenuItem("Dither Alpha to 1-bit", nullptr, &state.ditherAlpha, !state.originalAlpha.empty());
+            if (state.ditherAlpha && !state.originalAlpha.empty()) {
+                ImGui::SliderInt("GIF Transparent Index", &state.gifTransparentIndex, -1, 255,
+                                 state.gifTransparentIndex < 0 ? "Any" : "%d");
+            }
+            if (ImGui::BeginMenu("Encoder Settings")) {
+                ImageIO::EncodeOptions& encode = state.encodeOptions;
+                ImGui::SliderInt("JPEG Quality", &encode.jpegQuality, 1, 100);
+                ImGui::SliderInt("PNG Compression", &encode.pngCompression, 0, 9);
+                if (ImGui::BeginCombo("PNG Strategy", ImageIO::getPngStrategyName(encode.pngStrategy).c_str())) {
+                    for (size_t i = 0; i < ImageIO::getPngStrategyIds().size(); ++i) {
+                        auto strategy = static_cast<ImageIO::PngStrategy>(i);
+                        if (ImGui::Selectable(ImageIO::getPngStrategyName(strategy).c_str(),
+                                              encode.pngStrategy == strategy)) {
+                            encode.pngStrategy = strategy;
+                        }
+                    }
+                    ImGui::EndCombo();
+                }
+                ImGui::Checkbox("Lossless WebP", &encode.webpLossless);
+                if (!encode.webpLossless) {
+                    ImGui::SliderInt("WebP Quality", &encode.webpQuality, 1, 100);
+                }
+                ImGui::SliderInt("AVIF Quality", &encode.avifQuality, 0, 100,
+                                 encode.avifQuality == 100 ? "Lossless" : "%d");
+                ImGui::SliderInt("JPEG XL Quality", &encode.jxlQuality, 0, 100,
+                                 encode.jxlQuality == 100 ? "Lossless" : "%d");
+                ImGui::Checkbox("1-bit TIFF for Two Colors", &encode.bilevelTiff);
+                ImGui::Checkbox("Keep EXIF and Color Profile", &encode.keepMetadata);
+                ImGui::EndMenu();
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Load Settings...")) {
+                std::string filepath = Platform::openFileDialog();
+                std::string error;
+                std::vector<std::string> warnings;
+                if (!filepath.empty()) {
+                    if (Settings::loadSettings(filepath, state.params, &error, &warnings)) {
+                        settingsLoaded(state, warnings);
+                    } else {
+                        std::cerr << "Error: " << error << std::endl;
+                    }
+                }
+            }
+            if (ImGui::MenuItem("Save Settings...")) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty() && !Settings::saveSettings(filepath, state.params)) {
+                    std::cerr << "Failed to save settings" << std::endl;
+                }
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Export CMYK Plates...", nullptr, false,
+                                state.imageLoaded && state.params.colorMode == Dithering::ColorMode::CMYK)) {
+                std::string filepath = Platform::saveFileDialog();
+                Errors::Error error;
+                if (!filepath.empty() && !exportPlates(state, filepath, error)) {
+                    std::cerr << "Failed to export plates"
+                              << (error.message.empty() ? "" : ": " + error.message) << std::endl;
+                }
+            }
+            if (ImGui::MenuItem("Export Poster PDF...", nullptr, false, state.imageLoaded)) {
+                state.showPosterExport = true;
+            }
+            if (ImGui::MenuItem("Export Print PDF...", nullptr, false, state.imageLoaded)) {
+                state.showPrintExport = true;
+            }
+            if (ImGui::MenuItem("Export ASCII Art...", nullptr, false, state.imageLoaded)) {
+                state.showAsciiExport = true;
+                updateAsciiPreview(state);
+            }
+            if (ImGui::BeginMenu("Export ANSI Text", state.imageLoaded)) {
+                ImGui::SliderInt("Columns", &state.ansiColumns, 0, 400, state.ansiColumns > 0 ? "%d" : "Native");
+                for (const auto& id : Ascii::getAnsiColorIds()) {
+                    Ascii::AnsiColors colors;
+                    Ascii::parseAnsiColors(id, colors);
+                    if (ImGui::MenuItem((Ascii::getAnsiColorName(colors) + "...").c_str())) {
+                        std::string filepath = Platform::saveFileDialog();
+                        if (!filepath.empty()) queueAnsiText(state, filepath, colors);
+                    }
+                }
+                ImGui::EndMenu();
+            }
+            if (ImGui::MenuItem("Export Plotter Dots...", nullptr, false, state.imageLoaded)) {
+                state.showPlotterExport = true;
+            }
+            if (ImGui::MenuItem("Export for Display (C Array)...", nullptr, false, state.imageLoaded)) {
+                state.showEmbeddedExport = true;
+            }
+            // The palette as it's used now; an adaptive one is extracted from the image
+            bool adaptive = state.params.paletteMode == Dithering::PaletteMode::ADAPTIVE;
+            if (ImGui::MenuItem("Export Palette (GPL, ASE, HEX, PNG)...", nullptr, false,
+                                !adaptive || state.imageLoaded)) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) {
+                    std::vector<cv::Vec3b> palette = adaptive
+                        ? Dithering::generatePalette(Dithering::preprocessImage(state.originalImage, state.params),
+                                                     state.params.paletteSize, state.params.paletteMethod,
+                                                     state.params.seed)
+                        : Dithering::getPalette(state.params);
+                    Errors::Error error;
+                    if (PaletteFile::savePalette(filepath, palette, &error)) {
+                        std::cout << "Saved " << palette.size() << " colors to " << filepath << std::endl;
+                    } else {
+                        std::cerr << "Error: " << error.message << std::endl;
+                    }
+                }
+            }
+            if (ImGui::MenuItem("Export Animation (GIF, WebP, APNG)...", nullptr, false, state.animatedGif)) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) queueAnimationExport(state, filepath);
+            }
+            if (ImGui::MenuItem("Export Vector Halftone (SVG)...", nullptr, false,
+                                state.imageLoaded && Halftone::supportsVectorHalftone(state.params))) {
+                std::string filepath = Platform::saveFileDialog();
+                const cv::Mat& source = state.preciseImage.empty() ? state.originalImage : state.preciseImage;
+                if (!filepath.empty() && !Halftone::exportSvg(source, state.params, filepath)) {
+                    std::cerr << "Failed to export SVG" << std::endl;
+                }
+            }
+            if (ImGui::MenuItem("Export Zoomable Tiles (DZI)...", nullptr, false, state.imageLoaded)) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) {
+                    if (ImageIO::getExtension(filepath) != "dzi") filepath += ".dzi";
+                    queueExport(state, filepath);
+                }
+            }
+            if (ImGui::BeginMenu("Export Dithering Progression", state.imageLoaded)) {
+                float seconds = static_cast<float>(state.progressionOptions.seconds);
+                if (ImGui::SliderFloat("Seconds", &seconds, 1.0f, 30.0f, "%.0f")) {
+                    state.progressionOptions.seconds = seconds;
+                }
+                if (ImGui::IsItemHovered()) ImGui::SetTooltip("Length before the result is held at the end");
+                float fps = static_cast<float>(state.progressionOptions.fps);
+                if (ImGui::SliderFloat("FPS##Progression", &fps, 5.0f, 60.0f, "%.0f")) {
+                    state.progressionOptions.fps = fps;
+                }
+                ImGui::SliderInt("Upscale##Progression", &state.progressionOptions.upscale, 1, 8);
+                if (ImGui::MenuItem("Save GIF, WebP or Video...")) {
+                    std::string filepath = Platform::saveFileDialog();
+                    if (!filepath.empty()) {
+                        if (ImageIO::getExtension(filepath).empty()) filepath += ".gif";
+                        queueProgression(state, filepath);
+                    }
+                }
+                ImGui::EndMenu();
+            }
+            if (ImGui::MenuItem("Export Montage...", nullptr, false, state.imageLoaded)) {
+                state.montagePresetList = Settings::listPresets();
+                state.showMontage = true;
+            }
+            if (ImGui::BeginMenu("Set as Wallpaper", state.imageLoaded)) {
+                const char* modes[] = {"Fill Screen", "Fit to Screen", "Center"};
+                for (int i = 0; i < IM_ARRAYSIZE(modes); ++i) {
+                    if (ImGui::MenuItem(modes[i])) {
+                        state.wallpaperOptions.mode = static_cast<Wallpaper::FitMode>(i);
+                        queueWallpaper(state);
+                    }
+                }
+                ImGui::Separator();
+                colorEditBGR("Background", state.wallpaperOptions.background);
+                ImGui::EndMenu();
+            }
+            if (ImGui::MenuItem("Compare With Image...", nullptr, false, state.imageLoaded)) {
+                std::string filepath = Platform::openFileDialog();
+                cv::Mat reference, referenceAlpha;
+                if (!filepath.empty() && ImageIO::loadImage(filepath, reference, referenceAlpha)) {
+                    state.referenceImage = reference;
+                    state.referenceFile = filepath;
+                    state.showComparison = true;
+                    updateComparison(state);
+                    updateQuality(state);
+                } else if (!filepath.empty()) {
+                    std::cerr << "Error: Could not load image: " << filepath << std::endl;
+                }
+            }
+            if (ImGui::MenuItem("Export Video...", nullptr, false, !state.videoFile.empty())) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) queueVideoExport(state, filepath);
+            }
+            if (ImGui::MenuItem("Batch Export Folder...")) {
+                std::string filepath = Platform::openFileDialog();
+                if (!filepath.empty()) queueBatchExport(state, std::filesystem::path(filepath).parent_path().string());
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Pick any image in the folder. Every image in it is dithered with the\n"
+                                  "current settings into its \"dithered\" subfolder");
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Exit", "Alt+F4")) {
+                glfwSetWindowShouldClose(glfwGetCurrentContext(), true);
+            }
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("Edit")) {
+            if (ImGui::MenuItem("Undo", "Ctrl+Z", false, state.historyIndex > 0)) undo(state);
+            if (ImGui::MenuItem("Redo", "Ctrl+Y", false, state.historyIndex + 1 < state.history.size())) redo(state);
+            ImGui::Separator();
+            ImGui::MenuItem("Settings History", nullptr, &state.showHistory);
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("Image", state.imageLoaded && !state.isVideo && !state.webcam)) {
+            if (ImGui::MenuItem("Rotate 90\xc2\xb0 Clockwise")) {
+                transformImage(state, [](const cv::Mat& image) { return Transform::rotateImage(image, 90); });
+            }
+            if (ImGui::MenuItem("Rotate 90\xc2\xb0 Counterclockwise")) {
+                transformImage(state, [](const cv::Mat& image) { return Transform::rotateImage(image, 270); });
+            }
+            if (ImGui::MenuItem("Rotate 180\xc2\xb0")) {
+                transformImage(state, [](const cv::Mat& image) { return Transform::rotateImage(image, 180); });
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Flip Horizontal")) {
+                transformImage(state, [](const cv::Mat& image) {
+                    return Transform::flipImage(image, Transform::FlipAxis::HORIZONTAL);
+                });
+            }
+            if (ImGui::MenuItem("Flip Vertical")) {
+                transformImage(state, [](const cv::Mat& image) {
+                    return Transform::flipImage(image, Transform::FlipAxis::VERTICAL);
+                });
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Crop...")) {
+                state.cropRect[0] = state.cropRect[1] = 0;
+                state.cropRect[2] = state.originalImage.cols;
+                state.cropRect[3] = state.originalImage.rows;
+                state.showCrop = true;
+            }
+            if (ImGui::MenuItem("Resize...")) {
+                state.resizeSize[0] = state.originalImage.cols;
+                state.resizeSize[1] = state.originalImage.rows;
+                state.showResize = true;
+            }
+            if (ImGui::BeginMenu("Descreen")) {
+                for (size_t i = 0; i < Descreen::getMethodIds().size(); ++i) {
+                    auto method = static_cast<Descreen::Method>(i);
+                    if (ImGui::MenuItem(Descreen::getMethodName(method).c_str())) queueDescreen(state, method);
+                }
+                ImGui::EndMenu();
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Smooth away the dots of a scanned print or a dithered image");
+            }
+            ImGui::Separator();
+            if (ImGui::MenuItem("Dither Region...")) {
+                state.maskRect[0] = state.maskRect[1] = 0;
+                state.maskRect[2] = state.originalImage.cols;
+                state.maskRect[3] = state.originalImage.rows;
+                state.showMaskRegion = true;
+            }
+            if (ImGui::MenuItem("Load Dither Mask...")) {
+                std::string filepath = Platform::openFileDialog();
+                cv::Mat mask;
+                if (!filepath.empty() && ImageIO::loadMask(filepath, mask)) {
+                    cv::resize(mask, state.ditherMask, state.originalImage.size(), 0, 0, cv::INTER_LINEAR);
+                    processImage(state);
+                } else if (!filepath.empty()) {
+                    std::cerr << "Error: Could not load mask: " << filepath << std::endl;
+                }
+            }
+            if (ImGui::MenuItem("Clear Dither Mask", nullptr, false, !state.ditherMask.empty())) {
+                state.ditherMask = cv::Mat();
+                processImage(state);
+            }
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("View")) {
+            ImGui::MenuItem("Split View", nullptr, &state.splitView);
+            ImGui::MenuItem("Show Original", nullptr, &state.showOriginal);
+            ImGui::MenuItem("Show Processed", nullptr, &state.showProcessed);
+            ImGui::MenuItem("Magnifier", nullptr, &state.magnifier);
+            ImGui::Separator();
+            ImGui::MenuItem("Job Queue", nullptr, &state.showQueue);
+            if (ImGui::MenuItem("Quality Metrics", nullptr, &state.showQuality)) {
+                updateQuality(state);
+            }
+            if (ImGui::MenuItem("Histogram", nullptr, &state.showHistogram)) {
+                updateSourceHistogram(state);
+            }
+            if (ImGui::MenuItem("Algorithm Explorer", nullptr, &state.showExplorer) &&
+                state.showExplorer && state.explorerThumbnails.empty()) {
+                renderExplorer(state);
+            }
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("Settings")) {
+            bool honorProfiles = ImageIO::getColorManagement() == ImageIO::ColorManagement::HONOR_PROFILES;
+            if (ImGui::MenuItem("Honor Embedded Color Profiles", nullptr, &honorProfiles)) {
+                ImageIO::setColorManagement(honorProfiles ? ImageIO::ColorManagement::HONOR_PROFILES
+                                                          : ImageIO::ColorManagement::ASSUME_SRGB);
+            }
+            if (honorProfiles && ImGui::BeginMenu("Profile Space")) {
+                for (size_t i = 0; i < ColorProfile::getSpaceIds().size(); ++i) {
+                    auto space = static_cast<ColorProfile::Space>(i);
+                    if (ImGui::MenuItem(ColorProfile::getSpaceName(space).c_str(), nullptr,
+                                        ImageIO::getProfileSpace() == space)) {
+                        ImageIO::setProfileSpace(space);
+                    }
+                }
+                ImGui::EndMenu();
+            }
+            ImGui::MenuItem("Tag Output with Color Profile", nullptr, &state.encodeOptions.tagProfile);
+
+            if (ImGui::SliderFloat("Fast Preview Above (MP)", &state.previewBudgetMP, 0.5f, 50.0f, "%.1f")) {
+                if (state.autoUpdate) processImage(state);
+            }
+            if (ImGui::SliderInt("Preview Max Size (px)", &state.previewMaxDimension, 0, 8192,
+                                 state.previewMaxDimension > 0 ? "%d" : "Full")) {
+                if (state.autoUpdate) processImage(state);
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Dither a downscaled copy for the preview; export is always full size");
+            }
+            ImGui::SliderInt("Memory Limit (MB)", &state.memoryLimitMB, 256, 16384);
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Images needing more working memory are dithered in bands");
+            }
+            int decodeLimitMB = static_cast<int>(ImageIO::getDecodeMemoryLimit() >> 20);
+            if (ImGui::SliderInt("Open Limit (MB)", &decodeLimitMB, 0, 16384, decodeLimitMB > 0 ? "%d" : "Off")) {
+                ImageIO::setDecodeMemoryLimit(static_cast<size_t>(decodeLimitMB) << 20);
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Images that would need more memory to open are refused, or downscaled");
+            }
+            bool downscaleHuge = ImageIO::getDownscaleOversized();
+            if (ImGui::MenuItem("Downscale Huge Images", nullptr, &downscaleHuge)) {
+                ImageIO::setDownscaleOversized(downscaleHuge);
+            }
+            int svgWidth = ImageIO::getSvgWidth();
+            if (ImGui::SliderInt("SVG Width (px)", &svgWidth, 0, 8192, svgWidth > 0 ? "%d" : "Own Size")) {
+                ImageIO::setSvgWidth(svgWidth);
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Width SVG files are rasterized at when opened, keeping their shape");
+            }
+            ImGui::MenuItem("Recommended Settings on Algorithm Change", nullptr, &state.recommendedOnSwitch);
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Switching to another family of algorithms resets strength, threshold\n"
+                                  "and pattern scale, and fixes values the new algorithm can't use well");
+            }
+
+            ImGui::Separator();
+            bool perceptual = state.params.renderingIntent == Dithering::RenderingIntent::PERCEPTUAL;
+            if (ImGui::MenuItem("Relative Colorimetric Intent", nullptr, !perceptual)) {
+                state.params.renderingIntent = Dithering::RenderingIntent::RELATIVE_COLORIMETRIC;
+                if (state.autoUpdate) processImage(state);
+            }
+            if (ImGui::MenuItem("Perceptual Intent", nullptr, perceptual)) {
+                state.params.renderingIntent = Dithering::RenderingIntent::PERCEPTUAL;
+                if (state.autoUpdate) processImage(state);
+            }
+
+            ImGui::MenuItem("Automation Server...", nullptr, &state.showServer);
+
+            ImGui::Separator();
+            if (ImGui::BeginMenu("FFmpeg")) {
+                // Checked when the menu first opens and after changes
+                pollFfmpegCheck(state);
+                if (state.ffmpegTools.empty() && !state.ffmpegCheck) {
+                    startFfmpegCheck(state);
+                    std::snprintf(state.ffmpegPath, sizeof(state.ffmpegPath), "%s", Platform::getFfmpegPath().c_str());
+                }
+                if (state.ffmpegCheck) ImGui::TextDisabled("Checking...");
+                for (const auto& tool : state.ffmpegTools) {
+                    if (tool.path.empty()) {
+                        ImGui::TextDisabled("%s: not found", tool.name.c_str());
+                    } else {
+                        ImGui::Text("%s %s: %s (%s)", tool.name.c_str(),
+                                    tool.version.empty() ? "(unknown version)" : tool.version.c_str(),
+                                    tool.path.c_str(), tool.source.c_str());
+                    }
+                }
+                ImGui::InputText("Folder or Binary", state.ffmpegPath, sizeof(state.ffmpegPath));
+                // A check already running would report the old tools
+                ImGui::BeginDisabled(state.ffmpegCheck != nullptr);
+                if (ImGui::Button("Use")) {
+                    if (!Platform::setFfmpegPath(state.ffmpegPath)) {
+                        std::cerr << "Failed to save the ffmpeg path" << std::endl;
+                    }
+                    state.ffmpegTools.clear();
+                }
+                ImGui::SameLine();
+                if (ImGui::Button("Reset")) {
+                    Platform::setFfmpegPath("");
+                    state.ffmpegTools.clear();
+                }
+                ImGui::SameLine();
+                if (ImGui::Button("Check Again")) state.ffmpegTools.clear();
+                ImGui::EndDisabled();
+                ImGui::EndMenu();
+            }
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("Presets")) {
+            std::vector<std::string> presets = Settings::listPresets();
+            for (const auto& name : presets) {
+                if (ImGui::MenuItem(name.c_str())) {
+                    std::string error;
+                    std::vector<std::string> warnings;
+                    if (Settings::loadPreset(name, state.params, &error, &warnings)) {
+                        settingsLoaded(state, warnings);
+                    } else {
+                        std::cerr << "Error: " << error << std::endl;
+                    }
+                }
+            }
+            if (presets.empty()) {
+                ImGui::TextDisabled("No saved presets");
+            }
+            ImGui::Separator();
+            // Also crops and scales a loaded image to the device's screen
+            if (ImGui::BeginMenu("Retro Devices")) {
+                for (const auto& device : Devices::getDeviceLibrary()) {
+                    if (ImGui::MenuItem(device.name.c_str())) {
+                        Devices::applyDevice(device, state.params);
+                        state.selectedAlgorithm = static_cast<int>(state.params.algorithm);
+                        state.selectedPalette = static_cast<int>(state.params.paletteMode);
+                        if (state.imageLoaded && !state.isVideo) {
+                            transformImage(state, [&device](const cv::Mat& image) {
+                                return Devices::fitToScreen(image, device);
+                            });
+                        } else {
+                            processImage(state);
+                        }
+                    }
+                    if (ImGui::IsItemHovered()) ImGui::SetTooltip("%s", device.description.c_str());
+                }
+                ImGui::EndMenu();
+            }
+            if (ImGui::MenuItem("Save Preset...")) {
+                state.showSavePreset = true;
+                state.presetError.clear();
+            }
+            if (ImGui::BeginMenu("Delete Preset", !presets.empty())) {
+                for (const auto& name : presets) {
+                    if (ImGui::MenuItem(name.c_str()) && !Settings::deletePreset(name)) {
+                        std::cerr << "Failed to delete preset " << name << std::endl;
+                    }
+                }
+                ImGui::EndMenu();
+            }
+            ImGui::EndMenu();
+        }
+
+        if (ImGui::BeginMenu("Help")) {
+            if (ImGui::MenuItem("About")) {
+                std::cout << "Dither's Boyfriend - Advanced Dithering Application" << std::endl;
+            }
+            if (ImGui::MenuItem("Usage Statistics", nullptr, &state.showUsageStats)) {
+                state.usageStatsRevision = -1;  // Pick up jobs other processes recorded
+            }
+            ImGui::EndMenu();
+        }
+
+        ImGui::EndMainMenuBar();
+    }
+
+    // Left panel - Controls
+    ImGui::SetNextWindowPos(ImVec2(0, 20));
+    ImGui::SetNextWindowSize(ImVec2(400, io.DisplaySize.y - 20));
+    ImGui::Begin("Controls", nullptr, ImGuiWindowFlags_NoMove | ImGuiWindowFlags_NoResize);
+
+    ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.9f, 0.9f, 1.0f, 1.0f));
+    ImGui::TextWrapped("Dither's Boyfriend");
+    ImGui::PopStyleColor();
+    ImGui::Separator();
+
+    // Algorithm selection
+    ImGui::Text("Dithering Algorithm");
+    const auto& algorithmLibrary = Dithering::getAlgorithmLibrary();
+    const Dithering::AlgorithmInfo* algorithmInfo = &Dithering::getAlgorithmInfo(state.params.algorithm);
+    const auto& pluginAlgorithms = Plugins::getAlgorithms();
+    std::string algorithmLabel = algorithmInfo->name;
+    for (const auto& plugin : pluginAlgorithms) {
+        if (plugin.id == state.params.plugin) algorithmLabel = plugin.name;
+    }
+    if (ImGui::BeginCombo("##Algorithm", algorithmLabel.c_str())) {
+        // Grouped by category, in the order the categories are declared
+        for (int c = 0; c <= static_cast<int>(Dithering::AlgorithmCategory::THRESHOLD); ++c) {
+            auto category = static_cast<Dithering::AlgorithmCategory>(c);
+            ImGui::TextDisabled("%s", Dithering::getAlgorithmCategoryName(category).c_str());
+            for (const auto& info : algorithmLibrary) {
+                if (info.category != category) continue;
+                bool selected = info.algorithm == state.params.algorithm && state.params.plugin.empty();
+                if (ImGui::Selectable(info.name.c_str(), selected) && !selected) {
+                    selectAlgorithm(state, info.algorithm);
+                }
+                if (selected) ImGui::SetItemDefaultFocus();
+            }
+        }
+        // Plugins keep the built-in algorithm underneath, used if one fails
+        if (!pluginAlgorithms.empty()) ImGui::TextDisabled("Plugins");
+        for (const auto& plugin : pluginAlgorithms) {
+            bool selected = plugin.id == state.params.plugin;
+            if (ImGui::Selectable(plugin.name.c_str(), selected) && !selected) {
+                state.params.plugin = plugin.id;
+                if (state.autoUpdate) processImage(state);
+            }
+            if (!plugin.description.empty() && ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("%s", plugin.description.c_str());
+            }
+            if (selected) ImGui::SetItemDefaultFocus();
+        }
+        ImGui::EndCombo();
+    }
+
+    // Plugin options as key=value lines, passed through untouched
+    if (!state.params.plugin.empty()) {
+        std::string text;
+        for (const auto& option : state.params.pluginOptions) text += option.first + "=" + option.second + "\n";
+        ImVec2 size(0, ImGui::GetTextLineHeight() * 4);
+        if (inputTextMultiline("Plugin Options", text, size)) {
+            std::map<std::string, std::string> options;
+            std::istringstream lines(text);
+            std::string line;
+            while (std::getline(lines, line)) {
+                size_t equals = line.find('=');
+                if (equals != std::string::npos && equals > 0) options[line.substr(0, equals)] = line.substr(equals + 1);
+            }
+            if (options != state.params.pluginOptions) {
+                state.params.pluginOptions = options;
+                if (state.autoUpdate) processImage(state);
+            }
+        }
+    }
+
+    ImGui::Separator();
+
+    // Palette selection
+    ImGui::Text("Color Palette");
+    const char* palettes[] = {
+        "Monochrome", "Grayscale 4", "Grayscale 8", "Grayscale 16",
+        "CGA", "EGA", "VGA", "Game Boy", "PICO-8", "Custom", "Adaptive"
+    };
+
+    if (ImGui::Combo("##Palette", &state.selectedPalette, palettes, IM_ARRAYSIZE(palettes))) {
+        state.params.paletteMode = static_cast<Dithering::PaletteMode>(state.selectedPalette);
+        pruneProtectedColors(state);
+        if (state.autoUpdate) processImage(state);
+    }
+
+    // CMYK separates the image into four screened ink plates; the palette is unused
+    const char* colorModes[] = {"RGB", "CMYK", "Multi-Ink", "Pixel Format"};
+    int colorMode = static_cast<int>(state.params.colorMode);
+    if (ImGui::Combo("Color Mode", &colorMode, colorModes, IM_ARRAYSIZE(colorModes))) {
+        state.params.colorMode = static_cast<Dithering::ColorMode>(colorMode);
+        if (state.params.colorMode == Dithering::ColorMode::MULTI_INK && state.params.inks.empty()) {
+            // Riso blue and fluorescent pink, a classic duotone pairing
+            state.params.inks = {cv::Vec3b(191, 120, 0), cv::Vec3b(176, 72, 255)};
+        }
+        if (state.autoUpdate) processImage(state);
+    }
+
+    // Duotone/tritone inks, printed on the paper color
+    if (state.params.colorMode == Dithering::ColorMode::MULTI_INK) {
+        bool inksChanged = false;
+        for (size_t i = 0; i < state.params.inks.size(); ++i) {
+            std::string label = "Ink " + std::to_string(i + 1);
+            if (colorEditBGR(label.c_str(), state.params.inks[i])) inksChanged = true;
+            ImGui::SameLine();
+        }
+        if (colorEditBGR("Paper##MultiInk", state.params.paperColor)) inksChanged = true;
+
+        int inkCount = static_cast<int>(state.params.inks.size());
+        if (ImGui::RadioButton("Duotone", inkCount == 2) && inkCount != 2) {
+            state.params.inks.resize(2);
+            inksChanged = true;
+        }
+        ImGui::SameLine();
+        if (ImGui::RadioButton("Tritone", inkCount == 3) && inkCount != 3) {
+            state.params.inks.push_back(cv::Vec3b(0, 232, 255));  // Riso yellow
+            inksChanged = true;
+        }
+        if (inksChanged && state.autoUpdate) processImage(state);
+    }
+
+    // Per-channel bit depths of embedded display formats
+    if (state.params.colorMode == Dithering::ColorMode::PIXEL_FORMAT) {
+        const char* pixelFormats[] = {"RGB565", "RGB332", "RGBA4444"};
+        int pixelFormat = static_cast<int>(state.params.pixelFormat);
+        if (ImGui::Combo("Pixel Format", &pixelFormat, pixelFormats, IM_ARRAYSIZE(pixelFormats))) {
+            state.params.pixelFormat = static_cast<Dithering::PixelFormat>(pixelFormat);
+            if (state.autoUpdate) processImage(state);
+        }
+    }
+
+    if (state.params.paletteMode == Dithering::PaletteMode::ADAPTIVE ||
+        state.params.paletteMode == Dithering::PaletteMode::CUSTOM) {
+        bool paletteSettingsChanged = false;
+        if (ImGui::SliderInt("Palette Size", &state.params.paletteSize, 2, 64)) paletteSettingsChanged = true;
+
+        const char* methods[] = {"K-Means", "Median Cut"};
+        int method = static_cast<int>(state.params.paletteMethod);
+        if (ImGui::Combo("Method", &method, methods, IM_ARRAYSIZE(methods))) {
+            state.params.paletteMethod = static_cast<Dithering::PaletteMethod>(method);
+            paletteSettingsChanged = true;
+        }
+
+        bool adaptive = state.params.paletteMode == Dithering::PaletteMode::ADAPTIVE;
+        if (paletteSettingsChanged && adaptive && state.autoUpdate) processImage(state);
+
+        // Freeze the image's own palette into the editable custom palette
+        if (state.imageLoaded && ImGui::Button("Extract Palette From Image", ImVec2(-1, 0))) {
+            state.params.customPalette = Dithering::generatePalette(
+                Dithering::preprocessImage(state.originalImage, state.params),
+                state.params.paletteSize, state.params.paletteMethod, state.params.seed);
+            state.params.paletteMode = Dithering::PaletteMode::CUSTOM;
+            state.selectedPalette = static_cast<int>(Dithering::PaletteMode::CUSTOM);
+            pruneProtectedColors(state);
+            if (state.autoUpdate) processImage(state);
+        }
+
+        // Palettes kept in other programs (GIMP, Photoshop, Paint.NET, Lospec)
+        if (ImGui::Button("Import Palette File...", ImVec2(-1, 0))) {
+            std::string filepath = Platform::openFileDialog();
+            Errors::Error error;
+            if (!filepath.empty()) {
+                if (PaletteFile::loadPalette(filepath, state.params.customPalette, &error)) {
+                    state.params.paletteMode = Dithering::PaletteMode::CUSTOM;
+                    state.selectedPalette = static_cast<int>(Dithering::PaletteMode::CUSTOM);
+                    pruneProtectedColors(state);
+                    if (state.autoUpdate) processImage(state);
+                } else {
+                    std::cerr << "Error: " << error.message << std::endl;
+                }
+            }
+        }
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("GIMP .gpl, Adobe .ase/.act, .pal, Paint.NET .txt, .hex or a .png swatch");
+        }
+        if (ImGui::Button("Browse Lospec Palettes...", ImVec2(-1, 0))) {
+            state.showLospec = true;
+            if (state.lospecResults.empty() && state.lospecError.empty()) startLospecSearch(state, state.lospecPage);
+        }
+    }
+
+    // Ink and paper colors for 1-bit output
+    if (state.params.paletteMode == Dithering::PaletteMode::MONOCHROME) {
+        bool colorsChanged = false;
+        if (colorEditBGR("Ink", state.params.inkColor)) colorsChanged = true;
+        ImGui::SameLine();
+        if (colorEditBGR("Paper", state.params.paperColor)) colorsChanged = true;
+        ImGui::SameLine();
+        if (ImGui::SmallButton("Swap")) {
+            std::swap(state.params.inkColor, state.params.paperColor);
+            colorsChanged = true;
+        }
+        if (colorsChanged && state.autoUpdate) processImage(state);
+    }
+
+    // Custom palette editor
+    if (state.params.paletteMode == Dithering::PaletteMode::CUSTOM) {
+        std::vector<cv::Vec3b>& colors = state.params.customPalette;
+        bool paletteChanged = false;
+        if (colors.empty()) {
+            colors = {cv::Vec3b(0, 0, 0), cv::Vec3b(255, 255, 255)};
+            paletteChanged = true;
+        }
+
+        int removeIndex = -1;
+        for (size_t i = 0; i < colors.size(); ++i) {
+            ImGui::PushID(static_cast<int>(i));
+            // Protected colors only match exactly, error diffusion never touches them
+            std::vector<cv::Vec3b>& protectedColors = state.params.protectedColors;
+            auto protectedEntry = std::find(protectedColors.begin(), protectedColors.end(), colors[i]);
+            bool isProtected = protectedEntry != protectedColors.end();
+
+            if (colorEditBGR("##color", colors[i])) {
+                if (isProtected) *protectedEntry = colors[i];
+                paletteChanged = true;
+            }
+            ImGui::SameLine();
+            if (ImGui::Checkbox("Protect", &isProtected)) {
+                if (isProtected) {
+                    protectedColors.push_back(colors[i]);
+                } else {
+                    protectedColors.erase(protectedEntry);
+                }
+                paletteChanged = true;
+            }
+            if (colors.size() > 1) {
+                ImGui::SameLine();
+                if (ImGui::SmallButton("Remove")) removeIndex = static_cast<int>(i);
+            }
+            ImGui::PopID();
+        }
+
+        if (removeIndex >= 0) {
+            colors.erase(colors.begin() + removeIndex);
+            pruneProtectedColors(state);
+            paletteChanged = true;
+        }
+        if (ImGui::Button("Add Color")) {
+            colors.push_back(cv::Vec3b(128, 128, 128));
+            paletteChanged = true;
+        }
+
+        if (paletteChanged && state.autoUpdate) processImage(state);
     }
 
     ImGui::Separator();
@@ -296,19 +2601,520 @@ void renderGUI(AppState& state) {
 
     bool needsUpdate = false;
 
-    if (ImGui::SliderFloat("Strength", &state.params.strength, 0.0f, 2.0f)) needsUpdate = true;
-    if (ImGui::SliderFloat("Serpentine", &state.params.serpentine, 0.0f, 1.0f)) needsUpdate = true;
-    if (ImGui::SliderFloat("Gamma", &state.params.gamma, 0.1f, 3.0f)) needsUpdate = true;
-    if (ImGui::SliderFloat("Contrast", &state.params.contrast, 0.0f, 3.0f)) needsUpdate = true;
-    if (ImGui::SliderFloat("Brightness", &state.params.brightness, -1.0f, 1.0f)) needsUpdate = true;
-    if (ImGui::SliderFloat("Saturation", &state.params.saturation, 0.0f, 2.0f)) needsUpdate = true;
+    // Only the general settings the algorithm actually reads
+    algorithmInfo = &Dithering::getAlgorithmInfo(state.params.algorithm);
+    if (algorithmInfo->usesStrength) {
+        if (ImGui::SliderFloat("Strength", &state.params.strength, 0.0f, 2.0f)) needsUpdate = true;
+    }
+
+    // With Auto on, the slider shows the Otsu threshold; dragging it takes over manually
+    if (algorithmInfo->usesThreshold) {
+        if (ImGui::SliderFloat("Threshold", &state.params.threshold, 0.0f, 1.0f)) {
+            state.params.autoThreshold = false;
+            needsUpdate = true;
+        }
+        ImGui::SameLine();
+        if (ImGui::Checkbox("Auto##Threshold", &state.params.autoThreshold)) needsUpdate = true;
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Pick the threshold with Otsu's method on the luma histogram");
+        }
+    }
+    if (algorithmInfo->usesSerpentine) {
+        if (ImGui::SliderFloat("Serpentine", &state.params.serpentine, 0.0f, 1.0f)) needsUpdate = true;
+    }
+    if (algorithmInfo->usesErrorEdges && ImGui::TreeNode("Advanced Diffusion")) {
+        const char* edges[] = {"Discard", "Reflect", "Wrap"};
+        int edge = static_cast<int>(state.params.errorEdge);
+        if (ImGui::Combo("Edges", &edge, edges, IM_ARRAYSIZE(edges))) {
+            state.params.errorEdge = static_cast<Dithering::ErrorEdge>(edge);
+            needsUpdate = true;
+        }
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Where error diffused past the left or right edge of the image goes");
+        }
+        if (ImGui::Checkbox("Clamp Error", &state.params.clampError)) needsUpdate = true;
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Drop error that pushes past black or white; off lets it ring on to the neighbors");
+        }
+        ImGui::TreePop();
+    }
+    if (ImGui::SliderFloat("Gamma", &state.params.gamma, 0.1f, 3.0f)) needsUpdate = true;
+    if (ImGui::TreeNode("Levels and Curve")) {
+        if (ImGui::SliderFloat("Black Point", &state.params.levelsBlack, 0.0f, 1.0f)) {
+            state.params.levelsWhite = std::max(state.params.levelsWhite, state.params.levelsBlack + 0.01f);
+            needsUpdate = true;
+        }
+        if (ImGui::SliderFloat("Midtones", &state.params.levelsMidtone, 0.1f, 10.0f, "%.2f",
+                               ImGuiSliderFlags_Logarithmic)) {
+            needsUpdate = true;
+        }
+        if (ImGui::IsItemHovered()) ImGui::SetTooltip("Above 1 lightens the midtones, below 1 darkens them");
+        if (ImGui::SliderFloat("White Point", &state.params.levelsWhite, 0.0f, 1.0f)) {
+            state.params.levelsBlack = std::min(state.params.levelsBlack, state.params.levelsWhite - 0.01f);
+            needsUpdate = true;
+        }
+        if (editToneCurve(state.params.toneCurve, state.curveDragPoint)) needsUpdate = true;
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Click to add a point, drag to move it, right click to remove it");
+        }
+        if (ImGui::Button("Reset Levels and Curve")) {
+            state.params.levelsBlack = 0.0f;
+            state.params.levelsMidtone = 1.0f;
+            state.params.levelsWhite = 1.0f;
+            state.params.toneCurve.clear();
+            needsUpdate = true;
+        }
+        ImGui::TreePop();
+    }
+    if (ImGui::SliderFloat("Contrast", &state.params.contrast, 0.0f, 3.0f)) needsUpdate = true;
+    if (ImGui::SliderFloat("Brightness", &state.params.brightness, -1.0f, 1.0f)) needsUpdate = true;
+    if (ImGui::SliderFloat("Saturation", &state.params.saturation, 0.0f, 2.0f)) needsUpdate = true;
+    if (ImGui::SliderFloat("Exposure", &state.params.exposure, -4.0f, 4.0f, "%.2f stops")) needsUpdate = true;
+    const char* denoiseMethods[] = {"None", "Median", "Bilateral"};
+    int denoise = static_cast<int>(state.params.denoise);
+    if (ImGui::Combo("Denoise", &denoise, denoiseMethods, IM_ARRAYSIZE(denoiseMethods))) {
+        state.params.denoise = static_cast<Dithering::Denoise>(denoise);
+        needsUpdate = true;
+    }
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Filter sensor noise before dithering, so it doesn't turn into speckle.\n"
+                          "Median suits specks, Bilateral smooths grain; both keep edges");
+    }
+    if (state.params.denoise != Dithering::Denoise::NONE) {
+        if (ImGui::SliderFloat("Denoise Strength", &state.params.denoiseStrength, 0.0f, 1.0f)) needsUpdate = true;
+    }
+    if (ImGui::SliderFloat("Blur", &state.params.blurRadius, 0.0f, 10.0f, "%.1f px")) needsUpdate = true;
+    if (ImGui::SliderFloat("Sharpen", &state.params.sharpenAmount, 0.0f, 4.0f)) needsUpdate = true;
+    if (state.params.sharpenAmount > 0.0f) {
+        if (ImGui::SliderFloat("Sharpen Radius", &state.params.sharpenRadius, 0.1f, 10.0f, "%.1f px")) {
+            needsUpdate = true;
+        }
+    }
+    if (ImGui::Checkbox("Linear Light", &state.params.linearize)) needsUpdate = true;
+    const char* distances[] = {"RGB", "Weighted RGB", "CIE76", "CIEDE2000"};
+    int distance = static_cast<int>(state.params.colorDistance);
+    if (ImGui::Combo("Color Distance", &distance, distances, IM_ARRAYSIZE(distances))) {
+        state.params.colorDistance = static_cast<Dithering::ColorDistance>(distance);
+        needsUpdate = true;
+    }
+    const char* workingSpaces[] = {"RGB", "Lab", "YCbCr", "HSL"};
+    int workingSpace = static_cast<int>(state.params.workingSpace);
+    if (ImGui::Combo("Working Space", &workingSpace, workingSpaces, IM_ARRAYSIZE(workingSpaces))) {
+        state.params.workingSpace = static_cast<Dithering::WorkingSpace>(workingSpace);
+        needsUpdate = true;
+    }
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Color space for matching and error diffusion.\n"
+                          "Lab reduces banding with small palettes");
+    }
+    const char* grayConversions[] = {"Rec. 601", "Rec. 709", "Average", "Red Channel", "Green Channel",
+                                     "Blue Channel", "Custom"};
+    int grayConversion = static_cast<int>(state.params.grayConversion);
+    if (ImGui::Combo("Gray Conversion", &grayConversion, grayConversions, IM_ARRAYSIZE(grayConversions))) {
+        state.params.grayConversion = static_cast<Dithering::GrayConversion>(grayConversion);
+        needsUpdate = true;
+    }
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("How colors turn gray with grayscale and monochrome palettes.\n"
+                          "Rec. 709 suits HD video, Average keeps saturated colors light,\n"
+                          "one channel works like a color filter on black and white film");
+    }
+    if (state.params.grayConversion == Dithering::GrayConversion::CUSTOM) {
+        if (ImGui::SliderFloat("Red Weight", &state.params.grayWeights[2], 0.0f, 1.0f)) needsUpdate = true;
+        if (ImGui::SliderFloat("Green Weight", &state.params.grayWeights[1], 0.0f, 1.0f)) needsUpdate = true;
+        if (ImGui::SliderFloat("Blue Weight", &state.params.grayWeights[0], 0.0f, 1.0f)) needsUpdate = true;
+    }
+    const char* roundingModes[] = {"Nearest", "Floor", "Ceil", "Stochastic"};
+    int rounding = static_cast<int>(state.params.rounding);
+    if (ImGui::Combo("Rounding", &rounding, roundingModes, IM_ARRAYSIZE(roundingModes))) {
+        state.params.rounding = static_cast<Dithering::RoundingMode>(rounding);
+        needsUpdate = true;
+    }
+    if (ImGui::SliderFloat("Clip Black %", &state.params.clipBlack, 0.0f, 5.0f)) needsUpdate = true;
+    if (ImGui::SliderFloat("Clip White %", &state.params.clipWhite, 0.0f, 5.0f)) needsUpdate = true;
+    if (ImGui::SliderFloat("Protect Highlights", &state.params.highlightProtect, 0.5f, 1.0f)) needsUpdate = true;
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Pixels brighter than this stay pure white (1 = off)");
+    }
+    if (ImGui::SliderFloat("Protect Shadows", &state.params.shadowProtect, 0.0f, 0.5f)) needsUpdate = true;
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Pixels darker than this stay pure black (0 = off)");
+    }
+    if (ImGui::SliderFloat("Dot Gain", &state.params.dotGain, 0.0f, 45.0f, "%.0f%%")) needsUpdate = true;
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Lightens midtones by the dot gain expected in print, e.g. 15-25%% for risograph (0 = off)");
+    }
+
+    // Bayer matrix size, preset by the algorithm picked
+    if (Dithering::getBayerSize(state.params.algorithm) > 0) {
+        if (ImGui::SliderInt("Matrix Size", &state.params.bayerSize, 2, Dithering::MAX_BAYER_SIZE)) {
+            needsUpdate = true;
+        }
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Sizes made of 2s and 3s (2, 3, 4, 6, 8, 9, 12, ...) are exact Bayer matrices");
+        }
+        if (ImGui::Checkbox("Clustered Dots", &state.params.bayerClustered)) needsUpdate = true;
+    }
+    if (algorithmInfo->usesPatternScale) {
+        if (ImGui::SliderFloat("Screen Rotation", &state.params.ditherAngle, 0.0f, 90.0f, "%.0f deg")) {
+            needsUpdate = true;
+        }
+    }
+
+    // Threshold matrix from the library
+    if (state.params.algorithm == Dithering::Algorithm::ORDERED_MATRIX) {
+        const Matrices::MatrixInfo* current = Matrices::findMatrix(state.params.matrix);
+        if (ImGui::BeginCombo("Matrix", current ? current->name.c_str() : state.params.matrix.c_str())) {
+            for (const auto& info : Matrices::getMatrixLibrary()) {
+                if (ImGui::Selectable(info.name.c_str(), current == &info)) {
+                    state.params.matrix = info.id;
+                    needsUpdate = true;
+                }
+                if (ImGui::IsItemHovered()) {
+                    ImGui::SetTooltip("%s (%dx%d)", info.description.c_str(), info.size, info.size);
+                }
+            }
+            ImGui::EndCombo();
+        }
+        if (current) {
+            ImGui::TextDisabled("%s", current->description.c_str());
+        }
+    }
+
+    // Local thresholding window and sensitivity
+    if (state.params.algorithm == Dithering::Algorithm::SAUVOLA ||
+        state.params.algorithm == Dithering::Algorithm::NIBLACK ||
+        state.params.algorithm == Dithering::Algorithm::BRADLEY) {
+        if (ImGui::SliderInt("Window", &state.params.localWindow, 3, 101)) needsUpdate = true;
+        if (ImGui::SliderFloat("k", &state.params.localK, 0.0f, 0.6f)) needsUpdate = true;
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Higher k lowers the threshold: cleaner background, thinner strokes");
+        }
+    }
+
+    if (state.params.algorithm == Dithering::Algorithm::STRUCTURE_AWARE) {
+        if (ImGui::SliderFloat("Edge Sensitivity", &state.params.edgeSensitivity, 0.0f, 4.0f)) needsUpdate = true;
+    }
+
+    // Riemersma pixel order and error memory
+    if (state.params.algorithm == Dithering::Algorithm::RIEMERSMA) {
+        const char* curves[] = {"Hilbert", "Peano", "Z-Order"};
+        int curve = static_cast<int>(state.params.curve);
+        if (ImGui::Combo("Curve", &curve, curves, IM_ARRAYSIZE(curves))) {
+            state.params.curve = static_cast<Dithering::CurveType>(curve);
+            needsUpdate = true;
+        }
+        if (ImGui::SliderInt("History", &state.params.riemersmaHistory, 1, 64)) needsUpdate = true;
+        if (ImGui::SliderFloat("Decay", &state.params.riemersmaDecay, 1.0f, 64.0f)) needsUpdate = true;
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Weight of the newest error relative to the oldest");
+        }
+    }
+
+    // Halftone screen
+    if (state.params.algorithm == Dithering::Algorithm::HALFTONE) {
+        const char* shapes[] = {"Circle", "Diamond"};
+        int shape = static_cast<int>(state.params.halftoneShape);
+        if (ImGui::Combo("Dot Shape", &shape, shapes, IM_ARRAYSIZE(shapes))) {
+            state.params.halftoneShape = static_cast<Dithering::HalftoneShape>(shape);
+            needsUpdate = true;
+        }
+        ImGui::BeginDisabled(state.params.screenLpi > 0.0f);
+        if (ImGui::SliderFloat("Cell Size", &state.params.halftoneCellSize, 3.0f, 32.0f)) needsUpdate = true;
+        ImGui::EndDisabled();
+
+        // Classic print angles: C 15, M 75, Y 0, K 45
+        if (ImGui::TreeNode("Screen Angles")) {
+            if (ImGui::SliderFloat("Cyan", &state.params.screenAngles[2], 0.0f, 90.0f, "%.0f deg")) needsUpdate = true;
+            if (ImGui::SliderFloat("Magenta", &state.params.screenAngles[1], 0.0f, 90.0f, "%.0f deg")) needsUpdate = true;
+            if (ImGui::SliderFloat("Yellow", &state.params.screenAngles[0], 0.0f, 90.0f, "%.0f deg")) needsUpdate = true;
+            if (ImGui::SliderFloat("Black (gray)", &state.params.grayScreenAngle, 0.0f, 90.0f, "%.0f deg")) needsUpdate = true;
+            ImGui::TreePop();
+        }
+    }
+
+    // Screen ruling in print units, for halftone and clustered Bayer cells
+    if (Dithering::usesScreenRuling(state.params)) {
+        if (ImGui::SliderFloat("Lines Per Inch", &state.params.screenLpi, 0.0f, 300.0f, "%.0f lpi")) {
+            needsUpdate = true;
+        }
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Size the cells for printing at the DPI below, e.g. 65-85 lpi for newsprint\n"
+                              "or 133-150 for magazines (0 = size them by hand)");
+        }
+        if (state.params.screenLpi > 0.0f) {
+            if (ImGui::SliderFloat("Print DPI", &state.params.printDpi, 72.0f, 2400.0f, "%.0f")) needsUpdate = true;
+            Dithering::Parameters ruled = state.params;
+            std::vector<std::string> warnings;
+            Dithering::applyScreenRuling(ruled, &warnings);
+            if (state.params.algorithm == Dithering::Algorithm::HALFTONE) {
+                ImGui::TextDisabled("Cell: %.1f px", ruled.halftoneCellSize);
+            } else {
+                ImGui::TextDisabled("Pattern scale: %.0fx", ruled.ditherScale);
+            }
+            for (const auto& warning : warnings) {
+                ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.8f, 0.3f, 1.0f));
+                ImGui::TextWrapped("%s", warning.c_str());
+                ImGui::PopStyleColor();
+            }
+        }
+    }
+
+    // Stipple dots
+    if (state.params.algorithm == Dithering::Algorithm::STIPPLE) {
+        if (ImGui::SliderInt("Dots", &state.params.stippleDots, 100, 50000)) needsUpdate = true;
+        if (ImGui::SliderFloat("Dot Size", &state.params.stippleDotSize, 0.5f, 8.0f, "%.1f px")) needsUpdate = true;
+        if (ImGui::SliderInt("Relaxation Steps", &state.params.stippleIterations, 0, 100)) needsUpdate = true;
+    }
+
+    // User threshold matrix for the pattern algorithm
+    if (state.params.algorithm == Dithering::Algorithm::PATTERN_DITHER) {
+        ImGui::Text("Pattern Matrix (e.g. [[0,2],[3,1]])");
+        ImGui::InputText("##PatternMatrix", state.patternMatrixInput, sizeof(state.patternMatrixInput));
+        ImGui::SameLine();
+        if (ImGui::Button("Apply")) {
+            if (Matrices::parseMatrix(state.patternMatrixInput, state.params.patternMatrix)) {
+                needsUpdate = true;
+            } else {
+                std::cerr << "Invalid pattern matrix" << std::endl;
+            }
+        }
+        if (ImGui::Button("Load Matrix Image")) {
+            std::string filepath = Platform::openFileDialog();
+            if (!filepath.empty() && Matrices::loadMatrixImage(filepath, state.params.patternMatrix)) {
+                needsUpdate = true;
+            }
+        }
+        if (!state.params.patternMatrix.empty()) {
+            ImGui::SameLine();
+            if (ImGui::Button("Use Built-in 4x4")) {
+                state.params.patternMatrix = cv::Mat();
+                needsUpdate = true;
+            }
+            ImGui::TextDisabled("Custom %dx%d matrix", state.params.patternMatrix.cols,
+                                state.params.patternMatrix.rows);
+        }
+    }
+
+    // Pre-made blue noise texture instead of the generated one
+    if (state.params.algorithm == Dithering::Algorithm::BLUE_NOISE) {
+        cv::Mat texture;
+        if (ImGui::Button("Load Noise Texture")) {
+            std::string filepath = Platform::openFileDialog();
+            if (!filepath.empty()) {
+                state.noiseTextureError.clear();
+                if (Matrices::loadNoiseTexture(filepath, texture, state.noiseTextureError)) {
+                    state.params.noiseTexture = filepath;
+                    needsUpdate = true;
+                }
+            }
+        }
+        if (!state.params.noiseTexture.empty()) {
+            ImGui::SameLine();
+            if (ImGui::Button("Use Generated")) {
+                state.params.noiseTexture.clear();
+                needsUpdate = true;
+            }
+        }
+
+        // Loaded textures are cached, so checking every frame is cheap
+        std::string error;
+        if (!state.noiseTextureError.empty()) {
+            ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", state.noiseTextureError.c_str());
+        }
+        if (state.params.noiseTexture.empty()) {
+            ImGui::TextDisabled("Generated 64x64 texture");
+        } else if (Matrices::loadNoiseTexture(state.params.noiseTexture, texture, error)) {
+            std::string name = std::filesystem::path(state.params.noiseTexture).filename().string();
+            ImGui::TextDisabled("%s, %dx%d", name.c_str(), texture.cols, texture.rows);
+        } else {
+            ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s, using the generated texture", error.c_str());
+        }
+    }
+
+    // User error diffusion kernel
+    if (state.params.algorithm == Dithering::Algorithm::CUSTOM_KERNEL) {
+        ImGui::Text("Kernel as [dx, dy, weight] triples");
+        ImGui::InputText("##Kernel", state.kernelInput, sizeof(state.kernelInput));
+        ImGui::SameLine();
+        if (ImGui::Button("Apply##Kernel")) {
+            std::vector<Dithering::KernelWeight> kernel;
+            state.kernelError.clear();
+            if (Dithering::parseKernel(state.kernelInput, kernel, state.kernelError) &&
+                Dithering::validateKernel(kernel, state.params.normalizeKernel, state.kernelError)) {
+                state.params.diffusionKernel = kernel;
+                needsUpdate = true;
+            }
+        }
+        if (ImGui::Checkbox("Normalize Weights", &state.params.normalizeKernel)) {
+            std::string error;
+            if (!state.params.normalizeKernel &&
+                !Dithering::validateKernel(state.params.diffusionKernel, false, error)) {
+                state.params.normalizeKernel = true;
+                state.kernelError = error;
+            } else {
+                needsUpdate = true;
+            }
+        }
+        if (!state.kernelError.empty()) {
+            ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", state.kernelError.c_str());
+        } else if (state.params.diffusionKernel.empty()) {
+            ImGui::TextDisabled("Using Floyd-Steinberg until a kernel is applied");
+        } else {
+            ImGui::TextDisabled("%d weights", static_cast<int>(state.params.diffusionKernel.size()));
+        }
+    }
+
+    // Threshold pattern offset, for ordered algorithms
+    if (algorithmInfo->usesPhase) {
+        if (ImGui::SliderInt("Phase X", &state.params.phaseX, 0, 15)) needsUpdate = true;
+        if (ImGui::SliderInt("Phase Y", &state.params.phaseY, 0, 15)) needsUpdate = true;
+    }
+
+    // Chunky threshold patterns for pixel art
+    if (algorithmInfo->usesPatternScale) {
+        int scale = std::max(1, static_cast<int>(state.params.ditherScale + 0.5f));
+        if (ImGui::SliderInt("Pattern Scale", &scale, 1, Dithering::MAX_PATTERN_SCALE)) {
+            state.params.ditherScale = static_cast<float>(scale);
+            needsUpdate = true;
+        }
+    }
+
+    // Pixel-art look: dither small, scale up with hard edges
+    if (ImGui::SliderInt("Pixelate", &state.params.pixelate, 1, 32, "%dx")) needsUpdate = true;
+    if (ImGui::SliderFloat("Blend", &state.params.blend, 0.0f, 1.0f)) needsUpdate = true;
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Mix the adjusted image back in, leaving a dither texture\n"
+                          "over it instead of full quantization (0 = off)");
+    }
+
+    const char* backends[] = {"Auto", "CPU", "GPU (OpenCL)"};
+    int backend = static_cast<int>(state.params.backend);
+    if (ImGui::Combo("Backend", &backend, backends, IM_ARRAYSIZE(backends))) {
+        state.params.backend = static_cast<Dithering::Backend>(backend);
+        needsUpdate = true;
+    }
+    if (ImGui::IsItemHovered()) {
+        ImGui::SetTooltip("Ordered and threshold algorithms with grayscale palettes can run on the GPU.\n"
+                          "Auto uses it for images from about 1080p up");
+    }
+
+    bool usesSeed = algorithmInfo->usesSeed || state.params.rounding == Dithering::RoundingMode::STOCHASTIC ||
+                    state.params.paletteMode == Dithering::PaletteMode::ADAPTIVE ||
+                    Dithering::hasChannelOverrides(state.params);
+    if (usesSeed) {
+        if (ImGui::SliderInt("Random Seed", reinterpret_cast<int*>(&state.params.seed), 0, 1000)) needsUpdate = true;
+    }
+    if (usesSeed && state.isVideo) {
+        ImGui::Checkbox("New Seed Every Frame", &state.videoOptions.varySeed);
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Off: noise algorithms repeat the same pattern on every frame");
+        }
+    }
+    if (state.isVideo) {
+        ImGui::Checkbox("Shift Pattern Every Frame", &state.videoOptions.cycleMatrix);
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Moves ordered, blue noise and halftone patterns each frame so they average\n"
+                              "out over time. Off: the pattern stays put, which flickers least");
+        }
+        ImGui::SliderFloat("Temporal Error Carry", &state.videoOptions.temporalCarry, 0.0f, 1.0f, "%.2f");
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Experimental: part of each frame's quantization error is added to the next.\n"
+                              "Frames are then dithered one at a time");
+        }
+    }
+
+    // Per-channel overrides: each plane dithered on its own with its own settings
+    if (ImGui::TreeNode("Per-Channel Settings")) {
+        const char* spaces[] = {"RGB", "YCbCr (luma/chroma)"};
+        int space = static_cast<int>(state.params.channelSpace);
+        if (ImGui::Combo("Planes", &space, spaces, IM_ARRAYSIZE(spaces))) {
+            state.params.channelSpace = static_cast<Dithering::ChannelSpace>(space);
+            needsUpdate = true;
+        }
+        if (ImGui::SliderInt("Levels per Plane", &state.params.levels, 2, 16)) needsUpdate = true;
 
-    if (state.selectedAlgorithm >= 8 && state.selectedAlgorithm <= 11) {
-        int bayerSizes[] = {2, 4, 8, 16};
-        state.params.bayerSize = bayerSizes[state.selectedAlgorithm - 8];
+        // RGB planes are listed R, G, B rather than in storage order
+        const int order[] = {2, 1, 0};
+        for (int i = 0; i < 3; ++i) {
+            int c = state.params.channelSpace == Dithering::ChannelSpace::YCRCB ? i : order[i];
+            Dithering::ChannelOverride& channel = state.params.channelOverrides[c];
+            ImGui::PushID(c);
+            std::string name = Dithering::getChannelName(state.params.channelSpace, c);
+            if (ImGui::Checkbox(name.c_str(), &channel.enabled)) needsUpdate = true;
+            if (channel.enabled) {
+                std::vector<const char*> algorithms;
+                for (const auto& info : Dithering::getAlgorithmLibrary()) algorithms.push_back(info.name.c_str());
+                int algorithm = static_cast<int>(channel.algorithm);
+                if (ImGui::Combo("Algorithm", &algorithm, algorithms.data(), static_cast<int>(algorithms.size()))) {
+                    channel.algorithm = static_cast<Dithering::Algorithm>(algorithm);
+                    needsUpdate = true;
+                }
+                if (ImGui::SliderFloat("Strength", &channel.strength, 0.0f, 2.0f)) needsUpdate = true;
+                if (ImGui::SliderFloat("Threshold", &channel.threshold, 0.0f, 1.0f)) needsUpdate = true;
+            }
+            ImGui::PopID();
+        }
+        if (Dithering::hasChannelOverrides(state.params)) {
+            ImGui::TextDisabled("Planes are dithered separately; the palette is not used");
+        }
+        ImGui::TreePop();
     }
 
-    if (ImGui::SliderInt("Random Seed", reinterpret_cast<int*>(&state.params.seed), 0, 1000)) needsUpdate = true;
+    // Extra passes over the result, e.g. blur then dither again with another algorithm
+    if (ImGui::TreeNode("Stages")) {
+        auto& stages = state.params.stages;
+        const char* types[] = {"Dither", "Blur", "Sharpen"};
+        int remove = -1;
+        for (size_t i = 0; i < stages.size(); ++i) {
+            Dithering::Stage& stage = stages[i];
+            ImGui::PushID(static_cast<int>(i));
+            int type = static_cast<int>(stage.type);
+            ImGui::SetNextItemWidth(120);
+            if (ImGui::Combo("##Type", &type, types, IM_ARRAYSIZE(types))) {
+                stage.type = static_cast<Dithering::StageType>(type);
+                needsUpdate = true;
+            }
+            ImGui::SameLine();
+            if (ImGui::SmallButton("Remove")) remove = static_cast<int>(i);
+
+            if (stage.type == Dithering::StageType::DITHER) {
+                if (stage.params) {
+                    std::string algorithm = Dithering::getAlgorithmName(stage.params->algorithm);
+                    ImGui::TextDisabled("Own settings: %s", algorithm.c_str());
+                    ImGui::SameLine();
+                    if (ImGui::SmallButton("Use Main")) {
+                        stage.params.reset();
+                        needsUpdate = true;
+                    }
+                } else {
+                    ImGui::TextDisabled("Same settings as the main pass");
+                }
+                // Snapshot of the current settings; change them afterwards for the main pass
+                if (ImGui::SmallButton("Copy Current Settings")) {
+                    auto params = std::make_shared<Dithering::Parameters>(state.params);
+                    params->stages.clear();
+                    stage.params = params;
+                    needsUpdate = true;
+                }
+            } else {
+                float maximum = stage.type == Dithering::StageType::BLUR ? 10.0f : 4.0f;
+                if (ImGui::SliderFloat("Amount", &stage.amount, 0.0f, maximum)) needsUpdate = true;
+            }
+            if (ImGui::SliderFloat("Mix", &stage.mix, 0.0f, 1.0f)) needsUpdate = true;
+            ImGui::Separator();
+            ImGui::PopID();
+        }
+        if (remove >= 0) {
+            stages.erase(stages.begin() + remove);
+            needsUpdate = true;
+        }
+        if (ImGui::Button("Add Stage") && stages.size() < 16) {
+            stages.push_back(Dithering::Stage());
+            needsUpdate = true;
+        }
+        ImGui::TreePop();
+    }
 
     if (needsUpdate && state.autoUpdate) {
         processImage(state);
@@ -337,12 +3143,34 @@ void renderGUI(AppState& state) {
                 );
             }
         }
+        state.originalAlpha.release();
+        state.preciseImage.release();
         state.imageLoaded = true;
         state.currentFile = "test_gradient.png";
+        state.loadWarnings.clear();
         updateTexture(state.originalTexture, state.originalImage);
         processImage(state);
     }
 
+    // Open an image from a pasted http(s) link
+    ImGui::BeginDisabled(state.download != nullptr);
+    ImGui::SetNextItemWidth(-90);
+    bool openUrl = ImGui::InputText("##url", state.urlInput, sizeof(state.urlInput),
+                                    ImGuiInputTextFlags_EnterReturnsTrue);
+    ImGui::SameLine();
+    openUrl |= ImGui::Button("Open URL", ImVec2(-1, 0));
+    ImGui::EndDisabled();
+    if (openUrl && Platform::isRemoteUrl(state.urlInput)) {
+        loadImage(state, state.urlInput);
+    }
+    if (state.download) {
+        size_t received = state.download->received;
+        size_t total = state.download->total;
+        std::string label = "Downloading " + ImageIO::formatFileSize(received);
+        if (total > 0) label += " of " + ImageIO::formatFileSize(total);
+        ImGui::ProgressBar(total > 0 ? static_cast<float>(received) / total : 0.0f, ImVec2(-1, 0), label.c_str());
+    }
+
     if (state.imageLoaded && ImGui::Button("Reset Parameters", ImVec2(-1, 30))) {
         state.params = Dithering::Parameters();
         state.selectedAlgorithm = 0;
@@ -354,15 +3182,61 @@ void renderGUI(AppState& state) {
 
     // Stats
     ImGui::Text("Statistics");
+    if (!state.loadError.empty()) {
+        ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "Could not open the image");
+        ImGui::TextWrapped("%s", state.loadError.c_str());
+    }
     if (state.imageLoaded) {
         ImGui::Text("Image: %dx%d", state.originalImage.cols, state.originalImage.rows);
         ImGui::Text("Processing time: %.2f ms", state.processingTime);
+        if (state.savedFileSize > 0) {
+            ImGui::Text("Saved file: %s", ImageIO::formatFileSize(state.savedFileSize).c_str());
+        }
+        if (state.previewIsProxy) {
+            ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.8f, 0.3f, 1.0f));
+            ImGui::TextWrapped("Preview uses %s for speed; export uses %s",
+                               Dithering::getAlgorithmName(Dithering::getPreviewProxy(state.params.algorithm)).c_str(),
+                               Dithering::getAlgorithmName(state.params.algorithm).c_str());
+            ImGui::PopStyleColor();
+        }
+        if (state.previewIsDownscaled) {
+            ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.8f, 0.3f, 1.0f));
+            ImGui::TextWrapped("Approximate preview at %dx%d; export is full size",
+                               state.processedImage.cols, state.processedImage.rows);
+            ImGui::PopStyleColor();
+        }
+        for (const auto& warning : state.loadWarnings) {
+            ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(1.0f, 0.8f, 0.3f, 1.0f));
+            ImGui::TextWrapped("%s", warning.message.c_str());
+            ImGui::PopStyleColor();
+        }
+
+        // Scopes of the dithered result
+        if (!state.levelsHistogram.empty()) {
+            float width = ImGui::GetContentRegionAvail().x;
+            ImGui::PlotHistogram("##Levels", state.levelsHistogram.data(),
+                                 static_cast<int>(state.levelsHistogram.size()), 0, nullptr,
+                                 0.0f, FLT_MAX, ImVec2(width, 50));
+            if (state.waveformTexture) {
+                ImGui::Image((void*)(intptr_t)state.waveformTexture, ImVec2(width, 50));
+            }
+            ImGui::Text("Clipped: %.1f%% black, %.1f%% white", state.clippedBlack, state.clippedWhite);
+            ImGui::Text("Ink density: %.0f%%", state.inkDensity);
+        }
     }
 
-    if (state.videoProcessing) {
-        ImGui::Text("Processing video...");
-        ImGui::ProgressBar(state.videoProgress);
-        ImGui::Text("Frame %d / %d", state.currentFrame, state.totalFrames);
+    if (state.job) {
+        std::string stage;
+        {
+            std::lock_guard<std::mutex> lock(state.job->stageMutex);
+            stage = state.job->stage;
+        }
+        ImGui::Text("%s...", stage.c_str());
+        ImGui::ProgressBar(state.job->progress);
+        if (ImGui::Button("Cancel", ImVec2(-1, 0))) {
+            state.job->cancel = true;
+            state.restartPending = false;
+        }
     }
 
     ImGui::End();
@@ -370,9 +3244,16 @@ void renderGUI(AppState& state) {
     // Right panel - Image display
     ImGui::SetNextWindowPos(ImVec2(400, 20));
     ImGui::SetNextWindowSize(ImVec2(io.DisplaySize.x - 400, io.DisplaySize.y - 20));
-    ImGui::Begin("Preview", nullptr, ImGuiWindowFlags_NoMove | ImGuiWindowFlags_NoResize);
+    // With the magnifier on, the wheel zooms it instead of scrolling the preview
+    ImGuiWindowFlags previewFlags = state.magnifier ? ImGuiWindowFlags_NoScrollWithMouse : ImGuiWindowFlags_None;
+    ImGui::Begin("Preview", nullptr, ImGuiWindowFlags_NoMove | ImGuiWindowFlags_NoResize | previewFlags);
 
     if (state.imageLoaded) {
+        if (state.webcam) {
+            std::lock_guard<std::mutex> lock(state.webcam->mutex);
+            ImGui::Text("Live: webcam %d, %dx%d, %.1f fps", state.webcamDevice, state.processedImage.cols,
+                        state.processedImage.rows, state.webcam->fps);
+        }
         ImVec2 availSize = ImGui::GetContentRegionAvail();
 
         if (state.splitView) {
@@ -380,24 +3261,27 @@ void renderGUI(AppState& state) {
             float halfWidth = availSize.x * 0.5f - 10;
 
             if (state.showOriginal && state.originalTexture) {
-                ImGui::BeginChild("Original", ImVec2(halfWidth, availSize.y), true);
+                ImGui::BeginChild("Original", ImVec2(halfWidth, availSize.y), true, previewFlags);
                 ImGui::Text("Original");
                 float scale = std::min(halfWidth / state.originalImage.cols,
                                      (availSize.y - 30) / state.originalImage.rows);
                 ImVec2 imgSize(state.originalImage.cols * scale, state.originalImage.rows * scale);
                 ImGui::Image((void*)(intptr_t)state.originalTexture, imgSize);
+                if (state.magnifier && ImGui::IsItemHovered()) showMagnifier(state, imgSize);
                 ImGui::EndChild();
             }
 
             ImGui::SameLine();
 
             if (state.showProcessed && state.processedTexture) {
-                ImGui::BeginChild("Processed", ImVec2(halfWidth, availSize.y), true);
+                ImGui::BeginChild("Processed", ImVec2(halfWidth, availSize.y), true, previewFlags);
                 ImGui::Text("Dithered");
                 float scale = std::min(halfWidth / state.processedImage.cols,
                                      (availSize.y - 30) / state.processedImage.rows);
                 ImVec2 imgSize(state.processedImage.cols * scale, state.processedImage.rows * scale);
+                selectPreviewLevel(state, scale);
                 ImGui::Image((void*)(intptr_t)state.processedTexture, imgSize);
+                if (state.magnifier && ImGui::IsItemHovered()) showMagnifier(state, imgSize);
                 ImGui::EndChild();
             }
         } else {
@@ -414,7 +3298,9 @@ void renderGUI(AppState& state) {
                     cursorPos.y + (availSize.y - imgSize.y) * 0.5f
                 ));
 
+                selectPreviewLevel(state, scale);
                 ImGui::Image((void*)(intptr_t)state.processedTexture, imgSize);
+                if (state.magnifier && ImGui::IsItemHovered()) showMagnifier(state, imgSize);
             }
         }
     } else {
@@ -439,9 +3325,843 @@ void renderGUI(AppState& state) {
         ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.6f, 0.8f, 1.0f, 1.0f));
         ImGui::TextWrapped("Supported: PNG, JPEG, BMP, TIFF, WebP");
         ImGui::PopStyleColor();
+
+        // Previous work, from the thumbnail cache so it shows at once
+        if (!state.recentFiles.empty()) {
+            const float cellWidth = 140.0f;
+            int columns = std::max(1, static_cast<int>((availSize.x - 40) / cellWidth));
+            ImGui::Dummy(ImVec2(0, 20));
+            ImGui::SetCursorPosX(20);
+            ImGui::Text("Recent files");
+            ImGui::SameLine();
+            if (ImGui::SmallButton("Clear")) clearRecentFiles(state);
+
+            std::string open;
+            for (size_t i = 0; i < state.recentFiles.size(); ++i) {
+                const auto& recent = state.recentFiles[i];
+                if (i % columns == 0) {
+                    ImGui::SetCursorPosX(20);
+                } else {
+                    ImGui::SameLine();
+                }
+
+                ImGui::PushID(static_cast<int>(i));
+                ImGui::BeginGroup();
+                if (state.recentTextures[i]) {
+                    const cv::Size& size = state.recentSizes[i];
+                    ImGui::Image((void*)(intptr_t)state.recentTextures[i], ImVec2(size.width, size.height));
+                } else {
+                    ImGui::Dummy(ImVec2(RecentFiles::THUMBNAIL_SIZE, RecentFiles::THUMBNAIL_SIZE));
+                }
+                bool clicked = ImGui::IsItemClicked();
+                std::string name = std::filesystem::path(recent.path).filename().string();
+                ImGui::PushTextWrapPos(ImGui::GetCursorPosX() + RecentFiles::THUMBNAIL_SIZE);
+                ImGui::TextDisabled("%s", name.c_str());
+                ImGui::PopTextWrapPos();
+                ImGui::EndGroup();
+                if (ImGui::IsItemHovered()) ImGui::SetTooltip("%s", recent.path.c_str());
+                ImGui::PopID();
+                if (clicked) open = recent.path;
+            }
+            if (!open.empty()) loadImage(state, open);
+        }
     }
 
     ImGui::End();
+
+    // Job queue: background exports with pause, reordering and cancel
+    if (state.showQueue) {
+        ImGui::SetNextWindowSize(ImVec2(420, 300), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Job Queue", &state.showQueue);
+
+        bool paused = state.queue.isPaused();
+        if (ImGui::Button(paused ? "Resume" : "Pause")) {
+            if (paused) {
+                state.queue.resume();
+            } else {
+                state.queue.pause();
+            }
+        }
+        ImGui::SameLine();
+        if (ImGui::Button("Clear Finished")) {
+            state.queue.clearFinished();
+        }
+        ImGui::Separator();
+
+        std::vector<Queue::JobInfo> jobs = state.queue.snapshot();
+        if (jobs.empty()) {
+            ImGui::TextDisabled("No jobs. File > Add to Queue... renders in the background.");
+        }
+        int pending = 0;
+        for (const auto& job : jobs) {
+            ImGui::PushID(job.id);
+            ImGui::Text("%s", job.name.c_str());
+            if (job.state == Queue::JobState::RUNNING) {
+                std::string label = paused ? "Paused" : job.stage;
+                ImGui::ProgressBar(job.progress, ImVec2(-1, 0), label.c_str());
+            } else {
+                ImGui::SameLine();
+                ImGui::TextDisabled("%s", Queue::getJobStateName(job.state).c_str());
+            }
+            if (job.state == Queue::JobState::FAILED && !job.error.message.empty()) {
+                ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", job.error.message.c_str());
+                std::string hint = Errors::getErrorHint(job.error.code);
+                if (!hint.empty()) ImGui::TextDisabled("%s", hint.c_str());
+            }
+            if (job.state == Queue::JobState::PENDING) {
+                if (ImGui::Button("Up") && pending > 0) state.queue.move(job.id, pending - 1);
+                ImGui::SameLine();
+                if (ImGui::Button("Down")) state.queue.move(job.id, pending + 1);
+                ImGui::SameLine();
+                ++pending;
+            }
+            if (job.state == Queue::JobState::PENDING || job.state == Queue::JobState::RUNNING) {
+                if (ImGui::Button("Cancel")) state.queue.cancel(job.id);
+            }
+            ImGui::Separator();
+            ImGui::PopID();
+        }
+        ImGui::End();
+    }
+
+    // Poster export: sheet layout and PDF
+    if (state.showPosterExport) {
+        ImGui::SetNextWindowSize(ImVec2(340, 260), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export Poster PDF", &state.showPosterExport);
+        Poster::Options& options = state.posterOptions;
+
+        const Poster::PaperSize* paper = Poster::findPaperSize(options.paper);
+        if (ImGui::BeginCombo("Paper", paper ? paper->name.c_str() : options.paper.c_str())) {
+            for (const auto& size : Poster::getPaperSizes()) {
+                if (ImGui::Selectable(size.name.c_str(), paper == &size)) {
+                    options.paper = size.id;
+                }
+            }
+            ImGui::EndCombo();
+        }
+        ImGui::Checkbox("Landscape", &options.landscape);
+        float width = static_cast<float>(options.posterWidthInches);
+        if (ImGui::SliderFloat("Poster Width", &width, 0.0f, 120.0f, width > 0.0f ? "%.1f in" : "Native")) {
+            options.posterWidthInches = width;
+        }
+        float dpi = static_cast<float>(options.dpi);
+        if (ImGui::SliderFloat("DPI", &dpi, 72.0f, 600.0f, "%.0f")) options.dpi = dpi;
+        float overlap = static_cast<float>(options.overlapInches);
+        if (ImGui::SliderFloat("Overlap", &overlap, 0.0f, 1.0f, "%.2f in")) options.overlapInches = overlap;
+        ImGui::Checkbox("Crop Marks", &options.cropMarks);
+
+        ImGui::Separator();
+        Poster::Layout layout;
+        if (state.imageLoaded && Poster::computeLayout(state.originalImage.size(), options, layout)) {
+            ImGui::Text("%d x %d sheets (%d pages)", layout.columns, layout.rows, layout.pages());
+            ImGui::Text("Poster: %.1f x %.1f in", layout.posterPixels.width / options.dpi,
+                        layout.posterPixels.height / options.dpi);
+            if (ImGui::Button("Export...", ImVec2(-1, 0))) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) queuePoster(state, filepath);
+            }
+        } else {
+            ImGui::TextWrapped("Overlap and margins don't fit on this paper, or the poster is too large.");
+        }
+
+        ImGui::End();
+    }
+
+    // Print export: the image on one page at its physical size
+    if (state.showPrintExport) {
+        ImGui::SetNextWindowSize(ImVec2(340, 220), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export Print PDF", &state.showPrintExport);
+        Poster::PrintOptions& options = state.printOptions;
+
+        const Poster::PaperSize* paper = Poster::findPaperSize(options.paper);
+        if (ImGui::BeginCombo("Paper", paper ? paper->name.c_str() : options.paper.c_str())) {
+            for (const auto& size : Poster::getPaperSizes()) {
+                if (ImGui::Selectable(size.name.c_str(), paper == &size)) {
+                    options.paper = size.id;
+                }
+            }
+            ImGui::EndCombo();
+        }
+        ImGui::Checkbox("Landscape", &options.landscape);
+        // Shared with the screen ruling, so the cells come out at the LPI asked for
+        options.dpi = state.params.printDpi;
+        if (ImGui::SliderFloat("DPI", &state.params.printDpi, 72.0f, 1200.0f, "%.0f")) {
+            options.dpi = state.params.printDpi;
+            if (state.params.screenLpi > 0.0f) processImage(state);
+        }
+        bool vector = Halftone::supportsVectorHalftone(state.params);
+        if (vector) ImGui::Checkbox("Vector Dots", &state.printVector);
+
+        ImGui::Separator();
+        double widthInches = 0.0, heightInches = 0.0;
+        bool fits = state.imageLoaded &&
+                    Poster::computePrintSize(state.originalImage.size(), options, widthInches, heightInches);
+        ImGui::Text("Printed size: %.2f x %.2f in", widthInches, heightInches);
+        if (fits) {
+            if (ImGui::Button("Export...", ImVec2(-1, 0))) {
+                std::string filepath = Platform::saveFileDialog();
+                if (!filepath.empty()) queuePrintPdf(state, filepath, vector && state.printVector);
+            }
+        } else if (state.imageLoaded) {
+            ImGui::TextWrapped("Too big for this paper. Raise the DPI or use Export Poster PDF.");
+        }
+
+        ImGui::End();
+    }
+
+    // Pen plotter export: drawing size, pen moves and dot order
+    if (state.showPlotterExport) {
+        ImGui::SetNextWindowSize(ImVec2(340, 240), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export Plotter Dots", &state.showPlotterExport);
+        Plotter::Options& options = state.plotterOptions;
+
+        float width = static_cast<float>(options.widthMm);
+        if (ImGui::SliderFloat("Width", &width, 20.0f, 1000.0f, "%.0f mm")) options.widthMm = width;
+        ImGui::Checkbox("Shorten Pen Travel", &options.shortenTravel);
+        float penUp = static_cast<float>(options.penUpZ), penDown = static_cast<float>(options.penDownZ);
+        if (ImGui::SliderFloat("Pen Up Z", &penUp, -10.0f, 10.0f, "%.1f mm")) options.penUpZ = penUp;
+        if (ImGui::SliderFloat("Pen Down Z", &penDown, -10.0f, 10.0f, "%.1f mm")) options.penDownZ = penDown;
+        float feed = static_cast<float>(options.feedRate);
+        if (ImGui::SliderFloat("Feed Rate", &feed, 100.0f, 10000.0f, "%.0f mm/min")) options.feedRate = feed;
+
+        ImGui::Separator();
+        ImGui::TextDisabled("CSV, JSON, G-code (.gcode, .nc) or HPGL (.hpgl, .plt), by extension");
+        if (ImGui::Button("Export...", ImVec2(-1, 0))) {
+            std::string filepath = Platform::saveFileDialog();
+            if (!filepath.empty()) queuePlotterDots(state, filepath);
+        }
+        std::string summary = state.plotterSummary->get();
+        if (!summary.empty()) ImGui::TextWrapped("%s", summary.c_str());
+
+        ImGui::End();
+    }
+
+    // Embedded display export: byte layout, bit order and C array name
+    if (state.showEmbeddedExport) {
+        ImGui::SetNextWindowSize(ImVec2(360, 220), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export for Display", &state.showEmbeddedExport);
+        Embedded::Options& options = state.embeddedOptions;
+
+        if (ImGui::BeginCombo("Layout", Embedded::getLayoutName(options.layout).c_str())) {
+            for (const auto& id : Embedded::getLayoutIds()) {
+                Embedded::Layout layout;
+                Embedded::parseLayout(id, layout);
+                if (ImGui::Selectable(Embedded::getLayoutName(layout).c_str(), options.layout == layout)) {
+                    options.layout = layout;
+                }
+            }
+            ImGui::EndCombo();
+        }
+        int bitOrder = static_cast<int>(options.bitOrder);
+        const char* bitOrders[] = {"Default", "MSB First", "LSB First"};
+        if (ImGui::Combo("Bit Order", &bitOrder, bitOrders, 3)) {
+            options.bitOrder = static_cast<Embedded::BitOrder>(bitOrder);
+        }
+        ImGui::Checkbox("Invert (bits set for dark pixels)", &options.invert);
+        ImGui::InputText("Array Name", state.embeddedName, sizeof(state.embeddedName));
+        ImGui::TextDisabled("Empty name: taken from the file name");
+
+        ImGui::Separator();
+        if (ImGui::Button("Export...", ImVec2(-1, 0))) {
+            std::string filepath = Platform::saveFileDialog();
+            if (!filepath.empty()) {
+                options.name = state.embeddedName;
+                queueEmbedded(state, filepath);
+            }
+        }
+        std::string summary = state.embeddedSummary->get();
+        if (!summary.empty()) ImGui::TextWrapped("%s", summary.c_str());
+
+        ImGui::End();
+    }
+
+    // ASCII art: options, a preview in the (monospaced) UI font, and export
+    if (state.showAsciiExport) {
+        ImGui::SetNextWindowSize(ImVec2(520, 480), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export ASCII Art", &state.showAsciiExport);
+        Ascii::Options& options = state.asciiOptions;
+
+        bool changed = ImGui::SliderInt("Columns", &options.columns, 20, 300);
+        if (ImGui::InputText("Ramp", state.asciiRamp, sizeof(state.asciiRamp)) &&
+            std::string(state.asciiRamp).size() >= 2) {
+            options.ramp = state.asciiRamp;
+            changed = true;
+        }
+        changed |= ImGui::Checkbox("Braille (2x4 dots per character)", &options.braille);
+        changed |= ImGui::Checkbox("Invert (light text on dark)", &options.invert);
+        changed |= ImGui::SliderFloat("Character Aspect", &options.charAspect, 1.0f, 3.0f, "%.2f");
+        if (changed) updateAsciiPreview(state);
+
+        if (ImGui::Button("Save Text...")) {
+            std::string filepath = Platform::saveFileDialog();
+            if (!filepath.empty() && !Ascii::exportText(state.asciiLines, filepath)) {
+                std::cerr << "Failed to save ASCII art" << std::endl;
+            }
+        }
+        ImGui::SameLine();
+        if (ImGui::Button("Save Image...")) {
+            std::string filepath = Platform::saveFileDialog();
+            cv::Mat rendered = Ascii::renderText(state.asciiLines, options, state.params.inkColor,
+                                                 state.params.paperColor);
+            ImageIO::ExportOptions exportOptions;
+            exportOptions.encode = state.encodeOptions;
+            if (!filepath.empty() && !ImageIO::saveImage(filepath, rendered, cv::Mat(), exportOptions)) {
+                std::cerr << "Failed to save ASCII art image" << std::endl;
+            }
+        }
+        ImGui::SameLine();
+        if (ImGui::Button("Copy Text")) {
+            ImGui::SetClipboardText(Ascii::joinLines(state.asciiLines).c_str());
+        }
+
+        ImGui::Separator();
+        ImGui::BeginChild("##AsciiPreview", ImVec2(0, 0), false, ImGuiWindowFlags_HorizontalScrollbar);
+        if (options.braille && !state.asciiRendered.empty()) {
+            ImGui::Image((void*)(intptr_t)state.asciiTexture,
+                         ImVec2(static_cast<float>(state.asciiRendered.cols),
+                                static_cast<float>(state.asciiRendered.rows)));
+        } else {
+            for (const auto& line : state.asciiLines) ImGui::TextUnformatted(line.c_str());
+        }
+        ImGui::EndChild();
+
+        ImGui::End();
+    }
+
+    // Name the current settings; an existing preset with the name is replaced
+    if (state.showSavePreset) {
+        ImGui::SetNextWindowSize(ImVec2(300, 120), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Save Preset", &state.showSavePreset);
+        ImGui::InputText("Name", state.presetName, sizeof(state.presetName));
+        if (ImGui::Button("Save", ImVec2(-1, 0))) {
+            if (Settings::savePreset(state.presetName, state.params, &state.presetError)) {
+                state.showSavePreset = false;
+            }
+        }
+        if (!state.presetError.empty()) {
+            ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", state.presetError.c_str());
+        }
+        ImGui::End();
+    }
+
+    // Crop to a rectangle, in pixels of the current image
+    if (state.showCrop) {
+        ImGui::SetNextWindowSize(ImVec2(300, 140), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Crop", &state.showCrop);
+        ImGui::InputInt2("Position", state.cropRect);
+        ImGui::InputInt2("Size", state.cropRect + 2);
+        if (ImGui::Button("Crop", ImVec2(-1, 0))) {
+            cv::Rect rect(state.cropRect[0], state.cropRect[1], state.cropRect[2], state.cropRect[3]);
+            transformImage(state, [rect](const cv::Mat& image) { return Transform::cropImage(image, rect); });
+            state.showCrop = false;
+        }
+        ImGui::End();
+    }
+
+    // Rectangles to dither, the rest of the image keeps its original look
+    if (state.showMaskRegion) {
+        ImGui::SetNextWindowSize(ImVec2(300, 160), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Dither Region", &state.showMaskRegion);
+        ImGui::InputInt2("Position", state.maskRect);
+        ImGui::InputInt2("Size", state.maskRect + 2);
+        if (ImGui::Button("Add Region", ImVec2(-1, 0)) && state.imageLoaded) {
+            cv::Rect rect(state.maskRect[0], state.maskRect[1], state.maskRect[2], state.maskRect[3]);
+            cv::Mat region = Dithering::maskFromRects(state.originalImage.size(), {rect});
+            if (state.ditherMask.empty()) {
+                state.ditherMask = region;
+            } else {
+                cv::max(state.ditherMask, region, state.ditherMask);
+            }
+            processImage(state);
+        }
+        ImGui::TextDisabled("Outside the regions the original shows");
+        ImGui::End();
+    }
+
+    // Resize with a choice of resampling filter
+    if (state.showResize) {
+        ImGui::SetNextWindowSize(ImVec2(300, 170), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Resize", &state.showResize);
+        int previous[2] = {state.resizeSize[0], state.resizeSize[1]};
+        if (ImGui::InputInt2("Size", state.resizeSize) && state.resizeKeepAspect && state.imageLoaded) {
+            // Follow whichever dimension was edited
+            cv::Size limit = state.resizeSize[0] != previous[0] ? cv::Size(state.resizeSize[0], 0)
+                                                                : cv::Size(0, state.resizeSize[1]);
+            cv::Size fitted = Transform::fitSize(state.originalImage.size(), limit);
+            state.resizeSize[0] = fitted.width;
+            state.resizeSize[1] = fitted.height;
+        }
+        ImGui::Checkbox("Keep Aspect Ratio", &state.resizeKeepAspect);
+
+        const char* filters[] = {"Nearest Neighbor", "Bilinear", "Bicubic", "Area", "Lanczos"};
+        ImGui::Combo("Filter", &state.resizeFilter, filters, IM_ARRAYSIZE(filters));
+
+        if (ImGui::Button("Resize", ImVec2(-1, 0)) && state.resizeSize[0] > 0 && state.resizeSize[1] > 0) {
+            cv::Size size(state.resizeSize[0], state.resizeSize[1]);
+            Transform::Filter filter = static_cast<Transform::Filter>(state.resizeFilter);
+            transformImage(state, [size, filter](const cv::Mat& image) {
+                return Transform::resizeImage(image, size, filter);
+            });
+            state.showResize = false;
+        }
+        ImGui::End();
+    }
+
+    // Comparison with a reference image
+    if (state.showComparison) {
+        ImGui::SetNextWindowSize(ImVec2(360, 420), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Comparison", &state.showComparison);
+
+        if (!state.comparisonValid) {
+            ImGui::TextWrapped("Use File > Compare With Image to load a reference.");
+        } else {
+            const Compare::Result& result = state.comparison;
+            ImGui::TextWrapped("Reference: %s", state.referenceFile.c_str());
+            if (result.offset.x != 0.0 || result.offset.y != 0.0) {
+                ImGui::Text("Aligned by %+.0f, %+.0f px", result.offset.x, result.offset.y);
+            }
+            ImGui::Separator();
+            ImGui::Text("Tone SSIM: %.4f", result.toneSsim);
+            ImGui::Text("Tone PSNR: %.2f dB", result.tonePsnr);
+            ImGui::Text("Pixel PSNR: %.2f dB", result.psnr);
+            ImGui::Text("Mean difference: %.2f", result.meanDifference);
+            ImGui::Text("Matching pixels: %.1f%%", result.matchingPixels);
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Dither patterns rarely match exactly; the tone metrics\n"
+                                  "compare blurred copies, as seen from a distance");
+            }
+
+            ImGui::Separator();
+            if (ImGui::Checkbox("Show Difference", &state.showReferenceDiff)) {
+                updateTexture(state.comparisonTexture,
+                              state.showReferenceDiff ? result.diff : result.aligned);
+            }
+            float width = ImGui::GetContentRegionAvail().x;
+            float height = width * result.diff.rows / std::max(1, result.diff.cols);
+            ImGui::Image((void*)(intptr_t)state.comparisonTexture, ImVec2(width, height));
+        }
+
+        if (!state.referenceImage.empty() && ImGui::Button("Clear Reference")) {
+            state.referenceImage = cv::Mat();
+            state.referenceFile.clear();
+            state.alignedSource = cv::Mat();
+            state.alignedReference = cv::Mat();
+            state.comparisonValid = false;
+        }
+
+        ImGui::End();
+    }
+
+    // Every algorithm side by side; clicking one selects it
+    if (state.showExplorer) {
+        ImGui::SetNextWindowSize(ImVec2(720, 560), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Algorithm Explorer", &state.showExplorer);
+
+        if (ImGui::Button("Refresh", ImVec2(120, 0))) renderExplorer(state);
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Render again with the current palette and adjustments");
+        }
+        ImGui::SameLine();
+        if (ImGui::Button("Save Contact Sheet...") && !state.explorerThumbnails.empty()) {
+            std::string filepath = Platform::saveFileDialog();
+            if (!filepath.empty()) {
+                cv::Mat sheet = Dithering::composeContactSheet(state.explorerThumbnails);
+                if (ImageIO::saveImage(filepath, sheet, cv::Mat(), ImageIO::ExportOptions())) {
+                    std::cout << "Saved contact sheet to " << filepath << std::endl;
+                } else {
+                    std::cerr << "Failed to save contact sheet" << std::endl;
+                }
+            }
+        }
+
+        if (state.explorerThumbnails.empty()) {
+            ImGui::TextWrapped("Load an image to compare every algorithm on it.");
+        } else {
+            ImGui::TextDisabled("Click an algorithm to use it");
+            const float cellWidth = 170.0f;
+            int columns = std::max(1, static_cast<int>(ImGui::GetContentRegionAvail().x / cellWidth));
+            for (size_t i = 0; i < state.explorerThumbnails.size(); ++i) {
+                const auto& thumbnail = state.explorerThumbnails[i];
+                if (i % columns != 0) ImGui::SameLine();
+
+                ImGui::BeginGroup();
+                float scale = 160.0f / std::max(thumbnail.image.cols, thumbnail.image.rows);
+                ImGui::Image((void*)(intptr_t)state.explorerTextures[i],
+                             ImVec2(thumbnail.image.cols * scale, thumbnail.image.rows * scale));
+                bool clicked = ImGui::IsItemClicked();
+                bool selected = thumbnail.algorithm == state.params.algorithm;
+                std::string name = Dithering::getAlgorithmName(thumbnail.algorithm);
+                if (selected) {
+                    ImGui::TextColored(ImVec4(1.0f, 0.8f, 0.3f, 1.0f), "%s", name.c_str());
+                } else {
+                    ImGui::Text("%s", name.c_str());
+                }
+                ImGui::EndGroup();
+
+                if (clicked && !selected) selectAlgorithm(state, thumbnail.algorithm);
+            }
+        }
+
+        ImGui::End();
+    }
+
+    // Histogram of the source
+    if (state.showHistogram) {
+        ImGui::SetNextWindowSize(ImVec2(320, 360), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Histogram", &state.showHistogram);
+
+        if (state.sourceHistogram[0].empty()) {
+            ImGui::TextWrapped("Load an image to see its histogram.");
+        } else {
+            if (ImGui::Checkbox("After Adjustments", &state.histogramAdjusted)) {
+                updateSourceHistogram(state);
+            }
+            if (ImGui::IsItemHovered()) {
+                ImGui::SetTooltip("Levels after brightness, contrast, gamma and the other\n"
+                                  "adjustments, as the dithering sees them");
+            }
+
+            const char* labels[4] = {"Luma", "Red", "Green", "Blue"};
+            const ImVec4 colors[4] = {ImVec4(0.8f, 0.8f, 0.8f, 1.0f), ImVec4(0.9f, 0.3f, 0.3f, 1.0f),
+                                      ImVec4(0.3f, 0.8f, 0.3f, 1.0f), ImVec4(0.3f, 0.5f, 0.9f, 1.0f)};
+            float width = ImGui::GetContentRegionAvail().x;
+            for (int i = 0; i < 4; ++i) {
+                ImGui::Text("%s", labels[i]);
+                ImGui::PushStyleColor(ImGuiCol_PlotHistogram, colors[i]);
+                ImGui::PlotHistogram((std::string("##") + labels[i]).c_str(), state.sourceHistogram[i].data(),
+                                     static_cast<int>(state.sourceHistogram[i].size()), 0, nullptr,
+                                     0.0f, FLT_MAX, ImVec2(width, i == 0 ? 70.0f : 40.0f));
+                ImGui::PopStyleColor();
+
+                // Mark the threshold on the luma histogram
+                if (i == 0 && Dithering::getAlgorithmInfo(state.params.algorithm).usesThreshold) {
+                    ImVec2 min = ImGui::GetItemRectMin(), max = ImGui::GetItemRectMax();
+                    float x = min.x + state.params.threshold * (max.x - min.x);
+                    ImGui::GetWindowDrawList()->AddLine(ImVec2(x, min.y), ImVec2(x, max.y),
+                                                        IM_COL32(255, 200, 60, 255), 1.5f);
+                }
+            }
+        }
+
+        ImGui::End();
+    }
+
+    // Objective quality of the result
+    if (state.showQuality) {
+        ImGui::SetNextWindowSize(ImVec2(300, 220), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Quality", &state.showQuality);
+
+        if (!state.qualityValid) {
+            ImGui::TextWrapped("Load an image to measure the result.");
+        } else {
+            auto showQuality = [](const Compare::Quality& quality) {
+                ImGui::Text("PSNR: %.2f dB", quality.psnr);
+                ImGui::Text("SSIM: %.4f", quality.ssim);
+                ImGui::Text("Mean CIEDE2000: %.2f", quality.deltaE);
+            };
+            ImGui::Text("Against the original");
+            showQuality(state.quality);
+            if (!state.referenceImage.empty()) {
+                ImGui::Separator();
+                ImGui::Text("Against the reference");
+                showQuality(state.referenceQuality);
+            }
+
+            // Exact colors, for hard palette limits
+            const Compare::ColorReport& report = state.colorReport;
+            ImGui::Separator();
+            if (report.limit > 0) {
+                ImGui::Text("Colors: %zu of %d", report.colors.size(), report.limit);
+            } else {
+                ImGui::Text("Colors: %zu", report.colors.size());
+            }
+            ImVec4 red(1.0f, 0.4f, 0.4f, 1.0f);
+            if (report.outsidePalette > 0) {
+                ImGui::SameLine();
+                ImGui::TextColored(red, "%d not in the palette", report.outsidePalette);
+            } else if (report.exceedsPalette()) {
+                ImGui::SameLine();
+                ImGui::TextColored(red, "over the limit");
+            }
+            if (ImGui::TreeNode("Used Colors")) {
+                for (size_t i = 0; i < report.colors.size() && i < 256; ++i) {
+                    const Compare::ColorCount& count = report.colors[i];
+                    ImVec4 rgb(count.color[2] / 255.0f, count.color[1] / 255.0f, count.color[0] / 255.0f, 1.0f);
+                    ImGui::PushID(static_cast<int>(i));
+                    ImGui::ColorButton("##color", rgb, ImGuiColorEditFlags_None, ImVec2(14, 14));
+                    ImGui::PopID();
+                    ImGui::SameLine();
+                    ImGui::Text("#%02x%02x%02x  %d px%s", count.color[2], count.color[1], count.color[0],
+                                count.pixels, count.inPalette ? "" : "  (not in palette)");
+                }
+                if (report.colors.size() > 256) ImGui::TextDisabled("and %zu more", report.colors.size() - 256);
+                ImGui::TreePop();
+            }
+            if (state.previewIsDownscaled || state.previewIsProxy) {
+                ImGui::Separator();
+                ImGui::TextDisabled("Measured on the preview, export for exact figures");
+            }
+        }
+        if (ImGui::IsWindowHovered()) {
+            ImGui::SetTooltip("Measured on blurred copies, as seen from a distance.\n"
+                              "Higher PSNR and SSIM and lower CIEDE2000 are closer");
+        }
+
+        ImGui::End();
+    }
+
+    // Settings history: click a step to go back to it
+    if (state.showHistory) {
+        ImGui::SetNextWindowSize(ImVec2(320, 360), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Settings History", &state.showHistory);
+        ImGui::TextDisabled("Right-click a step to copy its settings");
+        ImGui::Separator();
+        for (size_t i = 0; i < state.history.size(); ++i) {
+            const HistoryEntry& entry = state.history[i];
+            ImGui::PushID(static_cast<int>(i));
+            std::string label = std::to_string(i + 1) + ". " + entry.label;
+            // Undone steps are dimmed until a new change replaces them
+            if (i > state.historyIndex) ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.5f, 0.5f, 0.5f, 1.0f));
+            if (ImGui::Selectable(label.c_str(), i == state.historyIndex)) restoreHistory(state, i);
+            if (i > state.historyIndex) ImGui::PopStyleColor();
+            if (ImGui::BeginPopupContextItem()) {
+                if (ImGui::MenuItem("Go to This Step")) restoreHistory(state, i);
+                if (ImGui::MenuItem("Copy Settings")) ImGui::SetClipboardText(Settings::toJson(entry.params).c_str());
+                ImGui::EndPopup();
+            }
+            ImGui::PopID();
+        }
+        ImGui::End();
+    }
+
+    // Automation server: the HTTP API, started only from here
+    if (state.showServer) {
+        ImGui::SetNextWindowSize(ImVec2(440, 320), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Automation Server", &state.showServer);
+
+        if (state.server.isRunning()) {
+            ImGui::Text("Listening on http://127.0.0.1:%d/api/", state.server.getPort());
+            ImGui::Text("Token: %s", state.server.getToken().c_str());
+            ImGui::SameLine();
+            if (ImGui::SmallButton("Copy")) ImGui::SetClipboardText(state.server.getToken().c_str());
+            if (ImGui::Button("Stop Server")) state.server.stop();
+        } else {
+            ImGui::InputInt("Port", &state.serverPort);
+            state.serverPort = std::clamp(state.serverPort, 1, 65535);
+            if (ImGui::Button("Start Server")) {
+                Automation::Options options;
+                options.port = state.serverPort;
+                options.defaults = state.params;
+                auto log = [&state](const std::string& message) {
+                    std::lock_guard<std::mutex> lock(state.serverLogMutex);
+                    state.serverLog.push_back(message);
+                    if (state.serverLog.size() > 100) state.serverLog.erase(state.serverLog.begin());
+                };
+                state.serverError.clear();
+                if (!state.server.start(options, log, &state.serverError)) {
+                    std::cerr << "Error: " << state.serverError << std::endl;
+                }
+            }
+            if (!state.serverError.empty()) {
+                ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", state.serverError.c_str());
+            }
+        }
+        ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.6f, 0.6f, 0.6f, 1.0f));
+        ImGui::TextWrapped("Only this computer can connect, and every request needs the token. Requests "
+                           "without settings use the ones current when the server started.");
+        ImGui::PopStyleColor();
+
+        ImGui::Separator();
+        std::lock_guard<std::mutex> lock(state.serverLogMutex);
+        if (state.serverLog.empty()) ImGui::TextDisabled("No requests yet");
+        for (auto it = state.serverLog.rbegin(); it != state.serverLog.rend(); ++it) {
+            ImGui::TextUnformatted(it->c_str());
+        }
+
+        ImGui::End();
+    }
+
+    // Settings variants side by side with captions, for sharing a comparison
+    if (state.showMontage) {
+        ImGui::SetNextWindowSize(ImVec2(380, 420), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Export Montage", &state.showMontage);
+
+        ImGui::Checkbox("Current Settings", &state.montageCurrent);
+        for (const auto& name : state.montagePresetList) {
+            auto chosen = std::find(state.montagePresets.begin(), state.montagePresets.end(), name);
+            bool selected = chosen != state.montagePresets.end();
+            if (ImGui::Checkbox(name.c_str(), &selected)) {
+                if (selected) {
+                    state.montagePresets.push_back(name);
+                } else {
+                    state.montagePresets.erase(chosen);
+                }
+            }
+        }
+        if (state.montagePresetList.empty()) ImGui::TextDisabled("No saved presets");
+        ImGui::Separator();
+
+        const char* layouts[] = {"Row", "Column", "Grid"};
+        int layout = static_cast<int>(state.montageOptions.layout);
+        if (ImGui::Combo("Layout", &layout, layouts, IM_ARRAYSIZE(layouts))) {
+            state.montageOptions.layout = static_cast<Compare::MontageLayout>(layout);
+        }
+        ImGui::SliderInt("Panel Size (px)", &state.montageOptions.panelSize, 128, 2048);
+        if (ImGui::IsItemHovered()) ImGui::SetTooltip("Longest side each variant is dithered at");
+        ImGui::Checkbox("Include Original", &state.montageOptions.includeOriginal);
+
+        size_t count = state.montagePresets.size() + (state.montageCurrent ? 1 : 0);
+        ImGui::BeginDisabled(!state.imageLoaded || count < 2);
+        if (ImGui::Button("Save Montage...")) {
+            std::string filepath = Platform::saveFileDialog();
+            if (!filepath.empty()) {
+                if (ImageIO::getExtension(filepath).empty()) filepath += ".png";
+                queueMontage(state, filepath);
+            }
+        }
+        ImGui::EndDisabled();
+        if (count < 2) ImGui::TextDisabled("Choose at least two variants");
+
+        ImGui::End();
+    }
+
+    // Render text as the image, for logos and lettering
+    if (state.showRenderText) {
+        ImGui::SetNextWindowSize(ImVec2(420, 300), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Render Text", &state.showRenderText);
+
+        ImGui::InputTextMultiline("Text", state.textInput, sizeof(state.textInput), ImVec2(0, 80));
+        ImGui::InputText("Font", state.textFont, sizeof(state.textFont));
+        if (ImGui::IsItemHovered()) {
+            ImGui::SetTooltip("Font name or file; empty for the default. Needs ImageMagick");
+        }
+        ImGui::SliderInt("Size (px)", &state.textOptions.size, 8, 512);
+        ImGui::SliderInt("Padding (px)", &state.textOptions.padding, 0, 256);
+        colorEditBGR("Text Color", state.textOptions.color);
+        colorEditBGR("Background##Text", state.textOptions.background);
+
+        ImGui::BeginDisabled(state.textInput[0] == '\0');
+        if (ImGui::Button("Render")) {
+            state.textOptions.font = state.textFont;
+            cv::Mat rendered;
+            std::vector<ImageIO::Warning> warnings;
+            if (ImageIO::renderText(state.textInput, state.textOptions, rendered, &warnings)) {
+                setLoadedImage(state, "text.png", rendered, cv::Mat(), cv::Mat(), warnings);
+            }
+        }
+        ImGui::EndDisabled();
+
+        ImGui::End();
+    }
+
+    // Lospec palette browser: community palettes, cached for offline use
+    if (state.showLospec) {
+        pollLospecSearch(state);
+        ImGui::SetNextWindowSize(ImVec2(460, 480), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Lospec Palettes", &state.showLospec);
+
+        bool search = ImGui::InputText("Title", state.lospecQuery, sizeof(state.lospecQuery),
+                                       ImGuiInputTextFlags_EnterReturnsTrue);
+        ImGui::InputInt("Colors", &state.lospecColors);
+        state.lospecColors = std::clamp(state.lospecColors, 0, 256);
+        if (ImGui::IsItemHovered()) ImGui::SetTooltip("Only palettes of exactly this many colors (0 = any)");
+        if (ImGui::Button("Search") || search) {
+            startLospecSearch(state, 0);
+        }
+        ImGui::SameLine();
+        ImGui::BeginDisabled(state.lospecPage == 0);
+        if (ImGui::Button("< Prev")) {
+            startLospecSearch(state, state.lospecPage - 1);
+        }
+        ImGui::EndDisabled();
+        ImGui::SameLine();
+        ImGui::BeginDisabled(static_cast<int>(state.lospecResults.size()) < Lospec::PAGE_SIZE);
+        if (ImGui::Button("Next >")) {
+            startLospecSearch(state, state.lospecPage + 1);
+        }
+        ImGui::EndDisabled();
+        ImGui::SameLine();
+        ImGui::Text("Page %d", state.lospecPage + 1);
+
+        if (state.lospecSearch) {
+            ImGui::TextDisabled("Searching lospec.com...");
+        } else if (!state.lospecError.empty()) {
+            ImGui::TextColored(ImVec4(1.0f, 0.4f, 0.4f, 1.0f), "%s", state.lospecError.c_str());
+        } else if (state.lospecOffline) {
+            ImGui::TextColored(ImVec4(1.0f, 0.8f, 0.3f, 1.0f), "Offline: showing cached palettes");
+        }
+        ImGui::Separator();
+
+        ImGui::BeginChild("##LospecResults");
+        if (!state.lospecSearch && state.lospecError.empty() && state.lospecResults.empty()) {
+            ImGui::TextDisabled("No palettes found");
+        }
+        for (size_t i = 0; i < state.lospecResults.size(); ++i) {
+            const Lospec::PaletteInfo& palette = state.lospecResults[i];
+            ImGui::PushID(static_cast<int>(i));
+            ImGui::Text("%s (%zu colors)", palette.title.c_str(), palette.colors.size());
+            if (!palette.author.empty()) {
+                ImGui::SameLine();
+                ImGui::TextDisabled("by %s", palette.author.c_str());
+            }
+            if (ImGui::Button("Use")) {
+                Lospec::rememberPalette(palette);
+                state.params.customPalette = palette.colors;
+                state.params.paletteMode = Dithering::PaletteMode::CUSTOM;
+                state.selectedPalette = static_cast<int>(Dithering::PaletteMode::CUSTOM);
+                pruneProtectedColors(state);
+                if (state.autoUpdate) processImage(state);
+            }
+            // Swatches wrap to the window width, after the button on the first row
+            int perRow = std::max(1, static_cast<int>(ImGui::GetContentRegionAvail().x / 20.0f) - 2);
+            for (size_t c = 0; c < palette.colors.size(); ++c) {
+                const cv::Vec3b& color = palette.colors[c];
+                ImVec4 rgb(color[2] / 255.0f, color[1] / 255.0f, color[0] / 255.0f, 1.0f);
+                if (c % perRow != 0 || c == 0) ImGui::SameLine();
+                ImGui::PushID(static_cast<int>(c));
+                ImGui::ColorButton("##swatch", rgb, ImGuiColorEditFlags_None, ImVec2(16, 16));
+                ImGui::PopID();
+            }
+            ImGui::PopID();
+            ImGui::Separator();
+        }
+        ImGui::EndChild();
+
+        ImGui::End();
+    }
+
+    // Usage statistics window (counters never leave this machine)
+    if (state.showUsageStats) {
+        ImGui::SetNextWindowSize(ImVec2(360, 320), ImGuiCond_FirstUseEver);
+        ImGui::Begin("Usage Statistics", &state.showUsageStats);
+
+        // The file is only read when it has changed, not every frame
+        if (state.usageStatsRevision != UsageStats::getRevision()) {
+            state.usageStatsRevision = UsageStats::getRevision();
+            state.usageStats = UsageStats::getUsageStats();
+        }
+        const UsageStats::Stats& stats = state.usageStats;
+        ImGui::Text("Images processed: %d", stats.imagesProcessed);
+        ImGui::Text("Videos processed: %d", stats.videosProcessed);
+        ImGui::Text("Average size: %.2f MP", stats.averageMegapixels());
+        ImGui::Text("Average job time: %.2f s", stats.averageSeconds());
+
+        ImGui::Separator();
+        ImGui::Text("Algorithms");
+        for (const auto& [name, count] : stats.algorithmCounts) {
+            ImGui::BulletText("%s: %d", name.c_str(), count);
+        }
+
+        ImGui::Separator();
+        ImGui::PushStyleColor(ImGuiCol_Text, ImVec4(0.6f, 0.6f, 0.6f, 1.0f));
+        ImGui::TextWrapped("Stored only on this computer: %s", UsageStats::getStatsPath().c_str());
+        ImGui::PopStyleColor();
+        if (ImGui::Button("Clear Statistics") && !UsageStats::clearUsageStats()) {
+            std::cerr << "Error: Could not clear usage statistics" << std::endl;
+        }
+
+        ImGui::End();
+    }
 }
 
 // Setup Dear ImGui style (Photoshop-like dark theme)
@@ -504,6 +4224,9 @@ void glfwErrorCallback(int error, const char* description) {
 }
 
 int main(int argc, char** argv) {
+    ImageIO::enableOpenExr();
+    Platform::ignoreBrokenPipes();
+
     // Set GLFW error callback
     glfwSetErrorCallback(glfwErrorCallback);
 
@@ -584,6 +4307,7 @@ int main(int argc, char** argv) {
     // Set up drag and drop
     glfwSetWindowUserPointer(window, &state);
     glfwSetDropCallback(window, dropCallback);
+    refreshRecentFiles(state);
 
     // Load image from command line if provided
     if (argc > 1) {
@@ -604,7 +4328,13 @@ int main(int argc, char** argv) {
         ImGui::NewFrame();
 
         // Render GUI
+        pollProcessing(state);
+        pollWebcam(state);
+        pollDownload(state);
+        pollDescreen(state);
+        pollThumbnailUpdate(state);
         renderGUI(state);
+        recordHistory(state);
 
         // Rendering
         ImGui::Render();
@@ -619,8 +4349,31 @@ int main(int argc, char** argv) {
     }
 
     // Cleanup
+    if (state.job) {
+        state.job->cancel = true;
+        state.job->thread.join();
+    }
+    if (state.webcam) {
+        state.webcam->stop = true;
+        state.webcam->thread.join();
+    }
+    state.server.stop();
+    if (state.lospecSearch) state.lospecSearch->thread.join();
+    if (state.ffmpegCheck) state.ffmpegCheck->thread.join();
+    if (state.download) state.download->thread.join();
+    if (state.thumbnailUpdate) state.thumbnailUpdate->thread.join();
     if (state.originalTexture) glDeleteTextures(1, &state.originalTexture);
     if (state.processedTexture) glDeleteTextures(1, &state.processedTexture);
+    if (state.asciiTexture) glDeleteTextures(1, &state.asciiTexture);
+    for (GLuint texture : state.magnifierTextures) {
+        if (texture) glDeleteTextures(1, &texture);
+    }
+    for (GLuint texture : state.explorerTextures) {
+        if (texture) glDeleteTextures(1, &texture);
+    }
+    for (GLuint texture : state.recentTextures) {
+        if (texture) glDeleteTextures(1, &texture);
+    }
 
     ImGui_ImplOpenGL3_Shutdown();
     ImGui_ImplGlfw_Shutdown();
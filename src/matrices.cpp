@@ -0,0 +1,274 @@
+#include "matrices.h"
+#include <algorithm>
+#include <cctype>
+#include <cmath>
+#include <cstdlib>
+#include <filesystem>
+#include <map>
+#include <mutex>
+#include <numeric>
+
+namespace Matrices {
+
+// Turn a spot function into evenly spaced thresholds: pixels with the lowest
+// spot value switch on first. Ties are broken with a Bayer-style bit-reversal
+// order so equal pixels still fill in a dispersed pattern
+static cv::Mat rankThresholds(const cv::Mat& spot) {
+    int size = spot.rows;
+    int count = size * size;
+
+    auto tieBreak = [size](int x, int y) {
+        int value = 0;
+        for (int bit = 1; bit < size; bit <<= 1) {
+            int xBit = (x & bit) ? 1 : 0;
+            int yBit = (y & bit) ? 1 : 0;
+            value = (value << 2) | ((xBit ^ yBit) << 1) | yBit;
+        }
+        return value;
+    };
+
+    std::vector<int> order(count);
+    std::iota(order.begin(), order.end(), 0);
+    std::stable_sort(order.begin(), order.end(), [&](int a, int b) {
+        float spotA = spot.at<float>(a / size, a % size);
+        float spotB = spot.at<float>(b / size, b % size);
+        if (spotA != spotB) return spotA < spotB;
+        return tieBreak(a % size, a / size) < tieBreak(b % size, b / size);
+    });
+
+    cv::Mat thresholds(size, size, CV_32F);
+    for (int rank = 0; rank < count; ++rank) {
+        thresholds.at<float>(order[rank] / size, order[rank] % size) = (rank + 0.5f) / count;
+    }
+    return thresholds;
+}
+
+// Build a matrix from a spot function evaluated at pixel centers
+template <typename SpotFunction>
+static cv::Mat fromSpotFunction(int size, SpotFunction spot) {
+    cv::Mat values(size, size, CV_32F);
+    for (int y = 0; y < size; ++y) {
+        for (int x = 0; x < size; ++x) {
+            values.at<float>(y, x) = spot(x + 0.5f, y + 0.5f);
+        }
+    }
+    return rankThresholds(values);
+}
+
+// Horizontal line screen: lines thicken from the row center outwards
+static cv::Mat linesMatrix() {
+    const int size = 8;
+    return fromSpotFunction(size, [size](float, float y) {
+        return std::fabs(y - size / 2.0f);
+    });
+}
+
+// Diagonal line screen at 45 degrees
+static cv::Mat diagonalMatrix() {
+    const int size = 8;
+    return fromSpotFunction(size, [size](float x, float y) {
+        float d = std::fmod(x + y, static_cast<float>(size));
+        return std::fabs(d - size / 2.0f);
+    });
+}
+
+// Round clustered dots on a 45 degree screen (classic cosine spot function).
+// Along each axis the screen repeats every `period` pixels
+static cv::Mat dotScreenMatrix(int size, float period) {
+    const float k = 2.0f * static_cast<float>(CV_PI) / period;
+    return fromSpotFunction(size, [k](float x, float y) {
+        return -(std::cos(k * (x + y)) + std::cos(k * (x - y)));
+    });
+}
+
+// Single dot growing outward in a spiral from the cell center
+static cv::Mat spiralMatrix() {
+    const int size = 8;
+    return fromSpotFunction(size, [size](float x, float y) {
+        float dx = x - size / 2.0f;
+        float dy = y - size / 2.0f;
+        float ring = std::max(std::fabs(dx), std::fabs(dy));
+        float angle = std::atan2(dy, dx) / (2.0f * static_cast<float>(CV_PI)) + 0.5f;
+        return ring + angle * 0.99f;
+    });
+}
+
+const std::vector<MatrixInfo>& getMatrixLibrary() {
+    static const std::vector<MatrixInfo> library = {
+        {"lines", "Lines", "Horizontal line screen, like engraving or scanlines", 8, true},
+        {"diagonal", "Diagonal", "Line screen at 45 degrees", 8, true},
+        {"dots45", "Dots 45", "Round clustered dots on a 45 degree screen", 8, true},
+        {"spiral", "Spiral", "Dots that grow in a spiral from the cell center", 8, true},
+        {"newspaper", "Newspaper", "85 lpi newsprint halftone when printed at 300 dpi", 10, true},
+    };
+    return library;
+}
+
+const MatrixInfo* findMatrix(const std::string& id) {
+    const auto& library = getMatrixLibrary();
+    auto it = std::find_if(library.begin(), library.end(),
+                           [&id](const MatrixInfo& info) { return info.id == id; });
+    return it != library.end() ? &*it : nullptr;
+}
+
+cv::Mat getMatrix(const std::string& id) {
+    if (id == "lines") return linesMatrix();
+    if (id == "diagonal") return diagonalMatrix();
+    if (id == "dots45") return dotScreenMatrix(8, 8.0f);
+    if (id == "spiral") return spiralMatrix();
+    // 300 dpi / 85 lpi = 3.53 px between dot rows; at 45 degrees that repeats
+    // every 3.53 * sqrt(2) = 5 px along each axis
+    if (id == "newspaper") return dotScreenMatrix(10, 5.0f);
+    return cv::Mat();
+}
+
+// Largest user matrix accepted, in either dimension
+static const int MAX_USER_MATRIX_SIZE = 256;
+
+// Map arbitrary matrix values into thresholds in [0, 1). Values already in
+// [0, 1] are kept; anything else (Bayer indices, 0-255 gray) is scaled so the
+// smallest and largest values sit half a step inside the range
+static cv::Mat normalizeThresholds(const cv::Mat& values) {
+    double minValue, maxValue;
+    cv::minMaxLoc(values, &minValue, &maxValue);
+
+    cv::Mat thresholds;
+    if (minValue >= 0.0 && maxValue <= 1.0) {
+        thresholds = cv::min(values, 0.999);
+    } else {
+        double range = maxValue - minValue + 1.0;
+        values.convertTo(thresholds, CV_32F, 1.0 / range, (0.5 - minValue) / range);
+    }
+    return thresholds;
+}
+
+bool parseMatrix(const std::string& text, cv::Mat& matrix) {
+    std::vector<std::vector<float>> rows;
+    std::vector<float> flat;
+    int depth = 0;
+
+    const char* p = text.c_str();
+    while (*p) {
+        if (*p == '[') {
+            depth++;
+            if (depth == 2) rows.emplace_back();
+            p++;
+        } else if (*p == ']') {
+            depth--;
+            p++;
+        } else if (*p == ',' || std::isspace(static_cast<unsigned char>(*p))) {
+            p++;
+        } else {
+            char* end = nullptr;
+            float value = std::strtof(p, &end);
+            if (end == p) return false;
+            (depth >= 2 ? rows.back() : flat).push_back(value);
+            p = end;
+        }
+        if (depth < 0) return false;
+    }
+    if (depth != 0) return false;
+
+    // A flat list must be square
+    if (rows.empty()) {
+        int side = static_cast<int>(std::lround(std::sqrt(static_cast<double>(flat.size()))));
+        if (side == 0 || side * side != static_cast<int>(flat.size())) return false;
+        for (int y = 0; y < side; ++y) {
+            rows.emplace_back(flat.begin() + y * side, flat.begin() + (y + 1) * side);
+        }
+    } else if (!flat.empty()) {
+        return false;
+    }
+
+    int height = static_cast<int>(rows.size());
+    int width = static_cast<int>(rows[0].size());
+    if (width == 0 || width > MAX_USER_MATRIX_SIZE || height > MAX_USER_MATRIX_SIZE) return false;
+
+    cv::Mat values(height, width, CV_32F);
+    for (int y = 0; y < height; ++y) {
+        if (static_cast<int>(rows[y].size()) != width) return false;
+        for (int x = 0; x < width; ++x) {
+            values.at<float>(y, x) = rows[y][x];
+        }
+    }
+
+    matrix = normalizeThresholds(values);
+    return true;
+}
+
+bool loadMatrixImage(const std::string& path, cv::Mat& matrix) {
+    cv::Mat gray = cv::imread(path, cv::IMREAD_GRAYSCALE);
+    if (gray.empty() || gray.cols > MAX_USER_MATRIX_SIZE || gray.rows > MAX_USER_MATRIX_SIZE) {
+        return false;
+    }
+
+    cv::Mat values;
+    gray.convertTo(values, CV_32F);
+    matrix = normalizeThresholds(values);
+    return true;
+}
+
+bool loadNoiseTexture(const std::string& path, cv::Mat& texture, std::string& error) {
+    // Failures are kept too, so callers asking every frame don't reread a bad file
+    struct Entry {
+        std::filesystem::file_time_type modified;
+        std::uintmax_t size;
+        cv::Mat texture;
+        std::string error;
+    };
+    static std::mutex cacheMutex;
+    static std::map<std::string, Entry> cache;
+
+    std::error_code ec;
+    auto modified = std::filesystem::last_write_time(path, ec);
+    std::uintmax_t size = ec ? 0 : std::filesystem::file_size(path, ec);
+    if (ec) {
+        error = "can't read " + path;
+        return false;
+    }
+
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    auto cached = cache.find(path);
+    if (cached != cache.end() && cached->second.modified == modified && cached->second.size == size) {
+        if (cached->second.texture.empty()) {
+            error = cached->second.error;
+            return false;
+        }
+        texture = cached->second.texture;
+        return true;
+    }
+
+    auto fail = [&](const std::string& message) {
+        cache[path] = {modified, size, cv::Mat(), message};
+        error = message;
+        return false;
+    };
+
+    cv::Mat image = cv::imread(path, cv::IMREAD_ANYDEPTH | cv::IMREAD_ANYCOLOR);
+    if (image.empty()) return fail("can't read " + path + " as an image");
+    if (image.cols > MAX_NOISE_TEXTURE_SIZE || image.rows > MAX_NOISE_TEXTURE_SIZE) {
+        return fail("texture is larger than " + std::to_string(MAX_NOISE_TEXTURE_SIZE) + " pixels a side");
+    }
+
+    cv::Mat values;
+    cv::extractChannel(image, values, image.channels() >= 3 ? 2 : 0);
+    values.convertTo(values, CV_32F);
+
+    int count = static_cast<int>(values.total());
+    const float* data = values.ptr<float>();
+    std::vector<int> order(count);
+    std::iota(order.begin(), order.end(), 0);
+    std::stable_sort(order.begin(), order.end(), [data](int a, int b) { return data[a] < data[b]; });
+
+    cv::Mat ranked(values.size(), CV_32F);
+    float* out = ranked.ptr<float>();
+    for (int i = 0; i < count; ++i) {
+        out[order[i]] = (i + 0.5f) / count;
+    }
+
+    cache[path] = {modified, size, ranked, std::string()};
+    texture = ranked;
+    return true;
+}
+
+} // namespace Matrices
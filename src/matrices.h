@@ -0,0 +1,44 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace Matrices {
+
+// Description of a threshold matrix, for listing it in the UI
+struct MatrixInfo {
+    std::string id;             // Name used in settings and on the command line
+    std::string name;           // Display name
+    std::string description;    // One-line summary of the look
+    int size;                   // Matrix width and height in pixels
+    bool clustered;             // Clustered (halftone-like) rather than dispersed dots
+};
+
+// Every matrix in the library, in display order
+const std::vector<MatrixInfo>& getMatrixLibrary();
+
+// Look up a matrix by id (nullptr if unknown)
+const MatrixInfo* findMatrix(const std::string& id);
+
+// Threshold matrix with values in [0, 1), CV_32F (empty if the id is unknown)
+cv::Mat getMatrix(const std::string& id);
+
+// Parse a user matrix written as a JSON-style array, e.g. [[0,2],[3,1]] or a flat
+// [0,2,3,1] with a square number of entries. Values are normalized into [0, 1)
+bool parseMatrix(const std::string& text, cv::Mat& matrix);
+
+// Load a grayscale image as a threshold matrix (dark pixels switch on first)
+bool loadMatrixImage(const std::string& path, cv::Mat& matrix);
+
+// Load a pre-made noise texture (e.g. one of the free 64x64 or 256x256 blue
+// noise sets, as PNG, 16-bit or HDR) as a threshold map with values in [0, 1),
+// CV_32F, up to MAX_NOISE_TEXTURE_SIZE a side. Color files use their red
+// channel. Values are replaced by their rank, so every threshold is equally
+// common whatever the file's depth or range. Textures, and the reason a file
+// couldn't be used, are kept for the rest of the session and read again only
+// when the file's modification time or size changes
+const int MAX_NOISE_TEXTURE_SIZE = 1024;
+bool loadNoiseTexture(const std::string& path, cv::Mat& texture, std::string& error);
+
+} // namespace Matrices
@@ -0,0 +1,321 @@
+#include "palettefile.h"
+#include "imageio.h"
+#include <algorithm>
+#include <cctype>
+#include <cstdio>
+#include <cstring>
+#include <filesystem>
+#include <fstream>
+#include <iterator>
+#include <sstream>
+
+namespace PaletteFile {
+
+using Errors::ErrorCode;
+
+const std::vector<std::string>& getPaletteExtensions() {
+    static const std::vector<std::string> extensions = {"gpl", "ase", "act", "pal", "txt", "hex", "png"};
+    return extensions;
+}
+
+const std::vector<std::string>& getPaletteSaveExtensions() {
+    static const std::vector<std::string> extensions = {"gpl", "ase", "hex", "png"};
+    return extensions;
+}
+
+static std::string toHex(const cv::Vec3b& color) {
+    char hex[8];
+    std::snprintf(hex, sizeof(hex), "%02x%02x%02x", color[2], color[1], color[0]);
+    return hex;
+}
+
+static cv::Vec3b fromRgb(int r, int g, int b) {
+    return cv::Vec3b(static_cast<uchar>(std::clamp(b, 0, 255)), static_cast<uchar>(std::clamp(g, 0, 255)),
+                     static_cast<uchar>(std::clamp(r, 0, 255)));
+}
+
+static cv::Vec3b fromUnitRgb(float r, float g, float b) {
+    return fromRgb(cvRound(r * 255.0f), cvRound(g * 255.0f), cvRound(b * 255.0f));
+}
+
+static bool startsWith(const std::vector<unsigned char>& data, const char* signature) {
+    size_t length = std::strlen(signature);
+    return data.size() >= length && std::memcmp(data.data(), signature, length) == 0;
+}
+
+static uint16_t readU16BE(const unsigned char* p) {
+    return static_cast<uint16_t>(p[0] << 8 | p[1]);
+}
+
+static uint32_t readU32BE(const unsigned char* p) {
+    return static_cast<uint32_t>(p[0]) << 24 | static_cast<uint32_t>(p[1]) << 16 |
+           static_cast<uint32_t>(p[2]) << 8 | p[3];
+}
+
+static uint32_t readU32LE(const unsigned char* p) {
+    return static_cast<uint32_t>(p[3]) << 24 | static_cast<uint32_t>(p[2]) << 16 |
+           static_cast<uint32_t>(p[1]) << 8 | p[0];
+}
+
+static float readFloatBE(const unsigned char* p) {
+    uint32_t bits = readU32BE(p);
+    float value;
+    std::memcpy(&value, &bits, sizeof(value));
+    return value;
+}
+
+// GIMP: a "GIMP Palette" line, then "R G B name" lines among Name:, Columns:
+// and # comment lines
+static void parseGpl(const std::string& text, std::vector<cv::Vec3b>& colors) {
+    std::istringstream lines(text);
+    std::string line;
+    std::getline(lines, line);
+    while (std::getline(lines, line)) {
+        int r, g, b;
+        if (line.empty() || line[0] == '#') continue;
+        std::istringstream values(line);
+        if (values >> r >> g >> b) colors.push_back(fromRgb(r, g, b));
+    }
+}
+
+// JASC: "JASC-PAL", a version line and a count line, then "R G B" lines
+static void parseJascPal(const std::string& text, std::vector<cv::Vec3b>& colors) {
+    std::istringstream lines(text);
+    std::string line;
+    for (int i = 0; i < 3; ++i) std::getline(lines, line);
+    while (std::getline(lines, line)) {
+        int r, g, b;
+        std::istringstream values(line);
+        if (values >> r >> g >> b) colors.push_back(fromRgb(r, g, b));
+    }
+}
+
+// Microsoft RIFF palette: a "data" chunk holding a version, a count and that
+// many R, G, B, flags entries
+static void parseRiffPal(const std::vector<unsigned char>& data, std::vector<cv::Vec3b>& colors) {
+    size_t offset = 12;
+    while (offset + 8 <= data.size()) {
+        uint32_t size = readU32LE(&data[offset + 4]);
+        if (std::memcmp(&data[offset], "data", 4) == 0 && offset + 12 <= data.size()) {
+            size_t count = data[offset + 10] | data[offset + 11] << 8;
+            for (size_t i = 0; i < count && offset + 12 + i * 4 + 3 <= data.size(); ++i) {
+                const unsigned char* entry = &data[offset + 12 + i * 4];
+                colors.push_back(fromRgb(entry[0], entry[1], entry[2]));
+            }
+            return;
+        }
+        offset += 8 + size + (size & 1);
+    }
+}
+
+// Adobe Color Table: 256 R, G, B triples, optionally followed by the number
+// of colors used and a transparent index (big-endian 16 bits each)
+static void parseAct(const std::vector<unsigned char>& data, std::vector<cv::Vec3b>& colors) {
+    size_t count = std::min<size_t>(256, data.size() / 3);
+    if (data.size() >= 772) count = std::min<size_t>(count, readU16BE(&data[768]));
+    for (size_t i = 0; i < count; ++i) colors.push_back(fromRgb(data[i * 3], data[i * 3 + 1], data[i * 3 + 2]));
+}
+
+// Adobe Swatch Exchange: "ASEF", a version and a block count, then blocks of
+// which color entries (type 1) hold a UTF-16 name, a color model and its
+// big-endian float values. Group blocks are skipped
+static void parseAse(const std::vector<unsigned char>& data, std::vector<cv::Vec3b>& colors) {
+    if (data.size() < 12) return;
+    uint32_t blocks = readU32BE(&data[8]);
+    size_t offset = 12;
+    for (uint32_t block = 0; block < blocks && offset + 6 <= data.size(); ++block) {
+        uint16_t type = readU16BE(&data[offset]);
+        uint32_t length = readU32BE(&data[offset + 2]);
+        size_t start = offset + 6;
+        offset = start + length;
+        if (type != 0x0001 || offset > data.size() || length < 2) continue;
+
+        size_t model = start + 2 + readU16BE(&data[start]) * 2;
+        if (model + 4 > offset) continue;
+        std::string name(reinterpret_cast<const char*>(&data[model]), 4);
+        auto value = [&](int i) {
+            size_t at = model + 4 + i * 4;
+            return at + 4 <= offset ? readFloatBE(&data[at]) : 0.0f;
+        };
+
+        if (name == "RGB ") {
+            colors.push_back(fromUnitRgb(value(0), value(1), value(2)));
+        } else if (name == "CMYK") {
+            float k = 1.0f - value(3);
+            colors.push_back(fromUnitRgb((1.0f - value(0)) * k, (1.0f - value(1)) * k, (1.0f - value(2)) * k));
+        } else if (name == "Gray") {
+            colors.push_back(fromUnitRgb(value(0), value(0), value(0)));
+        } else if (name == "LAB ") {
+            // L is stored as 0-1, a and b as they are
+            cv::Mat lab(1, 1, CV_32FC3, cv::Scalar(value(0) * 100.0f, value(1), value(2)));
+            cv::Mat bgr;
+            cv::cvtColor(lab, bgr, cv::COLOR_Lab2BGR);
+            cv::Vec3f color = bgr.at<cv::Vec3f>(0, 0);
+            colors.push_back(fromUnitRgb(color[2], color[1], color[0]));
+        }
+    }
+}
+
+// Hex lists and Paint.NET: each line's first word, as RRGGBB or AARRGGBB
+// with an optional #. Lines starting with ; are comments
+static void parseHexList(const std::string& text, std::vector<cv::Vec3b>& colors) {
+    std::istringstream lines(text);
+    std::string line;
+    while (std::getline(lines, line)) {
+        std::istringstream words(line);
+        std::string word;
+        if (!(words >> word) || word[0] == ';') continue;
+        if (word[0] == '#') word = word.substr(1);
+        if ((word.size() != 6 && word.size() != 8) ||
+            word.find_first_not_of("0123456789abcdefABCDEF") != std::string::npos) {
+            continue;
+        }
+        unsigned long rgb = std::stoul(word.substr(word.size() - 6), nullptr, 16);
+        colors.push_back(fromRgb((rgb >> 16) & 0xFF, (rgb >> 8) & 0xFF, rgb & 0xFF));
+    }
+}
+
+// Swatch images: distinct colors row by row. More than 256 means it's a
+// picture rather than a palette
+static bool parseSwatch(const std::string& path, std::vector<cv::Vec3b>& colors) {
+    cv::Mat image = cv::imread(path, cv::IMREAD_COLOR);
+    for (int y = 0; y < image.rows; ++y) {
+        for (int x = 0; x < image.cols; ++x) {
+            const cv::Vec3b& color = image.at<cv::Vec3b>(y, x);
+            if (std::find(colors.begin(), colors.end(), color) != colors.end()) continue;
+            if (colors.size() == 256) return false;
+            colors.push_back(color);
+        }
+    }
+    return true;
+}
+
+static void appendU16BE(std::string& data, uint16_t value) {
+    data += static_cast<char>(value >> 8);
+    data += static_cast<char>(value & 0xFF);
+}
+
+static void appendU32BE(std::string& data, uint32_t value) {
+    appendU16BE(data, static_cast<uint16_t>(value >> 16));
+    appendU16BE(data, static_cast<uint16_t>(value & 0xFFFF));
+}
+
+// Version 1.0, one RGB color entry per color named after its hex value
+static std::string writeAse(const std::vector<cv::Vec3b>& colors) {
+    std::string data = "ASEF";
+    appendU16BE(data, 1);
+    appendU16BE(data, 0);
+    appendU32BE(data, static_cast<uint32_t>(colors.size()));
+    for (const auto& color : colors) {
+        std::string name = "#" + toHex(color);
+        std::string block;
+        appendU16BE(block, static_cast<uint16_t>(name.size() + 1));
+        for (char c : name) appendU16BE(block, static_cast<uint16_t>(c));
+        appendU16BE(block, 0);
+        block += "RGB ";
+        for (int channel = 2; channel >= 0; --channel) {
+            float value = color[channel] / 255.0f;
+            uint32_t bits;
+            std::memcpy(&bits, &value, sizeof(bits));
+            appendU32BE(block, bits);
+        }
+        appendU16BE(block, 2);     // Normal (not global or spot) color
+        appendU16BE(data, 0x0001);
+        appendU32BE(data, static_cast<uint32_t>(block.size()));
+        data += block;
+    }
+    return data;
+}
+
+static std::string writeGpl(const std::vector<cv::Vec3b>& colors, const std::string& name) {
+    std::ostringstream text;
+    text << "GIMP Palette\nName: " << name << "\nColumns: " << std::min<size_t>(colors.size(), 16) << "\n#\n";
+    for (const auto& color : colors) {
+        char line[32];
+        std::snprintf(line, sizeof(line), "%3d %3d %3d\t#%s\n", color[2], color[1], color[0], toHex(color).c_str());
+        text << line;
+    }
+    return text.str();
+}
+
+bool loadPalette(const std::string& path, std::vector<cv::Vec3b>& colors, Errors::Error* error) {
+    auto fail = [error](ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    std::string extension = ImageIO::getExtension(path);
+    const auto& extensions = getPaletteExtensions();
+    if (std::find(extensions.begin(), extensions.end(), extension) == extensions.end()) {
+        return fail(ErrorCode::UNSUPPORTED_FORMAT,
+                    "Not a palette file (expected .gpl, .ase, .act, .pal, .txt, .hex or .png)");
+    }
+    std::ifstream file(path, std::ios::binary);
+    if (!file) return fail(ErrorCode::NOT_FOUND, "Could not open " + path);
+    std::vector<unsigned char> data((std::istreambuf_iterator<char>(file)), std::istreambuf_iterator<char>());
+    std::string text(data.begin(), data.end());
+
+    std::vector<cv::Vec3b> found;
+    if (startsWith(data, "GIMP Palette")) {
+        parseGpl(text, found);
+    } else if (startsWith(data, "JASC-PAL")) {
+        parseJascPal(text, found);
+    } else if (startsWith(data, "RIFF") && data.size() >= 12 && std::memcmp(&data[8], "PAL ", 4) == 0) {
+        parseRiffPal(data, found);
+    } else if (startsWith(data, "ASEF")) {
+        parseAse(data, found);
+    } else if (extension == "act") {
+        parseAct(data, found);
+    } else if (extension == "png") {
+        if (!parseSwatch(path, found)) {
+            return fail(ErrorCode::DECODE, path + " has more than 256 colors, so it isn't a palette swatch");
+        }
+    } else {
+        parseHexList(text, found);
+    }
+
+    std::vector<cv::Vec3b> unique;
+    for (const auto& color : found) {
+        if (std::find(unique.begin(), unique.end(), color) == unique.end()) unique.push_back(color);
+    }
+    if (unique.empty()) return fail(ErrorCode::DECODE, "No colors found in " + path);
+    colors = unique;
+    return true;
+}
+
+bool savePalette(const std::string& path, const std::vector<cv::Vec3b>& colors, Errors::Error* error) {
+    auto fail = [error](ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    std::string extension = ImageIO::getExtension(path);
+    const auto& extensions = getPaletteSaveExtensions();
+    if (std::find(extensions.begin(), extensions.end(), extension) == extensions.end()) {
+        return fail(ErrorCode::UNSUPPORTED_FORMAT, "Palettes can be saved as .gpl, .ase, .hex or .png");
+    }
+    if (colors.empty()) return fail(ErrorCode::INVALID_ARGUMENT, "The palette has no colors");
+
+    if (extension == "png") {
+        cv::Mat swatch(1, static_cast<int>(colors.size()), CV_8UC3);
+        for (size_t i = 0; i < colors.size(); ++i) swatch.at<cv::Vec3b>(0, static_cast<int>(i)) = colors[i];
+        if (!cv::imwrite(path, swatch)) return fail(ErrorCode::IO, "Could not write " + path);
+        return true;
+    }
+
+    std::string data;
+    if (extension == "gpl") {
+        data = writeGpl(colors, std::filesystem::path(path).stem().string());
+    } else if (extension == "ase") {
+        data = writeAse(colors);
+    } else {
+        for (const auto& color : colors) data += toHex(color) + "\n";
+    }
+    std::ofstream file(path, std::ios::binary);
+    if (!file.write(data.data(), static_cast<std::streamsize>(data.size()))) {
+        return fail(ErrorCode::IO, "Could not write " + path);
+    }
+    return true;
+}
+
+} // namespace PaletteFile
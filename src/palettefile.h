@@ -0,0 +1,36 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "errors.h"
+
+namespace PaletteFile {
+
+// Extensions of the palette files loadPalette reads, without the dot
+const std::vector<std::string>& getPaletteExtensions();
+
+// Read the colors of a palette file, as BGR for Parameters::customPalette.
+// Formats are told apart by their content where they have a signature:
+//   .gpl   GIMP palette
+//   .ase   Adobe Swatch Exchange (RGB, CMYK, Lab and gray swatches)
+//   .act   Adobe Color Table, 256 RGB triples and an optional color count
+//   .pal   JASC (Paint Shop Pro) text or Microsoft RIFF palette
+//   .txt   Paint.NET palette, AARRGGBB per line with ; comments
+//   .hex   One RRGGBB (or #RRGGBB) per line, as exported by Lospec
+//   .png   Swatch image, such as savePalette writes; its distinct colors in
+//          reading order (at most 256)
+// Repeated colors are kept once, in the order they first appear. On failure
+// error says why
+bool loadPalette(const std::string& path, std::vector<cv::Vec3b>& colors, Errors::Error* error = nullptr);
+
+// Extensions savePalette writes: gpl, ase, hex and png
+const std::vector<std::string>& getPaletteSaveExtensions();
+
+// Write BGR colors as a palette file, by extension. GIMP palettes are named
+// after the file; the PNG swatch has one pixel per color in a row, as Lospec
+// and most pixel art editors use. On failure error says why
+bool savePalette(const std::string& path, const std::vector<cv::Vec3b>& colors, Errors::Error* error = nullptr);
+
+} // namespace PaletteFile
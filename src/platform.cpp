@@ -1,9 +1,37 @@
 #include "platform.h"
 #include <iostream>
+#include <cctype>
 #include <cstring>
-
-#ifndef _WIN32
 #include <cstdio>
+#include <cstdlib>
+#include <csignal>
+#include <algorithm>
+#include <chrono>
+#include <filesystem>
+#include <fstream>
+#include <iterator>
+#include <map>
+#include <mutex>
+#include <random>
+#include <thread>
+#include <utility>
+#include <vector>
+
+#ifdef _WIN32
+#define popen _popen
+#define pclose _pclose
+#else
+#include <sys/resource.h>
+#include <sys/wait.h>
+#include <unistd.h>
+#endif
+
+#ifdef __linux__
+#include <sys/syscall.h>
+#endif
+
+#ifdef __APPLE__
+#include <mach-o/dyld.h>
 #endif
 
 namespace Platform {
@@ -20,7 +48,7 @@ std::string openFileDialog() {
     ofn.hwndOwner = NULL;
     ofn.lpstrFile = filepath;
     ofn.nMaxFile = sizeof(filepath);
-    ofn.lpstrFilter = "Image Files\0*.png;*.jpg;*.jpeg;*.bmp;*.tiff;*.webp\0All Files\0*.*\0";
+    ofn.lpstrFilter = "Image Files\0*.png;*.jpg;*.jpeg;*.bmp;*.tiff;*.webp;*.avif;*.jxl;*.heic;*.heif;*.pbm;*.pgm;*.ppm;*.pnm;*.dng;*.cr2;*.cr3;*.nef;*.arw;*.raf;*.orf;*.rw2\0All Files\0*.*\0";
     ofn.nFilterIndex = 1;
     ofn.lpstrFileTitle = NULL;
     ofn.nMaxFileTitle = 0;
@@ -34,7 +62,7 @@ std::string openFileDialog() {
     // Unix-like systems (Linux/macOS)
 
     // Try zenity (GTK-based, most common on Linux)
-    FILE* pipe = popen("zenity --file-selection --title='Select Image' --file-filter='Images | *.png *.jpg *.jpeg *.bmp *.tiff *.webp' 2>/dev/null", "r");
+    FILE* pipe = popen("zenity --file-selection --title='Select Image' --file-filter='Images | *.png *.jpg *.jpeg *.bmp *.tiff *.webp *.avif *.jxl *.heic *.heif *.pbm *.pgm *.ppm *.pnm *.dng *.cr2 *.cr3 *.nef *.arw *.raf *.orf *.rw2' 2>/dev/null", "r");
     if (pipe) {
         char buffer[512];
         if (fgets(buffer, sizeof(buffer), pipe)) {
@@ -49,7 +77,7 @@ std::string openFileDialog() {
     }
 
     // Try kdialog (KDE)
-    pipe = popen("kdialog --getopenfilename ~ 'Images (*.png *.jpg *.jpeg *.bmp *.tiff *.webp)' 2>/dev/null", "r");
+    pipe = popen("kdialog --getopenfilename ~ 'Images (*.png *.jpg *.jpeg *.bmp *.tiff *.webp *.avif *.jxl *.heic *.heif *.pbm *.pgm *.ppm *.pnm *.dng *.cr2 *.cr3 *.nef *.arw *.raf *.orf *.rw2)' 2>/dev/null", "r");
     if (pipe) {
         char buffer[512];
         if (fgets(buffer, sizeof(buffer), pipe)) {
@@ -162,4 +190,1083 @@ std::string saveFileDialog() {
     return filename;
 }
 
+bool isRemoteUrl(const std::string& path) {
+    return path.rfind("http://", 0) == 0 || path.rfind("https://", 0) == 0;
+}
+
+// Quote an argument for the shell that popen runs
+static std::string shellQuote(const std::string& arg) {
+#ifdef _WIN32
+    // cmd expands %VAR% even inside quotes, so each % (common in URLs) is
+    // closed out of the quotes and escaped with a caret, which only works there
+    std::string quoted = "\"";
+    for (char c : arg) {
+        if (c == '%') {
+            quoted += "\"^%\"";
+        } else {
+            quoted += c;
+        }
+    }
+    return quoted + "\"";
+#else
+    std::string quoted = "'";
+    for (char c : arg) {
+        if (c == '\'') {
+            quoted += "'\\''";
+        } else {
+            quoted += c;
+        }
+    }
+    return quoted + "'";
+#endif
+}
+
+std::string createTempFile(const std::string& suffix) {
+    std::error_code ec;
+    std::filesystem::path directory = std::filesystem::temp_directory_path(ec);
+    if (ec) return "";
+#ifdef _WIN32
+    // CREATE_NEW fails if the name is taken, so try random names until one is free
+    std::random_device random;
+    for (int attempt = 0; attempt < 100; ++attempt) {
+        char name[32];
+        std::snprintf(name, sizeof(name), "%08x%08x", random(), random());
+        std::string path = (directory / ("dithers-boyfriend-" + std::string(name) + suffix)).string();
+        HANDLE file = CreateFileA(path.c_str(), GENERIC_WRITE, 0, nullptr, CREATE_NEW, FILE_ATTRIBUTE_NORMAL, nullptr);
+        if (file != INVALID_HANDLE_VALUE) {
+            CloseHandle(file);
+            return path;
+        }
+        if (GetLastError() != ERROR_FILE_EXISTS) return "";
+    }
+    return "";
+#else
+    std::string pattern = (directory / ("dithers-boyfriend-XXXXXX" + suffix)).string();
+    std::vector<char> path(pattern.begin(), pattern.end());
+    path.push_back('\0');
+    int fd = mkstemps(path.data(), static_cast<int>(suffix.size()));
+    if (fd < 0) return "";
+    close(fd);
+    return path.data();
+#endif
+}
+
+// File extension from the URL path (ignoring query and fragment), so the
+// temp file keeps a name that format detection understands
+static std::string urlExtension(const std::string& url) {
+    std::string path = url.substr(0, url.find_first_of("?#"));
+    size_t slash = path.find_last_of('/');
+    size_t dot = path.find_last_of('.');
+    if (dot == std::string::npos || (slash != std::string::npos && dot < slash)) return "";
+
+    std::string ext = path.substr(dot);
+    if (ext.size() > 6) return "";
+    for (size_t i = 1; i < ext.size(); ++i) {
+        if (!std::isalnum(static_cast<unsigned char>(ext[i]))) return "";
+    }
+    return ext;
+}
+
+std::string downloadToTempFile(const std::string& url, size_t maxBytes, DownloadProgress progress) {
+    if (!isRemoteUrl(url)) return "";
+
+#ifdef _WIN32
+    if (url.find('"') != std::string::npos) return "";
+    const char* readMode = "rb";
+    const char* nullDevice = "NUL";
+#else
+    const char* readMode = "r";
+    const char* nullDevice = "/dev/null";
+#endif
+
+    // Only follow redirects to http(s), and let curl reject oversized
+    // responses early when the server announces their size
+    std::string curlArgs = " --proto =http,https --proto-redir =http,https --max-filesize " +
+                           std::to_string(maxBytes) + " " + shellQuote(url) + " 2>" + nullDevice;
+
+    // Ask for the size first so progress can show a total (not all servers send one)
+    size_t total = 0;
+    FILE* pipe = popen(("curl -sSIL" + curlArgs).c_str(), "r");
+    if (pipe) {
+        char buffer[512];
+        while (fgets(buffer, sizeof(buffer), pipe)) {
+            std::string line = buffer;
+            std::transform(line.begin(), line.end(), line.begin(), ::tolower);
+            if (line.rfind("http/", 0) == 0) {
+                total = 0;  // New response in a redirect chain
+            } else if (line.rfind("content-length:", 0) == 0) {
+                total = std::strtoull(line.c_str() + 15, nullptr, 10);
+            }
+        }
+        pclose(pipe);
+    }
+
+    if (total > maxBytes) {
+        std::cerr << "Error: Remote file is " << total << " bytes, limit is " << maxBytes << std::endl;
+        return "";
+    }
+
+    std::string tempPath = createTempFile(urlExtension(url));
+    if (tempPath.empty()) return "";
+
+    std::ofstream out(tempPath, std::ios::binary);
+    if (!out) {
+        std::error_code ec;
+        std::filesystem::remove(tempPath, ec);
+        return "";
+    }
+
+    pipe = popen(("curl -sSfL" + curlArgs).c_str(), readMode);
+    if (!pipe) {
+        out.close();
+        std::error_code ec;
+        std::filesystem::remove(tempPath, ec);
+        return "";
+    }
+
+    // Stream the body in chunks, stopping as soon as the limit is exceeded
+    std::vector<char> chunk(64 * 1024);
+    size_t received = 0;
+    bool tooLarge = false;
+    size_t count;
+    while ((count = fread(chunk.data(), 1, chunk.size(), pipe)) > 0) {
+        received += count;
+        if (received > maxBytes) {
+            tooLarge = true;
+            break;
+        }
+        out.write(chunk.data(), count);
+        if (progress) progress(received, total);
+    }
+
+    int status = pclose(pipe);
+    out.close();
+
+    if (tooLarge || status != 0 || received == 0 || !out) {
+        if (tooLarge) {
+            std::cerr << "Error: Remote file exceeds the " << maxBytes << " byte limit" << std::endl;
+        } else {
+            std::cerr << "Error: Could not download " << url << " (is curl installed?)" << std::endl;
+        }
+        std::error_code ec;
+        std::filesystem::remove(tempPath, ec);
+        return "";
+    }
+
+    return tempPath;
+}
+
+// Looked up once per program, as each lookup starts a shell
+static bool haveCommand(const std::string& name) {
+    static std::mutex cacheMutex;
+    static std::map<std::string, bool> found;
+    {
+        std::lock_guard<std::mutex> lock(cacheMutex);
+        auto cached = found.find(name);
+        if (cached != found.end()) return cached->second;
+    }
+
+#ifdef _WIN32
+    bool installed = std::system(("where " + name + " >NUL 2>NUL").c_str()) == 0;
+#else
+    bool installed = std::system(("command -v " + name + " >/dev/null 2>&1").c_str()) == 0;
+#endif
+    std::lock_guard<std::mutex> lock(cacheMutex);
+    found[name] = installed;
+    return installed;
+}
+
+// Folder of the running program, where a bundled ffmpeg would be
+static std::filesystem::path getExecutableDir() {
+    std::error_code ec;
+#ifdef _WIN32
+    char buffer[MAX_PATH];
+    DWORD length = GetModuleFileNameA(nullptr, buffer, MAX_PATH);
+    if (length == 0 || length == MAX_PATH) return {};
+    return std::filesystem::path(std::string(buffer, length)).parent_path();
+#elif defined(__APPLE__)
+    char buffer[4096];
+    uint32_t size = sizeof(buffer);
+    if (_NSGetExecutablePath(buffer, &size) != 0) return {};
+    std::filesystem::path executable = std::filesystem::canonical(buffer, ec);
+    return ec ? std::filesystem::path() : executable.parent_path();
+#else
+    std::filesystem::path executable = std::filesystem::read_symlink("/proc/self/exe", ec);
+    return ec ? std::filesystem::path() : executable.parent_path();
+#endif
+}
+
+// Settings kept across runs, one "key<TAB>value" per line like the usage statistics
+static std::string getConfigPath() {
+    std::string dir = getAppDataDir();
+    return dir.empty() ? "" : dir + "/config.txt";
+}
+
+// Read once and then kept in memory, since every ffmpeg run looks it up.
+// Only this process changes it, through setFfmpegPath
+static std::mutex configMutex;
+
+// Call with configMutex held
+static std::map<std::string, std::string>& cachedConfig() {
+    static std::map<std::string, std::string> config;
+    static bool loaded = false;
+    if (loaded) return config;
+    loaded = true;
+    std::ifstream file(getConfigPath());
+    std::string line;
+    while (std::getline(file, line)) {
+        size_t tab = line.find('\t');
+        if (tab != std::string::npos) config[line.substr(0, tab)] = line.substr(tab + 1);
+    }
+    return config;
+}
+
+std::string getFfmpegPath() {
+    std::lock_guard<std::mutex> lock(configMutex);
+    auto& config = cachedConfig();
+    auto match = config.find("ffmpeg");
+    return match == config.end() ? "" : match->second;
+}
+
+bool setFfmpegPath(const std::string& path) {
+    std::string configPath = getConfigPath();
+    if (configPath.empty() || path.find_first_of("\r\n") != std::string::npos) return false;
+
+    std::lock_guard<std::mutex> lock(configMutex);
+    auto& config = cachedConfig();
+    if (path.empty()) {
+        config.erase("ffmpeg");
+    } else {
+        config["ffmpeg"] = path;
+    }
+    std::ofstream file(configPath);
+    for (const auto& [key, value] : config) file << key << "\t" << value << "\n";
+    return static_cast<bool>(file);
+}
+
+ToolInfo findFfmpegTool(const std::string& name) {
+    ToolInfo tool;
+    tool.name = name;
+#ifdef _WIN32
+    const std::string program = name + ".exe";
+    const char separator = ';';
+#else
+    const std::string program = name;
+    const char separator = ':';
+#endif
+    std::error_code ec;
+    auto found = [&](const std::filesystem::path& path, const char* source) {
+        if (!std::filesystem::is_regular_file(path, ec)) return false;
+        tool.path = path.string();
+        tool.source = source;
+        return true;
+    };
+
+    // A configured ffmpeg binary may have any name; ffprobe is next to it
+    std::filesystem::path configured = getFfmpegPath();
+    if (!configured.empty()) {
+        bool folder = std::filesystem::is_directory(configured, ec);
+        if (!folder && name == "ffmpeg" && found(configured, "configured")) return tool;
+        if (found((folder ? configured : configured.parent_path()) / program, "configured")) return tool;
+    }
+    std::filesystem::path bundled = getExecutableDir();
+    if (!bundled.empty() && found(bundled / program, "bundled")) return tool;
+
+    const char* searchPath = std::getenv("PATH");
+    std::string folders = searchPath ? searchPath : "";
+    for (size_t start = 0; start <= folders.size();) {
+        size_t end = folders.find(separator, start);
+        if (end == std::string::npos) end = folders.size();
+        if (end > start && found(std::filesystem::path(folders.substr(start, end - start)) / program, "PATH")) {
+            return tool;
+        }
+        start = end + 1;
+    }
+    return tool;
+}
+
+// Start of a command line running a tool ("" if it wasn't found). Tools on
+// the PATH run by name, as they always have
+static std::string toolCommand(const std::string& name) {
+    ToolInfo tool = findFfmpegTool(name);
+    if (tool.path.empty()) return "";
+    if (tool.source == "PATH") return name;
+#ifdef _WIN32
+    // cmd drops the outer quotes of a command line that starts with one
+    return "call " + shellQuote(tool.path);
+#else
+    return shellQuote(tool.path);
+#endif
+}
+
+ToolInfo checkFfmpegTool(const std::string& name) {
+    ToolInfo tool = findFfmpegTool(name);
+    std::string command = toolCommand(name);
+    if (command.empty()) return tool;
+#ifdef _WIN32
+    command += " -version 2>NUL";
+#else
+    command += " -version 2>/dev/null";
+#endif
+    FILE* pipe = popen(command.c_str(), "r");
+    if (!pipe) return tool;
+
+    // "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 ..."
+    char line[512];
+    if (fgets(line, sizeof(line), pipe)) {
+        std::string text = line;
+        size_t at = text.find(" version ");
+        if (at != std::string::npos) {
+            at += 9;
+            tool.version = text.substr(at, text.find_first_of(" \r\n", at) - at);
+        }
+    }
+    while (fgets(line, sizeof(line), pipe)) {}
+    pclose(pipe);
+    return tool;
+}
+
+// Command lines that decode a format, most specific first. {input} and
+// {output} are replaced by the quoted paths; toStdout tools print the image
+struct ImageConverter {
+    const char* program;
+    std::string arguments;
+    bool toStdout;
+};
+
+static std::vector<ImageConverter> imageConverters(const std::string& format, int width = 0) {
+    std::vector<ImageConverter> converters;
+    if (format == "svg") {
+        // Rasterized straight at the width asked for, transparent where nothing is drawn
+        std::string size = std::to_string(width);
+        converters.push_back({"resvg", (width > 0 ? "-w " + size + " " : "") + "{input} {output}", false});
+        converters.push_back({"rsvg-convert", (width > 0 ? "-w " + size + " " : "") + "-o {output} {input}", false});
+        converters.push_back({"inkscape", "{input} --export-type=png --export-filename={output}" +
+                                              (width > 0 ? " -w " + size : ""), false});
+        converters.push_back({"magick", "-background none -density 300 {input}" +
+                                            (width > 0 ? " -resize " + size + "x" : "") + " {output}", false});
+        return converters;
+    }
+    if (format == "raw") {
+        // Camera white balance, 16 bits with the sRGB-like gamma, as TIFF
+        converters.push_back({"dcraw_emu", "-w -6 -T -Z {output} {input}", false});
+        converters.push_back({"dcraw", "-c -w -6 -T {input}", true});
+#ifdef __APPLE__
+        converters.push_back({"sips", "-s format tiff {input} --out {output}", false});
+#endif
+        converters.push_back({"magick", "{input} -depth 16 {output}", false});
+        return converters;
+    }
+    if (format == "heic" || format == "heif") {
+        converters.push_back({"heif-dec", "{input} -o {output}", false});
+        converters.push_back({"heif-convert", "{input} {output}", false});
+    } else if (format == "avif") {
+        converters.push_back({"avifdec", "{input} {output}", false});
+    } else if (format == "jxl") {
+        converters.push_back({"djxl", "{input} {output}", false});
+    }
+#ifdef __APPLE__
+    converters.push_back({"sips", "-s format png {input} --out {output}", false});
+#endif
+    converters.push_back({"magick", "{input} {output}", false});
+    return converters;
+}
+
+bool hasImageConverter(const std::string& format) {
+    for (const auto& converter : imageConverters(format)) {
+        if (haveCommand(converter.program)) return true;
+    }
+    return false;
+}
+
+bool identifyRawImage(const std::string& input, int& width, int& height) {
+#ifdef _WIN32
+    if (input.find('"') != std::string::npos) return false;
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+
+    // Everything the program prints, "" if it isn't installed
+    auto run = [&](const std::string& program, const std::string& arguments) {
+        std::string output;
+        if (!haveCommand(program)) return output;
+        std::string command = program + " " + arguments + " " + shellQuote(input) + " 2>" + nullDevice;
+        FILE* pipe = popen(command.c_str(), "r");
+        if (!pipe) return output;
+        char buffer[512];
+        while (fgets(buffer, sizeof(buffer), pipe)) output += buffer;
+        pclose(pipe);
+        return output;
+    };
+
+    // "Output size: 6034 x 4028", the size after any shrinking the decoder does
+    for (const auto& tool : {std::make_pair(std::string("raw-identify"), std::string("-v")),
+                             std::make_pair(std::string("dcraw"), std::string("-i -v"))}) {
+        std::string output = run(tool.first, tool.second);
+        size_t at = output.find("Output size:");
+        if (at != std::string::npos &&
+            std::sscanf(output.c_str() + at, "Output size: %d x %d", &width, &height) == 2 &&
+            width > 0 && height > 0) {
+            return true;
+        }
+    }
+
+    // -ping reads only as much of the file as it needs for the size
+    std::string output = run("magick", "identify -ping -format \"%w %h\"");
+    return std::sscanf(output.c_str(), "%d %d", &width, &height) == 2 && width > 0 && height > 0;
+}
+
+bool convertImage(const std::string& input, const std::string& output, const std::string& format, int width) {
+#ifdef _WIN32
+    if (input.find('"') != std::string::npos || output.find('"') != std::string::npos) return false;
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+
+    for (const auto& converter : imageConverters(format, width)) {
+        if (!haveCommand(converter.program)) continue;
+        std::string arguments = converter.arguments;
+        for (const auto& field : {std::make_pair(std::string("{input}"), input),
+                                  std::make_pair(std::string("{output}"), output)}) {
+            size_t at = arguments.find(field.first);
+            if (at != std::string::npos) arguments.replace(at, field.first.size(), shellQuote(field.second));
+        }
+        std::string command = std::string(converter.program) + " " + arguments +
+                              (converter.toStdout ? " >" + shellQuote(output) : std::string(" >") + nullDevice) +
+                              " 2>" + nullDevice;
+        std::error_code ec;
+        if (std::system(command.c_str()) == 0 && std::filesystem::file_size(output, ec) > 0 && !ec) {
+            return true;
+        }
+    }
+    return false;
+}
+
+bool renderText(const std::string& text, const std::string& font, int size, const std::string& color,
+                const std::string& background, int padding, const std::string& output) {
+    if (text.empty() || !haveCommand("magick")) return false;
+#ifdef _WIN32
+    for (const std::string* value : {&text, &font, &output}) {
+        if (value->find('"') != std::string::npos) return false;
+    }
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+
+    // label: sizes the canvas to the text. A leading @ would read a file and
+    // % starts an escape, so both are escaped
+    std::string label = text[0] == '@' ? "\\" : "";
+    for (char c : text) label += c == '%' ? std::string("%%") : std::string(1, c);
+    std::string command = "magick -background " + shellQuote("#" + background) + " -fill " + shellQuote("#" + color) +
+                          (font.empty() ? "" : " -font " + shellQuote(font)) + " -pointsize " + std::to_string(size) +
+                          " " + shellQuote("label:" + label) + " -bordercolor " + shellQuote("#" + background) +
+                          " -border " + std::to_string(padding) + " " + shellQuote(output) + " >" + nullDevice +
+                          " 2>" + nullDevice;
+    std::error_code ec;
+    return std::system(command.c_str()) == 0 && std::filesystem::file_size(output, ec) > 0 && !ec;
+}
+
+// Run a shell command, checking keepGoing every 50 ms while it runs and
+// stopping it (with anything it started) when that returns false. The exit
+// status, or -1 if it couldn't be started or was stopped
+static int runCancellable(const std::string& command, const std::function<bool()>& keepGoing) {
+#ifdef _WIN32
+    // A job object takes ffmpeg down along with the cmd that started it
+    std::string line = "cmd /c " + command;
+    STARTUPINFOA startup = {};
+    startup.cb = sizeof(startup);
+    PROCESS_INFORMATION process = {};
+    if (!CreateProcessA(nullptr, line.data(), nullptr, nullptr, FALSE, CREATE_NO_WINDOW | CREATE_SUSPENDED, nullptr,
+                        nullptr, &startup, &process)) {
+        return -1;
+    }
+    HANDLE job = CreateJobObjectA(nullptr, nullptr);
+    if (job) AssignProcessToJobObject(job, process.hProcess);
+    ResumeThread(process.hThread);
+    CloseHandle(process.hThread);
+
+    int status = -1;
+    while (true) {
+        DWORD wait = WaitForSingleObject(process.hProcess, 50);
+        if (wait == WAIT_OBJECT_0) {
+            DWORD code;
+            if (GetExitCodeProcess(process.hProcess, &code)) status = static_cast<int>(code);
+            break;
+        }
+        if (wait != WAIT_TIMEOUT) break;
+        if (keepGoing && !keepGoing()) {
+            if (job) {
+                TerminateJobObject(job, 1);
+            } else {
+                TerminateProcess(process.hProcess, 1);
+            }
+            WaitForSingleObject(process.hProcess, INFINITE);
+            break;
+        }
+    }
+    CloseHandle(process.hProcess);
+    if (job) CloseHandle(job);
+    return status;
+#else
+    // exec makes the program replace the shell, so the signal reaches it
+    std::string line = "exec " + command;
+    pid_t pid = fork();
+    if (pid < 0) return -1;
+    if (pid == 0) {
+        execl("/bin/sh", "sh", "-c", line.c_str(), static_cast<char*>(nullptr));
+        _exit(127);
+    }
+
+    int status = 0;
+    while (true) {
+        pid_t done = waitpid(pid, &status, WNOHANG);
+        if (done == pid) return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+        if (done < 0) return -1;
+        if (keepGoing && !keepGoing()) {
+            kill(pid, SIGTERM);
+            waitpid(pid, &status, 0);
+            return -1;
+        }
+        std::this_thread::sleep_for(std::chrono::milliseconds(50));
+    }
+#endif
+}
+
+bool muxAudio(const std::string& video, const std::string& source, const std::string& output,
+              double startSeconds, const std::function<bool()>& keepGoing) {
+#ifdef _WIN32
+    for (const std::string* path : {&video, &source, &output}) {
+        if (path->find('"') != std::string::npos) return false;
+    }
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+    std::string ffmpeg = toolCommand("ffmpeg");
+    if (ffmpeg.empty()) return false;
+
+    std::string ext = std::filesystem::path(output).extension().string();
+    std::transform(ext.begin(), ext.end(), ext.begin(), [](unsigned char c) { return std::tolower(c); });
+    // Seeking before -i skips to the clip's start; -shortest ends the audio with the video
+    char seek[48] = "";
+    if (startSeconds > 0.0) std::snprintf(seek, sizeof(seek), "-ss %.3f ", startSeconds);
+    for (const char* audioCodec : {"copy", ext == ".webm" ? "libopus" : "aac"}) {
+        // "?" keeps sources without an audio stream from failing
+        std::string command = ffmpeg + " -y -v error -i " + shellQuote(video) + " " + seek + "-i " + shellQuote(source) +
+                              " -map 0:v:0 -map " + shellQuote("1:a:0?") + " -c:v copy -c:a " + audioCodec +
+                              " -shortest " + shellQuote(output) + " >" + nullDevice + " 2>" + nullDevice;
+        std::error_code ec;
+        if (runCancellable(command, keepGoing) == 0 && std::filesystem::file_size(output, ec) > 0 && !ec) {
+            return true;
+        }
+        if (keepGoing && !keepGoing()) return false;
+    }
+    return false;
+}
+
+FILE* startVideoEncoder(const std::string& output, int width, int height, double fps,
+                        const std::vector<std::string>& codecArgs) {
+#ifdef _WIN32
+    if (output.find('"') != std::string::npos) return nullptr;
+    const char* nullDevice = "NUL";
+    const char* mode = "wb";
+#else
+    const char* nullDevice = "/dev/null";
+    const char* mode = "w";
+#endif
+    std::string ffmpeg = toolCommand("ffmpeg");
+    if (ffmpeg.empty()) return nullptr;
+
+    char rate[32];
+    std::snprintf(rate, sizeof(rate), "%.6g", fps);
+    std::string command = ffmpeg + " -y -v error -f rawvideo -pix_fmt bgr24 -s " + std::to_string(width) + "x" +
+                          std::to_string(height) + " -r " + rate + " -i -";
+    for (const auto& arg : codecArgs) command += " " + shellQuote(arg);
+    command += " " + shellQuote(output) + " >" + nullDevice + " 2>" + nullDevice;
+    return popen(command.c_str(), mode);
+}
+
+bool finishVideoEncoder(FILE* encoder, const std::string& output) {
+    if (!encoder) return false;
+    int status = pclose(encoder);
+    std::error_code ec;
+    return status == 0 && std::filesystem::file_size(output, ec) > 0 && !ec;
+}
+
+std::vector<std::string> listFfmpegEncoders() {
+    std::vector<std::string> encoders;
+    std::string command = toolCommand("ffmpeg");
+    if (command.empty()) return encoders;
+#ifdef _WIN32
+    command += " -hide_banner -encoders 2>NUL";
+#else
+    command += " -hide_banner -encoders 2>/dev/null";
+#endif
+    FILE* pipe = popen(command.c_str(), "r");
+    if (!pipe) return encoders;
+
+    // Lines after the legend look like " V....D libx264    libx264 H.264 ..."
+    char line[512];
+    bool listing = false;
+    while (fgets(line, sizeof(line), pipe)) {
+        std::string text = line;
+        if (text.find("------") != std::string::npos) {
+            listing = true;
+            continue;
+        }
+        size_t flags = text.find_first_not_of(' ');
+        if (!listing || flags == std::string::npos || text[flags] != 'V') continue;
+        size_t name = text.find_first_not_of(' ', text.find(' ', flags));
+        if (name == std::string::npos) continue;
+        encoders.push_back(text.substr(name, text.find_first_of(" \r\n", name) - name));
+    }
+    pclose(pipe);
+    return encoders;
+}
+
+bool testFfmpegEncoder(const std::string& encoder) {
+    // Encoder names are plain identifiers; anything else never reaches the shell
+    if (encoder.empty() || encoder.find_first_not_of("abcdefghijklmnopqrstuvwxyz0123456789_") != std::string::npos) {
+        return false;
+    }
+#ifdef _WIN32
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+    std::string ffmpeg = toolCommand("ffmpeg");
+    if (ffmpeg.empty()) return false;
+    std::string command = ffmpeg + " -v error -f lavfi -i color=c=black:s=256x256:r=25:d=0.2 -pix_fmt nv12 -c:v " +
+                          encoder + " -f null - >" + nullDevice + " 2>" + nullDevice;
+    return std::system(command.c_str()) == 0;
+}
+
+std::vector<std::map<std::string, std::string>> probeStreams(const std::string& path) {
+    std::vector<std::map<std::string, std::string>> streams;
+#ifdef _WIN32
+    if (path.find('"') != std::string::npos) return streams;
+    const char* nullDevice = "NUL";
+#else
+    const char* nullDevice = "/dev/null";
+#endif
+    std::string ffprobe = toolCommand("ffprobe");
+    if (ffprobe.empty()) return streams;
+
+    std::string command = ffprobe + " -v error -show_entries stream=codec_type,codec_name,channels,sample_rate " +
+                          shellQuote(path) + " 2>" + nullDevice;
+    FILE* pipe = popen(command.c_str(), "r");
+    if (!pipe) return streams;
+
+    // Each stream is a [STREAM] ... [/STREAM] block of key=value lines
+    char line[512];
+    while (fgets(line, sizeof(line), pipe)) {
+        std::string text = line;
+        text.erase(text.find_last_not_of("\r\n") + 1);
+        if (text == "[STREAM]") {
+            streams.emplace_back();
+            continue;
+        }
+        size_t equals = text.find('=');
+        if (streams.empty() || equals == std::string::npos) continue;
+        streams.back()[text.substr(0, equals)] = text.substr(equals + 1);
+    }
+    pclose(pipe);
+    return streams;
+}
+
+bool readClipboardImage(std::vector<unsigned char>& data) {
+    data.clear();
+
+#if defined(_WIN32) || defined(__APPLE__)
+    // Have the system save the clipboard image as PNG to a temp file
+    std::string tempPath = createTempFile(".png");
+    if (tempPath.empty()) return false;
+#ifdef _WIN32
+    // Inside a PowerShell single-quoted string, a quote is written twice
+    std::string quotedPath;
+    for (char c : tempPath) quotedPath += (c == '\'') ? std::string("''") : std::string(1, c);
+    std::string command = "powershell -NoProfile -STA -Command \"Add-Type -AssemblyName System.Windows.Forms; "
+        "$image = [System.Windows.Forms.Clipboard]::GetImage(); "
+        "if ($image) { $image.Save('" + quotedPath + "', [System.Drawing.Imaging.ImageFormat]::Png) } "
+        "else { exit 1 }\" >NUL 2>NUL";
+#else
+    std::string command = "osascript -e 'set out to open for access POSIX file \"" + tempPath +
+        "\" with write permission' -e 'write (the clipboard as «class PNGf») to out' "
+        "-e 'close access out' >/dev/null 2>&1";
+#endif
+    int status = std::system(command.c_str());
+    std::ifstream in(tempPath, std::ios::binary);
+    if (status == 0 && in) {
+        data.assign(std::istreambuf_iterator<char>(in), std::istreambuf_iterator<char>());
+    }
+    in.close();
+    std::error_code ec;
+    std::filesystem::remove(tempPath, ec);
+#else
+    // Wayland or X11 clipboard, read as PNG
+    const char* command = std::getenv("WAYLAND_DISPLAY")
+        ? "wl-paste --no-newline --type image/png 2>/dev/null"
+        : "xclip -selection clipboard -target image/png -out 2>/dev/null";
+    FILE* pipe = popen(command, "r");
+    if (!pipe) return false;
+
+    std::vector<unsigned char> chunk(64 * 1024);
+    size_t count;
+    while ((count = fread(chunk.data(), 1, chunk.size(), pipe)) > 0) {
+        data.insert(data.end(), chunk.begin(), chunk.begin() + count);
+    }
+    if (pclose(pipe) != 0) data.clear();
+#endif
+
+    return !data.empty();
+}
+
+bool copyImageToClipboard(const std::string& pngFile) {
+    if (!std::filesystem::is_regular_file(pngFile)) return false;
+
+#ifdef _WIN32
+    // The bitmap SetImage adds has no alpha, so the PNG itself goes along as
+    // the "PNG" format, which browsers, Office and image editors paste with
+    // its transparency. Copying the data object keeps it after PowerShell exits
+    if (pngFile.find('\'') != std::string::npos) return false;
+    std::string command = "powershell -NoProfile -STA -Command \"Add-Type -AssemblyName System.Windows.Forms; "
+        "$image = [System.Drawing.Image]::FromFile('" + pngFile + "'); "
+        "$png = New-Object System.IO.MemoryStream(,[System.IO.File]::ReadAllBytes('" + pngFile + "')); "
+        "$data = New-Object System.Windows.Forms.DataObject; $data.SetImage($image); $data.SetData('PNG', $png); "
+        "[System.Windows.Forms.Clipboard]::SetDataObject($data, $true); $image.Dispose()\" >NUL 2>NUL";
+#elif defined(__APPLE__)
+    if (pngFile.find_first_of("'\"") != std::string::npos) return false;
+    std::string command = "osascript -e 'set the clipboard to (read (POSIX file \"" + pngFile +
+        "\") as «class PNGf»)' >/dev/null 2>&1";
+#else
+    // Both keep running in the background to serve the clipboard
+    std::string command = std::string(std::getenv("WAYLAND_DISPLAY")
+        ? "wl-copy --type image/png < "
+        : "xclip -selection clipboard -target image/png -in < ") + shellQuote(pngFile) + " >/dev/null 2>&1";
+#endif
+    return std::system(command.c_str()) == 0;
+}
+
+bool captureScreen(std::vector<unsigned char>& data) {
+    data.clear();
+
+#if defined(_WIN32) || defined(__APPLE__)
+    std::string tempPath = createTempFile(".png");
+    if (tempPath.empty()) return false;
+#ifdef _WIN32
+    // Inside a PowerShell single-quoted string, a quote is written twice
+    std::string quotedPath;
+    for (char c : tempPath) quotedPath += (c == '\'') ? std::string("''") : std::string(1, c);
+    std::string command = "powershell -NoProfile -Command \"Add-Type -AssemblyName System.Windows.Forms; "
+        "$area = [System.Windows.Forms.SystemInformation]::VirtualScreen; "
+        "$image = New-Object System.Drawing.Bitmap $area.Width, $area.Height; "
+        "[System.Drawing.Graphics]::FromImage($image).CopyFromScreen($area.Left, $area.Top, 0, 0, $image.Size); "
+        "$image.Save('" + quotedPath + "', [System.Drawing.Imaging.ImageFormat]::Png)\" >NUL 2>NUL";
+#else
+    std::string command = "screencapture -x -t png " + shellQuote(tempPath) + " >/dev/null 2>&1";
+#endif
+    int status = std::system(command.c_str());
+    std::ifstream in(tempPath, std::ios::binary);
+    if (status == 0 && in) {
+        data.assign(std::istreambuf_iterator<char>(in), std::istreambuf_iterator<char>());
+    }
+    in.close();
+    std::error_code ec;
+    std::filesystem::remove(tempPath, ec);
+#else
+    // PNG on standard output
+    const char* command = std::getenv("WAYLAND_DISPLAY")
+        ? "grim - 2>/dev/null"
+        : "maim 2>/dev/null || import -silent -window root png:- 2>/dev/null";
+    FILE* pipe = popen(command, "r");
+    if (!pipe) return false;
+
+    std::vector<unsigned char> chunk(256 * 1024);
+    size_t count;
+    while ((count = fread(chunk.data(), 1, chunk.size(), pipe)) > 0) {
+        data.insert(data.end(), chunk.begin(), chunk.begin() + count);
+    }
+    if (pclose(pipe) != 0) data.clear();
+#endif
+
+    return !data.empty();
+}
+
+bool getScreenSize(int& width, int& height) {
+    width = height = 0;
+
+#ifdef _WIN32
+    width = GetSystemMetrics(SM_CXSCREEN);
+    height = GetSystemMetrics(SM_CYSCREEN);
+#else
+    // "Resolution: 2560 x 1600 Retina" from system_profiler, or from xrandr the
+    // primary output ("HDMI-1 connected primary 2560x1440+0+0 ..."), else the
+    // current mode of the first output ("1920x1080 60.00*+")
+#ifdef __APPLE__
+    FILE* pipe = popen("system_profiler SPDisplaysDataType 2>/dev/null", "r");
+#else
+    FILE* pipe = popen("xrandr --current 2>/dev/null", "r");
+#endif
+    if (!pipe) return false;
+    char line[512];
+#ifndef __APPLE__
+    int firstWidth = 0, firstHeight = 0;
+#endif
+    while (fgets(line, sizeof(line), pipe)) {
+#ifdef __APPLE__
+        const char* value = std::strstr(line, "Resolution:");
+        if (value && std::sscanf(value, "Resolution: %d x %d", &width, &height) == 2) break;
+#else
+        const char* primary = std::strstr(line, " connected primary ");
+        if (primary && std::sscanf(primary, " connected primary %dx%d", &width, &height) == 2) break;
+        if (firstWidth == 0 && std::strchr(line, '*')) std::sscanf(line, " %dx%d", &firstWidth, &firstHeight);
+#endif
+        width = height = 0;
+    }
+    pclose(pipe);
+#ifndef __APPLE__
+    if (width <= 0 || height <= 0) {
+        width = firstWidth;
+        height = firstHeight;
+    }
+#endif
+#endif
+
+    return width > 0 && height > 0;
+}
+
+#if !defined(_WIN32) && !defined(__APPLE__)
+// file:// URI of an absolute path, with spaces and other reserved bytes
+// percent-encoded as desktops expect
+static std::string fileUri(const std::string& path) {
+    std::string uri = "file://";
+    for (unsigned char c : path) {
+        if (std::isalnum(c) || c == '/' || c == '-' || c == '_' || c == '.' || c == '~') {
+            uri += static_cast<char>(c);
+        } else {
+            char escape[4];
+            std::snprintf(escape, sizeof(escape), "%%%02X", c);
+            uri += escape;
+        }
+    }
+    return uri;
+}
+#endif
+
+bool setDesktopWallpaper(const std::string& imageFile) {
+    if (!std::filesystem::is_regular_file(imageFile)) return false;
+
+#ifdef _WIN32
+    // The wide call, so profile folders with non-ASCII names work
+    std::wstring path = std::filesystem::path(imageFile).wstring();
+    return SystemParametersInfoW(SPI_SETDESKWALLPAPER, 0, const_cast<wchar_t*>(path.c_str()),
+                                 SPIF_UPDATEINIFILE | SPIF_SENDCHANGE) != 0;
+#else
+#ifdef __APPLE__
+    if (imageFile.find_first_of("'\"") != std::string::npos) return false;
+    std::string command = "osascript -e 'tell application \"System Events\" to tell every desktop to set picture to \"" +
+        imageFile + "\"' >/dev/null 2>&1";
+#else
+    const char* current = std::getenv("XDG_CURRENT_DESKTOP");
+    std::string desktop = current ? current : "";
+    std::transform(desktop.begin(), desktop.end(), desktop.begin(), ::tolower);
+    std::string file = shellQuote(imageFile);
+    std::string uri = shellQuote(fileUri(imageFile));
+
+    std::string command;
+    if (desktop.find("kde") != std::string::npos) {
+        command = "plasma-apply-wallpaperimage " + file;
+    } else if (desktop.find("xfce") != std::string::npos) {
+        // One last-image property per monitor and workspace
+        command = "xfconf-query -c xfce4-desktop -l | grep last-image | "
+                  "while read -r property; do xfconf-query -c xfce4-desktop -p \"$property\" -s " + file + "; done";
+    } else if (desktop.find("cinnamon") != std::string::npos) {
+        command = "gsettings set org.cinnamon.desktop.background picture-uri " + uri;
+    } else if (desktop.find("mate") != std::string::npos) {
+        command = "gsettings set org.mate.background picture-filename " + file;
+    } else if (!desktop.empty() && haveCommand("gsettings")) {
+        // GNOME 42 and later show picture-uri-dark in dark mode; older ones don't have it
+        command = "gsettings set org.gnome.desktop.background picture-uri " + uri +
+                  " && { gsettings set org.gnome.desktop.background picture-uri-dark " + uri + " || true; }";
+    } else if (haveCommand("feh")) {
+        command = "feh --no-fehbg --bg-fill " + file;
+    } else {
+        return false;
+    }
+    command = "(" + command + ") >/dev/null 2>&1";
+#endif
+    return std::system(command.c_str()) == 0;
+#endif
+}
+
+// Build a failed check with the offending path in the message
+static PathCheck pathError(PathErrorCode code, const std::string& path, const std::string& reason) {
+    PathCheck check;
+    check.code = code;
+    check.path = path;
+    check.message = reason + ": " + path;
+    return check;
+}
+
+static bool hasAllowedExtension(const std::filesystem::path& path, const std::vector<std::string>& extensions) {
+    if (extensions.empty()) return true;
+
+    std::string ext = path.extension().string();
+    if (!ext.empty() && ext[0] == '.') ext.erase(0, 1);
+    std::transform(ext.begin(), ext.end(), ext.begin(), ::tolower);
+    return std::find(extensions.begin(), extensions.end(), ext) != extensions.end();
+}
+
+static std::string joinExtensions(const std::vector<std::string>& extensions) {
+    std::string joined;
+    for (const auto& ext : extensions) {
+        joined += (joined.empty() ? "" : ", ") + ext;
+    }
+    return joined;
+}
+
+// Paths that can't be converted on this platform (e.g. unrepresentable
+// characters on Windows) throw from std::filesystem, so every check runs
+// inside a try block and reports them as UNREADABLE instead
+PathCheck validateInputFile(const std::string& path, const std::vector<std::string>& extensions) {
+    if (path.empty()) return pathError(PathErrorCode::EMPTY, path, "No file given");
+
+    try {
+        std::error_code ec;
+        std::filesystem::path fsPath(path);
+        if (!std::filesystem::exists(fsPath, ec)) {
+            return pathError(PathErrorCode::NOT_FOUND, path, "File not found");
+        }
+        if (std::filesystem::is_directory(fsPath, ec)) {
+            return pathError(PathErrorCode::IS_DIRECTORY, path, "Expected a file but got a directory");
+        }
+        if (!hasAllowedExtension(fsPath, extensions)) {
+            return pathError(PathErrorCode::BAD_EXTENSION, path,
+                             "Unsupported file type (expected " + joinExtensions(extensions) + ")");
+        }
+
+        std::filesystem::path canonical = std::filesystem::canonical(fsPath, ec);
+        if (ec) return pathError(PathErrorCode::UNREADABLE, path, "Could not resolve path");
+
+        PathCheck check;
+        check.path = canonical.string();
+        return check;
+    } catch (const std::exception&) {
+        return pathError(PathErrorCode::UNREADABLE, path, "Path can't be used on this system");
+    }
+}
+
+PathCheck validateOutputFile(const std::string& path, const std::vector<std::string>& extensions) {
+    if (path.empty()) return pathError(PathErrorCode::EMPTY, path, "No output file given");
+
+    try {
+        std::error_code ec;
+        std::filesystem::path fsPath = std::filesystem::absolute(path, ec);
+        if (ec) return pathError(PathErrorCode::UNREADABLE, path, "Could not resolve path");
+
+        if (std::filesystem::is_directory(fsPath, ec)) {
+            return pathError(PathErrorCode::IS_DIRECTORY, path, "Expected a file but got a directory");
+        }
+        if (!hasAllowedExtension(fsPath, extensions)) {
+            return pathError(PathErrorCode::BAD_EXTENSION, path,
+                             "Unsupported output type (expected " + joinExtensions(extensions) + ")");
+        }
+
+        std::filesystem::path parent = fsPath.parent_path();
+        if (!std::filesystem::is_directory(parent, ec)) {
+            return pathError(PathErrorCode::NO_PARENT_DIRECTORY, path, "Output directory does not exist");
+        }
+
+        PathCheck check;
+        check.path = (std::filesystem::canonical(parent, ec) / fsPath.filename()).string();
+        if (ec) check.path = fsPath.string();
+        return check;
+    } catch (const std::exception&) {
+        return pathError(PathErrorCode::UNREADABLE, path, "Path can't be used on this system");
+    }
+}
+
+PathCheck validateDirectory(const std::string& path, bool create) {
+    if (path.empty()) return pathError(PathErrorCode::EMPTY, path, "No directory given");
+
+    try {
+        std::error_code ec;
+        std::filesystem::path fsPath(path);
+        if (!std::filesystem::exists(fsPath, ec)) {
+            if (!create || !std::filesystem::create_directories(fsPath, ec) || ec) {
+                return pathError(PathErrorCode::NOT_FOUND, path, "Directory not found");
+            }
+        }
+        if (!std::filesystem::is_directory(fsPath, ec)) {
+            return pathError(PathErrorCode::NOT_DIRECTORY, path, "Expected a directory but got a file");
+        }
+
+        std::filesystem::path canonical = std::filesystem::canonical(fsPath, ec);
+        if (ec) return pathError(PathErrorCode::UNREADABLE, path, "Could not resolve path");
+
+        PathCheck check;
+        check.path = canonical.string();
+        return check;
+    } catch (const std::exception&) {
+        return pathError(PathErrorCode::UNREADABLE, path, "Path can't be used on this system");
+    }
+}
+
+std::string getAppDataDir() {
+    std::filesystem::path dir;
+
+#ifdef _WIN32
+    if (const char* appData = std::getenv("APPDATA")) {
+        dir = std::filesystem::path(appData) / "DithersBoyfriend";
+    }
+#elif defined(__APPLE__)
+    if (const char* home = std::getenv("HOME")) {
+        dir = std::filesystem::path(home) / "Library" / "Application Support" / "DithersBoyfriend";
+    }
+#else
+    if (const char* dataHome = std::getenv("XDG_DATA_HOME"); dataHome && *dataHome) {
+        dir = std::filesystem::path(dataHome) / "dithers-boyfriend";
+    } else if (const char* home = std::getenv("HOME")) {
+        dir = std::filesystem::path(home) / ".local" / "share" / "dithers-boyfriend";
+    }
+#endif
+
+    if (dir.empty()) return "";
+
+    std::error_code ec;
+    std::filesystem::create_directories(dir, ec);
+    return ec ? "" : dir.string();
+}
+
+void ignoreBrokenPipes() {
+#ifndef _WIN32
+    std::signal(SIGPIPE, SIG_IGN);
+#endif
+}
+
+bool lowerThreadPriority(int niceness) {
+    niceness = std::clamp(niceness, 0, 19);
+
+#ifdef _WIN32
+    int priority = THREAD_PRIORITY_NORMAL;
+    if (niceness >= 15) {
+        priority = THREAD_PRIORITY_IDLE;
+    } else if (niceness >= 10) {
+        priority = THREAD_PRIORITY_LOWEST;
+    } else if (niceness > 0) {
+        priority = THREAD_PRIORITY_BELOW_NORMAL;
+    }
+    return SetThreadPriority(GetCurrentThread(), priority) != 0;
+#elif defined(__linux__)
+    // Linux applies niceness per thread
+    return setpriority(PRIO_PROCESS, static_cast<id_t>(syscall(SYS_gettid)), niceness) == 0;
+#else
+    // Other Unix systems only support it for the whole process
+    return setpriority(PRIO_PROCESS, 0, niceness) == 0;
+#endif
+}
+
 } // namespace Platform
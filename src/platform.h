@@ -1,6 +1,11 @@
 #pragma once
 
+#include <cstddef>
+#include <cstdio>
+#include <functional>
+#include <map>
 #include <string>
+#include <vector>
 
 // Platform-specific includes
 #ifdef _WIN32
@@ -12,9 +17,170 @@
 #endif
 
 namespace Platform {
+    // Why a user-supplied path was rejected
+    enum class PathErrorCode {
+        NONE,
+        EMPTY,
+        NOT_FOUND,
+        IS_DIRECTORY,           // A file was expected
+        NOT_DIRECTORY,          // A directory was expected
+        BAD_EXTENSION,
+        NO_PARENT_DIRECTORY,    // Output would go into a directory that doesn't exist
+        UNREADABLE              // Can't be represented or resolved on this system
+    };
+
+    // Outcome of validating a path; on success, path holds the canonical form
+    struct PathCheck {
+        PathErrorCode code = PathErrorCode::NONE;
+        std::string path;
+        std::string message;
+
+        bool ok() const { return code == PathErrorCode::NONE; }
+    };
+
+    // Validate an existing file to read. extensions lists the allowed lowercase
+    // extensions without the dot (empty = any)
+    PathCheck validateInputFile(const std::string& path, const std::vector<std::string>& extensions = {});
+
+    // Validate a file to write: its directory must exist and it must not be a directory
+    PathCheck validateOutputFile(const std::string& path, const std::vector<std::string>& extensions = {});
+
+    // Validate a directory, optionally creating it if missing
+    PathCheck validateDirectory(const std::string& path, bool create = false);
+
     // Open file dialog - returns selected filepath or empty string
     std::string openFileDialog();
 
     // Save file dialog - returns selected filepath or empty string
     std::string saveFileDialog();
+
+    // Check whether a path is an http(s) URL rather than a local file
+    bool isRemoteUrl(const std::string& path);
+
+    // Create an empty file with a unique name in the temp directory, ending in
+    // suffix (e.g. ".png"), that no other process can have opened first.
+    // Returns its path (caller removes it) or empty string on failure
+    std::string createTempFile(const std::string& suffix);
+
+    // Download progress callback: (bytes received, total bytes or 0 if unknown)
+    using DownloadProgress = std::function<void(size_t, size_t)>;
+
+    // Download a URL to a temporary file with curl, aborting once it exceeds maxBytes.
+    // Returns the temp file path (caller removes it) or empty string on failure
+    std::string downloadToTempFile(const std::string& url, size_t maxBytes,
+                                   DownloadProgress progress = nullptr);
+
+    // Decode an image OpenCV can't read with a command line decoder. format is
+    // the input's extension (heic, heif, avif, jxl), written to output as PNG
+    // by heif-dec or heif-convert, avifdec or djxl, sips on macOS or ImageMagick;
+    // "raw" for camera RAW files, demosaiced to a 16-bit TIFF by dcraw_emu
+    // (LibRaw), dcraw, sips or ImageMagick; or "svg", rasterized to a PNG width
+    // pixels wide (0 = its own size) by resvg, rsvg-convert, Inkscape or
+    // ImageMagick. False if none is installed or all failed
+    bool convertImage(const std::string& input, const std::string& output, const std::string& format,
+                      int width = 0);
+
+    // Whether convertImage has a decoder for this format
+    bool hasImageConverter(const std::string& format);
+
+    // Size a camera RAW file decodes to, read from its header by LibRaw's
+    // raw-identify, dcraw or ImageMagick without demosaicing it. False if
+    // none is installed or none could read it
+    bool identifyRawImage(const std::string& input, int& width, int& height);
+
+    // Render text to a PNG with ImageMagick: size is in pixels, colors are hex
+    // without the #, and font a font name or file ("" = ImageMagick's default).
+    // Line breaks start new lines. False if ImageMagick isn't installed or failed
+    bool renderText(const std::string& text, const std::string& font, int size, const std::string& color,
+                    const std::string& background, int padding, const std::string& output);
+
+    // Add the audio of source to a video OpenCV wrote without sound, saving
+    // the result as output, with ffmpeg. The audio is copied as is when the
+    // container takes its codec and re-encoded otherwise (Opus for WebM, AAC
+    // for the rest). A source without audio gives a plain copy of the video.
+    // The audio is taken from startSeconds on, for videos of a trimmed clip.
+    // keepGoing is polled while ffmpeg runs; returning false stops it. False
+    // if ffmpeg isn't installed, failed or was stopped
+    bool muxAudio(const std::string& video, const std::string& source, const std::string& output,
+                  double startSeconds = 0.0, const std::function<bool()>& keepGoing = nullptr);
+
+    // Start ffmpeg encoding raw 8-bit BGR frames, written whole to the returned
+    // pipe, into a video. codecArgs are its output options (codec, quality,
+    // pixel format); the container follows output's extension. Null if ffmpeg
+    // isn't installed. A failing ffmpeg shows up as a short write, given
+    // ignoreBrokenPipes
+    FILE* startVideoEncoder(const std::string& output, int width, int height, double fps,
+                            const std::vector<std::string>& codecArgs);
+
+    // Close the pipe and wait for ffmpeg; true if it wrote the video
+    bool finishVideoEncoder(FILE* encoder, const std::string& output);
+
+    // Names of the video encoders ffmpeg was built with (empty without ffmpeg)
+    std::vector<std::string> listFfmpegEncoders();
+
+    // Whether an ffmpeg encoder works, tried on a few blank frames
+    bool testFfmpegEncoder(const std::string& encoder);
+
+    // Streams of a media file as ffprobe reports them, one map per stream with
+    // codec_type, codec_name and, for audio, channels and sample_rate. Empty if
+    // ffprobe isn't installed or can't read the file
+    std::vector<std::map<std::string, std::string>> probeStreams(const std::string& path);
+
+    // Where ffmpeg or ffprobe is run from
+    struct ToolInfo {
+        std::string name;           // ffmpeg or ffprobe
+        std::string path;           // Full path of the program (empty = not found)
+        std::string source;         // "configured", "bundled" or "PATH"
+        std::string version;        // e.g. 6.1.1, filled in by checkFfmpegTool
+    };
+
+    // Find ffmpeg or ffprobe: in the folder set with setFfmpegPath, then
+    // bundled next to this program, then on the PATH. Every function here
+    // that runs them uses this
+    ToolInfo findFfmpegTool(const std::string& name);
+
+    // Same, also running it for its version ("" if it wouldn't run)
+    ToolInfo checkFfmpegTool(const std::string& name);
+
+    // Use the ffmpeg and ffprobe in a folder (or next to an ffmpeg binary)
+    // from now on, saved in the app data directory; empty goes back to the
+    // bundled copy or PATH. False if it couldn't be saved
+    bool setFfmpegPath(const std::string& path);
+
+    // The path set with setFfmpegPath ("" if none)
+    std::string getFfmpegPath();
+
+    // Image on the system clipboard, as PNG bytes. Uses wl-paste or xclip on
+    // Linux, osascript on macOS and PowerShell on Windows; false if there's none
+    bool readClipboardImage(std::vector<unsigned char>& data);
+
+    // Put a PNG file's image on the system clipboard (same tools as above)
+    bool copyImageToClipboard(const std::string& pngFile);
+
+    // Screenshot of the whole desktop (every monitor), as PNG bytes. Uses grim
+    // on Wayland, maim or ImageMagick's import on X11, screencapture on macOS
+    // and PowerShell on Windows; false if none worked. macOS asks for the
+    // screen recording permission the first time
+    bool captureScreen(std::vector<unsigned char>& data);
+
+    // Resolution of the primary display in pixels. Uses GetSystemMetrics on
+    // Windows, system_profiler on macOS and xrandr on Linux; false if unknown
+    bool getScreenSize(int& width, int& height);
+
+    // Make an image file the desktop wallpaper. The file must stay where it is.
+    // Uses SystemParametersInfo on Windows and System Events on macOS; on Linux
+    // plasma-apply-wallpaperimage (KDE), xfconf-query (Xfce), gsettings (GNOME,
+    // Cinnamon, MATE) or feh for other X11 desktops. False if none worked
+    bool setDesktopWallpaper(const std::string& imageFile);
+
+    // Per-user application data directory (created if missing, empty string on failure)
+    std::string getAppDataDir();
+
+    // Make writing to a pipe whose reader quit (a failing ffmpeg) a failed
+    // write instead of the end of the program. Process-wide, so main calls it once
+    void ignoreBrokenPipes();
+
+    // Lower the calling thread's scheduling priority (niceness 0-19, as for nice(1)).
+    // It can't be raised again without privileges, so call this from job threads
+    bool lowerThreadPriority(int niceness);
 }
@@ -0,0 +1,210 @@
+#include "plotter.h"
+#include "imageio.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdio>
+#include <fstream>
+#include <limits>
+
+namespace Plotter {
+
+// Algorithms that hold each threshold over ditherScale x ditherScale pixels
+static bool usesPatternScale(Dithering::Algorithm algorithm) {
+    switch (algorithm) {
+        case Dithering::Algorithm::ORDERED_BAYER_2X2:
+        case Dithering::Algorithm::ORDERED_BAYER_4X4:
+        case Dithering::Algorithm::ORDERED_BAYER_8X8:
+        case Dithering::Algorithm::ORDERED_BAYER_16X16:
+        case Dithering::Algorithm::PATTERN_DITHER:
+        case Dithering::Algorithm::ORDERED_MATRIX:
+            return true;
+        default:
+            return false;
+    }
+}
+
+// Greedy nearest-neighbour tour from the origin. Dots are bucketed on a grid
+// and each search widens ring by ring until no closer dot can be left
+static std::vector<cv::Point2d> orderNearest(const std::vector<cv::Point2d>& dots, const cv::Size2d& size,
+                                             double cell) {
+    const int columns = std::max(1, static_cast<int>(std::ceil(size.width / cell)) + 1);
+    const int rows = std::max(1, static_cast<int>(std::ceil(size.height / cell)) + 1);
+    auto bucketOf = [&](const cv::Point2d& point) {
+        return cv::Point(std::clamp(static_cast<int>(point.x / cell), 0, columns - 1),
+                         std::clamp(static_cast<int>(point.y / cell), 0, rows - 1));
+    };
+    std::vector<std::vector<int>> buckets(static_cast<size_t>(columns) * rows);
+    for (size_t i = 0; i < dots.size(); ++i) {
+        cv::Point bucket = bucketOf(dots[i]);
+        buckets[bucket.y * columns + bucket.x].push_back(static_cast<int>(i));
+    }
+
+    std::vector<cv::Point2d> ordered;
+    ordered.reserve(dots.size());
+    cv::Point2d pen(0.0, 0.0);
+    while (ordered.size() < dots.size()) {
+        cv::Point center = bucketOf(pen);
+        int bestBucket = -1;
+        size_t bestSlot = 0;
+        double bestDistance = std::numeric_limits<double>::max();
+        for (int ring = 0; ring <= std::max(columns, rows); ++ring) {
+            if (bestBucket >= 0 && (ring - 1) * cell >= std::sqrt(bestDistance)) break;
+            for (int by = center.y - ring; by <= center.y + ring; ++by) {
+                if (by < 0 || by >= rows) continue;
+                // Only the ring's edge: every column on its top and bottom rows, the ends otherwise
+                bool edgeRow = by == center.y - ring || by == center.y + ring;
+                int step = edgeRow ? 1 : std::max(1, 2 * ring);
+                for (int bx = center.x - ring; bx <= center.x + ring; bx += step) {
+                    if (bx < 0 || bx >= columns) continue;
+                    const std::vector<int>& bucket = buckets[by * columns + bx];
+                    for (size_t slot = 0; slot < bucket.size(); ++slot) {
+                        cv::Point2d delta = dots[bucket[slot]] - pen;
+                        double distance = delta.dot(delta);
+                        if (distance < bestDistance) {
+                            bestDistance = distance;
+                            bestBucket = by * columns + bx;
+                            bestSlot = slot;
+                        }
+                    }
+                }
+            }
+        }
+
+        std::vector<int>& bucket = buckets[bestBucket];
+        pen = dots[bucket[bestSlot]];
+        ordered.push_back(pen);
+        bucket[bestSlot] = bucket.back();
+        bucket.pop_back();
+    }
+    return ordered;
+}
+
+Plot traceDots(const cv::Mat& result, const Dithering::Parameters& inputParams, const Options& options) {
+    Dithering::Parameters params = inputParams;
+    Dithering::applyScreenRuling(params);
+    Plot plot;
+    if (result.empty()) return plot;
+
+    const int pixelate = std::max(1, params.pixelate);
+    // Rotated matrices don't hold their decisions over whole pixel blocks
+    const bool rotated = std::fmod(params.ditherAngle, 360.0f) != 0.0f;
+    const int scale = usesPatternScale(params.algorithm) && !rotated
+        ? std::max(1, static_cast<int>(std::lround(params.ditherScale))) : 1;
+    const int block = pixelate * scale;
+    const double mmPerPixel = options.widthMm / result.cols;
+    plot.sizeMm = cv::Size2d(options.widthMm, result.rows * mmPerPixel);
+    plot.pitchMm = block * mmPerPixel;
+
+    // Pattern blocks start where the phase puts them; a partial block comes first
+    auto firstBlock = [&](int phase) {
+        int offset = ((-phase % scale) + scale) % scale * pixelate;
+        return offset > 0 ? offset - block : 0;
+    };
+    const int startX = firstBlock(params.phaseX), startY = firstBlock(params.phaseY);
+
+    cv::Mat gray;
+    cv::cvtColor(result, gray, cv::COLOR_BGR2GRAY);
+
+    // Stipple dots are drawn several pixels wide; one pen dot at each one's middle
+    if (params.algorithm == Dithering::Algorithm::STIPPLE) {
+        cv::Mat ink = gray < 128, labels, stats, centroids;
+        int count = cv::connectedComponentsWithStats(ink, labels, stats, centroids, 8, CV_32S);
+        for (int i = 1; i < count; ++i) {
+            plot.dots.push_back(cv::Point2d((centroids.at<double>(i, 0) + 0.5) * mmPerPixel,
+                                            (centroids.at<double>(i, 1) + 0.5) * mmPerPixel));
+        }
+        plot.pitchMm = std::sqrt(static_cast<double>(result.total()) / std::max(count, 2)) * mmPerPixel;
+        if (options.shortenTravel && !plot.dots.empty()) {
+            plot.dots = orderNearest(plot.dots, plot.sizeMm, plot.pitchMm * 8.0);
+        }
+        return plot;
+    }
+
+    bool reverse = false;
+    for (int y = startY; y < result.rows; y += block) {
+        std::vector<cv::Point2d> row;
+        for (int x = startX; x < result.cols; x += block) {
+            // Middle of the part of the block inside the image
+            int left = std::max(x, 0), top = std::max(y, 0);
+            int right = std::min(x + block, result.cols), bottom = std::min(y + block, result.rows);
+            int sampleX = (left + right - 1) / 2, sampleY = (top + bottom - 1) / 2;
+            if (gray.at<uchar>(sampleY, sampleX) >= 128) continue;
+            row.push_back(cv::Point2d((left + right) / 2.0 * mmPerPixel, (top + bottom) / 2.0 * mmPerPixel));
+        }
+        // Without reordering, rows alternate direction
+        if (reverse) std::reverse(row.begin(), row.end());
+        reverse = !reverse;
+        plot.dots.insert(plot.dots.end(), row.begin(), row.end());
+    }
+
+    if (options.shortenTravel && !plot.dots.empty()) {
+        plot.dots = orderNearest(plot.dots, plot.sizeMm, plot.pitchMm * 8.0);
+    }
+    return plot;
+}
+
+double travelDistance(const Plot& plot) {
+    double distance = 0.0;
+    cv::Point2d pen(0.0, 0.0);
+    for (const auto& dot : plot.dots) {
+        distance += cv::norm(dot - pen);
+        pen = dot;
+    }
+    return distance;
+}
+
+const std::vector<std::string>& getPlotterExtensions() {
+    static const std::vector<std::string> extensions = {"csv", "json", "gcode", "nc", "hpgl", "plt"};
+    return extensions;
+}
+
+bool exportPlot(const Plot& plot, const std::string& path, const Options& options) {
+    std::string ext = ImageIO::getExtension(path);
+    if (std::find(getPlotterExtensions().begin(), getPlotterExtensions().end(), ext) ==
+        getPlotterExtensions().end()) {
+        return false;
+    }
+
+    std::ofstream file(path, std::ios::binary);
+    if (!file) return false;
+
+    char line[160];
+    if (ext == "csv") {
+        file << "x_mm,y_mm\n";
+        for (const auto& dot : plot.dots) {
+            std::snprintf(line, sizeof(line), "%.3f,%.3f\n", dot.x, dot.y);
+            file << line;
+        }
+    } else if (ext == "json") {
+        std::snprintf(line, sizeof(line), "{\"units\": \"mm\", \"width\": %.3f, \"height\": %.3f, \"pitch\": %.4f, ",
+                      plot.sizeMm.width, plot.sizeMm.height, plot.pitchMm);
+        file << line << "\"dots\": [";
+        for (size_t i = 0; i < plot.dots.size(); ++i) {
+            std::snprintf(line, sizeof(line), "%s[%.3f, %.3f]", i > 0 ? ", " : "", plot.dots[i].x, plot.dots[i].y);
+            file << line;
+        }
+        file << "]}\n";
+    } else if (ext == "gcode" || ext == "nc") {
+        std::snprintf(line, sizeof(line), "; %zu dots, %.1f x %.1f mm\nG21\nG90\nG0 Z%.3f\n", plot.dots.size(),
+                      plot.sizeMm.width, plot.sizeMm.height, options.penUpZ);
+        file << line;
+        for (const auto& dot : plot.dots) {
+            std::snprintf(line, sizeof(line), "G0 X%.3f Y%.3f\nG1 Z%.3f F%.0f\nG0 Z%.3f\n", dot.x,
+                          plot.sizeMm.height - dot.y, options.penDownZ, options.feedRate, options.penUpZ);
+            file << line;
+        }
+        file << "G0 X0 Y0\nM2\n";
+    } else {
+        // HPGL plotter units are 0.025 mm
+        file << "IN;SP1;PA;\n";
+        for (const auto& dot : plot.dots) {
+            std::snprintf(line, sizeof(line), "PU%ld,%ld;PD;PU;\n", std::lround(dot.x * 40.0),
+                          std::lround((plot.sizeMm.height - dot.y) * 40.0));
+            file << line;
+        }
+        file << "PU0,0;SP0;\n";
+    }
+    return static_cast<bool>(file);
+}
+
+} // namespace Plotter
@@ -0,0 +1,45 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Plotter {
+
+// Pen plotter drawing options
+struct Options {
+    double widthMm = 200.0;         // Width of the drawing; height follows the image
+    bool shortenTravel = true;      // Visit dots nearest-first instead of row by row
+    double penUpZ = 2.0;            // G-code Z heights in mm
+    double penDownZ = 0.0;
+    double feedRate = 1500.0;       // G-code pen down speed in mm/min
+};
+
+// Dots to plot, in mm from the top left corner, in drawing order
+struct Plot {
+    cv::Size2d sizeMm;
+    double pitchMm = 0.0;           // Distance between neighbouring dot positions
+    std::vector<cv::Point2d> dots;
+};
+
+// One dot per dark pixel of a two-color result. Pixelated results and the
+// threshold matrix algorithms (Bayer, pattern, library matrices) repeat each
+// decision over blocks of pixelate x pattern scale pixels, so those are
+// sampled once per block and the dot pitch grows to match. Stippling gives one
+// dot per stipple, however many pixels it covers (dots that touch plot as one)
+Plot traceDots(const cv::Mat& result, const Dithering::Parameters& params, const Options& options);
+
+// Pen-up distance in mm from the origin through every dot
+double travelDistance(const Plot& plot);
+
+// Output formats by extension: csv (x_mm,y_mm rows), json, gcode/nc (Z for
+// pen up and down) and hpgl/plt
+const std::vector<std::string>& getPlotterExtensions();
+
+// Write the dots in the format matching the path's extension. G-code and
+// HPGL put the origin at the bottom left, as plotters do
+bool exportPlot(const Plot& plot, const std::string& path, const Options& options);
+
+} // namespace Plotter
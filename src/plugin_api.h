@@ -0,0 +1,69 @@
+#pragma once
+
+// C interface for dithering plugins. A plugin is a shared library (.so on
+// Linux, .dylib on macOS, .dll on Windows) in the plugins folder of the app
+// data directory that exports dithers_plugin(). Only this header is needed to
+// build one; it has no C++ or OpenCV types so any compiler and language that
+// can produce a C ABI library will do.
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DITHERS_PLUGIN_API_VERSION 1
+
+#ifdef _WIN32
+#define DITHERS_PLUGIN_EXPORT __declspec(dllexport)
+#else
+#define DITHERS_PLUGIN_EXPORT __attribute__((visibility("default")))
+#endif
+
+// A --plugin-option key=value pair, passed through untouched
+typedef struct DithersOption {
+    const char* key;
+    const char* value;
+} DithersOption;
+
+// The image to dither, already adjusted (gamma, contrast, blur, ...). Pixels
+// are 8-bit BGR, stride bytes apart per row, and are replaced in place. Output
+// colors should come from the palette, which is BGR triples
+typedef struct DithersImage {
+    uint8_t* pixels;
+    int width;
+    int height;
+    int stride;
+    const uint8_t* palette;
+    int paletteSize;
+    float strength;             // The usual 0-2 strength slider
+    float threshold;            // Already applied to the pixels; for algorithms that want it anyway
+    uint32_t seed;
+    int phaseX;
+    int phaseY;
+    const DithersOption* options;
+    int optionCount;
+} DithersImage;
+
+typedef struct DithersAlgorithm {
+    const char* id;             // Command line id, e.g. "my-dither"; must not clash with built-in ids
+    const char* name;           // Display name
+    const char* description;
+    // Return 0 on success. May be called from several threads at once
+    int (*dither)(DithersImage* image);
+} DithersAlgorithm;
+
+typedef struct DithersPlugin {
+    int apiVersion;             // DITHERS_PLUGIN_API_VERSION the plugin was built against
+    int algorithmCount;
+    const DithersAlgorithm* algorithms;
+} DithersPlugin;
+
+// The one function a plugin exports. Called once, when plugins are first used;
+// the returned data must stay valid until the program exits
+typedef const DithersPlugin* (*DithersPluginEntry)(void);
+#define DITHERS_PLUGIN_ENTRY "dithers_plugin"
+
+#ifdef __cplusplus
+}
+#endif
@@ -0,0 +1,180 @@
+#include "plugins.h"
+#include "dithering.h"
+#include "platform.h"
+#include "plugin_api.h"
+#include "settings.h"
+#include <algorithm>
+#include <filesystem>
+#include <set>
+
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <dlfcn.h>
+#endif
+
+namespace Plugins {
+
+struct Registry {
+    std::vector<AlgorithmInfo> algorithms;
+    std::vector<const DithersAlgorithm*> entries;   // Same order as algorithms
+    std::vector<std::string> errors;
+};
+
+static const char* libraryExtension() {
+#if defined(_WIN32)
+    return ".dll";
+#elif defined(__APPLE__)
+    return ".dylib";
+#else
+    return ".so";
+#endif
+}
+
+// Open a library and find its entry point. Libraries are never closed, since
+// the algorithm tables they return live in them
+static DithersPluginEntry openLibrary(const std::string& path, std::string& error) {
+#ifdef _WIN32
+    HMODULE library = LoadLibraryA(path.c_str());
+    if (!library) {
+        error = "could not load (error " + std::to_string(GetLastError()) + ")";
+        return nullptr;
+    }
+    auto entry = reinterpret_cast<DithersPluginEntry>(GetProcAddress(library, DITHERS_PLUGIN_ENTRY));
+#else
+    void* library = dlopen(path.c_str(), RTLD_NOW | RTLD_LOCAL);
+    if (!library) {
+        const char* message = dlerror();
+        error = message ? message : "could not load";
+        return nullptr;
+    }
+    auto entry = reinterpret_cast<DithersPluginEntry>(dlsym(library, DITHERS_PLUGIN_ENTRY));
+#endif
+    if (!entry) error = std::string("no ") + DITHERS_PLUGIN_ENTRY + "() function";
+    return entry;
+}
+
+static Registry loadRegistry() {
+    Registry registry;
+    std::string dir = getPluginDir();
+    if (dir.empty()) return registry;
+
+    std::vector<std::filesystem::path> paths;
+    std::error_code ec;
+    for (const auto& entry : std::filesystem::directory_iterator(dir, ec)) {
+        if (entry.is_regular_file() && entry.path().extension() == libraryExtension()) {
+            paths.push_back(entry.path());
+        }
+    }
+    std::sort(paths.begin(), paths.end());
+
+    std::set<std::string> builtIn;
+    for (const auto& info : Dithering::getAlgorithmLibrary()) builtIn.insert(Settings::getAlgorithmId(info.algorithm));
+
+    // Keyed by id, so the list comes out sorted
+    std::map<std::string, std::pair<AlgorithmInfo, const DithersAlgorithm*>> found;
+    for (const auto& path : paths) {
+        std::string name = path.filename().string();
+        std::string error;
+        DithersPluginEntry entry = openLibrary(path.string(), error);
+        const DithersPlugin* plugin = entry ? entry() : nullptr;
+        if (entry && !plugin) error = "registered nothing";
+        if (plugin && plugin->apiVersion != DITHERS_PLUGIN_API_VERSION) {
+            error = "built for plugin API " + std::to_string(plugin->apiVersion) + ", this version uses " +
+                    std::to_string(DITHERS_PLUGIN_API_VERSION);
+            plugin = nullptr;
+        }
+        if (!plugin) {
+            registry.errors.push_back(name + ": " + error);
+            continue;
+        }
+
+        for (int i = 0; i < plugin->algorithmCount && plugin->algorithms; ++i) {
+            const DithersAlgorithm& algorithm = plugin->algorithms[i];
+            if (!algorithm.id || !algorithm.dither) continue;
+            if (builtIn.count(algorithm.id) || found.count(algorithm.id)) {
+                registry.errors.push_back(name + ": algorithm id \"" + algorithm.id + "\" is already taken");
+                continue;
+            }
+            AlgorithmInfo info;
+            info.id = algorithm.id;
+            info.name = algorithm.name ? algorithm.name : algorithm.id;
+            info.description = algorithm.description ? algorithm.description : "";
+            info.library = path.string();
+            found[info.id] = {info, &algorithm};
+        }
+    }
+
+    for (const auto& entry : found) {
+        registry.algorithms.push_back(entry.second.first);
+        registry.entries.push_back(entry.second.second);
+    }
+    return registry;
+}
+
+static const Registry& getRegistry() {
+    static const Registry registry = loadRegistry();
+    return registry;
+}
+
+std::string getPluginDir() {
+    std::string dir = Platform::getAppDataDir();
+    if (dir.empty()) return "";
+    return (std::filesystem::path(dir) / "plugins").string();
+}
+
+const std::vector<AlgorithmInfo>& getAlgorithms() {
+    return getRegistry().algorithms;
+}
+
+const std::vector<std::string>& getLoadErrors() {
+    return getRegistry().errors;
+}
+
+bool hasAlgorithm(const std::string& id) {
+    const auto& algorithms = getAlgorithms();
+    return std::any_of(algorithms.begin(), algorithms.end(), [&](const AlgorithmInfo& info) { return info.id == id; });
+}
+
+bool runAlgorithm(const std::string& id, cv::Mat& image, const std::vector<cv::Vec3b>& palette, float strength,
+                  float threshold, unsigned int seed, int phaseX, int phaseY,
+                  const std::map<std::string, std::string>& options, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    const Registry& registry = getRegistry();
+    const DithersAlgorithm* algorithm = nullptr;
+    for (size_t i = 0; i < registry.algorithms.size(); ++i) {
+        if (registry.algorithms[i].id == id) algorithm = registry.entries[i];
+    }
+    if (!algorithm) return fail(Errors::ErrorCode::INVALID_ARGUMENT, "No plugin algorithm named " + id);
+    if (image.empty() || image.type() != CV_8UC3 || palette.empty()) {
+        return fail(Errors::ErrorCode::INVALID_ARGUMENT, "Plugin " + id + " needs an 8-bit color image and a palette");
+    }
+
+    std::vector<DithersOption> pairs;
+    for (const auto& option : options) pairs.push_back({option.first.c_str(), option.second.c_str()});
+
+    DithersImage request;
+    request.pixels = image.data;
+    request.width = image.cols;
+    request.height = image.rows;
+    request.stride = static_cast<int>(image.step);
+    request.palette = palette[0].val;
+    request.paletteSize = static_cast<int>(palette.size());
+    request.strength = strength;
+    request.threshold = threshold;
+    request.seed = seed;
+    request.phaseX = phaseX;
+    request.phaseY = phaseY;
+    request.options = pairs.empty() ? nullptr : pairs.data();
+    request.optionCount = static_cast<int>(pairs.size());
+    int status = algorithm->dither(&request);
+    if (status != 0) {
+        return fail(Errors::ErrorCode::INTERNAL, "Plugin " + id + " failed (status " + std::to_string(status) + ")");
+    }
+    return true;
+}
+
+} // namespace Plugins
@@ -0,0 +1,38 @@
+#pragma once
+
+#include "errors.h"
+#include <map>
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace Plugins {
+
+// An algorithm registered by a plugin library
+struct AlgorithmInfo {
+    std::string id;
+    std::string name;
+    std::string description;
+    std::string library;        // Path of the library it came from
+};
+
+// Folder plugins are loaded from: "plugins" in the app data directory
+std::string getPluginDir();
+
+// Algorithms of all plugins, sorted by id. Libraries are loaded on first use
+// and stay loaded; ids clashing with built-in algorithms or an earlier plugin
+// are skipped
+const std::vector<AlgorithmInfo>& getAlgorithms();
+
+// Libraries that could not be loaded, with the reason
+const std::vector<std::string>& getLoadErrors();
+
+bool hasAlgorithm(const std::string& id);
+
+// Dither 8-bit BGR pixels in place with a plugin algorithm. False, with the
+// reason in error, if there is no such algorithm or it reported a failure
+bool runAlgorithm(const std::string& id, cv::Mat& image, const std::vector<cv::Vec3b>& palette, float strength,
+                  float threshold, unsigned int seed, int phaseX, int phaseY,
+                  const std::map<std::string, std::string>& options, Errors::Error* error = nullptr);
+
+} // namespace Plugins
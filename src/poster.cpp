@@ -0,0 +1,365 @@
+#include "poster.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdint>
+#include <cstdio>
+#include <fstream>
+
+namespace Poster {
+
+const std::vector<PaperSize>& getPaperSizes() {
+    static const std::vector<PaperSize> sizes = {
+        {"letter", "US Letter", 8.5, 11.0},
+        {"legal", "US Legal", 8.5, 14.0},
+        {"tabloid", "Tabloid", 11.0, 17.0},
+        {"a4", "A4", 8.27, 11.69},
+        {"a3", "A3", 11.69, 16.54},
+    };
+    return sizes;
+}
+
+const PaperSize* findPaperSize(const std::string& id) {
+    for (const auto& size : getPaperSizes()) {
+        if (size.id == id) return &size;
+    }
+    return nullptr;
+}
+
+// Page size in inches after orientation
+static bool pageSize(const std::string& id, bool landscape, double& width, double& height) {
+    const PaperSize* paper = findPaperSize(id);
+    if (!paper) return false;
+    width = landscape ? paper->heightInches : paper->widthInches;
+    height = landscape ? paper->widthInches : paper->heightInches;
+    return true;
+}
+
+static bool pageSize(const Options& options, double& width, double& height) {
+    return pageSize(options.paper, options.landscape, width, height);
+}
+
+bool computeLayout(const cv::Size& imageSize, const Options& options, Layout& layout) {
+    double pageWidth, pageHeight;
+    if (imageSize.width <= 0 || imageSize.height <= 0 || options.dpi <= 0.0 ||
+        !pageSize(options, pageWidth, pageHeight)) {
+        return false;
+    }
+
+    int printableWidth = static_cast<int>((pageWidth - 2.0 * options.marginInches) * options.dpi);
+    int printableHeight = static_cast<int>((pageHeight - 2.0 * options.marginInches) * options.dpi);
+    int overlap = static_cast<int>(std::round(options.overlapInches * options.dpi));
+    if (printableWidth <= overlap || printableHeight <= overlap) return false;
+
+    // Clamped in double before converting, so huge widths or DPIs can't overflow int
+    double width = options.posterWidthInches > 0.0 ? std::round(options.posterWidthInches * options.dpi)
+                                                   : imageSize.width;
+    double height = std::round(static_cast<double>(imageSize.height) * width / imageSize.width);
+    if (!(width <= MAX_POSTER_PIXELS && height <= MAX_POSTER_PIXELS)) return false;
+    int posterWidth = std::max(1, static_cast<int>(width));
+    int posterHeight = std::max(1, static_cast<int>(height));
+    layout.posterPixels = cv::Size(posterWidth, posterHeight);
+
+    auto sheets = [overlap](int length, int printable) {
+        int step = printable - overlap;
+        return std::max(1, (std::max(length - overlap, 1) + step - 1) / step);
+    };
+    layout.columns = sheets(posterWidth, printableWidth);
+    layout.rows = sheets(posterHeight, printableHeight);
+    return true;
+}
+
+// Compress an image for a PDF FlateDecode stream. PNG's IDAT data is exactly
+// zlib with PNG row predictors, which PDF can decode directly (Predictor 15)
+static bool encodeFlate(const cv::Mat& image, std::vector<uchar>& data) {
+    std::vector<uchar> png;
+    if (!cv::imencode(".png", image, png, {cv::IMWRITE_PNG_COMPRESSION, 6})) return false;
+    if (png.size() < 33 || png[25] != 2 || png[28] != 0) return false;  // 8-bit RGB, not interlaced
+
+    data.clear();
+    size_t pos = 8;
+    while (pos + 12 <= png.size()) {
+        size_t length = (static_cast<size_t>(png[pos]) << 24) | (png[pos + 1] << 16) |
+                        (png[pos + 2] << 8) | png[pos + 3];
+        std::string type(png.begin() + pos + 4, png.begin() + pos + 8);
+        if (pos + 12 + length > png.size()) return false;
+        if (type == "IDAT") {
+            data.insert(data.end(), png.begin() + pos + 8, png.begin() + pos + 8 + length);
+        }
+        pos += 12 + length;
+    }
+    return !data.empty();
+}
+
+static std::string format(const char* pattern, double a, double b, double c = 0.0, double d = 0.0) {
+    char buffer[128];
+    std::snprintf(buffer, sizeof(buffer), pattern, a, b, c, d);
+    return buffer;
+}
+
+bool exportPosterPdf(const cv::Mat& image, const std::string& path, const Options& options) {
+    Layout layout;
+    double pageWidthInches, pageHeightInches;
+    if (image.empty() || !computeLayout(image.size(), options, layout) ||
+        !pageSize(options, pageWidthInches, pageHeightInches)) {
+        return false;
+    }
+
+    std::ofstream file(path, std::ios::binary);
+    if (!file) return false;
+
+    const double pointsPerPixel = 72.0 / options.dpi;
+    const double pageWidth = pageWidthInches * 72.0;
+    const double pageHeight = pageHeightInches * 72.0;
+    const double margin = options.marginInches * 72.0;
+    const int printableWidth = static_cast<int>((pageWidthInches - 2.0 * options.marginInches) * options.dpi);
+    const int printableHeight = static_cast<int>((pageHeightInches - 2.0 * options.marginInches) * options.dpi);
+    const int overlap = static_cast<int>(std::round(options.overlapInches * options.dpi));
+    const int pages = layout.pages();
+
+    // Nearest-neighbour source pixel for every poster column and row
+    std::vector<int> sourceX(layout.posterPixels.width), sourceY(layout.posterPixels.height);
+    for (int x = 0; x < layout.posterPixels.width; ++x) {
+        sourceX[x] = static_cast<int>(std::min<int64_t>(image.cols - 1,
+            static_cast<int64_t>(x) * image.cols / layout.posterPixels.width));
+    }
+    for (int y = 0; y < layout.posterPixels.height; ++y) {
+        sourceY[y] = static_cast<int>(std::min<int64_t>(image.rows - 1,
+            static_cast<int64_t>(y) * image.rows / layout.posterPixels.height));
+    }
+
+    // Objects: 1 catalog, 2 page tree, 3 font, then page, contents and image per sheet
+    std::vector<std::streamoff> offsets(4 + 3 * pages, 0);
+    auto beginObject = [&](int number) {
+        offsets[number] = file.tellp();
+        file << number << " 0 obj\n";
+    };
+
+    file << "%PDF-1.4\n%\xE2\xE3\xCF\xD3\n";
+    beginObject(1);
+    file << "<< /Type /Catalog /Pages 2 0 R >>\nendobj\n";
+    beginObject(2);
+    file << "<< /Type /Pages /Count " << pages << " /Kids [";
+    for (int p = 0; p < pages; ++p) file << " " << 4 + 3 * p << " 0 R";
+    file << " ] >>\nendobj\n";
+    beginObject(3);
+    file << "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj\n";
+
+    const int step[2] = {printableWidth - overlap, printableHeight - overlap};
+    for (int row = 0; row < layout.rows; ++row) {
+        for (int column = 0; column < layout.columns; ++column) {
+            int page = row * layout.columns + column;
+            int pageObject = 4 + 3 * page;
+
+            cv::Rect tile(column * step[0], row * step[1], printableWidth, printableHeight);
+            tile = tile & cv::Rect(0, 0, layout.posterPixels.width, layout.posterPixels.height);
+            cv::Mat sheet(tile.size(), CV_8UC3);
+            for (int y = 0; y < tile.height; ++y) {
+                const cv::Vec3b* source = image.ptr<cv::Vec3b>(sourceY[tile.y + y]);
+                cv::Vec3b* target = sheet.ptr<cv::Vec3b>(y);
+                for (int x = 0; x < tile.width; ++x) {
+                    target[x] = source[sourceX[tile.x + x]];
+                }
+            }
+
+            std::vector<uchar> imageData;
+            if (!encodeFlate(sheet, imageData)) return false;
+
+            // Image hangs from the top-left margin; PDF's origin is bottom-left
+            double imageWidth = tile.width * pointsPerPixel;
+            double imageHeight = tile.height * pointsPerPixel;
+            double left = margin, top = pageHeight - margin;
+            double bottom = top - imageHeight;
+
+            std::string content = "q\n" + format("%.3f 0 0 %.3f %.3f %.3f cm\n", imageWidth, imageHeight,
+                                                 left, bottom) + "/Im0 Do\nQ\n";
+
+            // Trim lines sit halfway into the overlap with neighbouring sheets and on
+            // the poster's outer edges. Cutting every sheet on them lets the sheets
+            // butt together; cutting only top and left edges leaves overlap for glue
+            if (options.cropMarks) {
+                double trimLeft = left + (column > 0 ? overlap / 2 * pointsPerPixel : 0.0);
+                double trimRight = left + imageWidth -
+                    (column + 1 < layout.columns ? (overlap - overlap / 2) * pointsPerPixel : 0.0);
+                double trimTop = top - (row > 0 ? overlap / 2 * pointsPerPixel : 0.0);
+                double trimBottom = bottom +
+                    (row + 1 < layout.rows ? (overlap - overlap / 2) * pointsPerPixel : 0.0);
+
+                double gap = 2.0, length = margin - 2.0 * gap;
+                if (length > 0.0) {
+                    content += "0.25 w 0 G\n";
+                    for (double x : {trimLeft, trimRight}) {
+                        content += format("%.3f %.3f m %.3f %.3f l S\n", x, top + gap, x, top + gap + length);
+                        content += format("%.3f %.3f m %.3f %.3f l S\n", x, bottom - gap, x, bottom - gap - length);
+                    }
+                    for (double y : {trimTop, trimBottom}) {
+                        double right = left + imageWidth;
+                        content += format("%.3f %.3f m %.3f %.3f l S\n", left - gap, y, left - gap - length, y);
+                        content += format("%.3f %.3f m %.3f %.3f l S\n", right + gap, y, right + gap + length, y);
+                    }
+                }
+            }
+
+            // Sheet position, so the poster can be put together in order
+            char label[96];
+            std::snprintf(label, sizeof(label), "Row %d of %d, column %d of %d", row + 1, layout.rows,
+                          column + 1, layout.columns);
+            content += "BT /F1 6 Tf " + format("%.3f %.3f Td ", left, std::max(2.0, margin / 2.0 - 2.0)) +
+                       "(" + label + ") Tj ET\n";
+
+            beginObject(pageObject);
+            file << "<< /Type /Page /Parent 2 0 R "
+                 << format("/MediaBox [0 0 %.3f %.3f] ", pageWidth, pageHeight)
+                 << "/Resources << /Font << /F1 3 0 R >> /XObject << /Im0 " << pageObject + 2 << " 0 R >> >> "
+                 << "/Contents " << pageObject + 1 << " 0 R >>\nendobj\n";
+
+            beginObject(pageObject + 1);
+            file << "<< /Length " << content.size() << " >>\nstream\n" << content << "\nendstream\nendobj\n";
+
+            beginObject(pageObject + 2);
+            file << "<< /Type /XObject /Subtype /Image /Width " << tile.width << " /Height " << tile.height
+                 << " /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /FlateDecode"
+                 << " /DecodeParms << /Predictor 15 /Colors 3 /BitsPerComponent 8 /Columns " << tile.width << " >>"
+                 << " /Length " << imageData.size() << " >>\nstream\n";
+            file.write(reinterpret_cast<const char*>(imageData.data()), static_cast<std::streamsize>(imageData.size()));
+            file << "\nendstream\nendobj\n";
+        }
+    }
+
+    // Cross-reference table: fixed-width byte offsets of every object
+    std::streamoff xref = file.tellp();
+    file << "xref\n0 " << offsets.size() << "\n0000000000 65535 f \n";
+    for (size_t i = 1; i < offsets.size(); ++i) {
+        char entry[24];
+        std::snprintf(entry, sizeof(entry), "%010lld 00000 n \n", static_cast<long long>(offsets[i]));
+        file << entry;
+    }
+    file << "trailer\n<< /Size " << offsets.size() << " /Root 1 0 R >>\nstartxref\n" << xref << "\n%%EOF\n";
+    return static_cast<bool>(file);
+}
+
+bool computePrintSize(const cv::Size& imageSize, const PrintOptions& options, double& widthInches,
+                      double& heightInches) {
+    double pageWidth, pageHeight;
+    if (imageSize.width <= 0 || imageSize.height <= 0 || options.dpi <= 0.0 ||
+        !pageSize(options.paper, options.landscape, pageWidth, pageHeight)) {
+        return false;
+    }
+    widthInches = imageSize.width / options.dpi;
+    heightInches = imageSize.height / options.dpi;
+    return widthInches <= pageWidth - 2.0 * options.marginInches &&
+           heightInches <= pageHeight - 2.0 * options.marginInches;
+}
+
+// PDF fill color operator for a BGR color
+static std::string fillColor(const cv::Vec3b& color) {
+    return format("%.3f %.3f %.3f rg\n", color[2] / 255.0, color[1] / 255.0, color[0] / 255.0);
+}
+
+// Content stream drawing a vector halftone into the image rectangle: paper,
+// then each screen's dots, clipped to the rectangle. Circles are four Bezier
+// arcs; overprinting screens use the /Multiply graphics state
+static std::string halftoneContent(const Halftone::VectorHalftone& halftone, double left, double top,
+                                   double pointsPerPixel) {
+    double width = halftone.size.width * pointsPerPixel;
+    double height = halftone.size.height * pointsPerPixel;
+    std::string content = "q\n" + format("%.3f %.3f %.3f %.3f re W n\n", left, top - height, width, height);
+    content += fillColor(halftone.paper) + format("%.3f %.3f %.3f %.3f re f\n", left, top - height, width, height);
+    if (halftone.screens.size() > 1) content += "/Multiply gs\n";
+
+    const double kappa = 0.5523;    // Bezier control distance for a quarter circle
+    for (const Halftone::Screen& screen : halftone.screens) {
+        content += fillColor(screen.ink);
+        double radians = screen.angle * CV_PI / 180.0;
+        double c = std::cos(radians), s = std::sin(radians);
+        for (const Halftone::Dot& dot : screen.dots) {
+            // Image y runs down, PDF y runs up
+            double x = left + dot.center.x * pointsPerPixel;
+            double y = top - dot.center.y * pointsPerPixel;
+            double r = dot.size * pointsPerPixel;
+            if (halftone.shape == Dithering::HalftoneShape::DIAMOND) {
+                content += format("%.2f %.2f m %.2f %.2f l ", x + r * c, y - r * s, x - r * s, y - r * c);
+                content += format("%.2f %.2f l %.2f %.2f l h f\n", x - r * c, y + r * s, x + r * s, y + r * c);
+            } else {
+                double k = r * kappa;
+                content += format("%.2f %.2f m ", x + r, y);
+                content += format("%.2f %.2f %.2f %.2f ", x + r, y + k, x + k, y + r) + format("%.2f %.2f c ", x, y + r);
+                content += format("%.2f %.2f %.2f %.2f ", x - k, y + r, x - r, y + k) + format("%.2f %.2f c ", x - r, y);
+                content += format("%.2f %.2f %.2f %.2f ", x - r, y - k, x - k, y - r) + format("%.2f %.2f c ", x, y - r);
+                content += format("%.2f %.2f %.2f %.2f ", x + k, y - r, x + r, y - k) +
+                           format("%.2f %.2f c f\n", x + r, y);
+            }
+        }
+    }
+    return content + "Q\n";
+}
+
+bool exportPrintPdf(const cv::Mat& image, const std::string& path, const PrintOptions& options,
+                    const Halftone::VectorHalftone* halftone) {
+    double widthInches, heightInches, pageWidthInches, pageHeightInches;
+    if (image.empty() || !computePrintSize(image.size(), options, widthInches, heightInches) ||
+        !pageSize(options.paper, options.landscape, pageWidthInches, pageHeightInches)) {
+        return false;
+    }
+
+    std::vector<uchar> imageData;
+    if (!halftone && !encodeFlate(image, imageData)) return false;
+
+    std::ofstream file(path, std::ios::binary);
+    if (!file) return false;
+
+    // Centered on the page
+    const double pageWidth = pageWidthInches * 72.0;
+    const double pageHeight = pageHeightInches * 72.0;
+    const double width = widthInches * 72.0;
+    const double height = heightInches * 72.0;
+    const double left = (pageWidth - width) / 2.0;
+    const double bottom = (pageHeight - height) / 2.0;
+
+    std::string content = halftone
+        ? halftoneContent(*halftone, left, bottom + height, 72.0 / options.dpi)
+        : "q\n" + format("%.3f 0 0 %.3f %.3f %.3f cm\n", width, height, left, bottom) + "/Im0 Do\nQ\n";
+
+    // Objects: 1 catalog, 2 page tree, 3 page, 4 contents, 5 image or graphics state
+    std::vector<std::streamoff> offsets(6, 0);
+    auto beginObject = [&](int number) {
+        offsets[number] = file.tellp();
+        file << number << " 0 obj\n";
+    };
+
+    file << "%PDF-1.4\n%\xE2\xE3\xCF\xD3\n";
+    beginObject(1);
+    file << "<< /Type /Catalog /Pages 2 0 R >>\nendobj\n";
+    beginObject(2);
+    file << "<< /Type /Pages /Count 1 /Kids [ 3 0 R ] >>\nendobj\n";
+    beginObject(3);
+    file << "<< /Type /Page /Parent 2 0 R " << format("/MediaBox [0 0 %.3f %.3f] ", pageWidth, pageHeight)
+         << (halftone ? "/Resources << /ExtGState << /Multiply 5 0 R >> >> "
+                      : "/Resources << /XObject << /Im0 5 0 R >> >> ")
+         << "/Contents 4 0 R >>\nendobj\n";
+    beginObject(4);
+    file << "<< /Length " << content.size() << " >>\nstream\n" << content << "\nendstream\nendobj\n";
+
+    beginObject(5);
+    if (halftone) {
+        file << "<< /Type /ExtGState /BM /Multiply >>\nendobj\n";
+    } else {
+        file << "<< /Type /XObject /Subtype /Image /Width " << image.cols << " /Height " << image.rows
+             << " /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /FlateDecode"
+             << " /DecodeParms << /Predictor 15 /Colors 3 /BitsPerComponent 8 /Columns " << image.cols << " >>"
+             << " /Length " << imageData.size() << " >>\nstream\n";
+        file.write(reinterpret_cast<const char*>(imageData.data()), static_cast<std::streamsize>(imageData.size()));
+        file << "\nendstream\nendobj\n";
+    }
+
+    std::streamoff xref = file.tellp();
+    file << "xref\n0 " << offsets.size() << "\n0000000000 65535 f \n";
+    for (size_t i = 1; i < offsets.size(); ++i) {
+        char entry[24];
+        std::snprintf(entry, sizeof(entry), "%010lld 00000 n \n", static_cast<long long>(offsets[i]));
+        file << entry;
+    }
+    file << "trailer\n<< /Size " << offsets.size() << " /Root 1 0 R >>\nstartxref\n" << xref << "\n%%EOF\n";
+    return static_cast<bool>(file);
+}
+
+} // namespace Poster
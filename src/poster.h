@@ -0,0 +1,74 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "halftone.h"
+
+namespace Poster {
+
+// Printer paper size, portrait orientation
+struct PaperSize {
+    std::string id;             // Short name used on the command line
+    std::string name;
+    double widthInches;
+    double heightInches;
+};
+
+// Poster layout options
+struct Options {
+    std::string paper = "letter";
+    bool landscape = false;
+    double dpi = 300.0;             // Print resolution; each image pixel is at least one dot
+    double posterWidthInches = 0.0; // Width of the assembled poster (0 = one image pixel per dot)
+    double overlapInches = 0.25;    // Image repeated on neighbouring sheets, for gluing
+    double marginInches = 0.25;     // Unprintable border kept clear on every sheet
+    bool cropMarks = true;          // Marks where each sheet is trimmed before assembly
+};
+
+// Single-page print options
+struct PrintOptions {
+    std::string paper = "letter";
+    bool landscape = false;
+    double dpi = 300.0;             // Image pixels per inch on paper
+    double marginInches = 0.25;     // Unprintable border kept clear
+};
+
+// Sheet grid worked out for an image
+struct Layout {
+    cv::Size posterPixels;      // Image size after scaling to the poster width
+    int columns = 0;
+    int rows = 0;
+    int pages() const { return columns * rows; }
+};
+
+// Longest poster side in printer dots (about 55 feet at 300 DPI); larger
+// posters are refused by computeLayout
+const int MAX_POSTER_PIXELS = 200000;
+
+// Supported paper sizes
+const std::vector<PaperSize>& getPaperSizes();
+
+// Look up a paper size by id (nullptr if unknown)
+const PaperSize* findPaperSize(const std::string& id);
+
+// Work out the sheet grid without writing anything
+bool computeLayout(const cv::Size& imageSize, const Options& options, Layout& layout);
+
+// Split the image into overlapping sheets and write them as a multi-page PDF.
+// Scaling uses nearest-neighbour so the dither pattern prints crisp
+bool exportPosterPdf(const cv::Mat& image, const std::string& path, const Options& options);
+
+// Printed size of an image in inches at options.dpi; false if it doesn't fit
+// inside the margins of the page (or the paper is unknown)
+bool computePrintSize(const cv::Size& imageSize, const PrintOptions& options, double& widthInches,
+                      double& heightInches);
+
+// Write a one-page PDF with the image centered at its physical size, so it
+// prints at options.dpi without any scaling in the print dialog. With a
+// halftone, its dots are drawn as vector shapes instead of the raster
+bool exportPrintPdf(const cv::Mat& image, const std::string& path, const PrintOptions& options,
+                    const Halftone::VectorHalftone* halftone = nullptr);
+
+} // namespace Poster
@@ -0,0 +1,163 @@
+#include "queue.h"
+#include <algorithm>
+
+namespace Queue {
+
+JobQueue::~JobQueue() {
+    {
+        std::lock_guard<std::mutex> lock(mutex);
+        stopping = true;
+        for (auto& job : jobs) job->cancel = true;
+    }
+    changed.notify_all();
+    if (worker.joinable()) worker.join();
+}
+
+int JobQueue::enqueue(const std::string& name, Task task) {
+    std::lock_guard<std::mutex> lock(mutex);
+    auto job = std::make_shared<Job>();
+    job->info.id = nextId++;
+    job->info.name = name;
+    job->task = std::move(task);
+    jobs.push_back(job);
+
+    // The worker starts with the first job and stays around for later ones
+    if (!worker.joinable()) {
+        worker = std::thread([this]() { run(); });
+    }
+    changed.notify_all();
+    return job->info.id;
+}
+
+void JobQueue::pause() {
+    std::lock_guard<std::mutex> lock(mutex);
+    paused = true;
+}
+
+void JobQueue::resume() {
+    {
+        std::lock_guard<std::mutex> lock(mutex);
+        paused = false;
+    }
+    changed.notify_all();
+}
+
+bool JobQueue::isPaused() {
+    std::lock_guard<std::mutex> lock(mutex);
+    return paused;
+}
+
+bool JobQueue::cancel(int id) {
+    std::lock_guard<std::mutex> lock(mutex);
+    for (auto& job : jobs) {
+        if (job->info.id != id) continue;
+        if (job->info.state == JobState::PENDING) {
+            job->info.state = JobState::CANCELLED;
+        } else if (job->info.state != JobState::RUNNING) {
+            return false;
+        }
+        job->cancel = true;
+        changed.notify_all();
+        return true;
+    }
+    return false;
+}
+
+bool JobQueue::move(int id, int position) {
+    std::lock_guard<std::mutex> lock(mutex);
+    auto isPending = [](const std::shared_ptr<Job>& job) { return job->info.state == JobState::PENDING; };
+    auto found = std::find_if(jobs.begin(), jobs.end(),
+                              [id](const std::shared_ptr<Job>& job) { return job->info.id == id; });
+    if (found == jobs.end() || !isPending(*found)) return false;
+
+    std::shared_ptr<Job> job = *found;
+    jobs.erase(found);
+
+    // Pending jobs stay after finished and running ones
+    auto target = std::find_if(jobs.begin(), jobs.end(), isPending);
+    for (int i = 0; i < position && target != jobs.end(); ++i) ++target;
+    jobs.insert(target, job);
+    return true;
+}
+
+void JobQueue::clearFinished() {
+    std::lock_guard<std::mutex> lock(mutex);
+    jobs.erase(std::remove_if(jobs.begin(), jobs.end(), [](const std::shared_ptr<Job>& job) {
+        return job->info.state != JobState::PENDING && job->info.state != JobState::RUNNING;
+    }), jobs.end());
+}
+
+std::vector<JobInfo> JobQueue::snapshot() {
+    std::lock_guard<std::mutex> lock(mutex);
+    std::vector<JobInfo> infos;
+    for (const auto& job : jobs) infos.push_back(job->info);
+    return infos;
+}
+
+void JobQueue::run() {
+    std::unique_lock<std::mutex> lock(mutex);
+    while (true) {
+        std::shared_ptr<Job> job;
+        changed.wait(lock, [&]() {
+            if (stopping) return true;
+            if (paused) return false;
+            auto next = std::find_if(jobs.begin(), jobs.end(), [](const std::shared_ptr<Job>& candidate) {
+                return candidate->info.state == JobState::PENDING;
+            });
+            if (next == jobs.end()) return false;
+            job = *next;
+            return true;
+        });
+        if (stopping) return;
+
+        // Keep finished jobs in front of pending ones, in the order they ran
+        jobs.erase(std::find(jobs.begin(), jobs.end(), job));
+        auto firstPending = std::find_if(jobs.begin(), jobs.end(), [](const std::shared_ptr<Job>& candidate) {
+            return candidate->info.state == JobState::PENDING;
+        });
+        jobs.insert(firstPending, job);
+        job->info.state = JobState::RUNNING;
+
+        Dithering::ProgressCallback progress = [this, job](float fraction, const std::string& stage) {
+            std::unique_lock<std::mutex> progressLock(mutex);
+            job->info.progress = fraction;
+            job->info.stage = stage;
+            changed.wait(progressLock, [&]() { return !paused || job->cancel || stopping; });
+            return !job->cancel;
+        };
+
+        lock.unlock();
+        Errors::Error error;
+        bool ok = false;
+        try {
+            ok = job->task(progress, error);
+        } catch (const std::exception& e) {
+            error = Errors::fromException(e);
+        }
+        lock.lock();
+
+        if (job->cancel) {
+            job->info.state = JobState::CANCELLED;
+        } else if (ok) {
+            job->info.state = JobState::DONE;
+            job->info.progress = 1.0f;
+        } else {
+            job->info.state = JobState::FAILED;
+            job->info.error = error;
+        }
+        job->task = nullptr;
+    }
+}
+
+std::string getJobStateName(JobState state) {
+    switch (state) {
+        case JobState::PENDING: return "Waiting";
+        case JobState::RUNNING: return "Running";
+        case JobState::DONE: return "Done";
+        case JobState::FAILED: return "Failed";
+        case JobState::CANCELLED: return "Cancelled";
+        default: return "Unknown";
+    }
+}
+
+} // namespace Queue
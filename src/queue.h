@@ -0,0 +1,88 @@
+#pragma once
+
+#include <condition_variable>
+#include <deque>
+#include <functional>
+#include <memory>
+#include <mutex>
+#include <string>
+#include <thread>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+
+namespace Queue {
+
+enum class JobState {
+    PENDING,
+    RUNNING,
+    DONE,
+    FAILED,
+    CANCELLED
+};
+
+// The work of a job. It reports through progress (which returns false once the
+// job is cancelled) and returns whether it succeeded; error says why not.
+// Exceptions it throws fail the job
+using Task = std::function<bool(const Dithering::ProgressCallback& progress, Errors::Error& error)>;
+
+// What the UI shows for a job
+struct JobInfo {
+    int id = 0;
+    std::string name;
+    JobState state = JobState::PENDING;
+    float progress = 0.0f;
+    std::string stage;
+    Errors::Error error;
+};
+
+// Long-running jobs (exports, batches) run one at a time on a worker thread, in
+// queue order. Pausing holds the running job at its next progress report and
+// starts nothing new until resumed. All members are thread-safe
+struct JobQueue {
+    JobQueue() = default;
+    JobQueue(const JobQueue&) = delete;
+    JobQueue& operator=(const JobQueue&) = delete;
+    ~JobQueue();
+
+    // Add a job at the end of the queue, returns its id
+    int enqueue(const std::string& name, Task task);
+
+    void pause();
+    void resume();
+    bool isPaused();
+
+    // Cancel a pending or running job; false if it has already finished
+    bool cancel(int id);
+
+    // Move a pending job to position among the pending jobs (0 = next)
+    bool move(int id, int position);
+
+    // Forget jobs that are done, failed or cancelled
+    void clearFinished();
+
+    // Every job in queue order (jobs that have run come first, in the order they ran)
+    std::vector<JobInfo> snapshot();
+
+private:
+    struct Job {
+        JobInfo info;
+        Task task;
+        bool cancel = false;
+    };
+
+    void run();
+
+    std::mutex mutex;
+    std::condition_variable changed;
+    std::deque<std::shared_ptr<Job>> jobs;
+    std::thread worker;
+    bool paused = false;
+    bool stopping = false;
+    int nextId = 1;
+};
+
+std::string getJobStateName(JobState state);
+
+} // namespace Queue
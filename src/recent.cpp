@@ -0,0 +1,196 @@
+#include "recent.h"
+#include "imageio.h"
+#include "platform.h"
+#include <algorithm>
+#include <cstdio>
+#include <filesystem>
+#include <fstream>
+#include <iomanip>
+#include <mutex>
+#include <sstream>
+
+namespace RecentFiles {
+
+// Guards the list file, which thumbnails made on a worker thread also update
+static std::mutex listMutex;
+
+std::string getRecentFilesPath() {
+    std::string dir = Platform::getAppDataDir();
+    return dir.empty() ? "" : dir + "/recent_files.txt";
+}
+
+std::string getThumbnailDir() {
+    std::string dir = Platform::getAppDataDir();
+    return dir.empty() ? "" : dir + "/thumbnails";
+}
+
+// Modification time of a file, 0 if it doesn't exist. Only compared with
+// earlier readings, so the clock's epoch doesn't matter
+static int64_t getModified(const std::string& path) {
+    std::error_code ec;
+    if (!std::filesystem::is_regular_file(path, ec)) return 0;
+    auto time = std::filesystem::last_write_time(path, ec);
+    return ec ? 0 : static_cast<int64_t>(time.time_since_epoch().count());
+}
+
+// Thumbnails are named after a hash of the file's path (FNV-1a)
+static std::string thumbnailPath(const std::string& path) {
+    std::string dir = getThumbnailDir();
+    if (dir.empty()) return "";
+    uint64_t hash = 14695981039346656037ULL;
+    for (unsigned char c : path) {
+        hash ^= c;
+        hash *= 1099511628211ULL;
+    }
+    std::ostringstream name;
+    name << std::hex << std::setw(16) << std::setfill('0') << hash << ".png";
+    return (std::filesystem::path(dir) / name.str()).string();
+}
+
+// Shrink an image to THUMBNAIL_SIZE and store it; the thumbnail's path, or
+// empty if the image couldn't be loaded or written
+static std::string makeThumbnail(const std::string& path, const cv::Mat& loaded) {
+    cv::Mat image = loaded;
+    if (image.empty()) {
+        cv::Mat alpha;
+        if (!ImageIO::loadImage(path, image, alpha) || image.empty()) return "";
+    }
+
+    std::string thumbnail = thumbnailPath(path);
+    if (thumbnail.empty()) return "";
+    std::error_code ec;
+    std::filesystem::create_directories(getThumbnailDir(), ec);
+
+    double scale = std::min(1.0, static_cast<double>(THUMBNAIL_SIZE) / std::max(image.cols, image.rows));
+    cv::Size size(std::max(1, cvRound(image.cols * scale)), std::max(1, cvRound(image.rows * scale)));
+    cv::Mat small;
+    cv::resize(image, small, size, 0, 0, cv::INTER_AREA);
+    return cv::imwrite(thumbnail, small) ? thumbnail : "";
+}
+
+// File format: one "modified<TAB>path" line per file, newest first. The
+// modification time is the one the thumbnail was made from
+static std::vector<RecentFile> readList() {
+    std::vector<RecentFile> files;
+    std::ifstream file(getRecentFilesPath());
+    std::string line;
+
+    while (std::getline(file, line)) {
+        size_t tab = line.find('\t');
+        if (tab == std::string::npos || tab + 1 >= line.size()) continue;
+        RecentFile recent;
+        try {
+            recent.modified = std::stoll(line.substr(0, tab));
+        } catch (const std::exception&) {
+            continue;
+        }
+        recent.path = line.substr(tab + 1);
+        files.push_back(recent);
+    }
+    return files;
+}
+
+static bool saveList(const std::vector<RecentFile>& files) {
+    std::string path = getRecentFilesPath();
+    if (path.empty()) return false;
+
+    std::ofstream file(path);
+    if (!file) return false;
+    for (const auto& recent : files) {
+        file << recent.modified << "\t" << recent.path << "\n";
+    }
+    return static_cast<bool>(file);
+}
+
+void recordFile(const std::string& path, const cv::Mat& image) {
+    if (Platform::isRemoteUrl(path)) return;
+    std::error_code ec;
+    std::string absolute = std::filesystem::absolute(path, ec).lexically_normal().string();
+    if (ec) return;
+    int64_t modified = getModified(absolute);
+    if (modified == 0) return;
+
+    std::lock_guard<std::mutex> lock(listMutex);
+    std::vector<RecentFile> files = readList();
+    files.erase(std::remove_if(files.begin(), files.end(),
+                               [&](const RecentFile& recent) { return recent.path == absolute; }),
+                files.end());
+
+    RecentFile recent;
+    recent.path = absolute;
+    recent.modified = modified;
+    makeThumbnail(absolute, image);
+    files.insert(files.begin(), recent);
+
+    while (files.size() > MAX_RECENT_FILES) {
+        std::filesystem::remove(thumbnailPath(files.back().path), ec);
+        files.pop_back();
+    }
+    saveList(files);
+}
+
+std::vector<RecentFile> getRecentFiles(bool makeThumbnails) {
+    std::vector<RecentFile> files;
+    {
+        std::lock_guard<std::mutex> lock(listMutex);
+        bool changed = false;
+        std::error_code ec;
+
+        for (RecentFile& recent : readList()) {
+            int64_t modified = getModified(recent.path);
+            if (modified == 0) {
+                // Gone; drop it so it doesn't take a place in the list
+                std::filesystem::remove(thumbnailPath(recent.path), ec);
+                changed = true;
+                continue;
+            }
+
+            // An outdated thumbnail is still shown until the new one is made
+            recent.thumbnail = thumbnailPath(recent.path);
+            bool exists = std::filesystem::exists(recent.thumbnail, ec);
+            recent.stale = modified != recent.modified || !exists;
+            if (!exists) recent.thumbnail.clear();
+            files.push_back(recent);
+        }
+
+        if (changed) saveList(files);
+    }
+
+    if (makeThumbnails) updateThumbnails(files);
+    return files;
+}
+
+void updateThumbnails(std::vector<RecentFile>& files) {
+    bool made = false;
+    for (RecentFile& recent : files) {
+        if (!recent.stale) continue;
+        recent.modified = getModified(recent.path);
+        recent.thumbnail = makeThumbnail(recent.path, cv::Mat());
+        recent.stale = false;
+        made = true;
+    }
+    if (!made) return;
+
+    // The list may have changed meanwhile, so only the times of these files are updated
+    std::lock_guard<std::mutex> lock(listMutex);
+    std::vector<RecentFile> list = readList();
+    for (RecentFile& entry : list) {
+        for (const RecentFile& recent : files) {
+            if (recent.path == entry.path) entry.modified = recent.modified;
+        }
+    }
+    saveList(list);
+}
+
+bool clearRecentFiles() {
+    std::string path = getRecentFilesPath();
+    if (path.empty()) return false;
+
+    std::lock_guard<std::mutex> lock(listMutex);
+    std::remove(path.c_str());
+    std::error_code ec;
+    std::filesystem::remove_all(getThumbnailDir(), ec);
+    return !ec;
+}
+
+} // namespace RecentFiles
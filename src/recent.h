@@ -0,0 +1,45 @@
+#pragma once
+
+#include <cstdint>
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace RecentFiles {
+
+// A file opened earlier, newest first in the list
+struct RecentFile {
+    std::string path;
+    int64_t modified = 0;           // File's modification time when its thumbnail was made
+    std::string thumbnail;          // PNG in the thumbnail cache (empty = none could be made)
+    bool stale = false;             // The thumbnail is missing or older than the file
+};
+
+// Files kept in the list; older ones are dropped along with their thumbnails
+const size_t MAX_RECENT_FILES = 12;
+
+// Longest side of the cached thumbnails, in pixels
+const int THUMBNAIL_SIZE = 128;
+
+// Put a file at the top of the list. image is the file's content as loaded,
+// used for the thumbnail so the file isn't read twice (empty = load it).
+// URLs and files that don't exist are ignored
+void recordFile(const std::string& path, const cv::Mat& image = cv::Mat());
+
+// The list, newest first. Files that no longer exist are left out. Files
+// changed since their thumbnail was made are marked stale and, with
+// makeThumbnails, get a new one
+std::vector<RecentFile> getRecentFiles(bool makeThumbnails = true);
+
+// Make new thumbnails for the stale files of a list and record them. Big
+// images take a while to load, so the GUI runs this on a worker thread
+void updateThumbnails(std::vector<RecentFile>& files);
+
+// Forget all files and delete the cached thumbnails
+bool clearRecentFiles();
+
+// Location of the list file and of the thumbnail cache folder
+std::string getRecentFilesPath();
+std::string getThumbnailDir();
+
+} // namespace RecentFiles
@@ -0,0 +1,501 @@
+#include "script.h"
+#include "embedded.h"
+#include "imageio.h"
+#include "descreen.h"
+#include "devices.h"
+#include "lospec.h"
+#include "palettefile.h"
+#include "settings.h"
+#include "transform.h"
+#include <algorithm>
+#include <cctype>
+#include <cstdio>
+#include <filesystem>
+#include <fstream>
+#include <map>
+#include <sstream>
+
+namespace Script {
+
+const std::vector<CommandInfo>& getCommands() {
+    static const std::vector<CommandInfo> commands = {
+        {"load <image>", "Load an image to work on"},
+        {"resize <w>x<h> [filter]", "Resize; leave out w or h to keep the aspect. Filters as for --resize-filter"},
+        {"crop <x>,<y>,<w>,<h>", "Crop to a rectangle"},
+        {"rotate <degrees>", "Rotate clockwise by 90, 180 or 270"},
+        {"flip <horizontal|vertical>", "Mirror the image"},
+        {"descreen [method] [period]", "Smooth away a dither or halftone screen: gaussian, sinc or fft (default), "
+                                       "period in pixels detected if left out"},
+        {"dither", "Dither the image with the current settings"},
+        {"save <file>", "Save the image by extension; .h, .c and .bin pack it for embedded displays"},
+        {"set <setting> <value>", "Change a setting, named as in settings files (see --settings-schema)"},
+        {"settings <file.json>", "Load a settings file"},
+        {"preset <name>", "Load a saved preset"},
+        {"palette <file>", "Use a palette file's colors as custom palette (.gpl, .ase, .act, .pal, .txt, .hex, .png)"},
+        {"lospec <name>", "Use a lospec.com palette as custom palette, by its URL name (e.g. pico-8)"},
+        {"device <id>", "Emulate a retro screen (gameboy, macintosh, eink, cga-composite): use its palette and "
+                        "algorithm, and crop and scale the loaded image, if any, to its resolution"},
+        {"reset", "Go back to the settings the script started with"},
+        {"let <name> <value>", "Set a variable"},
+        {"print <text>", "Print a message"},
+        {"for <name> in <files>", "Repeat the lines up to \"end\" for each of a folder's images, or of the files "
+                                  "matching a pattern such as photos/*.jpg"},
+    };
+    return commands;
+}
+
+// One command, after splitting into words
+struct Line {
+    int number = 0;
+    std::vector<std::string> words;
+    size_t end = 0;             // For "for" lines: index of the matching "end"
+};
+
+struct State {
+    std::string name;
+    Dithering::Parameters initial;
+    Dithering::Parameters params;
+    std::map<std::string, std::string> variables;
+    cv::Mat color, alpha, precise;  // The image worked on, as loadImage returns it
+    LogCallback log;
+    bool overwrite = true;          // save may replace existing files
+};
+
+// Split a line into words: spaces separate them and "double quotes" group
+// them. Lines starting with # are comments
+static bool splitWords(const std::string& text, std::vector<std::string>& words, std::string& error) {
+    size_t start = text.find_first_not_of(" \t\r");
+    if (start == std::string::npos || text[start] == '#') return true;
+
+    std::string word;
+    bool inWord = false, quoted = false;
+    for (size_t i = start; i < text.size(); ++i) {
+        char c = text[i];
+        if (c == '"') {
+            quoted = !quoted;
+            inWord = true;
+        } else if (!quoted && (c == ' ' || c == '\t' || c == '\r')) {
+            if (inWord) words.push_back(word);
+            word.clear();
+            inWord = false;
+        } else {
+            word += c;
+            inWord = true;
+        }
+    }
+    if (quoted) {
+        error = "Missing closing quote";
+        return false;
+    }
+    if (inWord) words.push_back(word);
+    return true;
+}
+
+// Replace $name and ${name} with variables; $$ is a literal $
+static bool expand(const std::string& word, const State& state, std::string& out, std::string& error) {
+    out.clear();
+    for (size_t i = 0; i < word.size(); ++i) {
+        if (word[i] != '$' || i + 1 >= word.size()) {
+            out += word[i];
+            continue;
+        }
+        std::string name;
+        if (word[i + 1] == '$') {
+            out += '$';
+            ++i;
+            continue;
+        } else if (word[i + 1] == '{') {
+            size_t close = word.find('}', i + 2);
+            if (close == std::string::npos) {
+                error = "Missing } in " + word;
+                return false;
+            }
+            name = word.substr(i + 2, close - i - 2);
+            i = close;
+        } else {
+            size_t end = i + 1;
+            while (end < word.size() && (std::isalnum(static_cast<unsigned char>(word[end])) || word[end] == '_')) {
+                ++end;
+            }
+            name = word.substr(i + 1, end - i - 1);
+            if (name.empty()) {
+                out += '$';
+                continue;
+            }
+            i = end - 1;
+        }
+
+        auto value = state.variables.find(name);
+        if (value == state.variables.end()) {
+            error = "Unknown variable $" + name;
+            return false;
+        }
+        out += value->second;
+    }
+    return true;
+}
+
+// Set a variable to a file path, with name.name, name.dir and name.ext for its parts
+static void setFileVariable(State& state, const std::string& name, const std::string& path) {
+    std::filesystem::path file(path);
+    std::string ext = file.extension().string();
+    state.variables[name] = path;
+    state.variables[name + ".name"] = file.stem().string();
+    state.variables[name + ".dir"] = file.parent_path().string();
+    state.variables[name + ".ext"] = ext.empty() ? ext : ext.substr(1);
+}
+
+// * and ? wildcards within one file name
+static bool matchesPattern(const std::string& name, const std::string& pattern) {
+    size_t n = 0, p = 0, starN = std::string::npos, starP = std::string::npos;
+    while (n < name.size()) {
+        if (p < pattern.size() && (pattern[p] == '?' || pattern[p] == name[n])) {
+            ++n;
+            ++p;
+        } else if (p < pattern.size() && pattern[p] == '*') {
+            starP = p++;
+            starN = n;
+        } else if (starP != std::string::npos) {
+            p = starP + 1;
+            n = ++starN;
+        } else {
+            return false;
+        }
+    }
+    while (p < pattern.size() && pattern[p] == '*') ++p;
+    return p == pattern.size();
+}
+
+// A folder's images, or the files matching a pattern in its last part
+static std::vector<std::string> listFiles(const std::string& pattern) {
+    std::vector<std::string> files;
+    std::error_code ec;
+    std::filesystem::path folder = pattern;
+    std::string filePattern;
+    if (!std::filesystem::is_directory(folder, ec)) {
+        filePattern = folder.filename().string();
+        folder = folder.parent_path();
+        if (folder.empty()) folder = ".";
+    }
+
+    for (const auto& entry : std::filesystem::directory_iterator(folder, ec)) {
+        if (!entry.is_regular_file()) continue;
+        try {
+            std::string path = entry.path().string();
+            bool wanted = filePattern.empty() ? ImageIO::isImageFile(path)
+                                              : matchesPattern(entry.path().filename().string(), filePattern);
+            if (wanted) files.push_back(path);
+        } catch (const std::exception&) {
+            // Name can't be represented as a narrow string on this system
+        }
+    }
+    std::sort(files.begin(), files.end());
+    return files;
+}
+
+// Apply a transform to the image and its alpha and full precision copies
+static bool transformImage(State& state, const std::function<cv::Mat(const cv::Mat&)>& transform) {
+    cv::Mat color = transform(state.color);
+    if (color.empty()) return false;
+    state.color = color;
+    if (!state.alpha.empty()) state.alpha = transform(state.alpha);
+    if (!state.precise.empty()) state.precise = transform(state.precise);
+    return true;
+}
+
+// Run one command with its words already expanded. For loops are run by runLines
+static bool runCommand(const std::vector<std::string>& words, State& state, Errors::Error& error) {
+    using Errors::ErrorCode;
+    auto fail = [&error](ErrorCode code, const std::string& message) {
+        error = {code, message};
+        return false;
+    };
+    const std::string& command = words[0];
+    const size_t arguments = words.size() - 1;
+    auto expect = [&](size_t minimum, size_t maximum, const char* usage) {
+        if (arguments >= minimum && arguments <= maximum) return true;
+        error = {ErrorCode::INVALID_ARGUMENT, std::string("Usage: ") + usage};
+        return false;
+    };
+    auto needImage = [&]() {
+        if (!state.color.empty()) return true;
+        error = {ErrorCode::INVALID_ARGUMENT, command + " needs an image; load one first"};
+        return false;
+    };
+
+    if (command == "load") {
+        if (!expect(1, 1, "load <image>")) return false;
+        cv::Mat color, alpha, precise;
+        if (!ImageIO::loadImage(words[1], color, alpha, nullptr, &precise)) {
+            Errors::Error failure = ImageIO::describeLoadFailure(words[1]);
+            return fail(failure.code, words[1] + ": " + failure.message);
+        }
+        state.color = color;
+        state.alpha = alpha;
+        state.precise = precise;
+    }
+    else if (command == "resize") {
+        if (!expect(1, 2, "resize <w>x<h> [filter]") || !needImage()) return false;
+        int width = 0, height = 0;
+        size_t x = words[1].find('x');
+        std::string widthText = words[1].substr(0, x);
+        std::string heightText = x != std::string::npos ? words[1].substr(x + 1) : "";
+        if (x == std::string::npos || (!widthText.empty() && std::sscanf(widthText.c_str(), "%d", &width) != 1) ||
+            (!heightText.empty() && std::sscanf(heightText.c_str(), "%d", &height) != 1) ||
+            width < 0 || height < 0 || width + height == 0) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Invalid size: " + words[1] + ", expected e.g. 640x480 or 640x");
+        }
+        Transform::Filter filter = Transform::Filter::AREA;
+        if (arguments > 1 && !Transform::parseFilter(words[2], filter)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Unknown filter: " + words[2]);
+        }
+        cv::Size size = Transform::fitSize(state.color.size(), cv::Size(width, height));
+        if (!transformImage(state, [&](const cv::Mat& image) { return Transform::resizeImage(image, size, filter); })) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Could not resize to " + words[1]);
+        }
+    }
+    else if (command == "crop") {
+        if (!expect(1, 1, "crop <x>,<y>,<w>,<h>") || !needImage()) return false;
+        cv::Rect rect;
+        if (std::sscanf(words[1].c_str(), "%d,%d,%d,%d", &rect.x, &rect.y, &rect.width, &rect.height) != 4) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Invalid rectangle: " + words[1]);
+        }
+        if (!transformImage(state, [&](const cv::Mat& image) { return Transform::cropImage(image, rect); })) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Crop " + words[1] + " is outside the image");
+        }
+    }
+    else if (command == "rotate") {
+        if (!expect(1, 1, "rotate <degrees>") || !needImage()) return false;
+        int degrees = 0;
+        if (std::sscanf(words[1].c_str(), "%d", &degrees) != 1 || degrees % 90 != 0) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Rotation must be a multiple of 90 degrees");
+        }
+        transformImage(state, [&](const cv::Mat& image) { return Transform::rotateImage(image, degrees); });
+    }
+    else if (command == "flip") {
+        if (!expect(1, 1, "flip <horizontal|vertical>") || !needImage()) return false;
+        if (words[1] != "horizontal" && words[1] != "vertical") {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Unknown flip: " + words[1] + ", expected horizontal or vertical");
+        }
+        auto axis = words[1] == "horizontal" ? Transform::FlipAxis::HORIZONTAL : Transform::FlipAxis::VERTICAL;
+        transformImage(state, [&](const cv::Mat& image) { return Transform::flipImage(image, axis); });
+    }
+    else if (command == "descreen") {
+        if (!expect(0, 2, "descreen [method] [period]") || !needImage()) return false;
+        Descreen::Options options;
+        if (arguments > 0 && !Descreen::parseMethod(words[1], options.method)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Unknown descreen method: " + words[1]);
+        }
+        if (arguments > 1 && (std::sscanf(words[2].c_str(), "%f", &options.period) != 1 || options.period <= 0.0f)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Invalid period: " + words[2]);
+        }
+        state.color = Descreen::reconstruct(state.color, options, &options.period);
+        if (!state.precise.empty()) state.precise = Descreen::reconstruct(state.precise, options);
+    }
+    else if (command == "dither") {
+        if (!expect(0, 0, "dither") || !needImage()) return false;
+        cv::Mat input = state.precise.empty() ? state.color : state.precise;
+        ImageIO::applyMatte(input, state.alpha, ImageIO::ExportOptions());
+        state.color = Dithering::ditherImage(input, state.params);
+        state.precise = cv::Mat();
+    }
+    else if (command == "save") {
+        if (!expect(1, 1, "save <file>") || !needImage()) return false;
+        const std::string& path = words[1];
+        std::error_code ec;
+        if (!state.overwrite && std::filesystem::exists(path, ec)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, path + " already exists");
+        }
+        std::filesystem::path folder = std::filesystem::path(path).parent_path();
+        if (!folder.empty() && !std::filesystem::create_directories(folder, ec) && ec) {
+            return fail(ErrorCode::IO, "Could not create " + folder.string());
+        }
+
+        const auto& embedded = Embedded::getEmbeddedExtensions();
+        bool saved;
+        if (std::find(embedded.begin(), embedded.end(), ImageIO::getExtension(path)) != embedded.end()) {
+            saved = Embedded::exportEmbedded(state.color, path, Embedded::Options());
+        } else {
+            saved = ImageIO::saveImage(path, state.color, state.alpha, ImageIO::ExportOptions());
+        }
+        if (!saved) {
+            Errors::Error failure = ImageIO::describeSaveFailure(path);
+            return fail(failure.code, path + ": " + failure.message);
+        }
+        if (state.log) state.log("Saved " + path);
+    }
+    else if (command == "set") {
+        if (!expect(2, 2, "set <setting> <value>")) return false;
+        std::string message;
+        if (!Settings::setField(state.params, words[1], words[2], &message)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, message);
+        }
+    }
+    else if (command == "settings") {
+        if (!expect(1, 1, "settings <file.json>")) return false;
+        std::string message;
+        if (!Settings::loadSettings(words[1], state.params, &message)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, words[1] + ": " + message);
+        }
+    }
+    else if (command == "preset") {
+        if (!expect(1, 1, "preset <name>")) return false;
+        std::string message;
+        if (!Settings::loadPreset(words[1], state.params, &message)) {
+            return fail(ErrorCode::NOT_FOUND, message);
+        }
+    }
+    else if (command == "palette") {
+        if (!expect(1, 1, "palette <file>")) return false;
+        Errors::Error failure;
+        if (!PaletteFile::loadPalette(words[1], state.params.customPalette, &failure)) {
+            return fail(failure.code, words[1] + ": " + failure.message);
+        }
+        state.params.paletteMode = Dithering::PaletteMode::CUSTOM;
+    }
+    else if (command == "lospec") {
+        if (!expect(1, 1, "lospec <name>")) return false;
+        Lospec::PaletteInfo palette;
+        Errors::Error failure;
+        if (!Lospec::downloadPalette(words[1], palette, nullptr, &failure)) {
+            return fail(failure.code, failure.message);
+        }
+        state.params.customPalette = palette.colors;
+        state.params.paletteMode = Dithering::PaletteMode::CUSTOM;
+    }
+    else if (command == "device") {
+        if (!expect(1, 1, "device <id>")) return false;
+        const Devices::DeviceInfo* device = Devices::findDevice(words[1]);
+        if (!device) return fail(ErrorCode::INVALID_ARGUMENT, "Unknown device: " + words[1]);
+        Devices::applyDevice(*device, state.params);
+        if (!state.color.empty()) {
+            transformImage(state, [&](const cv::Mat& image) { return Devices::fitToScreen(image, *device); });
+        }
+    }
+    else if (command == "reset") {
+        if (!expect(0, 0, "reset")) return false;
+        state.params = state.initial;
+    }
+    else if (command == "let") {
+        if (!expect(2, 2, "let <name> <value>")) return false;
+        state.variables[words[1]] = words[2];
+    }
+    else if (command == "print") {
+        std::string text;
+        for (size_t i = 1; i < words.size(); ++i) text += (i > 1 ? " " : "") + words[i];
+        if (state.log) state.log(text);
+    }
+    else {
+        return fail(ErrorCode::INVALID_ARGUMENT, "Unknown command: " + command);
+    }
+    return true;
+}
+
+// Run lines [begin, end). Errors are returned with the script name and line number
+static bool runLines(const std::vector<Line>& lines, size_t begin, size_t end, State& state, Errors::Error& error) {
+    for (size_t i = begin; i < end; ++i) {
+        const Line& line = lines[i];
+        const bool loop = line.words[0] == "for";
+        std::vector<std::string> words(line.words.size());
+        std::string message;
+        for (size_t w = 0; w < words.size() && message.empty(); ++w) {
+            // The loop variable is set per file, so it isn't expanded on the for line itself
+            if (loop && w == 1) {
+                words[w] = line.words[w];
+            } else {
+                expand(line.words[w], state, words[w], message);
+            }
+        }
+
+        std::vector<std::string> files;
+        if (!message.empty()) {
+            error = {Errors::ErrorCode::INVALID_ARGUMENT, message};
+        } else if (loop && (words.size() != 4 || words[2] != "in")) {
+            error = {Errors::ErrorCode::INVALID_ARGUMENT, "Usage: for <name> in <files>"};
+        } else if (loop && (files = listFiles(words[3])).empty()) {
+            error = {Errors::ErrorCode::NOT_FOUND, "No files match " + words[3]};
+        } else if (!loop) {
+            try {
+                runCommand(words, state, error);
+            } catch (const std::exception& e) {
+                error = Errors::fromException(e);
+            }
+        }
+        if (!error.ok()) {
+            error.message = state.name + ":" + std::to_string(line.number) + ": " + error.message;
+            return false;
+        }
+
+        if (loop) {
+            for (const auto& file : files) {
+                setFileVariable(state, words[1], file);
+                if (!runLines(lines, i + 1, line.end, state, error)) return false;
+            }
+            i = line.end;
+        }
+    }
+    return true;
+}
+
+bool runScriptText(const std::string& text, const std::string& name, const std::vector<std::string>& args,
+                   const Dithering::Parameters& params, LogCallback log, Errors::Error* error, bool overwrite) {
+    auto fail = [&](int number, const std::string& message) {
+        if (error) *error = {Errors::ErrorCode::INVALID_ARGUMENT, name + ":" + std::to_string(number) + ": " + message};
+        return false;
+    };
+
+    // Split into lines and pair each for with its end
+    std::vector<Line> lines;
+    std::vector<size_t> open;
+    std::istringstream stream(text);
+    std::string content;
+    for (int number = 1; std::getline(stream, content); ++number) {
+        Line line;
+        line.number = number;
+        std::string message;
+        if (!splitWords(content, line.words, message)) return fail(number, message);
+        if (line.words.empty()) continue;
+
+        if (line.words[0] == "for") {
+            open.push_back(lines.size());
+        } else if (line.words[0] == "end") {
+            if (line.words.size() > 1) return fail(number, "Unexpected text after end");
+            if (open.empty()) return fail(number, "end without for");
+            lines[open.back()].end = lines.size();
+            open.pop_back();
+        }
+        lines.push_back(line);
+    }
+    if (!open.empty()) return fail(lines[open.back()].number, "for without end");
+
+    State state;
+    state.name = name;
+    state.initial = params;
+    state.params = params;
+    state.log = log;
+    state.overwrite = overwrite;
+    for (size_t i = 0; i < args.size(); ++i) state.variables[std::to_string(i + 1)] = args[i];
+
+    Errors::Error failure;
+    if (!runLines(lines, 0, lines.size(), state, failure)) {
+        if (error) *error = failure;
+        return false;
+    }
+    return true;
+}
+
+bool runScript(const std::string& path, const std::vector<std::string>& args, const Dithering::Parameters& params,
+               LogCallback log, Errors::Error* error) {
+    std::ifstream file(path, std::ios::binary);
+    if (!file) {
+        std::error_code ec;
+        bool exists = std::filesystem::exists(path, ec);
+        if (error) *error = {exists ? Errors::ErrorCode::IO : Errors::ErrorCode::NOT_FOUND, "Could not read " + path};
+        return false;
+    }
+    std::stringstream text;
+    text << file.rdbuf();
+    return runScriptText(text.str(), std::filesystem::path(path).filename().string(), args, params, log, error);
+}
+
+} // namespace Script
@@ -0,0 +1,50 @@
+#pragma once
+
+#include <functional>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+
+namespace Script {
+
+// Pipeline scripts: one command per line, words separated by spaces, "quoted"
+// words may contain spaces and lines starting with # are comments. $name and
+// ${name} are replaced by variables: $1, $2, ... are the script's arguments,
+// let sets one, and a for loop sets its variable to each file in turn, with
+// ${var.name}, ${var.dir} and ${var.ext} for the file's parts. For example:
+//
+//   preset "Dusk"
+//   set colorMode multi-ink
+//   set inks #222034,#45283c
+//   for photo in $1/*.jpg
+//       load $photo
+//       resize 320x0
+//       dither
+//       save out/${photo.name}.png
+//       save out/${photo.name}.h
+//   end
+struct CommandInfo {
+    std::string usage;
+    std::string description;
+};
+
+// The commands, in the order they are documented
+const std::vector<CommandInfo>& getCommands();
+
+// Messages from print and from each saved file
+using LogCallback = std::function<void(const std::string&)>;
+
+// Run a script file, starting from params. Stops at the first failing command;
+// error then says which line it was and why
+bool runScript(const std::string& path, const std::vector<std::string>& args, const Dithering::Parameters& params,
+               LogCallback log = nullptr, Errors::Error* error = nullptr);
+
+// Same for script text; name is used in error messages. Without overwrite,
+// save fails instead of replacing an existing file
+bool runScriptText(const std::string& text, const std::string& name, const std::vector<std::string>& args,
+                   const Dithering::Parameters& params, LogCallback log = nullptr, Errors::Error* error = nullptr,
+                   bool overwrite = true);
+
+} // namespace Script
@@ -0,0 +1,1091 @@
+#include "settings.h"
+#include "json.h"
+#include "matrices.h"
+#include "platform.h"
+#include <algorithm>
+#include <cctype>
+#include <cmath>
+#include <cstdio>
+#include <cstdlib>
+#include <filesystem>
+#include <fstream>
+#include <functional>
+#include <map>
+#include <sstream>
+#include <utility>
+#include <vector>
+
+namespace Settings {
+
+using Dithering::Parameters;
+
+// --- Settings fields ---
+
+// One setting: its schema, and how it maps to and from Parameters. Read errors
+// are returned without the field name, which the caller adds
+struct Field {
+    std::string name;
+    Json schema;
+    std::function<Json(const Parameters&)> write;
+    std::function<bool(const Json&, Parameters&, std::string&)> read;
+};
+
+static std::string formatNumber(double value) {
+    std::string out;
+    writeJson(Json::of(value), out, 0);
+    return out;
+}
+
+static Json rangeSchema(const char* type, const char* description, double minimum, double maximum) {
+    return Json::object()
+        .set("type", Json::of(type))
+        .set("description", Json::of(description))
+        .set("minimum", Json::of(minimum))
+        .set("maximum", Json::of(maximum));
+}
+
+static bool readNumber(const Json& value, bool integer, double minimum, double maximum,
+                       double& out, std::string& error) {
+    if (integer ? !value.isInteger() : !value.isNumber()) {
+        error = integer ? "must be a whole number" : "must be a number";
+        return false;
+    }
+    if (!std::isfinite(value.number) || value.number < minimum || value.number > maximum) {
+        error = "must be between " + formatNumber(minimum) + " and " + formatNumber(maximum);
+        return false;
+    }
+    out = value.number;
+    return true;
+}
+
+static Field floatField(const char* name, const char* description, double minimum, double maximum,
+                        float Parameters::*member) {
+    return {name, rangeSchema("number", description, minimum, maximum),
+        [member](const Parameters& params) { return Json::of(static_cast<double>(params.*member)); },
+        [member, minimum, maximum](const Json& value, Parameters& params, std::string& error) {
+            double number;
+            if (!readNumber(value, false, minimum, maximum, number, error)) return false;
+            params.*member = static_cast<float>(number);
+            return true;
+        }};
+}
+
+static Field intField(const char* name, const char* description, int minimum, int maximum,
+                      int Parameters::*member) {
+    return {name, rangeSchema("integer", description, minimum, maximum),
+        [member](const Parameters& params) { return Json::of(static_cast<double>(params.*member)); },
+        [member, minimum, maximum](const Json& value, Parameters& params, std::string& error) {
+            double number;
+            if (!readNumber(value, true, minimum, maximum, number, error)) return false;
+            params.*member = static_cast<int>(number);
+            return true;
+        }};
+}
+
+static Field boolField(const char* name, const char* description, bool Parameters::*member) {
+    return {name, Json::object().set("type", Json::of("boolean")).set("description", Json::of(description)),
+        [member](const Parameters& params) { return Json::of(params.*member); },
+        [member](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::BOOLEAN) {
+                error = "must be true or false";
+                return false;
+            }
+            params.*member = value.boolean;
+            return true;
+        }};
+}
+
+static Json enumSchema(const char* description, const std::vector<std::string>& ids) {
+    Json values = Json::array();
+    for (const auto& id : ids) values.push(Json::of(id));
+    return Json::object()
+        .set("type", Json::of("string"))
+        .set("description", Json::of(description))
+        .set("enum", values);
+}
+
+static bool readEnum(const Json& value, const std::vector<std::string>& ids, int& index, std::string& error) {
+    for (size_t i = 0; value.type == Json::Type::STRING && i < ids.size(); ++i) {
+        if (ids[i] == value.string) {
+            index = static_cast<int>(i);
+            return true;
+        }
+    }
+    error = "must be one of:";
+    for (const auto& id : ids) error += " " + id;
+    return false;
+}
+
+// Enum values are stored by name, in the enum's declaration order
+template <typename E>
+static Field enumField(const char* name, const char* description, const std::vector<std::string>& ids,
+                       E Parameters::*member) {
+    return {name, enumSchema(description, ids),
+        [member, ids](const Parameters& params) { return Json::of(ids[static_cast<int>(params.*member)]); },
+        [member, ids](const Json& value, Parameters& params, std::string& error) {
+            int index;
+            if (!readEnum(value, ids, index, error)) return false;
+            params.*member = static_cast<E>(index);
+            return true;
+        }};
+}
+
+// Colors are "#rrggbb" strings; Parameters keep them as BGR
+static Json colorToJson(const cv::Vec3b& color) {
+    char hex[8];
+    std::snprintf(hex, sizeof(hex), "#%02x%02x%02x", color[2], color[1], color[0]);
+    return Json::of(hex);
+}
+
+static bool readColor(const Json& value, cv::Vec3b& color, std::string& error) {
+    std::string hex = value.string;
+    if (!hex.empty() && hex[0] == '#') hex = hex.substr(1);
+    char* end = nullptr;
+    unsigned long rgb = std::strtoul(hex.c_str(), &end, 16);
+    if (value.type != Json::Type::STRING || hex.size() != 6 || end != hex.c_str() + 6) {
+        error = "must be a color like \"#1d2b53\"";
+        return false;
+    }
+    color = cv::Vec3b(rgb & 0xFF, (rgb >> 8) & 0xFF, (rgb >> 16) & 0xFF);
+    return true;
+}
+
+static Json colorSchema(const char* description) {
+    return Json::object()
+        .set("$ref", Json::of("#/$defs/color"))
+        .set("description", Json::of(description));
+}
+
+static Field colorField(const char* name, const char* description, cv::Vec3b Parameters::*member) {
+    return {name, colorSchema(description),
+        [member](const Parameters& params) { return colorToJson(params.*member); },
+        [member](const Json& value, Parameters& params, std::string& error) {
+            return readColor(value, params.*member, error);
+        }};
+}
+
+static Field colorListField(const char* name, const char* description, int maxItems,
+                            std::vector<cv::Vec3b> Parameters::*member) {
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of(description))
+        .set("items", Json::object().set("$ref", Json::of("#/$defs/color")));
+    if (maxItems > 0) schema.set("maxItems", Json::of(static_cast<double>(maxItems)));
+
+    return {name, schema,
+        [member](const Parameters& params) {
+            Json colors = Json::array();
+            for (const auto& color : params.*member) colors.push(colorToJson(color));
+            return colors;
+        },
+        [member, maxItems](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::ARRAY ||
+                (maxItems > 0 && value.items.size() > static_cast<size_t>(maxItems))) {
+                error = maxItems > 0 ? "must be a list of up to " + std::to_string(maxItems) + " colors"
+                                     : "must be a list of colors";
+                return false;
+            }
+            std::vector<cv::Vec3b> colors(value.items.size());
+            for (size_t i = 0; i < colors.size(); ++i) {
+                if (!readColor(value.items[i], colors[i], error)) return false;
+            }
+            params.*member = colors;
+            return true;
+        }};
+}
+
+static const std::vector<std::string>& algorithmIds() {
+    // Same names as the CLI's --algorithm, in Algorithm enum order
+    static const std::vector<std::string> ids = {
+        "floyd-steinberg", "atkinson", "jarvis", "stucki", "burkes", "sierra", "sierra-two",
+        "sierra-lite", "bayer-2x2", "bayer-4x4", "bayer-8x8", "bayer-16x16", "blue-noise",
+        "white-noise", "random", "pattern", "dot-diffusion", "riemersma", "gradient", "variable",
+        "ostromoukhov", "fan", "shiau-fan", "steven-pigeon", "matrix", "ign", "halftone", "threshold",
+        "sauvola", "niblack", "bradley", "structure", "posterize",
+        "stipple", "kernel"
+    };
+    return ids;
+}
+
+static const std::vector<std::string>& paletteModeIds() {
+    static const std::vector<std::string> ids = {
+        "monochrome", "gray4", "gray8", "gray16", "cga", "ega", "vga", "gameboy", "pico8", "custom", "adaptive"
+    };
+    return ids;
+}
+
+static Field matrixField() {
+    std::vector<std::string> ids;
+    for (const auto& info : Matrices::getMatrixLibrary()) ids.push_back(info.id);
+    return {"matrix", enumSchema("Library matrix used by the \"matrix\" algorithm", ids),
+        [](const Parameters& params) { return Json::of(params.matrix); },
+        [ids](const Json& value, Parameters& params, std::string& error) {
+            int index;
+            if (!readEnum(value, ids, index, error)) return false;
+            params.matrix = ids[index];
+            return true;
+        }};
+}
+
+// Plugins may not be installed where the settings are loaded; an unknown id
+// dithers with the built-in algorithm instead
+static Field pluginField() {
+    Json schema = Json::object()
+        .set("type", Json::of("string"))
+        .set("description", Json::of("Plugin algorithm id used instead of algorithm (empty = none)"));
+    return {"plugin", schema,
+        [](const Parameters& params) { return Json::of(params.plugin); },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::STRING) {
+                error = "must be a string";
+                return false;
+            }
+            params.plugin = value.string;
+            return true;
+        }};
+}
+
+// Like plugins, the texture file may be missing where the settings are loaded;
+// blue noise then falls back to the generated texture
+static Field noiseTextureField() {
+    Json schema = Json::object()
+        .set("type", Json::of("string"))
+        .set("description", Json::of("Noise texture file tiled by blue-noise (empty = generated)"));
+    return {"noiseTexture", schema,
+        [](const Parameters& params) { return Json::of(params.noiseTexture); },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::STRING) {
+                error = "must be a string";
+                return false;
+            }
+            params.noiseTexture = value.string;
+            return true;
+        }};
+}
+
+static Field pluginOptionsField() {
+    Json schema = Json::object()
+        .set("type", Json::of("object"))
+        .set("description", Json::of("Options passed through to the plugin algorithm"))
+        .set("additionalProperties", Json::object().set("type", Json::of("string")));
+    return {"pluginOptions", schema,
+        [](const Parameters& params) {
+            Json options = Json::object();
+            for (const auto& option : params.pluginOptions) options.set(option.first, Json::of(option.second));
+            return options;
+        },
+        [](const Json& value, Parameters& params, std::string& error) {
+            std::map<std::string, std::string> options;
+            for (const auto& member : value.members) {
+                if (member.second.type != Json::Type::STRING) break;
+                options[member.first] = member.second.string;
+            }
+            if (value.type != Json::Type::OBJECT || options.size() != value.members.size()) {
+                error = "must be an object of string values";
+                return false;
+            }
+            params.pluginOptions = options;
+            return true;
+        }};
+}
+
+static const std::vector<Field>& getFields();
+
+// A stage's own dither settings are a nested settings object, without stages
+// of their own. Unknown fields are skipped as in a document
+static Json stageSettingsToJson(const Parameters& params) {
+    Json settings = Json::object();
+    for (const auto& field : getFields()) {
+        if (field.name != "stages") settings.set(field.name, field.write(params));
+    }
+    return settings;
+}
+
+static bool readStageSettings(const Json& value, Parameters& params, std::string& error) {
+    if (value.type != Json::Type::OBJECT) {
+        error = "must be a settings object";
+        return false;
+    }
+    const auto& fields = getFields();
+    for (const auto& [key, member] : value.members) {
+        auto field = std::find_if(fields.begin(), fields.end(), [&key](const Field& f) { return f.name == key; });
+        if (field == fields.end() || field->name == "stages") continue;
+        std::string message;
+        if (!field->read(member, params, message)) {
+            error = "setting \"" + key + "\" " + message;
+            return false;
+        }
+    }
+    return true;
+}
+
+static Field stagesField() {
+    const std::vector<std::string> types = {"dither", "blur", "sharpen"};
+    Json stage = Json::object()
+        .set("type", Json::of("object"))
+        .set("properties", Json::object()
+            .set("type", enumSchema("What the stage does", types))
+            .set("amount", rangeSchema("number", "Blur sigma in pixels or sharpen strength", 0.0, 20.0))
+            .set("mix", rangeSchema("number", "Share of the stage's output blended over its input", 0.0, 1.0))
+            .set("settings", Json::object().set("$ref", Json::of("#/$defs/settings"))))
+        .set("required", Json::array().push(Json::of("type")))
+        .set("additionalProperties", Json::of(false));
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of("Passes run in order on the dithered result. Dither stages without "
+                                     "settings use the main ones"))
+        .set("items", stage)
+        .set("maxItems", Json::of(16.0));
+
+    return {"stages", schema,
+        [types](const Parameters& params) {
+            Json stages = Json::array();
+            for (const auto& stage : params.stages) {
+                Json item = Json::object()
+                    .set("type", Json::of(types[static_cast<int>(stage.type)]))
+                    .set("amount", Json::of(static_cast<double>(stage.amount)))
+                    .set("mix", Json::of(static_cast<double>(stage.mix)));
+                if (stage.params) item.set("settings", stageSettingsToJson(*stage.params));
+                stages.push(item);
+            }
+            return stages;
+        },
+        [types](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::ARRAY || value.items.size() > 16) {
+                error = "must be a list of up to 16 stages";
+                return false;
+            }
+            std::vector<Dithering::Stage> stages;
+            for (size_t i = 0; i < value.items.size(); ++i) {
+                const Json& item = value.items[i];
+                std::string where = "stage " + std::to_string(i + 1) + ": ";
+                const Json* type = item.find("type");
+                int index;
+                if (item.type != Json::Type::OBJECT || !type || !readEnum(*type, types, index, error)) {
+                    error = where + (type ? "type " + error : "must be an object with a type");
+                    return false;
+                }
+
+                Dithering::Stage stage;
+                stage.type = static_cast<Dithering::StageType>(index);
+                double number;
+                if (const Json* amount = item.find("amount")) {
+                    if (!readNumber(*amount, false, 0.0, 20.0, number, error)) {
+                        error = where + "amount " + error;
+                        return false;
+                    }
+                    stage.amount = static_cast<float>(number);
+                }
+                if (const Json* mix = item.find("mix")) {
+                    if (!readNumber(*mix, false, 0.0, 1.0, number, error)) {
+                        error = where + "mix " + error;
+                        return false;
+                    }
+                    stage.mix = static_cast<float>(number);
+                }
+                if (const Json* settings = item.find("settings")) {
+                    auto stageParams = std::make_shared<Parameters>();
+                    if (!readStageSettings(*settings, *stageParams, error)) {
+                        error = where + error;
+                        return false;
+                    }
+                    stage.params = stageParams;
+                }
+                stages.push_back(stage);
+            }
+            params.stages = stages;
+            return true;
+        }};
+}
+
+static Field patternMatrixField() {
+    Json row = Json::object()
+        .set("type", Json::of("array"))
+        .set("items", Json::object().set("type", Json::of("number")));
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of("Thresholds for the \"pattern\" algorithm, one array per row "
+                                     "(empty = built-in 4x4). Values outside 0-1 are rescaled"))
+        .set("items", row)
+        .set("maxItems", Json::of(256.0));
+
+    return {"patternMatrix", schema,
+        [](const Parameters& params) {
+            Json rows = Json::array();
+            for (int y = 0; y < params.patternMatrix.rows; ++y) {
+                Json row = Json::array();
+                for (int x = 0; x < params.patternMatrix.cols; ++x) {
+                    row.push(Json::of(static_cast<double>(params.patternMatrix.at<float>(y, x))));
+                }
+                rows.push(row);
+            }
+            return rows;
+        },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type == Json::Type::ARRAY && value.items.empty()) {
+                params.patternMatrix = cv::Mat();
+                return true;
+            }
+            // Same rules as matrices typed in the GUI or passed to --pattern-matrix
+            std::string text;
+            writeJson(value, text, 0);
+            cv::Mat matrix;
+            if (value.type != Json::Type::ARRAY || !Matrices::parseMatrix(text, matrix)) {
+                error = "must be a rectangular array of number rows, up to 256x256";
+                return false;
+            }
+            params.patternMatrix = matrix;
+            return true;
+        }};
+}
+
+static Field diffusionKernelField() {
+    Json triple = Json::object()
+        .set("type", Json::of("array"))
+        .set("items", Json::object().set("type", Json::of("number")))
+        .set("minItems", Json::of(3.0))
+        .set("maxItems", Json::of(3.0));
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of("Error weights for the \"kernel\" algorithm as [dx, dy, weight], "
+                                     "dx right and dy down of the current pixel (empty = Floyd-Steinberg)"))
+        .set("items", triple)
+        .set("maxItems", Json::of(static_cast<double>(Dithering::MAX_KERNEL_WEIGHTS)));
+
+    return {"diffusionKernel", schema,
+        [](const Parameters& params) {
+            Json entries = Json::array();
+            for (const auto& entry : params.diffusionKernel) {
+                entries.push(Json::array()
+                    .push(Json::of(static_cast<double>(entry.dx)))
+                    .push(Json::of(static_cast<double>(entry.dy)))
+                    .push(Json::of(static_cast<double>(entry.weight))));
+            }
+            return entries;
+        },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type == Json::Type::ARRAY && value.items.empty()) {
+                params.diffusionKernel.clear();
+                return true;
+            }
+            // Same rules as kernels typed in the GUI or passed to --kernel; whether
+            // the sum is too large depends on normalizeKernel, checked when dithering
+            std::string text;
+            writeJson(value, text, 0);
+            std::vector<Dithering::KernelWeight> kernel;
+            if (value.type != Json::Type::ARRAY || !Dithering::parseKernel(text, kernel, error) ||
+                !Dithering::validateKernel(kernel, true, error)) {
+                return false;
+            }
+            params.diffusionKernel = kernel;
+            return true;
+        }};
+}
+
+static Field toneCurveField() {
+    Json pair = Json::object()
+        .set("type", Json::of("array"))
+        .set("items", Json::object().set("type", Json::of("number")).set("minimum", Json::of(0.0))
+                                    .set("maximum", Json::of(1.0)))
+        .set("minItems", Json::of(2.0))
+        .set("maxItems", Json::of(2.0));
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of("Tone curve as [input, output] points in 0-1, applied after the levels "
+                                     "(empty = none)"))
+        .set("items", pair)
+        .set("maxItems", Json::of(static_cast<double>(Dithering::MAX_CURVE_POINTS)));
+
+    return {"toneCurve", schema,
+        [](const Parameters& params) {
+            Json points = Json::array();
+            for (const auto& point : params.toneCurve) {
+                points.push(Json::array()
+                    .push(Json::of(static_cast<double>(point.x)))
+                    .push(Json::of(static_cast<double>(point.y))));
+            }
+            return points;
+        },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type == Json::Type::ARRAY && value.items.empty()) {
+                params.toneCurve.clear();
+                return true;
+            }
+            // Same rules as curves edited in the GUI or passed to --curve
+            std::string text;
+            writeJson(value, text, 0);
+            std::vector<cv::Point2f> points;
+            if (value.type != Json::Type::ARRAY || !Dithering::parseToneCurve(text, points, error)) return false;
+            params.toneCurve = points;
+            return true;
+        }};
+}
+
+static Field seedField() {
+    return {"seed", rangeSchema("integer", "Random seed", 0, 4294967295.0),
+        [](const Parameters& params) { return Json::of(static_cast<double>(params.seed)); },
+        [](const Json& value, Parameters& params, std::string& error) {
+            double number;
+            if (!readNumber(value, true, 0, 4294967295.0, number, error)) return false;
+            params.seed = static_cast<unsigned int>(number);
+            return true;
+        }};
+}
+
+static Field screenAnglesField() {
+    // Named by ink, stored per B, G, R channel
+    const char* inks[3] = {"yellow", "magenta", "cyan"};
+    Json properties = Json::object();
+    for (int c = 2; c >= 0; --c) {
+        properties.set(inks[c], rangeSchema("number", "Screen angle in degrees", -360.0, 360.0));
+    }
+    Json schema = Json::object()
+        .set("type", Json::of("object"))
+        .set("description", Json::of("Halftone screen angle of each color channel"))
+        .set("properties", properties)
+        .set("additionalProperties", Json::of(false));
+
+    return {"screenAngles", schema,
+        [inks](const Parameters& params) {
+            Json angles = Json::object();
+            for (int c = 2; c >= 0; --c) angles.set(inks[c], Json::of(static_cast<double>(params.screenAngles[c])));
+            return angles;
+        },
+        [inks](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::OBJECT) {
+                error = "must be an object with cyan, magenta and yellow angles";
+                return false;
+            }
+            cv::Vec3f angles = params.screenAngles;
+            for (const auto& [key, angle] : value.members) {
+                int c = 0;
+                while (c < 3 && key != inks[c]) c++;
+                double number;
+                if (c == 3) {
+                    error = "has unknown ink \"" + key + "\"";
+                    return false;
+                }
+                if (!readNumber(angle, false, -360.0, 360.0, number, error)) {
+                    error = key + " " + error;
+                    return false;
+                }
+                angles[c] = static_cast<float>(number);
+            }
+            params.screenAngles = angles;
+            return true;
+        }};
+}
+
+static Field grayWeightsField() {
+    // Named by channel, stored per B, G, R
+    const char* channels[3] = {"blue", "green", "red"};
+    Json properties = Json::object();
+    for (int c = 2; c >= 0; --c) {
+        properties.set(channels[c], rangeSchema("number", "Weight of the channel, scaled with the others to sum to 1",
+                                                0.0, 1000.0));
+    }
+    Json schema = Json::object()
+        .set("type", Json::of("object"))
+        .set("description", Json::of("Channel weights for the custom gray conversion"))
+        .set("properties", properties)
+        .set("additionalProperties", Json::of(false));
+
+    return {"grayWeights", schema,
+        [channels](const Parameters& params) {
+            Json weights = Json::object();
+            for (int c = 2; c >= 0; --c) weights.set(channels[c], Json::of(static_cast<double>(params.grayWeights[c])));
+            return weights;
+        },
+        [channels](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::OBJECT) {
+                error = "must be an object with red, green and blue weights";
+                return false;
+            }
+            cv::Vec3f weights = params.grayWeights;
+            for (const auto& [key, weight] : value.members) {
+                int c = 0;
+                while (c < 3 && key != channels[c]) c++;
+                double number;
+                if (c == 3) {
+                    error = "has unknown channel \"" + key + "\"";
+                    return false;
+                }
+                if (!readNumber(weight, false, 0.0, 1000.0, number, error)) {
+                    error = key + " " + error;
+                    return false;
+                }
+                weights[c] = static_cast<float>(number);
+            }
+            params.grayWeights = weights;
+            return true;
+        }};
+}
+
+static Field channelOverridesField() {
+    Json properties = Json::object()
+        .set("enabled", Json::object().set("type", Json::of("boolean")))
+        .set("algorithm", enumSchema("Algorithm for this plane", algorithmIds()))
+        .set("strength", rangeSchema("number", "Error diffusion strength", 0.0, 2.0))
+        .set("threshold", rangeSchema("number", "Rounding point between levels (lower = brighter)", 0.0, 1.0));
+    Json item = Json::object()
+        .set("type", Json::of("object"))
+        .set("properties", properties)
+        .set("additionalProperties", Json::of(false));
+    Json schema = Json::object()
+        .set("type", Json::of("array"))
+        .set("description", Json::of("Per-plane overrides, in channelSpace order (B, G, R or Y, Cr, Cb)"))
+        .set("items", item)
+        .set("minItems", Json::of(3.0))
+        .set("maxItems", Json::of(3.0));
+
+    return {"channelOverrides", schema,
+        [](const Parameters& params) {
+            Json channels = Json::array();
+            for (const auto& channel : params.channelOverrides) {
+                channels.push(Json::object()
+                    .set("enabled", Json::of(channel.enabled))
+                    .set("algorithm", Json::of(algorithmIds()[static_cast<int>(channel.algorithm)]))
+                    .set("strength", Json::of(static_cast<double>(channel.strength)))
+                    .set("threshold", Json::of(static_cast<double>(channel.threshold))));
+            }
+            return channels;
+        },
+        [](const Json& value, Parameters& params, std::string& error) {
+            if (value.type != Json::Type::ARRAY || value.items.size() != 3) {
+                error = "must be a list of 3 channel objects";
+                return false;
+            }
+            auto channels = params.channelOverrides;
+            for (size_t c = 0; c < 3; ++c) {
+                for (const auto& [key, member] : value.items[c].members) {
+                    double number;
+                    int index;
+                    bool ok = false;
+                    if (key == "enabled") {
+                        ok = member.type == Json::Type::BOOLEAN;
+                        if (ok) channels[c].enabled = member.boolean;
+                        else error = "must be true or false";
+                    } else if (key == "algorithm") {
+                        ok = readEnum(member, algorithmIds(), index, error);
+                        if (ok) channels[c].algorithm = static_cast<Dithering::Algorithm>(index);
+                    } else if (key == "strength") {
+                        ok = readNumber(member, false, 0.0, 2.0, number, error);
+                        if (ok) channels[c].strength = static_cast<float>(number);
+                    } else if (key == "threshold") {
+                        ok = readNumber(member, false, 0.0, 1.0, number, error);
+                        if (ok) channels[c].threshold = static_cast<float>(number);
+                    } else {
+                        error = "is an unknown channel setting";
+                    }
+                    if (!ok) {
+                        error = "[" + std::to_string(c) + "]." + key + " " + error;
+                        return false;
+                    }
+                }
+            }
+            params.channelOverrides = channels;
+            return true;
+        }};
+}
+
+// Every setting, in the order they're written
+static const std::vector<Field>& getFields() {
+    static const std::vector<Field> fields = {
+        enumField("algorithm", "Dithering algorithm", algorithmIds(), &Parameters::algorithm),
+        pluginField(),
+        pluginOptionsField(),
+        enumField("paletteMode", "Color palette", paletteModeIds(), &Parameters::paletteMode),
+        colorListField("customPalette", "Colors of the custom palette", 0, &Parameters::customPalette),
+        colorListField("protectedColors", "Palette colors used only where the image matches them exactly", 0,
+                       &Parameters::protectedColors),
+        intField("paletteSize", "Colors generated by the adaptive palette", 2, 256, &Parameters::paletteSize),
+        enumField("paletteMethod", "Adaptive palette generation", {"kmeans", "median-cut"},
+                  &Parameters::paletteMethod),
+        enumField("rounding", "How pixels are rounded to palette colors",
+                  {"nearest", "floor", "ceil", "stochastic"}, &Parameters::rounding),
+        enumField("colorDistance", "Color difference used for matching",
+                  {"rgb", "weighted-rgb", "cie76", "ciede2000"}, &Parameters::colorDistance),
+        enumField("renderingIntent", "How the tonal range is mapped onto the palette",
+                  {"relative", "perceptual"}, &Parameters::renderingIntent),
+        enumField("workingSpace", "Color space quantization and error diffusion work in",
+                  {"rgb", "lab", "ycrcb", "hsl"}, &Parameters::workingSpace),
+        enumField("grayConversion", "How color becomes gray for grayscale and monochrome output",
+                  {"rec601", "rec709", "average", "red", "green", "blue", "custom"}, &Parameters::grayConversion),
+        grayWeightsField(),
+        colorField("inkColor", "Dark color of monochrome output", &Parameters::inkColor),
+        colorField("paperColor", "Light color of monochrome and multi-ink output", &Parameters::paperColor),
+        enumField("colorMode", "How color output is produced", {"rgb", "cmyk", "multi-ink", "pixel-format"},
+                  &Parameters::colorMode),
+        enumField("pixelFormat", "Display pixel format for the pixel-format color mode",
+                  {"rgb565", "rgb332", "rgba4444"}, &Parameters::pixelFormat),
+        colorListField("inks", "Spot inks for the multi-ink color mode", 3, &Parameters::inks),
+        floatField("strength", "Error diffusion strength", 0.0, 2.0, &Parameters::strength),
+        floatField("threshold", "Rounding point between palette levels", 0.0, 1.0, &Parameters::threshold),
+        boolField("autoThreshold", "Pick the threshold with Otsu's method", &Parameters::autoThreshold),
+        intField("localWindow", "Window size in pixels for local thresholding", 3, 255, &Parameters::localWindow),
+        floatField("localK", "Local threshold sensitivity", -1.0, 1.0, &Parameters::localK),
+        floatField("edgeSensitivity", "How strongly edges stop error in structure-aware diffusion", 0.0, 4.0,
+                   &Parameters::edgeSensitivity),
+        enumField("curve", "Pixel order for Riemersma", {"hilbert", "peano", "z-order"}, &Parameters::curve),
+        intField("riemersmaHistory", "Errors Riemersma remembers along the curve", 1, 256,
+                 &Parameters::riemersmaHistory),
+        floatField("riemersmaDecay", "Weight of the newest Riemersma error relative to the oldest", 1.0, 256.0,
+                   &Parameters::riemersmaDecay),
+        floatField("serpentine", "Serpentine scanning (0 = off, 1 = on)", 0.0, 1.0, &Parameters::serpentine),
+        enumField("errorEdge", "Where error diffused past the left or right edge goes",
+                  {"discard", "reflect", "wrap"}, &Parameters::errorEdge),
+        boolField("clampError", "Clamp pixel plus error to 0-255 before quantizing (false = let it ring)",
+                  &Parameters::clampError),
+        floatField("colorization", "Slight color tint", 0.0, 1.0, &Parameters::colorization),
+        intField("levels", "Levels per plane with channel overrides", 2, 256, &Parameters::levels),
+        enumField("channelSpace", "Planes the channel overrides apply to", {"bgr", "ycrcb"},
+                  &Parameters::channelSpace),
+        channelOverridesField(),
+        floatField("gamma", "Gamma correction", 0.1, 3.0, &Parameters::gamma),
+        floatField("levelsBlack", "Input level (0-1) that becomes black", 0.0, 1.0, &Parameters::levelsBlack),
+        floatField("levelsWhite", "Input level (0-1) that becomes white", 0.0, 1.0, &Parameters::levelsWhite),
+        floatField("levelsMidtone", "Levels midtone gamma (>1 lightens)", 0.1, 10.0, &Parameters::levelsMidtone),
+        toneCurveField(),
+        floatField("contrast", "Contrast", 0.0, 3.0, &Parameters::contrast),
+        floatField("brightness", "Brightness", -1.0, 1.0, &Parameters::brightness),
+        floatField("saturation", "Saturation", 0.0, 2.0, &Parameters::saturation),
+        floatField("exposure", "Exposure in stops", -4.0, 4.0, &Parameters::exposure),
+        enumField("denoise", "Noise filter before the other adjustments", {"none", "median", "bilateral"},
+                  &Parameters::denoise),
+        floatField("denoiseStrength", "Noise filter strength", 0.0, 1.0, &Parameters::denoiseStrength),
+        floatField("blurRadius", "Gaussian blur sigma in pixels", 0.0, 50.0, &Parameters::blurRadius),
+        floatField("sharpenAmount", "Unsharp mask strength", 0.0, 4.0, &Parameters::sharpenAmount),
+        floatField("sharpenRadius", "Unsharp mask sigma in pixels", 0.1, 50.0, &Parameters::sharpenRadius),
+        boolField("linearize", "Quantize and diffuse error in linear light", &Parameters::linearize),
+        floatField("clipBlack", "Minimum % of pixels forced to pure black", 0.0, 100.0, &Parameters::clipBlack),
+        floatField("clipWhite", "Minimum % of pixels forced to pure white", 0.0, 100.0, &Parameters::clipWhite),
+        floatField("highlightProtect", "Luma above which pixels stay pure white (1 = off)", 0.0, 1.0,
+                   &Parameters::highlightProtect),
+        floatField("shadowProtect", "Luma below which pixels stay pure black (0 = off)", 0.0, 1.0,
+                   &Parameters::shadowProtect),
+        floatField("dotGain", "Print dot gain in % at 50% ink, compensated before dithering (0 = off)", 0.0, 45.0,
+                   &Parameters::dotGain),
+        intField("bayerSize", "Bayer matrix size", 2, Dithering::MAX_BAYER_SIZE, &Parameters::bayerSize),
+        boolField("bayerClustered", "One round dot per Bayer matrix instead of dispersed pixels",
+                  &Parameters::bayerClustered),
+        floatField("ditherAngle", "Rotation of the Bayer, pattern and library matrices in degrees", -360.0, 360.0,
+                   &Parameters::ditherAngle),
+        matrixField(),
+        patternMatrixField(),
+        diffusionKernelField(),
+        boolField("normalizeKernel", "Scale the custom kernel's weights to sum to 1", &Parameters::normalizeKernel),
+        seedField(),
+        boolField("useBlueNoise", "Use blue noise for ordered dithering", &Parameters::useBlueNoise),
+        noiseTextureField(),
+        floatField("ditherScale", "Pixels per threshold matrix cell", 1.0, Dithering::MAX_PATTERN_SCALE,
+                   &Parameters::ditherScale),
+        intField("pixelate", "Dither at 1/N resolution, then scale up with nearest neighbor", 1, 64,
+                 &Parameters::pixelate),
+        floatField("blend", "Mix the adjusted source back in (0 = fully dithered, 1 = source)", 0.0, 1.0,
+                   &Parameters::blend),
+        enumField("backend", "Where ordered and threshold algorithms run", {"auto", "cpu", "gpu"},
+                  &Parameters::backend),
+        enumField("halftoneShape", "Halftone dot shape", {"circle", "diamond"}, &Parameters::halftoneShape),
+        floatField("halftoneCellSize", "Halftone cell size in pixels", 2.0, 256.0, &Parameters::halftoneCellSize),
+        floatField("screenLpi", "Screen ruling in lines per inch at printDpi for halftone and clustered Bayer, "
+                   "sets the cell size (0 = off)", 0.0, 600.0, &Parameters::screenLpi),
+        floatField("printDpi", "Image pixels per inch when printed, for screenLpi and print PDFs", 36.0, 2400.0,
+                   &Parameters::printDpi),
+        screenAnglesField(),
+        floatField("grayScreenAngle", "Halftone angle for grayscale output (K ink)", -360.0, 360.0,
+                   &Parameters::grayScreenAngle),
+        intField("stippleDots", "Number of stipple dots", 1, 200000, &Parameters::stippleDots),
+        floatField("stippleDotSize", "Stipple dot radius in pixels where the ink is solid", 0.5, 64.0,
+                   &Parameters::stippleDotSize),
+        intField("stippleIterations", "Stipple relaxation steps", 0, 500, &Parameters::stippleIterations),
+        intField("phaseX", "Threshold matrix horizontal offset in pixels", -4096, 4096, &Parameters::phaseX),
+        intField("phaseY", "Threshold matrix vertical offset in pixels", -4096, 4096, &Parameters::phaseY),
+        stagesField(),
+    };
+    return fields;
+}
+
+std::string getSettingsSchema() {
+    const Parameters defaults;
+    Json properties = Json::object();
+    for (const auto& field : getFields()) {
+        Json schema = field.schema;
+        schema.set("default", field.write(defaults));
+        properties.set(field.name, schema);
+    }
+
+    Json settings = Json::object()
+        .set("type", Json::of("object"))
+        .set("description", Json::of("Dithering settings. Missing fields take their default"))
+        .set("properties", properties)
+        .set("additionalProperties", Json::of(false));
+    Json color = Json::object()
+        .set("type", Json::of("string"))
+        .set("description", Json::of("Hex RGB color"))
+        .set("pattern", Json::of("^#?[0-9a-fA-F]{6}$"));
+
+    Json schema = Json::object()
+        .set("$schema", Json::of("https://json-schema.org/draft/2020-12/schema"))
+        .set("title", Json::of("Dither's Boyfriend settings"))
+        .set("type", Json::of("object"))
+        .set("properties", Json::object()
+            .set("$schema", Json::object().set("type", Json::of("string")))
+            .set("version", Json::object()
+                .set("const", Json::of(static_cast<double>(SCHEMA_VERSION)))
+                .set("description", Json::of("Settings format version")))
+            .set("settings", Json::object().set("$ref", Json::of("#/$defs/settings"))))
+        .set("required", Json::array().push(Json::of("version")).push(Json::of("settings")))
+        .set("additionalProperties", Json::of(false))
+        .set("$defs", Json::object().set("settings", settings).set("color", color));
+
+    std::string out;
+    writeJson(schema, out, 0);
+    return out + "\n";
+}
+
+std::string toJson(const Parameters& params) {
+    Json settings = Json::object();
+    for (const auto& field : getFields()) {
+        settings.set(field.name, field.write(params));
+    }
+
+    Json document = Json::object()
+        .set("version", Json::of(static_cast<double>(SCHEMA_VERSION)))
+        .set("settings", settings);
+    std::string out;
+    writeJson(document, out, 0);
+    return out + "\n";
+}
+
+// Steps that bring an older document's "settings" object up to date:
+// migrations()[0] turns a version 1 document into version 2, and so on. A step
+// renames keys and maps old values (enum ids, changed units) to what the next
+// version means by them; it never has to add fields, missing ones take their
+// default. Add a step whenever SCHEMA_VERSION is bumped
+using Migration = std::function<void(Json& settings)>;
+
+static const std::vector<Migration>& migrations() {
+    static const std::vector<Migration> steps = {};
+    return steps;
+}
+
+bool fromJson(const std::string& json, Parameters& params, std::string* error,
+              std::vector<std::string>* warnings) {
+    auto fail = [error](const std::string& message) {
+        if (error) *error = message;
+        return false;
+    };
+    auto warn = [warnings](const std::string& message) {
+        if (warnings) warnings->push_back(message);
+    };
+
+    Json document;
+    std::string parseError;
+    if (!parseJson(json, document, &parseError)) return fail(parseError);
+    if (document.type != Json::Type::OBJECT) return fail("Settings must be a JSON object");
+
+    const Json* version = document.find("version");
+    if (!version || !version->isInteger()) return fail("Missing settings format version");
+    if (version->number > SCHEMA_VERSION) {
+        return fail("Settings are from a newer version (format " + formatNumber(version->number) +
+                    "); this build reads up to format " + std::to_string(SCHEMA_VERSION));
+    }
+    if (version->number < 1) return fail("Unknown settings format " + formatNumber(version->number));
+
+    for (const auto& member : document.members) {
+        if (member.first != "$schema" && member.first != "version" && member.first != "settings") {
+            return fail("Unknown property \"" + member.first + "\"");
+        }
+    }
+    const Json* found = document.find("settings");
+    if (!found || found->type != Json::Type::OBJECT) return fail("Missing \"settings\" object");
+
+    Json settings = *found;
+    for (int from = static_cast<int>(version->number); from < SCHEMA_VERSION; ++from) {
+        migrations()[from - 1](settings);
+    }
+
+    // Missing fields take their default, so a document saved before a field
+    // existed still gives the result it gave then. Fields and enum values this
+    // build doesn't know (written by a newer build without a format bump) are
+    // skipped with a warning rather than rejecting the whole document
+    Parameters result;
+    const auto& fields = getFields();
+    for (const auto& [key, value] : settings.members) {
+        auto field = std::find_if(fields.begin(), fields.end(), [&key](const Field& f) { return f.name == key; });
+        if (field == fields.end()) {
+            warn("Ignored unknown setting \"" + key + "\"");
+            continue;
+        }
+
+        std::string message;
+        if (!field->read(value, result, message)) {
+            if (field->schema.find("enum") && value.type == Json::Type::STRING) {
+                warn("Ignored unknown value \"" + value.string + "\" for setting \"" + key + "\"");
+                continue;
+            }
+            return fail("Setting \"" + key + "\" " + message);
+        }
+    }
+
+    params = result;
+    return true;
+}
+
+std::vector<std::string> getChangedFields(const Parameters& before, const Parameters& after) {
+    std::vector<std::string> names;
+    for (const auto& field : getFields()) {
+        std::string a, b;
+        writeJson(field.write(before), a, 0);
+        writeJson(field.write(after), b, 0);
+        if (a != b) names.push_back(field.name);
+    }
+    return names;
+}
+
+std::string describeField(const Parameters& params, const std::string& name) {
+    const auto& fields = getFields();
+    auto field = std::find_if(fields.begin(), fields.end(), [&name](const Field& f) { return f.name == name; });
+    if (field == fields.end()) return "";
+
+    Json value = field->write(params);
+    std::string flat = value.string;
+    if (value.type != Json::Type::STRING) {
+        std::string text;
+        writeJson(value, text, 0);
+        // One line without quotes, and lists such as palettes cut short
+        for (char c : text) {
+            if (c == '\n' || c == '"') continue;
+            if (c == ' ' && !flat.empty() && flat.back() == ' ') continue;
+            flat += c;
+        }
+    }
+    const size_t maxLength = 24;
+    if (flat.size() > maxLength) flat = flat.substr(0, maxLength - 3) + "...";
+    return flat;
+}
+
+bool setField(Parameters& params, const std::string& name, const std::string& value, std::string* error) {
+    const auto& fields = getFields();
+    auto field = std::find_if(fields.begin(), fields.end(), [&name](const Field& f) { return f.name == name; });
+    if (field == fields.end()) {
+        if (error) *error = "Unknown setting \"" + name + "\"";
+        return false;
+    }
+
+    // Bare words are strings, and bare comma lists arrays of strings, so
+    // "atkinson" and "#222034,#45283c" need no JSON quoting
+    Json json;
+    if (!parseJson(value, json)) {
+        const Json* type = field->schema.find("type");
+        if (type && type->string == "array") {
+            json = Json::array();
+            std::stringstream items(value);
+            std::string item;
+            while (std::getline(items, item, ',')) json.push(Json::of(item));
+        } else {
+            json = Json::of(value);
+        }
+    }
+
+    std::string message;
+    Parameters result = params;
+    if (!field->read(json, result, message)) {
+        if (error) *error = "Setting \"" + name + "\" " + message;
+        return false;
+    }
+    params = result;
+    return true;
+}
+
+std::string getAlgorithmId(Dithering::Algorithm algo) {
+    size_t index = static_cast<size_t>(algo);
+    return index < algorithmIds().size() ? algorithmIds()[index] : "unknown";
+}
+
+std::string getPaletteModeId(Dithering::PaletteMode mode) {
+    size_t index = static_cast<size_t>(mode);
+    return index < paletteModeIds().size() ? paletteModeIds()[index] : "unknown";
+}
+
+bool saveSettings(const std::string& path, const Parameters& params) {
+    std::ofstream file(path);
+    if (!file) return false;
+    file << toJson(params);
+    return static_cast<bool>(file);
+}
+
+bool loadSettings(const std::string& path, Parameters& params, std::string* error,
+                  std::vector<std::string>* warnings) {
+    std::ifstream file(path);
+    if (!file) {
+        if (error) *error = "Could not read " + path;
+        return false;
+    }
+    std::stringstream buffer;
+    buffer << file.rdbuf();
+    return fromJson(buffer.str(), params, error, warnings);
+}
+
+// Preset file for a name, or empty if the name can't be used as one
+static std::string presetPath(const std::string& name, bool create, std::string* error) {
+    auto fail = [error](const std::string& message) {
+        if (error) *error = message;
+        return std::string();
+    };
+    if (name.empty() || name.size() > 64 || name[0] == '.' ||
+        name.find_first_of("/\\:*?\"<>|") != std::string::npos) {
+        return fail("Invalid preset name: \"" + name + "\"");
+    }
+
+    std::string dir = Platform::getAppDataDir();
+    if (dir.empty()) return fail("No app data directory");
+    std::filesystem::path folder = std::filesystem::path(dir) / "presets";
+    std::error_code ec;
+    if (create) std::filesystem::create_directories(folder, ec);
+    return (folder / (name + ".json")).string();
+}
+
+std::vector<std::string> listPresets() {
+    std::vector<std::string> names;
+    std::string dir = Platform::getAppDataDir();
+    if (dir.empty()) return names;
+
+    std::error_code ec;
+    for (const auto& entry : std::filesystem::directory_iterator(std::filesystem::path(dir) / "presets", ec)) {
+        if (entry.is_regular_file() && entry.path().extension() == ".json") {
+            names.push_back(entry.path().stem().string());
+        }
+    }
+    std::sort(names.begin(), names.end());
+    return names;
+}
+
+bool savePreset(const std::string& name, const Parameters& params, std::string* error) {
+    std::string path = presetPath(name, true, error);
+    if (path.empty()) return false;
+    if (!saveSettings(path, params)) {
+        if (error) *error = "Could not write " + path;
+        return false;
+    }
+    return true;
+}
+
+bool loadPreset(const std::string& name, Parameters& params, std::string* error,
+                std::vector<std::string>* warnings) {
+    std::string path = presetPath(name, false, error);
+    if (path.empty()) return false;
+    if (!std::filesystem::exists(path)) {
+        if (error) *error = "No preset named \"" + name + "\"";
+        return false;
+    }
+    return loadSettings(path, params, error, warnings);
+}
+
+bool deletePreset(const std::string& name) {
+    std::string path = presetPath(name, false, nullptr);
+    std::error_code ec;
+    return !path.empty() && std::filesystem::remove(path, ec);
+}
+
+} // namespace Settings
@@ -0,0 +1,60 @@
+#pragma once
+
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+
+namespace Settings {
+
+// Version of the settings document format. Bump it (and add a migration step in
+// settings.cpp) whenever a field is renamed, removed or changes meaning; adding
+// a field doesn't need a bump
+constexpr int SCHEMA_VERSION = 1;
+
+// JSON Schema (draft 2020-12) describing settings documents, for external tools
+// and form validation. The "settings" definition is what presets and project
+// files embed
+std::string getSettingsSchema();
+
+// Write parameters as a settings document: {"version": 1, "settings": {...}}
+std::string toJson(const Dithering::Parameters& params);
+
+// Read a settings document, migrating older formats. Fields that are missing
+// take their default; unknown fields and enum values are skipped and listed in
+// warnings. On failure params is unchanged and error says what was wrong
+bool fromJson(const std::string& json, Dithering::Parameters& params, std::string* error = nullptr,
+              std::vector<std::string>* warnings = nullptr);
+
+// Settings file names of the fields that differ, in document order
+std::vector<std::string> getChangedFields(const Dithering::Parameters& before, const Dithering::Parameters& after);
+
+// Value of one setting as short text for captions, e.g. "0.8" or "atkinson".
+// Long values such as palettes and matrices are shortened; empty if unknown
+std::string describeField(const Dithering::Parameters& params, const std::string& name);
+
+// Change one setting by its settings file name, e.g. ("strength", "0.8"). The
+// value is JSON, though bare words and comma lists are read as strings and
+// arrays of strings ("atkinson", "#222034,#45283c")
+bool setField(Dithering::Parameters& params, const std::string& name, const std::string& value,
+              std::string* error = nullptr);
+
+// Ids used in settings files (the same as the CLI's names), e.g. "floyd-steinberg"
+std::string getAlgorithmId(Dithering::Algorithm algo);
+std::string getPaletteModeId(Dithering::PaletteMode mode);
+
+// File helpers around toJson/fromJson
+bool saveSettings(const std::string& path, const Dithering::Parameters& params);
+bool loadSettings(const std::string& path, Dithering::Parameters& params, std::string* error = nullptr,
+                  std::vector<std::string>* warnings = nullptr);
+
+// Named presets, kept as settings documents in the "presets" folder of the
+// app data directory. Names may not contain path separators or reserved
+// characters
+std::vector<std::string> listPresets();    // Sorted by name
+bool savePreset(const std::string& name, const Dithering::Parameters& params, std::string* error = nullptr);
+bool loadPreset(const std::string& name, Dithering::Parameters& params, std::string* error = nullptr,
+                std::vector<std::string>* warnings = nullptr);
+bool deletePreset(const std::string& name);
+
+} // namespace Settings
@@ -0,0 +1,122 @@
+#include "stats.h"
+#include "platform.h"
+#include <atomic>
+#include <filesystem>
+#include <fstream>
+#include <mutex>
+#include <sstream>
+
+namespace UsageStats {
+
+// Jobs can finish on several threads at once (batches, the GUI queue, the
+// automation server), so every read and write of the file goes through this
+static std::mutex fileMutex;
+static std::atomic<int> revision{0};
+
+int getRevision() {
+    return revision;
+}
+
+std::string getStatsPath() {
+    std::string dir = Platform::getAppDataDir();
+    return dir.empty() ? "" : dir + "/usage_stats.txt";
+}
+
+// File format: one tab-separated "key<TAB>value" per line, with
+// "algorithm<TAB>name<TAB>count" lines for the per-algorithm counters
+static Stats readUsageStats() {
+    Stats stats;
+    std::ifstream file(getStatsPath());
+    std::string line;
+
+    while (std::getline(file, line)) {
+        std::istringstream fields(line);
+        std::string key, value;
+        if (!std::getline(fields, key, '\t') || !std::getline(fields, value, '\t')) continue;
+
+        try {
+            if (key == "algorithm") {
+                std::string count;
+                if (std::getline(fields, count, '\t')) {
+                    stats.algorithmCounts[value] = std::stoi(count);
+                }
+            } else if (key == "images") {
+                stats.imagesProcessed = std::stoi(value);
+            } else if (key == "videos") {
+                stats.videosProcessed = std::stoi(value);
+            } else if (key == "megapixels") {
+                stats.totalMegapixels = std::stod(value);
+            } else if (key == "seconds") {
+                stats.totalSeconds = std::stod(value);
+            }
+        } catch (const std::exception&) {
+            // Skip malformed lines rather than losing the rest
+        }
+    }
+
+    return stats;
+}
+
+Stats getUsageStats() {
+    std::lock_guard<std::mutex> lock(fileMutex);
+    return readUsageStats();
+}
+
+// Written to a temporary file and renamed over the old one, so a crash or a
+// full disk mid-write can't leave the counters half written
+static bool saveUsageStats(const Stats& stats) {
+    std::string path = getStatsPath();
+    if (path.empty()) return false;
+
+    std::string tempPath = path + ".tmp";
+    std::ofstream file(tempPath);
+    if (!file) return false;
+
+    file << "images\t" << stats.imagesProcessed << "\n";
+    file << "videos\t" << stats.videosProcessed << "\n";
+    file << "megapixels\t" << stats.totalMegapixels << "\n";
+    file << "seconds\t" << stats.totalSeconds << "\n";
+    for (const auto& [name, count] : stats.algorithmCounts) {
+        file << "algorithm\t" << name << "\t" << count << "\n";
+    }
+    file.close();
+
+    std::error_code ec;
+    if (!file) {
+        std::filesystem::remove(tempPath, ec);
+        return false;
+    }
+    std::filesystem::rename(tempPath, path, ec);
+    return !ec;
+}
+
+void recordJob(const std::string& algorithmName, int width, int height, double seconds, bool video) {
+    std::lock_guard<std::mutex> lock(fileMutex);
+    Stats stats = readUsageStats();
+
+    stats.algorithmCounts[algorithmName]++;
+    if (video) {
+        stats.videosProcessed++;
+    } else {
+        stats.imagesProcessed++;
+    }
+    stats.totalMegapixels += static_cast<double>(width) * height / 1e6;
+    stats.totalSeconds += seconds;
+
+    saveUsageStats(stats);
+    revision++;
+}
+
+bool clearUsageStats() {
+    std::string path = getStatsPath();
+    if (path.empty()) return false;
+
+    std::lock_guard<std::mutex> lock(fileMutex);
+    // A missing file (nothing recorded yet) counts as cleared
+    std::error_code ec;
+    std::filesystem::remove(path, ec);
+    revision++;
+    return !ec;
+}
+
+} // namespace UsageStats
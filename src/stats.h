@@ -0,0 +1,37 @@
+#pragma once
+
+#include <map>
+#include <string>
+
+namespace UsageStats {
+
+// Local usage counters, stored only in the app data directory (never sent anywhere)
+struct Stats {
+    std::map<std::string, int> algorithmCounts;    // Jobs per algorithm name
+    int imagesProcessed = 0;
+    int videosProcessed = 0;
+    double totalMegapixels = 0.0;   // Summed image/frame size of all jobs
+    double totalSeconds = 0.0;      // Summed job duration
+
+    int jobs() const { return imagesProcessed + videosProcessed; }
+    double averageMegapixels() const { return jobs() > 0 ? totalMegapixels / jobs() : 0.0; }
+    double averageSeconds() const { return jobs() > 0 ? totalSeconds / jobs() : 0.0; }
+};
+
+// Read the current statistics (empty if nothing has been recorded yet)
+Stats getUsageStats();
+
+// Add one finished job to the statistics
+void recordJob(const std::string& algorithmName, int width, int height, double seconds, bool video);
+
+// Delete all recorded statistics
+bool clearUsageStats();
+
+// Changes whenever this process records a job or clears the statistics, so a
+// display can reread them only when they have changed
+int getRevision();
+
+// Location of the statistics file
+std::string getStatsPath();
+
+} // namespace UsageStats
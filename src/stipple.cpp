@@ -0,0 +1,145 @@
+#include "stipple.h"
+#include <algorithm>
+#include <cmath>
+#include <random>
+
+namespace Stipple {
+
+// Longest side of the density map the dots are relaxed on
+static const int WORK_SIZE = 1024;
+
+static float luma(const cv::Vec3b& color) {
+    return 0.114f * color[0] + 0.587f * color[1] + 0.299f * color[2];
+}
+
+// Draw a random position with probability following the density, by rejection
+static bool samplePosition(const cv::Mat& density, std::mt19937& rng, cv::Point2f& position) {
+    std::uniform_real_distribution<float> unit(0.0f, 1.0f);
+    for (int attempt = 0; attempt < 1000; ++attempt) {
+        float x = unit(rng) * density.cols;
+        float y = unit(rng) * density.rows;
+        int px = std::min(static_cast<int>(x), density.cols - 1);
+        int py = std::min(static_cast<int>(y), density.rows - 1);
+        if (unit(rng) < density.at<float>(py, px)) {
+            position = cv::Point2f(x, y);
+            return true;
+        }
+    }
+    return false;
+}
+
+Stipples placeStipples(const cv::Mat& preprocessed, const Dithering::Parameters& params) {
+    Stipples stipples;
+    stipples.size = preprocessed.size();
+    std::vector<cv::Vec3b> palette = Dithering::getPalette(params);
+    if (palette.empty()) palette = {cv::Vec3b(0, 0, 0), cv::Vec3b(255, 255, 255)};
+    auto byLuma = [](const cv::Vec3b& a, const cv::Vec3b& b) { return luma(a) < luma(b); };
+    stipples.ink = *std::min_element(palette.begin(), palette.end(), byLuma);
+    stipples.paper = *std::max_element(palette.begin(), palette.end(), byLuma);
+    if (preprocessed.empty()) return stipples;
+
+    // Density is ink coverage: 0 on paper, 1 where the ink color is solid
+    cv::Mat level, density;
+    preprocessed.convertTo(level, CV_32F);
+    level = Dithering::convertToGray(level, params);
+    cv::Vec3f weights = Dithering::getGrayWeights(params);
+    float inkLevel = cv::Vec3f(stipples.ink).dot(weights), paperLevel = cv::Vec3f(stipples.paper).dot(weights);
+    float range = std::max(paperLevel - inkLevel, 1.0f);
+    level.convertTo(density, CV_32F, -1.0 / range, paperLevel / range);
+    cv::max(density, 0.0, density);
+    cv::min(density, 1.0, density);
+
+    double scale = std::min(1.0, static_cast<double>(WORK_SIZE) / std::max(density.cols, density.rows));
+    if (scale < 1.0) {
+        cv::resize(density, density, cv::Size(), scale, scale, cv::INTER_AREA);
+    }
+    if (cv::sum(density)[0] < 1e-3) return stipples;
+
+    std::mt19937 rng(params.seed);
+    int count = std::clamp(params.stippleDots, 1, 200000);
+    std::vector<cv::Point2f> sites;
+    cv::Point2f position;
+    while (static_cast<int>(sites.size()) < count && samplePosition(density, rng, position)) {
+        sites.push_back(position);
+    }
+
+    // Voronoi cells from the distance transform: every site marks one pixel and
+    // each pixel is labeled with the nearest marked one, in raster order
+    const int cols = density.cols, rows = density.rows;
+    std::vector<double> weight(sites.size()), sumX(sites.size()), sumY(sites.size());
+    std::vector<int> area(sites.size());
+    int iterations = std::clamp(params.stippleIterations, 0, 500);
+    for (int iteration = 0; iteration <= iterations; ++iteration) {
+        cv::Mat marks(rows, cols, CV_8U, cv::Scalar(255));
+        std::vector<int> siteAt(static_cast<size_t>(rows) * cols, -1);
+        for (size_t i = 0; i < sites.size(); ++i) {
+            int px = std::clamp(static_cast<int>(sites[i].x), 0, cols - 1);
+            int py = std::clamp(static_cast<int>(sites[i].y), 0, rows - 1);
+            marks.at<uchar>(py, px) = 0;
+            if (siteAt[static_cast<size_t>(py) * cols + px] < 0) siteAt[static_cast<size_t>(py) * cols + px] = i;
+        }
+        std::vector<int> labelSite = {-1};
+        for (int y = 0; y < rows; ++y) {
+            const uchar* row = marks.ptr<uchar>(y);
+            for (int x = 0; x < cols; ++x) {
+                if (row[x] == 0) labelSite.push_back(siteAt[static_cast<size_t>(y) * cols + x]);
+            }
+        }
+        cv::Mat distances, labels;
+        cv::distanceTransform(marks, distances, labels, cv::DIST_L2, cv::DIST_MASK_5, cv::DIST_LABEL_PIXEL);
+
+        std::fill(weight.begin(), weight.end(), 0.0);
+        std::fill(sumX.begin(), sumX.end(), 0.0);
+        std::fill(sumY.begin(), sumY.end(), 0.0);
+        std::fill(area.begin(), area.end(), 0);
+        for (int y = 0; y < rows; ++y) {
+            const int* label = labels.ptr<int>(y);
+            const float* ink = density.ptr<float>(y);
+            for (int x = 0; x < cols; ++x) {
+                int site = label[x] > 0 && label[x] < static_cast<int>(labelSite.size()) ? labelSite[label[x]] : -1;
+                if (site < 0) continue;
+                weight[site] += ink[x];
+                sumX[site] += ink[x] * (x + 0.5);
+                sumY[site] += ink[x] * (y + 0.5);
+                area[site]++;
+            }
+        }
+        if (iteration == iterations) break;
+
+        // Dots left without ink (sharing a pixel, or on paper) start over elsewhere
+        for (size_t i = 0; i < sites.size(); ++i) {
+            if (weight[i] > 1e-6) {
+                sites[i] = cv::Point2f(static_cast<float>(sumX[i] / weight[i]), static_cast<float>(sumY[i] / weight[i]));
+            } else {
+                samplePosition(density, rng, sites[i]);
+            }
+        }
+    }
+
+    // Dot area follows the mean coverage of the cell; faint cells still get a visible dot
+    float maxRadius = std::max(0.5f, params.stippleDotSize);
+    for (size_t i = 0; i < sites.size(); ++i) {
+        if (weight[i] <= 1e-6 || area[i] == 0) continue;
+        float coverage = static_cast<float>(weight[i] / area[i]);
+        float radius = std::max(0.5f, maxRadius * std::sqrt(coverage));
+        stipples.dots.push_back({cv::Point2f(static_cast<float>(sites[i].x / scale),
+                                             static_cast<float>(sites[i].y / scale)), radius});
+    }
+    return stipples;
+}
+
+cv::Mat drawStipples(const Stipples& stipples) {
+    cv::Mat result(stipples.size, CV_8UC3, cv::Scalar(stipples.paper[0], stipples.paper[1], stipples.paper[2]));
+    cv::Scalar ink(stipples.ink[0], stipples.ink[1], stipples.ink[2]);
+
+    // Sub-pixel centers and radii, 4 fractional bits
+    const int shift = 4;
+    const float unit = 1 << shift;
+    for (const auto& dot : stipples.dots) {
+        cv::Point center(cvRound((dot.center.x - 0.5f) * unit), cvRound((dot.center.y - 0.5f) * unit));
+        cv::circle(result, center, cvRound(dot.size * unit), ink, cv::FILLED, cv::LINE_8, shift);
+    }
+    return result;
+}
+
+} // namespace Stipple
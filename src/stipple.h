@@ -0,0 +1,32 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <vector>
+
+#include "dithering.h"
+#include "halftone.h"
+
+namespace Stipple {
+
+// Stipple dots of the darkest palette color on the lightest
+struct Stipples {
+    cv::Size size;              // Image size in pixels
+    cv::Vec3b ink;              // BGR
+    cv::Vec3b paper;
+    std::vector<Halftone::Dot> dots;    // Circles, radius in pixels
+};
+
+// Place params.stippleDots dots by weighted Voronoi relaxation (Secord 2002):
+// dots start where the image is dark and are moved to the ink-weighted
+// centroid of their Voronoi cell params.stippleIterations times, which
+// spreads them evenly like charges repelling each other. Each dot's area
+// follows the tone of its cell, up to params.stippleDotSize. preprocessed is
+// BGR on the 0-255 scale, as preprocessImage returns it. The seed sets the
+// starting positions. Relaxation runs on at most 1024 pixels along the long
+// side, so time depends on the dot count more than the image size
+Stipples placeStipples(const cv::Mat& preprocessed, const Dithering::Parameters& params);
+
+// Draw the dots with hard edges, so only the two colors appear
+cv::Mat drawStipples(const Stipples& stipples);
+
+} // namespace Stipple
@@ -0,0 +1,103 @@
+#include "tiles.h"
+#include <algorithm>
+#include <cmath>
+#include <filesystem>
+#include <fstream>
+
+namespace Tiles {
+
+using Errors::ErrorCode;
+
+Pyramid::Pyramid(const cv::Mat& image) {
+    if (image.empty()) return;
+    levels.push_back(image);
+    while (levels.back().cols > 1 || levels.back().rows > 1) {
+        const cv::Mat& previous = levels.back();
+        cv::Mat half;
+        cv::resize(previous, half, cv::Size((previous.cols + 1) / 2, (previous.rows + 1) / 2), 0, 0, cv::INTER_AREA);
+        levels.push_back(half);
+    }
+}
+
+cv::Size Pyramid::getLevelSize(int level) const {
+    if (level < 0 || level >= getLevelCount()) return cv::Size();
+    return levels[level].size();
+}
+
+cv::Mat Pyramid::getLevel(int level) const {
+    if (level < 0 || level >= getLevelCount()) return cv::Mat();
+    return levels[level];
+}
+
+cv::Size Pyramid::getTileGrid(int level) const {
+    cv::Size size = getLevelSize(level);
+    return cv::Size((size.width + TILE_SIZE - 1) / TILE_SIZE, (size.height + TILE_SIZE - 1) / TILE_SIZE);
+}
+
+cv::Mat Pyramid::getTile(int level, int column, int row) const {
+    cv::Size grid = getTileGrid(level);
+    if (column < 0 || row < 0 || column >= grid.width || row >= grid.height) return cv::Mat();
+    const cv::Mat& image = levels[level];
+    cv::Rect rect(column * TILE_SIZE, row * TILE_SIZE, TILE_SIZE, TILE_SIZE);
+    return image(rect & cv::Rect(0, 0, image.cols, image.rows));
+}
+
+int Pyramid::getLevelForScale(double scale) const {
+    if (empty() || scale >= 1.0) return 0;
+    int level = static_cast<int>(std::floor(std::log2(1.0 / std::max(scale, 1e-9))));
+    return std::clamp(level, 0, getLevelCount() - 1);
+}
+
+bool exportDeepZoom(const cv::Mat& image, const std::string& path, Errors::Error* error,
+                    const Dithering::ProgressCallback& progress) {
+    auto fail = [error](ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    if (image.empty()) return fail(ErrorCode::INVALID_ARGUMENT, "There is no image to tile");
+    std::filesystem::path descriptor(path);
+    if (descriptor.extension() != ".dzi") {
+        return fail(ErrorCode::UNSUPPORTED_FORMAT, "Deep Zoom images are saved as .dzi");
+    }
+
+    // Deep Zoom numbers levels from 1x1 up, the reverse of the pyramid
+    Pyramid pyramid(image);
+    int count = pyramid.getLevelCount();
+    std::filesystem::path folder = descriptor.parent_path() / (descriptor.stem().string() + "_files");
+    // Tiles left over from a bigger image would otherwise mix with the new ones
+    std::error_code ec;
+    std::filesystem::remove_all(folder, ec);
+    if (ec) return fail(ErrorCode::IO, "Could not replace " + folder.string());
+    for (int level = count - 1; level >= 0; --level) {
+        int number = count - 1 - level;
+        std::filesystem::path levelFolder = folder / std::to_string(number);
+        std::filesystem::create_directories(levelFolder, ec);
+        if (ec) return fail(ErrorCode::IO, "Could not create " + levelFolder.string());
+
+        cv::Size grid = pyramid.getTileGrid(level);
+        for (int row = 0; row < grid.height; ++row) {
+            for (int column = 0; column < grid.width; ++column) {
+                std::filesystem::path tilePath =
+                    levelFolder / (std::to_string(column) + "_" + std::to_string(row) + ".png");
+                if (!cv::imwrite(tilePath.string(), pyramid.getTile(level, column, row))) {
+                    return fail(ErrorCode::IO, "Could not write " + tilePath.string());
+                }
+            }
+        }
+        if (progress && !progress(static_cast<float>(number + 1) / count, "Writing tiles")) {
+            return fail(ErrorCode::CANCELLED, "Cancelled");
+        }
+    }
+
+    std::ofstream file(path);
+    file << "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"
+         << "<Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"" << TILE_SIZE
+         << "\" Overlap=\"0\" Format=\"png\">\n"
+         << "  <Size Width=\"" << image.cols << "\" Height=\"" << image.rows << "\"/>\n"
+         << "</Image>\n";
+    if (!file) return fail(ErrorCode::IO, "Could not write " + path);
+    return true;
+}
+
+} // namespace Tiles
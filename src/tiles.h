@@ -0,0 +1,51 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+
+namespace Tiles {
+
+constexpr int TILE_SIZE = 256;
+
+// Zoom pyramid of a result for viewers that pan and zoom images too big to
+// show at once. Level 0 is the result pixel for pixel; each level above is
+// half the size of the one below (rounded up), down to 1x1. Zoomed-out levels
+// are area-averaged, so they show the tone of the dither rather than aliasing
+class Pyramid {
+public:
+    Pyramid() = default;
+    explicit Pyramid(const cv::Mat& image);
+
+    bool empty() const { return levels.empty(); }
+    int getLevelCount() const { return static_cast<int>(levels.size()); }
+    cv::Size getLevelSize(int level) const;
+
+    // Whole level as one image, empty if out of range
+    cv::Mat getLevel(int level) const;
+
+    // Columns and rows of tiles at a level
+    cv::Size getTileGrid(int level) const;
+
+    // Tile of a level, TILE_SIZE square except along the right and bottom
+    // edges. Shares memory with the pyramid; empty if out of range
+    cv::Mat getTile(int level, int column, int row) const;
+
+    // Finest level that still shows at least scale screen pixels per level
+    // pixel, for a view drawn at scale times the result's size
+    int getLevelForScale(double scale) const;
+
+private:
+    std::vector<cv::Mat> levels;
+};
+
+// Write a Deep Zoom image (as read by OpenSeadragon and similar viewers):
+// path is the .dzi descriptor, and the PNG tiles go in <name>_files next to
+// it, one folder per level numbered from 1x1 up. Progress is per level
+bool exportDeepZoom(const cv::Mat& image, const std::string& path, Errors::Error* error = nullptr,
+                    const Dithering::ProgressCallback& progress = nullptr);
+
+} // namespace Tiles
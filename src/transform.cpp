@@ -0,0 +1,94 @@
+#include "transform.h"
+#include <algorithm>
+#include <cmath>
+
+namespace Transform {
+
+const std::vector<std::string>& getFilterIds() {
+    static const std::vector<std::string> ids = {"nearest", "bilinear", "bicubic", "area", "lanczos"};
+    return ids;
+}
+
+bool parseFilter(const std::string& id, Filter& filter) {
+    const auto& ids = getFilterIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    filter = static_cast<Filter>(match - ids.begin());
+    return true;
+}
+
+std::string getFilterName(Filter filter) {
+    switch (filter) {
+        case Filter::NEAREST: return "Nearest Neighbor";
+        case Filter::BILINEAR: return "Bilinear";
+        case Filter::BICUBIC: return "Bicubic";
+        case Filter::AREA: return "Area";
+        case Filter::LANCZOS: return "Lanczos";
+        default: return "Unknown";
+    }
+}
+
+cv::Size fitSize(const cv::Size& size, const cv::Size& maxSize) {
+    if (size.width <= 0 || size.height <= 0) return size;
+    if (maxSize.width > 0 && maxSize.height > 0) return maxSize;
+
+    double aspect = static_cast<double>(size.width) / size.height;
+    if (maxSize.width > 0) {
+        return cv::Size(maxSize.width, std::max(1, static_cast<int>(std::lround(maxSize.width / aspect))));
+    }
+    if (maxSize.height > 0) {
+        return cv::Size(std::max(1, static_cast<int>(std::lround(maxSize.height * aspect))), maxSize.height);
+    }
+    return size;
+}
+
+cv::Mat resizeImage(const cv::Mat& image, const cv::Size& size, Filter filter) {
+    if (image.empty() || size.width <= 0 || size.height <= 0) return cv::Mat();
+
+    int interpolation = cv::INTER_LINEAR;
+    switch (filter) {
+        case Filter::NEAREST: interpolation = cv::INTER_NEAREST; break;
+        case Filter::BICUBIC: interpolation = cv::INTER_CUBIC; break;
+        case Filter::AREA: interpolation = cv::INTER_AREA; break;
+        case Filter::LANCZOS: interpolation = cv::INTER_LANCZOS4; break;
+        case Filter::BILINEAR:
+        default: break;
+    }
+
+    cv::Mat resized;
+    cv::resize(image, resized, size, 0, 0, interpolation);
+    return resized;
+}
+
+cv::Mat cropImage(const cv::Mat& image, const cv::Rect& rect) {
+    cv::Rect region = rect & cv::Rect(0, 0, image.cols, image.rows);
+    if (image.empty() || region.empty()) return cv::Mat();
+    return image(region).clone();
+}
+
+int normalizeRotation(int degrees) {
+    return ((degrees % 360 + 360) % 360 + 45) / 90 * 90 % 360;
+}
+
+cv::Mat rotateImage(const cv::Mat& image, int degrees) {
+    if (image.empty() || degrees % 90 != 0) return cv::Mat();
+
+    cv::Mat rotated;
+    switch (normalizeRotation(degrees)) {
+        case 90: cv::rotate(image, rotated, cv::ROTATE_90_CLOCKWISE); break;
+        case 180: cv::rotate(image, rotated, cv::ROTATE_180); break;
+        case 270: cv::rotate(image, rotated, cv::ROTATE_90_COUNTERCLOCKWISE); break;
+        default: rotated = image.clone(); break;
+    }
+    return rotated;
+}
+
+cv::Mat flipImage(const cv::Mat& image, FlipAxis axis) {
+    if (image.empty()) return cv::Mat();
+
+    cv::Mat flipped;
+    cv::flip(image, flipped, axis == FlipAxis::HORIZONTAL ? 1 : 0);
+    return flipped;
+}
+
+} // namespace Transform
@@ -0,0 +1,41 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+namespace Transform {
+
+// Resampling filters for resizing
+enum class Filter {
+    NEAREST,            // Hard pixel edges, for pixel art
+    BILINEAR,
+    BICUBIC,
+    AREA,               // Averages source pixels, best for shrinking photos
+    LANCZOS             // Sharpest, may ring around hard edges
+};
+
+enum class FlipAxis {
+    HORIZONTAL,         // Mirror left to right
+    VERTICAL            // Mirror top to bottom
+};
+
+// Filter lookup by command line id (nearest, bilinear, bicubic, area, lanczos)
+const std::vector<std::string>& getFilterIds();
+bool parseFilter(const std::string& id, Filter& filter);
+std::string getFilterName(Filter filter);
+
+// Size that fits within maxSize keeping the aspect ratio of size (either
+// dimension of maxSize may be 0 to leave it unconstrained)
+cv::Size fitSize(const cv::Size& size, const cv::Size& maxSize);
+
+// Any angle in degrees, negative too, snapped to the nearest quarter turn: 0, 90, 180 or 270
+int normalizeRotation(int degrees);
+
+// Each returns a new image; an empty result means the input couldn't be transformed
+cv::Mat resizeImage(const cv::Mat& image, const cv::Size& size, Filter filter);
+cv::Mat cropImage(const cv::Mat& image, const cv::Rect& rect);     // Clamped to the image
+cv::Mat rotateImage(const cv::Mat& image, int degrees);            // Clockwise, multiple of 90
+cv::Mat flipImage(const cv::Mat& image, FlipAxis axis);
+
+} // namespace Transform
@@ -0,0 +1,1492 @@
+#include "video.h"
+#include "imageio.h"
+#include "json.h"
+#include "platform.h"
+#include "transform.h"
+#include <algorithm>
+#include <cctype>
+#include <chrono>
+#include <cmath>
+#include <cstdio>
+#include <cstdlib>
+#include <deque>
+#include <filesystem>
+#include <fstream>
+#include <functional>
+#include <future>
+#include <iostream>
+#include <limits>
+#include <map>
+#include <thread>
+#include <unordered_set>
+#include <utility>
+#include <vector>
+
+namespace Video {
+
+const std::vector<std::string>& getVideoExtensions() {
+    static const std::vector<std::string> extensions = {"mp4", "avi", "mov", "mkv", "webm", "m4v"};
+    return extensions;
+}
+
+bool isVideoFile(const std::string& filename) {
+    const auto& extensions = getVideoExtensions();
+    return std::find(extensions.begin(), extensions.end(), ImageIO::getExtension(filename)) != extensions.end();
+}
+
+bool getVideoInfo(const std::string& path, VideoInfo& info, Errors::Error* error) {
+    cv::VideoCapture cap(path);
+    if (!cap.isOpened()) {
+        std::error_code ec;
+        bool exists = std::filesystem::exists(path, ec);
+        if (error) {
+            *error = exists ? Errors::Error{Errors::ErrorCode::DECODE, "Could not open video: " + path}
+                            : Errors::Error{Errors::ErrorCode::NOT_FOUND, "No such file: " + path};
+        }
+        return false;
+    }
+
+    info = VideoInfo();
+    cap.set(cv::CAP_PROP_ORIENTATION_AUTO, 0);
+    info.rotation = Transform::normalizeRotation(static_cast<int>(cap.get(cv::CAP_PROP_ORIENTATION_META)));
+    info.size = cv::Size(static_cast<int>(cap.get(cv::CAP_PROP_FRAME_WIDTH)),
+                         static_cast<int>(cap.get(cv::CAP_PROP_FRAME_HEIGHT)));
+    if (info.rotation == 90 || info.rotation == 270) std::swap(info.size.width, info.size.height);
+    info.fps = cap.get(cv::CAP_PROP_FPS);
+    info.frameCount = std::max(0, static_cast<int>(cap.get(cv::CAP_PROP_FRAME_COUNT)));
+    if (info.fps > 0.0) info.durationSeconds = info.frameCount / info.fps;
+
+    // The FourCC's characters, least significant byte first
+    int fourcc = static_cast<int>(cap.get(cv::CAP_PROP_FOURCC));
+    for (int shift = 0; shift < 32; shift += 8) {
+        char c = static_cast<char>((fourcc >> shift) & 0xff);
+        if (std::isprint(static_cast<unsigned char>(c)) && c != ' ') info.codec += c;
+    }
+
+    std::vector<std::map<std::string, std::string>> streams = Platform::probeStreams(path);
+    info.probed = !streams.empty();
+    bool videoSeen = false;
+    for (auto& stream : streams) {
+        if (stream["codec_type"] == "video" && !videoSeen) {
+            videoSeen = true;
+            if (!stream["codec_name"].empty()) info.codec = stream["codec_name"];
+        } else if (stream["codec_type"] == "audio" && !info.hasAudio) {
+            info.hasAudio = true;
+            info.audioCodec = stream["codec_name"];
+            info.audioChannels = std::atoi(stream["channels"].c_str());
+            info.audioSampleRate = std::atoi(stream["sample_rate"].c_str());
+        }
+    }
+    return true;
+}
+
+cv::Size getFrameSize(const cv::Size& source, const Options& options) {
+    cv::Size size = source;
+    if (options.frameSize.width > 0 || options.frameSize.height > 0) {
+        size = Transform::fitSize(source, options.frameSize);
+    } else if (options.scale > 0.0 && options.scale != 1.0) {
+        size = cv::Size(static_cast<int>(std::lround(source.width * options.scale)),
+                        static_cast<int>(std::lround(source.height * options.scale)));
+    }
+    if (options.maxWidth > 0 && size.width > options.maxWidth) {
+        size = Transform::fitSize(size, cv::Size(options.maxWidth, 0));
+    }
+    if (size == source) return size;
+    return cv::Size(std::max(2, size.width / 2 * 2), std::max(2, size.height / 2 * 2));
+}
+
+bool parseTime(const std::string& text, double& seconds) {
+    double total = 0.0;
+    size_t start = 0;
+    for (int field = 0; field < 3; ++field) {
+        size_t colon = text.find(':', start);
+        std::string value = text.substr(start, colon == std::string::npos ? colon : colon - start);
+        if (value.empty() || value.find_first_not_of("0123456789.") != std::string::npos) return false;
+        char* end = nullptr;
+        double number = std::strtod(value.c_str(), &end);
+        if (*end != '\0') return false;
+        total = total * 60.0 + number;
+        if (colon == std::string::npos) {
+            seconds = total;
+            return true;
+        }
+        start = colon + 1;
+    }
+    return false;
+}
+
+const std::vector<std::string>& getCodecIds() {
+    static const std::vector<std::string> ids = {"opencv", "h264", "h265", "vp9", "av1", "prores"};
+    return ids;
+}
+
+bool parseCodec(const std::string& id, Codec& codec) {
+    const auto& ids = getCodecIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    codec = static_cast<Codec>(match - ids.begin());
+    return true;
+}
+
+std::string getCodecName(Codec codec) {
+    switch (codec) {
+        case Codec::OPENCV: return "OpenCV MPEG-4";
+        case Codec::H264: return "H.264";
+        case Codec::H265: return "H.265";
+        case Codec::VP9: return "VP9";
+        case Codec::AV1: return "AV1";
+        case Codec::PRORES: return "ProRes";
+        default: return "Unknown";
+    }
+}
+
+const std::vector<std::string>& getHardwareIds() {
+    static const std::vector<std::string> ids = {"none", "auto", "nvenc", "qsv", "videotoolbox", "amf"};
+    return ids;
+}
+
+bool parseHardware(const std::string& id, Hardware& hardware) {
+    const auto& ids = getHardwareIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    hardware = static_cast<Hardware>(match - ids.begin());
+    return true;
+}
+
+std::string getHardwareName(Hardware hardware) {
+    switch (hardware) {
+        case Hardware::NONE: return "Software";
+        case Hardware::AUTO: return "Automatic";
+        case Hardware::NVENC: return "NVIDIA NVENC";
+        case Hardware::QSV: return "Intel Quick Sync";
+        case Hardware::VIDEOTOOLBOX: return "Apple VideoToolbox";
+        case Hardware::AMF: return "AMD AMF";
+        default: return "Unknown";
+    }
+}
+
+std::string getHardwareEncoder(Codec codec, Hardware hardware) {
+    const char* suffix = nullptr;
+    switch (hardware) {
+        case Hardware::NVENC: suffix = "_nvenc"; break;
+        case Hardware::QSV: suffix = "_qsv"; break;
+        case Hardware::VIDEOTOOLBOX: suffix = "_videotoolbox"; break;
+        case Hardware::AMF: suffix = "_amf"; break;
+        default: return "";
+    }
+    switch (codec) {
+        case Codec::H264: return std::string("h264") + suffix;
+        case Codec::H265: return std::string("hevc") + suffix;
+        case Codec::VP9: return hardware == Hardware::QSV ? "vp9_qsv" : "";
+        case Codec::AV1: return hardware == Hardware::VIDEOTOOLBOX ? "" : std::string("av1") + suffix;
+        default: return "";
+    }
+}
+
+std::vector<HardwareEncoder> detectHardwareEncoders() {
+    std::vector<std::string> built = Platform::listFfmpegEncoders();
+    std::vector<HardwareEncoder> encoders;
+    for (int h = static_cast<int>(Hardware::NVENC); h <= static_cast<int>(Hardware::AMF); ++h) {
+        for (int c = static_cast<int>(Codec::H264); c <= static_cast<int>(Codec::PRORES); ++c) {
+            HardwareEncoder encoder = {static_cast<Codec>(c), static_cast<Hardware>(h), ""};
+            encoder.name = getHardwareEncoder(encoder.codec, encoder.hardware);
+            if (encoder.name.empty() || std::find(built.begin(), built.end(), encoder.name) == built.end()) continue;
+            if (Platform::testFfmpegEncoder(encoder.name)) encoders.push_back(encoder);
+        }
+    }
+    return encoders;
+}
+
+// The hardware to encode with: the requested one if it works for the codec,
+// for AUTO the first that does, otherwise NONE (software)
+static Hardware resolveHardware(const EncoderSettings& settings) {
+    if (settings.hardware == Hardware::NONE) return Hardware::NONE;
+    std::vector<std::string> built = Platform::listFfmpegEncoders();
+    for (int h = static_cast<int>(Hardware::NVENC); h <= static_cast<int>(Hardware::AMF); ++h) {
+        Hardware hardware = static_cast<Hardware>(h);
+        if (settings.hardware != Hardware::AUTO && hardware != settings.hardware) continue;
+        std::string name = getHardwareEncoder(settings.codec, hardware);
+        if (!name.empty() && std::find(built.begin(), built.end(), name) != built.end() &&
+            Platform::testFfmpegEncoder(name)) {
+            return hardware;
+        }
+    }
+    return Hardware::NONE;
+}
+
+// ffmpeg output options for the encoder settings
+static std::vector<std::string> getCodecArgs(const EncoderSettings& settings, const std::string& outputPath) {
+    std::vector<std::string> args;
+    std::string pixelFormat = settings.pixelFormat.empty() ? "yuv420p" : settings.pixelFormat;
+    std::string quality = std::to_string(std::clamp(settings.crf, 0, 63));
+    std::string bitrate = std::to_string(settings.bitrateKbps) + "k";
+    std::string ext = ImageIO::getExtension(outputPath);
+
+    if (settings.hardware != Hardware::NONE) {
+        // Each vendor has its own constant quality option; VideoToolbox's
+        // runs the other way, 100 being best
+        std::string crf = std::to_string(std::clamp(settings.crf, 1, 51));
+        args = {"-c:v", getHardwareEncoder(settings.codec, settings.hardware)};
+        if (settings.bitrateKbps > 0) {
+            args.insert(args.end(), {"-b:v", bitrate});
+        } else if (settings.hardware == Hardware::NVENC) {
+            args.insert(args.end(), {"-rc", "vbr", "-cq", crf, "-b:v", "0"});
+        } else if (settings.hardware == Hardware::QSV) {
+            args.insert(args.end(), {"-global_quality", crf});
+        } else if (settings.hardware == Hardware::VIDEOTOOLBOX) {
+            args.insert(args.end(), {"-q:v", std::to_string(std::clamp(100 - settings.crf * 2, 1, 100))});
+        } else if (settings.hardware == Hardware::AMF) {
+            args.insert(args.end(), {"-rc", "cqp", "-qp_i", crf, "-qp_p", crf});
+        }
+        if (settings.codec == Codec::H265 && (ext == "mp4" || ext == "mov" || ext == "m4v")) {
+            args.insert(args.end(), {"-tag:v", "hvc1"});
+        }
+        args.insert(args.end(), {"-pix_fmt", settings.pixelFormat.empty() ? "nv12" : settings.pixelFormat});
+        return args;
+    }
+
+    switch (settings.codec) {
+        case Codec::H264:
+        case Codec::H265:
+            args = {"-c:v", settings.codec == Codec::H264 ? "libx264" : "libx265", "-preset", settings.preset};
+            if (settings.bitrateKbps > 0) args.insert(args.end(), {"-b:v", bitrate});
+            else args.insert(args.end(), {"-crf", std::to_string(std::clamp(settings.crf, 0, 51))});
+            // QuickTime only plays H.265 in MP4 and MOV with this tag
+            if (settings.codec == Codec::H265 && (ext == "mp4" || ext == "mov" || ext == "m4v")) {
+                args.insert(args.end(), {"-tag:v", "hvc1"});
+            }
+            break;
+        case Codec::VP9:
+        case Codec::AV1:
+            // Constant quality needs the bitrate set to 0
+            args = {"-c:v", settings.codec == Codec::VP9 ? "libvpx-vp9" : "libaom-av1", "-row-mt", "1"};
+            if (settings.bitrateKbps > 0) args.insert(args.end(), {"-b:v", bitrate});
+            else args.insert(args.end(), {"-crf", quality, "-b:v", "0"});
+            break;
+        case Codec::PRORES:
+            args = {"-c:v", "prores_ks", "-profile:v", "3"};
+            if (settings.pixelFormat.empty()) pixelFormat = "yuv422p10le";
+            break;
+        default:
+            break;
+    }
+    args.insert(args.end(), {"-pix_fmt", pixelFormat});
+    return args;
+}
+
+// Find the source frame that best matches the first one, so that cutting the
+// clip just before it loops back to the start with the smallest visible jump.
+// The clip is frameCount frames from firstFrame (0 = to the end); the result
+// counts from firstFrame
+static int findLoopPoint(const std::string& inputPath, int firstFrame, int frameCount, double minFraction) {
+    cv::VideoCapture cap(inputPath);
+    if (!cap.isOpened()) return -1;
+    if (firstFrame > 0) cap.set(cv::CAP_PROP_POS_FRAMES, firstFrame);
+
+    int minIndex = std::max(1, static_cast<int>(frameCount * minFraction));
+
+    cv::Mat frame, small, first;
+    int index = 0;
+    int bestIndex = -1;
+    double bestDiff = std::numeric_limits<double>::max();
+
+    while ((frameCount <= 0 || index < frameCount) && cap.read(frame)) {
+        // Compare small grayscale thumbnails, enough to judge overall similarity
+        cv::resize(frame, small, cv::Size(64, 64), 0, 0, cv::INTER_AREA);
+        cv::cvtColor(small, small, cv::COLOR_BGR2GRAY);
+
+        if (index == 0) {
+            first = small.clone();
+        } else if (index >= minIndex) {
+            double diff = cv::norm(small, first, cv::NORM_L1);
+            if (diff < bestDiff) {
+                bestDiff = diff;
+                bestIndex = index;
+            }
+        }
+        index++;
+    }
+
+    return bestIndex;
+}
+
+// Phone videos store rotation as display metadata rather than rotated frames.
+// It's applied here instead of by the backend, so it works the same on every
+// backend and options.rotation can override it. Turns off auto-rotation on cap
+static int frameRotation(cv::VideoCapture& cap, const Options& options) {
+    cap.set(cv::CAP_PROP_ORIENTATION_AUTO, 0);
+    int rotation = options.rotation >= 0 ? options.rotation
+                                         : static_cast<int>(cap.get(cv::CAP_PROP_ORIENTATION_META));
+    return Transform::normalizeRotation(rotation);
+}
+
+// Rotate a frame clockwise by a multiple of 90 degrees
+static void rotateFrame(cv::Mat& frame, int rotation) {
+    switch (rotation) {
+        case 90: cv::rotate(frame, frame, cv::ROTATE_90_CLOCKWISE); break;
+        case 180: cv::rotate(frame, frame, cv::ROTATE_180); break;
+        case 270: cv::rotate(frame, frame, cv::ROTATE_90_COUNTERCLOCKWISE); break;
+        default: break;
+    }
+}
+
+// FNV-1a hash of a frame's pixel data
+static uint64_t hashFrame(const cv::Mat& frame) {
+    uint64_t hash = 14695981039346656037ULL;
+    for (int y = 0; y < frame.rows; ++y) {
+        const uchar* row = frame.ptr<uchar>(y);
+        size_t rowBytes = frame.cols * frame.elemSize();
+        for (size_t i = 0; i < rowBytes; ++i) {
+            hash = (hash ^ row[i]) * 1099511628211ULL;
+        }
+    }
+    return hash;
+}
+
+static std::string toHex(uint64_t value) {
+    char buffer[17];
+    std::snprintf(buffer, sizeof(buffer), "%016llx", static_cast<unsigned long long>(value));
+    return buffer;
+}
+
+// Dither a frame, reusing the result from the on-disk cache when the same frame
+// was already processed with identical settings
+static cv::Mat ditherCached(const cv::Mat& frame, const Dithering::Parameters& params,
+                            const std::filesystem::path& cacheDir) {
+    if (cacheDir.empty()) {
+        return Dithering::ditherImage(frame, params);
+    }
+
+    // A per-frame seed or a cycled matrix phase can dither the same picture
+    // differently, so the frame's own settings are part of the key
+    uint64_t key = hashFrame(frame) ^ (Dithering::hashParameters(params) * 0x9e3779b97f4a7c15ULL);
+    std::filesystem::path cachePath = cacheDir / (toHex(key) + ".png");
+    cv::Mat cached = cv::imread(cachePath.string(), cv::IMREAD_COLOR);
+    if (!cached.empty() && cached.size() == frame.size()) {
+        return cached;
+    }
+
+    // Frames are dithered in parallel and the same picture can be in flight
+    // twice, so each thread writes its own file and moves it into place
+    cv::Mat dithered = Dithering::ditherImage(frame, params);
+    std::filesystem::path tempPath = cacheDir /
+        (toHex(key) + "." + std::to_string(std::hash<std::thread::id>{}(std::this_thread::get_id())) + ".png");
+    std::error_code ec;
+    if (cv::imwrite(tempPath.string(), dithered)) std::filesystem::rename(tempPath, cachePath, ec);
+    if (ec) std::filesystem::remove(tempPath, ec);
+    return dithered;
+}
+
+// Generate one adaptive palette from frames sampled across the whole clip,
+// so the colors don't shift from frame to frame
+static std::vector<cv::Vec3b> generateLockedPalette(const std::vector<cv::Mat>& frames,
+                                                    const Dithering::Parameters& params) {
+    std::vector<cv::Mat> samples;
+    cv::Mat small;
+    for (const cv::Mat& frame : frames) {
+        cv::resize(frame, small, cv::Size(128, 128), 0, 0, cv::INTER_AREA);
+        samples.push_back(Dithering::preprocessImage(small, params));
+    }
+    if (samples.empty()) return {};
+
+    cv::Mat combined;
+    cv::vconcat(samples, combined);
+    return Dithering::generatePalette(combined, params.paletteSize,
+                                      params.paletteMethod, params.seed);
+}
+
+// Settings for an output frame. Noise-based algorithms either repeat the same
+// pattern on every frame (steady, compresses well) or get a fresh one per frame
+// (film grain). Cycled threshold matrices move by an R2 low-discrepancy
+// sequence, so consecutive frames land far apart and offsets within 64 pixels
+// come up evenly, which averages ordered patterns out over time
+static Dithering::Parameters getFrameParams(const Dithering::Parameters& params, const Options& options, int frame) {
+    Dithering::Parameters frameParams = params;
+    if (options.varySeed) {
+        frameParams.seed = params.seed + static_cast<unsigned int>(frame);
+    }
+    if (options.cycleMatrix) {
+        frameParams.phaseX += static_cast<int>(std::fmod(frame * 0.7548776662466927, 1.0) * 64.0);
+        frameParams.phaseY += static_cast<int>(std::fmod(frame * 0.5698402909980532, 1.0) * 64.0);
+    }
+    return frameParams;
+}
+
+// Dithering at a lower resolution and scaling up with nearest-neighbour keeps
+// the dither pixels large enough to survive the encoder
+static cv::Mat ditherFrame(const cv::Mat& frame, const Dithering::Parameters& params, int ditherHeight,
+                           const std::filesystem::path& cacheDir) {
+    if (ditherHeight <= 0 || ditherHeight >= frame.rows) return ditherCached(frame, params, cacheDir);
+
+    int ditherWidth = std::max(1, frame.cols * ditherHeight / frame.rows);
+    cv::Mat small, dithered;
+    cv::resize(frame, small, cv::Size(ditherWidth, ditherHeight), 0, 0, cv::INTER_AREA);
+    cv::resize(ditherCached(small, params, cacheDir), dithered, frame.size(), 0, 0, cv::INTER_NEAREST);
+    return dithered;
+}
+
+// Reports stages and frames, with the speed and time left measured from the
+// start of the stage
+class ProgressReporter {
+public:
+    explicit ProgressReporter(const ProgressCallback& callback) : callback(callback) {}
+
+    bool stage(const std::string& name) {
+        current.stage = name;
+        current.fps = 0.0;
+        current.etaSeconds = -1.0;
+        stageStart = std::chrono::steady_clock::now();
+        stageFrame = current.frame;
+        return !callback || callback(current);
+    }
+
+    // Whether to carry on, without changing what is reported
+    bool keepGoing() {
+        return !callback || callback(current);
+    }
+
+    bool frames(int frame, int totalFrames) {
+        current.frame = frame;
+        current.totalFrames = totalFrames;
+        double elapsed = std::chrono::duration<double>(std::chrono::steady_clock::now() - stageStart).count();
+        if (elapsed > 0.0 && frame > stageFrame) {
+            current.fps = (frame - stageFrame) / elapsed;
+            current.etaSeconds = std::max(0, totalFrames - frame) / current.fps;
+        }
+        return !callback || callback(current);
+    }
+
+private:
+    const ProgressCallback& callback;
+    Progress current;
+    std::chrono::steady_clock::time_point stageStart = std::chrono::steady_clock::now();
+    int stageFrame = 0;
+};
+
+bool analyzeClip(const std::string& inputPath, const Dithering::Parameters& params, const Options& options,
+                 std::vector<cv::Vec3b>& palette, ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+
+    cv::VideoCapture cap(inputPath);
+    if (!cap.isOpened()) {
+        std::error_code ec;
+        if (!std::filesystem::exists(inputPath, ec)) {
+            return fail(Errors::ErrorCode::NOT_FOUND, "No such file: " + inputPath);
+        }
+        return fail(Errors::ErrorCode::DECODE, "Could not open video: " + inputPath);
+    }
+
+    double fps = cap.get(cv::CAP_PROP_FPS);
+    if (fps <= 0.0) fps = 30.0;
+    int frameCount = std::max(0, static_cast<int>(cap.get(cv::CAP_PROP_FRAME_COUNT)));
+    int firstFrame = static_cast<int>(std::lround(std::max(options.startSeconds, 0.0) * fps));
+    int endFrame = options.endSeconds > 0.0 ? static_cast<int>(std::lround(options.endSeconds * fps)) : frameCount;
+    if (frameCount > 0) endFrame = std::min(endFrame, frameCount);
+    int clipFrames = std::max(endFrame - firstFrame, 1);
+    int sampleFrames = std::clamp(options.paletteSampleFrames, 1, clipFrames);
+
+    if (!reporter.stage("Analyzing clip")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    std::vector<cv::Mat> frames;
+    cv::Mat frame;
+    for (int i = 0; i < sampleFrames; ++i) {
+        cap.set(cv::CAP_PROP_POS_FRAMES, firstFrame + static_cast<double>(i) * clipFrames / sampleFrames);
+        if (!cap.read(frame)) break;
+        frames.push_back(frame.clone());
+        if (!reporter.frames(i + 1, sampleFrames)) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+
+    palette = generateLockedPalette(frames, params);
+    if (palette.empty()) return fail(Errors::ErrorCode::DECODE, "Could not read frames from " + inputPath);
+    return true;
+}
+
+// Why ImageIO::saveAnimation failed, judged from the format
+static Errors::Error describeAnimationFailure(const std::string& outputPath) {
+    std::string ext = ImageIO::getExtension(outputPath);
+    if (ext == "webp" && !cv::haveImageWriter(outputPath)) {
+        return {Errors::ErrorCode::UNSUPPORTED_FORMAT, "This OpenCV build can't write WebP: " + outputPath};
+    }
+    if (ext == "gif") {
+        return {Errors::ErrorCode::IO, "Could not write animation (GIF frames need 256 colors or fewer): " + outputPath};
+    }
+    return {Errors::ErrorCode::IO, "Could not write animation: " + outputPath};
+}
+
+std::string getSpriteSheetDescriptor(const std::string& sheetPath) {
+    return std::filesystem::path(sheetPath).replace_extension(".json").string();
+}
+
+// Pack the frames left to right, top to bottom into one image, with the
+// padding transparent, and describe where each one is in a JSON file
+static bool saveSpriteSheet(const std::string& outputPath, const ImageIO::Animation& animation,
+                            const Options& options, Errors::Error& error) {
+    if (animation.frames.empty()) {
+        error = {Errors::ErrorCode::DECODE, "No frames for the sprite sheet"};
+        return false;
+    }
+    const cv::Size cell = animation.frames[0].color.size();
+    const int count = static_cast<int>(animation.frames.size());
+    const int padding = std::max(0, options.sheet.padding);
+    int columns = options.sheet.columns > 0 ? options.sheet.columns
+                                            : static_cast<int>(std::ceil(std::sqrt(static_cast<double>(count))));
+    columns = std::min(columns, count);
+    const int rows = (count + columns - 1) / columns;
+
+    cv::Mat sheet(rows * cell.height + (rows - 1) * padding, columns * cell.width + (columns - 1) * padding,
+                  CV_8UC3, cv::Scalar(0, 0, 0));
+    cv::Mat alpha(sheet.size(), CV_8U, cv::Scalar(0));
+    bool transparent = padding > 0 || count % columns != 0;
+    std::vector<cv::Rect> placed;
+    for (int i = 0; i < count; ++i) {
+        const ImageIO::AnimationFrame& frame = animation.frames[i];
+        cv::Rect rect((i % columns) * (cell.width + padding), (i / columns) * (cell.height + padding),
+                      cell.width, cell.height);
+        frame.color.copyTo(sheet(rect));
+        if (frame.alpha.empty()) {
+            alpha(rect).setTo(255);
+        } else {
+            frame.alpha.copyTo(alpha(rect));
+            transparent = true;
+        }
+        placed.push_back(rect);
+    }
+
+    ImageIO::ExportOptions exportOptions;
+    exportOptions.encode = options.encode;
+    if (!ImageIO::saveImage(outputPath, sheet, transparent ? alpha : cv::Mat(), exportOptions)) {
+        error = ImageIO::describeSaveFailure(outputPath);
+        error.message += ": " + outputPath;
+        return false;
+    }
+
+    Json frames = Json::array();
+    for (int i = 0; i < count; ++i) {
+        frames.push(Json::object()
+                        .set("x", Json::of(placed[i].x))
+                        .set("y", Json::of(placed[i].y))
+                        .set("w", Json::of(placed[i].width))
+                        .set("h", Json::of(placed[i].height))
+                        .set("durationMs", Json::of(animation.frames[i].delayMs)));
+    }
+    Json descriptor = Json::object()
+                          .set("image", Json::of(std::filesystem::path(outputPath).filename().string()))
+                          .set("frameWidth", Json::of(cell.width))
+                          .set("frameHeight", Json::of(cell.height))
+                          .set("columns", Json::of(columns))
+                          .set("rows", Json::of(rows))
+                          .set("padding", Json::of(padding))
+                          .set("frameCount", Json::of(count))
+                          .set("frames", std::move(frames));
+    std::string descriptorPath = getSpriteSheetDescriptor(outputPath);
+    std::ofstream file(descriptorPath);
+    file << writeJson(descriptor) << "\n";
+    if (!file) {
+        error = {Errors::ErrorCode::IO, "Could not write the sprite sheet descriptor: " + descriptorPath};
+        return false;
+    }
+    return true;
+}
+
+// Closes the ffmpeg pipe if processVideo leaves with it still open, e.g.
+// when a frame worker throws
+struct EncoderGuard {
+    FILE*& encoder;
+    const std::string& output;
+    ~EncoderGuard() {
+        if (encoder) Platform::finishVideoEncoder(encoder, output);
+    }
+};
+
+// Restores OpenCV's thread count however processVideo leaves. The count is
+// process-wide, so it holds for other OpenCV work (the GUI preview) meanwhile
+struct ThreadCountGuard {
+    int previous = cv::getNumThreads();
+    ~ThreadCountGuard() { cv::setNumThreads(previous); }
+};
+
+// Whether the settings need ffmpeg rather than OpenCV's writer, whose MPEG-4
+// only goes into MP4, MOV and AVI
+static bool usesFfmpeg(const EncoderSettings& settings, const std::string& outputPath) {
+    std::string ext = ImageIO::getExtension(outputPath);
+    bool openCvContainer = ext == "mp4" || ext == "m4v" || ext == "mov" || ext == "avi";
+    return settings.codec != Codec::OPENCV || settings.hardware != Hardware::NONE || !openCvContainer;
+}
+
+// The codec ffmpeg encodes with: without one, VP9 for WebM and H.264 otherwise
+static Codec getEncoderCodec(const EncoderSettings& settings, const std::string& outputPath) {
+    if (settings.codec != Codec::OPENCV) return settings.codec;
+    return ImageIO::getExtension(outputPath) == "webm" ? Codec::VP9 : Codec::H264;
+}
+
+// Start ffmpeg for the settings, saying which GPU encodes. Null without ffmpeg
+static FILE* startEncoder(const EncoderSettings& requested, const std::string& outputPath, cv::Size size,
+                          double fps) {
+    EncoderSettings settings = requested;
+    settings.codec = getEncoderCodec(settings, outputPath);
+    settings.hardware = resolveHardware(settings);
+    if (settings.hardware != Hardware::NONE) {
+        std::cout << "Encoding with " << getHardwareName(settings.hardware) << std::endl;
+    } else if (requested.hardware != Hardware::NONE && requested.hardware != Hardware::AUTO) {
+        std::cerr << "Warning: " << getHardwareName(requested.hardware) << " can't encode "
+                  << getCodecName(settings.codec) << " here, using software" << std::endl;
+    }
+    return Platform::startVideoEncoder(outputPath, size.width, size.height, fps, getCodecArgs(settings, outputPath));
+}
+
+// Message for an encoder that couldn't be started
+static std::string describeMissingEncoder(const EncoderSettings& settings, const std::string& outputPath) {
+    return getCodecName(getEncoderCodec(settings, outputPath)) +
+           " output needs ffmpeg, which wasn't found (see --check-ffmpeg)";
+}
+
+// Send a frame to ffmpeg; false on a short write
+static bool writeEncoderFrame(FILE* encoder, const cv::Mat& frame) {
+    // ffmpeg reads tightly packed BGR rows
+    cv::Mat packed = frame.isContinuous() ? frame : frame.clone();
+    size_t bytes = packed.total() * packed.elemSize();
+    return std::fwrite(packed.data, 1, bytes, encoder) == bytes;
+}
+
+// Process video frame by frame
+bool processVideo(const std::string& inputPath, const std::string& outputPath,
+                  const Dithering::Parameters& inputParams, const Options& options,
+                  ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+
+    cv::VideoCapture cap(inputPath);
+    if (!cap.isOpened()) {
+        std::error_code ec;
+        if (!std::filesystem::exists(inputPath, ec)) {
+            return fail(Errors::ErrorCode::NOT_FOUND, "No such file: " + inputPath);
+        }
+        return fail(Errors::ErrorCode::DECODE, "Could not open video: " + inputPath);
+    }
+
+    int rotation = frameRotation(cap, options);
+
+    int frameWidth = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_WIDTH));
+    int frameHeight = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_HEIGHT));
+    if (rotation == 90 || rotation == 270) {
+        std::swap(frameWidth, frameHeight);
+    }
+
+    // Frames are scaled as they're read, so large sources aren't dithered at
+    // full size, and enlarged after dithering
+    cv::Size scaledSize = getFrameSize(cv::Size(frameWidth, frameHeight), options);
+    int upscale = std::max(1, options.upscale);
+    frameWidth = scaledSize.width * upscale;
+    frameHeight = scaledSize.height * upscale;
+    double sourceFps = cap.get(cv::CAP_PROP_FPS);
+    int sourceFrames = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_COUNT));
+    if (sourceFps <= 0.0) sourceFps = 30.0;
+
+    double outputFps = options.outputFps > 0.0 ? options.outputFps : sourceFps;
+
+    // A trimmed clip starts at the frame nearest the start time. From here on
+    // source frames are counted from there, and endFrame (if any) is the
+    // first one left out
+    int firstFrame = static_cast<int>(std::lround(std::max(options.startSeconds, 0.0) * sourceFps));
+    int endFrame = -1;
+    if (firstFrame > 0) {
+        if (sourceFrames > 0 && firstFrame >= sourceFrames) {
+            return fail(Errors::ErrorCode::INVALID_ARGUMENT, "The start time is past the end of " + inputPath);
+        }
+        cap.set(cv::CAP_PROP_POS_FRAMES, firstFrame);
+        if (sourceFrames > 0) sourceFrames -= firstFrame;
+    }
+    if (options.endSeconds > 0.0) {
+        endFrame = static_cast<int>(std::lround(options.endSeconds * sourceFps)) - firstFrame;
+        if (endFrame <= 0) {
+            return fail(Errors::ErrorCode::INVALID_ARGUMENT, "The end time must come after the start time");
+        }
+        if (sourceFrames <= 0 || endFrame < sourceFrames) sourceFrames = endFrame;
+    }
+
+    Dithering::Parameters params = inputParams;
+    if (options.lockPalette && params.paletteMode == Dithering::PaletteMode::ADAPTIVE) {
+        std::vector<cv::Vec3b> palette;
+        Errors::Error analyzeError;
+        if (analyzeClip(inputPath, params, options, palette, progress, &analyzeError)) {
+            params.customPalette = palette;
+            params.paletteMode = Dithering::PaletteMode::CUSTOM;
+        } else if (analyzeError.code == Errors::ErrorCode::CANCELLED) {
+            return fail(analyzeError.code, analyzeError.message);
+        }
+    }
+
+    // Seamless loops stop just before the frame that matches the start
+    if (options.loopMode == LoopMode::SEAMLESS_TRIM) {
+        if (!reporter.stage("Finding loop point")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+        int loopEnd = findLoopPoint(inputPath, firstFrame, sourceFrames, options.minLoopFraction);
+        if (loopEnd > 0) {
+            std::cout << "Seamless loop point: frame " << loopEnd << " of " << sourceFrames << std::endl;
+            sourceFrames = loopEnd;
+            endFrame = loopEnd;
+        }
+    }
+
+    bool pingPong = options.loopMode == LoopMode::PING_PONG;
+    int totalFrames = static_cast<int>(sourceFrames * outputFps / sourceFps);
+    if (pingPong) totalFrames = std::max(totalFrames * 2 - 2, totalFrames);
+
+    // Cached frames live in one directory per settings combination
+    std::filesystem::path cacheDir;
+    if (!options.cacheDir.empty()) {
+        cacheDir = std::filesystem::path(options.cacheDir) / toHex(Dithering::hashParameters(params));
+        std::error_code ec;
+        std::filesystem::create_directories(cacheDir, ec);
+        if (ec) {
+            std::cerr << "Warning: Could not create frame cache: " << cacheDir.string() << std::endl;
+            cacheDir.clear();
+        }
+    }
+
+    // Animated image outputs and sprite sheets collect the frames and are
+    // written at the end. Sprite sheets keep every frameStep-th frame, and
+    // stop once they have maxFrames
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    bool animatedOutput = options.spriteSheet ||
+                          std::find(animationExtensions.begin(), animationExtensions.end(),
+                                    ImageIO::getExtension(outputPath)) != animationExtensions.end();
+    ImageIO::Animation animation;
+    int frameStep = options.spriteSheet ? std::max(1, options.sheet.frameStep) : 1;
+    int outputLimit = options.spriteSheet && options.sheet.maxFrames > 0 ? options.sheet.maxFrames * frameStep : 0;
+    if (outputLimit > 0) totalFrames = std::min(totalFrames, outputLimit);
+    int collected = 0;
+    if (!options.spriteSheet && ImageIO::getExtension(outputPath) == "gif" && outputFps > 50.0) {
+        std::cerr << "Warning: Most browsers slow GIF frames shorter than 20 ms down to 100 ms; "
+                     "use 50 fps or less" << std::endl;
+    }
+    // Keep long jobs from making the rest of the machine unresponsive. Before
+    // ffmpeg starts, so the encoder inherits the lower priority (except on
+    // Windows, where priority is per thread and isn't passed on)
+    if (options.niceness > 0 && !Platform::lowerThreadPriority(options.niceness)) {
+        std::cerr << "Warning: Could not lower job priority" << std::endl;
+    }
+    cv::VideoWriter writer;
+    FILE* encoder = nullptr;
+    bool encoderFailed = false;
+    EncoderGuard encoderGuard{encoder, outputPath};
+    auto closeEncoder = [&]() {
+        bool finished = Platform::finishVideoEncoder(encoder, outputPath);
+        encoder = nullptr;
+        return finished;
+    };
+    if (!animatedOutput && usesFfmpeg(options.encoder, outputPath)) {
+        encoder = startEncoder(options.encoder, outputPath, cv::Size(frameWidth, frameHeight), outputFps);
+        if (!encoder) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, describeMissingEncoder(options.encoder, outputPath));
+        }
+    } else if (!animatedOutput) {
+        writer.open(outputPath, cv::VideoWriter::fourcc('m', 'p', '4', 'v'), outputFps,
+                    cv::Size(frameWidth, frameHeight));
+        if (!writer.isOpened()) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, "Could not create video: " + outputPath);
+        }
+    }
+    // Say before the job, not after it, when the sound can't be added
+    bool addAudio = !animatedOutput && options.keepAudio && !pingPong;
+    if (addAudio && Platform::findFfmpegTool("ffmpeg").path.empty()) {
+        std::cerr << "Warning: ffmpeg wasn't found (see --check-ffmpeg), the video will be silent" << std::endl;
+        addAudio = false;
+    }
+    auto writeFrame = [&](const cv::Mat& frame) {
+        if (encoder) {
+            if (!encoderFailed && !writeEncoderFrame(encoder, frame)) encoderFailed = true;
+            return;
+        }
+        if (!animatedOutput) {
+            writer.write(frame);
+            return;
+        }
+        // Delays are rounded from the start of the clip, so they don't drift
+        double index = static_cast<double>(collected++);
+        if (static_cast<int>(index) % frameStep != 0 || (outputLimit > 0 && index >= outputLimit)) return;
+        ImageIO::AnimationFrame animationFrame;
+        animationFrame.color = frame.clone();
+        animationFrame.delayMs = static_cast<int>(std::lround((index + frameStep) * 1000.0 / outputFps) -
+                                                  std::lround(index * 1000.0 / outputFps));
+        animation.frames.push_back(animationFrame);
+    };
+
+    // Walk output timestamps over the source timeline, keeping one frame of
+    // lookahead so frames can be duplicated, dropped or blended
+    auto readFrame = [&cap, rotation, scaledSize](cv::Mat& frame) {
+        if (!cap.read(frame)) return false;
+        rotateFrame(frame, rotation);
+        if (scaledSize.area() > 0 && frame.size() != scaledSize) {
+            cv::resize(frame, frame, scaledSize, 0, 0, cv::INTER_AREA);
+        }
+        return true;
+    };
+
+    cv::Mat current, next;
+    if (!readFrame(current)) {
+        closeEncoder();
+        return fail(Errors::ErrorCode::DECODE, "Could not read frames from " + inputPath);
+    }
+    bool hasNext = readFrame(next);
+
+    ThreadCountGuard threadCountGuard;
+    if (options.threads > 0) {
+        cv::setNumThreads(options.threads);
+    }
+    // OpenCV's pool threads can't be lowered, so a niced job leaves the
+    // parallelism to its frame workers, which are
+    if (options.niceness > 0) cv::setNumThreads(0);
+
+    double sourcePerOutput = sourceFps / outputFps;
+    int sourceIndex = 0;
+    int outputIndex = 0;
+
+    // Ping-pong keeps the dithered frames (PNG-compressed, which is very
+    // effective on dithered output) to replay them in reverse afterwards
+    std::vector<std::vector<uchar>> encodedFrames;
+
+    // Frames are dithered on worker threads and written in order. Waiting for
+    // the oldest once jobs are in flight keeps only that many frames in memory
+    int jobs = options.jobs > 0 ? options.jobs
+             : options.threads > 0 ? options.threads
+             : std::clamp(static_cast<int>(std::thread::hardware_concurrency()), 1, 8);
+    std::deque<std::future<cv::Mat>> pending;
+    int written = 0;
+    bool cancelled = false;
+    cv::Mat carriedInput, lastOutput;
+    auto finishOldest = [&]() {
+        cv::Mat dithered = pending.front().get();
+        pending.pop_front();
+        writeFrame(dithered);
+        written++;
+        lastOutput = dithered;
+
+        if (pingPong) {
+            encodedFrames.emplace_back();
+            cv::imencode(".png", dithered, encodedFrames.back());
+        }
+
+        if (!reporter.frames(written, std::max(totalFrames, written))) cancelled = true;
+    };
+
+    cancelled = !reporter.stage("Dithering");
+    while (!cancelled && (outputLimit == 0 || outputIndex < outputLimit)) {
+        double position = outputIndex * sourcePerOutput;
+
+        if (position >= sourceIndex + 1) {
+            if (!hasNext || sourceIndex + 1 == endFrame) break;
+            std::swap(current, next);
+            hasNext = readFrame(next);
+            sourceIndex++;
+            continue;
+        }
+
+        double fraction = position - sourceIndex;
+        cv::Mat frame;
+        if (options.frameRateMode == FrameRateMode::BLEND && hasNext && fraction > 0.0) {
+            cv::addWeighted(current, 1.0 - fraction, next, fraction, 0.0, frame);
+        } else if (options.frameRateMode == FrameRateMode::NEAREST && hasNext && fraction >= 0.5) {
+            frame = next;
+        } else {
+            frame = current;
+        }
+
+        Dithering::Parameters frameParams = getFrameParams(params, options, outputIndex);
+
+        // Part of the last frame's quantization error goes into this one, so
+        // areas that came out too dark come out lighter next time. This needs
+        // the last frame's result, so frames are dithered one at a time
+        if (options.temporalCarry > 0.0f) {
+            while (!pending.empty()) finishOldest();
+            if (!carriedInput.empty() && !lastOutput.empty()) {
+                cv::Mat output = lastOutput;
+                if (output.size() != carriedInput.size()) {
+                    cv::resize(output, output, carriedInput.size(), 0, 0, cv::INTER_NEAREST);
+                }
+                cv::Mat carried, error;
+                frame.convertTo(carried, CV_32F);
+                cv::subtract(carriedInput, output, error, cv::Mat(), CV_32F);
+                cv::scaleAdd(error, std::min(options.temporalCarry, 1.0f), carried, carried);
+                // frame may share the decoded frame's buffer, so it gets a new one
+                frame = cv::Mat();
+                carried.convertTo(frame, CV_8U);
+            }
+            // A copy, since the decoded frames are read into the same buffers again
+            carriedInput = frame.clone();
+        }
+
+        if (pending.size() >= static_cast<size_t>(jobs)) finishOldest();
+        pending.push_back(std::async(std::launch::async,
+            [frame = frame.clone(), frameParams, &options, &cacheDir, upscale]() {
+                if (options.niceness > 0) Platform::lowerThreadPriority(options.niceness);
+                cv::Mat dithered = ditherFrame(frame, frameParams, options.ditherHeight, cacheDir);
+                if (upscale > 1) cv::resize(dithered, dithered, cv::Size(), upscale, upscale, cv::INTER_NEAREST);
+                return dithered;
+            }));
+        outputIndex++;
+    }
+    while (!pending.empty() && !cancelled) finishOldest();
+    for (auto& frame : pending) frame.wait();
+
+    // Reverse pass, skipping both ends so they aren't shown twice in a row
+    if (encodedFrames.size() > 2 && !cancelled) cancelled = !reporter.stage("Reversing");
+    for (int i = static_cast<int>(encodedFrames.size()) - 2; i >= 1 && !cancelled; --i) {
+        writeFrame(cv::imdecode(encodedFrames[i], cv::IMREAD_COLOR));
+        outputIndex++;
+
+        if (!reporter.frames(outputIndex, std::max(totalFrames, outputIndex))) cancelled = true;
+    }
+
+    cap.release();
+    writer.release();
+    if (cancelled) {
+        // ffmpeg finishes the frames it has; the partial file goes either way
+        closeEncoder();
+        std::error_code ec;
+        std::filesystem::remove(outputPath, ec);
+        return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+    // Past here the frames are all written, so only adding the audio can still be cancelled
+    if (encoder) reporter.stage("Encoding");
+    if (encoder && (!closeEncoder() || encoderFailed)) {
+        return fail(Errors::ErrorCode::ENCODER_MISSING,
+                    "ffmpeg could not encode " + getCodecName(getEncoderCodec(options.encoder, outputPath)) +
+                    " into " + outputPath + " (is the encoder installed, and does the container take the codec?)");
+    }
+
+    // OpenCV writes the picture only, so the sound is added afterwards. Played
+    // backwards it would make no sense, so ping-pong loops stay silent
+    if (addAudio) {
+        reporter.stage("Adding audio");
+        std::string withAudio = outputPath + ".audio" + std::filesystem::path(outputPath).extension().string();
+        std::error_code ec;
+        bool muxed = Platform::muxAudio(outputPath, inputPath, withAudio, firstFrame / sourceFps,
+                                        [&reporter]() { return reporter.keepGoing(); });
+        if (!muxed && !reporter.keepGoing()) {
+            std::filesystem::remove(withAudio, ec);
+            std::filesystem::remove(outputPath, ec);
+            return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+        }
+        if (muxed) std::filesystem::rename(withAudio, outputPath, ec);
+        if (!muxed || ec) {
+            std::filesystem::remove(withAudio, ec);
+            std::cerr << "Warning: Could not copy the audio (is ffmpeg installed?), the video is silent" << std::endl;
+        }
+    }
+    if (animatedOutput) reporter.stage("Saving");
+    if (options.spriteSheet) {
+        Errors::Error saveError;
+        if (!saveSpriteSheet(outputPath, animation, options, saveError)) return fail(saveError.code, saveError.message);
+    } else if (animatedOutput && !ImageIO::saveAnimation(outputPath, animation, options.encode)) {
+        Errors::Error saveError = describeAnimationFailure(outputPath);
+        return fail(saveError.code, saveError.message);
+    }
+    return true;
+}
+
+bool previewFrame(const std::string& inputPath, double seconds, const Dithering::Parameters& inputParams,
+                  const Options& options, cv::Mat& result, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    cv::VideoCapture cap(inputPath);
+    if (!cap.isOpened()) {
+        std::error_code ec;
+        if (!std::filesystem::exists(inputPath, ec)) {
+            return fail(Errors::ErrorCode::NOT_FOUND, "No such file: " + inputPath);
+        }
+        return fail(Errors::ErrorCode::DECODE, "Could not open video: " + inputPath);
+    }
+
+    double fps = cap.get(cv::CAP_PROP_FPS);
+    if (fps <= 0.0) fps = 30.0;
+    int frameCount = static_cast<int>(cap.get(cv::CAP_PROP_FRAME_COUNT));
+    int frameIndex = static_cast<int>(std::lround(std::max(seconds, 0.0) * fps));
+    if (frameCount > 0 && frameIndex >= frameCount) {
+        return fail(Errors::ErrorCode::INVALID_ARGUMENT, "The preview time is past the end of " + inputPath);
+    }
+
+    // Same palette and noise as this frame gets in the full job
+    Dithering::Parameters params = inputParams;
+    std::vector<cv::Vec3b> palette;
+    if (options.lockPalette && params.paletteMode == Dithering::PaletteMode::ADAPTIVE &&
+        analyzeClip(inputPath, params, options, palette)) {
+        params.customPalette = palette;
+        params.paletteMode = Dithering::PaletteMode::CUSTOM;
+    }
+    double outputFps = options.outputFps > 0.0 ? options.outputFps : fps;
+    double clipSeconds = std::max(seconds - std::max(options.startSeconds, 0.0), 0.0);
+    params = getFrameParams(params, options, static_cast<int>(std::lround(clipSeconds * outputFps)));
+
+    int rotation = frameRotation(cap, options);
+
+    cv::Mat frame;
+    cap.set(cv::CAP_PROP_POS_FRAMES, frameIndex);
+    if (!cap.read(frame)) {
+        char time[32];
+        std::snprintf(time, sizeof(time), "%.2f s", seconds);
+        return fail(Errors::ErrorCode::DECODE,
+                    std::string("Could not read the frame at ") + time + " from " + inputPath);
+    }
+    rotateFrame(frame, rotation);
+    cv::Size scaledSize = getFrameSize(frame.size(), options);
+    if (scaledSize != frame.size()) cv::resize(frame, frame, scaledSize, 0, 0, cv::INTER_AREA);
+
+    result = ditherFrame(frame, params, options.ditherHeight, {});
+    int upscale = std::max(1, options.upscale);
+    if (upscale > 1) cv::resize(result, result, cv::Size(), upscale, upscale, cv::INTER_NEAREST);
+    return true;
+}
+
+bool processAnimation(const std::string& inputPath, const std::string& outputPath,
+                      const Dithering::Parameters& inputParams, const Options& options,
+                      ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    if (std::find(animationExtensions.begin(), animationExtensions.end(), ImageIO::getExtension(outputPath)) ==
+        animationExtensions.end()) {
+        return fail(Errors::ErrorCode::UNSUPPORTED_FORMAT,
+                    "Animations can be saved as GIF, WebP or APNG: " + outputPath);
+    }
+    ImageIO::Animation animation;
+    if (!ImageIO::loadGifAnimation(inputPath, animation)) {
+        Errors::Error loadError = ImageIO::describeLoadFailure(inputPath);
+        return fail(loadError.code, loadError.message + ": " + inputPath);
+    }
+
+    // A locked palette also lets every frame share the GIF's global color table
+    Dithering::Parameters params = inputParams;
+    if (options.lockPalette && params.paletteMode == Dithering::PaletteMode::ADAPTIVE) {
+        int frameCount = static_cast<int>(animation.frames.size());
+        int sampleFrames = std::max(1, std::min(options.paletteSampleFrames, frameCount));
+        std::vector<cv::Mat> samples;
+        for (int i = 0; i < sampleFrames; ++i) {
+            samples.push_back(animation.frames[static_cast<size_t>(i) * frameCount / sampleFrames].color);
+        }
+        std::vector<cv::Vec3b> palette = generateLockedPalette(samples, params);
+        if (!palette.empty()) {
+            params.customPalette = palette;
+            params.paletteMode = Dithering::PaletteMode::CUSTOM;
+        }
+    }
+
+    int total = static_cast<int>(animation.frames.size());
+    if (!reporter.stage("Dithering")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    for (int i = 0; i < total; ++i) {
+        ImageIO::AnimationFrame& frame = animation.frames[i];
+        frame.color = ditherFrame(frame.color, getFrameParams(params, options, i), options.ditherHeight, {});
+
+        if (!reporter.frames(i + 1, total)) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+
+    reporter.stage("Saving");
+    if (!ImageIO::saveAnimation(outputPath, animation, options.encode)) {
+        Errors::Error saveError = describeAnimationFailure(outputPath);
+        return fail(saveError.code, saveError.message);
+    }
+    return true;
+}
+
+bool captureScreen(const cv::Rect& region, cv::Mat& frame, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    std::vector<unsigned char> data;
+    if (!Platform::captureScreen(data)) {
+        return fail(Errors::ErrorCode::IO,
+                    "Could not capture the screen (is grim, maim or ImageMagick installed, or is permission denied?)");
+    }
+    cv::Mat alpha;
+    if (!ImageIO::loadImageFromMemory(data, frame, alpha)) {
+        return fail(Errors::ErrorCode::DECODE, "Could not decode the screen capture");
+    }
+    if (region.area() > 0) {
+        cv::Rect clipped = region & cv::Rect(0, 0, frame.cols, frame.rows);
+        if (clipped.area() == 0) {
+            char size[32];
+            std::snprintf(size, sizeof(size), "%dx%d", frame.cols, frame.rows);
+            return fail(Errors::ErrorCode::INVALID_ARGUMENT, std::string("The region is outside the screen (") +
+                        size + ")");
+        }
+        frame = frame(clipped).clone();
+    }
+    return true;
+}
+
+bool recordScreen(const cv::Rect& region, double seconds, const std::string& outputPath,
+                  const Dithering::Parameters& params, const Options& options,
+                  ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+
+    double fps = options.outputFps > 0.0 ? options.outputFps : 5.0;
+    int totalFrames = std::max(1, static_cast<int>(std::ceil(seconds * fps)));
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    bool animatedOutput = std::find(animationExtensions.begin(), animationExtensions.end(),
+                                    ImageIO::getExtension(outputPath)) != animationExtensions.end();
+    int upscale = std::max(1, options.upscale);
+
+    auto captureFrame = [&](int index, cv::Mat& result, Errors::Error* captureError) {
+        cv::Mat frame;
+        if (!captureScreen(region, frame, captureError)) return false;
+        cv::Size scaledSize = getFrameSize(frame.size(), options);
+        if (scaledSize != frame.size()) cv::resize(frame, frame, scaledSize, 0, 0, cv::INTER_AREA);
+        result = ditherFrame(frame, getFrameParams(params, options, index), options.ditherHeight, {});
+        if (upscale > 1) cv::resize(result, result, cv::Size(), upscale, upscale, cv::INTER_NEAREST);
+        return true;
+    };
+
+    // The first capture sets the size, and shows whether capturing works at all
+    cv::Mat frame;
+    if (!reporter.stage("Recording")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    auto start = std::chrono::steady_clock::now();
+    if (!captureFrame(0, frame, error)) return false;
+    cv::VideoWriter writer;
+    if (!animatedOutput) {
+        writer.open(outputPath, cv::VideoWriter::fourcc('m', 'p', '4', 'v'), fps, frame.size());
+        if (!writer.isOpened()) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, "Could not create video: " + outputPath);
+        }
+    }
+
+    ImageIO::Animation animation;
+    int written = 0;
+    bool cancelled = false;
+    while (!cancelled) {
+        // Hold the frame until the next capture is due, filling the slots a
+        // slow capture missed
+        double elapsed = std::chrono::duration<double>(std::chrono::steady_clock::now() - start).count();
+        int due = std::clamp(static_cast<int>(elapsed * fps) + 1, written + 1, totalFrames);
+        if (animatedOutput) {
+            ImageIO::AnimationFrame animationFrame;
+            animationFrame.color = frame;
+            animationFrame.delayMs = static_cast<int>(std::lround(due * 1000.0 / fps) -
+                                                      std::lround(written * 1000.0 / fps));
+            animation.frames.push_back(animationFrame);
+        } else {
+            for (int i = written; i < due; ++i) writer.write(frame);
+        }
+        written = due;
+        if (!reporter.frames(written, totalFrames)) cancelled = true;
+        if (written >= totalFrames || cancelled) break;
+
+        std::this_thread::sleep_until(start + std::chrono::duration<double>(written / fps));
+        cv::Mat next;
+        if (!captureFrame(written, next, nullptr)) {
+            // Keep what was recorded so far rather than losing it all
+            std::cerr << "Warning: Screen capture failed, stopping the recording early" << std::endl;
+            break;
+        }
+        frame = next;
+    }
+
+    writer.release();
+    if (cancelled) {
+        std::error_code ec;
+        std::filesystem::remove(outputPath, ec);
+        return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+    if (animatedOutput) {
+        reporter.stage("Saving");
+        if (!ImageIO::saveAnimation(outputPath, animation, options.encode)) {
+            Errors::Error saveError = describeAnimationFailure(outputPath);
+            return fail(saveError.code, saveError.message);
+        }
+    }
+    return true;
+}
+
+// Colors in an image, counting up to limit
+static int countColors(const cv::Mat& image, int limit) {
+    std::unordered_set<uint32_t> colors;
+    for (int y = 0; y < image.rows; ++y) {
+        for (int x = 0; x < image.cols; ++x) {
+            const cv::Vec3b& pixel = image.at<cv::Vec3b>(y, x);
+            colors.insert((pixel[0] << 16) | (pixel[1] << 8) | pixel[2]);
+            if (static_cast<int>(colors.size()) >= limit) return limit;
+        }
+    }
+    return static_cast<int>(colors.size());
+}
+
+bool exportProgression(const cv::Mat& input, const std::string& outputPath, const Dithering::Parameters& params,
+                       const ProgressionOptions& options, ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+    if (input.empty()) return fail(Errors::ErrorCode::INVALID_ARGUMENT, "There is no image to dither");
+
+    double fps = options.fps > 0.0 ? options.fps : 20.0;
+    // Steps are counted on the image the diffusion works on, smaller when pixelated
+    cv::Size working = Dithering::getWorkingSize(input.size(), params);
+    int pixels = working.area();
+    int step = options.pixelsPerFrame;
+    if (step <= 0 && options.rowsPerFrame > 0) step = options.rowsPerFrame * working.width;
+    if (step <= 0) step = std::max(1, static_cast<int>(std::ceil(pixels / std::max(1.0, options.seconds * fps))));
+    int totalFrames = (pixels + step - 1) / step + 1;
+    int holdFrames = std::max(1, static_cast<int>(std::lround(options.holdSeconds * fps)));
+    int upscale = std::max(1, options.upscale);
+
+    const auto& animationExtensions = ImageIO::getAnimationExtensions();
+    bool animatedOutput = std::find(animationExtensions.begin(), animationExtensions.end(),
+                                    ImageIO::getExtension(outputPath)) != animationExtensions.end();
+    int delayMs = static_cast<int>(std::lround(1000.0 / fps));
+    ImageIO::Animation animation;
+    int written = 0;
+
+    // Videos are written as the frames come, like processVideo's
+    cv::Size frameSize(input.cols * upscale, input.rows * upscale);
+    cv::VideoWriter writer;
+    FILE* encoder = nullptr;
+    bool encoderFailed = false;
+    EncoderGuard encoderGuard{encoder, outputPath};
+    if (!animatedOutput && usesFfmpeg(options.encoder, outputPath)) {
+        encoder = startEncoder(options.encoder, outputPath, frameSize, fps);
+        if (!encoder) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, describeMissingEncoder(options.encoder, outputPath));
+        }
+    } else if (!animatedOutput) {
+        writer.open(outputPath, cv::VideoWriter::fourcc('m', 'p', '4', 'v'), fps, frameSize);
+        if (!writer.isOpened()) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, "Could not create video: " + outputPath);
+        }
+    }
+    auto writeFrame = [&](const cv::Mat& frame) {
+        if (!encoder) {
+            writer.write(frame);
+        } else if (!encoderFailed && !writeEncoderFrame(encoder, frame)) {
+            encoderFailed = true;
+        }
+    };
+
+    if (!reporter.stage("Dithering")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    cv::Mat result = Dithering::ditherWithSnapshots(input, params, step, [&](const cv::Mat& snapshot) {
+        cv::Mat frame;
+        cv::resize(snapshot, frame, cv::Size(), upscale, upscale, cv::INTER_NEAREST);
+        if (animatedOutput) {
+            ImageIO::AnimationFrame animationFrame;
+            animationFrame.color = frame;
+            animationFrame.delayMs = delayMs;
+            animation.frames.push_back(animationFrame);
+        } else {
+            writeFrame(frame);
+        }
+        return reporter.frames(++written, totalFrames);
+    });
+    if (result.empty()) {
+        writer.release();
+        if (encoder) Platform::finishVideoEncoder(encoder, outputPath);
+        encoder = nullptr;
+        std::error_code ec;
+        std::filesystem::remove(outputPath, ec);
+        return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+
+    if (!animatedOutput) {
+        cv::Mat last;
+        cv::resize(result, last, cv::Size(), upscale, upscale, cv::INTER_NEAREST);
+        for (int i = 0; i < holdFrames; ++i) writeFrame(last);
+        writer.release();
+        if (encoder) reporter.stage("Encoding");
+        bool encoded = !encoder || Platform::finishVideoEncoder(encoder, outputPath);
+        encoder = nullptr;
+        if (!encoded || encoderFailed) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING,
+                        "ffmpeg could not encode " + getCodecName(getEncoderCodec(options.encoder, outputPath)) +
+                        " into " + outputPath + " (is the encoder installed, and does the container take the codec?)");
+        }
+        return true;
+    }
+
+    animation.frames.back().delayMs = delayMs * holdFrames;
+    if (ImageIO::getExtension(outputPath) == "gif") {
+        // The result's colors, then evenly spaced grays with what's left. A
+        // pixel that differs from the result isn't finished yet
+        int levels = 256 - countColors(result, 256);
+        const cv::Mat& finished = animation.frames.back().color;
+        if (levels >= 2) {
+            cv::Mat gray, grays;
+            cv::cvtColor(input, gray, cv::COLOR_BGR2GRAY);
+            cv::Mat lut(1, 256, CV_8U);
+            for (int i = 0; i < 256; ++i) {
+                lut.at<uchar>(0, i) = static_cast<uchar>(std::lround(i * (levels - 1) / 255.0) * 255 / (levels - 1));
+            }
+            cv::LUT(gray, lut, gray);
+            cv::resize(gray, gray, finished.size(), 0, 0, cv::INTER_NEAREST);
+            cv::cvtColor(gray, grays, cv::COLOR_GRAY2BGR);
+            for (auto& frame : animation.frames) {
+                cv::Mat differs;
+                cv::compare(frame.color, finished, differs, cv::CMP_NE);
+                cv::cvtColor(differs, differs, cv::COLOR_BGR2GRAY);
+                grays.copyTo(frame.color, differs);
+            }
+        }
+    }
+    if (!reporter.stage("Saving")) return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    if (!ImageIO::saveAnimation(outputPath, animation)) {
+        Errors::Error saveError = describeAnimationFailure(outputPath);
+        return fail(saveError.code, saveError.message);
+    }
+    return true;
+}
+
+// Scale an image to fit the frame, centered on black. Nearest-neighbour keeps
+// dither patterns crisp instead of smearing them
+static cv::Mat fitToFrame(const cv::Mat& image, const cv::Size& frameSize) {
+    if (image.size() == frameSize) return image;
+
+    double scale = std::min(static_cast<double>(frameSize.width) / image.cols,
+                            static_cast<double>(frameSize.height) / image.rows);
+    cv::Size scaledSize(std::max(1, static_cast<int>(image.cols * scale)),
+                        std::max(1, static_cast<int>(image.rows * scale)));
+    cv::Mat scaled;
+    cv::resize(image, scaled, scaledSize, 0, 0, cv::INTER_NEAREST);
+
+    cv::Mat frame = cv::Mat::zeros(frameSize, CV_8UC3);
+    scaled.copyTo(frame(cv::Rect((frameSize.width - scaledSize.width) / 2,
+                                 (frameSize.height - scaledSize.height) / 2,
+                                 scaledSize.width, scaledSize.height)));
+    return frame;
+}
+
+// Build a slideshow from a folder of images, sized to the first image
+bool createSlideshow(const std::string& folder, const std::string& outputPath,
+                     const SlideshowOptions& options, ProgressCallback progress, Errors::Error* error) {
+    auto fail = [error](Errors::ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+    ProgressReporter reporter(progress);
+
+    std::vector<std::string> files;
+    std::error_code ec;
+    for (const auto& entry : std::filesystem::directory_iterator(folder, ec)) {
+        if (!entry.is_regular_file()) continue;
+        try {
+            std::string path = entry.path().string();
+            if (ImageIO::isImageFile(path)) files.push_back(path);
+        } catch (const std::exception&) {
+            // Name can't be represented as a narrow string on this system
+            std::cerr << "Warning: Skipping file with unsupported name in " << folder << std::endl;
+        }
+    }
+    if (ec || files.empty()) {
+        return fail(Errors::ErrorCode::NOT_FOUND, "No images found in " + folder);
+    }
+    std::sort(files.begin(), files.end());
+
+    auto loadSlide = [](const std::string& path) {
+        cv::Mat color, alpha;
+        ImageIO::loadImage(path, color, alpha);
+        return color;
+    };
+
+    cv::Mat first = loadSlide(files[0]);
+    if (first.empty()) {
+        Errors::Error loadError = ImageIO::describeLoadFailure(files[0]);
+        return fail(loadError.code, loadError.message + ": " + files[0]);
+    }
+    cv::Size frameSize = first.size();
+
+    double fps = options.fps > 0.0 ? options.fps : 30.0;
+    cv::VideoWriter writer;
+    FILE* encoder = nullptr;
+    bool encoderFailed = false;
+    EncoderGuard encoderGuard{encoder, outputPath};
+    if (usesFfmpeg(options.encoder, outputPath)) {
+        encoder = startEncoder(options.encoder, outputPath, frameSize, fps);
+        if (!encoder) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, describeMissingEncoder(options.encoder, outputPath));
+        }
+    } else {
+        writer.open(outputPath, cv::VideoWriter::fourcc('m', 'p', '4', 'v'), fps, frameSize);
+        if (!writer.isOpened()) {
+            return fail(Errors::ErrorCode::ENCODER_MISSING, "Could not create video: " + outputPath);
+        }
+    }
+    auto writeFrame = [&](const cv::Mat& frame) {
+        if (!encoder) {
+            writer.write(frame);
+        } else if (!encoderFailed && !writeEncoderFrame(encoder, frame)) {
+            encoderFailed = true;
+        }
+    };
+
+    int framesPerImage = std::max(1, static_cast<int>(options.secondsPerImage * fps + 0.5));
+    int fadeFrames = options.crossfade
+        ? std::min(static_cast<int>(options.crossfadeSeconds * fps + 0.5), framesPerImage / 2)
+        : 0;
+    int totalFrames = static_cast<int>(files.size()) * framesPerImage;
+    int written = 0;
+
+    cv::Mat current = first;
+    bool cancelled = !reporter.stage("Writing frames");
+    for (size_t i = 0; i < files.size() && !cancelled; ++i) {
+        cv::Mat next;
+        if (i + 1 < files.size()) {
+            next = loadSlide(files[i + 1]);
+            if (next.empty()) {
+                std::cerr << "Warning: Skipping unreadable image: " << files[i + 1] << std::endl;
+                next = current;
+            } else {
+                next = fitToFrame(next, frameSize);
+            }
+        }
+
+        // The fade into the next image takes up the end of this one's time
+        int fade = next.empty() ? 0 : fadeFrames;
+        for (int f = 0; f < framesPerImage && !cancelled; ++f) {
+            int fadeIndex = f - (framesPerImage - fade);
+            if (fadeIndex >= 0) {
+                double t = static_cast<double>(fadeIndex + 1) / (fade + 1);
+                cv::Mat blended;
+                cv::addWeighted(current, 1.0 - t, next, t, 0.0, blended);
+                writeFrame(blended);
+            } else {
+                writeFrame(current);
+            }
+
+            written++;
+            if (!reporter.frames(written, totalFrames)) cancelled = true;
+        }
+
+        current = next;
+    }
+
+    writer.release();
+    if (encoder && !cancelled) reporter.stage("Encoding");
+    bool encoded = !encoder || Platform::finishVideoEncoder(encoder, outputPath);
+    encoder = nullptr;
+    if (cancelled) {
+        std::filesystem::remove(outputPath, ec);
+        return fail(Errors::ErrorCode::CANCELLED, "Cancelled");
+    }
+    if (!encoded || encoderFailed) {
+        return fail(Errors::ErrorCode::ENCODER_MISSING,
+                    "ffmpeg could not encode " + getCodecName(getEncoderCodec(options.encoder, outputPath)) +
+                    " into " + outputPath + " (is the encoder installed, and does the container take the codec?)");
+    }
+    return true;
+}
+
+} // namespace Video
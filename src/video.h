@@ -0,0 +1,258 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <functional>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+#include "imageio.h"
+
+namespace Video {
+
+// How source frames are mapped to output frames when the frame rate changes
+enum class FrameRateMode {
+    NEAREST,        // Drop or duplicate the nearest source frame
+    BLEND           // Blend the two neighbouring source frames
+};
+
+// Loop-friendly export modes
+enum class LoopMode {
+    NONE,
+    PING_PONG,      // Play forward, then backward
+    SEAMLESS_TRIM   // Cut at the frame that best matches the first one
+};
+
+// Video codecs. OPENCV is OpenCV's own MPEG-4 writer; the others are encoded
+// by ffmpeg from raw frames, so it has to be installed with that encoder
+enum class Codec {
+    OPENCV,
+    H264,           // libx264
+    H265,           // libx265
+    VP9,            // libvpx-vp9, for WebM
+    AV1,            // libaom-av1
+    PRORES          // prores_ks (HQ profile), for editing in MOV
+};
+
+// Lookup by command line id: opencv, h264, h265, vp9, av1, prores
+const std::vector<std::string>& getCodecIds();
+bool parseCodec(const std::string& id, Codec& codec);
+std::string getCodecName(Codec codec);
+
+// GPU encoders ffmpeg can hand H.264, H.265, VP9 and AV1 to, depending on
+// the vendor. Much faster than software on long clips, but less efficient at
+// the same quality
+enum class Hardware {
+    NONE,           // Software encoder
+    AUTO,           // The first that works for the codec, else software
+    NVENC,          // NVIDIA
+    QSV,            // Intel Quick Sync
+    VIDEOTOOLBOX,   // Apple
+    AMF             // AMD
+};
+
+// Lookup by command line id: none, auto, nvenc, qsv, videotoolbox, amf
+const std::vector<std::string>& getHardwareIds();
+bool parseHardware(const std::string& id, Hardware& hardware);
+std::string getHardwareName(Hardware hardware);
+
+// ffmpeg's encoder for a codec on some hardware ("" if there is none)
+std::string getHardwareEncoder(Codec codec, Hardware hardware);
+
+struct HardwareEncoder {
+    Codec codec;
+    Hardware hardware;
+    std::string name;               // ffmpeg encoder, e.g. hevc_nvenc
+};
+
+// Hardware encoders that work on this machine: built into ffmpeg and able to
+// encode test frames, since they're often built in without the GPU or driver.
+// Takes a moment per encoder
+std::vector<HardwareEncoder> detectHardwareEncoders();
+
+// How ffmpeg encodes video output. Compression smears dither patterns, so the
+// defaults lean towards quality; yuv444p keeps color patterns from bleeding
+struct EncoderSettings {
+    Codec codec = Codec::OPENCV;
+    int crf = 16;                   // Constant quality, lower is better (ignored by ProRes)
+    int bitrateKbps = 0;            // Target bitrate instead of constant quality (0 = use crf)
+    std::string preset = "medium";  // x264/x265 speed preset: ultrafast ... veryslow
+    std::string pixelFormat;        // e.g. yuv444p (empty = yuv420p, yuv422p10le for ProRes, nv12 on hardware)
+    Hardware hardware = Hardware::NONE;   // With OPENCV as the codec, H.264 is used
+};
+
+// How frames are packed into a sprite sheet
+struct SpriteSheetOptions {
+    int columns = 0;                // Frames per row (0 = as square a grid as possible)
+    int padding = 0;                // Transparent pixels between frames
+    int frameStep = 1;              // Keep every nth frame
+    int maxFrames = 0;              // Stop after this many frames (0 = no limit)
+};
+
+// Video processing options
+struct Options {
+    double outputFps = 0.0;         // Output frame rate (0 = same as source)
+    FrameRateMode frameRateMode = FrameRateMode::NEAREST;
+    LoopMode loopMode = LoopMode::NONE;
+    double minLoopFraction = 0.5;   // Shortest seamless loop, as a fraction of the clip
+    int rotation = -1;              // Clockwise rotation override in degrees (-1 = from metadata)
+    bool lockPalette = false;       // Generate an adaptive palette once from sampled frames
+    int paletteSampleFrames = 8;    // Frames sampled for the locked palette (see analyzeClip)
+    bool varySeed = false;          // Offset the random seed by the frame number (default: same noise every frame)
+    bool cycleMatrix = false;       // Shift ordered, blue noise and halftone patterns every frame (default: fixed)
+    float temporalCarry = 0.0f;     // Experimental: share of each frame's quantization error added to the next (0-1)
+    std::string cacheDir;           // Reuse dithered frames stored here across runs (empty = off)
+    int niceness = 0;               // Lower the job's and ffmpeg's priority (0 = normal, 19 = lowest); frames
+                                    // are then dithered on the job's own workers, not OpenCV's thread pool
+    int threads = 0;                // Limit worker threads (0 = all cores)
+                                    // Both change OpenCV's thread count, which is process-wide, until the job ends
+    int jobs = 0;                   // Frames dithered at the same time (0 = threads if set, else one per core up to 8)
+    double startSeconds = 0.0;      // Process only the clip from here ...
+    double endSeconds = 0.0;        // ... to here (0 = the end of the video)
+    cv::Size frameSize;             // Scale frames to this size first; 0 for w or h keeps the aspect (0x0 = native)
+    double scale = 1.0;             // Or scale them by this factor
+    int maxWidth = 0;               // Then scale them down to at most this width (0 = no limit)
+    int upscale = 1;                // Enlarge dithered frames this many times with nearest-neighbour
+    int ditherHeight = 0;           // Dither at this height, then upscale with nearest-neighbour (0 = native)
+    EncoderSettings encoder;        // Codec for video output (the container follows the extension)
+    bool keepAudio = true;          // Copy the source's audio into video output (needs ffmpeg; not for ping-pong)
+    ImageIO::EncodeOptions encode;  // WebP and PNG settings for animated WebP and APNG output
+    bool spriteSheet = false;       // Save the frames as one image in a grid, with a JSON descriptor
+    SpriteSheetOptions sheet;
+};
+
+// Slideshow options
+struct SlideshowOptions {
+    double secondsPerImage = 2.0;
+    bool crossfade = false;         // Blend into the next image instead of cutting
+    double crossfadeSeconds = 0.5;
+    double fps = 30.0;
+    EncoderSettings encoder;        // Codec, as for Options
+};
+
+// Dither progression animation options
+struct ProgressionOptions {
+    int pixelsPerFrame = 0;         // Pixels finished between frames ...
+    int rowsPerFrame = 0;           // ... or rows' worth of them (0 for both = fit in seconds)
+    double seconds = 6.0;           // Length of the dithering part when no step is given
+    double fps = 20.0;
+    double holdSeconds = 2.0;       // The finished result stays on screen this long at the end
+    int upscale = 1;                // Enlarge frames this many times with nearest-neighbour
+    EncoderSettings encoder;        // Codec for video output, as for Options
+};
+
+// Where a job is, for progress reports
+struct Progress {
+    std::string stage;              // e.g. "Finding loop point", "Dithering", "Adding audio"
+    int frame = 0;                  // Frames written so far
+    int totalFrames = 0;            // Estimated total frames
+    double fps = 0.0;               // Frames written per second during this stage (0 = not measured yet)
+    double etaSeconds = -1.0;       // Time left for this stage's frames (-1 = unknown)
+};
+
+// Called as each stage starts and after each frame. Returning false before or
+// while frames are written cancels the job, which deletes the partial output
+// and fails with CANCELLED
+using ProgressCallback = std::function<bool(const Progress&)>;
+
+// Size the frames of a source this size are dithered at (before ditherHeight
+// and upscale), after frameSize or scale and maxWidth. Rounded to even sizes, which most
+// codecs need, when scaled
+cv::Size getFrameSize(const cv::Size& source, const Options& options);
+
+// Where the JSON descriptor of a sprite sheet is written: next to it, with a
+// .json extension. It lists the grid, and each frame's rectangle and duration
+std::string getSpriteSheetDescriptor(const std::string& sheetPath);
+
+// Parse a time as seconds ("90.5") or [hh:]mm:ss[.fff] ("1:30.5")
+bool parseTime(const std::string& text, double& seconds);
+
+// What a video holds, for showing before processing it
+struct VideoInfo {
+    cv::Size size;                  // As shown, with the rotation metadata applied
+    int rotation = 0;               // Clockwise degrees from the metadata
+    double fps = 0.0;
+    int frameCount = 0;             // As the container reports it; an estimate for some formats
+    double durationSeconds = 0.0;
+    std::string codec;              // e.g. h264 (a FourCC like avc1 without ffprobe)
+    bool probed = false;            // Whether ffprobe ran, so the audio fields below are known
+    bool hasAudio = false;
+    std::string audioCodec;
+    int audioChannels = 0;
+    int audioSampleRate = 0;        // Hz
+};
+
+// Read a video's properties with OpenCV, and its codecs and audio stream with
+// ffprobe when it's installed. On failure error says why
+bool getVideoInfo(const std::string& path, VideoInfo& info, Errors::Error* error = nullptr);
+
+// Build one palette of params.paletteSize colors (by params.paletteMethod) from
+// paletteSampleFrames frames spread over the clip, between startSeconds and
+// endSeconds if set, so the colors don't pump from frame to frame. This is
+// the pre-pass lockPalette runs; it reports the "Analyzing clip" stage per
+// sampled frame, and returning false from progress cancels it
+bool analyzeClip(const std::string& inputPath, const Dithering::Parameters& params, const Options& options,
+                 std::vector<cv::Vec3b>& palette, ProgressCallback progress = nullptr,
+                 Errors::Error* error = nullptr);
+
+// Extensions of the video formats that can be processed
+const std::vector<std::string>& getVideoExtensions();
+
+// Check whether a file looks like a video by its extension
+bool isVideoFile(const std::string& filename);
+
+// Dither every frame of a video and write the result. Outputs with an
+// animation extension (see ImageIO::getAnimationExtensions) are written as
+// animated images, which keep dither patterns crisp but hold every frame in
+// memory until the end, so they suit short clips. On failure error says why
+bool processVideo(const std::string& inputPath, const std::string& outputPath,
+                  const Dithering::Parameters& params, const Options& options,
+                  ProgressCallback progress = nullptr, Errors::Error* error = nullptr);
+
+// Dither the frame shown at a time (seconds into the video) the way
+// processVideo would, to try settings before the full job. Rotation, frame
+// size, locked palette, per-frame seed and matrix, ditherHeight and upscale
+// apply; temporalCarry doesn't, as it depends on the frames before
+bool previewFrame(const std::string& inputPath, double seconds, const Dithering::Parameters& params,
+                  const Options& options, cv::Mat& result, Errors::Error* error = nullptr);
+
+// Dither every frame of an animated GIF with the same settings and write it as
+// an animated GIF, WebP or APNG keeping the frame delays, loop count and
+// transparency. lockPalette, paletteSampleFrames, varySeed, cycleMatrix and
+// ditherHeight apply; the rest is for videos
+bool processAnimation(const std::string& inputPath, const std::string& outputPath,
+                      const Dithering::Parameters& params, const Options& options,
+                      ProgressCallback progress = nullptr, Errors::Error* error = nullptr);
+
+// Grab the screen (see Platform::captureScreen), cropped to region if it isn't
+// empty. On failure error says why
+bool captureScreen(const cv::Rect& region, cv::Mat& frame, Errors::Error* error = nullptr);
+
+// Record the screen (or region of it) for a number of seconds, dithering each
+// capture, at options.outputFps (default 5; grabbing takes tens of ms, so
+// this is for low frame rates). A capture that takes longer than a frame is
+// held for the frames it missed, so the recording keeps to real time. Outputs
+// with an animation extension are written as animated images, others with
+// OpenCV's MPEG-4 writer. frameSize, scale, maxWidth, varySeed, cycleMatrix,
+// ditherHeight and upscale apply
+bool recordScreen(const cv::Rect& region, double seconds, const std::string& outputPath,
+                  const Dithering::Parameters& params, const Options& options,
+                  ProgressCallback progress = nullptr, Errors::Error* error = nullptr);
+
+// Animate an image being dithered, from the input to the result, in the order
+// error diffusion works through it (see Dithering::ditherWithSnapshots).
+// Outputs with an animation extension are written as animated images, which
+// hold every frame in memory and so suit small images, others as videos like
+// processVideo's (see EncoderSettings). GIF frames show the part not yet dithered in grays, as they
+// have 256 colors to share with the result
+bool exportProgression(const cv::Mat& input, const std::string& outputPath, const Dithering::Parameters& params,
+                       const ProgressionOptions& options, ProgressCallback progress = nullptr,
+                       Errors::Error* error = nullptr);
+
+// Assemble the images in a folder (in file name order) into a slideshow video
+bool createSlideshow(const std::string& folder, const std::string& outputPath,
+                     const SlideshowOptions& options, ProgressCallback progress = nullptr,
+                     Errors::Error* error = nullptr);
+
+} // namespace Video
@@ -0,0 +1,103 @@
+#include "wallpaper.h"
+#include "platform.h"
+#include "transform.h"
+#include <algorithm>
+#include <chrono>
+#include <cmath>
+#include <filesystem>
+
+namespace Wallpaper {
+
+using Errors::ErrorCode;
+
+const std::vector<std::string>& getFitModeIds() {
+    static const std::vector<std::string> ids = {"fill", "fit", "center"};
+    return ids;
+}
+
+bool parseFitMode(const std::string& id, FitMode& mode) {
+    const auto& ids = getFitModeIds();
+    auto match = std::find(ids.begin(), ids.end(), id);
+    if (match == ids.end()) return false;
+    mode = static_cast<FitMode>(match - ids.begin());
+    return true;
+}
+
+cv::Mat renderWallpaper(const cv::Mat& image, const Dithering::Parameters& params, const cv::Size& screen,
+                        FitMode mode, const cv::Vec3b& background) {
+    if (image.empty() || screen.width <= 0 || screen.height <= 0) return cv::Mat();
+
+    double scaleX = static_cast<double>(screen.width) / image.cols;
+    double scaleY = static_cast<double>(screen.height) / image.rows;
+    double scale = 1.0;
+    if (mode == FitMode::FILL) scale = std::max(scaleX, scaleY);
+    if (mode == FitMode::FIT) scale = std::min(scaleX, scaleY);
+
+    // Where the scaled image lands, the part of it on screen, and that part
+    // back in image pixels
+    cv::Size scaled(std::max(1, static_cast<int>(std::lround(image.cols * scale))),
+                    std::max(1, static_cast<int>(std::lround(image.rows * scale))));
+    cv::Rect target(cv::Point((screen.width - scaled.width) / 2, (screen.height - scaled.height) / 2), scaled);
+    cv::Rect visible = target & cv::Rect(0, 0, screen.width, screen.height);
+    cv::Rect source(static_cast<int>((visible.x - target.x) / scale), static_cast<int>((visible.y - target.y) / scale),
+                    std::max(1, static_cast<int>(std::lround(visible.width / scale))),
+                    std::max(1, static_cast<int>(std::lround(visible.height / scale))));
+
+    cv::Mat placed = Transform::cropImage(image, source);
+    if (placed.size() != visible.size()) {
+        Transform::Filter filter = scale < 1.0 ? Transform::Filter::AREA : Transform::Filter::LANCZOS;
+        placed = Transform::resizeImage(placed, visible.size(), filter);
+    }
+    cv::Mat dithered = Dithering::ditherImage(placed, params);
+    if (dithered.empty()) return cv::Mat();
+    if (dithered.size() != visible.size()) {
+        cv::resize(dithered, dithered, visible.size(), 0, 0, cv::INTER_NEAREST);
+    }
+
+    cv::Mat canvas(screen, CV_8UC3, cv::Scalar(background[0], background[1], background[2]));
+    dithered.copyTo(canvas(visible));
+    return canvas;
+}
+
+bool setWallpaper(const cv::Mat& image, const Dithering::Parameters& params, const Options& options,
+                  Errors::Error* error, std::string* savedPath) {
+    auto fail = [error](ErrorCode code, const std::string& message) {
+        if (error) *error = {code, message};
+        return false;
+    };
+
+    cv::Size screen = options.screen;
+    if (screen.empty()) {
+        int width = 0, height = 0;
+        if (!Platform::getScreenSize(width, height)) {
+            return fail(ErrorCode::INVALID_ARGUMENT, "Could not find the screen resolution; give it explicitly");
+        }
+        screen = cv::Size(width, height);
+    }
+
+    cv::Mat wallpaper = renderWallpaper(image, params, screen, options.mode, options.background);
+    if (wallpaper.empty()) return fail(ErrorCode::INVALID_ARGUMENT, "There is no image to make a wallpaper of");
+
+    std::string dir = Platform::getAppDataDir();
+    if (dir.empty()) return fail(ErrorCode::IO, "Could not create the app data directory");
+
+    // A new name each time, since desktops cache the picture by its path
+    std::error_code ec;
+    std::vector<std::filesystem::path> previous;
+    for (const auto& entry : std::filesystem::directory_iterator(dir, ec)) {
+        if (entry.path().filename().string().rfind("wallpaper-", 0) == 0) previous.push_back(entry.path());
+    }
+    auto stamp = std::chrono::system_clock::now().time_since_epoch().count();
+    std::string path = (std::filesystem::path(dir) / ("wallpaper-" + std::to_string(stamp) + ".png")).string();
+    if (!cv::imwrite(path, wallpaper)) return fail(ErrorCode::IO, "Could not write " + path);
+    if (savedPath) *savedPath = path;
+
+    if (!Platform::setDesktopWallpaper(path)) {
+        return fail(ErrorCode::INTERNAL, "Could not set the wallpaper on this desktop; the image is saved at " + path);
+    }
+    // Only now, so a desktop that refused the new picture still has the old one
+    for (const auto& old : previous) std::filesystem::remove(old, ec);
+    return true;
+}
+
+} // namespace Wallpaper
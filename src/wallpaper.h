@@ -0,0 +1,41 @@
+#pragma once
+
+#include <opencv2/opencv.hpp>
+#include <string>
+#include <vector>
+
+#include "dithering.h"
+#include "errors.h"
+
+namespace Wallpaper {
+
+// How the image is placed on a screen of another shape
+enum class FitMode {
+    FILL,           // Scaled to cover the screen, the overflow cropped
+    FIT,            // Scaled to show whole, the background around it
+    CENTER          // At its own size in the middle, cropped or surrounded by the background
+};
+
+// Lookup by command line id (fill, fit, center)
+const std::vector<std::string>& getFitModeIds();
+bool parseFitMode(const std::string& id, FitMode& mode);
+
+struct Options {
+    FitMode mode = FitMode::FILL;
+    cv::Vec3b background = cv::Vec3b(0, 0, 0);     // BGR, around FIT and CENTER images
+    cv::Size screen;                                // Empty = the primary display's resolution
+};
+
+// The image placed on a screen-sized canvas and dithered there, so the
+// pattern lands on the screen's own pixels rather than being scaled after.
+// The background is left as a flat color
+cv::Mat renderWallpaper(const cv::Mat& image, const Dithering::Parameters& params, const cv::Size& screen,
+                        FitMode mode, const cv::Vec3b& background);
+
+// Render for the screen and make it the desktop wallpaper. The file is kept
+// in the app data directory, where the desktop reads it from, replacing the
+// one set before once the desktop has taken it; savedPath receives its path
+bool setWallpaper(const cv::Mat& image, const Dithering::Parameters& params, const Options& options,
+                  Errors::Error* error = nullptr, std::string* savedPath = nullptr);
+
+} // namespace Wallpaper